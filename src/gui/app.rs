@@ -1,19 +1,26 @@
 // iced GUI application for nterm - Terminal-style IDE
 
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::PathBuf;
 use std::time::Duration;
 
 use iced::widget::{
-    button, column, container, markdown, mouse_area, row, scrollable, text, text_input, Column, Row, Space,
+    button, canvas, column, container, markdown, mouse_area, row, scrollable, text, text_input, Column, Row, Space,
 };
 use iced::{Color, Element, Font, Length, Subscription, Task, Theme};
 use iced::keyboard::{self, Key};
 use iced::mouse;
 
 use crate::shared::{Config, flatten_node, FileNode, VisibleItem, ThemeMode, send_message};
+use crate::shared::config_writer::ConfigWriter;
+use crate::shared::code_lens::{self, LensAction};
+use crate::shared::indent_guides;
+use crate::shared::syntax;
+use crate::shared::toolchain::Toolchain;
 
 use super::message::{Divider, Message, Panel};
+use super::minimap::{self, Minimap};
 use super::syntax::SyntaxHighlighter;
 use super::theme::{get_iced_theme, panel_style, TerminalColors};
 use super::terminal_widget::TerminalView;
@@ -26,6 +33,9 @@ const DIVIDER_WIDTH: f32 = 4.0;
 // Text input IDs for focus management
 const CHAT_INPUT_ID: &str = "chat_input";
 
+/// Scrollable ID of the editor, for jumping from the minimap
+const EDITOR_SCROLL_ID: &str = "editor_scroll";
+
 /// Panel layout sizes (as fractions 0.0 to 1.0)
 #[derive(Debug, Clone, Copy)]
 pub struct PanelSizes {
@@ -76,6 +86,7 @@ impl ChatMessage {
 pub struct NtermGui {
     // Core state (reused from TUI)
     config: Config,
+    config_writer: ConfigWriter,
     file_tree: Vec<FileNode>,
     visible_items: Vec<VisibleItem>,
     selected_idx: usize,
@@ -84,6 +95,10 @@ pub struct NtermGui {
     editor_content: String,
     editor_file_path: Option<PathBuf>,
     editor_scroll: usize,
+    /// First line and number of lines in the editor's view, for the minimap
+    editor_view: (f32, f32),
+    /// Workspace references to each function of the open file, as last counted
+    lens_references: Option<(PathBuf, HashMap<String, usize>)>,
 
     // Terminal state
     terminal_view: TerminalView,
@@ -107,6 +122,8 @@ pub struct NtermGui {
 
     // Current workspace
     workspace_path: PathBuf,
+    /// Virtualenv and pinned Node/Rust versions found in the workspace
+    toolchain: Toolchain,
 
     // Syntax highlighting
     syntax_highlighter: SyntaxHighlighter,
@@ -116,19 +133,24 @@ impl NtermGui {
     pub fn new() -> (Self, Task<Message>) {
         let config = Config::load();
         let theme_mode = config.theme;
+        crate::shared::syntax::set_overrides(&config.syntax_overrides);
         let colors = TerminalColors::from_mode(theme_mode);
 
         // Use current directory as workspace
         let workspace_path = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        crate::shared::workspace::set_excluded(&workspace_path, &config.excluded_dirs(&workspace_path));
 
         let mut app = Self {
             config,
+            config_writer: ConfigWriter::start(Config::get_config_path(), |e| eprintln!("{}", e)),
             file_tree: Vec::new(),
             visible_items: Vec::new(),
             selected_idx: 0,
             editor_content: String::from("// Welcome to nterm GUI\n// Select a file from the file tree to edit\n// \n// Keyboard shortcuts:\n//   Tab        - Cycle panels\n//   Ctrl+T     - Toggle theme\n//   Ctrl+Q     - Quit\n//   Arrow keys - Navigate\n//   Drag dividers to resize panels"),
             editor_file_path: None,
+            lens_references: None,
             editor_scroll: 0,
+            editor_view: (0.0, 0.0),
             terminal_view: TerminalView::new(),
             chat_messages: vec![
                 ChatMessage::new("System".to_string(), "Welcome to nterm AI Chat".to_string()),
@@ -141,6 +163,7 @@ impl NtermGui {
             dragging_divider: None,
             window_size: (1200.0, 800.0),
             menu_open_idx: None,
+            toolchain: Toolchain::detect(&workspace_path),
             workspace_path,
             syntax_highlighter: SyntaxHighlighter::new(),
         };
@@ -177,7 +200,7 @@ impl NtermGui {
     fn update_visible_items(&mut self) {
         self.visible_items.clear();
         for node in &self.file_tree {
-            flatten_node(node, &mut self.visible_items);
+            flatten_node(node, &mut self.visible_items, false);
         }
     }
 
@@ -188,7 +211,7 @@ impl NtermGui {
 
         let target_path = self.visible_items[idx].path.clone();
 
-        fn toggle_recursive(nodes: &mut Vec<FileNode>, target: &PathBuf) -> bool {
+        fn toggle_recursive(nodes: &mut [FileNode], target: &PathBuf) -> bool {
             for node in nodes.iter_mut() {
                 if &node.path == target {
                     node.toggle_expand();
@@ -222,6 +245,7 @@ impl NtermGui {
         if self.editor_file_path.as_ref() == Some(&path) {
             return;
         }
+        self.editor_view.0 = 0.0;
 
         // Check file size first to avoid blocking on large files
         const MAX_PREVIEW_SIZE: u64 = 512 * 1024; // 512KB limit for preview
@@ -274,11 +298,70 @@ impl NtermGui {
         self.active_panel = Panel::Editor;
     }
 
+    /// Language of the open file when code lenses are shown for it
+    fn lens_language(&self) -> Option<String> {
+        let language = syntax::language_key(self.editor_file_path.as_deref()?)?;
+        (self.config.code_lens && code_lens::supports(Some(&language))).then_some(language)
+    }
+
+    /// Count references to the open file's functions on a blocking thread
+    fn count_lens_references(&self) -> Task<Message> {
+        let (Some(language), Some(path)) = (self.lens_language(), self.editor_file_path.clone()) else { return Task::none() };
+        let lines: Vec<&str> = self.editor_content.lines().collect();
+        let names: HashSet<String> = code_lens::find_symbols(&language, &lines, 0..lines.len())
+            .into_iter().map(|symbol| symbol.name).collect();
+        let root = self.workspace_path.clone();
+        Task::perform(
+            async move {
+                tokio::task::spawn_blocking(move || code_lens::count_references(&root, &names)).await.unwrap_or_default()
+            },
+            move |counts| Message::ReferencesCounted(path.clone(), counts),
+        )
+    }
+
+    /// Run the code lens `action` on the function defined at `line` of the open file
+    fn run_code_lens(&mut self, line: usize, action: LensAction) -> Task<Message> {
+        let (Some(language), Some(path)) = (self.lens_language(), self.editor_file_path.clone()) else { return Task::none() };
+        let lines: Vec<&str> = self.editor_content.lines().collect();
+        let Some(symbol) = code_lens::find_symbols(&language, &lines, line..line + 1).pop() else { return Task::none() };
+        match action {
+            LensAction::RunTest => {
+                let relative = path.strip_prefix(&self.workspace_path).unwrap_or(&path);
+                if let Some(command) = code_lens::test_command(&language, relative, &symbol.name) {
+                    if !self.terminal_view.is_running() {
+                        let _ = self.terminal_view.start();
+                    }
+                    let _ = self.terminal_view.input(&format!("{}\r", command));
+                    self.active_panel = Panel::Terminal;
+                }
+                Task::none()
+            }
+            LensAction::Explain => {
+                let body = code_lens::symbol_body(&language, &lines, line);
+                self.chat_input = code_lens::explain_prompt(&path, &language, &symbol, &body);
+                self.active_panel = Panel::Chat;
+                self.update(Message::ChatSend)
+            }
+            LensAction::References => {
+                let count = self.lens_references.as_ref()
+                    .filter(|(p, _)| *p == path)
+                    .and_then(|(_, counts)| counts.get(&symbol.name).copied());
+                let text = match count {
+                    Some(count) => format!("`{}` is referenced {} time(s) in the workspace", symbol.name, count),
+                    None => format!("References to `{}` are still being counted", symbol.name),
+                };
+                self.chat_messages.push(ChatMessage::new("System".to_string(), text));
+                Task::none()
+            }
+        }
+    }
+
     pub fn update(&mut self, message: Message) -> Task<Message> {
         match message {
             Message::FileTreeSelect(idx) => {
                 self.selected_idx = idx;
                 self.load_file(idx);
+                return self.count_lens_references();
             }
             Message::FileTreeToggle(idx) => {
                 self.toggle_node(idx);
@@ -362,7 +445,7 @@ impl NtermGui {
                 };
                 self.colors = TerminalColors::from_mode(self.theme_mode);
                 self.config.theme = self.theme_mode;
-                let _ = self.config.save();
+                self.config_writer.save(&self.config);
             }
             Message::FocusPanel(panel) => {
                 self.active_panel = panel;
@@ -433,7 +516,7 @@ impl NtermGui {
                 };
                 self.colors = TerminalColors::from_mode(self.theme_mode);
                 self.config.theme = self.theme_mode;
-                let _ = self.config.save();
+                self.config_writer.save(&self.config);
             }
             // Help menu actions
             Message::MenuAbout => {
@@ -446,7 +529,30 @@ impl NtermGui {
             Message::Quit => {
                 std::process::exit(0);
             }
-            Message::EditorScroll(_) => {}
+            Message::EditorScrolled { start, size } => {
+                let lines = self.editor_line_count();
+                self.editor_view = (start * lines, size * lines);
+            }
+            Message::MinimapJump(fraction) => {
+                // Center the view on the point; the scrollable's offset runs over the part not in view
+                let size = (self.editor_view.1 / self.editor_line_count()).min(1.0);
+                let top = (fraction - size / 2.0).clamp(0.0, (1.0 - size).max(0.0));
+                let y = if size < 1.0 { top / (1.0 - size) } else { 0.0 };
+                // Snapping doesn't report a scroll, so move the highlight here
+                self.editor_view.0 = top * self.editor_line_count();
+                return scrollable::snap_to(scrollable::Id::new(EDITOR_SCROLL_ID), scrollable::RelativeOffset { x: 0.0, y });
+            }
+            Message::CodeLens { line, action } => {
+                return self.run_code_lens(line, action);
+            }
+            Message::ReferencesCounted(path, counts) => {
+                self.lens_references = Some((path, counts));
+            }
+            Message::MenuToggleMinimap => {
+                self.menu_open_idx = None;
+                self.config.minimap = !self.config.minimap;
+                self.config_writer.save(&self.config);
+            }
             Message::WindowResized(w, h) => {
                 self.window_size = (w as f32, h as f32);
             }
@@ -486,15 +592,13 @@ impl NtermGui {
     fn handle_key(&mut self, key: Key, modifiers: keyboard::Modifiers) -> Task<Message> {
         // Global shortcuts first
         match key.as_ref() {
-            Key::Named(keyboard::key::Named::Tab) => {
-                if !modifiers.control() {
-                    self.active_panel = self.active_panel.next();
-                    // Focus chat input when switching to Chat panel
-                    if self.active_panel == Panel::Chat {
-                        return text_input::focus(text_input::Id::new(CHAT_INPUT_ID));
-                    }
-                    return Task::none();
+            Key::Named(keyboard::key::Named::Tab) if !modifiers.control() => {
+                self.active_panel = self.active_panel.next();
+                // Focus chat input when switching to Chat panel
+                if self.active_panel == Panel::Chat {
+                    return text_input::focus(text_input::Id::new(CHAT_INPUT_ID));
                 }
+                return Task::none();
             }
             Key::Character("t") if modifiers.control() => {
                 self.theme_mode = match self.theme_mode {
@@ -503,7 +607,7 @@ impl NtermGui {
                 };
                 self.colors = TerminalColors::from_mode(self.theme_mode);
                 self.config.theme = self.theme_mode;
-                let _ = self.config.save();
+                self.config_writer.save(&self.config);
                 return Task::none();
             }
             Key::Character("m") if modifiers.control() => {
@@ -513,12 +617,10 @@ impl NtermGui {
             Key::Character("q") if modifiers.control() => {
                 std::process::exit(0);
             }
-            Key::Named(keyboard::key::Named::Escape) => {
-                // Close menu if open
-                if self.menu_open_idx.is_some() {
-                    self.menu_open_idx = None;
-                    return Task::none();
-                }
+            // Close menu if open
+            Key::Named(keyboard::key::Named::Escape) if self.menu_open_idx.is_some() => {
+                self.menu_open_idx = None;
+                return Task::none();
             }
             _ => {}
         }
@@ -530,19 +632,15 @@ impl NtermGui {
         match self.active_panel {
             Panel::FileTree => {
                 match key.as_ref() {
-                    Key::Named(keyboard::key::Named::ArrowUp) => {
-                        if self.selected_idx > 0 {
-                            self.selected_idx -= 1;
-                            // Preview file on keyboard navigation (keeps focus in file tree)
-                            self.preview_file(self.selected_idx);
-                        }
+                    Key::Named(keyboard::key::Named::ArrowUp) if self.selected_idx > 0 => {
+                        self.selected_idx -= 1;
+                        // Preview file on keyboard navigation (keeps focus in file tree)
+                        self.preview_file(self.selected_idx);
                     }
-                    Key::Named(keyboard::key::Named::ArrowDown) => {
-                        if self.selected_idx + 1 < self.visible_items.len() {
-                            self.selected_idx += 1;
-                            // Preview file on keyboard navigation (keeps focus in file tree)
-                            self.preview_file(self.selected_idx);
-                        }
+                    Key::Named(keyboard::key::Named::ArrowDown) if self.selected_idx + 1 < self.visible_items.len() => {
+                        self.selected_idx += 1;
+                        // Preview file on keyboard navigation (keeps focus in file tree)
+                        self.preview_file(self.selected_idx);
                     }
                     Key::Named(keyboard::key::Named::Enter) => {
                         self.load_file(self.selected_idx);
@@ -920,6 +1018,7 @@ impl NtermGui {
                 2 => vec![
                     ("Reset Layout", Message::MenuResetLayout),
                     ("Toggle Theme", Message::MenuToggleTheme),
+                    ("Toggle Minimap", Message::MenuToggleMinimap),
                 ],
                 3 => vec![
                     ("About", Message::MenuAbout),
@@ -978,6 +1077,10 @@ impl NtermGui {
         .into()
     }
 
+    fn editor_line_count(&self) -> f32 {
+        self.editor_content.lines().count().max(1) as f32
+    }
+
     fn view_editor(&self) -> Element<'_, Message> {
         let is_active = self.active_panel == Panel::Editor;
         let colors = self.colors;
@@ -989,11 +1092,12 @@ impl NtermGui {
             .map(|n| n.to_string_lossy().to_string())
             .unwrap_or_else(|| "Untitled".to_string());
 
-        // Get file extension for syntax highlighting
+        // Get the language for syntax highlighting
+        let content_lines: Vec<&str> = self.editor_content.lines().collect();
         let extension = self
             .editor_file_path
             .as_ref()
-            .and_then(|p| SyntaxHighlighter::extension_from_path(p));
+            .and_then(|p| SyntaxHighlighter::language_for(p, &content_lines));
 
         // Header
         let header = container(
@@ -1005,33 +1109,82 @@ impl NtermGui {
         .padding([2, 5])
         .width(Length::Fill);
 
+        // Code lenses after function definitions
+        let lens_language = self.lens_language();
+        let symbols: HashMap<usize, code_lens::Symbol> = lens_language.as_deref()
+            .map(|language| {
+                let lines: Vec<&str> = self.editor_content.lines().collect();
+                code_lens::find_symbols(language, &lines, 0..lines.len())
+            })
+            .unwrap_or_default()
+            .into_iter().map(|symbol| (symbol.line, symbol))
+            .collect();
+        let references = self.lens_references.as_ref()
+            .filter(|(path, _)| Some(path) == self.editor_file_path.as_ref())
+            .map(|(_, counts)| counts);
+
         // Editor content with syntax-highlighted line numbers
-        let lines: Vec<Element<'_, Message>> = self
-            .editor_content
-            .lines()
+        let indent_width = self.config.indent_for_contents(self.editor_file_path.as_deref(), &content_lines).width;
+        let lines: Vec<Element<'_, Message>> = content_lines
+            .iter()
             .enumerate()
-            .map(|(i, line)| {
+            .map(|(i, &line)| {
                 let line_num = text(format!("{:>4} ", i + 1))
                     .size(FONT_SIZE)
                     .font(Font::MONOSPACE)
                     .color(colors.line_number);
 
+                // Indentation guides replace the leading whitespace at each level
+                let guides = if self.config.indent_guides {
+                    indent_guides::guide_columns(&content_lines, i, indent_width)
+                } else {
+                    Vec::new()
+                };
+                let (indent, line) = match guides.last() {
+                    Some(&last) => {
+                        let leading = line.chars().take_while(|c| c.is_whitespace()).count();
+                        let split = line.char_indices().nth(leading).map_or(line.len(), |(at, _)| at);
+                        // Blank lines carry the guides of the code around them past their end
+                        let mut whitespace = line[..split].chars().chain(std::iter::repeat(' '));
+                        let indent: String = (0..leading.max(last + 1))
+                            .map(|col| {
+                                let c = whitespace.next().unwrap_or(' ');
+                                if guides.contains(&col) { '│' } else { c }
+                            })
+                            .collect();
+                        (indent, &line[split..])
+                    }
+                    None => (String::new(), line),
+                };
+
                 // Get syntax-highlighted spans for this line
                 let highlighted = self.syntax_highlighter.highlight_line(line, extension.as_deref());
 
                 // Build row of highlighted text spans
-                let spans: Vec<Element<'_, Message>> = highlighted
-                    .into_iter()
-                    .map(|span| {
-                        text(span.text)
-                            .size(FONT_SIZE)
-                            .font(Font::MONOSPACE)
-                            .color(span.color)
-                            .into()
-                    })
-                    .collect();
-
-                let line_content = Row::with_children(spans).spacing(0);
+                let mut spans: Vec<Element<'_, Message>> = Vec::new();
+                if !indent.is_empty() {
+                    spans.push(text(indent).size(FONT_SIZE).font(Font::MONOSPACE).color(colors.indent_guide).into());
+                }
+                spans.extend(highlighted.into_iter().map(|span| {
+                    text(span.text)
+                        .size(FONT_SIZE)
+                        .font(Font::MONOSPACE)
+                        .color(span.color)
+                        .into()
+                }));
+
+                let mut line_content = Row::with_children(spans).spacing(0);
+                if let Some(symbol) = symbols.get(&i) {
+                    let count = references.map(|counts| counts.get(&symbol.name).copied().unwrap_or(0));
+                    for (action, label) in code_lens::lenses(symbol, count) {
+                        line_content = line_content.push(
+                            button(text(format!("  {}", label)).size(FONT_SIZE - 2).font(Font::MONOSPACE).color(colors.line_number))
+                                .on_press(Message::CodeLens { line: i, action })
+                                .padding(0)
+                                .style(button::text),
+                        );
+                    }
+                }
 
                 row![line_num, line_content]
                     .spacing(2)
@@ -1042,12 +1195,36 @@ impl NtermGui {
         let editor_scroll = scrollable(
             Column::with_children(lines).spacing(0).padding([0, 5])
         )
+        .id(scrollable::Id::new(EDITOR_SCROLL_ID))
+        .on_scroll(|viewport| {
+            let height = viewport.content_bounds().height.max(1.0);
+            Message::EditorScrolled {
+                start: viewport.absolute_offset().y / height,
+                size: (viewport.bounds().height / height).min(1.0),
+            }
+        })
         .height(Length::Fill)
         .width(Length::Fill);
 
+        let body: Element<'_, Message> = if self.config.minimap {
+            let lines = self.editor_line_count();
+            let map = canvas(Minimap {
+                content: &self.editor_content,
+                view_start: self.editor_view.0 / lines,
+                view_size: (self.editor_view.1 / lines).min(1.0),
+                text: Color { a: 0.35, ..colors.foreground },
+                highlight: Color { a: 0.15, ..colors.foreground },
+            })
+            .width(Length::Fixed(minimap::WIDTH))
+            .height(Length::Fill);
+            row![editor_scroll, map].into()
+        } else {
+            editor_scroll.into()
+        };
+
         let content = column![
             header,
-            editor_scroll,
+            body,
         ];
 
         container(content)
@@ -1264,6 +1441,10 @@ impl NtermGui {
                 .font(Font::MONOSPACE)
                 .color(colors.selection_fg),
             Space::with_width(Length::Fill),
+            text(self.toolchain.label().map(|label| format!("[{}]  ", label)).unwrap_or_default())
+                .size(HEADER_SIZE)
+                .font(Font::MONOSPACE)
+                .color(colors.line_number),
             text("Tab: Cycle | Ctrl+T: Theme | Ctrl+Q: Quit")
                 .size(HEADER_SIZE)
                 .font(Font::MONOSPACE)
@@ -1298,7 +1479,7 @@ impl NtermGui {
 
     fn cycle_model(&mut self) {
         self.config.cycle_model();
-        let _ = self.config.save();
+        self.config_writer.save(&self.config);
         let model_name = self.config.get_selected_model().display_name();
         self.chat_messages.push(ChatMessage::new("System".to_string(), format!("Switched model to: {}", model_name)));
     }
@@ -1,7 +1,12 @@
 // Message types for iced application
 
+use std::collections::HashMap;
+use std::path::PathBuf;
+
 use iced::keyboard;
 
+use crate::shared::code_lens::LensAction;
+
 /// Identifies which divider is being dragged
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Divider {
@@ -22,7 +27,14 @@ pub enum Message {
     FileTreeDown,
 
     // Editor
-    EditorScroll(f32),
+    /// The editor scrolled: top and height of the view as fractions of the buffer
+    EditorScrolled { start: f32, size: f32 },
+    /// Scroll the editor so this fraction of the buffer is in the middle of the view
+    MinimapJump(f32),
+    /// A code lens was clicked on the function defined at this 0-based line
+    CodeLens { line: usize, action: LensAction },
+    /// Workspace references to the functions defined in this file
+    ReferencesCounted(PathBuf, HashMap<String, usize>),
 
     // Terminal
     TerminalStart,
@@ -66,6 +78,7 @@ pub enum Message {
     // View menu (2)
     MenuResetLayout,
     MenuToggleTheme,
+    MenuToggleMinimap,
     // Help menu (3)
     MenuAbout,
 
@@ -76,9 +89,10 @@ pub enum Message {
     WindowResized(u32, u32),
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum Panel {
     FileTree,
+    #[default]
     Editor,
     Terminal,
     Chat,
@@ -103,9 +117,3 @@ impl Panel {
         }
     }
 }
-
-impl Default for Panel {
-    fn default() -> Self {
-        Panel::Editor
-    }
-}
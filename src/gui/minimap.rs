@@ -0,0 +1,102 @@
+// Minimap beside the GUI editor: every line of the buffer drawn as a thin bar
+// as long as its text, with the part in view highlighted. Clicking or dragging
+// scrolls the editor to that point.
+
+use iced::mouse;
+use iced::widget::canvas::{self, event, Event, Frame, Geometry};
+use iced::{Color, Point, Rectangle, Renderer, Size, Theme};
+
+use super::message::Message;
+
+/// Width of the minimap column
+pub const WIDTH: f32 = 80.0;
+
+/// Height of a line when the whole buffer fits at this scale
+const LINE_HEIGHT: f32 = 2.0;
+
+/// Width of a character
+const CHAR_WIDTH: f32 = 1.0;
+
+pub struct Minimap<'a> {
+    pub content: &'a str,
+    /// Top of the editor's view as a fraction of the whole buffer
+    pub view_start: f32,
+    /// Height of the editor's view as a fraction of the whole buffer
+    pub view_size: f32,
+    pub text: Color,
+    pub highlight: Color,
+}
+
+impl Minimap<'_> {
+    /// Height of one line when the buffer is drawn into `height`
+    fn line_height(&self, height: f32) -> f32 {
+        let lines = self.content.lines().count().max(1) as f32;
+        LINE_HEIGHT.min(height / lines)
+    }
+
+    /// Fraction of the buffer at `y` pixels from the top
+    fn fraction_at(&self, y: f32, height: f32) -> f32 {
+        let drawn = self.line_height(height) * self.content.lines().count().max(1) as f32;
+        (y / drawn).clamp(0.0, 1.0)
+    }
+}
+
+impl canvas::Program<Message> for Minimap<'_> {
+    /// Whether the left button is held on the minimap
+    type State = bool;
+
+    fn update(&self, dragging: &mut bool, event: Event, bounds: Rectangle, cursor: mouse::Cursor) -> (event::Status, Option<Message>) {
+        let Event::Mouse(event) = event else { return (event::Status::Ignored, None) };
+        match event {
+            mouse::Event::ButtonPressed(mouse::Button::Left) => {
+                let Some(position) = cursor.position_in(bounds) else { return (event::Status::Ignored, None) };
+                *dragging = true;
+                (event::Status::Captured, Some(Message::MinimapJump(self.fraction_at(position.y, bounds.height))))
+            }
+            mouse::Event::CursorMoved { position } if *dragging => {
+                let y = position.y - bounds.y;
+                (event::Status::Captured, Some(Message::MinimapJump(self.fraction_at(y, bounds.height))))
+            }
+            mouse::Event::ButtonReleased(mouse::Button::Left) if *dragging => {
+                *dragging = false;
+                (event::Status::Captured, None)
+            }
+            _ => (event::Status::Ignored, None),
+        }
+    }
+
+    fn draw(&self, _dragging: &bool, renderer: &Renderer, _theme: &Theme, bounds: Rectangle, _cursor: mouse::Cursor) -> Vec<Geometry> {
+        let mut frame = Frame::new(renderer, bounds.size());
+        let line_height = self.line_height(bounds.height);
+        let mut last_row = -1.0;
+        for (i, line) in self.content.lines().enumerate() {
+            let y = i as f32 * line_height;
+            // Lines that land on an already drawn pixel row add nothing
+            if y.floor() <= last_row {
+                continue;
+            }
+            last_row = y.floor();
+            let indent = line.len() - line.trim_start().len();
+            let length = line.trim().chars().count();
+            if length == 0 {
+                continue;
+            }
+            let x = (indent as f32 * CHAR_WIDTH).min(bounds.width);
+            let width = (length as f32 * CHAR_WIDTH).min(bounds.width - x);
+            frame.fill_rectangle(Point::new(x, y), Size::new(width, line_height.max(1.0)), self.text);
+        }
+
+        let drawn = line_height * self.content.lines().count().max(1) as f32;
+        let view = Size::new(bounds.width, (self.view_size * drawn).max(2.0));
+        frame.fill_rectangle(Point::new(0.0, self.view_start * drawn), view, self.highlight);
+        vec![frame.into_geometry()]
+    }
+
+    fn mouse_interaction(&self, dragging: &bool, bounds: Rectangle, cursor: mouse::Cursor) -> mouse::Interaction {
+        if *dragging || cursor.is_over(bounds) {
+            mouse::Interaction::Pointer
+        } else {
+            mouse::Interaction::default()
+        }
+    }
+}
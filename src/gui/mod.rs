@@ -2,7 +2,19 @@
 
 pub mod app;
 pub mod message;
+pub mod minimap;
 pub mod styles;
 pub mod syntax;
 pub mod terminal_widget;
 pub mod theme;
+
+use app::NtermGui;
+
+/// Open the nterm window and run until it is closed
+pub fn run() -> iced::Result {
+    iced::application(NtermGui::title, NtermGui::update, NtermGui::view)
+        .subscription(NtermGui::subscription)
+        .theme(NtermGui::theme)
+        .window_size((1200.0, 800.0))
+        .run_with(NtermGui::new)
+}
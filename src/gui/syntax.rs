@@ -2,8 +2,9 @@
 
 use iced::Color;
 use syntect::easy::HighlightLines;
-use syntect::highlighting::{Style, ThemeSet};
-use syntect::parsing::SyntaxSet;
+use syntect::highlighting::Style;
+
+use crate::shared::syntax::{self, DEFAULT_THEME};
 
 /// A highlighted text segment with color information
 #[derive(Debug, Clone)]
@@ -12,18 +13,16 @@ pub struct HighlightedSpan {
     pub color: Color,
 }
 
-/// Syntax highlighter for the GUI editor
-pub struct SyntaxHighlighter {
-    syntax_set: SyntaxSet,
-    theme_set: ThemeSet,
-}
+/// Default text color used when a line can't be highlighted
+const PLAIN_TEXT_COLOR: Color = Color::from_rgb(208.0 / 255.0, 208.0 / 255.0, 208.0 / 255.0);
+
+/// Syntax highlighter for the GUI editor (uses the shared syntax assets)
+pub struct SyntaxHighlighter;
 
 impl SyntaxHighlighter {
     pub fn new() -> Self {
-        Self {
-            syntax_set: SyntaxSet::load_defaults_newlines(),
-            theme_set: ThemeSet::load_defaults(),
-        }
+        syntax::preload();
+        Self
     }
 
     /// Convert syntect style to iced Color
@@ -35,23 +34,24 @@ impl SyntaxHighlighter {
         )
     }
 
-    /// Get the file extension from a path
-    pub fn extension_from_path(path: &std::path::Path) -> Option<String> {
-        path.extension()
-            .and_then(|e| e.to_str())
-            .map(|s| s.to_lowercase())
+    /// Get the language key of a file from its path and contents (see `syntax::detect_language`)
+    pub fn language_for(path: &std::path::Path, lines: &[&str]) -> Option<String> {
+        syntax::detect_language(path, lines)
     }
 
     /// Highlight a single line of code
     pub fn highlight_line(&self, line: &str, extension: Option<&str>) -> Vec<HighlightedSpan> {
-        let syntax = extension
-            .and_then(|ext| self.syntax_set.find_syntax_by_extension(ext))
-            .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text());
+        // Plain text until the shared assets have finished loading
+        let Some(assets) = syntax::assets() else {
+            return Self::plain(line);
+        };
 
-        let theme = &self.theme_set.themes["base16-ocean.dark"];
+        let syntax = assets.find_syntax(extension);
+
+        let theme = assets.theme(DEFAULT_THEME);
         let mut highlighter = HighlightLines::new(syntax, theme);
 
-        match highlighter.highlight_line(line, &self.syntax_set) {
+        match highlighter.highlight_line(line, &assets.syntax_set) {
             Ok(ranges) => {
                 ranges
                     .into_iter()
@@ -61,16 +61,18 @@ impl SyntaxHighlighter {
                     })
                     .collect()
             }
-            Err(_) => {
-                // Fall back to plain text
-                vec![HighlightedSpan {
-                    text: line.to_string(),
-                    color: Color::from_rgb8(208, 208, 208), // Default gray
-                }]
-            }
+            // Fall back to plain text
+            Err(_) => Self::plain(line),
         }
     }
 
+    fn plain(line: &str) -> Vec<HighlightedSpan> {
+        vec![HighlightedSpan {
+            text: line.to_string(),
+            color: PLAIN_TEXT_COLOR,
+        }]
+    }
+
     /// Highlight all lines in the content
     pub fn highlight_content(&self, content: &str, extension: Option<&str>) -> Vec<Vec<HighlightedSpan>> {
         content
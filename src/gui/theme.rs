@@ -17,6 +17,7 @@ pub struct TerminalColors {
     pub directory: Color,
     pub file: Color,
     pub line_number: Color,
+    pub indent_guide: Color,
     pub comment: Color,
     pub keyword: Color,
     pub string: Color,
@@ -34,6 +35,7 @@ impl TerminalColors {
             directory: Color::from_rgb(0.0, 0.69, 1.0),         // #00afff - blue
             file: Color::from_rgb(0.82, 0.82, 0.82),            // #d0d0d0
             line_number: Color::from_rgb(0.45, 0.45, 0.45),     // #737373
+            indent_guide: Color::from_rgb(0.23, 0.23, 0.23),    // #3a3a3a
             comment: Color::from_rgb(0.45, 0.55, 0.45),         // greenish gray
             keyword: Color::from_rgb(0.8, 0.4, 0.8),            // purple
             string: Color::from_rgb(0.6, 0.8, 0.4),             // green
@@ -51,6 +53,7 @@ impl TerminalColors {
             directory: Color::from_rgb(0.0, 0.53, 1.0),         // #0087ff
             file: Color::from_rgb(0.11, 0.11, 0.11),            // #1c1c1c
             line_number: Color::from_rgb(0.6, 0.6, 0.6),        // gray
+            indent_guide: Color::from_rgb(0.85, 0.85, 0.85),    // light gray
             comment: Color::from_rgb(0.4, 0.5, 0.4),            // greenish gray
             keyword: Color::from_rgb(0.6, 0.2, 0.6),            // purple
             string: Color::from_rgb(0.3, 0.6, 0.2),             // green
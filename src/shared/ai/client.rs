@@ -1,45 +1,105 @@
 // AI API client implementations
 
+use std::time::{Duration, Instant};
+
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 
 use super::models::{ModelConfig, Provider};
+use super::tools::{AiReply, TokenUsage, ToolCall, ToolSpec};
+
+pub async fn send_message(config: &ModelConfig, history: &[String], input: &str) -> Result<String, String> {
+    send_message_with_tools(config, history, input, &[]).await.map(|reply| reply.text)
+}
 
-pub async fn send_message(config: &ModelConfig, _history: &[String], input: &str) -> Result<String, String> {
+/// Send a message offering `tools` to the model. Providers without tool support
+/// (Ollama's generate API, Echo) just reply with text.
+pub async fn send_message_with_tools(
+    config: &ModelConfig,
+    _history: &[String],
+    input: &str,
+    tools: &[ToolSpec],
+) -> Result<AiReply, String> {
+    let tuning = Tuning { system: config.system_prompt.as_deref().filter(|s| !s.trim().is_empty()), temperature: config.temperature };
     match config.provider {
-        Provider::Echo => Ok(format!("Echo: {}", input)),
+        Provider::Echo => Ok(AiReply { text: format!("Echo: {}", input), ..Default::default() }),
         Provider::Gemini => {
             if let Some(key) = &config.api_key {
-                send_gemini_message(input, key, &config.model_id).await
+                send_gemini_message(input, key, &config.model_id, tools, tuning).await
             } else {
-                Err("Gemini API Key missing. Please set it in Settings (Ctrl+S).".to_string())
+                Err("Gemini API Key missing. Please set it in Settings.".to_string())
             }
         },
         Provider::OpenAI => {
             if let Some(key) = &config.api_key {
-                send_openai_message(input, key, &config.model_id, config.base_url.as_deref()).await
+                send_openai_message(input, key, &config.model_id, config.base_url.as_deref(), tools, tuning).await
             } else {
-                Err("OpenAI API Key missing. Please set it in Settings (Ctrl+S).".to_string())
+                Err("OpenAI API Key missing. Please set it in Settings.".to_string())
             }
         },
         Provider::Anthropic => {
             if let Some(key) = &config.api_key {
-                send_anthropic_message(input, key, &config.model_id).await
+                send_anthropic_message(input, key, &config.model_id, tools, tuning).await
             } else {
-                Err("Anthropic API Key missing. Please set it in Settings (Ctrl+S).".to_string())
+                Err("Anthropic API Key missing. Please set it in Settings.".to_string())
             }
         },
         Provider::Ollama => {
-            send_ollama_message(input, &config.model_id, config.base_url.as_deref()).await
+            send_ollama_message(input, &config.model_id, config.base_url.as_deref(), tuning).await
         },
     }
 }
 
+/// System prompt and sampling temperature of a request, when set
+#[derive(Clone, Copy)]
+struct Tuning<'a> {
+    system: Option<&'a str>,
+    temperature: Option<f32>,
+}
+
+/// Minimal request to check a model's settings; returns the round-trip time
+pub async fn test_connection(config: &ModelConfig) -> Result<Duration, String> {
+    let start = Instant::now();
+    send_message(config, &[], "ping").await
+        .map(|_| start.elapsed())
+        .map_err(|e| describe_error(config, &e))
+}
+
+/// Turn a client error into a short diagnosis for the settings panel
+pub fn describe_error(config: &ModelConfig, error: &str) -> String {
+    let lower = error.to_lowercase();
+    if lower.contains("api key missing") {
+        "No API key set".to_string()
+    } else if lower.starts_with("network error") {
+        let host = config.base_url.as_deref().unwrap_or(match config.provider {
+            Provider::Ollama => "localhost:11434",
+            _ => "the provider",
+        });
+        format!("Cannot reach {}", host)
+    } else if ["(401", "(403", "api key not valid", "invalid x-api-key", "incorrect api key", "invalid_api_key"]
+        .iter().any(|marker| lower.contains(marker))
+    {
+        "Bad API key".to_string()
+    } else if lower.contains("(404") || lower.contains("not found") || lower.contains("model_not_found") {
+        format!("Unknown model id '{}'", config.model_id)
+    } else {
+        // The first line is enough; bodies can be long JSON
+        error.lines().next().unwrap_or(error).chars().take(120).collect()
+    }
+}
+
 // ============ Gemini ============
 
 #[derive(Serialize)]
 struct GeminiRequest {
     contents: Vec<GeminiContent>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<Value>,
+    #[serde(rename = "systemInstruction", skip_serializing_if = "Option::is_none")]
+    system_instruction: Option<Value>,
+    #[serde(rename = "generationConfig", skip_serializing_if = "Option::is_none")]
+    generation_config: Option<Value>,
 }
 
 #[derive(Serialize)]
@@ -54,9 +114,20 @@ struct GeminiPart {
 }
 
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct GeminiResponse {
     candidates: Option<Vec<GeminiCandidate>>,
     error: Option<GeminiError>,
+    usage_metadata: Option<GeminiUsage>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiUsage {
+    #[serde(default)]
+    prompt_token_count: u32,
+    #[serde(default)]
+    candidates_token_count: u32,
 }
 
 #[derive(Deserialize)]
@@ -70,8 +141,17 @@ struct GeminiContentResponse {
 }
 
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct GeminiPartResponse {
-    text: String,
+    text: Option<String>,
+    function_call: Option<GeminiFunctionCall>,
+}
+
+#[derive(Deserialize)]
+struct GeminiFunctionCall {
+    name: String,
+    #[serde(default)]
+    args: Value,
 }
 
 #[derive(Deserialize)]
@@ -79,7 +159,7 @@ struct GeminiError {
     message: String,
 }
 
-async fn send_gemini_message(input: &str, api_key: &str, model_id: &str) -> Result<String, String> {
+async fn send_gemini_message(input: &str, api_key: &str, model_id: &str, tools: &[ToolSpec], tuning: Tuning<'_>) -> Result<AiReply, String> {
     let url = format!(
         "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent?key={}",
         model_id, api_key
@@ -95,6 +175,16 @@ async fn send_gemini_message(input: &str, api_key: &str, model_id: &str) -> Resu
             role: "user".to_string(),
             parts: vec![GeminiPart { text: input.to_string() }],
         }],
+        tools: if tools.is_empty() {
+            Vec::new()
+        } else {
+            let declarations: Vec<Value> = tools.iter()
+                .map(|t| json!({ "name": t.name, "description": t.description, "parameters": t.parameters }))
+                .collect();
+            vec![json!({ "functionDeclarations": declarations })]
+        },
+        system_instruction: tuning.system.map(|system| json!({ "parts": [{ "text": system }] })),
+        generation_config: tuning.temperature.map(|temperature| json!({ "temperature": temperature })),
     };
 
     let response = client.post(&url)
@@ -120,10 +210,20 @@ async fn send_gemini_message(input: &str, api_key: &str, model_id: &str) -> Resu
     }
 
     if let Some(candidates) = gemini_resp.candidates {
-        if let Some(candidate) = candidates.first() {
-            if let Some(part) = candidate.content.parts.first() {
-                return Ok(part.text.clone());
+        if let Some(candidate) = candidates.into_iter().next() {
+            let mut reply = AiReply {
+                usage: gemini_resp.usage_metadata.map(|u| TokenUsage { input: u.prompt_token_count, output: u.candidates_token_count }),
+                ..Default::default()
+            };
+            for part in candidate.content.parts {
+                if let Some(text) = part.text {
+                    reply.text.push_str(&text);
+                }
+                if let Some(call) = part.function_call {
+                    reply.tool_calls.push(ToolCall { name: call.name, arguments: call.args });
+                }
             }
+            return Ok(reply);
         }
     }
 
@@ -136,6 +236,10 @@ async fn send_gemini_message(input: &str, api_key: &str, model_id: &str) -> Resu
 struct OpenAIRequest {
     model: String,
     messages: Vec<OpenAIMessage>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
 }
 
 #[derive(Serialize)]
@@ -148,6 +252,13 @@ struct OpenAIMessage {
 struct OpenAIResponse {
     choices: Option<Vec<OpenAIChoice>>,
     error: Option<OpenAIError>,
+    usage: Option<OpenAIUsage>,
+}
+
+#[derive(Deserialize)]
+struct OpenAIUsage {
+    prompt_tokens: u32,
+    completion_tokens: u32,
 }
 
 #[derive(Deserialize)]
@@ -157,7 +268,20 @@ struct OpenAIChoice {
 
 #[derive(Deserialize)]
 struct OpenAIMessageResponse {
-    content: String,
+    content: Option<String>,
+    tool_calls: Option<Vec<OpenAIToolCall>>,
+}
+
+#[derive(Deserialize)]
+struct OpenAIToolCall {
+    function: OpenAIFunctionCall,
+}
+
+#[derive(Deserialize)]
+struct OpenAIFunctionCall {
+    name: String,
+    /// JSON-encoded arguments object
+    arguments: String,
 }
 
 #[derive(Deserialize)]
@@ -165,7 +289,14 @@ struct OpenAIError {
     message: String,
 }
 
-async fn send_openai_message(input: &str, api_key: &str, model_id: &str, base_url: Option<&str>) -> Result<String, String> {
+async fn send_openai_message(
+    input: &str,
+    api_key: &str,
+    model_id: &str,
+    base_url: Option<&str>,
+    tools: &[ToolSpec],
+    tuning: Tuning<'_>,
+) -> Result<AiReply, String> {
     let base = base_url.unwrap_or("https://api.openai.com/v1");
     let url = format!("{}/chat/completions", base);
 
@@ -174,12 +305,19 @@ async fn send_openai_message(input: &str, api_key: &str, model_id: &str, base_ur
         .build()
         .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
 
+    let system = tuning.system.map(|system| OpenAIMessage { role: "system".to_string(), content: system.to_string() });
     let request_body = OpenAIRequest {
         model: model_id.to_string(),
-        messages: vec![OpenAIMessage {
-            role: "user".to_string(),
-            content: input.to_string(),
-        }],
+        messages: system.into_iter()
+            .chain([OpenAIMessage { role: "user".to_string(), content: input.to_string() }])
+            .collect(),
+        tools: tools.iter()
+            .map(|t| json!({
+                "type": "function",
+                "function": { "name": t.name, "description": t.description, "parameters": t.parameters }
+            }))
+            .collect(),
+        temperature: tuning.temperature,
     };
 
     let response = client.post(&url)
@@ -206,8 +344,17 @@ async fn send_openai_message(input: &str, api_key: &str, model_id: &str, base_ur
     }
 
     if let Some(choices) = openai_resp.choices {
-        if let Some(choice) = choices.first() {
-            return Ok(choice.message.content.clone());
+        if let Some(choice) = choices.into_iter().next() {
+            let message = choice.message;
+            let tool_calls = message.tool_calls.unwrap_or_default().into_iter()
+                .map(|call| {
+                    let arguments = serde_json::from_str(&call.function.arguments)
+                        .map_err(|e| format!("Invalid arguments for {}: {}", call.function.name, e))?;
+                    Ok(ToolCall { name: call.function.name, arguments })
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            let usage = openai_resp.usage.map(|u| TokenUsage { input: u.prompt_tokens, output: u.completion_tokens });
+            return Ok(AiReply { text: message.content.unwrap_or_default(), tool_calls, usage });
         }
     }
 
@@ -221,6 +368,12 @@ struct AnthropicRequest {
     model: String,
     max_tokens: u32,
     messages: Vec<AnthropicMessage>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
 }
 
 #[derive(Serialize)]
@@ -233,11 +386,21 @@ struct AnthropicMessage {
 struct AnthropicResponse {
     content: Option<Vec<AnthropicContent>>,
     error: Option<AnthropicError>,
+    usage: Option<AnthropicUsage>,
 }
 
+#[derive(Deserialize)]
+struct AnthropicUsage {
+    input_tokens: u32,
+    output_tokens: u32,
+}
+
+/// A content block: `text`, or `tool_use` with a name and input
 #[derive(Deserialize)]
 struct AnthropicContent {
-    text: String,
+    text: Option<String>,
+    name: Option<String>,
+    input: Option<Value>,
 }
 
 #[derive(Deserialize)]
@@ -245,7 +408,7 @@ struct AnthropicError {
     message: String,
 }
 
-async fn send_anthropic_message(input: &str, api_key: &str, model_id: &str) -> Result<String, String> {
+async fn send_anthropic_message(input: &str, api_key: &str, model_id: &str, tools: &[ToolSpec], tuning: Tuning<'_>) -> Result<AiReply, String> {
     let url = "https://api.anthropic.com/v1/messages";
 
     let client = Client::builder()
@@ -260,6 +423,11 @@ async fn send_anthropic_message(input: &str, api_key: &str, model_id: &str) -> R
             role: "user".to_string(),
             content: input.to_string(),
         }],
+        tools: tools.iter()
+            .map(|t| json!({ "name": t.name, "description": t.description, "input_schema": t.parameters }))
+            .collect(),
+        system: tuning.system.map(str::to_string),
+        temperature: tuning.temperature,
     };
 
     let response = client.post(url)
@@ -287,8 +455,19 @@ async fn send_anthropic_message(input: &str, api_key: &str, model_id: &str) -> R
     }
 
     if let Some(content) = anthropic_resp.content {
-        if let Some(block) = content.first() {
-            return Ok(block.text.clone());
+        if !content.is_empty() {
+            let mut reply = AiReply {
+                usage: anthropic_resp.usage.map(|u| TokenUsage { input: u.input_tokens, output: u.output_tokens }),
+                ..Default::default()
+            };
+            for block in content {
+                match (block.text, block.name, block.input) {
+                    (Some(text), _, _) => reply.text.push_str(&text),
+                    (None, Some(name), Some(arguments)) => reply.tool_calls.push(ToolCall { name, arguments }),
+                    _ => {}
+                }
+            }
+            return Ok(reply);
         }
     }
 
@@ -302,15 +481,21 @@ struct OllamaRequest {
     model: String,
     prompt: String,
     stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    options: Option<Value>,
 }
 
 #[derive(Deserialize)]
 struct OllamaResponse {
     response: Option<String>,
     error: Option<String>,
+    prompt_eval_count: Option<u32>,
+    eval_count: Option<u32>,
 }
 
-async fn send_ollama_message(input: &str, model_id: &str, base_url: Option<&str>) -> Result<String, String> {
+async fn send_ollama_message(input: &str, model_id: &str, base_url: Option<&str>, tuning: Tuning<'_>) -> Result<AiReply, String> {
     let base = base_url.unwrap_or("http://localhost:11434");
     let url = format!("{}/api/generate", base);
 
@@ -323,6 +508,8 @@ async fn send_ollama_message(input: &str, model_id: &str, base_url: Option<&str>
         model: model_id.to_string(),
         prompt: input.to_string(),
         stream: false,
+        system: tuning.system.map(str::to_string),
+        options: tuning.temperature.map(|temperature| json!({ "temperature": temperature })),
     };
 
     let response = client.post(&url)
@@ -347,9 +534,27 @@ async fn send_ollama_message(input: &str, model_id: &str, base_url: Option<&str>
         return Err(format!("Ollama error: {}", error));
     }
 
-    if let Some(response) = ollama_resp.response {
-        return Ok(response);
+    if let Some(text) = ollama_resp.response {
+        let usage = ollama_resp.eval_count.map(|output| TokenUsage { input: ollama_resp.prompt_eval_count.unwrap_or(0), output });
+        return Ok(AiReply { text, tool_calls: Vec::new(), usage });
     }
 
     Err("No response content found in Ollama response".to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_error() {
+        let config = ModelConfig { model_id: "gpt-9".to_string(), provider: Provider::OpenAI, ..Default::default() };
+        assert_eq!(describe_error(&config, "OpenAI API Key missing. Please set it in Settings."), "No API key set");
+        assert_eq!(describe_error(&config, "API error (401 Unauthorized): {\"error\": {}}"), "Bad API key");
+        assert_eq!(describe_error(&config, "API error (404 Not Found): {}"), "Unknown model id 'gpt-9'");
+        assert_eq!(describe_error(&config, "Network error: dns error"), "Cannot reach the provider");
+        let ollama = ModelConfig { provider: Provider::Ollama, ..Default::default() };
+        assert_eq!(describe_error(&ollama, "Network error (is Ollama running?): refused"), "Cannot reach localhost:11434");
+        assert_eq!(describe_error(&config, "Failed to parse response\nbody"), "Failed to parse response");
+    }
+}
@@ -2,7 +2,11 @@
 
 pub mod client;
 pub mod models;
+pub mod presets;
+pub mod tools;
 
 // Re-export commonly used types
-pub use client::send_message;
+pub use client::{send_message, send_message_with_tools, test_connection};
 pub use models::{default_models, ModelConfig, Provider};
+pub use presets::{default_presets, ContextPreset, ContextRule};
+pub use tools::{AiReply, TokenUsage, ToolCall, ToolSpec};
@@ -32,6 +32,12 @@ pub struct ModelConfig {
     pub model_id: String,
     pub api_key: Option<String>,
     pub base_url: Option<String>,
+    /// Instructions sent ahead of every chat message; usually set by a context preset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    /// Sampling temperature; the provider's default when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
 }
 
 impl ModelConfig {
@@ -48,6 +54,8 @@ impl Default for ModelConfig {
             model_id: "gemini-2.0-flash".to_string(),
             api_key: None,
             base_url: None,
+            system_prompt: None,
+            temperature: None,
         }
     }
 }
@@ -61,6 +69,8 @@ pub fn default_models() -> Vec<ModelConfig> {
             model_id: "gemini-2.0-flash".to_string(),
             api_key: None,
             base_url: None,
+            system_prompt: None,
+            temperature: None,
         },
         ModelConfig {
             name: "GPT-4o Mini".to_string(),
@@ -68,6 +78,8 @@ pub fn default_models() -> Vec<ModelConfig> {
             model_id: "gpt-4o-mini".to_string(),
             api_key: None,
             base_url: None,
+            system_prompt: None,
+            temperature: None,
         },
         ModelConfig {
             name: "Claude Sonnet".to_string(),
@@ -75,6 +87,8 @@ pub fn default_models() -> Vec<ModelConfig> {
             model_id: "claude-sonnet-4-20250514".to_string(),
             api_key: None,
             base_url: None,
+            system_prompt: None,
+            temperature: None,
         },
         ModelConfig {
             name: "Ollama Llama".to_string(),
@@ -82,6 +96,8 @@ pub fn default_models() -> Vec<ModelConfig> {
             model_id: "llama3.2".to_string(),
             api_key: None,
             base_url: Some("http://localhost:11434".to_string()),
+            system_prompt: None,
+            temperature: None,
         },
         ModelConfig {
             name: "Echo (Offline)".to_string(),
@@ -89,6 +105,8 @@ pub fn default_models() -> Vec<ModelConfig> {
             model_id: "echo".to_string(),
             api_key: None,
             base_url: None,
+            system_prompt: None,
+            temperature: None,
         },
     ]
 }
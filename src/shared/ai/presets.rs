@@ -0,0 +1,134 @@
+// Context presets: named chat setups bundling a system prompt, temperature,
+// model and the context attached to each message, switched from the chat header
+
+use serde::{Deserialize, Serialize};
+
+use super::models::ModelConfig;
+
+/// Attached context is cut off after this many chars
+pub const MAX_CONTEXT_CHARS: usize = 20_000;
+
+/// Context sent along with each chat message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextRule {
+    /// File counts, languages, largest files and recent commits
+    WorkspaceSummary,
+    /// The file open in the editor
+    OpenFile,
+    /// The text selected in the editor
+    Selection,
+    /// Problems found in the open file
+    Problems,
+}
+
+impl ContextRule {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ContextRule::WorkspaceSummary => "workspace summary",
+            ContextRule::OpenFile => "open file",
+            ContextRule::Selection => "selection",
+            ContextRule::Problems => "problems",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContextPreset {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub system_prompt: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    /// Name of one of the configured models, selected with the preset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub context: Vec<ContextRule>,
+}
+
+impl ContextPreset {
+    /// `model` with the preset's system prompt and temperature
+    pub fn apply(&self, model: &ModelConfig) -> ModelConfig {
+        let mut model = model.clone();
+        if !self.system_prompt.trim().is_empty() {
+            model.system_prompt = Some(self.system_prompt.clone());
+        }
+        model.temperature = self.temperature.or(model.temperature);
+        model
+    }
+
+    /// Index of the preset's model among `models`
+    pub fn model_index(&self, models: &[ModelConfig]) -> Option<usize> {
+        let name = self.model.as_deref()?;
+        models.iter().position(|m| m.name.eq_ignore_ascii_case(name))
+    }
+
+    /// One line for the picker, e.g. `Terse shell helper (temperature 0.2; selection)`
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(model) = &self.model {
+            parts.push(model.clone());
+        }
+        if let Some(temperature) = self.temperature {
+            parts.push(format!("temperature {}", temperature));
+        }
+        let context: Vec<&str> = self.context.iter().map(ContextRule::label).collect();
+        if !context.is_empty() {
+            parts.push(format!("with {}", context.join(", ")));
+        }
+        if parts.is_empty() { self.name.clone() } else { format!("{} ({})", self.name, parts.join("; ")) }
+    }
+}
+
+/// `message` preceded by the context sections (title, text) that aren't empty,
+/// each cut to `MAX_CONTEXT_CHARS`
+pub fn with_context(message: &str, sections: &[(String, String)]) -> String {
+    let mut out = String::new();
+    for (title, text) in sections.iter().filter(|(_, text)| !text.trim().is_empty()) {
+        let text: String = text.chars().take(MAX_CONTEXT_CHARS).collect();
+        out.push_str(&format!("{}:\n```\n{}\n```\n\n", title, text.trim_end()));
+    }
+    out.push_str(message);
+    out
+}
+
+pub fn default_presets() -> Vec<ContextPreset> {
+    vec![
+        ContextPreset {
+            name: "Rust expert".to_string(),
+            system_prompt: "You are an experienced Rust developer. Prefer idiomatic, safe code and explain ownership or lifetime issues briefly.".to_string(),
+            temperature: Some(0.3),
+            model: None,
+            context: vec![ContextRule::WorkspaceSummary, ContextRule::Selection],
+        },
+        ContextPreset {
+            name: "Terse shell helper".to_string(),
+            system_prompt: "Answer with a single shell command or a short script and at most one sentence of explanation.".to_string(),
+            temperature: Some(0.2),
+            model: None,
+            context: Vec::new(),
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_and_context() {
+        let preset: ContextPreset = serde_json::from_str(
+            r#"{"name": "Reviewer", "system_prompt": "Be strict.", "temperature": 0.1, "model": "echo (offline)", "context": ["open_file", "problems"]}"#,
+        ).unwrap();
+        let models = super::super::models::default_models();
+        assert_eq!(preset.model_index(&models), Some(4));
+        let tuned = preset.apply(&models[0]);
+        assert_eq!((tuned.system_prompt.as_deref(), tuned.temperature), (Some("Be strict."), Some(0.1)));
+        assert_eq!(preset.summary(), "Reviewer (echo (offline); temperature 0.1; with open file, problems)");
+
+        let sections = vec![("Open file src/a.rs".to_string(), "fn main() {}\n".to_string()), ("Problems".to_string(), " ".to_string())];
+        assert_eq!(with_context("Why?", &sections), "Open file src/a.rs:\n```\nfn main() {}\n```\n\nWhy?");
+        assert_eq!(with_context("Why?", &[]), "Why?");
+    }
+}
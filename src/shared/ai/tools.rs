@@ -0,0 +1,85 @@
+// Tool (function) calling: tool definitions offered to the model and the calls it makes
+
+use serde_json::{json, Value};
+
+/// A function the model may call
+#[derive(Debug, Clone)]
+pub struct ToolSpec {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// JSON schema of the arguments object
+    pub parameters: Value,
+}
+
+/// A call the model made to one of the offered tools
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolCall {
+    pub name: String,
+    pub arguments: Value,
+}
+
+/// Tokens used by one request, when the provider reports them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenUsage {
+    pub input: u32,
+    pub output: u32,
+}
+
+/// A model reply: its text plus any tool calls
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AiReply {
+    pub text: String,
+    pub tool_calls: Vec<ToolCall>,
+    pub usage: Option<TokenUsage>,
+}
+
+pub const CREATE_FILE: &str = "create_file";
+
+/// Lets the model propose a new workspace file; nothing is written until the user approves it
+pub fn create_file_tool() -> ToolSpec {
+    ToolSpec {
+        name: CREATE_FILE,
+        description: "Create a new file in the user's workspace. The file is shown to the user for \
+                      approval before it is written. Existing files cannot be overwritten.",
+        parameters: json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "Path relative to the workspace root, e.g. src/utils.rs"
+                },
+                "content": {
+                    "type": "string",
+                    "description": "Full content of the new file"
+                }
+            },
+            "required": ["path", "content"]
+        }),
+    }
+}
+
+pub const RUN_COMMAND: &str = "run_command";
+
+/// Lets the model run a shell command in the workspace (see `sandbox`) and read its output
+pub fn run_command_tool() -> ToolSpec {
+    ToolSpec {
+        name: RUN_COMMAND,
+        description: "Run a shell command in the user's workspace, e.g. to build or test the project. \
+                      Commands run without network access and are killed after a timeout. Their \
+                      stdout and stderr are sent back to you.",
+        parameters: json!({
+            "type": "object",
+            "properties": {
+                "command": {
+                    "type": "string",
+                    "description": "Command line run with sh -c, e.g. cargo test"
+                },
+                "cwd": {
+                    "type": "string",
+                    "description": "Directory relative to the workspace root; defaults to the root"
+                }
+            },
+            "required": ["command"]
+        }),
+    }
+}
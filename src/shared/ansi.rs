@@ -0,0 +1,201 @@
+// ANSI escape sequence handling for terminal output
+
+/// Remove escape sequences (CSI, OSC, charset selection) from terminal output
+pub fn strip_ansi(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI: ESC [ params... final byte in 0x40..=0x7e
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('\x40'..='\x7e').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC: ESC ] ... terminated by BEL or ESC \
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            // Charset selection: ESC ( B and friends
+            Some('(') | Some(')') => {
+                chars.next();
+            }
+            _ => {}
+        }
+    }
+
+    out
+}
+
+/// Foreground color selected by an SGR sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnsiColor {
+    /// 256-color palette index (0-15 are the standard and bright colors)
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+/// A run of colored text on one line (char offsets, `end` exclusive)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorSpan {
+    pub start: usize,
+    pub end: usize,
+    pub fg: AnsiColor,
+}
+
+/// One line of terminal output as it was displayed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StyledLine {
+    pub text: String,
+    pub spans: Vec<ColorSpan>,
+}
+
+/// Interpret terminal output the way the terminal displayed it. Escape sequences
+/// are removed but SGR colors are kept as spans, `\r` returns to the start of the
+/// line so progress bars collapse to their final state, backspace steps back and
+/// other control characters are dropped.
+pub fn render_lines(input: &str) -> Vec<StyledLine> {
+    let mut lines = Vec::new();
+    let mut cells: Vec<(char, Option<AnsiColor>)> = Vec::new();
+    let mut col: usize = 0;
+    let mut fg = None;
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => {
+                lines.push(styled_line(&cells));
+                cells.clear();
+                col = 0;
+            }
+            '\r' => col = 0,
+            '\x08' => col = col.saturating_sub(1),
+            '\x1b' => match chars.next() {
+                Some('[') => {
+                    let mut params = String::new();
+                    let mut command = None;
+                    for c in chars.by_ref() {
+                        if ('\x40'..='\x7e').contains(&c) {
+                            command = Some(c);
+                            break;
+                        }
+                        params.push(c);
+                    }
+                    match command {
+                        Some('m') => fg = apply_sgr(&params, fg),
+                        // Erase in line: to the end, or the whole line
+                        Some('K') if params.is_empty() || params == "0" => cells.truncate(col),
+                        Some('K') if params == "2" => cells.clear(),
+                        _ => {}
+                    }
+                }
+                Some(']') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' {
+                            break;
+                        }
+                        if c == '\x1b' && chars.peek() == Some(&'\\') {
+                            chars.next();
+                            break;
+                        }
+                    }
+                }
+                Some('(') | Some(')') => {
+                    chars.next();
+                }
+                _ => {}
+            },
+            c if c.is_control() && c != '\t' => {}
+            c => {
+                if col < cells.len() {
+                    cells[col] = (c, fg);
+                } else {
+                    cells.resize(col, (' ', None));
+                    cells.push((c, fg));
+                }
+                col += 1;
+            }
+        }
+    }
+    lines.push(styled_line(&cells));
+    lines
+}
+
+/// The foreground color after an SGR sequence with `params`
+fn apply_sgr(params: &str, mut fg: Option<AnsiColor>) -> Option<AnsiColor> {
+    let codes: Vec<u16> = params.split(';').map(|p| p.parse().unwrap_or(0)).collect();
+    let mut i = 0;
+    while i < codes.len() {
+        match codes[i] {
+            0 | 39 => fg = None,
+            n @ 30..=37 => fg = Some(AnsiColor::Indexed((n - 30) as u8)),
+            n @ 90..=97 => fg = Some(AnsiColor::Indexed((n - 90 + 8) as u8)),
+            38 => match codes.get(i + 1) {
+                Some(5) => {
+                    fg = codes.get(i + 2).map(|&n| AnsiColor::Indexed(n as u8));
+                    i += 2;
+                }
+                Some(2) if i + 4 < codes.len() => {
+                    fg = Some(AnsiColor::Rgb(codes[i + 2] as u8, codes[i + 3] as u8, codes[i + 4] as u8));
+                    i += 4;
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        i += 1;
+    }
+    fg
+}
+
+fn styled_line(cells: &[(char, Option<AnsiColor>)]) -> StyledLine {
+    let text = cells.iter().map(|(c, _)| *c).collect();
+    let mut spans: Vec<ColorSpan> = Vec::new();
+    for (idx, (_, color)) in cells.iter().enumerate() {
+        let Some(fg) = *color else { continue };
+        match spans.last_mut() {
+            Some(span) if span.end == idx && span.fg == fg => span.end += 1,
+            _ => spans.push(ColorSpan { start: idx, end: idx + 1, fg }),
+        }
+    }
+    StyledLine { text, spans }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(strip_ansi("\x1b[1;31merror\x1b[0m: bad"), "error: bad");
+        assert_eq!(strip_ansi("\x1b]0;title\x07prompt$ "), "prompt$ ");
+        assert_eq!(strip_ansi("plain"), "plain");
+    }
+
+    #[test]
+    fn test_render_lines() {
+        let lines = render_lines("Downloading 10%\rDownloading 100%\r\ntypo\x08\x08po\x07\n\x1b[1;31merror\x1b[0m: \x1b[38;5;208mbad\x1b[m");
+        let texts: Vec<&str> = lines.iter().map(|l| l.text.as_str()).collect();
+        assert_eq!(texts, vec!["Downloading 100%", "typo", "error: bad"]);
+        assert!(lines[0].spans.is_empty());
+        assert_eq!(lines[2].spans, vec![
+            ColorSpan { start: 0, end: 5, fg: AnsiColor::Indexed(1) },
+            ColorSpan { start: 7, end: 10, fg: AnsiColor::Indexed(208) },
+        ]);
+    }
+}
@@ -0,0 +1,213 @@
+// What the host terminal can display: color depth, Unicode, mouse reporting
+// and the kitty keyboard protocol. Guessed from the environment at startup
+// (the keyboard protocol is also queried) and overridable in Config, so the
+// interface can fall back to 256 or 16 colors and ASCII on minimal terminals.
+
+use serde::{Deserialize, Serialize};
+
+/// Colors the terminal can show
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ColorLevel {
+    /// The 16 ANSI colors
+    #[serde(rename = "16")]
+    Basic,
+    /// The xterm 256-color palette
+    #[serde(rename = "256")]
+    Indexed,
+    /// 24-bit RGB
+    #[serde(rename = "truecolor")]
+    TrueColor,
+}
+
+/// Settings that replace what was detected, e.g. `"terminal": {"color": "256", "unicode": false}`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TerminalOverrides {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<ColorLevel>,
+    /// Box drawing and other non-ASCII characters (ASCII borders when off)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unicode: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mouse: Option<bool>,
+    /// Report keys unambiguously (e.g. Shift+Enter, Ctrl+I apart from Tab)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kitty_keyboard: Option<bool>,
+}
+
+impl TerminalOverrides {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    pub color: ColorLevel,
+    pub unicode: bool,
+    pub mouse: bool,
+    pub kitty_keyboard: bool,
+}
+
+impl Default for Capabilities {
+    /// A modern terminal emulator
+    fn default() -> Self {
+        Self { color: ColorLevel::TrueColor, unicode: true, mouse: true, kitty_keyboard: false }
+    }
+}
+
+/// Terminals without mouse reporting or anything beyond ASCII line drawing
+const MINIMAL_TERMS: [&str; 4] = ["dumb", "vt100", "vt102", "vt220"];
+
+/// `$TERM_PROGRAM` values of emulators known to do 24-bit color
+const TRUECOLOR_PROGRAMS: [&str; 5] = ["iTerm.app", "WezTerm", "vscode", "ghostty", "Hyper"];
+
+impl Capabilities {
+    /// Guess from the process environment
+    pub fn from_env() -> Self {
+        Self::detect(|name| std::env::var(name).ok().filter(|v| !v.is_empty()))
+    }
+
+    /// Guess from environment variables looked up with `var`
+    pub fn detect(var: impl Fn(&str) -> Option<String>) -> Self {
+        let term = var("TERM").unwrap_or_default();
+        let program = var("TERM_PROGRAM").unwrap_or_default();
+        let minimal = MINIMAL_TERMS.contains(&term.as_str());
+
+        let color = if minimal {
+            ColorLevel::Basic
+        } else if var("COLORTERM").is_some_and(|c| c == "truecolor" || c == "24bit")
+            || var("WT_SESSION").is_some()
+            || TRUECOLOR_PROGRAMS.contains(&program.as_str())
+            || term.ends_with("-direct")
+        {
+            ColorLevel::TrueColor
+        } else if term.contains("256color") || program == "Apple_Terminal" {
+            ColorLevel::Indexed
+        } else {
+            ColorLevel::Basic
+        };
+
+        // The first locale variable that is set decides; none set says nothing either way
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"].iter().find_map(|name| var(name));
+        let utf8 = locale.is_none_or(|l| {
+            let l = l.to_lowercase();
+            l.contains("utf-8") || l.contains("utf8")
+        });
+
+        Self {
+            color,
+            unicode: utf8 && !minimal,
+            mouse: !minimal && !term.is_empty(),
+            kitty_keyboard: var("KITTY_WINDOW_ID").is_some() || term == "xterm-kitty",
+        }
+    }
+
+    pub fn with_overrides(self, overrides: &TerminalOverrides) -> Self {
+        Self {
+            color: overrides.color.unwrap_or(self.color),
+            unicode: overrides.unicode.unwrap_or(self.unicode),
+            mouse: overrides.mouse.unwrap_or(self.mouse),
+            kitty_keyboard: overrides.kitty_keyboard.unwrap_or(self.kitty_keyboard),
+        }
+    }
+}
+
+/// Levels of the 6x6x6 cube in the 256-color palette
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Default xterm values of the 16 ANSI colors
+const ANSI_RGB: [[u8; 3]; 16] = [
+    [0, 0, 0], [205, 0, 0], [0, 205, 0], [205, 205, 0],
+    [0, 0, 238], [205, 0, 205], [0, 205, 205], [229, 229, 229],
+    [127, 127, 127], [255, 0, 0], [0, 255, 0], [255, 255, 0],
+    [92, 92, 255], [255, 0, 255], [0, 255, 255], [255, 255, 255],
+];
+
+fn distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    a.iter().zip(b).map(|(&x, y)| (x as i32 - y as i32).pow(2) as u32).sum()
+}
+
+/// RGB of an entry in the 256-color palette
+pub fn indexed_rgb(index: u8) -> [u8; 3] {
+    match index {
+        0..=15 => ANSI_RGB[index as usize],
+        16..=231 => {
+            let i = index - 16;
+            [CUBE_LEVELS[(i / 36) as usize], CUBE_LEVELS[(i / 6 % 6) as usize], CUBE_LEVELS[(i % 6) as usize]]
+        }
+        _ => [8 + (index - 232) * 10; 3],
+    }
+}
+
+/// Closest entry of the 256-color palette outside the 16 themeable ANSI colors
+pub fn nearest_indexed(rgb: [u8; 3]) -> u8 {
+    let level = |c: u8| CUBE_LEVELS.iter().enumerate().min_by_key(|(_, &l)| l.abs_diff(c)).map(|(i, _)| i as u8).unwrap_or(0);
+    let cube = 16 + 36 * level(rgb[0]) + 6 * level(rgb[1]) + level(rgb[2]);
+    let average = (rgb.iter().map(|&c| c as u32).sum::<u32>() / 3) as u8;
+    let gray = 232 + (average.saturating_sub(3) / 10).min(23);
+    if distance(indexed_rgb(gray), rgb) < distance(indexed_rgb(cube), rgb) { gray } else { cube }
+}
+
+/// Closest of the 16 ANSI colors
+pub fn nearest_ansi(rgb: [u8; 3]) -> u8 {
+    (0..16u8).min_by_key(|&i| distance(ANSI_RGB[i as usize], rgb)).unwrap_or(7)
+}
+
+/// ASCII stand-in for a character the terminal may not draw
+pub fn ascii_fallback(c: char) -> char {
+    match c {
+        '─' | '━' | '═' | '╌' | '┄' | '−' | '–' | '—' => '-',
+        '│' | '┃' | '║' | '╎' | '┆' | '▏' | '▕' => '|',
+        '┌' | '┐' | '└' | '┘' | '╭' | '╮' | '╰' | '╯' | '┏' | '┓' | '┗' | '┛'
+        | '╔' | '╗' | '╚' | '╝' | '├' | '┤' | '┬' | '┴' | '┼' => '+',
+        '▲' | '↑' => '^',
+        '▼' | '↓' => 'v',
+        '▸' | '▶' | '►' | '→' | '»' => '>',
+        '◂' | '◀' | '◄' | '←' | '«' => '<',
+        '●' | '•' | '·' | '○' | '◆' => '*',
+        '█' | '▓' | '▒' | '░' | '■' => '#',
+        '…' => '.',
+        '✓' | '✔' => '+',
+        '✗' | '✘' | '×' => 'x',
+        c if c.is_ascii() => c,
+        _ => '?',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(vars: &[(&str, &str)]) -> Capabilities {
+        Capabilities::detect(|name| vars.iter().find(|(n, _)| *n == name).map(|(_, v)| v.to_string()))
+    }
+
+    #[test]
+    fn test_detect() {
+        let caps = detect(&[("TERM", "xterm-256color"), ("COLORTERM", "truecolor"), ("LANG", "en_US.UTF-8")]);
+        assert_eq!(caps, Capabilities::default());
+
+        let caps = detect(&[("TERM", "screen-256color"), ("LANG", "C")]);
+        assert_eq!((caps.color, caps.unicode, caps.mouse), (ColorLevel::Indexed, false, true));
+
+        let caps = detect(&[("TERM", "vt100"), ("LC_ALL", "C.UTF-8")]);
+        assert_eq!((caps.color, caps.unicode, caps.mouse), (ColorLevel::Basic, false, false));
+
+        assert!(detect(&[("TERM", "xterm-kitty")]).kitty_keyboard);
+
+        let overrides = TerminalOverrides { color: Some(ColorLevel::TrueColor), unicode: Some(true), ..Default::default() };
+        let caps = detect(&[("TERM", "linux")]).with_overrides(&overrides);
+        assert_eq!((caps.color, caps.unicode, caps.mouse), (ColorLevel::TrueColor, true, true));
+    }
+
+    #[test]
+    fn test_color_mapping() {
+        assert_eq!(nearest_indexed([255, 0, 0]), 196);
+        assert_eq!(nearest_indexed([30, 30, 46]), 235);
+        assert_eq!(nearest_indexed([128, 128, 128]), 244);
+        assert_eq!(indexed_rgb(196), [255, 0, 0]);
+        assert_eq!(nearest_ansi([250, 10, 10]), 9);
+        assert_eq!(nearest_ansi(indexed_rgb(236)), 0);
+        assert_eq!("╭─▸ é".chars().map(ascii_fallback).collect::<String>(), "+-> ?");
+    }
+}
@@ -0,0 +1,227 @@
+// Chat transcript: structured messages with their metadata
+
+use std::borrow::Cow;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::ai::TokenUsage;
+
+/// Who wrote a chat message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    User,
+    Assistant,
+    /// Notices from nterm itself (greeting, errors); shown without a badge
+    System,
+}
+
+impl Role {
+    /// Badge shown in front of the message
+    pub fn label(&self) -> Option<&'static str> {
+        match self {
+            Role::User => Some("You"),
+            Role::Assistant => Some("AI"),
+            Role::System => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatMessage {
+    pub role: Role,
+    pub content: String,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    /// Display name of the model that replied
+    pub model: Option<String>,
+    pub usage: Option<TokenUsage>,
+}
+
+impl ChatMessage {
+    pub fn new(role: Role, content: impl Into<String>) -> Self {
+        Self {
+            role,
+            content: content.into(),
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            model: None,
+            usage: None,
+        }
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self::new(Role::User, content)
+    }
+
+    pub fn system(content: impl Into<String>) -> Self {
+        Self::new(Role::System, content)
+    }
+
+    /// A model reply, tagged with the model's name and the tokens it used
+    pub fn assistant(content: impl Into<String>, model: impl Into<String>, usage: Option<TokenUsage>) -> Self {
+        Self { model: Some(model.into()), usage, ..Self::new(Role::Assistant, content) }
+    }
+
+    /// Metadata line shown after the badge, e.g. `14:03 · Gemini Flash · 12→40 tokens`
+    pub fn meta(&self, show_timestamp: bool) -> String {
+        let mut parts = Vec::new();
+        if show_timestamp {
+            parts.push(format_clock(self.timestamp));
+        }
+        if let Some(model) = &self.model {
+            parts.push(model.clone());
+        }
+        if let Some(usage) = self.usage {
+            parts.push(format!("{}→{} tokens", usage.input, usage.output));
+        }
+        parts.join(" · ")
+    }
+
+    /// Long enough to be folded in the chat panel
+    pub fn is_long(&self) -> bool {
+        self.content.lines().nth(FOLD_LINES).is_some()
+    }
+
+    /// The message as `Label: content`, as sent back to the model for context
+    pub fn transcript_line(&self) -> String {
+        match self.role.label() {
+            Some(label) => format!("{}: {}", label, self.content),
+            None => self.content.clone(),
+        }
+    }
+}
+
+/// Lines of a long message shown until it is expanded
+pub const FOLD_LINES: usize = 40;
+
+/// Characters of a single line that are rendered; the rest is cut off
+pub const MAX_LINE_CHARS: usize = 2000;
+
+/// What to render of `text`: the first `FOLD_LINES` lines unless `expanded`,
+/// each cut to `MAX_LINE_CHARS`, and how many lines were folded away. Keeps a
+/// huge reply from making every frame wrap and style all of it.
+pub fn render_text(text: &str, expanded: bool) -> (Cow<'_, str>, usize) {
+    let total = text.lines().count();
+    let shown = if expanded { total } else { total.min(FOLD_LINES) };
+    let long_line = text.lines().take(shown).any(|l| l.len() > MAX_LINE_CHARS);
+    if shown == total && !long_line {
+        return (Cow::Borrowed(text), 0);
+    }
+    let lines: Vec<Cow<str>> = text.lines()
+        .take(shown)
+        .map(|line| match line.char_indices().nth(MAX_LINE_CHARS) {
+            Some((cut, _)) => Cow::Owned(format!("{}…", &line[..cut])),
+            None => Cow::Borrowed(line),
+        })
+        .collect();
+    (Cow::Owned(lines.join("\n")), total - shown)
+}
+
+/// Messages `/summarize` keeps verbatim after the summary
+pub const KEEP_RECENT: usize = 2;
+
+/// Prompt asking the model to compress `messages` into a short summary
+pub fn summary_prompt(messages: &[ChatMessage]) -> String {
+    let transcript: Vec<String> = messages.iter().map(ChatMessage::transcript_line).collect();
+    format!(
+        "Summarize the following conversation between a developer and an AI assistant in a few \
+         short bullet points. Keep decisions, file names, code identifiers and open questions; drop \
+         pleasantries. Reply with the summary only.\n\n{}",
+        transcript.join("\n\n")
+    )
+}
+
+/// Replace the first `count` messages with a single summary message
+pub fn replace_with_summary(history: &mut Vec<ChatMessage>, count: usize, summary: &str) {
+    let count = count.min(history.len());
+    let message = ChatMessage::system(format!("Summary of the earlier conversation:\n{}", summary.trim()));
+    history.splice(..count, std::iter::once(message));
+}
+
+/// Local wall-clock time as HH:MM (UTC where the local offset is unknown)
+pub fn format_clock(timestamp: u64) -> String {
+    let seconds = timestamp as i64 + local_offset(timestamp);
+    let minutes = seconds.rem_euclid(86_400) / 60;
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// Local date and time as YYYY-MM-DD HH:MM
+pub fn format_date_time(timestamp: u64) -> String {
+    let days = (timestamp as i64 + local_offset(timestamp)).div_euclid(86_400);
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02} {}", year, month, day, format_clock(timestamp))
+}
+
+#[cfg(unix)]
+fn local_offset(timestamp: u64) -> i64 {
+    let time = timestamp as libc::time_t;
+    // SAFETY: localtime_r only writes to the tm we pass in
+    unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&time, &mut tm).is_null() {
+            return 0;
+        }
+        tm.tm_gmtoff as i64
+    }
+}
+
+#[cfg(not(unix))]
+fn local_offset(_timestamp: u64) -> i64 {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_meta() {
+        let mut reply = ChatMessage::assistant("hi", "Gemini Flash", Some(TokenUsage { input: 12, output: 40 }));
+        assert_eq!(reply.meta(false), "Gemini Flash · 12→40 tokens");
+        reply.timestamp = 0;
+        assert_eq!(reply.meta(true).len(), "00:00 · Gemini Flash · 12→40 tokens".len());
+        assert_eq!(ChatMessage::user("hello").meta(false), "");
+        assert_eq!(reply.transcript_line(), "AI: hi");
+        assert_eq!(ChatMessage::system("Welcome").transcript_line(), "Welcome");
+        // Noon UTC, so the date holds in any usual local offset
+        assert!(format_date_time(1_709_208_000).starts_with("2024-02-29 "));
+    }
+
+    #[test]
+    fn test_replace_with_summary() {
+        let mut history = vec![
+            ChatMessage::user("rename foo"),
+            ChatMessage::assistant("done", "Echo", None),
+            ChatMessage::user("thanks"),
+        ];
+        assert!(summary_prompt(&history[..2]).ends_with("You: rename foo\n\nAI: done"));
+        replace_with_summary(&mut history, 2, "- renamed foo\n");
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].role, Role::System);
+        assert_eq!(history[0].content, "Summary of the earlier conversation:\n- renamed foo");
+        assert_eq!(history[1].content, "thanks");
+    }
+
+    #[test]
+    fn test_render_text() {
+        assert_eq!(render_text("short\nreply", false), (Cow::Borrowed("short\nreply"), 0));
+
+        let long: String = (0..100).map(|i| format!("line {}\n", i)).collect();
+        let (text, hidden) = render_text(&long, false);
+        assert_eq!((text.lines().count(), hidden), (FOLD_LINES, 100 - FOLD_LINES));
+        assert_eq!(render_text(&long, true).1, 0);
+        assert!(ChatMessage::assistant(long, "model", None).is_long());
+
+        let wide = "x".repeat(MAX_LINE_CHARS + 10);
+        let (text, _) = render_text(&wide, true);
+        assert_eq!(text.chars().count(), MAX_LINE_CHARS + 1);
+        assert!(text.ends_with('…'));
+    }
+}
@@ -0,0 +1,232 @@
+// Code lens: actions offered on function definitions (run the test, explain
+// it with AI, see its references). Without a parser or language server to ask,
+// definitions are recognized by per-language patterns on the line.
+
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use super::workspace;
+
+/// Files larger than this are not searched for references
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+/// Longest function body sent to the model for an explanation
+const MAX_EXPLAIN_LINES: usize = 120;
+
+static RUST_FN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^\s*(pub(\([^)]*\))?\s+)?((const|async|unsafe|extern\s+"[^"]*")\s+)*fn\s+([A-Za-z_][A-Za-z0-9_]*)"#).unwrap()
+});
+static PYTHON_DEF: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s*(async\s+)?def\s+([A-Za-z_][A-Za-z0-9_]*)").unwrap());
+static GO_FUNC: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^func\s+(\([^)]*\)\s*)?([A-Za-z_][A-Za-z0-9_]*)").unwrap());
+static JS_FUNCTION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\s*(export\s+)?(default\s+)?(async\s+)?function\s*\*?\s*([A-Za-z_$][A-Za-z0-9_$]*)").unwrap()
+});
+static WORD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[A-Za-z_$][A-Za-z0-9_$]*").unwrap());
+
+/// A function definition
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    /// 0-based line of the definition
+    pub line: usize,
+    pub is_test: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LensAction {
+    RunTest,
+    Explain,
+    References,
+}
+
+/// Definition pattern and the capture group holding the name, by extension
+fn definition_pattern(language: &str) -> Option<(&'static Regex, usize)> {
+    match language {
+        "rs" => Some((&RUST_FN, 5)),
+        "py" | "pyi" => Some((&PYTHON_DEF, 2)),
+        "go" => Some((&GO_FUNC, 2)),
+        "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" => Some((&JS_FUNCTION, 4)),
+        _ => None,
+    }
+}
+
+/// Whether code lenses are offered for files with this extension
+pub fn supports(language: Option<&str>) -> bool {
+    language.and_then(definition_pattern).is_some()
+}
+
+/// Function definitions on `lines[range]`; `lines` before the range are read
+/// for test attributes
+pub fn find_symbols<S: AsRef<str>>(language: &str, lines: &[S], range: std::ops::Range<usize>) -> Vec<Symbol> {
+    let Some((pattern, group)) = definition_pattern(language) else { return Vec::new() };
+    let end = range.end.min(lines.len());
+    (range.start.min(end)..end)
+        .filter_map(|line| {
+            let name = pattern.captures(lines[line].as_ref())?.get(group)?.as_str().to_string();
+            let is_test = match language {
+                "rs" => has_test_attribute(lines, line),
+                "py" | "pyi" => name.starts_with("test"),
+                "go" => name.starts_with("Test"),
+                _ => false,
+            };
+            Some(Symbol { name, line, is_test })
+        })
+        .collect()
+}
+
+/// `#[test]`, `#[tokio::test]` and the like among the attributes above `line`
+/// (`#[cfg(test)]` helpers are not tests)
+fn has_test_attribute<S: AsRef<str>>(lines: &[S], line: usize) -> bool {
+    lines[..line].iter().rev()
+        .map(|l| l.as_ref().trim())
+        .take_while(|l| l.starts_with("#[") || l.starts_with("///"))
+        .any(|l| l.starts_with("#[test") || l.contains("::test"))
+}
+
+/// The actions offered on `symbol`, with their labels; references show a
+/// count once it is known
+pub fn lenses(symbol: &Symbol, references: Option<usize>) -> Vec<(LensAction, String)> {
+    let mut lenses = Vec::new();
+    if symbol.is_test {
+        lenses.push((LensAction::RunTest, "▶ Run test".to_string()));
+    }
+    lenses.push((LensAction::Explain, "Explain".to_string()));
+    let label = match references {
+        Some(1) => "1 reference".to_string(),
+        Some(n) => format!("{} references", n),
+        None => "References".to_string(),
+    };
+    lenses.push((LensAction::References, label));
+    lenses
+}
+
+/// Shell command running the test `name` defined in `path` (relative to the workspace)
+pub fn test_command(language: &str, path: &Path, name: &str) -> Option<String> {
+    match language {
+        "rs" => Some(format!("cargo test {}", name)),
+        "py" | "pyi" => Some(format!("python -m pytest {} -k {}", path.display(), name)),
+        "go" => {
+            let dir = path.parent().map(|p| p.display().to_string()).filter(|d| !d.is_empty()).unwrap_or_else(|| ".".to_string());
+            Some(format!("go test -run '^{}$' ./{}", name, dir.trim_start_matches("./")))
+        }
+        _ => None,
+    }
+}
+
+/// Lines of the function defined at `line`: to its closing brace, or for
+/// Python to the end of the indented block (at most `MAX_EXPLAIN_LINES`)
+pub fn symbol_body<S: AsRef<str>>(language: &str, lines: &[S], line: usize) -> Vec<String> {
+    let body = lines.iter().skip(line).take(MAX_EXPLAIN_LINES).map(|l| l.as_ref());
+    let mut taken = Vec::new();
+    if matches!(language, "py" | "pyi") {
+        let indent = |l: &str| l.len() - l.trim_start().len();
+        let start = lines.get(line).map_or(0, |l| indent(l.as_ref()));
+        for (i, text) in body.enumerate() {
+            if i > 0 && !text.trim().is_empty() && indent(text) <= start {
+                break;
+            }
+            taken.push(text.to_string());
+        }
+    } else {
+        let mut depth = 0i32;
+        let mut opened = false;
+        for text in body {
+            taken.push(text.to_string());
+            for c in text.chars() {
+                match c {
+                    '{' => { depth += 1; opened = true; }
+                    '}' => depth -= 1,
+                    _ => {}
+                }
+            }
+            if opened && depth <= 0 {
+                break;
+            }
+        }
+    }
+    while taken.last().is_some_and(|l| l.trim().is_empty()) {
+        taken.pop();
+    }
+    taken
+}
+
+/// Prompt asking the model to explain `symbol`
+pub fn explain_prompt(path: &Path, language: &str, symbol: &Symbol, body: &[String]) -> String {
+    format!(
+        "Explain what `{}` in {} does, how it works and anything surprising about it.\n\n```{}\n{}\n```",
+        symbol.name, path.display(), language, body.join("\n")
+    )
+}
+
+/// How often each of `names` appears as a whole word across the workspace,
+/// not counting its definition. Reads every file, so call it off the UI thread.
+pub fn count_references(root: &Path, names: &HashSet<String>) -> HashMap<String, usize> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for entry in workspace::walk_files(root) {
+        if entry.metadata().map(|m| m.len() > MAX_FILE_SIZE).unwrap_or(true) {
+            continue;
+        }
+        let Ok(content) = fs::read_to_string(entry.path()) else { continue };
+        for word in WORD.find_iter(&content) {
+            if let Some(name) = names.get(word.as_str()) {
+                *counts.entry(name.clone()).or_default() += 1;
+            }
+        }
+    }
+    for count in counts.values_mut() {
+        *count = count.saturating_sub(1);
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_symbols() {
+        let lines = [
+            "pub(crate) async fn load() {",
+            "}",
+            "#[cfg(test)]",
+            "mod tests {",
+            "    #[test]",
+            "    /// Round trip",
+            "    fn test_load() {",
+            "        load();",
+            "    }",
+            "}",
+        ];
+        let symbols = find_symbols("rs", &lines, 0..lines.len());
+        assert_eq!(symbols, vec![
+            Symbol { name: "load".to_string(), line: 0, is_test: false },
+            Symbol { name: "test_load".to_string(), line: 6, is_test: true },
+        ]);
+        assert_eq!(symbol_body("rs", &lines, 6), vec!["    fn test_load() {", "        load();", "    }"]);
+        assert_eq!(find_symbols("rs", &lines, 5..7).len(), 1);
+
+        let python = ["class T:", "    def test_it(self):", "        pass", "", "    def other(self):"];
+        let symbols = find_symbols("py", &python, 0..python.len());
+        assert_eq!(symbols.iter().map(|s| s.is_test).collect::<Vec<_>>(), vec![true, false]);
+        assert_eq!(symbol_body("py", &python, 1), vec!["    def test_it(self):", "        pass"]);
+
+        assert_eq!(find_symbols("go", &["func (s *S) TestX(t *testing.T) {"], 0..1)[0].name, "TestX");
+        assert_eq!(test_command("go", Path::new("pkg/a_test.go"), "TestX").unwrap(), "go test -run '^TestX$' ./pkg");
+        assert!(find_symbols("md", &lines, 0..lines.len()).is_empty());
+    }
+
+    #[test]
+    fn test_count_references() {
+        let root = std::env::temp_dir().join(format!("nterm-code-lens-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("a.rs"), "fn load() {}\nfn main() { load(); load_all(); }").unwrap();
+        let names = HashSet::from(["load".to_string(), "main".to_string()]);
+        let counts = count_references(&root, &names);
+        assert_eq!(counts.get("load"), Some(&1));
+        assert_eq!(counts.get("main"), Some(&0));
+        let _ = fs::remove_dir_all(&root);
+    }
+}
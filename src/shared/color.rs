@@ -0,0 +1,182 @@
+// Color literals (`#1e1e2e`, `rgb(30, 30, 46)`) in stylesheets and config
+// files: found for the editor's swatches and rewritten by the color picker
+
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+/// Language keys whose files get swatches and the picker
+const COLOR_FILES: [&str; 11] = ["css", "scss", "sass", "less", "json", "toml", "yaml", "yml", "ini", "conf", "svg"];
+
+/// How a literal was written, so an edited color keeps the same notation
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColorFormat {
+    /// `#rgb`, `#rrggbb`, optionally with an alpha digit pair (kept as written)
+    Hex { short: bool, uppercase: bool, alpha: Option<String> },
+    /// `rgb(r, g, b)` or `rgba(r, g, b, a)` (alpha kept as written)
+    Rgb { alpha: Option<String> },
+}
+
+/// A color literal on a line; `start..end` are char columns
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorLiteral {
+    pub start: usize,
+    pub end: usize,
+    pub rgb: [u8; 3],
+    pub format: ColorFormat,
+}
+
+impl ColorLiteral {
+    /// The literal for `rgb` in this literal's notation
+    pub fn with_rgb(&self, rgb: [u8; 3]) -> String {
+        let [r, g, b] = rgb;
+        match &self.format {
+            ColorFormat::Hex { short, uppercase, alpha } => {
+                // Short form only while every channel still fits in one digit
+                let fits_short = rgb.iter().all(|c| c >> 4 == c & 0xf);
+                let digits = if *short && fits_short {
+                    format!("{:x}{:x}{:x}", r & 0xf, g & 0xf, b & 0xf)
+                } else {
+                    format!("{:02x}{:02x}{:02x}", r, g, b)
+                };
+                let alpha = alpha.as_deref().map(|a| if *short && !fits_short { format!("{0}{0}", a) } else { a.to_string() });
+                let hex = format!("#{}{}", digits, alpha.unwrap_or_default());
+                if *uppercase { hex.to_uppercase() } else { hex }
+            }
+            ColorFormat::Rgb { alpha: Some(alpha) } => format!("rgba({}, {}, {}, {})", r, g, b, alpha),
+            ColorFormat::Rgb { alpha: None } => format!("rgb({}, {}, {})", r, g, b),
+        }
+    }
+}
+
+/// Whether files with this language key get swatches
+pub fn supports(key: Option<&str>) -> bool {
+    key.is_some_and(|key| COLOR_FILES.contains(&key))
+}
+
+fn patterns() -> &'static (Regex, Regex) {
+    static PATTERNS: OnceLock<(Regex, Regex)> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        (
+            Regex::new(r"#([0-9a-fA-F]{3,8})\b").expect("valid hex pattern"),
+            Regex::new(r"rgba?\(\s*(\d{1,3})\s*,\s*(\d{1,3})\s*,\s*(\d{1,3})\s*(?:,\s*([0-9.]+%?)\s*)?\)")
+                .expect("valid rgb pattern"),
+        )
+    })
+}
+
+/// Color literals on `line`, left to right
+pub fn find_colors(line: &str) -> Vec<ColorLiteral> {
+    let (hex, rgb) = patterns();
+    let col = |byte: usize| line[..byte].chars().count();
+    let mut found = Vec::new();
+
+    for caps in hex.captures_iter(line) {
+        let whole = caps.get(0).expect("match");
+        // `a#fff` is an anchor or an id, not a color
+        if line[..whole.start()].chars().next_back().is_some_and(|c| c.is_alphanumeric() || c == '_') {
+            continue;
+        }
+        let digits = &caps[1];
+        let (color, alpha) = match digits.len() {
+            3 | 4 => digits.split_at(3),
+            6 | 8 => digits.split_at(6),
+            _ => continue,
+        };
+        let channels: Vec<u8> = if color.len() == 3 {
+            color.chars().map(|c| u8::from_str_radix(&c.to_string().repeat(2), 16).unwrap_or(0)).collect()
+        } else {
+            (0..3).map(|i| u8::from_str_radix(&color[i * 2..i * 2 + 2], 16).unwrap_or(0)).collect()
+        };
+        found.push(ColorLiteral {
+            start: col(whole.start()),
+            end: col(whole.end()),
+            rgb: [channels[0], channels[1], channels[2]],
+            format: ColorFormat::Hex {
+                short: color.len() == 3,
+                uppercase: digits.chars().any(|c| c.is_ascii_uppercase()),
+                alpha: (!alpha.is_empty()).then(|| alpha.to_string()),
+            },
+        });
+    }
+
+    for caps in rgb.captures_iter(line) {
+        let channels: Vec<u8> = (1..=3).filter_map(|i| caps[i].parse().ok()).collect();
+        if channels.len() < 3 {
+            continue; // a channel above 255
+        }
+        let whole = caps.get(0).expect("match");
+        found.push(ColorLiteral {
+            start: col(whole.start()),
+            end: col(whole.end()),
+            rgb: [channels[0], channels[1], channels[2]],
+            format: ColorFormat::Rgb { alpha: caps.get(4).map(|a| a.as_str().to_string()) },
+        });
+    }
+
+    found.sort_by_key(|c| c.start);
+    found
+}
+
+/// The color picker: one literal being edited channel by channel
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorPicker {
+    pub row: usize,
+    pub literal: ColorLiteral,
+    pub rgb: [u8; 3],
+    /// Selected channel: 0 red, 1 green, 2 blue
+    pub channel: usize,
+}
+
+impl ColorPicker {
+    pub fn new(row: usize, literal: ColorLiteral) -> Self {
+        Self { row, rgb: literal.rgb, literal, channel: 0 }
+    }
+
+    pub fn select(&mut self, delta: isize) {
+        self.channel = (self.channel as isize + delta).rem_euclid(3) as usize;
+    }
+
+    /// Change the selected channel, clamped to 0..=255
+    pub fn adjust(&mut self, delta: i16) {
+        let value = &mut self.rgb[self.channel];
+        *value = (*value as i16 + delta).clamp(0, 255) as u8;
+    }
+
+    /// The edited literal as it will be written back
+    pub fn literal_text(&self) -> String {
+        self.literal.with_rgb(self.rgb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_colors() {
+        let colors = find_colors("a { color: #1E1E2E; border: 1px solid #fff8; background: rgba(10, 20, 30, 0.5) } a#top");
+        assert_eq!(colors.len(), 3);
+        assert_eq!(colors[0].rgb, [0x1e, 0x1e, 0x2e]);
+        assert_eq!((colors[0].start, colors[0].end), (11, 18));
+        assert_eq!(colors[1].rgb, [255, 255, 255]);
+        assert_eq!(colors[2].rgb, [10, 20, 30]);
+        assert!(find_colors("issue #12345 and rgb(300, 0, 0)").is_empty());
+        assert!(supports(Some("css")) && !supports(Some("rs")));
+    }
+
+    #[test]
+    fn test_picker_keeps_notation() {
+        let colors = find_colors("#1E1E2E #fff8 rgba(10, 20, 30, 0.5) #abc");
+        let mut picker = ColorPicker::new(0, colors[0].clone());
+        picker.adjust(300);
+        assert_eq!(picker.literal_text(), "#FF1E2E");
+        picker.select(-1);
+        picker.adjust(-1);
+        assert_eq!(picker.literal_text(), "#FF1E2D");
+        assert_eq!(colors[1].with_rgb([0x11, 0x22, 0x33]), "#1238");
+        assert_eq!(colors[1].with_rgb([0x12, 0x22, 0x33]), "#12223388");
+        assert_eq!(colors[2].with_rgb([1, 2, 3]), "rgba(1, 2, 3, 0.5)");
+        assert_eq!(colors[3].with_rgb([0xaa, 0xbb, 0xcc]), "#abc");
+    }
+}
@@ -1,11 +1,18 @@
 // Configuration management
 
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use super::ai::{default_models, ModelConfig, Provider};
+use super::ai::{default_models, default_presets, ContextPreset, ModelConfig, Provider};
+use super::capabilities::TerminalOverrides;
+use super::diff;
+use super::highlight::HighlightBackend;
+use super::spell;
+use super::syntax;
 use super::theme::ThemeMode;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,6 +20,15 @@ pub struct RecentWorkspace {
     pub path: PathBuf,
     pub name: String,
     pub last_accessed: u64,
+    /// Files opened in this workspace, relative to it, most recent first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recent_files: Vec<PathBuf>,
+    /// Directories hidden from the tree and skipped by search and scans, relative to it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub excluded_dirs: Vec<PathBuf>,
+    /// Whether the project setup suggestions were shown on opening it
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub project_setup_shown: bool,
 }
 
 impl RecentWorkspace {
@@ -29,10 +45,186 @@ impl RecentWorkspace {
             path,
             name,
             last_accessed,
+            recent_files: Vec::new(),
+            excluded_dirs: Vec::new(),
+            project_setup_shown: false,
         }
     }
 }
 
+/// How keys in the editor are interpreted
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum KeybindingMode {
+    /// Keys insert text directly
+    #[default]
+    Default,
+    /// Modal editing (normal/insert/visual)
+    Vim,
+    /// Ctrl/Alt chords for line and word motion
+    Emacs,
+}
+
+/// Indentation used by the editor for a language
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct IndentConfig {
+    /// Columns per indent level
+    #[serde(default = "default_indent_width")]
+    pub width: usize,
+    /// Indent with a tab instead of spaces
+    #[serde(default)]
+    pub tabs: bool,
+}
+
+fn default_indent_width() -> usize {
+    4
+}
+
+fn default_detect_indent() -> bool {
+    true
+}
+
+fn default_preview_threshold_kb() -> u64 {
+    512
+}
+
+fn default_mirror_address() -> String {
+    "127.0.0.1:7370".to_string()
+}
+
+fn default_minimap() -> bool {
+    true
+}
+
+fn default_session_backups() -> usize {
+    10
+}
+
+fn default_code_lens() -> bool {
+    true
+}
+
+fn default_indent_guides() -> bool {
+    true
+}
+
+fn default_spell_language() -> String {
+    spell::DEFAULT_LANGUAGE.to_string()
+}
+
+impl Default for IndentConfig {
+    fn default() -> Self {
+        Self { width: default_indent_width(), tabs: false }
+    }
+}
+
+impl IndentConfig {
+    /// Text inserted for one indent level
+    pub fn unit(&self) -> String {
+        if self.tabs { "\t".to_string() } else { " ".repeat(self.width.max(1)) }
+    }
+
+    /// Indentation used by existing `lines`: tabs if most indented lines start with
+    /// one, else the most common step between a line and a deeper indented one.
+    /// `None` when there are too few indented lines to tell.
+    pub fn detect<S: AsRef<str>>(lines: &[S]) -> Option<IndentConfig> {
+        let (mut tab_lines, mut space_lines) = (0, 0);
+        let mut steps = [0usize; 9];
+        let mut previous = 0;
+        for line in lines.iter().map(AsRef::as_ref) {
+            let trimmed = line.trim_start();
+            // Blank lines, and ` * ` continuations of block comments, say nothing
+            if trimmed.is_empty() || trimmed.starts_with('*') {
+                continue;
+            }
+            if line.starts_with('\t') {
+                tab_lines += 1;
+                continue;
+            }
+            let spaces = line.len() - line.trim_start_matches(' ').len();
+            if spaces > 0 {
+                space_lines += 1;
+            }
+            if spaces > previous && spaces - previous < steps.len() {
+                steps[spaces - previous] += 1;
+            }
+            previous = spaces;
+        }
+        if tab_lines + space_lines < MIN_INDENTED_LINES {
+            return None;
+        }
+        if tab_lines > space_lines {
+            return Some(IndentConfig { width: default_indent_width(), tabs: true });
+        }
+        // Most common step; ties go to the wider one (2 vs 4 in nested blocks)
+        let (width, count) = steps.iter().enumerate().skip(1).max_by_key(|&(width, count)| (*count, width))?;
+        (*count > 0).then_some(IndentConfig { width, tabs: false })
+    }
+}
+
+/// Indented lines needed before `IndentConfig::detect` trusts what it sees
+const MIN_INDENTED_LINES: usize = 2;
+
+/// Whether new output moves a panel's view to it
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoScroll {
+    /// Always jump to the newest content
+    Always,
+    /// Follow new content while the view is at the bottom; stay put when scrolled back
+    #[default]
+    AtBottom,
+    /// Leave the view where it is
+    Never,
+}
+
+/// Auto-scroll behavior per panel, e.g. `"auto_scroll": {"terminal": "never", "chat": "always"}`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct AutoScrollConfig {
+    #[serde(default)]
+    pub terminal: AutoScroll,
+    #[serde(default)]
+    pub chat: AutoScroll,
+}
+
+/// Limits for commands the AI runs through the `run_command` tool
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandSandbox {
+    /// Offer the tool to the model; commands then run without asking first
+    #[serde(default)]
+    pub enabled: bool,
+    /// Let commands use the network (otherwise they get an empty network namespace)
+    #[serde(default)]
+    pub allow_network: bool,
+    /// Commands still running after this many seconds are killed
+    #[serde(default = "default_command_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_command_timeout() -> u64 {
+    30
+}
+
+impl Default for CommandSandbox {
+    fn default() -> Self {
+        Self { enabled: false, allow_network: false, timeout_secs: default_command_timeout() }
+    }
+}
+
+/// Error found while parsing a config file (line and column are 1-based)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Config {
     #[serde(default)]
@@ -41,8 +233,109 @@ pub struct Config {
     pub models: Vec<ModelConfig>,
     #[serde(default)]
     pub selected_model_idx: usize,
+    /// Chat setups (system prompt, temperature, model, attached context) to switch between
+    #[serde(default = "default_presets")]
+    pub context_presets: Vec<ContextPreset>,
+    /// Name of the context preset in use
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_preset: Option<String>,
     #[serde(default)]
     pub recent_workspaces: Vec<RecentWorkspace>,
+    /// Key binding overrides, e.g. `"ctrl+s": "save"`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub keybindings: BTreeMap<String, String>,
+    /// Keep scratch buffers across sessions
+    #[serde(default)]
+    pub persist_scratch: bool,
+    /// Editor input style: "default", "vim" or "emacs"
+    #[serde(default)]
+    pub keybinding_mode: KeybindingMode,
+    /// Editor highlighting theme: a bundled syntect theme (e.g. "InspiredGitHub",
+    /// "Solarized (light)") or the file name of a `.tmTheme` in `user_dir()/themes`;
+    /// by default it follows `theme`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub syntax_theme: Option<String>,
+    /// Syntax for extensions or dotfile names syntect doesn't know, e.g. `"vue": "html"`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub syntax_overrides: BTreeMap<String, String>,
+    /// Editor highlighting: "syntect" or "tree-sitter", which re-parses only
+    /// what an edit touched but needs a build with `--features tree-sitter`
+    #[serde(default)]
+    pub highlighter: HighlightBackend,
+    /// Indentation by extension or dotfile name, e.g. `"go": {"width": 8, "tabs": true}`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub indent: BTreeMap<String, IndentConfig>,
+    /// Follow the indentation a file already uses over the `indent` setting
+    #[serde(default = "default_detect_indent")]
+    pub detect_indent: bool,
+    /// Command re-run in the watch tab after every save, e.g. `cargo check`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watch_command: Option<String>,
+    /// Linter run after saving a file, by extension or dotfile name, e.g.
+    /// `"sh": "shellcheck -f gcc {file}"`; `{file}` is the saved file
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub linters: BTreeMap<String, String>,
+    /// Language server by extension or dotfile name, e.g. `"rs": "rust-analyzer"`;
+    /// started for the workspace when a file of that type is opened
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub language_servers: BTreeMap<String, String>,
+    /// Formatter by extension or dotfile name, used when no language server
+    /// formats the file, e.g. `"py": "black -q -"`; it reads the buffer on
+    /// stdin and prints the formatted text, `{file}` is the file's path
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub formatters: BTreeMap<String, String>,
+    /// Inlay hints from the language server by extension or dotfile name;
+    /// shown unless turned off, e.g. `"py": false`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub inlay_hints: BTreeMap<String, bool>,
+    /// How compared texts are matched up, in the compare view, the git gutter
+    /// and AI reviews: "myers" (the default) or "patience"
+    #[serde(default)]
+    pub diff_algorithm: diff::Algorithm,
+    /// Files larger than this open as a read-only preview that loads as you scroll
+    #[serde(default = "default_preview_threshold_kb")]
+    pub preview_threshold_kb: u64,
+    /// Where the read-only session mirror listens; keep it on localhost and
+    /// reach it through `ssh -L` rather than exposing it
+    #[serde(default = "default_mirror_address")]
+    pub mirror_address: String,
+    /// Underline misspellings in chat input, markdown files and code comments
+    #[serde(default)]
+    pub spellcheck: bool,
+    /// Hunspell dictionary name, e.g. "en_US" or "de_DE"
+    #[serde(default = "default_spell_language")]
+    pub spell_language: String,
+    /// Save modified buffers after this much idle time (and when leaving the editor)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub autosave_delay_ms: Option<u64>,
+    /// Show when each chat message was sent
+    #[serde(default)]
+    pub chat_timestamps: bool,
+    /// Strings that are known not to be secrets (e.g. test keys); chat messages
+    /// with other likely secrets need confirmation before they are sent
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub secret_allowlist: Vec<String>,
+    /// Whether and how the AI may run shell commands in the workspace
+    #[serde(default)]
+    pub ai_commands: CommandSandbox,
+    /// What the terminal supports, where detection at startup gets it wrong
+    #[serde(default, skip_serializing_if = "TerminalOverrides::is_empty")]
+    pub terminal: TerminalOverrides,
+    /// When new terminal output and chat messages scroll their panels
+    #[serde(default)]
+    pub auto_scroll: AutoScrollConfig,
+    /// Show the buffer overview beside the GUI editor
+    #[serde(default = "default_minimap")]
+    pub minimap: bool,
+    /// Run test / explain / references actions on function definitions
+    #[serde(default = "default_code_lens")]
+    pub code_lens: bool,
+    /// Faint vertical lines at each indentation level in the editor
+    #[serde(default = "default_indent_guides")]
+    pub indent_guides: bool,
+    /// Restore points of the config and chat database kept in `data_dir()/backups`; 0 turns them off
+    #[serde(default = "default_session_backups")]
+    pub session_backups: usize,
     // Legacy field for backward compatibility
     #[serde(skip_serializing, default)]
     pub gemini_api_key: Option<String>,
@@ -54,7 +347,37 @@ impl Default for Config {
             theme: ThemeMode::default(),
             models: default_models(),
             selected_model_idx: 0,
+            context_presets: default_presets(),
+            active_preset: None,
             recent_workspaces: Vec::new(),
+            keybindings: BTreeMap::new(),
+            persist_scratch: false,
+            keybinding_mode: KeybindingMode::Default,
+            syntax_theme: None,
+            syntax_overrides: BTreeMap::new(),
+            highlighter: HighlightBackend::Syntect,
+            indent: BTreeMap::new(),
+            detect_indent: default_detect_indent(),
+            watch_command: None,
+            linters: BTreeMap::new(),
+            language_servers: BTreeMap::new(),
+            formatters: BTreeMap::new(),
+            inlay_hints: BTreeMap::new(),
+            diff_algorithm: diff::Algorithm::Myers,
+            preview_threshold_kb: default_preview_threshold_kb(),
+            mirror_address: default_mirror_address(),
+            spellcheck: false,
+            spell_language: default_spell_language(),
+            autosave_delay_ms: None,
+            chat_timestamps: false,
+            secret_allowlist: Vec::new(),
+            ai_commands: CommandSandbox::default(),
+            terminal: TerminalOverrides::default(),
+            auto_scroll: AutoScrollConfig::default(),
+            minimap: default_minimap(),
+            code_lens: default_code_lens(),
+            indent_guides: default_indent_guides(),
+            session_backups: default_session_backups(),
             gemini_api_key: None,
         }
     }
@@ -63,28 +386,39 @@ impl Default for Config {
 impl Config {
     pub fn load() -> Self {
         let config_path = Self::get_config_path();
-        if let Ok(content) = fs::read_to_string(&config_path) {
-            let mut config: Config = serde_json::from_str(&content).unwrap_or_default();
-
-            // Migrate legacy gemini_api_key to new model system
-            if let Some(key) = config.gemini_api_key.take() {
-                if let Some(gemini_model) = config.models.iter_mut()
-                    .find(|m| m.provider == Provider::Gemini) {
-                    if gemini_model.api_key.is_none() {
-                        gemini_model.api_key = Some(key);
-                    }
-                }
-            }
+        match fs::read_to_string(&config_path) {
+            Ok(content) => Self::from_json(&content).unwrap_or_default(),
+            Err(_) => Self::default(),
+        }
+    }
 
-            // Ensure we have at least the default models
-            if config.models.is_empty() {
-                config.models = default_models();
+    /// Parse config file content, reporting where it is invalid
+    pub fn from_json(content: &str) -> Result<Self, ConfigError> {
+        let mut config: Config = serde_json::from_str(content).map_err(|e| ConfigError {
+            line: e.line().max(1),
+            column: e.column().max(1),
+            message: e.to_string(),
+        })?;
+
+        // Migrate legacy gemini_api_key to new model system
+        if let Some(key) = config.gemini_api_key.take() {
+            if let Some(gemini_model) = config.models.iter_mut()
+                .find(|m| m.provider == Provider::Gemini) {
+                if gemini_model.api_key.is_none() {
+                    gemini_model.api_key = Some(key);
+                }
             }
+        }
 
-            config
-        } else {
-            Self::default()
+        // Ensure we have at least the default models
+        if config.models.is_empty() {
+            config.models = default_models();
         }
+        if config.selected_model_idx >= config.models.len() {
+            config.selected_model_idx = 0;
+        }
+
+        Ok(config)
     }
 
     pub fn save(&self) -> std::io::Result<()> {
@@ -93,16 +427,112 @@ impl Config {
         fs::write(config_path, content)
     }
 
-    fn get_config_path() -> PathBuf {
+    /// Whether `path` is nterm's own config file
+    pub fn is_config_file(path: &Path) -> bool {
+        let config_path = Self::get_config_path();
+        path == config_path
+            || fs::canonicalize(path).ok().is_some_and(|p| Some(p) == fs::canonicalize(&config_path).ok())
+    }
+
+    pub fn get_config_path() -> PathBuf {
         dirs::home_dir()
             .unwrap_or_else(|| PathBuf::from("."))
             .join(".nterm_config.json")
     }
 
+    /// Directory for user-provided files: `themes/*.tmTheme`, `syntaxes/*.sublime-syntax`
+    pub fn user_dir() -> PathBuf {
+        dirs::home_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join(".config")
+            .join("nterm")
+    }
+
+    /// Directory for nterm's runtime data (backups, history, ...)
+    pub fn data_dir() -> PathBuf {
+        dirs::data_dir()
+            .or_else(dirs::home_dir)
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("nterm")
+    }
+
+    /// Indentation for a file, from the `indent` entry for its extension or name
+    pub fn indent_for(&self, path: Option<&Path>) -> IndentConfig {
+        let Some(key) = path.and_then(syntax::language_key) else {
+            return IndentConfig::default();
+        };
+        self.indent.iter()
+            .find(|(k, _)| syntax::normalize_key(k) == key)
+            .map(|(_, indent)| *indent)
+            .unwrap_or_default()
+    }
+
+    /// Lint command configured for the file type of `path`
+    pub fn linter_for(&self, path: &Path) -> Option<&str> {
+        let key = syntax::language_key(path)?;
+        self.linters.iter()
+            .find(|(k, _)| syntax::normalize_key(k) == key)
+            .map(|(_, command)| command.as_str())
+    }
+
+    /// Language server command configured for the file type of `path`
+    pub fn language_server_for(&self, path: &Path) -> Option<&str> {
+        let key = syntax::language_key(path)?;
+        self.language_servers.iter()
+            .find(|(k, _)| syntax::normalize_key(k) == key)
+            .map(|(_, command)| command.as_str())
+    }
+
+    /// Formatter command configured for the file type of `path`
+    pub fn formatter_for(&self, path: &Path) -> Option<&str> {
+        let key = syntax::language_key(path)?;
+        self.formatters.iter()
+            .find(|(k, _)| syntax::normalize_key(k) == key)
+            .map(|(_, command)| command.as_str())
+    }
+
+    /// Whether inlay hints are shown in files of the type of `path`
+    pub fn inlay_hints_for(&self, path: &Path) -> bool {
+        let Some(key) = syntax::language_key(path) else { return true };
+        self.inlay_hints.iter()
+            .find(|(k, _)| syntax::normalize_key(k) == key)
+            .is_none_or(|(_, &shown)| shown)
+    }
+
+    /// Show or hide inlay hints in files of the type of `path`; returns whether
+    /// they are shown now
+    pub fn toggle_inlay_hints(&mut self, path: &Path) -> Option<bool> {
+        let key = syntax::language_key(path)?;
+        let shown = !self.inlay_hints_for(path);
+        self.inlay_hints.retain(|k, _| syntax::normalize_key(k) != key);
+        // Shown is the default, so only hidden types are kept
+        if !shown {
+            self.inlay_hints.insert(key, false);
+        }
+        Some(shown)
+    }
+
+    /// Indentation for a file with these contents: what the file already uses
+    /// (see `detect_indent`), else `indent_for`. A detected tab keeps the configured width.
+    pub fn indent_for_contents<S: AsRef<str>>(&self, path: Option<&Path>, lines: &[S]) -> IndentConfig {
+        let configured = self.indent_for(path);
+        match IndentConfig::detect(lines).filter(|_| self.detect_indent) {
+            Some(detected) if detected.tabs => IndentConfig { tabs: true, ..configured },
+            Some(detected) => detected,
+            None => configured,
+        }
+    }
+
     pub fn get_selected_model(&self) -> &ModelConfig {
         self.models.get(self.selected_model_idx).unwrap_or(&self.models[0])
     }
 
+    /// The context preset in use, if it still exists
+    pub fn active_preset(&self) -> Option<&ContextPreset> {
+        let name = self.active_preset.as_deref()?;
+        self.context_presets.iter().find(|p| p.name == name)
+    }
+
     pub fn get_selected_model_mut(&mut self) -> &mut ModelConfig {
         let idx = self.selected_model_idx.min(self.models.len().saturating_sub(1));
         &mut self.models[idx]
@@ -117,11 +547,14 @@ impl Config {
     pub fn add_recent_workspace(&mut self, path: PathBuf) {
         const MAX_RECENT: usize = 10;
 
-        // Remove existing entry for this path if present
+        // Remove existing entry for this path if present, keeping what was remembered about it
+        let (recent_files, excluded_dirs, project_setup_shown) = self.recent_workspace(&path)
+            .map(|w| (w.recent_files.clone(), w.excluded_dirs.clone(), w.project_setup_shown))
+            .unwrap_or_default();
         self.recent_workspaces.retain(|w| w.path != path);
 
         // Add new entry at the front
-        self.recent_workspaces.insert(0, RecentWorkspace::new(path));
+        self.recent_workspaces.insert(0, RecentWorkspace { recent_files, excluded_dirs, project_setup_shown, ..RecentWorkspace::new(path) });
 
         // Keep only MAX_RECENT entries
         self.recent_workspaces.truncate(MAX_RECENT);
@@ -130,4 +563,137 @@ impl Config {
     pub fn get_recent_workspaces(&self) -> &[RecentWorkspace] {
         &self.recent_workspaces
     }
+
+    /// The recent workspace entry for `workspace`, however the path was spelled
+    pub fn recent_workspace(&self, workspace: &Path) -> Option<&RecentWorkspace> {
+        let target = workspace.canonicalize().unwrap_or_else(|_| workspace.to_path_buf());
+        self.recent_workspaces.iter()
+            .find(|w| w.path == workspace || w.path.canonicalize().is_ok_and(|p| p == target))
+    }
+
+    /// Remember `file` (relative to `workspace`) as the most recently opened one there
+    pub fn add_recent_file(&mut self, workspace: &Path, file: PathBuf) {
+        const MAX_RECENT_FILES: usize = 20;
+
+        let Some(path) = self.recent_workspace(workspace).map(|w| w.path.clone()) else { return };
+        let Some(entry) = self.recent_workspaces.iter_mut().find(|w| w.path == path) else { return };
+        entry.recent_files.retain(|f| *f != file);
+        entry.recent_files.insert(0, file);
+        entry.recent_files.truncate(MAX_RECENT_FILES);
+    }
+
+    /// Directories excluded from `workspace`, relative to it
+    pub fn excluded_dirs(&self, workspace: &Path) -> Vec<PathBuf> {
+        self.recent_workspace(workspace).map(|w| w.excluded_dirs.clone()).unwrap_or_default()
+    }
+
+    /// Exclude `dir` (relative to `workspace`) or include it again; whether it
+    /// is now excluded, or `None` if the workspace isn't a recent one
+    pub fn toggle_excluded_dir(&mut self, workspace: &Path, dir: PathBuf) -> Option<bool> {
+        let path = self.recent_workspace(workspace).map(|w| w.path.clone())?;
+        let entry = self.recent_workspaces.iter_mut().find(|w| w.path == path)?;
+        let excluded = !entry.excluded_dirs.contains(&dir);
+        if excluded {
+            entry.excluded_dirs.push(dir);
+            entry.excluded_dirs.sort();
+        } else {
+            entry.excluded_dirs.retain(|d| *d != dir);
+        }
+        Some(excluded)
+    }
+
+    /// Mark the project setup suggestions as shown for `workspace`; whether
+    /// they hadn't been yet. `false` if the workspace isn't a recent one.
+    pub fn take_project_setup(&mut self, workspace: &Path) -> bool {
+        let Some(path) = self.recent_workspace(workspace).map(|w| w.path.clone()) else { return false };
+        let Some(entry) = self.recent_workspaces.iter_mut().find(|w| w.path == path) else { return false };
+        !std::mem::replace(&mut entry.project_setup_shown, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_json_reports_location() {
+        let err = Config::from_json("{\n  \"theme\": \"Dark\",\n  \"selected_model_idx\": \"x\"\n}").err().unwrap();
+        assert_eq!(err.line, 3);
+
+        let config = Config::from_json("{\"theme\": \"Light\", \"keybindings\": {\"ctrl+s\": \"save\"}}").unwrap();
+        assert_eq!(config.theme, ThemeMode::Light);
+        assert_eq!(config.keybindings.get("ctrl+s").map(String::as_str), Some("save"));
+        assert!(!config.models.is_empty());
+        assert_eq!(config.auto_scroll.chat, AutoScroll::AtBottom);
+
+        let config = Config::from_json("{\"auto_scroll\": {\"terminal\": \"never\"}}").unwrap();
+        assert_eq!(config.auto_scroll, AutoScrollConfig { terminal: AutoScroll::Never, chat: AutoScroll::AtBottom });
+    }
+
+    #[test]
+    fn test_indent_for() {
+        let config = Config::from_json("{\"indent\": {\".go\": {\"tabs\": true}, \"py\": {\"width\": 2}}}").unwrap();
+        assert_eq!(config.indent_for(Some(Path::new("main.go"))).unit(), "\t");
+        assert_eq!(config.indent_for(Some(Path::new("a/b.PY"))).unit(), "  ");
+        assert_eq!(config.indent_for(Some(Path::new("lib.rs"))), IndentConfig::default());
+        assert_eq!(config.indent_for(None).width, 4);
+
+        let config = Config::from_json("{\"linters\": {\".sh\": \"shellcheck -f gcc {file}\"}}").unwrap();
+        assert_eq!(config.linter_for(Path::new("bin/deploy.sh")), Some("shellcheck -f gcc {file}"));
+        assert_eq!(config.linter_for(Path::new("main.rs")), None);
+
+        let config = Config::from_json("{\"language_servers\": {\"rs\": \"rust-analyzer\"}}").unwrap();
+        assert_eq!(config.language_server_for(Path::new("src/main.rs")), Some("rust-analyzer"));
+        assert_eq!(config.language_server_for(Path::new("setup.py")), None);
+
+        let config = Config::from_json("{\"formatters\": {\"py\": \"black -q -\"}}").unwrap();
+        assert_eq!(config.formatter_for(Path::new("setup.py")), Some("black -q -"));
+
+        let mut config = Config::from_json("{\"inlay_hints\": {\".py\": false}}").unwrap();
+        assert!(!config.inlay_hints_for(Path::new("setup.py")) && config.inlay_hints_for(Path::new("main.rs")));
+        assert_eq!(config.toggle_inlay_hints(Path::new("setup.py")), Some(true));
+        assert_eq!(config.toggle_inlay_hints(Path::new("main.rs")), Some(false));
+        assert_eq!(config.inlay_hints.into_iter().collect::<Vec<_>>(), vec![("rs".to_string(), false)]);
+    }
+
+    #[test]
+    fn test_recent_files() {
+        let mut config = Config::from_json("{}").unwrap();
+        let root = std::env::temp_dir();
+        config.add_recent_workspace(root.clone());
+        config.add_recent_file(&root, PathBuf::from("a.rs"));
+        config.add_recent_file(&root, PathBuf::from("b.rs"));
+        config.add_recent_file(&root, PathBuf::from("a.rs"));
+        config.add_recent_workspace(root.clone());
+        let files = &config.recent_workspace(&root).unwrap().recent_files;
+        assert_eq!(files, &[PathBuf::from("a.rs"), PathBuf::from("b.rs")]);
+        config.add_recent_file(Path::new("/not/recent"), PathBuf::from("c.rs"));
+        assert_eq!(config.recent_workspaces.len(), 1);
+
+        assert_eq!(config.toggle_excluded_dir(&root, PathBuf::from("dist")), Some(true));
+        config.add_recent_workspace(root.clone());
+        assert_eq!(config.excluded_dirs(&root), vec![PathBuf::from("dist")]);
+        assert_eq!(config.toggle_excluded_dir(&root, PathBuf::from("dist")), Some(false));
+        assert_eq!(config.toggle_excluded_dir(Path::new("/not/recent"), PathBuf::from("dist")), None);
+
+        assert!(config.take_project_setup(&root));
+        config.add_recent_workspace(root.clone());
+        assert!(!config.take_project_setup(&root));
+        assert!(!config.take_project_setup(Path::new("/not/recent")));
+    }
+
+    #[test]
+    fn test_detect_indent() {
+        let two = ["fn a() {", "  if x {", "    y();", "  }", "}"];
+        assert_eq!(IndentConfig::detect(&two), Some(IndentConfig { width: 2, tabs: false }));
+        let tabs = ["func a() {", "\tb()", "\tif c {", "\t\td()", "\t}", "}"];
+        assert!(IndentConfig::detect(&tabs).unwrap().tabs);
+        assert_eq!(IndentConfig::detect(&["a", "    b"]), None);
+
+        let config = Config::from_json("{\"indent\": {\"go\": {\"width\": 8}}}").unwrap();
+        let go = config.indent_for_contents(Some(Path::new("main.go")), &tabs);
+        assert_eq!(go, IndentConfig { width: 8, tabs: true });
+        let config = Config { detect_indent: false, ..config };
+        assert_eq!(config.indent_for_contents(Some(Path::new("a.rs")), &two).width, 4);
+    }
 }
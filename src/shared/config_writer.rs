@@ -0,0 +1,166 @@
+// Config file writes off the UI thread. Changes that come in quick succession
+// (theme toggles, model cycling, settings edits) are coalesced: the file is
+// written once things have been quiet for a moment, at most every couple of
+// seconds while they keep changing, and whatever is pending when the writer
+// is flushed or dropped. A pending change is dropped when it is cancelled,
+// e.g. because the file was just saved from the editor.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::config::Config;
+
+/// Quiet time after a change before it is written
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Longest a change waits while more keep coming in
+const MAX_DELAY: Duration = Duration::from_secs(2);
+
+/// How long `flush` and `cancel` wait for the writer
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(2);
+
+enum Request {
+    Write(String),
+    Flush(mpsc::Sender<()>),
+    Cancel(mpsc::Sender<()>),
+    /// A failure on the caller's side, reported through `on_error` like write failures
+    Error(String),
+}
+
+pub struct ConfigWriter {
+    tx: mpsc::Sender<Request>,
+}
+
+impl ConfigWriter {
+    /// Write to `path` on a background thread; failures are passed to `on_error`
+    pub fn start(path: PathBuf, on_error: impl Fn(String) + Send + 'static) -> Self {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let write = |content: &str| {
+                if let Err(e) = fs::write(&path, content) {
+                    on_error(format!("Could not save settings to {}: {}", path.display(), e));
+                }
+            };
+            while let Ok(request) = rx.recv() {
+                let mut pending = match request {
+                    Request::Write(content) => content,
+                    Request::Flush(done) | Request::Cancel(done) => {
+                        let _ = done.send(());
+                        continue;
+                    }
+                    Request::Error(message) => {
+                        on_error(message);
+                        continue;
+                    }
+                };
+                let deadline = Instant::now() + MAX_DELAY;
+                loop {
+                    let wait = DEBOUNCE.min(deadline.saturating_duration_since(Instant::now()));
+                    match rx.recv_timeout(wait) {
+                        Ok(Request::Write(content)) => pending = content,
+                        Ok(Request::Error(message)) => on_error(message),
+                        Ok(Request::Flush(done)) => {
+                            write(&pending);
+                            let _ = done.send(());
+                            break;
+                        }
+                        Ok(Request::Cancel(done)) => {
+                            let _ = done.send(());
+                            break;
+                        }
+                        Err(RecvTimeoutError::Timeout) => {
+                            write(&pending);
+                            break;
+                        }
+                        Err(RecvTimeoutError::Disconnected) => {
+                            write(&pending);
+                            return;
+                        }
+                    }
+                }
+            }
+        });
+        Self { tx }
+    }
+
+    /// Queue `config` to be written to nterm's config file
+    pub fn save(&self, config: &Config) {
+        let request = match serde_json::to_string_pretty(config) {
+            Ok(content) => Request::Write(content),
+            Err(e) => Request::Error(format!("Could not serialize settings: {}", e)),
+        };
+        let _ = self.tx.send(request);
+    }
+
+    /// Write any pending change now and wait for it to land
+    pub fn flush(&self) {
+        self.wait_for(Request::Flush);
+    }
+
+    /// Drop any pending change without writing it, for when the file was
+    /// just written by someone else. Returns once the writer is idle.
+    pub fn cancel(&self) {
+        self.wait_for(Request::Cancel);
+    }
+
+    fn wait_for(&self, request: fn(mpsc::Sender<()>) -> Request) {
+        let (done, finished) = mpsc::channel();
+        if self.tx.send(request(done)).is_ok() {
+            let _ = finished.recv_timeout(FLUSH_TIMEOUT);
+        }
+    }
+}
+
+impl Drop for ConfigWriter {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_writes_are_coalesced() {
+        let path = std::env::temp_dir().join(format!("nterm-config-writer-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let writer = ConfigWriter::start(path.clone(), |e| panic!("{}", e));
+        let mut config = Config::from_json("{}").unwrap();
+        for _ in 0..5 {
+            config.cycle_model();
+            writer.save(&config);
+        }
+        // Nothing is written while changes are still coming in
+        assert!(!path.exists());
+
+        writer.flush();
+        let written = Config::from_json(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(written.selected_model_idx, config.selected_model_idx);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_cancel_keeps_an_outside_write() {
+        let path = std::env::temp_dir().join(format!("nterm-config-writer-cancel-{}.json", std::process::id()));
+        let writer = ConfigWriter::start(path.clone(), |e| panic!("{}", e));
+        let mut config = Config::from_json("{}").unwrap();
+        config.cycle_model();
+        writer.save(&config);
+
+        // The file is saved from the editor while the change above is still pending
+        fs::write(&path, "{}").unwrap();
+        writer.cancel();
+        writer.flush();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{}");
+
+        // Later changes are written as usual
+        writer.save(&config);
+        writer.flush();
+        assert_ne!(fs::read_to_string(&path).unwrap(), "{}");
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,282 @@
+// Diagnostics (problems) collected from language servers, build output, linters and formatters
+
+use regex::Regex;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+
+/// Severity of a diagnostic, ordered from least to most severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Hint,
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    fn from_label(label: &str) -> Option<Self> {
+        match label {
+            "error" => Some(Severity::Error),
+            "warning" => Some(Severity::Warning),
+            "note" | "info" => Some(Severity::Info),
+            "help" | "hint" => Some(Severity::Hint),
+            _ => None,
+        }
+    }
+
+    /// Single-letter marker for list views
+    pub fn marker(&self) -> &'static str {
+        match self {
+            Severity::Error => "E",
+            Severity::Warning => "W",
+            Severity::Info => "I",
+            Severity::Hint => "H",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Severity::Error => "Errors",
+            Severity::Warning => "Warnings",
+            Severity::Info => "Info",
+            Severity::Hint => "Hints",
+        }
+    }
+}
+
+/// Where a diagnostic came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiagnosticSource {
+    Lsp,
+    Build,
+    Formatter,
+    Lint,
+    Config,
+}
+
+impl DiagnosticSource {
+    pub fn label(&self) -> &'static str {
+        match self {
+            DiagnosticSource::Lsp => "lsp",
+            DiagnosticSource::Build => "build",
+            DiagnosticSource::Formatter => "fmt",
+            DiagnosticSource::Lint => "lint",
+            DiagnosticSource::Config => "config",
+        }
+    }
+}
+
+/// A single problem at a file location (line and column are 1-based)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub path: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub severity: Severity,
+    pub message: String,
+    pub code: Option<String>,
+    pub source: DiagnosticSource,
+}
+
+impl Diagnostic {
+    /// `path:line:col` location string
+    pub fn location(&self) -> String {
+        format!("{}:{}:{}", self.path.display(), self.line, self.column)
+    }
+
+    /// 0-based char range to underline on the diagnostic's line: the word at
+    /// its column, or the one character there (the last one past the end)
+    pub fn span_in(&self, line: &str) -> (usize, usize) {
+        let chars: Vec<char> = line.chars().collect();
+        if chars.is_empty() {
+            return (0, 0);
+        }
+        let start = self.column.saturating_sub(1).min(chars.len() - 1);
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        let end = if is_word(chars[start]) {
+            chars[start..].iter().position(|&c| !is_word(c)).map_or(chars.len(), |n| start + n)
+        } else {
+            start + 1
+        };
+        (start, end)
+    }
+}
+
+// rustc/cargo: `error[E0425]: message` followed by ` --> src/main.rs:10:5`
+static CARGO_HEADER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(error|warning|note|help)(?:\[(\w+)\])?: (.+)$").unwrap()
+});
+static CARGO_LOCATION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\s*--> (.+?):(\d+):(\d+)\s*$").unwrap()
+});
+// gcc style / cargo --message-format=short / tsc --pretty:
+// `src/main.rs:10:5: error[E0425]: message`, `src/a.ts:3:7 - error TS2304: message`
+static COLON_STYLE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(.+?):(\d+):(\d+)(?::| -) (error|warning|note|info|help)(?:\[(\w+)\]| (TS\d+))?: (.+)$").unwrap()
+});
+// tsc: `src/a.ts(3,7): error TS2304: message`
+static TSC_STYLE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(.+?)\((\d+),(\d+)\): (error|warning) (TS\d+): (.+)$").unwrap()
+});
+
+/// Parse compiler/build output (cargo, rustc, tsc, gcc-style) into diagnostics
+pub fn parse_build_output(output: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    // Header waiting for its ` --> ` location line
+    let mut pending: Option<(Severity, Option<String>, String)> = None;
+
+    for raw in output.lines() {
+        let line = raw.trim_end();
+
+        if let Some(caps) = TSC_STYLE.captures(line) {
+            pending = None;
+            diagnostics.push(Diagnostic {
+                path: PathBuf::from(caps[1].trim()),
+                line: caps[2].parse().unwrap_or(1),
+                column: caps[3].parse().unwrap_or(1),
+                severity: Severity::from_label(&caps[4]).unwrap_or(Severity::Error),
+                message: caps[6].to_string(),
+                code: Some(caps[5].to_string()),
+                source: DiagnosticSource::Build,
+            });
+        } else if let Some(caps) = COLON_STYLE.captures(line) {
+            pending = None;
+            diagnostics.push(Diagnostic {
+                path: PathBuf::from(caps[1].trim()),
+                line: caps[2].parse().unwrap_or(1),
+                column: caps[3].parse().unwrap_or(1),
+                severity: Severity::from_label(&caps[4]).unwrap_or(Severity::Error),
+                message: caps[7].to_string(),
+                code: caps.get(5).or(caps.get(6)).map(|m| m.as_str().to_string()),
+                source: DiagnosticSource::Build,
+            });
+        } else if let Some(caps) = CARGO_HEADER.captures(line) {
+            pending = Severity::from_label(&caps[1])
+                .map(|sev| (sev, caps.get(2).map(|m| m.as_str().to_string()), caps[3].to_string()));
+        } else if let Some(caps) = CARGO_LOCATION.captures(line) {
+            if let Some((severity, code, message)) = pending.take() {
+                diagnostics.push(Diagnostic {
+                    path: PathBuf::from(&caps[1]),
+                    line: caps[2].parse().unwrap_or(1),
+                    column: caps[3].parse().unwrap_or(1),
+                    severity,
+                    message,
+                    code,
+                    source: DiagnosticSource::Build,
+                });
+            }
+        }
+    }
+
+    // The same problem shows up again when a build is re-run
+    let mut seen = HashSet::new();
+    diagnostics.retain(|d| seen.insert((d.path.clone(), d.line, d.column, d.message.clone())));
+    diagnostics
+}
+
+/// Collection of diagnostics from all sources
+#[derive(Debug, Default)]
+pub struct DiagnosticStore {
+    items: Vec<Diagnostic>,
+}
+
+impl DiagnosticStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace all diagnostics from `source`
+    pub fn set_source(&mut self, source: DiagnosticSource, items: Vec<Diagnostic>) {
+        self.items.retain(|d| d.source != source);
+        self.items.extend(items);
+        self.sort();
+    }
+
+    /// Replace the diagnostics from `source` for a single file (e.g. LSP publishDiagnostics)
+    pub fn set_file(&mut self, source: DiagnosticSource, path: &std::path::Path, items: Vec<Diagnostic>) {
+        self.items.retain(|d| !(d.source == source && d.path == path));
+        self.items.extend(items);
+        self.sort();
+    }
+
+    /// Replace the diagnostics from `source` for `paths` and for every file in `items`
+    /// (a linter run on one file may report on others, e.g. clippy)
+    pub fn set_files(&mut self, source: DiagnosticSource, paths: &[PathBuf], items: Vec<Diagnostic>) {
+        self.items.retain(|d| d.source != source || !(paths.contains(&d.path) || items.iter().any(|i| i.path == d.path)));
+        self.items.extend(items);
+        self.sort();
+    }
+
+    fn sort(&mut self) {
+        self.items.sort_by(|a, b| {
+            a.path.cmp(&b.path)
+                .then(a.line.cmp(&b.line))
+                .then(a.column.cmp(&b.column))
+        });
+    }
+
+    /// Diagnostics at least as severe as `min`
+    pub fn filtered(&self, min: Severity) -> Vec<&Diagnostic> {
+        self.items.iter().filter(|d| d.severity >= min).collect()
+    }
+
+    /// Number of diagnostics with exactly this severity
+    pub fn count(&self, severity: Severity) -> usize {
+        self.items.iter().filter(|d| d.severity == severity).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_build_output() {
+        let output = "\
+error[E0425]: cannot find value `x` in this scope
+  --> src/main.rs:10:5
+   |
+warning: unused variable: `y`
+ --> src/lib.rs:3:9
+src/app.ts(4,12): error TS2304: Cannot find name 'foo'.
+src/util.ts:7:1 - warning TS6133: 'bar' is declared but never read.
+error: could not compile `nterm` due to 1 previous error
+";
+        let diags = parse_build_output(output);
+        assert_eq!(diags.len(), 4);
+
+        assert_eq!(diags[0].path, PathBuf::from("src/main.rs"));
+        assert_eq!((diags[0].line, diags[0].column), (10, 5));
+        assert_eq!(diags[0].severity, Severity::Error);
+        assert_eq!(diags[0].code.as_deref(), Some("E0425"));
+
+        assert_eq!(diags[1].severity, Severity::Warning);
+        assert_eq!(diags[2].code.as_deref(), Some("TS2304"));
+        assert_eq!((diags[3].line, diags[3].severity), (7, Severity::Warning));
+    }
+
+    #[test]
+    fn test_store_filter_and_replace() {
+        let mut store = DiagnosticStore::new();
+        store.set_source(DiagnosticSource::Build, parse_build_output("a.rs:1:1: warning: w\nb.rs:2:1: error: e"));
+        assert_eq!(store.filtered(Severity::Hint).len(), 2);
+        assert_eq!(store.filtered(Severity::Error).len(), 1);
+
+        store.set_source(DiagnosticSource::Build, Vec::new());
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn test_span_in() {
+        let at = |column: usize| parse_build_output(&format!("a.rs:1:{}: error: e", column)).remove(0);
+        assert_eq!(at(5).span_in("let value_1 = x;"), (4, 11));
+        assert_eq!(at(13).span_in("let value_1 = x;"), (12, 13));
+        assert_eq!(at(40).span_in("x;"), (1, 2));
+        assert_eq!(at(1).span_in(""), (0, 0));
+    }
+}
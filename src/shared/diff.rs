@@ -0,0 +1,418 @@
+// Line-based diffs of two texts independent of git, with a choice of
+// algorithm: the model behind the git gutter, the AI review's view of what
+// changed and the compare view. `Diff` holds both texts and the aligned rows,
+// and lays them out as unified lines, side-by-side rows or a patch, so every
+// frontend renders from the same thing.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use serde::{Deserialize, Serialize};
+
+/// How the lines of two texts are matched up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Algorithm {
+    /// Shortest edit script; fast, but may match up stray blank lines and braces
+    #[default]
+    Myers,
+    /// Anchored on lines that occur once in each text, which keeps moved or
+    /// rewritten functions together
+    Patience,
+}
+
+/// How a row of a side-by-side diff relates the two sides
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffKind {
+    Same,
+    /// Line replaced: both sides present but different
+    Changed,
+    /// Only on the left side
+    Removed,
+    /// Only on the right side
+    Added,
+}
+
+/// One row of a side-by-side diff; line indices are 0-based
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiffRow {
+    pub left: Option<usize>,
+    pub right: Option<usize>,
+    pub kind: DiffKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+impl Op {
+    fn offset(self, a: usize, b: usize) -> Op {
+        match self {
+            Op::Equal(i, j) => Op::Equal(i + a, j + b),
+            Op::Delete(i) => Op::Delete(i + a),
+            Op::Insert(j) => Op::Insert(j + b),
+        }
+    }
+}
+
+/// Edit script turning `a` into `b`
+fn edit_script<T: Eq + Hash>(algorithm: Algorithm, a: &[T], b: &[T]) -> Vec<Op> {
+    // Common prefix and suffix don't need the search
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..].iter().rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (a_mid, b_mid) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    let mut ops: Vec<Op> = (0..prefix).map(|i| Op::Equal(i, i)).collect();
+    let middle = match algorithm {
+        Algorithm::Myers => myers(a_mid, b_mid),
+        Algorithm::Patience => patience(a_mid, b_mid),
+    };
+    ops.extend(middle.into_iter().map(|op| op.offset(prefix, prefix)));
+    ops.extend((0..suffix).map(|k| Op::Equal(a.len() - suffix + k, b.len() - suffix + k)));
+    ops
+}
+
+/// Patience diff: the longest run of lines unique to both sides, in the same
+/// order on both, anchors the diff and the gaps between anchors are diffed
+/// the same way; gaps without unique lines fall back to Myers
+fn patience<T: Eq + Hash>(a: &[T], b: &[T]) -> Vec<Op> {
+    let mut counts: HashMap<&T, (usize, usize, usize)> = HashMap::new();
+    for (i, line) in a.iter().enumerate() {
+        let entry = counts.entry(line).or_insert((0, 0, 0));
+        entry.0 += 1;
+        entry.2 = i;
+    }
+    let mut unique: Vec<(usize, usize)> = Vec::new();
+    let mut seen_in_b: HashMap<&T, (usize, usize)> = HashMap::new();
+    for (j, line) in b.iter().enumerate() {
+        let entry = seen_in_b.entry(line).or_insert((0, j));
+        entry.0 += 1;
+    }
+    for (line, (count_b, j)) in &seen_in_b {
+        if let Some(&(1, _, i)) = counts.get(line) {
+            if *count_b == 1 {
+                unique.push((i, *j));
+            }
+        }
+    }
+    unique.sort_unstable();
+    let anchors = longest_increasing(&unique);
+    if anchors.is_empty() {
+        return myers(a, b);
+    }
+
+    let mut ops = Vec::new();
+    let (mut prev_a, mut prev_b) = (0, 0);
+    for (i, j) in anchors.into_iter().chain(std::iter::once((a.len(), b.len()))) {
+        let gap = edit_script(Algorithm::Patience, &a[prev_a..i], &b[prev_b..j]);
+        ops.extend(gap.into_iter().map(|op| op.offset(prev_a, prev_b)));
+        if i < a.len() {
+            ops.push(Op::Equal(i, j));
+        }
+        (prev_a, prev_b) = (i + 1, j + 1);
+    }
+    ops
+}
+
+/// Longest subsequence of `pairs` (sorted by their first element) whose second
+/// elements increase, found by patience sorting
+fn longest_increasing(pairs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    // Index into `pairs` of the top card of each pile, and each card's predecessor
+    let mut piles: Vec<usize> = Vec::new();
+    let mut previous: Vec<Option<usize>> = vec![None; pairs.len()];
+    for (n, &(_, j)) in pairs.iter().enumerate() {
+        let pile = piles.partition_point(|&top| pairs[top].1 < j);
+        previous[n] = pile.checked_sub(1).map(|p| piles[p]);
+        if pile == piles.len() {
+            piles.push(n);
+        } else {
+            piles[pile] = n;
+        }
+    }
+    let mut sequence = Vec::new();
+    let mut card = piles.last().copied();
+    while let Some(n) = card {
+        sequence.push(pairs[n]);
+        card = previous[n];
+    }
+    sequence.reverse();
+    sequence
+}
+
+fn myers<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Op> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = n + m;
+    let offset = max as usize;
+    let mut v = vec![0isize; 2 * offset + 2];
+    let mut trace = Vec::new();
+
+    'search: for d in 0..=max {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let idx = (k + max) as usize;
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+                v[idx + 1]
+            } else {
+                v[idx - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    // Walk the trace backwards from (n, m) to recover the path
+    let mut ops = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && v[(k - 1 + max) as usize] < v[(k + 1 + max) as usize]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[(prev_k + max) as usize];
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            ops.push(Op::Equal((x - 1) as usize, (y - 1) as usize));
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            if x == prev_x {
+                ops.push(Op::Insert((y - 1) as usize));
+            } else {
+                ops.push(Op::Delete((x - 1) as usize));
+            }
+            x = prev_x;
+            y = prev_y;
+        }
+    }
+    ops.reverse();
+    ops
+}
+
+/// Side-by-side rows for two texts. Runs of removed and added lines are paired up
+/// as changed rows so edits line up across the two sides.
+pub fn diff_lines<S: AsRef<str>>(algorithm: Algorithm, left: &[S], right: &[S]) -> Vec<DiffRow> {
+    let a: Vec<&str> = left.iter().map(AsRef::as_ref).collect();
+    let b: Vec<&str> = right.iter().map(AsRef::as_ref).collect();
+
+    let mut rows = Vec::new();
+    let mut deleted = Vec::new();
+    let mut inserted = Vec::new();
+    let flush = |rows: &mut Vec<DiffRow>, deleted: &mut Vec<usize>, inserted: &mut Vec<usize>| {
+        for i in 0..deleted.len().max(inserted.len()) {
+            let (left, right) = (deleted.get(i).copied(), inserted.get(i).copied());
+            let kind = match (left, right) {
+                (Some(_), Some(_)) => DiffKind::Changed,
+                (Some(_), None) => DiffKind::Removed,
+                _ => DiffKind::Added,
+            };
+            rows.push(DiffRow { left, right, kind });
+        }
+        deleted.clear();
+        inserted.clear();
+    };
+
+    for op in edit_script(algorithm, &a, &b) {
+        match op {
+            Op::Delete(i) => deleted.push(i),
+            Op::Insert(j) => inserted.push(j),
+            Op::Equal(i, j) => {
+                flush(&mut rows, &mut deleted, &mut inserted);
+                rows.push(DiffRow { left: Some(i), right: Some(j), kind: DiffKind::Same });
+            }
+        }
+    }
+    flush(&mut rows, &mut deleted, &mut inserted);
+    rows
+}
+
+/// Row indices where each block of differences starts
+pub fn hunk_starts(rows: &[DiffRow]) -> Vec<usize> {
+    rows.iter()
+        .enumerate()
+        .filter(|(i, row)| row.kind != DiffKind::Same && (*i == 0 || rows[i - 1].kind == DiffKind::Same))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+
+/// One line of the unified layout; `left` and `right` are the 0-based line
+/// numbers on each side, `None` on the side the line isn't on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnifiedLine<'a> {
+    /// ` `, `-` or `+`
+    pub marker: char,
+    pub text: &'a str,
+    /// `Same`, `Removed` or `Added`
+    pub kind: DiffKind,
+    pub left: Option<usize>,
+    pub right: Option<usize>,
+}
+
+/// One row of the side-by-side layout: (line number, text) on each side
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SideBySideRow<'a> {
+    pub left: Option<(usize, &'a str)>,
+    pub right: Option<(usize, &'a str)>,
+    pub kind: DiffKind,
+}
+
+/// Two texts and how their lines line up
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diff {
+    pub left: Vec<String>,
+    pub right: Vec<String>,
+    pub rows: Vec<DiffRow>,
+}
+
+impl Diff {
+    pub fn new(algorithm: Algorithm, left: Vec<String>, right: Vec<String>) -> Self {
+        let rows = diff_lines(algorithm, &left, &right);
+        Self { left, right, rows }
+    }
+
+    /// Whether the two texts are the same
+    pub fn is_identical(&self) -> bool {
+        self.rows.iter().all(|row| row.kind == DiffKind::Same)
+    }
+
+    /// Lines of the right side that were added or changed
+    pub fn changed_lines(&self) -> Vec<usize> {
+        self.rows.iter()
+            .filter(|row| row.kind != DiffKind::Same)
+            .filter_map(|row| row.right)
+            .collect()
+    }
+
+    /// `rows` laid out in one column: a changed row becomes its removed line
+    /// followed by its added one
+    pub fn unified<'a>(&'a self, rows: &'a [DiffRow]) -> impl Iterator<Item = UnifiedLine<'a>> + 'a {
+        let text = |lines: &'a [String], idx: Option<usize>| idx.and_then(|i| lines.get(i)).map_or("", String::as_str);
+        rows.iter().flat_map(move |row| {
+            let removed = (row.kind != DiffKind::Same && row.left.is_some()).then(|| UnifiedLine {
+                marker: '-',
+                text: text(&self.left, row.left),
+                kind: DiffKind::Removed,
+                left: row.left,
+                right: None,
+            });
+            let kept = (row.kind != DiffKind::Removed).then(|| match row.kind {
+                DiffKind::Same => UnifiedLine { marker: ' ', text: text(&self.right, row.right), kind: DiffKind::Same, left: row.left, right: row.right },
+                _ => UnifiedLine { marker: '+', text: text(&self.right, row.right), kind: DiffKind::Added, left: None, right: row.right },
+            });
+            removed.into_iter().chain(kept)
+        })
+    }
+
+    /// `rows` laid out in two columns, with a gap on the side a line is missing from
+    pub fn side_by_side<'a>(&'a self, rows: &'a [DiffRow]) -> impl Iterator<Item = SideBySideRow<'a>> + 'a {
+        let side = |lines: &'a [String], idx: Option<usize>| idx.map(|i| (i, lines.get(i).map_or("", String::as_str)));
+        rows.iter().map(move |row| SideBySideRow {
+            left: side(&self.left, row.left),
+            right: side(&self.right, row.right),
+            kind: row.kind,
+        })
+    }
+
+    /// The differences as a unified patch with `context` unchanged lines
+    /// around each hunk; empty when the texts are the same
+    pub fn patch(&self, left_name: &str, right_name: &str, context: usize) -> String {
+        let lines: Vec<UnifiedLine> = self.unified(&self.rows).collect();
+        let changed: Vec<usize> = (0..lines.len()).filter(|&i| lines[i].kind != DiffKind::Same).collect();
+        let Some(&first) = changed.first() else { return String::new() };
+
+        // Changes closer together than twice the context share a hunk
+        let mut hunks = vec![(first, first)];
+        for &i in &changed[1..] {
+            match hunks.last_mut() {
+                Some((_, end)) if i - *end <= 2 * context + 1 => *end = i,
+                _ => hunks.push((i, i)),
+            }
+        }
+
+        let mut out = format!("--- {}\n+++ {}\n", left_name, right_name);
+        for (start, end) in hunks {
+            let range = start.saturating_sub(context)..(end + context + 1).min(lines.len());
+            let before = &lines[..range.start];
+            let hunk = &lines[range];
+            let header = |before: usize, count: usize| match count {
+                // An empty side is placed after the line it follows
+                0 => format!("{},0", before),
+                1 => format!("{}", before + 1),
+                _ => format!("{},{}", before + 1, count),
+            };
+            let old = header(before.iter().filter(|l| l.marker != '+').count(), hunk.iter().filter(|l| l.marker != '+').count());
+            let new = header(before.iter().filter(|l| l.marker != '-').count(), hunk.iter().filter(|l| l.marker != '-').count());
+            out.push_str(&format!("@@ -{} +{} @@\n", old, new));
+            for line in hunk {
+                out.push_str(&format!("{}{}\n", line.marker, line.text));
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_lines() {
+        let left = ["a", "b", "c", "d", "e"];
+        let right = ["a", "B", "c", "e", "f"];
+        let rows = diff_lines(Algorithm::Myers, &left, &right);
+        let kinds: Vec<DiffKind> = rows.iter().map(|r| r.kind).collect();
+        assert_eq!(kinds, vec![
+            DiffKind::Same,
+            DiffKind::Changed,
+            DiffKind::Same,
+            DiffKind::Removed,
+            DiffKind::Same,
+            DiffKind::Added,
+        ]);
+        assert_eq!(rows[1], DiffRow { left: Some(1), right: Some(1), kind: DiffKind::Changed });
+        assert_eq!(rows[5], DiffRow { left: None, right: Some(4), kind: DiffKind::Added });
+        assert_eq!(hunk_starts(&rows), vec![1, 3, 5]);
+
+        assert!(diff_lines(Algorithm::Myers, &left, &left).iter().all(|r| r.kind == DiffKind::Same));
+        assert_eq!(diff_lines::<&str>(Algorithm::Patience, &[], &["x"])[0].kind, DiffKind::Added);
+    }
+
+    #[test]
+    fn test_patience_and_layouts() {
+        // Swapped functions: patience keeps `two` whole instead of matching up braces
+        let left = ["fn one() {", "    work();", "}", "fn two() {", "    rest();", "}"];
+        let right = ["fn two() {", "    rest();", "}", "fn one() {", "    work();", "}"];
+        let kinds: Vec<DiffKind> = diff_lines(Algorithm::Patience, &left, &right).iter().map(|r| r.kind).collect();
+        assert_eq!(kinds, [&[DiffKind::Removed; 3][..], &[DiffKind::Same; 2], &[DiffKind::Added; 3], &[DiffKind::Same]].concat());
+
+        let lines = |s: &[&str]| s.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+        let diff = Diff::new(Algorithm::Myers, lines(&["a", "b", "c", "d", "e"]), lines(&["a", "B", "c", "e", "f"]));
+        assert!(!diff.is_identical());
+        assert_eq!(diff.changed_lines(), vec![1, 4]);
+        let unified: Vec<(char, &str, Option<usize>)> = diff.unified(&diff.rows).map(|l| (l.marker, l.text, l.left)).collect();
+        assert_eq!(unified[..3], [(' ', "a", Some(0)), ('-', "b", Some(1)), ('+', "B", None)]);
+        let split: Vec<SideBySideRow> = diff.side_by_side(&diff.rows[3..4]).collect();
+        assert_eq!(split[0], SideBySideRow { left: Some((3, "d")), right: None, kind: DiffKind::Removed });
+
+        assert_eq!(diff.patch("a", "b", 1), "--- a\n+++ b\n@@ -1,5 +1,5 @@\n a\n-b\n+B\n c\n-d\n e\n+f\n");
+        assert_eq!(diff.patch("a", "b", 0), "--- a\n+++ b\n@@ -2 +2 @@\n-b\n+B\n@@ -4 +3,0 @@\n-d\n@@ -5,0 +5 @@\n+f\n");
+        assert_eq!(Diff::new(Algorithm::Patience, lines(&["x"]), lines(&["x"])).patch("a", "b", 3), "");
+    }
+}
@@ -0,0 +1,192 @@
+// Text encodings: files that aren't UTF-8 (Latin-1, UTF-16) are decoded for
+// editing and written back in the encoding they were read in. Line endings are
+// kept the same way.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+
+/// Bytes looked at to guess UTF-16 without a byte order mark
+const SNIFF_BYTES: usize = 1024;
+
+/// Error message for data that isn't text
+pub const BINARY_FILE: &str = "binary file";
+
+/// How a file's text is stored on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextEncoding {
+    pub encoding: &'static Encoding,
+    /// The file starts with a byte order mark (kept on save)
+    pub bom: bool,
+}
+
+impl Default for TextEncoding {
+    fn default() -> Self {
+        Self { encoding: UTF_8, bom: false }
+    }
+}
+
+impl TextEncoding {
+    /// Name for the status area, e.g. `UTF-8`, `UTF-8 BOM`, `windows-1252`
+    pub fn label(&self) -> String {
+        if self.bom && self.encoding == UTF_8 {
+            "UTF-8 BOM".to_string()
+        } else {
+            self.encoding.name().to_string()
+        }
+    }
+
+    /// `text` as bytes in this encoding. Fails when a character has no
+    /// representation in it (e.g. `€` is fine in windows-1252, `→` is not).
+    pub fn encode(&self, text: &str) -> io::Result<Vec<u8>> {
+        let mut bytes = Vec::with_capacity(text.len() + 3);
+        if self.encoding == UTF_16LE || self.encoding == UTF_16BE {
+            // encoding_rs only decodes UTF-16
+            let little = self.encoding == UTF_16LE;
+            let unit = |u: u16| if little { u.to_le_bytes() } else { u.to_be_bytes() };
+            if self.bom {
+                bytes.extend(unit(0xFEFF));
+            }
+            bytes.extend(text.encode_utf16().flat_map(unit));
+            return Ok(bytes);
+        }
+        if self.bom {
+            bytes.extend(b"\xEF\xBB\xBF");
+        }
+        let (encoded, _, unmappable) = self.encoding.encode(text);
+        if unmappable {
+            let c = text.chars().find(|c| self.encoding.encode(&c.to_string()).2).unwrap_or('?');
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("'{}' can't be saved as {}", c, self.encoding.name()),
+            ));
+        }
+        bytes.extend_from_slice(&encoded);
+        Ok(bytes)
+    }
+}
+
+/// Line terminator written between lines on save
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    /// The ending most lines of `text` use; LF when there are no line breaks
+    pub fn detect(text: &str) -> Self {
+        let lines = text.matches('\n').count();
+        let crlf = text.matches("\r\n").count();
+        if crlf * 2 > lines { LineEnding::Crlf } else { LineEnding::Lf }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "LF",
+            LineEnding::Crlf => "CRLF",
+        }
+    }
+
+    pub fn toggled(&self) -> Self {
+        match self {
+            LineEnding::Lf => LineEnding::Crlf,
+            LineEnding::Crlf => LineEnding::Lf,
+        }
+    }
+}
+
+/// Decode file contents: a byte order mark wins, then UTF-16 by the pattern of
+/// zero bytes, then valid UTF-8, then windows-1252 (a superset of Latin-1).
+/// Other data with zero bytes is taken to be binary and refused.
+pub fn decode(bytes: &[u8]) -> io::Result<(String, TextEncoding)> {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+        let (text, _) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+        return Ok((text.into_owned(), TextEncoding { encoding, bom: true }));
+    }
+    // ASCII in UTF-16 is valid UTF-8 too, so this comes first
+    if let Some(encoding) = sniff_utf16(bytes) {
+        let (text, _) = encoding.decode_without_bom_handling(bytes);
+        return Ok((text.into_owned(), TextEncoding { encoding, bom: false }));
+    }
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return Ok((text.to_string(), TextEncoding::default()));
+    }
+    if bytes.contains(&0) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, BINARY_FILE));
+    }
+    let (text, _) = WINDOWS_1252.decode_without_bom_handling(bytes);
+    Ok((text.into_owned(), TextEncoding { encoding: WINDOWS_1252, bom: false }))
+}
+
+/// Read and decode a text file
+pub fn read_file(path: &Path) -> io::Result<(String, TextEncoding)> {
+    decode(&fs::read(path)?)
+}
+
+/// UTF-16 text that is mostly ASCII has a zero in every other byte
+fn sniff_utf16(bytes: &[u8]) -> Option<&'static Encoding> {
+    let sample = &bytes[..bytes.len().min(SNIFF_BYTES) & !1];
+    let pairs = sample.len() / 2;
+    if pairs < 2 {
+        return None;
+    }
+    let zeros = |offset: usize| sample.iter().skip(offset).step_by(2).filter(|&&b| b == 0).count();
+    let (even, odd) = (zeros(0), zeros(1));
+    match (even * 10 / pairs, odd * 10 / pairs) {
+        (0, 4..) => Some(UTF_16LE),
+        (4.., 0) => Some(UTF_16BE),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        let (text, enc) = decode(b"caf\xe9\n").unwrap();
+        assert_eq!((text.as_str(), enc.label()), ("café\n", "windows-1252".to_string()));
+
+        let (text, enc) = decode(b"\xEF\xBB\xBFhi").unwrap();
+        assert_eq!((text.as_str(), enc.label()), ("hi", "UTF-8 BOM".to_string()));
+
+        let le: Vec<u8> = "héllo".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        assert_eq!(decode(&le).unwrap(), ("héllo".to_string(), TextEncoding { encoding: UTF_16LE, bom: false }));
+        let be: Vec<u8> = [0xFE, 0xFF, 0, b'o', 0, b'k'].to_vec();
+        assert_eq!(decode(&be).unwrap().0, "ok");
+
+        assert_eq!(decode("naïve".as_bytes()).unwrap().1, TextEncoding::default());
+        assert!(decode(&[0x89, b'P', b'N', b'G', 0, 0, 0, 0x0D, 0xFF, 0xFE, 0x12]).is_err());
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        for bytes in [&b"caf\xe9 \x80"[..], b"\xFF\xFEh\0i\0", b"\xEF\xBB\xBFx\n", b"\0a\0b\0c"] {
+            let (text, enc) = decode(bytes).unwrap();
+            assert_eq!(enc.encode(&text).unwrap(), bytes);
+        }
+        let latin1 = TextEncoding { encoding: WINDOWS_1252, bom: false };
+        let err = latin1.encode("a → b").unwrap_err();
+        assert_eq!(err.to_string(), "'→' can't be saved as windows-1252");
+    }
+
+    #[test]
+    fn test_line_ending() {
+        assert_eq!(LineEnding::detect("a\r\nb\r\nc\n"), LineEnding::Crlf);
+        assert_eq!(LineEnding::detect("a\nb\r\nc\n"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("no breaks"), LineEnding::Lf);
+        assert_eq!(LineEnding::Lf.toggled().as_str(), "\r\n");
+    }
+}
@@ -0,0 +1,121 @@
+// Workspace events: things that happened in one part of nterm that other
+// parts react to. Each part subscribes to the kinds of event it cares about;
+// a published event is queued once for every subscriber to its kind, and the
+// deliveries are handed out in order.
+
+use std::collections::VecDeque;
+use std::path::PathBuf;
+
+use super::chat::ChatMessage;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorkspaceEvent {
+    /// A buffer was written to this file
+    FileSaved(PathBuf),
+    /// Something in the workspace changed on disk, e.g. a checkout
+    FilesChanged,
+    /// A terminal command (the watch command) exited
+    CommandFinished { command: String, success: bool },
+    /// A model's reply to a chat message arrived
+    AiResponseCompleted(ChatMessage),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    FileSaved,
+    FilesChanged,
+    CommandFinished,
+    AiResponseCompleted,
+}
+
+impl WorkspaceEvent {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            WorkspaceEvent::FileSaved(_) => EventKind::FileSaved,
+            WorkspaceEvent::FilesChanged => EventKind::FilesChanged,
+            WorkspaceEvent::CommandFinished { .. } => EventKind::CommandFinished,
+            WorkspaceEvent::AiResponseCompleted(_) => EventKind::AiResponseCompleted,
+        }
+    }
+}
+
+/// Subscriptions of `S` (an id for each subscriber) and the deliveries not yet handled
+pub struct EventBus<S> {
+    subscriptions: Vec<(S, Vec<EventKind>)>,
+    pending: VecDeque<(S, WorkspaceEvent)>,
+}
+
+impl<S: Copy + PartialEq> EventBus<S> {
+    pub fn new() -> Self {
+        Self { subscriptions: Vec::new(), pending: VecDeque::new() }
+    }
+
+    /// Deliver events of `kinds` to `subscriber`, after those subscribed earlier
+    pub fn subscribe(&mut self, subscriber: S, kinds: &[EventKind]) {
+        let Some((_, subscribed)) = self.subscriptions.iter_mut().find(|(s, _)| *s == subscriber) else {
+            self.subscriptions.push((subscriber, kinds.to_vec()));
+            return;
+        };
+        for kind in kinds {
+            if !subscribed.contains(kind) {
+                subscribed.push(*kind);
+            }
+        }
+    }
+
+    /// Stop delivering to `subscriber`, dropping what is queued for it
+    pub fn unsubscribe(&mut self, subscriber: S) {
+        self.subscriptions.retain(|(s, _)| *s != subscriber);
+        self.pending.retain(|(s, _)| *s != subscriber);
+    }
+
+    /// Queue `event` for everyone subscribed to its kind
+    pub fn publish(&mut self, event: WorkspaceEvent) {
+        let kind = event.kind();
+        for (subscriber, _) in self.subscriptions.iter().filter(|(_, kinds)| kinds.contains(&kind)) {
+            self.pending.push_back((*subscriber, event.clone()));
+        }
+    }
+
+    /// The next delivery to handle
+    pub fn pop(&mut self) -> Option<(S, WorkspaceEvent)> {
+        self.pending.pop_front()
+    }
+}
+
+impl<S: Copy + PartialEq> Default for EventBus<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_publish_to_subscribers() {
+        let mut bus = EventBus::new();
+        bus.subscribe("linter", &[EventKind::FileSaved]);
+        bus.subscribe("todos", &[EventKind::FilesChanged]);
+        bus.subscribe("watch", &[EventKind::FileSaved]);
+        bus.subscribe("watch", &[EventKind::CommandFinished, EventKind::FileSaved]);
+
+        bus.publish(WorkspaceEvent::FileSaved(PathBuf::from("src/main.rs")));
+        bus.publish(WorkspaceEvent::CommandFinished { command: "cargo test".to_string(), success: false });
+        bus.publish(WorkspaceEvent::AiResponseCompleted(ChatMessage::user("unheard")));
+        let deliveries: Vec<(&str, EventKind)> = std::iter::from_fn(|| bus.pop()).map(|(s, e)| (s, e.kind())).collect();
+        assert_eq!(deliveries, vec![
+            ("linter", EventKind::FileSaved),
+            ("watch", EventKind::FileSaved),
+            ("watch", EventKind::CommandFinished),
+        ]);
+
+        bus.publish(WorkspaceEvent::FilesChanged);
+        bus.publish(WorkspaceEvent::FileSaved(PathBuf::from("a.rs")));
+        bus.unsubscribe("watch");
+        assert_eq!(bus.pop().map(|(s, _)| s), Some("todos"));
+        assert_eq!(bus.pop().map(|(s, _)| s), Some("linter"));
+        assert_eq!(bus.pop(), None);
+    }
+}
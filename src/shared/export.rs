@@ -0,0 +1,75 @@
+// Export of highlighted buffers to HTML or ANSI text for sharing snippets
+
+use std::path::{Path, PathBuf};
+
+use syntect::easy::HighlightLines;
+use syntect::html::highlighted_html_for_string;
+use syntect::util::as_24_bit_terminal_escaped;
+
+use super::syntax::{self, DEFAULT_THEME};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Html,
+    Ansi,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Html => "html",
+            ExportFormat::Ansi => "ansi",
+        }
+    }
+}
+
+/// Render `content` with syntax highlighting for the given language key (usually the extension)
+pub fn render(content: &str, language: Option<&str>, format: ExportFormat) -> Result<String, String> {
+    let assets = syntax::assets_blocking();
+    let syntax = assets.find_syntax(language);
+    let theme = assets.theme(DEFAULT_THEME);
+
+    match format {
+        ExportFormat::Html => highlighted_html_for_string(content, &assets.syntax_set, syntax, theme)
+            .map_err(|e| e.to_string()),
+        ExportFormat::Ansi => {
+            let mut highlighter = HighlightLines::new(syntax, theme);
+            let mut out = String::new();
+            for line in syntect::util::LinesWithEndings::from(content) {
+                let ranges = highlighter.highlight_line(line, &assets.syntax_set).map_err(|e| e.to_string())?;
+                out.push_str(&as_24_bit_terminal_escaped(&ranges, false));
+            }
+            // Reset attributes so the snippet doesn't bleed into the shell
+            out.push_str("\x1b[0m");
+            Ok(out)
+        }
+    }
+}
+
+/// Render and write next to `source` (the same name with an `.html`/`.ansi` suffix appended)
+pub fn export_to_file(content: &str, source: &Path, format: ExportFormat) -> Result<PathBuf, String> {
+    let language = syntax::language_key(source);
+    let rendered = render(content, language.as_deref(), format)?;
+    let mut target = source.as_os_str().to_owned();
+    target.push(".");
+    target.push(format.extension());
+    let target = PathBuf::from(target);
+    std::fs::write(&target, rendered).map_err(|e| e.to_string())?;
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_formats() {
+        let html = render("fn main() {}\n", Some("rs"), ExportFormat::Html).unwrap();
+        assert!(html.starts_with("<pre"));
+        assert!(html.contains("main"));
+
+        let ansi = render("fn main() {}\n", Some("rs"), ExportFormat::Ansi).unwrap();
+        assert!(ansi.contains("\x1b[38;2;"));
+        assert!(ansi.ends_with("\x1b[0m"));
+    }
+}
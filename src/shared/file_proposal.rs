@@ -0,0 +1,107 @@
+// New files proposed by the AI through the create_file tool. Proposals are
+// checked against the workspace and only written once the user approves them.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+
+use super::ai::tools::CREATE_FILE;
+use super::ai::ToolCall;
+
+/// Proposals bigger than this are rejected outright
+const MAX_CONTENT_SIZE: usize = 512 * 1024;
+
+/// A file the AI wants to create (path relative to the workspace)
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileProposal {
+    pub path: PathBuf,
+    pub content: String,
+}
+
+/// Turn a `create_file` call into a proposal, rejecting unsafe ones.
+/// Returns `None` for calls to other tools.
+pub fn from_tool_call(root: &Path, call: &ToolCall) -> Option<Result<FileProposal, String>> {
+    if call.name != CREATE_FILE {
+        return None;
+    }
+    let path = call.arguments.get("path").and_then(|p| p.as_str());
+    let content = call.arguments.get("content").and_then(|c| c.as_str());
+    let (Some(path), Some(content)) = (path, content) else {
+        return Some(Err("create_file needs a path and content".to_string()));
+    };
+    let proposal = FileProposal { path: PathBuf::from(path), content: content.to_string() };
+    Some(validate(root, &proposal).map(|_| proposal))
+}
+
+/// Guardrails: the path must stay inside the workspace, outside hidden
+/// directories, and must not exist yet
+pub fn validate(root: &Path, proposal: &FileProposal) -> Result<PathBuf, String> {
+    let path = &proposal.path;
+    let display = path.display();
+    if path.as_os_str().is_empty() {
+        return Err("empty path".to_string());
+    }
+    for component in path.components() {
+        match component {
+            Component::Normal(name) if name.to_string_lossy().starts_with('.') => {
+                return Err(format!("{}: hidden paths are not allowed", display));
+            }
+            Component::Normal(_) => {}
+            Component::CurDir => {}
+            _ => return Err(format!("{}: path must be relative to the workspace", display)),
+        }
+    }
+    if proposal.content.len() > MAX_CONTENT_SIZE {
+        return Err(format!("{}: content is too large", display));
+    }
+    let target = root.join(path);
+    if target.exists() {
+        return Err(format!("{} already exists", display));
+    }
+    Ok(target)
+}
+
+/// Write an approved proposal, creating parent directories as needed
+pub fn write(root: &Path, proposal: &FileProposal) -> Result<PathBuf, String> {
+    let target = validate(root, proposal)?;
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    // create_new: never clobber a file that appeared since validation
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&target)
+        .map_err(|e| format!("{}: {}", proposal.path.display(), e))?;
+    file.write_all(proposal.content.as_bytes()).map_err(|e| e.to_string())?;
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_guardrails() {
+        let root = std::env::temp_dir().join(format!("nterm-proposal-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("existing.txt"), "keep").unwrap();
+
+        let call = |path: &str| ToolCall {
+            name: CREATE_FILE.to_string(),
+            arguments: json!({ "path": path, "content": "hello\n" }),
+        };
+        for bad in ["../escape.txt", "/etc/passwd", ".git/config", "existing.txt", ""] {
+            assert!(matches!(from_tool_call(&root, &call(bad)), Some(Err(_))), "{} should be rejected", bad);
+        }
+        let other = ToolCall { name: "delete_file".to_string(), arguments: json!({}) };
+        assert!(from_tool_call(&root, &other).is_none());
+
+        let proposal = from_tool_call(&root, &call("src/new.rs")).unwrap().unwrap();
+        let written = write(&root, &proposal).unwrap();
+        assert_eq!(fs::read_to_string(&written).unwrap(), "hello\n");
+        assert!(write(&root, &proposal).is_err());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -3,6 +3,8 @@
 use std::fs;
 use std::path::PathBuf;
 
+use super::workspace;
+
 #[derive(Clone, Debug)]
 pub struct FileNode {
     pub path: PathBuf,
@@ -11,12 +13,15 @@ pub struct FileNode {
     pub expanded: bool,
     pub children: Vec<FileNode>,
     pub depth: usize,
+    /// Directory excluded from the workspace, hidden unless asked for
+    pub excluded: bool,
 }
 
 impl FileNode {
     pub fn from_path(path: PathBuf, depth: usize) -> Self {
         let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
         let is_dir = path.is_dir();
+        let excluded = is_dir && workspace::is_excluded(&path);
         Self {
             path,
             name,
@@ -24,6 +29,7 @@ impl FileNode {
             expanded: false,
             children: Vec::new(),
             depth,
+            excluded,
         }
     }
 
@@ -66,34 +72,46 @@ pub struct VisibleItem {
     pub is_dir: bool,
     pub depth: usize,
     pub expanded: bool,
+    pub excluded: bool,
 }
 
-pub fn flatten_node(node: &FileNode, visible_items: &mut Vec<VisibleItem>) {
+/// Append `node` and its expanded descendants; excluded directories only with `show_excluded`
+pub fn flatten_node(node: &FileNode, visible_items: &mut Vec<VisibleItem>, show_excluded: bool) {
+    if node.excluded && !show_excluded {
+        return;
+    }
     visible_items.push(VisibleItem {
         name: node.name.clone(),
         path: node.path.clone(),
         is_dir: node.is_dir,
         depth: node.depth,
         expanded: node.expanded,
+        excluded: node.excluded,
     });
 
     if node.expanded {
         for child in &node.children {
-            flatten_node(child, visible_items);
+            flatten_node(child, visible_items, show_excluded);
         }
     }
 }
 
-pub fn toggle_node_recursive(nodes: &mut Vec<FileNode>, target: &PathBuf) -> bool {
+/// Recheck which loaded directories are excluded, after the exclusions changed
+pub fn mark_excluded(nodes: &mut [FileNode]) {
+    for node in nodes {
+        node.excluded = node.is_dir && workspace::is_excluded(&node.path);
+        mark_excluded(&mut node.children);
+    }
+}
+
+pub fn toggle_node_recursive(nodes: &mut [FileNode], target: &PathBuf) -> bool {
     for node in nodes.iter_mut() {
         if &node.path == target {
             node.toggle_expand();
             return true;
         }
-        if node.expanded {
-            if toggle_node_recursive(&mut node.children, target) {
-                return true;
-            }
+        if node.expanded && toggle_node_recursive(&mut node.children, target) {
+            return true;
         }
     }
     false
@@ -112,7 +130,7 @@ mod tests {
         assert_eq!(node.path, path);
         assert_eq!(node.name, "test_dir");
         assert_eq!(node.depth, 0);
-        assert_eq!(node.expanded, false);
+        assert!(!node.expanded);
         assert!(node.children.is_empty());
     }
 
@@ -125,6 +143,7 @@ mod tests {
             expanded: true,
             children: vec![],
             depth: 0,
+            excluded: false,
         };
 
         let child1 = FileNode {
@@ -134,6 +153,7 @@ mod tests {
             expanded: false,
             children: vec![],
             depth: 1,
+            excluded: false,
         };
 
         let child2 = FileNode {
@@ -143,18 +163,27 @@ mod tests {
             expanded: false,
             children: vec![],
             depth: 1,
+            excluded: false,
         };
 
         root.children.push(child1);
         root.children.push(child2);
 
         let mut visible = Vec::new();
-        flatten_node(&root, &mut visible);
+        flatten_node(&root, &mut visible, false);
 
         assert_eq!(visible.len(), 3);
         assert_eq!(visible[0].name, "root");
         assert_eq!(visible[1].name, "child1");
         assert_eq!(visible[2].name, "child2");
+
+        root.children[1].excluded = true;
+        let mut visible = Vec::new();
+        flatten_node(&root, &mut visible, false);
+        assert_eq!(visible.len(), 2);
+        let mut visible = Vec::new();
+        flatten_node(&root, &mut visible, true);
+        assert!(visible[2].excluded);
     }
 
     #[test]
@@ -166,6 +195,7 @@ mod tests {
             expanded: false, // Collapsed
             children: vec![],
             depth: 0,
+            excluded: false,
         };
 
         let child1 = FileNode {
@@ -175,12 +205,13 @@ mod tests {
             expanded: false,
             children: vec![],
             depth: 1,
+            excluded: false,
         };
 
         root.children.push(child1);
 
         let mut visible = Vec::new();
-        flatten_node(&root, &mut visible);
+        flatten_node(&root, &mut visible, false);
 
         assert_eq!(visible.len(), 1);
         assert_eq!(visible[0].name, "root");
@@ -0,0 +1,154 @@
+// Formatting a buffer with an external formatter such as `rustfmt` or
+// `black -`: the text goes to the command's stdin and the formatted text is
+// read back from its stdout
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use super::lsp::TextEdit;
+use super::toolchain::Toolchain;
+
+/// Run `command` in `root` on `text`. Blocks until the formatter exits, so
+/// call it off the UI thread. A failing exit status (usually a syntax error)
+/// or no output at all is an error, so a misconfigured command can't empty
+/// the buffer.
+pub fn run(command: &str, root: &Path, text: &str) -> Result<String, String> {
+    let mut child = Command::new("sh")
+        .args(["-c", command])
+        .current_dir(root)
+        .envs(Toolchain::detect(root).env())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("could not run `{}`: {}", command, e))?;
+    // Written on another thread so a formatter printing before it has read
+    // everything can't block on a full pipe
+    let writer = child.stdin.take().map(|mut stdin| {
+        let text = text.to_string();
+        thread::spawn(move || stdin.write_all(text.as_bytes()))
+    });
+    let output = child.wait_with_output().map_err(|e| format!("`{}` failed: {}", command, e))?;
+    if let Some(writer) = writer {
+        let _ = writer.join();
+    }
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = stderr.lines().map(str::trim).find(|l| !l.is_empty()).unwrap_or("");
+        return Err(if message.is_empty() { format!("`{}` exited with {}", command, output.status) } else { message.to_string() });
+    }
+    let formatted = String::from_utf8(output.stdout).map_err(|_| format!("`{}` printed invalid UTF-8", command))?;
+    if formatted.is_empty() && !text.is_empty() {
+        return Err(format!("`{}` printed nothing (it should write the formatted file to stdout)", command));
+    }
+    Ok(formatted)
+}
+
+/// The text of `lines` with a language server's formatting `edits` applied
+pub fn apply_edits<S: AsRef<str>>(lines: &[S], edits: &[TextEdit]) -> String {
+    let mut text = lines.iter().map(AsRef::as_ref).collect::<Vec<_>>().join("\n");
+    let mut starts = vec![0];
+    for line in lines {
+        starts.push(starts.last().unwrap_or(&0) + line.as_ref().len() + 1);
+    }
+    let offset = |(row, col): (usize, usize)| match lines.get(row) {
+        Some(line) => starts[row] + line.as_ref().char_indices().nth(col).map_or(line.as_ref().len(), |(i, _)| i),
+        None => text.len(),
+    };
+    let mut ranges: Vec<(usize, usize, &str)> = edits.iter().map(|e| (offset(e.start), offset(e.end), e.new_text.as_str())).collect();
+    // Back to front, so earlier offsets stay valid
+    ranges.sort_by_key(|&(start, end, _)| std::cmp::Reverse((start, end)));
+    for (start, end, new_text) in ranges {
+        let end = end.clamp(start, text.len());
+        text.replace_range(start.min(text.len())..end, new_text);
+    }
+    text
+}
+
+/// The edit turning `lines` into `formatted`, replacing only the lines that
+/// differ; `None` when nothing changes. A final newline in `formatted` ends
+/// the last line rather than adding an empty one.
+pub fn edit_for<S: AsRef<str>>(lines: &[S], formatted: &str) -> Option<TextEdit> {
+    let formatted = formatted.strip_suffix('\n').unwrap_or(formatted);
+    let new: Vec<&str> = formatted.split('\n').map(|l| l.strip_suffix('\r').unwrap_or(l)).collect();
+    let old: Vec<&str> = lines.iter().map(AsRef::as_ref).collect();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    if prefix == old.len() && prefix == new.len() {
+        return None;
+    }
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let (old_end, new_end) = (old.len() - suffix, new.len() - suffix);
+    let middle = new[prefix..new_end].join("\n");
+    let line_end = |row: usize| (row, old.get(row).map_or(0, |l| l.chars().count()));
+
+    let (start, end, new_text) = if prefix == old_end {
+        // Lines inserted
+        match old.len().checked_sub(1) {
+            Some(last) if prefix > last => (line_end(last), line_end(last), format!("\n{}", middle)),
+            _ => ((prefix, 0), (prefix, 0), format!("{}\n", middle)),
+        }
+    } else if prefix == new_end {
+        // Lines removed
+        if old_end < old.len() {
+            ((prefix, 0), (old_end, 0), String::new())
+        } else {
+            (line_end(prefix - 1), line_end(old.len() - 1), String::new())
+        }
+    } else {
+        ((prefix, 0), line_end(old_end - 1), middle)
+    };
+    Some(TextEdit { start, end, new_text })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(lines: &[&str], edit: &TextEdit) -> String {
+        let text = lines.join("\n");
+        let offset = |(row, col): (usize, usize)| {
+            lines[..row].iter().map(|l| l.len() + 1).sum::<usize>() + col
+        };
+        format!("{}{}{}", &text[..offset(edit.start)], edit.new_text, &text[offset(edit.end)..])
+    }
+
+    #[test]
+    fn test_edit_for() {
+        let lines = ["fn main(){", "let x=1;", "}"];
+        assert_eq!(edit_for(&lines, "fn main(){\nlet x=1;\n}\n"), None);
+
+        let changed = edit_for(&lines, "fn main() {\n    let x = 1;\n}\n").unwrap();
+        assert_eq!((changed.start, changed.end), ((0, 0), (1, 8)));
+        assert_eq!(apply(&lines, &changed), "fn main() {\n    let x = 1;\n}");
+
+        let inserted = edit_for(&lines, "fn main(){\nlet x=1;\n}\n\nfn other() {}\n").unwrap();
+        assert_eq!(apply(&lines, &inserted), "fn main(){\nlet x=1;\n}\n\nfn other() {}");
+        let inserted = edit_for(&lines, "fn main(){\n\nlet x=1;\n}").unwrap();
+        assert_eq!(apply(&lines, &inserted), "fn main(){\n\nlet x=1;\n}");
+
+        let removed = edit_for(&lines, "fn main(){\n}\n").unwrap();
+        assert_eq!(apply(&lines, &removed), "fn main(){\n}");
+        let removed = edit_for(&lines, "fn main(){\n").unwrap();
+        assert_eq!(apply(&lines, &removed), "fn main(){");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_and_apply() {
+        let lines = ["fn  main(){", "  é();", "}"];
+        let edits = [
+            TextEdit { start: (0, 2), end: (0, 4), new_text: " ".to_string() },
+            TextEdit { start: (0, 10), end: (0, 10), new_text: " ".to_string() },
+            TextEdit { start: (1, 0), end: (1, 2), new_text: "    ".to_string() },
+            TextEdit { start: (2, 1), end: (3, 0), new_text: "\n".to_string() },
+        ];
+        assert_eq!(apply_edits(&lines, &edits), "fn main() {\n    é();\n}\n");
+
+        let root = std::env::temp_dir();
+        assert_eq!(run("tr a-z A-Z", &root, "let x;\n"), Ok("LET X;\n".to_string()));
+        assert_eq!(run("echo 'error: expected `;`' >&2; exit 1", &root, "let x"), Err("error: expected `;`".to_string()));
+        assert!(run("true", &root, "let x;\n").is_err());
+    }
+}
@@ -0,0 +1,103 @@
+// Git change markers: which lines of a buffer were added, modified or deleted
+// relative to the version of the file in HEAD
+
+use std::path::Path;
+use std::process::Command;
+
+use super::diff::{self, Algorithm, DiffKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineChange {
+    Added,
+    Modified,
+    /// Lines were removed just above this one
+    Deleted,
+}
+
+impl LineChange {
+    /// Gutter marker
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            LineChange::Added => "+",
+            LineChange::Modified => "~",
+            LineChange::Deleted => "−",
+        }
+    }
+}
+
+/// Lines of `path` as committed in HEAD. `None` outside a git repository or
+/// for files git doesn't track. Runs `git`, so call it off the UI thread.
+pub fn head_lines(root: &Path, path: &Path) -> Option<Vec<String>> {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    // `./` makes git resolve the path from the workspace, which may be below the repository root
+    let output = Command::new("git")
+        .arg("show")
+        .arg(format!("HEAD:./{}", relative.display()))
+        .current_dir(root)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).lines().map(str::to_string).collect())
+}
+
+/// Changed lines of `lines` against `head`, sorted by line. A deletion is
+/// marked on the line after it (or the last line when the end was deleted).
+pub fn changes(algorithm: Algorithm, head: &[String], lines: &[String]) -> Vec<(usize, LineChange)> {
+    let mut found: Vec<(usize, LineChange)> = Vec::new();
+    let mut next_line = 0;
+    for row in diff::diff_lines(algorithm, head, lines) {
+        match (row.kind, row.right) {
+            (DiffKind::Added, Some(line)) => found.push((line, LineChange::Added)),
+            (DiffKind::Changed, Some(line)) => found.push((line, LineChange::Modified)),
+            (DiffKind::Removed, _) => {
+                let line = next_line.min(lines.len().saturating_sub(1));
+                if found.last().is_none_or(|&(l, _)| l != line) {
+                    found.push((line, LineChange::Deleted));
+                }
+            }
+            _ => {}
+        }
+        if let Some(line) = row.right {
+            next_line = line + 1;
+        }
+    }
+    found.sort_by_key(|&(line, _)| line);
+    found.dedup_by_key(|(line, _)| *line);
+    found
+}
+
+/// First lines of each run of changed lines
+pub fn hunk_starts(changes: &[(usize, LineChange)]) -> Vec<usize> {
+    changes.iter()
+        .enumerate()
+        .filter(|&(i, &(line, _))| i == 0 || changes[i - 1].0 + 1 != line)
+        .map(|(_, &(line, _))| line)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_changes() {
+        let head = lines("a\nb\nc\nd\ne");
+        let buffer = lines("a\nB\nc\ne\nf\ng");
+        let found = changes(Algorithm::Myers, &head, &buffer);
+        assert_eq!(found, vec![
+            (1, LineChange::Modified),
+            (3, LineChange::Deleted),
+            (4, LineChange::Added),
+            (5, LineChange::Added),
+        ]);
+        assert_eq!(hunk_starts(&found), vec![1, 3]);
+        assert_eq!(changes(Algorithm::Patience, &head, &lines("a\nb\nc")), vec![(2, LineChange::Deleted)]);
+        assert!(changes(Algorithm::Myers, &head, &head).is_empty());
+    }
+}
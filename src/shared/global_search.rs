@@ -0,0 +1,218 @@
+// "Search everything": workspace file names and contents, chat messages and
+// terminal history matched against one query and merged into a single list,
+// best matches first. Open buffers can also be searched on their own, with
+// their unsaved edits.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::chat::ChatMessage;
+use super::workspace;
+
+/// Larger files are left out of content search
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+
+/// Where a result opens
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchTarget {
+    File(PathBuf),
+    /// A 0-based line of a file
+    Line { path: PathBuf, line: usize },
+    /// Index into the chat history
+    Chat(usize),
+    /// A 0-based line of the terminal history
+    Terminal(usize),
+    /// A 0-based line and char column of the open buffer at `index` in tab order
+    Buffer { index: usize, line: usize, col: usize },
+}
+
+impl SearchTarget {
+    /// Short tag shown before the result
+    pub fn tag(&self) -> &'static str {
+        match self {
+            SearchTarget::File(_) => "file",
+            SearchTarget::Line { .. } => "text",
+            SearchTarget::Chat(_) => "chat",
+            SearchTarget::Terminal(_) => "term",
+            SearchTarget::Buffer { .. } => "buf",
+        }
+    }
+
+    /// Breaks ties between equally good matches from different sources
+    fn weight(&self) -> u32 {
+        match self {
+            SearchTarget::File(_) => 3,
+            SearchTarget::Line { .. } | SearchTarget::Buffer { .. } => 2,
+            SearchTarget::Chat(_) => 1,
+            SearchTarget::Terminal(_) => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    pub target: SearchTarget,
+    /// What the palette shows, e.g. `src/main.rs:12: fn main() {`
+    pub text: String,
+    pub score: u32,
+}
+
+impl SearchHit {
+    fn new(target: SearchTarget, text: String, quality: u32) -> Self {
+        let score = quality * 10 + target.weight();
+        Self { target, text, score }
+    }
+}
+
+/// How well `text` matches `query` (lowercase), or `None` when it doesn't
+/// contain it: matches at the start of a word beat ones inside a word, and a
+/// match of the whole text beats both
+pub fn match_quality(text: &str, query: &str) -> Option<u32> {
+    let lower = text.to_lowercase();
+    let at = lower.find(query)?;
+    let word_start = !lower[..at].chars().next_back().is_some_and(char::is_alphanumeric);
+    Some(if lower.trim() == query { 3 } else if word_start { 2 } else { 1 })
+}
+
+/// File names and lines of files under `root` matching `query`, at most
+/// `limit`. Reads files, so call it off the UI thread.
+pub fn search_workspace(root: &Path, query: &str, limit: usize) -> Vec<SearchHit> {
+    let query = query.trim().to_lowercase();
+    let mut hits = Vec::new();
+    if query.is_empty() {
+        return hits;
+    }
+    for entry in workspace::walk_files(root) {
+        if hits.len() >= limit {
+            break;
+        }
+        let path = entry.path();
+        let relative = path.strip_prefix(root).unwrap_or(path).to_path_buf();
+        let name = entry.file_name().to_string_lossy();
+        if let Some(quality) = match_quality(&name, &query) {
+            hits.push(SearchHit::new(SearchTarget::File(relative.clone()), relative.display().to_string(), quality + 1));
+        }
+        if entry.metadata().is_ok_and(|m| m.len() > MAX_FILE_BYTES) {
+            continue;
+        }
+        // Binary and non-UTF-8 files fail here and are skipped
+        let Ok(content) = fs::read_to_string(path) else { continue };
+        for (line, text) in content.lines().enumerate() {
+            if hits.len() >= limit {
+                break;
+            }
+            if let Some(quality) = match_quality(text, &query) {
+                let text = format!("{}:{}: {}", relative.display(), line + 1, text.trim());
+                hits.push(SearchHit::new(SearchTarget::Line { path: relative.clone(), line }, text, quality));
+            }
+        }
+    }
+    hits
+}
+
+/// Lines of the open buffers, given as (name, lines) in tab order, containing
+/// `query`, in buffer and line order; at most `limit`
+pub fn search_buffers<'a>(buffers: impl IntoIterator<Item = (String, &'a [String])>, query: &str, limit: usize) -> Vec<SearchHit> {
+    let query = query.trim().to_lowercase();
+    let mut hits = Vec::new();
+    if query.is_empty() {
+        return hits;
+    }
+    for (index, (name, lines)) in buffers.into_iter().enumerate() {
+        for (line, text) in lines.iter().enumerate() {
+            if hits.len() >= limit {
+                return hits;
+            }
+            let lower = text.to_lowercase();
+            let Some(at) = lower.find(&query) else { continue };
+            let col = lower[..at].chars().count();
+            let text = format!("{}:{}: {}", name, line + 1, text.trim());
+            // One quality for all, so ranking keeps them in order
+            hits.push(SearchHit::new(SearchTarget::Buffer { index, line, col }, text, 1));
+        }
+    }
+    hits
+}
+
+/// Chat messages containing `query`, newest first, shown by their matching line
+pub fn search_chat(messages: &[ChatMessage], query: &str) -> Vec<SearchHit> {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    messages.iter().enumerate().rev()
+        .filter_map(|(i, message)| {
+            let (line, quality) = message.content.lines().find_map(|l| match_quality(l, &query).map(|q| (l, q)))?;
+            let text = match message.role.label() {
+                Some(label) => format!("{}: {}", label, line.trim()),
+                None => line.trim().to_string(),
+            };
+            Some(SearchHit::new(SearchTarget::Chat(i), text, quality))
+        })
+        .collect()
+}
+
+/// Lines of terminal history containing `query`, newest first
+pub fn search_terminal(lines: &[String], query: &str) -> Vec<SearchHit> {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    lines.iter().enumerate().rev()
+        .filter_map(|(i, line)| {
+            let quality = match_quality(line, &query)?;
+            Some(SearchHit::new(SearchTarget::Terminal(i), line.trim().to_string(), quality))
+        })
+        .collect()
+}
+
+/// Best `limit` hits, keeping the given order among equal scores
+pub fn rank(mut hits: Vec<SearchHit>, limit: usize) -> Vec<SearchHit> {
+    hits.sort_by_key(|hit| std::cmp::Reverse(hit.score));
+    hits.truncate(limit);
+    hits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_match_quality() {
+        assert_eq!(match_quality("Config", "config"), Some(3));
+        assert_eq!(match_quality("load_config()", "config"), Some(2));
+        assert_eq!(match_quality("reconfigure", "config"), Some(1));
+        assert_eq!(match_quality("settings", "config"), None);
+    }
+
+    #[test]
+    fn test_search_and_rank() {
+        let root = std::env::temp_dir().join(format!("nterm-global-search-{}", std::process::id()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/parser.rs"), "fn main() {}\nfn parse() {}\n").unwrap();
+        fs::write(root.join("notes.txt"), "the parser is slow").unwrap();
+
+        let workspace = search_workspace(&root, "Parse", 10);
+        assert!(workspace.contains(&SearchHit::new(SearchTarget::File(PathBuf::from("src/parser.rs")), "src/parser.rs".to_string(), 3)));
+        assert!(workspace.iter().any(|h| h.target == SearchTarget::Line { path: PathBuf::from("src/parser.rs"), line: 1 }));
+        assert_eq!(search_workspace(&root, "parse", 1).len(), 1);
+
+        let chat = search_chat(&[ChatMessage::user("how do I parse this?"), ChatMessage::user("thanks")], "parse");
+        assert_eq!(chat.iter().map(|h| h.text.as_str()).collect::<Vec<_>>(), vec!["You: how do I parse this?"]);
+        let terminal = search_terminal(&["$ cargo run".to_string(), "error: parse failed".to_string()], "parse");
+        assert_eq!(terminal[0].target, SearchTarget::Terminal(1));
+
+        let unsaved = vec!["fn main() {".to_string(), "    let tree = Parser::new();".to_string()];
+        let scratch = vec!["parse later".to_string()];
+        let buffers = search_buffers([("main.rs".to_string(), unsaved.as_slice()), ("notes".to_string(), scratch.as_slice())], "parser", 10);
+        assert_eq!(buffers.iter().map(|h| (&h.target, h.text.as_str())).collect::<Vec<_>>(), vec![
+            (&SearchTarget::Buffer { index: 0, line: 1, col: 15 }, "main.rs:2: let tree = Parser::new();"),
+        ]);
+        assert_eq!(search_buffers([("notes".to_string(), scratch.as_slice())], "PARSE", 10)[0].target, SearchTarget::Buffer { index: 0, line: 0, col: 0 });
+
+        let ranked = rank([workspace, chat, terminal].concat(), 3);
+        assert_eq!(ranked[0].target, SearchTarget::File(PathBuf::from("src/parser.rs")));
+        assert_eq!(ranked.len(), 3);
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,211 @@
+// Non-interactive commands for scripts and CI, run without starting a UI:
+//
+//   nterm-cli ask "explain this" --file src/lib.rs [--model NAME] [--allow-secrets]
+//   nterm-cli search main [--limit N]
+//   nterm-cli history [QUERY] [--limit N]
+//   nterm-cli usage
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::ai::{self, ModelConfig};
+use super::config::Config;
+use super::chat::format_date_time;
+use super::storage;
+use super::{search, secrets, workspace};
+
+/// Past chat messages listed by `history` unless `--limit` says otherwise
+const HISTORY_LIMIT: usize = 20;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HeadlessCommand {
+    /// Send a prompt, with files attached, to the selected (or named) model
+    Ask {
+        prompt: String,
+        files: Vec<PathBuf>,
+        model: Option<String>,
+        allow_secrets: bool,
+    },
+    /// List workspace files whose name contains the pattern
+    Search { pattern: String, limit: usize },
+    /// Past chat messages (from every workspace) containing the query, newest first
+    History { query: String, limit: usize },
+    /// Tokens used per model
+    Usage,
+}
+
+impl HeadlessCommand {
+    /// Parse the arguments after the program name. `None` when they don't
+    /// start with a headless subcommand, so the UI should start.
+    pub fn parse(args: &[String]) -> Option<Result<Self, String>> {
+        let (name, rest) = args.split_first()?;
+        match name.as_str() {
+            "ask" => Some(Self::parse_ask(rest)),
+            "search" => Some(Self::parse_search(rest)),
+            "history" => Some(Self::parse_history(rest)),
+            "usage" => Some(Ok(HeadlessCommand::Usage)),
+            _ => None,
+        }
+    }
+
+    fn parse_ask(args: &[String]) -> Result<Self, String> {
+        let (mut words, mut files, mut model, mut allow_secrets) = (Vec::new(), Vec::new(), None, false);
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--file" | "-f" => files.push(PathBuf::from(args.next().ok_or("--file needs a path")?)),
+                "--model" | "-m" => model = Some(args.next().ok_or("--model needs a name")?.clone()),
+                "--allow-secrets" => allow_secrets = true,
+                _ => words.push(arg.as_str()),
+            }
+        }
+        if words.is_empty() {
+            return Err("usage: nterm-cli ask \"prompt\" [--file PATH]... [--model NAME] [--allow-secrets]".to_string());
+        }
+        Ok(HeadlessCommand::Ask { prompt: words.join(" "), files, model, allow_secrets })
+    }
+
+    fn parse_search(args: &[String]) -> Result<Self, String> {
+        let (pattern, limit) = words_and_limit(args, usize::MAX)?;
+        if pattern.is_empty() {
+            return Err("usage: nterm-cli search PATTERN [--limit N]".to_string());
+        }
+        Ok(HeadlessCommand::Search { pattern, limit })
+    }
+
+    fn parse_history(args: &[String]) -> Result<Self, String> {
+        let (query, limit) = words_and_limit(args, HISTORY_LIMIT)?;
+        Ok(HeadlessCommand::History { query, limit })
+    }
+}
+
+/// The words of `args` joined with spaces, and the `--limit` value (or `default`)
+fn words_and_limit(args: &[String], default: usize) -> Result<(String, usize), String> {
+    let (mut words, mut limit) = (Vec::new(), default);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--limit" | "-n" => {
+                let value = args.next().ok_or("--limit needs a number")?;
+                limit = value.parse().map_err(|_| format!("invalid limit '{}'", value))?;
+            }
+            _ => words.push(arg.as_str()),
+        }
+    }
+    Ok((words.join(" "), limit))
+}
+
+/// Run `command` in `root` and return what to print
+pub fn run(command: &HeadlessCommand, root: &Path) -> Result<String, String> {
+    match command {
+        HeadlessCommand::Ask { prompt, files, model, allow_secrets } => {
+            let config = Config::load();
+            let model = select_model(&config, model.as_deref())?;
+            let input = ask_input(prompt, files, &config.secret_allowlist, *allow_secrets)?;
+            let runtime = tokio::runtime::Runtime::new().map_err(|e| e.to_string())?;
+            runtime.block_on(ai::send_message(model, &[], &input))
+                .map(|reply| reply.trim().to_string())
+                .map_err(|e| format!("{}: {}", model.display_name(), ai::client::describe_error(model, &e)))
+        }
+        HeadlessCommand::Search { pattern, limit } => {
+            workspace::set_excluded(root, &Config::load().excluded_dirs(root));
+            let found = search::find_files(root, pattern, *limit);
+            let lines: Vec<String> = found.iter()
+                .map(|path| path.strip_prefix(root).unwrap_or(path).display().to_string())
+                .collect();
+            Ok(lines.join("\n"))
+        }
+        HeadlessCommand::History { query, limit } => {
+            let found = storage::open_default()?.search_messages(query, *limit)?;
+            let lines: Vec<String> = found.iter().rev()
+                .map(|stored| {
+                    let message = &stored.message;
+                    let who = message.role.label().unwrap_or("nterm");
+                    let first = message.content.lines().next().unwrap_or("");
+                    format!("{}  {}  {}: {}", format_date_time(message.timestamp), stored.workspace.display(), who, first)
+                })
+                .collect();
+            Ok(lines.join("\n"))
+        }
+        HeadlessCommand::Usage => {
+            let usage = storage::open_default()?.usage_by_model()?;
+            let lines: Vec<String> = usage.iter()
+                .map(|u| format!("{}: {} replies, {} input / {} output tokens", u.model, u.replies, u.input, u.output))
+                .collect();
+            Ok(lines.join("\n"))
+        }
+    }
+}
+
+/// The configured model whose name or model id is `name`, or the selected one
+fn select_model<'a>(config: &'a Config, name: Option<&str>) -> Result<&'a ModelConfig, String> {
+    let Some(name) = name else { return Ok(config.get_selected_model()) };
+    config.models.iter()
+        .find(|m| m.name.eq_ignore_ascii_case(name) || m.model_id.eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("no model named '{}' in the config", name))
+}
+
+/// The prompt with each file attached in a code block. Refuses files that look
+/// like they hold secrets unless `allow_secrets`, as the chat panel asks first.
+fn ask_input(prompt: &str, files: &[PathBuf], allowlist: &[String], allow_secrets: bool) -> Result<String, String> {
+    let mut input = String::new();
+    for path in files {
+        let text = fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+        if !allow_secrets {
+            if let Some(found) = secrets::scan(&text, allowlist).first() {
+                return Err(format!(
+                    "{} looks like it contains a secret ({} on line {}); pass --allow-secrets to send it anyway",
+                    path.display(), found.kind, found.line
+                ));
+            }
+        }
+        input.push_str(&format!("File: {}\n```\n{}\n```\n\n", path.display(), text.trim_end()));
+    }
+    input.push_str(prompt);
+    Ok(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(HeadlessCommand::parse(&args("ask explain this -f src/lib.rs --model echo")), Some(Ok(HeadlessCommand::Ask {
+            prompt: "explain this".to_string(),
+            files: vec![PathBuf::from("src/lib.rs")],
+            model: Some("echo".to_string()),
+            allow_secrets: false,
+        })));
+        assert_eq!(HeadlessCommand::parse(&args("search main --limit 3")), Some(Ok(HeadlessCommand::Search {
+            pattern: "main".to_string(),
+            limit: 3,
+        })));
+        assert_eq!(HeadlessCommand::parse(&args("history")), Some(Ok(HeadlessCommand::History {
+            query: String::new(),
+            limit: HISTORY_LIMIT,
+        })));
+        assert!(HeadlessCommand::parse(&args("ask --file x")).unwrap().is_err());
+        assert!(HeadlessCommand::parse(&args("--new-window")).is_none());
+    }
+
+    #[test]
+    fn test_ask_input() {
+        let dir = std::env::temp_dir().join(format!("nterm-headless-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (code, env) = (dir.join("a.rs"), dir.join(".env"));
+        fs::write(&code, "fn main() {}\n").unwrap();
+        fs::write(&env, "API_KEY=abcdef123456\n").unwrap();
+
+        let input = ask_input("why?", std::slice::from_ref(&code), &[], false).unwrap();
+        assert_eq!(input, format!("File: {}\n```\nfn main() {{}}\n```\n\nwhy?", code.display()));
+        let err = ask_input("why?", std::slice::from_ref(&env), &[], false).unwrap_err();
+        assert!(err.contains("--allow-secrets"));
+        assert!(ask_input("why?", &[env], &[], true).is_ok());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
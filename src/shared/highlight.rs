@@ -0,0 +1,111 @@
+// Highlighting backends: syntect's per-line grammars (the default), or
+// tree-sitter parse trees, re-parsed incrementally as the text is edited, in
+// builds with the `tree-sitter` feature. Both produce `HighlightSpan`s, which
+// is all an editor draws from.
+
+use std::sync::RwLock;
+
+use serde::{Deserialize, Serialize};
+use syntect::easy::HighlightLines;
+use syntect::highlighting::Color;
+
+use super::syntax::SyntaxAssets;
+
+/// Foreground of the chars `start..end` (char columns) of one line; `None`
+/// leaves the editor's default
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HighlightSpan {
+    pub start: usize,
+    pub end: usize,
+    pub fg: Option<[u8; 3]>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HighlightBackend {
+    #[default]
+    Syntect,
+    /// Needs a build with `--features tree-sitter`; languages without a
+    /// bundled grammar still use syntect
+    TreeSitter,
+}
+
+impl HighlightBackend {
+    /// Whether this build can highlight with the backend
+    pub fn available(self) -> bool {
+        match self {
+            HighlightBackend::Syntect => true,
+            HighlightBackend::TreeSitter => cfg!(feature = "tree-sitter"),
+        }
+    }
+}
+
+static BACKEND: RwLock<HighlightBackend> = RwLock::new(HighlightBackend::Syntect);
+
+/// Switch the backend editors highlight with; one this build lacks falls back
+/// to syntect. Returns the backend now in use.
+pub fn set_backend(backend: HighlightBackend) -> HighlightBackend {
+    let backend = if backend.available() { backend } else { HighlightBackend::Syntect };
+    if let Ok(mut current) = BACKEND.write() {
+        *current = backend;
+    }
+    backend
+}
+
+pub fn backend() -> HighlightBackend {
+    BACKEND.read().map(|b| *b).unwrap_or_default()
+}
+
+/// Spans for a line colored char by char, runs of the same color merged
+pub fn spans_from_colors(colors: &[Option<[u8; 3]>]) -> Vec<HighlightSpan> {
+    let mut spans: Vec<HighlightSpan> = Vec::new();
+    for (col, &fg) in colors.iter().enumerate() {
+        match spans.last_mut() {
+            Some(span) if span.fg == fg => span.end = col + 1,
+            _ => spans.push(HighlightSpan { start: col, end: col + 1, fg }),
+        }
+    }
+    spans
+}
+
+/// RGB of a theme color; fully transparent ones are left to the editor
+pub fn rgb(color: Color) -> Option<[u8; 3]> {
+    (color.a > 0).then_some([color.r, color.g, color.b])
+}
+
+/// One line highlighted on its own with syntect; slow on long lines
+pub fn syntect_line(assets: &SyntaxAssets, language: Option<&str>, theme: &str, content: &str) -> Vec<HighlightSpan> {
+    let mut highlighter = HighlightLines::new(assets.find_syntax(language), assets.theme(theme));
+    let Ok(ranges) = highlighter.highlight_line(content, &assets.syntax_set) else {
+        return Vec::new();
+    };
+    let mut spans = Vec::new();
+    let mut col = 0;
+    for (style, text) in ranges {
+        let len = text.chars().count();
+        if len > 0 {
+            spans.push(HighlightSpan { start: col, end: col + len, fg: rgb(style.foreground) });
+        }
+        col += len;
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spans_and_backend() {
+        let (red, blue) = (Some([255, 0, 0]), Some([0, 0, 255]));
+        assert_eq!(spans_from_colors(&[red, red, None, blue]), vec![
+            HighlightSpan { start: 0, end: 2, fg: red },
+            HighlightSpan { start: 2, end: 3, fg: None },
+            HighlightSpan { start: 3, end: 4, fg: blue },
+        ]);
+
+        let backend: HighlightBackend = serde_json::from_str("\"tree-sitter\"").unwrap();
+        assert_eq!(backend, HighlightBackend::TreeSitter);
+        assert_eq!(backend.available(), cfg!(feature = "tree-sitter"));
+    }
+}
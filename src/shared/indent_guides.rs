@@ -0,0 +1,75 @@
+// Indentation guides: the columns where each indentation level of a line
+// starts, drawn as faint vertical lines by the editors. Blank lines continue
+// the guides of the code around them.
+
+/// How far to look for a non-blank line around a blank one
+const BLANK_SCAN_LINES: usize = 100;
+
+/// Columns (in chars) of the indentation levels of `line` before its text;
+/// a tab is one level, spaces make a level every `width` columns
+fn level_starts(line: &str, width: usize) -> Vec<usize> {
+    let width = width.max(1);
+    let mut starts = Vec::new();
+    let mut spaces = 0;
+    for (col, c) in line.chars().enumerate() {
+        match c {
+            '\t' => {
+                starts.push(col);
+                spaces = 0;
+            }
+            ' ' => {
+                if spaces % width == 0 {
+                    starts.push(col);
+                }
+                spaces += 1;
+            }
+            _ => break,
+        }
+    }
+    starts
+}
+
+/// Columns to draw guides at on `lines[row]`
+pub fn guide_columns<S: AsRef<str>>(lines: &[S], row: usize, width: usize) -> Vec<usize> {
+    let Some(line) = lines.get(row).map(AsRef::as_ref) else { return Vec::new() };
+    if !line.trim().is_empty() {
+        return level_starts(line, width);
+    }
+    let non_blank = |line: &&S| !line.as_ref().trim().is_empty();
+    let above = lines[..row].iter().rev().take(BLANK_SCAN_LINES).find(non_blank);
+    let below = lines[row + 1..].iter().take(BLANK_SCAN_LINES).find(non_blank);
+    match (above, below) {
+        (Some(above), Some(below)) => {
+            let (above, below) = (level_starts(above.as_ref(), width), level_starts(below.as_ref(), width));
+            if above.len() <= below.len() { above } else { below }
+        }
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guide_columns() {
+        let lines = [
+            "fn main() {",
+            "    if ready {",
+            "        start();",
+            "",
+            "        stop();",
+            "    }",
+            "\t\tx",
+            "      y",
+            "}",
+        ];
+        assert!(guide_columns(&lines, 0, 4).is_empty());
+        assert_eq!(guide_columns(&lines, 2, 4), vec![0, 4]);
+        assert_eq!(guide_columns(&lines, 3, 4), vec![0, 4]);
+        assert_eq!(guide_columns(&lines, 5, 4), vec![0]);
+        assert_eq!(guide_columns(&lines, 6, 4), vec![0, 1]);
+        assert_eq!(guide_columns(&lines, 7, 4), vec![0, 4]);
+        assert!(guide_columns(&lines, 9, 4).is_empty());
+    }
+}
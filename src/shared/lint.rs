@@ -0,0 +1,112 @@
+// Linters run in the background after a save, e.g. clippy, eslint or shellcheck.
+// Their output is parsed into diagnostics for the problems panel and the gutter.
+
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use super::diagnostics::{parse_build_output, Diagnostic, DiagnosticSource, Severity};
+use super::terminal::quote;
+use super::toolchain::Toolchain;
+
+/// Placeholder in a lint command for the saved file
+pub const FILE_PLACEHOLDER: &str = "{file}";
+
+// `file:line:col: message`, as written by `eslint -f unix` (`[Error/rule]` at the end)
+static UNIX_STYLE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(\S[^:]*):(\d+):(\d+): (.+)$").unwrap()
+});
+static SEVERITY_WORD: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(error|warning|note|info|help)\b").unwrap()
+});
+
+/// The shell command for `template` with `{file}` replaced by the quoted `file`
+pub fn command_for(template: &str, file: &Path) -> String {
+    template.replace(FILE_PLACEHOLDER, &quote(file))
+}
+
+/// Run `command` in `root` and parse what it printed. Blocks until the linter
+/// exits, so call it off the UI thread. A failing exit status is expected when
+/// there are problems; only a command that can't start is an error.
+pub fn run(command: &str, root: &Path) -> Result<Vec<Diagnostic>, String> {
+    let output = Command::new("sh")
+        .args(["-c", command])
+        .current_dir(root)
+        .envs(Toolchain::detect(root).env())
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("could not run `{}`: {}", command, e))?;
+    // clippy reports on stderr, most other linters on stdout
+    let text = format!("{}\n{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+    let mut diagnostics = parse_output(&text);
+    for diagnostic in &mut diagnostics {
+        diagnostic.path = relative_to(root, &diagnostic.path);
+    }
+    Ok(diagnostics)
+}
+
+/// Compiler-style output plus `file:line:col: message` lines without a severity
+pub fn parse_output(output: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = parse_build_output(output);
+    for line in output.lines().map(str::trim_end) {
+        let Some(caps) = UNIX_STYLE.captures(line) else { continue };
+        let message = &caps[4];
+        if SEVERITY_WORD.is_match(message) {
+            continue; // already parsed as compiler output
+        }
+        diagnostics.push(Diagnostic {
+            path: PathBuf::from(&caps[1]),
+            line: caps[2].parse().unwrap_or(1),
+            column: caps[3].parse().unwrap_or(1),
+            severity: if message.contains("[Error") { Severity::Error } else { Severity::Warning },
+            message: message.to_string(),
+            code: None,
+            source: DiagnosticSource::Lint,
+        });
+    }
+    for diagnostic in &mut diagnostics {
+        diagnostic.source = DiagnosticSource::Lint;
+    }
+    diagnostics
+}
+
+/// Workspace-relative path without `.` components, as the other sources report them
+fn relative_to(root: &Path, path: &Path) -> PathBuf {
+    let path = path.strip_prefix(root).unwrap_or(path);
+    path.components().filter(|c| !matches!(c, Component::CurDir)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_output() {
+        let output = "\
+warning: unused variable: `x`
+ --> src/main.rs:2:9
+deploy.sh:3:6: note: Double quote to prevent globbing. [SC2086]
+/work/app.js:1:7: 'a' is assigned a value but never used. [Error/no-unused-vars]
+/work/app.js:2:1: Unexpected console statement. [Warning/no-console]
+";
+        let found = parse_output(output);
+        assert_eq!(found.len(), 4);
+        assert!(found.iter().all(|d| d.source == DiagnosticSource::Lint));
+        assert_eq!((found[0].path.as_path(), found[0].severity), (Path::new("src/main.rs"), Severity::Warning));
+        assert_eq!(found[1].severity, Severity::Info);
+        assert_eq!((found[2].line, found[2].severity), (1, Severity::Error));
+        assert_eq!(found[3].severity, Severity::Warning);
+        assert_eq!(relative_to(Path::new("/work"), &found[2].path), PathBuf::from("app.js"));
+    }
+
+    #[test]
+    fn test_run() {
+        let root = std::env::temp_dir();
+        let command = command_for("echo {file}:4:2: trailing whitespace; exit 1", Path::new("it's.txt"));
+        let found = run(&command, &root).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!((found[0].path.as_path(), found[0].line), (Path::new("it's.txt"), 4));
+    }
+}
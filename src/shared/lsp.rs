@@ -0,0 +1,708 @@
+// Language server client: the server configured for a file type is started
+// for the workspace and spoken to in JSON-RPC over its stdin and stdout.
+// Replies and notifications are read on a background thread and handed to a
+// callback; requests the server itself makes are answered with null.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+
+use super::toolchain::Toolchain;
+
+/// Id of the `initialize` request; the rest are numbered from 1
+const INITIALIZE_ID: u64 = 0;
+
+/// How long dropping a client waits for the server to answer `shutdown`
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How long it then waits for the server to exit before killing it
+const EXIT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Something the server sent
+#[derive(Debug, Clone, PartialEq)]
+pub enum LspMessage {
+    /// Reply to the request with this id
+    Response { id: u64, result: Result<Value, String> },
+    Notification { method: String, params: Value },
+    /// The server exited or closed its output
+    Exited,
+}
+
+/// A change to a document, with (row, col) positions in chars
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextEdit {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    pub new_text: String,
+}
+
+impl TextEdit {
+    /// An LSP `TextEdit` (or the insert range of an `InsertReplaceEdit`) against `lines`
+    pub fn from_json<S: AsRef<str>>(value: &Value, lines: &[S]) -> Option<Self> {
+        let range = value.get("range").or_else(|| value.get("insert"))?;
+        Some(Self {
+            start: position_from_json(range.get("start")?, lines)?,
+            end: position_from_json(range.get("end")?, lines)?,
+            new_text: value.get("newText")?.as_str()?.to_string(),
+        })
+    }
+}
+
+/// One entry of a completion reply
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompletionItem {
+    pub label: String,
+    /// LSP `CompletionItemKind`, 0 when the server didn't say
+    pub kind: u8,
+    pub detail: Option<String>,
+    /// What typing is matched against
+    pub filter_text: String,
+    pub sort_text: String,
+    /// Replaces the word being typed when the server gave no `edit`
+    pub insert_text: String,
+    pub edit: Option<TextEdit>,
+    /// Edits elsewhere in the file, e.g. an import the item needs
+    pub additional_edits: Vec<TextEdit>,
+}
+
+impl CompletionItem {
+    /// One-letter marker of what the item is: `f` functions and methods, `v`
+    /// variables and constants, `p` fields, `t` types, `m` modules, `k` keywords
+    pub fn icon(&self) -> &'static str {
+        match self.kind {
+            2..=4 => "f",
+            5 | 10 => "p",
+            6 | 12 | 21 => "v",
+            7 | 8 | 13 | 22 | 25 => "t",
+            9 | 17 | 19 => "m",
+            14 => "k",
+            15 => "s",
+            20 => "e",
+            _ => "·",
+        }
+    }
+}
+
+/// Items of a `textDocument/completion` reply (a list or a `CompletionList`),
+/// for the document `lines` it was asked about
+pub fn parse_completions<S: AsRef<str>>(result: &Value, lines: &[S]) -> Vec<CompletionItem> {
+    let items = result.get("items").unwrap_or(result).as_array().cloned().unwrap_or_default();
+    items.iter()
+        .filter_map(|item| {
+            let label = item.get("label")?.as_str()?.to_string();
+            let text = |key: &str| item.get(key).and_then(Value::as_str).map(str::to_string);
+            let edit = item.get("textEdit").and_then(|edit| TextEdit::from_json(edit, lines));
+            Some(CompletionItem {
+                kind: item.get("kind").and_then(Value::as_u64).unwrap_or(0) as u8,
+                detail: text("detail").filter(|d| !d.is_empty()),
+                filter_text: text("filterText").unwrap_or_else(|| label.clone()),
+                sort_text: text("sortText").unwrap_or_else(|| label.clone()),
+                insert_text: edit.as_ref().map(|e| e.new_text.clone()).or_else(|| text("insertText")).unwrap_or_else(|| label.clone()),
+                additional_edits: item.get("additionalTextEdits").and_then(Value::as_array).into_iter().flatten()
+                    .filter_map(|edit| TextEdit::from_json(edit, lines))
+                    .collect(),
+                edit,
+                label,
+            })
+        })
+        .collect()
+}
+
+/// Text a server wants shown inside a line, e.g. the inferred type after a
+/// binding or a parameter name before an argument
+#[derive(Debug, Clone, PartialEq)]
+pub struct InlayHint {
+    pub row: usize,
+    /// Char column the hint is drawn before
+    pub col: usize,
+    /// The hint's text with its padding
+    pub label: String,
+}
+
+/// Hints of a `textDocument/inlayHint` reply for the document `lines`, in order
+pub fn parse_inlay_hints<S: AsRef<str>>(result: &Value, lines: &[S]) -> Vec<InlayHint> {
+    let mut hints: Vec<InlayHint> = result.as_array().into_iter().flatten()
+        .filter_map(|hint| {
+            let (row, col) = position_from_json(hint.get("position")?, lines)?;
+            // A string, or parts that may each link somewhere
+            let text = match hint.get("label")? {
+                Value::String(label) => label.clone(),
+                Value::Array(parts) => parts.iter().filter_map(|p| p.get("value").and_then(Value::as_str)).collect(),
+                _ => return None,
+            };
+            let padded = |key: &str| hint.get(key).and_then(Value::as_bool).unwrap_or(false);
+            let label = format!(
+                "{}{}{}",
+                if padded("paddingLeft") { " " } else { "" },
+                text.replace(['\n', '\t'], " "),
+                if padded("paddingRight") { " " } else { "" },
+            );
+            (!text.is_empty()).then_some(InlayHint { row, col, label })
+        })
+        .collect();
+    hints.sort_by_key(|h| (h.row, h.col));
+    hints
+}
+
+/// A place in a file a definition or references reply points at
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub path: PathBuf,
+    pub line: usize,
+    /// LSP column (UTF-16 code units); see [`char_col`]
+    pub character: usize,
+}
+
+/// The locations in a definition or references reply: a `Location`, a list of
+/// them, or a list of `LocationLink`s
+pub fn parse_locations(result: &Value) -> Vec<Location> {
+    let values = match result {
+        Value::Array(values) => values.iter().collect(),
+        Value::Object(_) => vec![result],
+        _ => Vec::new(),
+    };
+    values.into_iter()
+        .filter_map(|value| {
+            let uri = value.get("uri").or_else(|| value.get("targetUri"))?.as_str()?;
+            let range = value.get("range").or_else(|| value.get("targetSelectionRange"))?;
+            Some(Location {
+                path: uri_to_path(uri)?,
+                line: range.pointer("/start/line")?.as_u64()? as usize,
+                character: range.pointer("/start/character")?.as_u64()? as usize,
+            })
+        })
+        .collect()
+}
+
+/// The text edits of a `WorkspaceEdit` (its `changes` or `documentChanges`)
+/// per file, still in LSP positions. Creating, renaming or deleting files
+/// isn't supported.
+pub fn workspace_edit_files(edit: &Value) -> Result<Vec<(PathBuf, Vec<Value>)>, String> {
+    let mut files: Vec<(PathBuf, Vec<Value>)> = Vec::new();
+    let mut add = |uri: &str, edits: &Value| -> Result<(), String> {
+        let path = uri_to_path(uri).ok_or_else(|| format!("unsupported document {}", uri))?;
+        let edits = edits.as_array().cloned().unwrap_or_default();
+        match files.iter_mut().find(|(p, _)| *p == path) {
+            Some((_, existing)) => existing.extend(edits),
+            None => files.push((path, edits)),
+        }
+        Ok(())
+    };
+    if let Some(changes) = edit.get("documentChanges").and_then(Value::as_array) {
+        for change in changes {
+            if change.get("kind").is_some() {
+                return Err("the edit creates, renames or deletes files".to_string());
+            }
+            let uri = change.pointer("/textDocument/uri").and_then(Value::as_str).unwrap_or("");
+            add(uri, change.get("edits").unwrap_or(&Value::Null))?;
+        }
+    } else if let Some(changes) = edit.get("changes").and_then(Value::as_object) {
+        for (uri, edits) in changes {
+            add(uri, edits)?;
+        }
+    }
+    Ok(files)
+}
+
+/// The LSP language id of a file, by extension
+pub fn language_id(path: &Path) -> String {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    let id = match extension.as_str() {
+        "rs" => "rust",
+        "py" | "pyi" => "python",
+        "js" | "mjs" | "cjs" => "javascript",
+        "jsx" => "javascriptreact",
+        "ts" | "mts" | "cts" => "typescript",
+        "tsx" => "typescriptreact",
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "hpp" | "hh" => "cpp",
+        "cs" => "csharp",
+        "rb" => "ruby",
+        "sh" | "bash" => "shellscript",
+        "md" => "markdown",
+        "yml" => "yaml",
+        other => other,
+    };
+    id.to_string()
+}
+
+/// `file://` URI of an absolute path
+pub fn path_to_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => uri.push(byte as char),
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+/// Path of a `file://` URI
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?.as_bytes();
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        let hex = encoded.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok()).and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (encoded[i], hex) {
+            (b'%', Some(byte)) => {
+                bytes.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                bytes.push(byte);
+                i += 1;
+            }
+        }
+    }
+    Some(PathBuf::from(String::from_utf8(bytes).ok()?))
+}
+
+/// LSP column (UTF-16 code units) of the char column `col` of `line`
+pub fn utf16_col(line: &str, col: usize) -> usize {
+    line.chars().take(col).map(char::len_utf16).sum()
+}
+
+/// Char column of the LSP column `utf16` of `line`
+pub fn char_col(line: &str, utf16: usize) -> usize {
+    let mut units = 0;
+    line.chars().take_while(|c| {
+        units += c.len_utf16();
+        units <= utf16
+    }).count()
+}
+
+/// An LSP `Position` at the char position (`row`, `col`) of `lines`
+pub fn position_json<S: AsRef<str>>(lines: &[S], row: usize, col: usize) -> Value {
+    let line = lines.get(row).map(|l| l.as_ref()).unwrap_or("");
+    json!({ "line": row, "character": utf16_col(line, col) })
+}
+
+/// The char position of an LSP `Position` in `lines`
+pub fn position_from_json<S: AsRef<str>>(value: &Value, lines: &[S]) -> Option<(usize, usize)> {
+    let row = value.get("line")?.as_u64()? as usize;
+    let character = value.get("character")?.as_u64()? as usize;
+    let line = lines.get(row).map(|l| l.as_ref()).unwrap_or("");
+    Some((row, char_col(line, character)))
+}
+
+/// Write one message with its `Content-Length` header
+fn write_message(out: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(out, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    out.flush()
+}
+
+/// Read one message; `None` at the end of the stream
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let length = length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "message without Content-Length"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    serde_json::from_slice(&body).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// The server's input; messages wait in `queued` until it has answered `initialize`
+struct Outbox {
+    stdin: ChildStdin,
+    ready: bool,
+    queued: Vec<Value>,
+    capabilities: Value,
+    /// Id of the `shutdown` request and who to tell when it is answered
+    shutdown: Option<(u64, mpsc::Sender<()>)>,
+}
+
+impl Outbox {
+    fn send(&mut self, message: Value) {
+        if self.ready {
+            let _ = write_message(&mut self.stdin, &message);
+        } else {
+            self.queued.push(message);
+        }
+    }
+}
+
+/// A running language server for one workspace
+pub struct LspClient {
+    /// The command it was started with
+    pub command: String,
+    child: Child,
+    outbox: Arc<Mutex<Outbox>>,
+    next_id: u64,
+    /// Version and text last sent for each open document
+    documents: HashMap<PathBuf, (i64, String)>,
+}
+
+impl LspClient {
+    /// Start `command` through the shell in `root` and initialize it. Messages
+    /// from the server are passed to `on_message` on a background thread.
+    pub fn start<F>(command: &str, root: &Path, mut on_message: F) -> Result<Self, String>
+    where
+        F: FnMut(LspMessage) + Send + 'static,
+    {
+        let mut child = Command::new("sh")
+            .args(["-c", command])
+            .current_dir(root)
+            .envs(Toolchain::detect(root).env())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("could not start `{}`: {}", command, e))?;
+        let (Some(mut stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            let _ = child.kill();
+            return Err(format!("could not talk to `{}`", command));
+        };
+
+        let initialize = json!({
+            "jsonrpc": "2.0",
+            "id": INITIALIZE_ID,
+            "method": "initialize",
+            "params": {
+                "processId": std::process::id(),
+                "rootUri": path_to_uri(root),
+                "workspaceFolders": [{ "uri": path_to_uri(root), "name": root.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default() }],
+                "capabilities": {
+                    "textDocument": {
+                        "synchronization": { "didSave": false },
+                        "completion": {
+                            "completionItem": { "snippetSupport": false, "insertReplaceSupport": true },
+                            "contextSupport": true
+                        },
+                        "documentSymbol": { "hierarchicalDocumentSymbolSupport": true },
+                        "formatting": { "dynamicRegistration": false },
+                        "inlayHint": { "dynamicRegistration": false }
+                    }
+                }
+            }
+        });
+        write_message(&mut stdin, &initialize).map_err(|e| format!("could not initialize `{}`: {}", command, e))?;
+
+        let outbox = Arc::new(Mutex::new(Outbox { stdin, ready: false, queued: Vec::new(), capabilities: Value::Null, shutdown: None }));
+        let reader_outbox = outbox.clone();
+        thread::spawn(move || {
+            let mut reader = BufReader::new(stdout);
+            while let Ok(Some(message)) = read_message(&mut reader) {
+                if let Some(message) = Self::receive(&reader_outbox, message) {
+                    on_message(message);
+                }
+            }
+            on_message(LspMessage::Exited);
+        });
+
+        Ok(Self { command: command.to_string(), child, outbox, next_id: INITIALIZE_ID + 1, documents: HashMap::new() })
+    }
+
+    /// Handle the protocol's own messages; anything for the app is returned
+    fn receive(outbox: &Mutex<Outbox>, message: Value) -> Option<LspMessage> {
+        let id = message.get("id").cloned();
+        match (message.get("method").and_then(Value::as_str), id) {
+            // A request from the server: only `workspace/configuration` expects a shaped answer
+            (Some(method), Some(id)) => {
+                let result = match method {
+                    "workspace/configuration" => {
+                        let count = message.pointer("/params/items").and_then(Value::as_array).map_or(0, Vec::len);
+                        Value::Array(vec![Value::Null; count])
+                    }
+                    _ => Value::Null,
+                };
+                if let Ok(mut outbox) = outbox.lock() {
+                    outbox.send(json!({ "jsonrpc": "2.0", "id": id, "result": result }));
+                }
+                None
+            }
+            (Some(method), None) => Some(LspMessage::Notification {
+                method: method.to_string(),
+                params: message.get("params").cloned().unwrap_or(Value::Null),
+            }),
+            (None, Some(id)) => {
+                let id = id.as_u64()?;
+                let result = match message.get("error") {
+                    Some(error) => Err(error.get("message").and_then(Value::as_str).unwrap_or("request failed").to_string()),
+                    None => Ok(message.get("result").cloned().unwrap_or(Value::Null)),
+                };
+                let mut outbox = outbox.lock().ok()?;
+                if let Some((_, done)) = outbox.shutdown.take_if(|(shutdown, _)| *shutdown == id) {
+                    let _ = done.send(());
+                    return None;
+                }
+                if id == INITIALIZE_ID {
+                    outbox.capabilities = result.ok()?.get("capabilities").cloned().unwrap_or(Value::Null);
+                    outbox.ready = true;
+                    let _ = write_message(&mut outbox.stdin, &json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }));
+                    for queued in std::mem::take(&mut outbox.queued) {
+                        let _ = write_message(&mut outbox.stdin, &queued);
+                    }
+                    return None;
+                }
+                Some(LspMessage::Response { id, result })
+            }
+            (None, None) => None,
+        }
+    }
+
+    /// Send a request, returning its id to match the response with
+    pub fn request(&mut self, method: &str, params: Value) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        if let Ok(mut outbox) = self.outbox.lock() {
+            outbox.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+        }
+        id
+    }
+
+    pub fn notify(&mut self, method: &str, params: Value) {
+        if let Ok(mut outbox) = self.outbox.lock() {
+            outbox.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+        }
+    }
+
+    /// Characters that should ask for completions when typed, e.g. `.` and `:`
+    pub fn trigger_characters(&self) -> Vec<String> {
+        let Ok(outbox) = self.outbox.lock() else { return Vec::new() };
+        outbox.capabilities.pointer("/completionProvider/triggerCharacters").and_then(Value::as_array).into_iter()
+            .flatten()
+            .filter_map(|c| c.as_str().map(str::to_string))
+            .collect()
+    }
+
+    /// Whether the server offers `capability`, e.g. `documentFormattingProvider`;
+    /// assumed while it is still starting
+    pub fn provides(&self, capability: &str) -> bool {
+        let Ok(outbox) = self.outbox.lock() else { return false };
+        if !outbox.ready {
+            return true;
+        }
+        match outbox.capabilities.get(capability) {
+            None | Some(Value::Null) | Some(Value::Bool(false)) => false,
+            Some(_) => true,
+        }
+    }
+
+    /// Open the document at `path` (absolute) on the server, or send its text
+    /// again if it changed since it was last sent
+    pub fn sync_document(&mut self, path: &Path, lines: &[String]) {
+        let text = lines.join("\n");
+        let uri = path_to_uri(path);
+        match self.documents.get(path) {
+            Some((_, sent)) if *sent == text => {}
+            Some(&(version, _)) => {
+                self.notify("textDocument/didChange", json!({
+                    "textDocument": { "uri": uri, "version": version + 1 },
+                    "contentChanges": [{ "text": text }],
+                }));
+                self.documents.insert(path.to_path_buf(), (version + 1, text));
+            }
+            None => {
+                self.notify("textDocument/didOpen", json!({
+                    "textDocument": { "uri": uri, "languageId": language_id(path), "version": 1, "text": text },
+                }));
+                self.documents.insert(path.to_path_buf(), (1, text));
+            }
+        }
+    }
+
+    /// Ask for completions at the char position (`row`, `col`) of the
+    /// document at `path`, sending its current text first
+    pub fn completion(&mut self, path: &Path, lines: &[String], row: usize, col: usize, trigger: Option<char>) -> u64 {
+        self.sync_document(path, lines);
+        let context = match trigger {
+            Some(c) => json!({ "triggerKind": 2, "triggerCharacter": c.to_string() }),
+            None => json!({ "triggerKind": 1 }),
+        };
+        self.request("textDocument/completion", json!({
+            "textDocument": { "uri": path_to_uri(path) },
+            "position": position_json(lines, row, col),
+            "context": context,
+        }))
+    }
+
+    /// Ask where the symbol at the char position (`row`, `col`) is defined
+    pub fn definition(&mut self, path: &Path, lines: &[String], row: usize, col: usize) -> u64 {
+        self.sync_document(path, lines);
+        self.request("textDocument/definition", json!({
+            "textDocument": { "uri": path_to_uri(path) },
+            "position": position_json(lines, row, col),
+        }))
+    }
+
+    /// Ask for the edits renaming the symbol at the char position (`row`, `col`) to `new_name`
+    pub fn rename(&mut self, path: &Path, lines: &[String], row: usize, col: usize, new_name: &str) -> u64 {
+        self.sync_document(path, lines);
+        self.request("textDocument/rename", json!({
+            "textDocument": { "uri": path_to_uri(path) },
+            "position": position_json(lines, row, col),
+            "newName": new_name,
+        }))
+    }
+
+    /// Ask for the functions, types and other symbols of the document at `path`
+    pub fn document_symbols(&mut self, path: &Path, lines: &[String]) -> u64 {
+        self.sync_document(path, lines);
+        self.request("textDocument/documentSymbol", json!({ "textDocument": { "uri": path_to_uri(path) } }))
+    }
+
+    /// Ask for the edits formatting the whole document, indenting with
+    /// `tab_size` spaces or tabs
+    pub fn formatting(&mut self, path: &Path, lines: &[String], tab_size: usize, insert_spaces: bool) -> u64 {
+        self.sync_document(path, lines);
+        self.request("textDocument/formatting", json!({
+            "textDocument": { "uri": path_to_uri(path) },
+            "options": { "tabSize": tab_size, "insertSpaces": insert_spaces, "trimTrailingWhitespace": true, "insertFinalNewline": true },
+        }))
+    }
+
+    /// Ask for the inlay hints of the whole document at `path`
+    pub fn inlay_hints(&mut self, path: &Path, lines: &[String]) -> u64 {
+        self.sync_document(path, lines);
+        let last = lines.len().saturating_sub(1);
+        self.request("textDocument/inlayHint", json!({
+            "textDocument": { "uri": path_to_uri(path) },
+            "range": { "start": position_json(lines, 0, 0), "end": position_json(lines, last, usize::MAX) },
+        }))
+    }
+
+    /// Ask for every use of the symbol at the char position (`row`, `col`),
+    /// its declaration included
+    pub fn references(&mut self, path: &Path, lines: &[String], row: usize, col: usize) -> u64 {
+        self.sync_document(path, lines);
+        self.request("textDocument/references", json!({
+            "textDocument": { "uri": path_to_uri(path) },
+            "position": position_json(lines, row, col),
+            "context": { "includeDeclaration": true },
+        }))
+    }
+}
+
+impl Drop for LspClient {
+    /// Shut the server down as the protocol asks: `shutdown`, its answer, then
+    /// `exit`. A server that doesn't answer or exit in time is killed.
+    fn drop(&mut self) {
+        let (done, answered) = mpsc::channel();
+        let id = self.next_id;
+        let sent = self.outbox.lock().is_ok_and(|mut outbox| {
+            outbox.ready && {
+                outbox.shutdown = Some((id, done));
+                write_message(&mut outbox.stdin, &json!({ "jsonrpc": "2.0", "id": id, "method": "shutdown" })).is_ok()
+            }
+        });
+        if sent {
+            let _ = answered.recv_timeout(SHUTDOWN_TIMEOUT);
+        }
+        if let Ok(mut outbox) = self.outbox.lock() {
+            let _ = write_message(&mut outbox.stdin, &json!({ "jsonrpc": "2.0", "method": "exit" }));
+        }
+        let deadline = Instant::now() + EXIT_TIMEOUT;
+        while matches!(self.child.try_wait(), Ok(None)) && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_and_positions() {
+        let mut out = Vec::new();
+        write_message(&mut out, &json!({ "id": 1, "result": null })).unwrap();
+        write_message(&mut out, &json!({ "method": "ok" })).unwrap();
+        let mut input = io::Cursor::new(out);
+        assert_eq!(read_message(&mut input).unwrap(), Some(json!({ "id": 1, "result": null })));
+        assert_eq!(read_message(&mut input).unwrap(), Some(json!({ "method": "ok" })));
+        assert_eq!(read_message(&mut input).unwrap(), None);
+
+        let path = Path::new("/work/my crate/ü.rs");
+        assert_eq!(path_to_uri(path), "file:///work/my%20crate/%C3%BC.rs");
+        assert_eq!(uri_to_path(&path_to_uri(path)).as_deref(), Some(path));
+        assert_eq!(utf16_col("a😀b", 2), 3);
+        assert_eq!(char_col("a😀b", 3), 2);
+    }
+
+    #[test]
+    fn test_parse_completions() {
+        let lines = ["let v = s.le"];
+        let reply = json!({
+            "isIncomplete": false,
+            "items": [
+                { "label": "len()", "kind": 2, "detail": "fn(&self) -> usize", "filterText": "len",
+                  "textEdit": { "range": { "start": { "line": 0, "character": 10 }, "end": { "line": 0, "character": 12 } }, "newText": "len()" } },
+                { "label": "HashMap", "kind": 22, "additionalTextEdits": [
+                    { "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 0 } }, "newText": "use std::collections::HashMap;\n" }] },
+                { "kind": 1 }
+            ]
+        });
+        let items = parse_completions(&reply, &lines);
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].icon(), "f");
+        assert_eq!(items[0].filter_text, "len");
+        assert_eq!(items[0].edit, Some(TextEdit { start: (0, 10), end: (0, 12), new_text: "len()".to_string() }));
+        assert_eq!((items[1].icon(), items[1].insert_text.as_str()), ("t", "HashMap"));
+        assert_eq!(items[1].additional_edits[0].start, (0, 0));
+        assert_eq!(parse_completions(&json!([{ "label": "x" }]), &lines)[0].icon(), "·");
+    }
+
+    #[test]
+    fn test_parse_locations() {
+        let range = json!({ "start": { "line": 4, "character": 7 }, "end": { "line": 4, "character": 10 } });
+        let single = json!({ "uri": "file:///work/src/a.rs", "range": range });
+        assert_eq!(parse_locations(&single), vec![Location { path: PathBuf::from("/work/src/a.rs"), line: 4, character: 7 }]);
+        let links = json!([
+            { "targetUri": "file:///work/b.rs", "targetRange": range, "targetSelectionRange": range },
+            { "targetUri": "https://example.com/c.rs", "targetSelectionRange": range }
+        ]);
+        assert_eq!(parse_locations(&links).len(), 1);
+        assert_eq!(parse_locations(&links)[0].path, PathBuf::from("/work/b.rs"));
+        assert!(parse_locations(&Value::Null).is_empty());
+
+        let edit = |line: u64| json!({ "range": { "start": { "line": line, "character": 0 }, "end": { "line": line, "character": 1 } }, "newText": "y" });
+        let document_changes = json!({ "documentChanges": [
+            { "textDocument": { "uri": "file:///work/a.rs", "version": 3 }, "edits": [edit(0)] },
+            { "textDocument": { "uri": "file:///work/b.rs", "version": null }, "edits": [edit(1)] },
+            { "textDocument": { "uri": "file:///work/a.rs", "version": 3 }, "edits": [edit(2)] }
+        ] });
+        let files = workspace_edit_files(&document_changes).unwrap();
+        assert_eq!(files.iter().map(|(p, e)| (p.to_str().unwrap(), e.len())).collect::<Vec<_>>(), vec![("/work/a.rs", 2), ("/work/b.rs", 1)]);
+        assert_eq!(workspace_edit_files(&json!({ "changes": { "file:///work/c.rs": [edit(0)] } })).unwrap().len(), 1);
+        assert!(workspace_edit_files(&json!({ "documentChanges": [{ "kind": "rename", "oldUri": "file:///a", "newUri": "file:///b" }] })).is_err());
+    }
+
+    #[test]
+    fn test_parse_inlay_hints() {
+        let hints = json!([
+            { "position": { "line": 1, "character": 12 }, "label": [{ "value": "name" }, { "value": ":" }], "kind": 2, "paddingRight": true },
+            { "position": { "line": 0, "character": 5 }, "label": ": Vec<u8>", "kind": 1 },
+            { "position": { "line": 0, "character": 1 }, "label": "" }
+        ]);
+        let lines = ["let x = vec![];", "    greet(\"é\", who);"];
+        assert_eq!(parse_inlay_hints(&hints, &lines), vec![
+            InlayHint { row: 0, col: 5, label: ": Vec<u8>".to_string() },
+            InlayHint { row: 1, col: 12, label: "name: ".to_string() },
+        ]);
+    }
+}
@@ -0,0 +1,230 @@
+// Read-only session mirror for pair debugging: viewers connect over TCP
+// (`nc 127.0.0.1 7370`, or through `ssh -L`), type the session token and see
+// the editor around the cursor above the shell's screen, repainted as it
+// changes. Other input from viewers is ignored.
+
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// A viewer that takes longer than this to accept a frame is disconnected
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a viewer has to type the token
+const TOKEN_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Clear the viewer's screen and move to the top left before each frame
+const REPAINT: &[u8] = b"\x1b[H\x1b[2J";
+
+type Frame = Arc<Vec<u8>>;
+
+pub struct MirrorServer {
+    address: SocketAddr,
+    token: String,
+    /// Frame queues of the viewers' writer threads
+    viewers: Arc<Mutex<Vec<Sender<Frame>>>>,
+    /// Sent to viewers as they connect
+    last_frame: Arc<Mutex<Frame>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl MirrorServer {
+    /// Listen on `address` (e.g. `127.0.0.1:7370`) and accept viewers on a
+    /// background thread. Each viewer gets its own thread, which asks for the
+    /// token and then writes frames.
+    pub fn start(address: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        let token = new_token();
+        let viewers = Arc::new(Mutex::new(Vec::new()));
+        let last_frame = Arc::new(Mutex::new(Frame::default()));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let (accepted, frame, stopped, expected) = (viewers.clone(), last_frame.clone(), stop.clone(), token.clone());
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if stopped.load(Ordering::Relaxed) {
+                    break;
+                }
+                let Ok(stream) = stream else { continue };
+                let (viewers, frame, stopped, expected) = (accepted.clone(), frame.clone(), stopped.clone(), expected.clone());
+                thread::spawn(move || {
+                    if check_token(&stream, &expected).unwrap_or(false) {
+                        serve_viewer(stream, &viewers, &frame, &stopped);
+                    }
+                });
+            }
+        });
+        Ok(Self { address, token, viewers, last_frame, stop })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// What viewers have to type before they see anything
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    pub fn viewer_count(&self) -> usize {
+        self.viewers.lock().map(|v| v.len()).unwrap_or(0)
+    }
+
+    /// Hand `frame` to every viewer's writer unless it is unchanged. Viewers
+    /// that are still writing an older frame skip to the newest one; ones
+    /// that disconnected are dropped.
+    pub fn publish(&self, frame: Vec<u8>) {
+        let Ok(mut last) = self.last_frame.lock() else { return };
+        if **last == frame {
+            return;
+        }
+        let frame = Arc::new(frame);
+        if let Ok(mut viewers) = self.viewers.lock() {
+            viewers.retain(|viewer| viewer.send(frame.clone()).is_ok());
+        }
+        *last = frame;
+    }
+}
+
+impl Drop for MirrorServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // Wake the accept loop so it sees the stop flag
+        let _ = TcpStream::connect(self.address);
+        // Writer threads stop once their queue is gone
+        if let Ok(mut viewers) = self.viewers.lock() {
+            viewers.clear();
+        }
+    }
+}
+
+/// A random token for one sharing session
+fn new_token() -> String {
+    let random = RandomState::new().hash_one((std::process::id(), std::time::SystemTime::now()));
+    format!("{:016x}", random)
+}
+
+/// Ask the viewer for the token and read one line
+fn check_token(stream: &TcpStream, expected: &str) -> io::Result<bool> {
+    stream.set_read_timeout(Some(TOKEN_TIMEOUT))?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    (&*stream).write_all(b"nterm session token: ")?;
+    let mut line = String::new();
+    BufReader::new(stream.take(256)).read_line(&mut line)?;
+    if line.trim() != expected {
+        let _ = (&*stream).write_all(b"wrong token\r\n");
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Write the current frame and then each new one until the viewer goes away
+/// or the server stops. Frames that pile up during a slow write are skipped.
+fn serve_viewer(mut stream: TcpStream, viewers: &Mutex<Vec<Sender<Frame>>>, last_frame: &Mutex<Frame>, stop: &AtomicBool) {
+    let (tx, rx) = mpsc::channel();
+    // Registered under the frame lock so no frame falls between the first one and the queue
+    let first = {
+        let Ok(last) = last_frame.lock() else { return };
+        let Ok(mut viewers) = viewers.lock() else { return };
+        // The server may have stopped while the token was being typed
+        if stop.load(Ordering::Relaxed) {
+            return;
+        }
+        viewers.push(tx);
+        last.clone()
+    };
+    if stream.write_all(&first).is_err() {
+        return;
+    }
+    while let Ok(mut frame) = rx.recv() {
+        while let Ok(newer) = rx.try_recv() {
+            frame = newer;
+        }
+        if stream.write_all(&frame).is_err() {
+            break;
+        }
+    }
+    let _ = stream.shutdown(std::net::Shutdown::Both);
+}
+
+/// One frame for viewers: a title line, the editor rows from `first_row` with
+/// line numbers and the cursor row highlighted, then the shell's screen rows
+/// (with their colors, as `vt100::Screen::rows_formatted` gives them)
+pub fn compose(title: &str, editor: &[String], first_row: usize, cursor_row: usize, terminal_rows: &[Vec<u8>]) -> Vec<u8> {
+    let mut frame = REPAINT.to_vec();
+    frame.extend_from_slice(format!("\x1b[1m── {} (read-only) ──\x1b[m\r\n", title).as_bytes());
+    let width = (first_row + editor.len()).max(1).to_string().len();
+    for (i, line) in editor.iter().enumerate() {
+        let row = first_row + i;
+        let text = format!("{:>width$} │ {}", row + 1, line, width = width);
+        let text = if row == cursor_row { format!("\x1b[7m{}\x1b[m", text) } else { text };
+        frame.extend_from_slice(text.as_bytes());
+        frame.extend_from_slice(b"\r\n");
+    }
+    frame.extend_from_slice("\x1b[1m── terminal ──\x1b[m\r\n".as_bytes());
+    for row in terminal_rows {
+        frame.extend_from_slice(row);
+        frame.extend_from_slice(b"\x1b[m\r\n");
+    }
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compose() {
+        let lines = vec!["fn main() {".to_string(), "}".to_string()];
+        let frame = String::from_utf8(compose("main.rs", &lines, 8, 9, &[b"$ ls".to_vec()])).unwrap();
+        assert!(frame.starts_with("\x1b[H\x1b[2J\x1b[1m── main.rs (read-only) ──"));
+        assert!(frame.contains(" 9 │ fn main() {\r\n\x1b[7m10 │ }\x1b[m\r\n"));
+        assert!(frame.ends_with("── terminal ──\x1b[m\r\n$ ls\x1b[m\r\n"));
+    }
+
+    /// Connect and get past the token prompt
+    fn connect(server: &MirrorServer, token: &str) -> TcpStream {
+        let mut viewer = TcpStream::connect(server.address()).unwrap();
+        viewer.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+        let mut prompt = [0u8; 21];
+        viewer.read_exact(&mut prompt).unwrap();
+        assert_eq!(&prompt, b"nterm session token: ");
+        viewer.write_all(format!("{}\n", token).as_bytes()).unwrap();
+        viewer
+    }
+
+    #[test]
+    fn test_viewers_get_frames() {
+        let server = MirrorServer::start("127.0.0.1:0").unwrap();
+        server.publish(b"first".to_vec());
+        let mut viewer = connect(&server, server.token());
+        let mut buffer = [0u8; 5];
+        viewer.read_exact(&mut buffer).unwrap();
+        assert_eq!(&buffer, b"first");
+
+        while server.viewer_count() == 0 {
+            thread::sleep(Duration::from_millis(5));
+        }
+        server.publish(b"again".to_vec());
+        viewer.read_exact(&mut buffer).unwrap();
+        assert_eq!(&buffer, b"again");
+    }
+
+    #[test]
+    fn test_wrong_token_sees_nothing() {
+        let server = MirrorServer::start("127.0.0.1:0").unwrap();
+        server.publish(b"secret".to_vec());
+        let mut viewer = connect(&server, "guess");
+        let mut reply = String::new();
+        viewer.read_to_string(&mut reply).unwrap();
+        assert_eq!(reply, "wrong token\r\n");
+        assert_eq!(server.viewer_count(), 0);
+    }
+}
@@ -1,14 +1,61 @@
 // Shared modules used by both TUI and GUI
 
 pub mod ai;
+pub mod ansi;
+pub mod capabilities;
+pub mod chat;
+pub mod code_lens;
+pub mod color;
 pub mod config;
+pub mod config_writer;
+pub mod diagnostics;
+pub mod diff;
+pub mod encoding;
+pub mod events;
+pub mod export;
+pub mod file_proposal;
 pub mod file_tree;
+pub mod format;
+pub mod git_gutter;
+pub mod global_search;
+pub mod headless;
+pub mod highlight;
+pub mod indent_guides;
+pub mod lint;
+pub mod lsp;
+pub mod mirror;
+pub mod permalink;
+pub mod preview;
+pub mod process_tree;
+pub mod project;
+pub mod prompt_history;
+pub mod recovery;
+pub mod refactor;
+pub mod remote;
+pub mod review;
+pub mod sandbox;
+pub mod scratch;
+pub mod scrollback;
+pub mod search;
+pub mod secrets;
+pub mod session_backup;
+pub mod spell;
+pub mod stats;
+pub mod storage;
+pub mod syntax;
+#[cfg(feature = "tree-sitter")]
+pub mod syntax_tree;
 pub mod terminal;
 pub mod theme;
+pub mod todo;
+pub mod toolchain;
+pub mod watch;
+pub mod watcher;
+pub mod workspace;
 
 // Re-export commonly used types
 pub use ai::send_message;
-pub use config::{Config, RecentWorkspace};
-pub use file_tree::{FileNode, VisibleItem, flatten_node, toggle_node_recursive};
+pub use config::{AutoScroll, AutoScrollConfig, CommandSandbox, Config, ConfigError, IndentConfig, KeybindingMode, RecentWorkspace};
+pub use file_tree::{FileNode, VisibleItem, flatten_node, mark_excluded, toggle_node_recursive};
 pub use terminal::{Terminal, TerminalCell, TerminalColor, TerminalEvent, TerminalSize};
 pub use theme::ThemeMode;
@@ -0,0 +1,123 @@
+// Permalinks: a GitHub or GitLab URL to lines of a file at the current
+// commit, built from the repository's remote, for pasting into chat or issues
+
+use std::path::Path;
+use std::process::Command;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Forge {
+    GitHub,
+    GitLab,
+}
+
+/// Where a repository is hosted, from its remote URL
+#[derive(Debug, Clone, PartialEq)]
+pub struct Remote {
+    pub forge: Forge,
+    pub host: String,
+    /// e.g. `owner/repo`, or `group/subgroup/repo` on GitLab
+    pub repo: String,
+}
+
+/// The host and repository of a remote URL such as `git@github.com:owner/repo.git`,
+/// `https://github.com/owner/repo` or `ssh://git@gitlab.com/group/repo.git`.
+/// `None` for hosts that aren't GitHub or GitLab.
+pub fn parse_remote(url: &str) -> Option<Remote> {
+    let url = url.trim();
+    let rest = match url.split_once("://") {
+        Some((_, rest)) => rest.to_string(),
+        // scp-like syntax: user@host:path
+        None => url.replacen(':', "/", 1),
+    };
+    let rest = rest.rsplit_once('@').filter(|(user, _)| !user.contains('/')).map_or(rest.as_str(), |(_, r)| r);
+    let (host, repo) = rest.split_once('/')?;
+    // A port is kept out of the web URL
+    let host = host.split(':').next()?.to_lowercase();
+    let repo = repo.trim_end_matches('/').trim_end_matches(".git").to_string();
+    let forge = if host.contains("github") {
+        Forge::GitHub
+    } else if host.contains("gitlab") {
+        Forge::GitLab
+    } else {
+        return None;
+    };
+    (repo.contains('/')).then_some(Remote { forge, host, repo })
+}
+
+/// URL of `lines` (1-based, inclusive) of `file` (relative to the repository
+/// root) at `commit`
+pub fn url(remote: &Remote, commit: &str, file: &str, lines: (usize, usize)) -> String {
+    let file: String = file.split('/').map(encode_segment).collect::<Vec<_>>().join("/");
+    let (blob, anchor) = match (remote.forge, lines) {
+        (Forge::GitHub, (first, last)) if first == last => ("blob", format!("L{}", first)),
+        (Forge::GitHub, (first, last)) => ("blob", format!("L{}-L{}", first, last)),
+        (Forge::GitLab, (first, last)) if first == last => ("-/blob", format!("L{}", first)),
+        (Forge::GitLab, (first, last)) => ("-/blob", format!("L{}-{}", first, last)),
+    };
+    format!("https://{}/{}/{}/{}/{}#{}", remote.host, remote.repo, blob, commit, file, anchor)
+}
+
+fn encode_segment(segment: &str) -> String {
+    segment.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .map_err(|e| format!("could not run git: {}", e))?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(if message.is_empty() { format!("git {} failed", args.join(" ")) } else { message });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// A permalink to `lines` (1-based, inclusive) of `path` at HEAD, using the
+/// `origin` remote (or the first one), and whether the file has changes not
+/// in HEAD, which may shift the lines. Runs `git`.
+pub fn permalink(path: &Path, lines: (usize, usize)) -> Result<(String, bool), String> {
+    let dir = path.parent().ok_or("the file has no directory")?;
+    let root = git(dir, &["rev-parse", "--show-toplevel"]).map_err(|_| "the file isn't in a git repository".to_string())?;
+    let commit = git(dir, &["rev-parse", "HEAD"]).map_err(|_| "the repository has no commits".to_string())?;
+    let remote_name = match git(dir, &["remote"])?.lines().map(str::to_string).collect::<Vec<_>>() {
+        names if names.iter().any(|n| n == "origin") => "origin".to_string(),
+        names => names.into_iter().next().ok_or("the repository has no remote")?,
+    };
+    let remote_url = git(dir, &["remote", "get-url", &remote_name])?;
+    let remote = parse_remote(&remote_url).ok_or_else(|| format!("{} is not a GitHub or GitLab remote", remote_url))?;
+
+    let canonical = path.canonicalize().map_err(|e| e.to_string())?;
+    let root = Path::new(&root).canonicalize().map_err(|e| e.to_string())?;
+    let relative = canonical.strip_prefix(&root).map_err(|_| "the file is outside the repository".to_string())?;
+    let relative = relative.to_string_lossy().replace('\\', "/");
+    let changed = git(&root, &["diff", "--quiet", "HEAD", "--", &relative]).is_err();
+    Ok((url(&remote, &commit, &relative, lines), changed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_remote_and_url() {
+        let github = Remote { forge: Forge::GitHub, host: "github.com".to_string(), repo: "owner/repo".to_string() };
+        assert_eq!(parse_remote("git@github.com:owner/repo.git"), Some(github.clone()));
+        assert_eq!(parse_remote("https://github.com/owner/repo"), Some(github.clone()));
+        assert_eq!(parse_remote("https://token@github.com/owner/repo.git/\n"), Some(github.clone()));
+        let gitlab = parse_remote("ssh://git@gitlab.example.com:2222/group/sub/repo.git").unwrap();
+        assert_eq!((gitlab.forge, gitlab.host.as_str(), gitlab.repo.as_str()), (Forge::GitLab, "gitlab.example.com", "group/sub/repo"));
+        assert_eq!(parse_remote("https://bitbucket.org/owner/repo.git"), None);
+        assert_eq!(parse_remote("/srv/git/repo.git"), None);
+
+        assert_eq!(url(&github, "abc123", "src/my file.rs", (7, 7)), "https://github.com/owner/repo/blob/abc123/src/my%20file.rs#L7");
+        assert_eq!(url(&github, "abc123", "a.rs", (3, 9)), "https://github.com/owner/repo/blob/abc123/a.rs#L3-L9");
+        assert_eq!(url(&gitlab, "abc123", "a.rs", (3, 9)), "https://gitlab.example.com/group/sub/repo/-/blob/abc123/a.rs#L3-9");
+    }
+}
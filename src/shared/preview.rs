@@ -0,0 +1,117 @@
+// Files too large to open in full are previewed: the editor shows them
+// read-only and reads the next chunk as the view nears the end of what is
+// loaded, so opening a huge log doesn't freeze the UI
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE};
+
+use super::encoding::{self, TextEncoding};
+
+/// Bytes read at a time
+pub const CHUNK_BYTES: u64 = 256 * 1024;
+
+/// How much of a previewed file is loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Preview {
+    pub encoding: TextEncoding,
+    /// Bytes read so far; always just after a line break unless the file is done
+    pub loaded: u64,
+    /// File size when the preview was opened
+    pub size: u64,
+}
+
+impl Preview {
+    pub fn is_complete(&self) -> bool {
+        self.loaded >= self.size
+    }
+
+    pub fn percent(&self) -> u64 {
+        (self.loaded * 100).checked_div(self.size).unwrap_or(100).min(100)
+    }
+}
+
+/// Read `path` in full, or only its first chunk when it is larger than `limit` bytes
+pub fn load(path: &Path, limit: u64) -> io::Result<(String, TextEncoding, Option<Preview>)> {
+    let size = path.metadata()?.len();
+    if size <= limit {
+        let (text, encoding) = encoding::read_file(path)?;
+        return Ok((text, encoding, None));
+    }
+    let bytes = read_bytes(path, 0, size, None)?;
+    let (text, encoding) = encoding::decode(&bytes)?;
+    let preview = Preview { encoding, loaded: bytes.len() as u64, size };
+    Ok((text, encoding, Some(preview)))
+}
+
+/// The chunk of `path` after what `preview` has loaded, and the preview
+/// advanced past it
+pub fn next_chunk(path: &Path, preview: Preview) -> io::Result<(String, Preview)> {
+    let bytes = read_bytes(path, preview.loaded, preview.size, Some(preview.encoding.encoding))?;
+    let (text, _) = preview.encoding.encoding.decode_without_bom_handling(&bytes);
+    let loaded = if bytes.is_empty() { preview.size } else { preview.loaded + bytes.len() as u64 };
+    Ok((text.into_owned(), Preview { loaded, ..preview }))
+}
+
+/// Up to `CHUNK_BYTES` from `offset`, ending after the last line break in
+/// them unless the chunk reaches `size`. The encoding is guessed from the
+/// bytes when not known yet.
+fn read_bytes(path: &Path, offset: u64, size: u64, known: Option<&'static Encoding>) -> io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut bytes = Vec::new();
+    file.take(CHUNK_BYTES).read_to_end(&mut bytes)?;
+    if offset + (bytes.len() as u64) < size {
+        // A cut inside a UTF-8 sequence reads as windows-1252, which breaks lines the same way
+        let guessed = known.unwrap_or_else(|| encoding::decode(&bytes[..bytes.len().min(1024)]).map_or(encoding_rs::UTF_8, |(_, e)| e.encoding));
+        if let Some(end) = line_break_end(&bytes, guessed) {
+            bytes.truncate(end);
+        }
+    }
+    Ok(bytes)
+}
+
+/// Position just after the last `\n` in `bytes`, in UTF-16 or a byte-based encoding
+fn line_break_end(bytes: &[u8], encoding: &'static Encoding) -> Option<usize> {
+    if encoding == UTF_16LE {
+        return (0..bytes.len().saturating_sub(1)).rev()
+            .find(|&i| i % 2 == 0 && bytes[i] == b'\n' && bytes[i + 1] == 0)
+            .map(|i| i + 2);
+    }
+    if encoding == UTF_16BE {
+        return (0..bytes.len().saturating_sub(1)).rev()
+            .find(|&i| i % 2 == 0 && bytes[i] == 0 && bytes[i + 1] == b'\n')
+            .map(|i| i + 2);
+    }
+    bytes.iter().rposition(|&b| b == b'\n').map(|i| i + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunks() {
+        let path = std::env::temp_dir().join(format!("nterm-preview-{}.log", std::process::id()));
+        let line = "x".repeat(99) + "\n";
+        let count = (CHUNK_BYTES as usize / line.len()) * 2 + 10;
+        std::fs::write(&path, line.repeat(count)).unwrap();
+
+        let (text, _, preview) = load(&path, 1024).unwrap();
+        let mut preview = preview.unwrap();
+        assert!(text.ends_with('\n'));
+        let mut lines = text.lines().count();
+        assert!(lines < count && !preview.is_complete());
+        while !preview.is_complete() {
+            let (text, next) = next_chunk(&path, preview).unwrap();
+            lines += text.lines().count();
+            preview = next;
+        }
+        assert_eq!((lines, preview.percent()), (count, 100));
+
+        assert!(load(&path, u64::MAX).unwrap().2.is_none());
+        let _ = std::fs::remove_file(&path);
+    }
+}
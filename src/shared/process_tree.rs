@@ -0,0 +1,158 @@
+// Processes running under a terminal: the shell's children and theirs, with
+// CPU and memory use read from `ps`, and signals to stop a runaway one
+
+use std::collections::HashMap;
+use std::process::Command;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub ppid: u32,
+    /// CPU use in percent of one core
+    pub cpu: f32,
+    /// Resident memory in KiB
+    pub memory_kb: u64,
+    /// Command line
+    pub command: String,
+}
+
+impl ProcessInfo {
+    /// Resident memory for display, e.g. `812K`, `14.2M` or `1.3G`
+    pub fn memory_label(&self) -> String {
+        match self.memory_kb {
+            kb if kb < 1024 => format!("{}K", kb),
+            kb if kb < 1024 * 1024 => format!("{:.1}M", kb as f64 / 1024.0),
+            kb => format!("{:.1}G", kb as f64 / (1024.0 * 1024.0)),
+        }
+    }
+}
+
+/// A process in the tree with how deep under the root it is
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessRow {
+    pub info: ProcessInfo,
+    pub depth: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    Interrupt,
+    Terminate,
+    Kill,
+    Stop,
+    Continue,
+}
+
+impl Signal {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Signal::Interrupt => "SIGINT",
+            Signal::Terminate => "SIGTERM",
+            Signal::Kill => "SIGKILL",
+            Signal::Stop => "SIGSTOP",
+            Signal::Continue => "SIGCONT",
+        }
+    }
+
+    fn number(&self) -> libc::c_int {
+        match self {
+            Signal::Interrupt => libc::SIGINT,
+            Signal::Terminate => libc::SIGTERM,
+            Signal::Kill => libc::SIGKILL,
+            Signal::Stop => libc::SIGSTOP,
+            Signal::Continue => libc::SIGCONT,
+        }
+    }
+}
+
+/// Processes in the output of `ps -A -o pid=,ppid=,pcpu=,rss=,args=`
+pub fn parse_ps(output: &str) -> Vec<ProcessInfo> {
+    output.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let pid = fields.next()?.parse().ok()?;
+            let ppid = fields.next()?.parse().ok()?;
+            let cpu = fields.next()?.parse().ok()?;
+            let memory_kb = fields.next()?.parse().ok()?;
+            let command = fields.collect::<Vec<_>>().join(" ");
+            Some(ProcessInfo { pid, ppid, cpu, memory_kb, command })
+        })
+        .collect()
+}
+
+/// `root` and everything under it, each process followed by its children (oldest first)
+pub fn tree(processes: &[ProcessInfo], root: u32) -> Vec<ProcessRow> {
+    let mut children: HashMap<u32, Vec<&ProcessInfo>> = HashMap::new();
+    for process in processes.iter().filter(|p| p.pid != p.ppid) {
+        children.entry(process.ppid).or_default().push(process);
+    }
+    for list in children.values_mut() {
+        list.sort_by_key(|p| p.pid);
+    }
+    let mut rows = Vec::new();
+    let mut stack: Vec<(&ProcessInfo, usize)> = processes.iter().filter(|p| p.pid == root).map(|p| (p, 0)).collect();
+    while let Some((process, depth)) = stack.pop() {
+        rows.push(ProcessRow { info: process.clone(), depth });
+        if let Some(list) = children.get(&process.pid) {
+            stack.extend(list.iter().rev().map(|&child| (child, depth + 1)));
+        }
+    }
+    rows
+}
+
+/// The process tree under `root`, read from `ps` now
+pub fn list(root: u32) -> Result<Vec<ProcessRow>, String> {
+    let output = Command::new("ps")
+        .args(["-A", "-o", "pid=,ppid=,pcpu=,rss=,args="])
+        .output()
+        .map_err(|e| format!("could not run ps: {}", e))?;
+    let rows = tree(&parse_ps(&String::from_utf8_lossy(&output.stdout)), root);
+    if rows.is_empty() {
+        return Err(format!("process {} is gone", root));
+    }
+    Ok(rows)
+}
+
+/// Send `signal` to the process `pid`
+pub fn send_signal(pid: u32, signal: Signal) -> Result<(), String> {
+    // SAFETY: kill has no memory effects
+    let result = unsafe { libc::kill(pid as libc::pid_t, signal.number()) };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_tree() {
+        let output = "    1     0  0.0  1200 /sbin/init\n\
+                      \x20 100     1  0.5  4096 bash\n\
+                      \x20 230   100 99.0 2097152 python train.py --epochs 10\n\
+                      \x20 120   100  0.0   600 sleep 100\n\
+                      \x20 231   230  1.5  2048 worker\n\
+                      \x20 300     1  0.0   100 other\n\
+                      garbage line\n";
+        let processes = parse_ps(output);
+        assert_eq!(processes.len(), 6);
+        assert_eq!(processes[2].command, "python train.py --epochs 10");
+        assert_eq!(processes[2].memory_label(), "2.0G");
+        assert_eq!(processes[1].memory_label(), "4.0M");
+
+        let rows = tree(&processes, 100);
+        let summary: Vec<(u32, usize)> = rows.iter().map(|r| (r.info.pid, r.depth)).collect();
+        assert_eq!(summary, vec![(100, 0), (120, 1), (230, 1), (231, 2)]);
+        assert!(tree(&processes, 999).is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_list_own_process() {
+        let rows = list(std::process::id()).unwrap();
+        assert_eq!((rows[0].info.pid, rows[0].depth), (std::process::id(), 0));
+    }
+}
@@ -0,0 +1,194 @@
+// Project detection: whether a workspace is a Cargo, npm or Python project,
+// from the manifest at its top, with the usual commands to build, test and run
+// it. Suggested once when a workspace is first opened.
+
+use std::fs;
+use std::path::Path;
+
+use super::ai::presets;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectKind {
+    Cargo,
+    Npm,
+    Python,
+}
+
+impl ProjectKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ProjectKind::Cargo => "Cargo",
+            ProjectKind::Npm => "npm",
+            ProjectKind::Python => "Python",
+        }
+    }
+}
+
+/// A shell command with what it is for, e.g. `Test` and `cargo test`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Task {
+    pub label: &'static str,
+    pub command: String,
+}
+
+impl Task {
+    fn new(label: &'static str, command: impl Into<String>) -> Self {
+        Self { label, command: command.into() }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Project {
+    pub kind: ProjectKind,
+    /// From the manifest, else the directory name
+    pub name: String,
+    /// The file it was detected from, e.g. `Cargo.toml`
+    pub manifest: &'static str,
+    /// Install, build, test and lint commands, in that order
+    pub tasks: Vec<Task>,
+    /// Command starting the program, when there is one to start
+    pub run: Option<String>,
+}
+
+impl Project {
+    /// Prompt asking the model for a summary of the project to keep in the
+    /// chat, with its manifest and README
+    pub fn summary_prompt(&self, root: &Path) -> String {
+        let read = |name: &str| fs::read_to_string(root.join(name)).ok();
+        let mut sections = Vec::new();
+        if let Some(manifest) = read(self.manifest) {
+            sections.push((self.manifest.to_string(), manifest));
+        }
+        if let Some(readme) = ["README.md", "README.rst", "README.txt", "README"].into_iter().find_map(|name| Some((name.to_string(), read(name)?))) {
+            sections.push(readme);
+        }
+        let message = format!(
+            "Summarize the {} project `{}` in a short paragraph to refer back to in this chat: what it does, its main parts, and how to build, test and run it.",
+            self.kind.label(), self.name,
+        );
+        presets::with_context(&message, &sections)
+    }
+}
+
+/// The project at the top of `root`: a `Cargo.toml`, a `package.json`, or a
+/// `pyproject.toml`, `setup.py` or `requirements.txt`, checked in that order
+pub fn detect(root: &Path) -> Option<Project> {
+    let dir_name = root.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    if let Ok(manifest) = fs::read_to_string(root.join("Cargo.toml")) {
+        return Some(cargo(root, &manifest, dir_name));
+    }
+    if let Ok(manifest) = fs::read_to_string(root.join("package.json")) {
+        return Some(npm(root, &manifest, dir_name));
+    }
+    python(root, dir_name)
+}
+
+fn cargo(root: &Path, manifest: &str, dir_name: String) -> Project {
+    let name = toml_string(manifest, "package", "name");
+    let binary = root.join("src/main.rs").is_file() || manifest.lines().any(|line| line.trim() == "[[bin]]");
+    Project {
+        kind: ProjectKind::Cargo,
+        name: name.unwrap_or(dir_name),
+        manifest: "Cargo.toml",
+        tasks: vec![Task::new("Build", "cargo build"), Task::new("Test", "cargo test"), Task::new("Lint", "cargo clippy")],
+        run: binary.then(|| "cargo run".to_string()),
+    }
+}
+
+fn npm(root: &Path, manifest: &str, dir_name: String) -> Project {
+    let package: serde_json::Value = serde_json::from_str(manifest).unwrap_or_default();
+    let runner = if root.join("pnpm-lock.yaml").is_file() {
+        "pnpm"
+    } else if root.join("yarn.lock").is_file() {
+        "yarn"
+    } else {
+        "npm"
+    };
+    let has_script = |script: &str| package.pointer(&format!("/scripts/{}", script)).is_some_and(|s| s.is_string());
+    let mut tasks = vec![Task::new("Install", format!("{} install", runner))];
+    for (label, script) in [("Build", "build"), ("Test", "test"), ("Lint", "lint")] {
+        if has_script(script) {
+            tasks.push(Task::new(label, format!("{} run {}", runner, script)));
+        }
+    }
+    Project {
+        kind: ProjectKind::Npm,
+        name: package.get("name").and_then(|n| n.as_str()).map(str::to_string).unwrap_or(dir_name),
+        manifest: "package.json",
+        tasks,
+        run: ["start", "dev"].into_iter().find(|s| has_script(s)).map(|script| format!("{} run {}", runner, script)),
+    }
+}
+
+fn python(root: &Path, dir_name: String) -> Option<Project> {
+    let manifest = ["pyproject.toml", "setup.py", "requirements.txt"].into_iter().find(|name| root.join(name).is_file())?;
+    let pyproject = fs::read_to_string(root.join("pyproject.toml")).unwrap_or_default();
+    let name = toml_string(&pyproject, "project", "name").or_else(|| toml_string(&pyproject, "tool.poetry", "name"));
+    let install = if manifest == "requirements.txt" { "pip install -r requirements.txt" } else { "pip install -e ." };
+    let run = if root.join("manage.py").is_file() {
+        Some("python manage.py runserver".to_string())
+    } else {
+        ["main.py", "app.py"].into_iter().find(|name| root.join(name).is_file()).map(|name| format!("python {}", name))
+    };
+    Some(Project {
+        kind: ProjectKind::Python,
+        name: name.unwrap_or(dir_name),
+        manifest,
+        tasks: vec![Task::new("Install", install), Task::new("Test", "pytest")],
+        run,
+    })
+}
+
+/// The string `key = "value"` in the `[section]` table of a TOML file
+fn toml_string(content: &str, section: &str, key: &str) -> Option<String> {
+    let header = format!("[{}]", section);
+    content.lines()
+        .map(str::trim)
+        .skip_while(|line| *line != header)
+        .skip(1)
+        .take_while(|line| !line.starts_with('['))
+        .find_map(|line| {
+            let (name, value) = line.split_once('=')?;
+            let value = value.trim();
+            let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+            (name.trim() == key).then(|| value[1..].split(quote).next().unwrap_or("").to_string())
+        })
+        .filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        let root = std::env::temp_dir().join(format!("nterm-project-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("src")).unwrap();
+        assert_eq!(detect(&root), None);
+
+        fs::write(root.join("requirements.txt"), "requests\n").unwrap();
+        fs::write(root.join("main.py"), "print('hi')\n").unwrap();
+        let python = detect(&root).unwrap();
+        assert_eq!((python.kind, python.manifest, python.run.as_deref()), (ProjectKind::Python, "requirements.txt", Some("python main.py")));
+        assert_eq!(python.tasks[0].command, "pip install -r requirements.txt");
+
+        fs::write(root.join("package.json"), r#"{"name": "web", "scripts": {"test": "jest", "dev": "vite"}}"#).unwrap();
+        fs::write(root.join("yarn.lock"), "").unwrap();
+        let npm = detect(&root).unwrap();
+        assert_eq!(npm.name, "web");
+        assert_eq!(npm.tasks.iter().map(|t| t.command.as_str()).collect::<Vec<_>>(), vec!["yarn install", "yarn run test"]);
+        assert_eq!(npm.run.as_deref(), Some("yarn run dev"));
+
+        fs::write(root.join("Cargo.toml"), "[workspace]\nmembers = []\n\n[package]\nname = \"tool\" # the CLI\nversion = \"0.1.0\"\n").unwrap();
+        let cargo = detect(&root).unwrap();
+        assert_eq!((cargo.kind, cargo.run.as_deref()), (ProjectKind::Cargo, None));
+        fs::write(root.join("src/main.rs"), "fn main() {}\n").unwrap();
+        fs::write(root.join("README.md"), "# Tool\nDoes things.\n").unwrap();
+        let cargo = detect(&root).unwrap();
+        assert_eq!(cargo.run.as_deref(), Some("cargo run"));
+        let prompt = cargo.summary_prompt(&root);
+        assert!(prompt.starts_with("Cargo.toml:\n```\n[workspace]") && prompt.contains("README.md:\n```\n# Tool") && prompt.contains("`tool`"));
+        let _ = fs::remove_dir_all(&root);
+    }
+}
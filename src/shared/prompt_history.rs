@@ -0,0 +1,111 @@
+// Shell-style history of the prompts sent from the chat input: Up and Down
+// step through it and a reverse search finds the newest prompt containing a
+// query. Prompts are kept per workspace with the chat transcript.
+
+/// Prompts loaded from storage and kept in memory
+pub const MAX_PROMPTS: usize = 500;
+
+#[derive(Debug, Clone, Default)]
+pub struct PromptHistory {
+    /// Oldest first, each prompt once
+    prompts: Vec<String>,
+    /// Prompt shown in the input while stepping through the history
+    position: Option<usize>,
+}
+
+impl PromptHistory {
+    pub fn new(prompts: Vec<String>) -> Self {
+        let mut history = Self::default();
+        for prompt in prompts {
+            history.push(&prompt);
+        }
+        history
+    }
+
+    /// Record a sent prompt as the newest, dropping an earlier copy of it
+    pub fn push(&mut self, prompt: &str) {
+        self.position = None;
+        if prompt.trim().is_empty() {
+            return;
+        }
+        self.prompts.retain(|p| p != prompt);
+        self.prompts.push(prompt.to_string());
+        if self.prompts.len() > MAX_PROMPTS {
+            self.prompts.remove(0);
+        }
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&str> {
+        self.prompts.get(idx).map(String::as_str)
+    }
+
+    /// Whether the input holds a prompt from the history rather than a draft
+    pub fn is_browsing(&self) -> bool {
+        self.position.is_some()
+    }
+
+    /// Stop stepping through the history (the input was edited)
+    pub fn reset(&mut self) {
+        self.position = None;
+    }
+
+    /// The prompt before the one shown (the newest to begin with); stays on the oldest
+    pub fn older(&mut self) -> Option<&str> {
+        let idx = match self.position {
+            Some(idx) => idx.saturating_sub(1),
+            None => self.prompts.len().checked_sub(1)?,
+        };
+        self.position = Some(idx);
+        self.get(idx)
+    }
+
+    /// The prompt after the one shown; `None` past the newest, which leaves
+    /// the history for an empty input
+    pub fn newer(&mut self) -> Option<&str> {
+        let idx = self.position? + 1;
+        self.position = (idx < self.prompts.len()).then_some(idx);
+        self.position.and_then(|idx| self.get(idx))
+    }
+
+    /// The newest prompt older than `before` containing `query`, ignoring case
+    pub fn search(&self, query: &str, before: usize) -> Option<usize> {
+        let query = query.to_lowercase();
+        self.prompts[..before.min(self.prompts.len())].iter().rposition(|p| p.to_lowercase().contains(&query))
+    }
+
+    /// Show the prompt at `idx` as if stepped to, so Up and Down continue from it
+    pub fn select(&mut self, idx: usize) {
+        self.position = (idx < self.prompts.len()).then_some(idx);
+    }
+
+    pub fn len(&self) -> usize {
+        self.prompts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prompts.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_browse_and_search() {
+        let mut history = PromptHistory::new(vec!["explain main".to_string(), "fix the parser".to_string()]);
+        history.push("explain main");
+        assert_eq!(history.len(), 2);
+
+        assert_eq!(history.older(), Some("explain main"));
+        assert_eq!(history.older(), Some("fix the parser"));
+        assert_eq!(history.older(), Some("fix the parser"));
+        assert_eq!(history.newer(), Some("explain main"));
+        assert_eq!(history.newer(), None);
+        assert!(!history.is_browsing());
+
+        assert_eq!(history.search("MAIN", history.len()), Some(1));
+        assert_eq!(history.search("e", 1), Some(0));
+        assert_eq!(history.search("main", 1), None);
+    }
+}
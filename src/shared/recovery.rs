@@ -0,0 +1,200 @@
+// Crash recovery: swap copies of modified buffers
+//
+// Backups are written periodically while a buffer has unsaved changes and are
+// removed on a clean exit. Any backups still present at startup therefore belong
+// to a session that did not shut down cleanly. A file's backup is named after
+// the file; untitled buffers are told apart by a random id of their own.
+
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::fs;
+use std::hash::BuildHasher;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::config::Config;
+
+/// A snapshot of an unsaved buffer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BufferBackup {
+    /// File the buffer belongs to, `None` for untitled buffers
+    pub path: Option<PathBuf>,
+    /// The buffer's `new_buffer_id`, which keys the backup of an untitled buffer
+    #[serde(default)]
+    pub buffer_id: u64,
+    /// Workspace the buffer was edited in
+    pub workspace: PathBuf,
+    pub lines: Vec<String>,
+    /// Seconds since the unix epoch when the backup was written
+    pub timestamp: u64,
+}
+
+impl BufferBackup {
+    pub fn new(path: Option<PathBuf>, buffer_id: u64, workspace: PathBuf, lines: Vec<String>) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Self {
+            path,
+            buffer_id,
+            workspace,
+            lines,
+            timestamp,
+        }
+    }
+
+    /// Whether this is the backup of the buffer with `path` and `buffer_id`
+    pub fn belongs_to(&self, path: Option<&Path>, buffer_id: u64) -> bool {
+        self.path.as_deref() == path && (path.is_some() || self.buffer_id == buffer_id)
+    }
+
+    /// Name shown in the restore prompt
+    pub fn display_name(&self) -> String {
+        match &self.path {
+            Some(path) => path.display().to_string(),
+            None => "[untitled]".to_string(),
+        }
+    }
+
+    /// How long ago the backup was written, e.g. "3m ago"
+    pub fn age_label(&self) -> String {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let secs = now.saturating_sub(self.timestamp);
+        match secs {
+            0..=59 => format!("{}s ago", secs),
+            60..=3599 => format!("{}m ago", secs / 60),
+            3600..=86399 => format!("{}h ago", secs / 3600),
+            _ => format!("{}d ago", secs / 86400),
+        }
+    }
+}
+
+/// Directory holding the backup files
+pub fn backup_dir() -> PathBuf {
+    Config::data_dir().join("backups")
+}
+
+/// A fresh id for an untitled buffer, unlikely to repeat across sessions
+pub fn new_buffer_id() -> u64 {
+    RandomState::new().hash_one((std::process::id(), SystemTime::now()))
+}
+
+/// FNV-1a, which unlike the std hashers gives the same result on every Rust
+/// release, so backups written by an older build are still found
+fn stable_hash(parts: &[&[u8]]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for part in parts {
+        // A separator keeps ("ab", "c") and ("a", "bc") apart
+        for byte in part.iter().chain(&[0xff]) {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+fn backup_file_name(path: Option<&Path>, buffer_id: u64, workspace: &Path) -> String {
+    let workspace = workspace.as_os_str().as_encoded_bytes();
+    let hash = match path {
+        Some(path) => stable_hash(&[workspace, path.as_os_str().as_encoded_bytes()]),
+        None => stable_hash(&[workspace, b"untitled", &buffer_id.to_le_bytes()]),
+    };
+    format!("{:016x}.json", hash)
+}
+
+/// Write (or overwrite) the backup for a buffer
+pub fn write_backup(backup: &BufferBackup) -> io::Result<()> {
+    write_backup_in(&backup_dir(), backup)
+}
+
+/// Remove the backup for a buffer, e.g. after it has been saved; `buffer_id`
+/// only matters for untitled buffers
+pub fn remove_backup(path: Option<&Path>, buffer_id: u64, workspace: &Path) {
+    remove_backup_in(&backup_dir(), path, buffer_id, workspace);
+}
+
+/// Load all backups left behind for a workspace, newest first
+pub fn load_backups(workspace: &Path) -> Vec<BufferBackup> {
+    load_backups_in(&backup_dir(), workspace)
+}
+
+fn write_backup_in(dir: &Path, backup: &BufferBackup) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let content = serde_json::to_string(backup)?;
+    fs::write(dir.join(backup_file_name(backup.path.as_deref(), backup.buffer_id, &backup.workspace)), content)
+}
+
+fn remove_backup_in(dir: &Path, path: Option<&Path>, buffer_id: u64, workspace: &Path) {
+    let _ = fs::remove_file(dir.join(backup_file_name(path, buffer_id, workspace)));
+}
+
+fn load_backups_in(dir: &Path, workspace: &Path) -> Vec<BufferBackup> {
+    let mut backups: Vec<BufferBackup> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|e| fs::read_to_string(e.path()).ok())
+        .filter_map(|content| serde_json::from_str::<BufferBackup>(&content).ok())
+        .filter(|b| b.workspace == workspace)
+        .collect();
+    backups.sort_by_key(|b| std::cmp::Reverse(b.timestamp));
+    backups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("nterm-recovery-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_backups_round_trip() {
+        let dir = temp_dir("round-trip");
+        let workspace = PathBuf::from("/work");
+        let file = BufferBackup::new(Some(PathBuf::from("/work/main.rs")), 1, workspace.clone(), vec!["fn main() {}".to_string()]);
+        let first = BufferBackup::new(None, 1, workspace.clone(), vec!["one".to_string()]);
+        let second = BufferBackup::new(None, 2, workspace.clone(), vec!["two".to_string()]);
+        let elsewhere = BufferBackup::new(None, 1, PathBuf::from("/other"), vec!["other".to_string()]);
+        for backup in [&file, &first, &second, &elsewhere] {
+            write_backup_in(&dir, backup).unwrap();
+        }
+
+        // Two untitled buffers each keep their own backup
+        let mut loaded: Vec<Vec<String>> = load_backups_in(&dir, &workspace).into_iter().map(|b| b.lines).collect();
+        loaded.sort();
+        assert_eq!(loaded, vec![vec!["fn main() {}".to_string()], vec!["one".to_string()], vec!["two".to_string()]]);
+
+        // A newer backup of the same buffer replaces the old one
+        write_backup_in(&dir, &BufferBackup::new(None, 2, workspace.clone(), vec!["two, edited".to_string()])).unwrap();
+        let loaded = load_backups_in(&dir, &workspace);
+        assert_eq!(loaded.len(), 3);
+        assert!(loaded.iter().any(|b| b.belongs_to(None, 2) && b.lines == ["two, edited"]));
+
+        // Removing one untitled backup leaves the other
+        remove_backup_in(&dir, None, 1, &workspace);
+        let loaded = load_backups_in(&dir, &workspace);
+        assert_eq!(loaded.len(), 2);
+        assert!(!loaded.iter().any(|b| b.belongs_to(None, 1)));
+        assert!(loaded.iter().any(|b| b.belongs_to(Some(Path::new("/work/main.rs")), 7)));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_backup_names_are_stable() {
+        // Fixed so that a toolchain update can't orphan existing backups
+        assert_eq!(backup_file_name(Some(Path::new("/work/a")), 0, Path::new("/work")), "a03e2b4c149ef0e5.json");
+        assert_ne!(stable_hash(&[b"ab", b"c"]), stable_hash(&[b"a", b"bc"]));
+        assert_ne!(backup_file_name(None, 1, Path::new("/work")), backup_file_name(None, 2, Path::new("/work")));
+        assert_eq!(backup_file_name(Some(Path::new("/work/a")), 1, Path::new("/work")), backup_file_name(Some(Path::new("/work/a")), 2, Path::new("/work")));
+    }
+}
@@ -0,0 +1,249 @@
+// AI-assisted rename/refactor: collect the lines that mention a symbol, ask the
+// model for replacements and apply only the edits the user accepted
+
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::lsp::TextEdit;
+use super::workspace;
+
+/// Files larger than this are skipped (generated or minified code)
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+/// Occurrences beyond this are not sent to the model
+pub const MAX_OCCURRENCES: usize = 200;
+
+/// A line mentioning the symbol (line is 1-based)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Occurrence {
+    pub path: PathBuf,
+    pub line: usize,
+    pub text: String,
+}
+
+/// A proposed replacement for one occurrence line
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineEdit {
+    pub path: PathBuf,
+    pub line: usize,
+    pub old: String,
+    pub new: String,
+    /// Whether the edit will be applied; the user can toggle this during review
+    pub accepted: bool,
+}
+
+fn symbol_pattern(symbol: &str) -> Option<Regex> {
+    Regex::new(&format!(r"\b{}\b", regex::escape(symbol))).ok()
+}
+
+/// Lines of `content` containing `symbol` as a whole word
+pub fn find_in_text(path: &Path, content: &str, symbol: &str) -> Vec<Occurrence> {
+    let Some(pattern) = symbol_pattern(symbol) else { return Vec::new() };
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| pattern.is_match(line))
+        .map(|(idx, line)| Occurrence { path: path.to_path_buf(), line: idx + 1, text: line.to_string() })
+        .collect()
+}
+
+/// Search every text file in the workspace; paths are relative to `root`
+pub fn find_occurrences(root: &Path, symbol: &str) -> Vec<Occurrence> {
+    let mut occurrences = Vec::new();
+    for entry in workspace::walk_files(root) {
+        if entry.metadata().map(|m| m.len() > MAX_FILE_SIZE).unwrap_or(true) {
+            continue;
+        }
+        let Ok(content) = fs::read_to_string(entry.path()) else {
+            continue;
+        };
+        let path = entry.path().strip_prefix(root).unwrap_or(entry.path());
+        occurrences.extend(find_in_text(path, &content, symbol));
+    }
+    occurrences.sort_by(|a, b| a.path.cmp(&b.path).then(a.line.cmp(&b.line)));
+    occurrences
+}
+
+fn location(path: &Path, line: usize) -> String {
+    format!("{}:{}: ", path.display(), line)
+}
+
+/// Prompt asking the model to rewrite the occurrence lines in a parseable format
+pub fn build_prompt(symbol: &str, instruction: &str, occurrences: &[Occurrence]) -> String {
+    let mut prompt = format!(
+        "You are refactoring the symbol `{}` across a code base.\n\
+         Requested change: {}\n\n\
+         Below is every line that mentions the symbol, formatted as `path:line: content`.\n\
+         Reply with ONLY the lines that must change, in the same `path:line: new content` format, \
+         one per line, keeping indentation. Do not invent lines that are not listed and do not add commentary.\n\n",
+        symbol, instruction
+    );
+    for occurrence in occurrences.iter().take(MAX_OCCURRENCES) {
+        prompt.push_str(&location(&occurrence.path, occurrence.line));
+        prompt.push_str(&occurrence.text);
+        prompt.push('\n');
+    }
+    prompt
+}
+
+/// Edits from the model's reply. Only lines that were sent as occurrences are
+/// accepted, so the model cannot touch anything else.
+pub fn parse_edits(response: &str, occurrences: &[Occurrence]) -> Vec<LineEdit> {
+    let by_location: BTreeMap<String, &Occurrence> = occurrences.iter()
+        .map(|o| (location(&o.path, o.line), o))
+        .collect();
+
+    let mut edits: Vec<LineEdit> = Vec::new();
+    for line in response.lines() {
+        // Models sometimes wrap the reply in a code fence or drop the space after the colon
+        let Some((loc, occurrence)) = by_location.iter()
+            .find(|(loc, _)| line.starts_with(loc.as_str()) || line.starts_with(loc.trim_end()))
+        else {
+            continue;
+        };
+        let new = line.strip_prefix(loc.as_str()).unwrap_or_else(|| &line[loc.trim_end().len()..]);
+        if new == occurrence.text || edits.iter().any(|e| e.path == occurrence.path && e.line == occurrence.line) {
+            continue;
+        }
+        edits.push(LineEdit {
+            path: occurrence.path.clone(),
+            line: occurrence.line,
+            old: occurrence.text.clone(),
+            new: new.to_string(),
+            accepted: true,
+        });
+    }
+    edits.sort_by(|a, b| a.path.cmp(&b.path).then(a.line.cmp(&b.line)));
+    edits
+}
+
+/// Line edits for `path` from a language server's text edits against its
+/// `lines`, one per changed line. Edits spanning lines aren't supported.
+pub fn from_text_edits(path: &Path, lines: &[String], edits: &[TextEdit]) -> Result<Vec<LineEdit>, String> {
+    let mut by_row: BTreeMap<usize, Vec<&TextEdit>> = BTreeMap::new();
+    for edit in edits {
+        if edit.start.0 != edit.end.0 || edit.new_text.contains('\n') || edit.start.0 >= lines.len() {
+            return Err(format!("{}: an edit spans lines", path.display()));
+        }
+        by_row.entry(edit.start.0).or_default().push(edit);
+    }
+    let mut line_edits = Vec::new();
+    for (row, mut row_edits) in by_row {
+        let old = &lines[row];
+        let mut chars: Vec<char> = old.chars().collect();
+        row_edits.sort_by_key(|edit| std::cmp::Reverse(edit.start.1));
+        for edit in row_edits {
+            let (start, end) = (edit.start.1.min(chars.len()), edit.end.1.min(chars.len()));
+            chars.splice(start..end.max(start), edit.new_text.chars());
+        }
+        let new: String = chars.into_iter().collect();
+        if new != *old {
+            line_edits.push(LineEdit { path: path.to_path_buf(), line: row + 1, old: old.clone(), new, accepted: true });
+        }
+    }
+    Ok(line_edits)
+}
+
+/// Group edits (sorted by path) into per-file runs
+pub fn group_by_file(edits: &[LineEdit]) -> Vec<(&Path, &[LineEdit])> {
+    edits
+        .chunk_by(|a, b| a.path == b.path)
+        .map(|chunk| (chunk[0].path.as_path(), chunk))
+        .collect()
+}
+
+/// Write the accepted edits (paths relative to `root`), returning the files changed.
+/// Nothing is written if any target line no longer matches what was proposed.
+pub fn apply_edits(root: &Path, edits: &[LineEdit]) -> Result<Vec<PathBuf>, String> {
+    let mut updated = Vec::new();
+    for (path, file_edits) in group_by_file(edits) {
+        let file_edits: Vec<&LineEdit> = file_edits.iter().filter(|e| e.accepted).collect();
+        if file_edits.is_empty() {
+            continue;
+        }
+        let full_path = root.join(path);
+        let content = fs::read_to_string(&full_path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut lines: Vec<String> = content.split('\n').map(str::to_string).collect();
+        for edit in file_edits {
+            let Some(line) = lines.get_mut(edit.line - 1) else {
+                return Err(format!("{} changed since the refactor was proposed", path.display()));
+            };
+            // Keep CRLF line endings intact
+            let cr = line.ends_with('\r');
+            if line.trim_end_matches('\r') != edit.old {
+                return Err(format!("{}:{} changed since the refactor was proposed", path.display(), edit.line));
+            }
+            *line = if cr { format!("{}\r", edit.new) } else { edit.new.clone() };
+        }
+        updated.push((full_path, lines.join("\n")));
+    }
+
+    for (path, content) in &updated {
+        fs::write(path, content).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    Ok(updated.into_iter().map(|(path, _)| path).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_and_parse() {
+        let content = "fn load_user() {}\nlet x = load_user();\nlet y = load_users();\n";
+        let occurrences = find_in_text(Path::new("src/user.rs"), content, "load_user");
+        assert_eq!(occurrences.len(), 2);
+        assert_eq!(occurrences[1].line, 2);
+
+        let response = "```\n\
+            src/user.rs:1: fn fetch_user() {}\n\
+            src/user.rs:2: let x = fetch_user();\n\
+            src/user.rs:3: let y = fetch_users();\n\
+            src/other.rs:9: rm -rf\n\
+            ```";
+        let edits = parse_edits(response, &occurrences);
+        assert_eq!(edits.len(), 2);
+        assert_eq!(edits[0].new, "fn fetch_user() {}");
+        assert_eq!(edits[1].old, "let x = load_user();");
+        assert!(edits.iter().all(|e| e.accepted));
+    }
+
+    #[test]
+    fn test_from_text_edits() {
+        let lines = vec!["let total = total + 1;".to_string(), "print(total)".to_string()];
+        let edit = |row: usize, start: usize, end: usize| TextEdit { start: (row, start), end: (row, end), new_text: "sum".to_string() };
+        let edits = from_text_edits(Path::new("a.rs"), &lines, &[edit(1, 6, 11), edit(0, 4, 9), edit(0, 12, 17)]).unwrap();
+        assert_eq!(edits.len(), 2);
+        assert_eq!((edits[0].line, edits[0].new.as_str()), (1, "let sum = sum + 1;"));
+        assert_eq!((edits[1].line, edits[1].new.as_str()), (2, "print(sum)"));
+        let spanning = TextEdit { start: (0, 4), end: (1, 2), new_text: "x".to_string() };
+        assert!(from_text_edits(Path::new("a.rs"), &lines, &[spanning]).is_err());
+    }
+
+    #[test]
+    fn test_apply_checks_old_lines() {
+        let root = std::env::temp_dir().join(format!("nterm-refactor-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("a.rs"), "let a = 1;\nuse_a(a);\n").unwrap();
+
+        let edit = |line: usize, old: &str, new: &str, accepted: bool| LineEdit {
+            path: PathBuf::from("a.rs"),
+            line,
+            old: old.to_string(),
+            new: new.to_string(),
+            accepted,
+        };
+        let stale = apply_edits(&root, &[edit(1, "let a = 2;", "let b = 2;", true)]);
+        assert!(stale.is_err());
+
+        let changed = apply_edits(&root, &[
+            edit(1, "let a = 1;", "let b = 1;", true),
+            edit(2, "use_a(a);", "use_a(b);", false),
+        ]).unwrap();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(fs::read_to_string(root.join("a.rs")).unwrap(), "let b = 1;\nuse_a(a);\n");
+        let _ = fs::remove_dir_all(&root);
+    }
+}
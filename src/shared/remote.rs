@@ -0,0 +1,246 @@
+// Remote control of a running nterm instance over a unix socket
+//
+// Protocol: the client sends one command per line and reads back a single line,
+// either `ok` or `error: <message>`.
+//
+//   open <path>[:line[:col]]   open a file in the editor
+//   action <name>              run an editor action (e.g. `save`)
+//   chat <prompt>              send a prompt to the AI chat
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::config::Config;
+
+/// Environment variable exported to spawned shells so nested `nterm --remote`
+/// calls reach the instance that owns the terminal
+pub const SOCKET_ENV: &str = "NTERM_SOCKET";
+
+/// A command received from a remote client
+#[derive(Debug, Clone, PartialEq)]
+pub enum RemoteCommand {
+    Open {
+        path: PathBuf,
+        line: Option<usize>,
+        column: Option<usize>,
+    },
+    Action(String),
+    Chat(String),
+}
+
+impl RemoteCommand {
+    /// Parse a protocol line
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let line = line.trim();
+        let (verb, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let rest = rest.trim();
+        match verb {
+            "open" if !rest.is_empty() => Ok(Self::parse_open(rest)),
+            "action" if !rest.is_empty() => Ok(RemoteCommand::Action(rest.to_string())),
+            "chat" if !rest.is_empty() => Ok(RemoteCommand::Chat(rest.to_string())),
+            "open" | "action" | "chat" => Err(format!("'{}' needs an argument", verb)),
+            _ => Err(format!("unknown command '{}'", verb)),
+        }
+    }
+
+    /// `path[:line[:col]]`, tolerating paths that themselves contain colons
+    fn parse_open(target: &str) -> Self {
+        let mut parts: Vec<&str> = target.rsplitn(3, ':').collect();
+        parts.reverse();
+        let numbers: Vec<Option<usize>> = parts.iter().skip(1).map(|p| p.parse().ok()).collect();
+        match (parts.len(), numbers.as_slice()) {
+            (3, [Some(line), Some(col)]) => RemoteCommand::Open {
+                path: PathBuf::from(parts[0]),
+                line: Some(*line),
+                column: Some(*col),
+            },
+            (3, [_, Some(line)]) => RemoteCommand::Open {
+                path: PathBuf::from(format!("{}:{}", parts[0], parts[1])),
+                line: Some(*line),
+                column: None,
+            },
+            (2, [Some(line)]) => RemoteCommand::Open {
+                path: PathBuf::from(parts[0]),
+                line: Some(*line),
+                column: None,
+            },
+            _ => RemoteCommand::Open {
+                path: PathBuf::from(target),
+                line: None,
+                column: None,
+            },
+        }
+    }
+
+    /// Serialize to a protocol line (without the trailing newline)
+    pub fn to_line(&self) -> String {
+        match self {
+            RemoteCommand::Open { path, line, column } => {
+                let mut target = path.display().to_string();
+                if let Some(line) = line {
+                    target.push_str(&format!(":{}", line));
+                    if let Some(column) = column {
+                        target.push_str(&format!(":{}", column));
+                    }
+                }
+                format!("open {}", target)
+            }
+            RemoteCommand::Action(name) => format!("action {}", name),
+            RemoteCommand::Chat(prompt) => format!("chat {}", prompt.replace('\n', " ")),
+        }
+    }
+
+    /// Make an `open` path absolute relative to the client's working directory
+    pub fn resolve_relative_to(self, cwd: &Path) -> Self {
+        match self {
+            RemoteCommand::Open { path, line, column } if path.is_relative() => RemoteCommand::Open {
+                path: cwd.join(path),
+                line,
+                column,
+            },
+            other => other,
+        }
+    }
+}
+
+/// Socket used by the running instance (overridable via `NTERM_SOCKET`)
+pub fn socket_path() -> PathBuf {
+    std::env::var_os(SOCKET_ENV)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Config::data_dir().join("nterm.sock"))
+}
+
+/// Parse `nterm --remote <args...>` into a command
+pub fn command_from_args(args: &[String]) -> Result<RemoteCommand, String> {
+    let line = args.join(" ");
+    let command = RemoteCommand::parse(&line)?;
+    let cwd = std::env::current_dir().map_err(|e| e.to_string())?;
+    Ok(command.resolve_relative_to(&cwd))
+}
+
+#[cfg(unix)]
+mod imp {
+    use super::RemoteCommand;
+    use std::fs;
+    use std::io::{self, BufRead, BufReader, Write};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::Path;
+    use std::thread;
+
+    pub fn send(socket: &Path, command: &RemoteCommand) -> io::Result<String> {
+        let mut stream = UnixStream::connect(socket)?;
+        writeln!(stream, "{}", command.to_line())?;
+        let mut reply = String::new();
+        BufReader::new(stream).read_line(&mut reply)?;
+        Ok(reply.trim().to_string())
+    }
+
+    pub fn serve<F>(socket: &Path, on_command: F) -> io::Result<()>
+    where
+        F: Fn(RemoteCommand) -> bool + Send + Sync + 'static,
+    {
+        if let Some(parent) = socket.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // A stale socket from a crashed instance would make bind fail
+        if socket.exists() && UnixStream::connect(socket).is_err() {
+            let _ = fs::remove_file(socket);
+        }
+        let listener = UnixListener::bind(socket)?;
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { continue };
+                let Ok(reader) = stream.try_clone() else { continue };
+                let mut writer = stream;
+                for line in BufReader::new(reader).lines() {
+                    let Ok(line) = line else { break };
+                    let reply = match RemoteCommand::parse(&line) {
+                        Ok(command) => {
+                            if !on_command(command) {
+                                return;
+                            }
+                            "ok".to_string()
+                        }
+                        Err(e) => format!("error: {}", e),
+                    };
+                    if writeln!(writer, "{}", reply).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use super::RemoteCommand;
+    use std::io;
+    use std::path::Path;
+
+    fn unsupported() -> io::Error {
+        io::Error::new(io::ErrorKind::Unsupported, "remote control is only supported on unix")
+    }
+
+    pub fn send(_socket: &Path, _command: &RemoteCommand) -> io::Result<String> {
+        Err(unsupported())
+    }
+
+    pub fn serve<F>(_socket: &Path, _on_command: F) -> io::Result<()>
+    where
+        F: Fn(RemoteCommand) -> bool + Send + Sync + 'static,
+    {
+        Err(unsupported())
+    }
+}
+
+/// Send a command to the running instance and return its reply
+pub fn send(socket: &Path, command: &RemoteCommand) -> io::Result<String> {
+    imp::send(socket, command)
+}
+
+/// Listen for remote commands on a background thread. `on_command` returning
+/// false stops the listener.
+pub fn serve<F>(socket: &Path, on_command: F) -> io::Result<()>
+where
+    F: Fn(RemoteCommand) -> bool + Send + Sync + 'static,
+{
+    imp::serve(socket, on_command)
+}
+
+/// Remove the socket file on shutdown
+pub fn cleanup(socket: &Path) {
+    let _ = std::fs::remove_file(socket);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(
+            RemoteCommand::parse("open src/lib.rs:10").unwrap(),
+            RemoteCommand::Open { path: PathBuf::from("src/lib.rs"), line: Some(10), column: None }
+        );
+        assert_eq!(
+            RemoteCommand::parse("open src/lib.rs:10:4").unwrap(),
+            RemoteCommand::Open { path: PathBuf::from("src/lib.rs"), line: Some(10), column: Some(4) }
+        );
+        assert_eq!(
+            RemoteCommand::parse("open README").unwrap(),
+            RemoteCommand::Open { path: PathBuf::from("README"), line: None, column: None }
+        );
+        assert_eq!(RemoteCommand::parse("chat hello there").unwrap(), RemoteCommand::Chat("hello there".into()));
+        assert!(RemoteCommand::parse("open").is_err());
+        assert!(RemoteCommand::parse("frobnicate x").is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let cmd = RemoteCommand::Open { path: PathBuf::from("/tmp/a.rs"), line: Some(3), column: Some(7) };
+        assert_eq!(RemoteCommand::parse(&cmd.to_line()).unwrap(), cmd);
+    }
+}
@@ -0,0 +1,135 @@
+// AI code review with comments anchored to line ranges: the file goes to the
+// model with line numbers (and a patch of the changes since HEAD to focus on),
+// and each `L12-15: comment` line of the reply becomes an inline annotation
+
+use regex::Regex;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use super::diff::Diff;
+
+/// Longer files are reviewed up to this line
+pub const MAX_REVIEW_LINES: usize = 2000;
+
+/// Unchanged lines around each change in the patch sent along
+const PATCH_CONTEXT: usize = 2;
+
+/// How far a comment's line is looked for after edits moved it
+const ANCHOR_SCAN_LINES: usize = 50;
+
+static COMMENT_LINE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\s*(?:[-*]\s*)?L(\d+)(?:\s*-\s*L?(\d+))?\s*:\s*(.*)$").unwrap()
+});
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReviewComment {
+    /// 0-based first and last line the comment is about
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+    /// Text of the first line when the review was made, to follow it through edits
+    pub anchor: String,
+    /// Whether the whole comment is shown rather than its first words
+    pub expanded: bool,
+}
+
+impl ReviewComment {
+    /// Where the comment's range starts in `lines` now: where it was if that
+    /// line is unchanged, else the nearest line with the anchor's text
+    pub fn locate<S: AsRef<str>>(&self, lines: &[S]) -> Option<usize> {
+        let matches = |row: usize| lines.get(row).is_some_and(|l| l.as_ref() == self.anchor);
+        if matches(self.start) {
+            return Some(self.start);
+        }
+        (1..=ANCHOR_SCAN_LINES).find_map(|offset| {
+            let before = self.start.checked_sub(offset).filter(|&row| matches(row));
+            before.or_else(|| Some(self.start + offset).filter(|&row| matches(row)))
+        })
+    }
+}
+
+/// Prompt asking the model to review `lines` of `path`; `changes` is the diff
+/// from the last commit to `lines`
+pub fn build_prompt<S: AsRef<str>>(path: &Path, lines: &[S], changes: Option<&Diff>) -> String {
+    let mut prompt = format!(
+        "Review the file {} below like a careful senior engineer: bugs, edge cases, \
+         unclear code, missing error handling. Skip praise and style nitpicks.\n\
+         Reply with ONLY one comment per line, formatted as `L<first>-L<last>: comment` \
+         (or `L<line>: comment`) using the line numbers shown. Reply with nothing if there is nothing to flag.\n",
+        path.display()
+    );
+    if let Some(diff) = changes.filter(|d| !d.is_identical()) {
+        let rows: Vec<String> = diff.changed_lines().iter().map(|row| (row + 1).to_string()).collect();
+        if !rows.is_empty() {
+            prompt.push_str(&format!("Focus on the lines changed since the last commit: {}\n", rows.join(", ")));
+        }
+        let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let patch = diff.patch(&format!("{} (last commit)", name), &format!("{} (now)", name), PATCH_CONTEXT);
+        prompt.push_str(&format!("The changes:\n```diff\n{}```\n", patch));
+    }
+    prompt.push('\n');
+    for (row, line) in lines.iter().take(MAX_REVIEW_LINES).enumerate() {
+        prompt.push_str(&format!("{:>5}: {}\n", row + 1, line.as_ref()));
+    }
+    prompt
+}
+
+/// Comments from the model's reply, for a file of `lines`; lines that don't
+/// start with a line reference continue the previous comment
+pub fn parse_comments<S: AsRef<str>>(reply: &str, lines: &[S]) -> Vec<ReviewComment> {
+    let last = lines.len().saturating_sub(1);
+    let mut comments: Vec<ReviewComment> = Vec::new();
+    for line in reply.lines() {
+        if let Some(captures) = COMMENT_LINE.captures(line) {
+            let number = |i: usize| captures.get(i).and_then(|m| m.as_str().parse::<usize>().ok());
+            let Some(first) = number(1) else { continue };
+            let second = number(2).unwrap_or(first);
+            let start = first.min(second).saturating_sub(1).min(last);
+            let end = first.max(second).saturating_sub(1).min(last);
+            comments.push(ReviewComment {
+                start,
+                end,
+                text: captures[3].trim().to_string(),
+                anchor: lines.get(start).map(|l| l.as_ref().to_string()).unwrap_or_default(),
+                expanded: false,
+            });
+        } else if let Some(comment) = comments.last_mut() {
+            let text = line.trim();
+            if !text.is_empty() && !text.starts_with("```") {
+                comment.text.push(' ');
+                comment.text.push_str(text);
+            }
+        }
+    }
+    comments.retain(|c| !c.text.is_empty());
+    comments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::diff::Algorithm;
+
+    #[test]
+    fn test_parse_and_locate() {
+        let lines = ["fn main() {", "    let x = parse();", "    x.unwrap();", "}"];
+        let reply = "```\nL2-L3: `parse` can fail and\nthe unwrap panics\n- L9: past the end\nL2 - 1: reversed\nnoise\n```";
+        let comments = parse_comments(reply, &lines);
+        assert_eq!(comments.len(), 3);
+        assert_eq!((comments[0].start, comments[0].end), (1, 2));
+        assert_eq!(comments[0].text, "`parse` can fail and the unwrap panics");
+        assert_eq!((comments[1].start, comments[1].end), (3, 3));
+        assert_eq!((comments[2].start, comments[2].end), (0, 1));
+
+        let edited = ["// entry point", "fn main() {", "    let x = parse();", "    x.unwrap();", "}"];
+        assert_eq!(comments[0].locate(&edited), Some(2));
+        assert_eq!(comments[0].locate(&["fn main() {}"]), None);
+        let head = ["fn main() {", "    let x = parse();", "    x.expect(\"parsed\");", "}"].map(String::from).to_vec();
+        let changes = Diff::new(Algorithm::Myers, head, lines.map(String::from).to_vec());
+        let prompt = build_prompt(Path::new("src/a.rs"), &lines, Some(&changes));
+        assert!(prompt.contains("changed since the last commit: 3\n"));
+        assert!(prompt.contains("+++ a.rs (now)\n@@ -1,4 +1,4 @@\n"));
+        assert!(prompt.contains("\n-    x.expect(\"parsed\");\n+    x.unwrap();\n"));
+        assert!(!build_prompt(Path::new("a.rs"), &lines, None).contains("```diff"));
+    }
+}
@@ -0,0 +1,225 @@
+// Commands the AI runs through the run_command tool. They run inside the
+// workspace with a scrubbed environment and a timeout, and unless the config
+// allows it, without network access (an empty network namespace on Linux, a
+// sandbox-exec profile on macOS). Platforms without either refuse to run them.
+
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::ai::tools::RUN_COMMAND;
+use super::ai::ToolCall;
+use super::config::CommandSandbox;
+
+/// Output beyond this many bytes per stream is dropped
+const MAX_OUTPUT: usize = 16 * 1024;
+
+/// How a command run went, with stdout and stderr kept apart for the model
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandOutput {
+    pub command: String,
+    /// Relative to the workspace root
+    pub cwd: PathBuf,
+    /// `None` when the command was killed
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub timed_out: bool,
+}
+
+impl CommandOutput {
+    /// Short note for the chat transcript
+    pub fn summary(&self) -> String {
+        match (self.timed_out, self.exit_code) {
+            (true, _) => format!("[ran `{}`: timed out]", self.command),
+            (false, Some(code)) => format!("[ran `{}`: exit {}]", self.command, code),
+            (false, None) => format!("[ran `{}`: killed]", self.command),
+        }
+    }
+
+    /// The result as sent back to the model
+    pub fn for_model(&self) -> String {
+        let status = match (self.timed_out, self.exit_code) {
+            (true, _) => "killed after the timeout".to_string(),
+            (false, Some(code)) => format!("exit code {}", code),
+            (false, None) => "killed by a signal".to_string(),
+        };
+        format!(
+            "$ {}\n(cwd: {}, {})\n<stdout>\n{}</stdout>\n<stderr>\n{}</stderr>",
+            self.command,
+            if self.cwd.as_os_str().is_empty() { Path::new(".") } else { &self.cwd }.display(),
+            status,
+            self.stdout,
+            self.stderr,
+        )
+    }
+}
+
+/// Run the command from a `run_command` call. Returns `None` for calls to other tools.
+pub fn from_tool_call(root: &Path, call: &ToolCall, config: &CommandSandbox) -> Option<Result<CommandOutput, String>> {
+    if call.name != RUN_COMMAND {
+        return None;
+    }
+    if !config.enabled {
+        return Some(Err("running commands is disabled".to_string()));
+    }
+    let Some(command) = call.arguments.get("command").and_then(|c| c.as_str()).filter(|c| !c.trim().is_empty()) else {
+        return Some(Err("run_command needs a command".to_string()));
+    };
+    let cwd = call.arguments.get("cwd").and_then(|c| c.as_str()).unwrap_or("");
+    Some(run(root, Path::new(cwd), command, config))
+}
+
+/// Run `command` with `sh -c` in `cwd` (relative to `root`) within the sandbox limits
+pub fn run(root: &Path, cwd: &Path, command: &str, config: &CommandSandbox) -> Result<CommandOutput, String> {
+    let dir = resolve_cwd(root, cwd)?;
+    let mut cmd = sandboxed(command, config.allow_network)?;
+    cmd.current_dir(&dir)
+        .env_clear()
+        .envs(["PATH", "HOME", "LANG", "USER"].iter().filter_map(|k| Some((k, std::env::var_os(k)?))))
+        .env("TERM", "dumb")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    // Own process group, so a timeout kills everything the command started
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
+
+    let mut child = cmd.spawn().map_err(|e| format!("could not start `{}`: {}", command, e))?;
+    let stdout = capture(child.stdout.take());
+    let stderr = capture(child.stderr.take());
+    let (exit_code, timed_out) = wait(&mut child, Duration::from_secs(config.timeout_secs.max(1)));
+
+    Ok(CommandOutput {
+        command: command.to_string(),
+        cwd: cwd.to_path_buf(),
+        exit_code,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+        timed_out,
+    })
+}
+
+/// The working directory must be an existing directory inside the workspace
+fn resolve_cwd(root: &Path, cwd: &Path) -> Result<PathBuf, String> {
+    if cwd.components().any(|c| !matches!(c, Component::Normal(_) | Component::CurDir)) {
+        return Err(format!("{}: cwd must be relative to the workspace", cwd.display()));
+    }
+    let root = root.canonicalize().map_err(|e| e.to_string())?;
+    // Resolves symlinks, which could otherwise point out of the workspace
+    let dir = root.join(cwd).canonicalize().map_err(|e| format!("{}: {}", cwd.display(), e))?;
+    if !dir.starts_with(&root) || !dir.is_dir() {
+        return Err(format!("{}: not a directory in the workspace", cwd.display()));
+    }
+    Ok(dir)
+}
+
+fn sandboxed(command: &str, allow_network: bool) -> Result<Command, String> {
+    if allow_network {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        return Ok(cmd);
+    }
+    #[cfg(target_os = "linux")]
+    {
+        let mut cmd = Command::new("unshare");
+        cmd.args(["--net", "--map-root-user", "--", "sh", "-c", command]);
+        Ok(cmd)
+    }
+    #[cfg(target_os = "macos")]
+    {
+        let mut cmd = Command::new("sandbox-exec");
+        cmd.args(["-p", "(version 1)(allow default)(deny network*)", "sh", "-c", command]);
+        Ok(cmd)
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    {
+        let _ = command;
+        Err("commands can't be cut off from the network on this platform; set ai_commands.allow_network to run them".to_string())
+    }
+}
+
+/// Read a pipe to the end on a background thread, keeping the first MAX_OUTPUT bytes
+fn capture(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let Some(mut pipe) = pipe else { return String::new() };
+        let mut kept = Vec::new();
+        let mut buffer = [0u8; 4096];
+        let mut dropped = 0;
+        while let Ok(n) = pipe.read(&mut buffer) {
+            if n == 0 {
+                break;
+            }
+            let room = MAX_OUTPUT.saturating_sub(kept.len()).min(n);
+            kept.extend_from_slice(&buffer[..room]);
+            dropped += n - room;
+        }
+        let mut text = String::from_utf8_lossy(&kept).into_owned();
+        if dropped > 0 {
+            text.push_str(&format!("\n[{} more bytes not shown]\n", dropped));
+        }
+        text
+    })
+}
+
+/// Wait for the child, killing its process group once `timeout` passes
+fn wait(child: &mut Child, timeout: Duration) -> (Option<i32>, bool) {
+    let started = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return (status.code(), false),
+            Ok(None) if started.elapsed() < timeout => thread::sleep(Duration::from_millis(20)),
+            _ => break,
+        }
+    }
+    #[cfg(unix)]
+    // SAFETY: kill has no memory effects; the negative pid targets the child's own group
+    unsafe {
+        libc::kill(-(child.id() as i32), libc::SIGKILL);
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+    (None, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config() -> CommandSandbox {
+        CommandSandbox { enabled: true, allow_network: true, timeout_secs: 1 }
+    }
+
+    #[test]
+    fn test_run_captures_streams() {
+        let root = std::env::temp_dir().join(format!("nterm-sandbox-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        let call = ToolCall {
+            name: RUN_COMMAND.to_string(),
+            arguments: json!({ "command": "pwd; echo oops >&2; exit 3", "cwd": "sub" }),
+        };
+        let output = from_tool_call(&root, &call, &config()).unwrap().unwrap();
+        assert_eq!(output.exit_code, Some(3));
+        assert!(output.stdout.trim_end().ends_with("sub"));
+        assert_eq!(output.stderr, "oops\n");
+        assert!(output.for_model().contains("<stderr>\noops\n</stderr>"));
+
+        let escape = ToolCall { arguments: json!({ "command": "ls", "cwd": "../" }), ..call.clone() };
+        assert!(from_tool_call(&root, &escape, &config()).unwrap().is_err());
+        let disabled = CommandSandbox { enabled: false, ..config() };
+        assert!(from_tool_call(&root, &call, &disabled).unwrap().is_err());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_timeout_kills_command() {
+        let started = Instant::now();
+        let output = run(&std::env::temp_dir(), Path::new(""), "sleep 5 & sleep 5", &config()).unwrap();
+        assert!(output.timed_out);
+        assert_eq!(output.exit_code, None);
+        assert!(started.elapsed() < Duration::from_secs(4));
+    }
+}
@@ -0,0 +1,63 @@
+// Scratch buffers: unsaved notes kept alongside the session
+//
+// Scratch buffers never touch the workspace. When `persist_scratch` is enabled
+// they are kept in storage on a clean exit and reopened next session.
+
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use super::config::Config;
+
+/// Prefix of generated scratch buffer names (`scratch-1`, `scratch-2`, ...)
+pub const SCRATCH_PREFIX: &str = "scratch-";
+
+/// A persisted scratch buffer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScratchBuffer {
+    pub name: String,
+    pub lines: Vec<String>,
+}
+
+/// First `scratch-N` name not already taken
+pub fn next_name<'a>(existing: impl IntoIterator<Item = &'a str>) -> String {
+    let highest = existing
+        .into_iter()
+        .filter_map(|name| name.strip_prefix(SCRATCH_PREFIX))
+        .filter_map(|n| n.parse::<usize>().ok())
+        .max()
+        .unwrap_or(0);
+    format!("{}{}", SCRATCH_PREFIX, highest + 1)
+}
+
+/// Where versions before the storage database kept a workspace's scratch buffers
+fn legacy_session_file(workspace: &Path) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    workspace.hash(&mut hasher);
+    Config::data_dir()
+        .join("scratch")
+        .join(format!("{:016x}.json", hasher.finish()))
+}
+
+/// Scratch buffers an older version saved for a workspace, removing the file
+/// so they are only moved into storage once
+pub fn take_legacy(workspace: &Path) -> Vec<ScratchBuffer> {
+    let file = legacy_session_file(workspace);
+    let Ok(content) = fs::read_to_string(&file) else { return Vec::new() };
+    let _ = fs::remove_file(file);
+    serde_json::from_str(&content).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_name() {
+        assert_eq!(next_name([]), "scratch-1");
+        assert_eq!(next_name(["scratch-1", "main.rs", "scratch-3"]), "scratch-4");
+        assert_eq!(next_name(["scratch-x"]), "scratch-1");
+    }
+}
@@ -0,0 +1,106 @@
+// Capturing ranges of terminal scrollback as plain text
+
+use super::ansi::{render_lines, StyledLine};
+
+/// Which part of the terminal history to capture
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureRange {
+    /// Output of the most recent command (between the last two prompts)
+    LastCommand,
+    All,
+    /// The last N lines
+    Last(usize),
+    /// 1-based inclusive line numbers
+    Lines(usize, usize),
+}
+
+impl CaptureRange {
+    /// Parse a range typed by the user: empty for the last command's output,
+    /// `all`, `N` for the last N lines, or `A-B` for a line range
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let spec = spec.trim();
+        if spec.is_empty() {
+            return Ok(CaptureRange::LastCommand);
+        }
+        if spec.eq_ignore_ascii_case("all") {
+            return Ok(CaptureRange::All);
+        }
+        let number = |s: &str| s.trim().parse::<usize>().map_err(|_| format!("invalid line number '{}'", s.trim()));
+        match spec.split_once('-') {
+            Some((start, end)) => {
+                let (start, end) = (number(start)?, number(end)?);
+                if start == 0 || end < start {
+                    return Err(format!("invalid range '{}'", spec));
+                }
+                Ok(CaptureRange::Lines(start, end))
+            }
+            None => Ok(CaptureRange::Last(number(spec)?)),
+        }
+    }
+}
+
+/// Parse the capture prompt: a range optionally followed by `color` to keep the output's colors
+pub fn parse_request(spec: &str) -> Result<(CaptureRange, bool), String> {
+    let spec = spec.trim();
+    match spec.strip_suffix("color") {
+        Some(range) => Ok((CaptureRange::parse(range)?, true)),
+        None => Ok((CaptureRange::parse(spec)?, false)),
+    }
+}
+
+/// Decode raw PTY output into lines as the terminal displayed them
+pub fn history_lines(bytes: &[u8]) -> Vec<StyledLine> {
+    render_lines(&String::from_utf8_lossy(bytes))
+}
+
+/// The requested lines of `lines`; an empty result means nothing matched
+pub fn capture(lines: &[StyledLine], range: CaptureRange) -> Vec<StyledLine> {
+    let selected = match range {
+        CaptureRange::All => lines,
+        CaptureRange::Last(n) => &lines[lines.len().saturating_sub(n)..],
+        CaptureRange::Lines(start, end) => {
+            let end = end.min(lines.len());
+            if start > end { &[] } else { &lines[start - 1..end] }
+        }
+        CaptureRange::LastCommand => last_command_output(lines).unwrap_or(&[]),
+    };
+    selected.to_vec()
+}
+
+/// Lines printed by the last command. The final line is taken to be the
+/// current (empty) prompt; the command line is the previous line starting with it.
+fn last_command_output(lines: &[StyledLine]) -> Option<&[StyledLine]> {
+    let prompt_idx = lines.iter().rposition(|l| !l.text.trim().is_empty())?;
+    let prompt = lines[prompt_idx].text.trim_end();
+    let command_idx = lines[..prompt_idx].iter().rposition(|l| l.text.starts_with(prompt))?;
+    Some(&lines[command_idx + 1..prompt_idx])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(CaptureRange::parse(""), Ok(CaptureRange::LastCommand));
+        assert_eq!(CaptureRange::parse("ALL"), Ok(CaptureRange::All));
+        assert_eq!(CaptureRange::parse("50"), Ok(CaptureRange::Last(50)));
+        assert_eq!(CaptureRange::parse("10 - 20"), Ok(CaptureRange::Lines(10, 20)));
+        assert!(CaptureRange::parse("20-10").is_err());
+        assert!(CaptureRange::parse("x").is_err());
+        assert_eq!(parse_request("all color"), Ok((CaptureRange::All, true)));
+        assert_eq!(parse_request("5"), Ok((CaptureRange::Last(5), false)));
+    }
+
+    #[test]
+    fn test_capture() {
+        let raw = b"\x1b[32muser@host\x1b[0m:~$ ls\r\nCargo.toml\r\nsrc\r\n\x1b[32muser@host\x1b[0m:~$ cargo check\r\n\x1b[1;31merror\x1b[0m: oops\r\nfailed\r\n\x1b[32muser@host\x1b[0m:~$ ";
+        let lines = history_lines(raw);
+        let texts = |range| capture(&lines, range).into_iter().map(|l| l.text).collect::<Vec<_>>();
+        assert_eq!(lines[1].text, "Cargo.toml");
+        assert_eq!(texts(CaptureRange::LastCommand), vec!["error: oops", "failed"]);
+        assert_eq!(texts(CaptureRange::Lines(2, 3)), vec!["Cargo.toml", "src"]);
+        assert_eq!(texts(CaptureRange::Last(1)), vec!["user@host:~$ "]);
+        assert!(capture(&lines, CaptureRange::Lines(40, 50)).is_empty());
+    }
+}
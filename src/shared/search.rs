@@ -0,0 +1,42 @@
+// Workspace file search by name, shared by the search modal and `nterm-cli search`
+
+use std::path::{Path, PathBuf};
+
+use super::workspace;
+
+/// Files under `root` whose name contains `query` (case-insensitive), in walk
+/// order, at most `limit`, skipping what every workspace walk skips (see
+/// `workspace::walk_files`). Paths start with `root`.
+pub fn find_files(root: &Path, query: &str, limit: usize) -> Vec<PathBuf> {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    workspace::walk_files(root)
+        .filter(|e| e.file_name().to_str().is_some_and(|name| name.to_lowercase().contains(&query)))
+        .take(limit)
+        .map(|e| e.path().to_path_buf())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_find_files() {
+        let root = std::env::temp_dir().join(format!("nterm-search-{}", std::process::id()));
+        for dir in ["src", "target/debug", ".git", "node_modules/pkg"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in ["src/Main.rs", "src/lib.rs", "target/debug/main.d", ".git/main", "node_modules/pkg/main.js"] {
+            fs::write(root.join(file), "").unwrap();
+        }
+        assert_eq!(find_files(&root, "main", 10), vec![root.join("src/Main.rs")]);
+        assert_eq!(find_files(&root, ".RS", 10).len(), 2);
+        assert_eq!(find_files(&root, ".rs", 1).len(), 1);
+        assert!(find_files(&root, " ", 10).is_empty());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,125 @@
+// Rolling restore points of the session state: the config file and the chat
+// and scratch buffer database are copied into a time-stamped directory at
+// startup and every half hour, keeping the newest few, so a corrupted or
+// clobbered state file can be rolled back with File > Restore Session From...
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::chat::format_date_time;
+use super::config::Config;
+use super::storage::Storage;
+
+/// Time between restore points while nterm runs
+pub const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+const CONFIG_FILE: &str = "config.json";
+const DATABASE_FILE: &str = "nterm.db";
+
+/// A saved copy of the session state, in a directory named after its Unix time
+#[derive(Debug, Clone, PartialEq)]
+pub struct RestorePoint {
+    pub dir: PathBuf,
+    pub timestamp: u64,
+}
+
+impl RestorePoint {
+    /// When it was made, e.g. `2024-03-01 14:05`
+    pub fn label(&self) -> String {
+        format_date_time(self.timestamp)
+    }
+
+    /// The saved config, if the restore point has one; an error if it doesn't parse
+    pub fn config(&self) -> Result<Option<Config>, String> {
+        match fs::read_to_string(self.dir.join(CONFIG_FILE)) {
+            Ok(content) => Config::from_json(&content).map(Some).map_err(|e| format!("saved config is invalid: {}", e)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// The saved database, if the restore point has one
+    pub fn database(&self) -> Option<PathBuf> {
+        Some(self.dir.join(DATABASE_FILE)).filter(|path| path.is_file())
+    }
+}
+
+/// Where restore points are kept
+pub fn backups_dir() -> PathBuf {
+    Config::data_dir().join("backups")
+}
+
+/// Restore points in `dir`, newest first
+pub fn list(dir: &Path) -> Vec<RestorePoint> {
+    let mut points: Vec<RestorePoint> = fs::read_dir(dir).into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let timestamp = entry.file_name().to_str()?.parse().ok()?;
+            Some(RestorePoint { dir: entry.path(), timestamp })
+        })
+        .collect();
+    points.sort_by_key(|point| std::cmp::Reverse(point.timestamp));
+    points
+}
+
+/// Save `config_file` and everything in `storage` as a new restore point in
+/// `dir`, then drop all but the newest `keep`. `spare` is never dropped, so a
+/// point about to be restored survives the snapshot taken before restoring it.
+pub fn create(dir: &Path, config_file: &Path, storage: Option<&dyn Storage>, keep: usize, spare: Option<&RestorePoint>) -> Result<RestorePoint, String> {
+    // Always after the newest point, even one made within the same second
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let timestamp = list(dir).first().map_or(now, |newest| now.max(newest.timestamp + 1));
+    let point = RestorePoint { dir: dir.join(timestamp.to_string()), timestamp };
+    fs::create_dir_all(&point.dir).map_err(|e| format!("could not create {}: {}", point.dir.display(), e))?;
+    if config_file.is_file() {
+        fs::copy(config_file, point.dir.join(CONFIG_FILE)).map_err(|e| format!("could not copy the config: {}", e))?;
+    }
+    if let Some(storage) = storage {
+        storage.backup_to(&point.dir.join(DATABASE_FILE))?;
+    }
+    for old in list(dir).iter().skip(keep.max(1)).filter(|old| Some(*old) != spare) {
+        let _ = fs::remove_dir_all(&old.dir);
+    }
+    Ok(point)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::chat::ChatMessage;
+    use crate::shared::storage::SqliteStorage;
+
+    #[test]
+    fn test_create_and_list() {
+        let dir = std::env::temp_dir().join(format!("nterm-session-backup-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let config_file = dir.join("config-in-use.json");
+        fs::write(&config_file, "{\"theme\": \"Light\"}").unwrap();
+        let mut storage = SqliteStorage::in_memory().unwrap();
+        storage.append_message(Path::new("/work"), &ChatMessage::user("hello")).unwrap();
+
+        let backups = dir.join("backups");
+        let first = create(&backups, &config_file, Some(&storage), 2, None).unwrap();
+        let second = create(&backups, &config_file, None, 2, None).unwrap();
+        let third = create(&backups, &config_file, Some(&storage), 2, None).unwrap();
+        assert!(second.timestamp > first.timestamp);
+        assert_eq!(list(&backups), vec![third.clone(), second.clone()]);
+
+        assert!(third.config().unwrap().is_some());
+        assert!(third.database().is_some());
+        assert!(second.database().is_none());
+        fs::write(third.dir.join(CONFIG_FILE), "{ not json").unwrap();
+        assert!(third.config().is_err());
+
+        // Restoring the oldest point at the limit snapshots first without losing it
+        fs::copy(third.database().unwrap(), second.dir.join(DATABASE_FILE)).unwrap();
+        let oldest = list(&backups).pop().unwrap();
+        assert_eq!(oldest, second);
+        let fourth = create(&backups, &config_file, Some(&storage), 2, Some(&oldest)).unwrap();
+        assert_eq!(list(&backups), vec![fourth, third, second.clone()]);
+        assert!(second.database().is_some());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
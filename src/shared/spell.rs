@@ -0,0 +1,472 @@
+// Spell checking against hunspell dictionaries (.aff/.dic)
+//
+// Only the parts of the hunspell format needed to list valid words are read:
+// prefix and suffix rules with their conditions and cross products. Every stem
+// is expanded once when the dictionary loads, so checking a word is a lookup.
+// Words added by the user go to a per-workspace list in the data dir.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use super::config::Config;
+
+pub const DEFAULT_LANGUAGE: &str = "en_US";
+
+/// Directories searched for `<language>.aff` / `<language>.dic`
+pub fn dictionary_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = std::env::var("DICPATH")
+        .map(|paths| std::env::split_paths(&paths).collect())
+        .unwrap_or_default();
+    if let Some(home) = dirs::home_dir() {
+        dirs.push(home.join(".local/share/hunspell"));
+        dirs.push(home.join("Library/Spelling"));
+    }
+    for dir in ["/usr/share/hunspell", "/usr/share/myspell", "/usr/share/myspell/dicts", "/Library/Spelling"] {
+        dirs.push(PathBuf::from(dir));
+    }
+    dirs
+}
+
+/// Which parts of a buffer are prose worth checking
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProseKind {
+    /// Everything outside code blocks and inline code
+    Markdown,
+    /// Text after a line comment marker
+    Comments(&'static str),
+}
+
+impl ProseKind {
+    pub fn for_path(path: &Path) -> Option<ProseKind> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        let kind = match ext.as_str() {
+            "md" | "markdown" | "txt" => ProseKind::Markdown,
+            "rs" | "c" | "h" | "cpp" | "hpp" | "cc" | "js" | "jsx" | "ts" | "tsx" | "go" | "java" | "kt"
+            | "swift" | "cs" | "scala" | "dart" | "zig" => ProseKind::Comments("//"),
+            "py" | "sh" | "bash" | "zsh" | "rb" | "toml" | "yaml" | "yml" | "pl" | "r" => ProseKind::Comments("#"),
+            "sql" | "lua" | "hs" => ProseKind::Comments("--"),
+            _ => return None,
+        };
+        Some(kind)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FlagMode {
+    Char,
+    Long,
+    Num,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum CharClass {
+    Any,
+    Set { chars: Vec<char>, negated: bool },
+}
+
+impl CharClass {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            CharClass::Any => true,
+            CharClass::Set { chars, negated } => chars.contains(&c) != *negated,
+        }
+    }
+}
+
+/// Affix condition such as `[^aeiou]y`, matched against the start (prefixes) or end (suffixes) of a stem
+fn parse_condition(condition: &str) -> Vec<CharClass> {
+    let mut classes = Vec::new();
+    let mut chars = condition.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => classes.push(CharClass::Any),
+            '[' => {
+                let mut set: Vec<char> = chars.by_ref().take_while(|&c| c != ']').collect();
+                let negated = set.first() == Some(&'^');
+                if negated {
+                    set.remove(0);
+                }
+                classes.push(CharClass::Set { chars: set, negated });
+            }
+            c => classes.push(CharClass::Set { chars: vec![c], negated: false }),
+        }
+    }
+    classes
+}
+
+#[derive(Debug, Clone)]
+struct Affix {
+    prefix: bool,
+    cross_product: bool,
+    strip: String,
+    add: String,
+    condition: Vec<CharClass>,
+}
+
+impl Affix {
+    fn apply(&self, stem: &str) -> Option<String> {
+        let chars: Vec<char> = stem.chars().collect();
+        let n = self.condition.len();
+        if chars.len() < n.max(self.strip.chars().count()) {
+            return None;
+        }
+        let window = if self.prefix { &chars[..n] } else { &chars[chars.len() - n..] };
+        if !self.condition.iter().zip(window).all(|(class, &c)| class.matches(c)) {
+            return None;
+        }
+        if self.prefix {
+            let rest = stem.strip_prefix(self.strip.as_str())?;
+            Some(format!("{}{}", self.add, rest))
+        } else {
+            let rest = stem.strip_suffix(self.strip.as_str())?;
+            Some(format!("{}{}", rest, self.add))
+        }
+    }
+}
+
+fn parse_flags(flags: &str, mode: FlagMode) -> Vec<String> {
+    match mode {
+        FlagMode::Char => flags.chars().map(String::from).collect(),
+        FlagMode::Long => {
+            let chars: Vec<char> = flags.chars().collect();
+            chars.chunks(2).map(|pair| pair.iter().collect()).collect()
+        }
+        FlagMode::Num => flags.split(',').map(|f| f.trim().to_string()).collect(),
+    }
+}
+
+pub struct SpellChecker {
+    words: HashSet<String>,
+    /// Words the user added, lowercased
+    custom: HashSet<String>,
+}
+
+impl SpellChecker {
+    /// Build the word list from the contents of an .aff and a .dic file
+    pub fn from_hunspell(aff: &str, dic: &str) -> Self {
+        let mut mode = FlagMode::Char;
+        let mut affixes: HashMap<String, Vec<Affix>> = HashMap::new();
+        let mut cross: HashMap<String, bool> = HashMap::new();
+        for line in aff.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                ["FLAG", "long", ..] => mode = FlagMode::Long,
+                ["FLAG", "num", ..] => mode = FlagMode::Num,
+                ["PFX" | "SFX", flag, cross_product, count] if count.parse::<usize>().is_ok() => {
+                    cross.insert(flag.to_string(), *cross_product == "Y");
+                }
+                [kind @ ("PFX" | "SFX"), flag, strip, add, condition, ..] => {
+                    let add = add.split('/').next().unwrap_or("");
+                    affixes.entry(flag.to_string()).or_default().push(Affix {
+                        prefix: *kind == "PFX",
+                        cross_product: cross.get(*flag).copied().unwrap_or(false),
+                        strip: if *strip == "0" { String::new() } else { strip.to_string() },
+                        add: if add == "0" { String::new() } else { add.to_string() },
+                        condition: parse_condition(condition),
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        let mut words = HashSet::new();
+        // The first line is the (approximate) word count
+        for line in dic.lines().skip(1) {
+            let entry = line.split_whitespace().next().unwrap_or("");
+            if entry.is_empty() {
+                continue;
+            }
+            let (stem, flags) = entry.split_once('/').unwrap_or((entry, ""));
+            let rules: Vec<&Affix> = parse_flags(flags, mode)
+                .iter()
+                .filter_map(|flag| affixes.get(flag))
+                .flatten()
+                .collect();
+
+            let mut suffixed = Vec::new();
+            for rule in rules.iter().filter(|r| !r.prefix) {
+                if let Some(form) = rule.apply(stem) {
+                    if rule.cross_product {
+                        suffixed.push(form.clone());
+                    }
+                    words.insert(form);
+                }
+            }
+            for rule in rules.iter().filter(|r| r.prefix) {
+                words.extend(rule.apply(stem));
+                if rule.cross_product {
+                    words.extend(suffixed.iter().filter_map(|form| rule.apply(form)));
+                }
+            }
+            words.insert(stem.to_string());
+        }
+        Self { words, custom: HashSet::new() }
+    }
+
+    /// Load `<language>.aff`/`.dic` from the first dictionary directory that has them
+    pub fn load(language: &str) -> Result<Self, String> {
+        for dir in dictionary_dirs() {
+            let aff = dir.join(format!("{}.aff", language));
+            let dic = dir.join(format!("{}.dic", language));
+            if aff.is_file() && dic.is_file() {
+                let read = |path: &Path| {
+                    fs::read(path)
+                        .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+                        .map_err(|e| format!("{}: {}", path.display(), e))
+                };
+                return Ok(Self::from_hunspell(&read(&aff)?, &read(&dic)?));
+            }
+        }
+        Err(format!("no hunspell dictionary for {} (set DICPATH or install one)", language))
+    }
+
+    /// Also accept the words in this workspace's custom dictionary
+    pub fn load_custom(&mut self, workspace: &Path) {
+        if let Ok(content) = fs::read_to_string(custom_dictionary_path(workspace)) {
+            self.custom.extend(content.lines().map(|w| w.trim().to_lowercase()).filter(|w| !w.is_empty()));
+        }
+    }
+
+    /// Add a word to the workspace's custom dictionary
+    pub fn add_word(&mut self, workspace: &Path, word: &str) -> Result<(), String> {
+        let path = custom_dictionary_path(workspace);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        writeln!(file, "{}", word).map_err(|e| e.to_string())?;
+        self.custom.insert(word.to_lowercase());
+        Ok(())
+    }
+
+    pub fn is_correct(&self, word: &str) -> bool {
+        let word = word.trim_matches('\'');
+        let lower = word.to_lowercase();
+        let word = word.strip_suffix("'s").unwrap_or(word);
+        self.words.contains(word)
+            || self.custom.contains(&lower)
+            // "The" at the start of a sentence
+            || (word.chars().next().is_some_and(char::is_uppercase) && self.words.contains(&lower))
+            || self.words.contains(lower.strip_suffix("'s").unwrap_or(&lower))
+    }
+
+    /// Dictionary words one (or failing that, two) edits away, best first
+    pub fn suggest(&self, word: &str, limit: usize) -> Vec<String> {
+        let lower = word.to_lowercase();
+        let capitalized = word.chars().next().is_some_and(char::is_uppercase);
+        let mut found: Vec<String> = Vec::new();
+        let push = |candidate: String, found: &mut Vec<String>| {
+            if candidate != lower && self.words.contains(&candidate) && !found.contains(&candidate) {
+                found.push(candidate);
+            }
+        };
+        let first = edits(&lower);
+        for candidate in &first {
+            push(candidate.clone(), &mut found);
+        }
+        if found.is_empty() {
+            for candidate in &first {
+                for second in edits(candidate) {
+                    push(second, &mut found);
+                }
+            }
+        }
+        found.truncate(limit);
+        if capitalized {
+            for suggestion in &mut found {
+                let mut chars = suggestion.chars();
+                if let Some(first) = chars.next() {
+                    *suggestion = first.to_uppercase().chain(chars).collect();
+                }
+            }
+        }
+        found
+    }
+
+    /// Misspelled words in a stretch of prose as char ranges (`end` exclusive)
+    pub fn misspelled(&self, text: &str) -> Vec<(usize, usize)> {
+        words(text).into_iter()
+            .filter(|&(start, end)| {
+                let word: String = text.chars().skip(start).take(end - start).collect();
+                !self.is_correct(&word)
+            })
+            .collect()
+    }
+}
+
+/// Words worth checking as char ranges. Tokens that look like code, paths or
+/// URLs are skipped, as are acronyms and camelCase identifiers.
+pub fn words(text: &str) -> Vec<(usize, usize)> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut ranges = Vec::new();
+    let mut idx = 0;
+    while idx < chars.len() {
+        if chars[idx].is_whitespace() {
+            idx += 1;
+            continue;
+        }
+        let token_start = idx;
+        while idx < chars.len() && !chars[idx].is_whitespace() {
+            idx += 1;
+        }
+        let token = &chars[token_start..idx];
+        if token.iter().any(|c| c.is_ascii_digit() || "/\\_@`<>{}=*#$".contains(*c)) {
+            continue;
+        }
+        let mut pos = 0;
+        while pos < token.len() {
+            if !token[pos].is_alphabetic() {
+                pos += 1;
+                continue;
+            }
+            let start = pos;
+            while pos < token.len() && (token[pos].is_alphabetic() || (token[pos] == '\'' && token.get(pos + 1).is_some_and(|c| c.is_alphabetic()))) {
+                pos += 1;
+            }
+            let word = &token[start..pos];
+            let inner_upper = word.iter().skip(1).any(|c| c.is_uppercase());
+            // "e.g" style abbreviations and file.ext names
+            let dotted = token.get(pos) == Some(&'.') && token.get(pos + 1).is_some_and(|c| c.is_alphabetic());
+            if word.len() > 1 && !inner_upper && !dotted && (start == 0 || token[start - 1] != '.') {
+                ranges.push((token_start + start, token_start + pos));
+            }
+        }
+    }
+    ranges
+}
+
+/// Char ranges of `line` that are prose. `in_fence` tracks markdown code blocks across lines.
+pub fn prose_range(line: &str, kind: ProseKind, in_fence: &mut bool) -> Option<(usize, usize)> {
+    let len = line.chars().count();
+    match kind {
+        ProseKind::Markdown => {
+            if line.trim_start().starts_with("```") {
+                *in_fence = !*in_fence;
+                return None;
+            }
+            (!*in_fence).then_some((0, len))
+        }
+        ProseKind::Comments(marker) => {
+            let byte = comment_start(line, marker)?;
+            let start = line[..byte].chars().count() + marker.chars().count();
+            Some((start, len))
+        }
+    }
+}
+
+/// Byte offset of a line comment marker that is not inside a string literal
+fn comment_start(line: &str, marker: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut escaped = false;
+    for (idx, c) in line.char_indices() {
+        match quote {
+            Some(q) => {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            // In C-like languages `'` also starts lifetimes, so only double quotes count there
+            None if c == '"' || (c == '\'' && marker != "//") => quote = Some(c),
+            None if line[idx..].starts_with(marker) => return Some(idx),
+            None => {}
+        }
+    }
+    None
+}
+
+/// Markdown inline code spans are not prose
+pub fn strip_inline_code(text: &str) -> String {
+    let mut inside = false;
+    text.chars()
+        .map(|c| {
+            if c == '`' {
+                inside = !inside;
+            }
+            if inside || c == '`' { ' ' } else { c }
+        })
+        .collect()
+}
+
+fn edits(word: &str) -> Vec<String> {
+    let chars: Vec<char> = word.chars().collect();
+    let alphabet = "abcdefghijklmnopqrstuvwxyz'";
+    let mut out = Vec::new();
+    for i in 0..=chars.len() {
+        let (left, right) = chars.split_at(i);
+        let left: String = left.iter().collect();
+        if !right.is_empty() {
+            out.push(format!("{}{}", left, right[1..].iter().collect::<String>()));
+        }
+        if right.len() > 1 {
+            out.push(format!("{}{}{}{}", left, right[1], right[0], right[2..].iter().collect::<String>()));
+        }
+        for c in alphabet.chars() {
+            let rest: String = right.iter().collect();
+            out.push(format!("{}{}{}", left, c, rest));
+            if !right.is_empty() {
+                out.push(format!("{}{}{}", left, c, right[1..].iter().collect::<String>()));
+            }
+        }
+    }
+    out
+}
+
+fn custom_dictionary_path(workspace: &Path) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    workspace.hash(&mut hasher);
+    Config::data_dir()
+        .join("dictionaries")
+        .join(format!("{:016x}.txt", hasher.finish()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AFF: &str = "SET UTF-8\n\
+        SFX S Y 2\n\
+        SFX S y ies [^aeiou]y\n\
+        SFX S 0 s [^y]\n\
+        PFX U Y 1\n\
+        PFX U 0 un .\n";
+    const DIC: &str = "5\nhappy/U\ncity/S\ncat/SU\nthe\nwrite\n";
+
+    #[test]
+    fn test_affix_expansion() {
+        let checker = SpellChecker::from_hunspell(AFF, DIC);
+        for word in ["cities", "cats", "uncats", "unhappy", "The", "cat's"] {
+            assert!(checker.is_correct(word), "{} should be correct", word);
+        }
+        for word in ["citys", "unthe", "teh"] {
+            assert!(!checker.is_correct(word), "{} should be misspelled", word);
+        }
+        assert_eq!(checker.suggest("teh", 3), vec!["the"]);
+        assert_eq!(checker.suggest("Wrte", 3), vec!["Write"]);
+    }
+
+    #[test]
+    fn test_prose_selection() {
+        let checker = SpellChecker::from_hunspell(AFF, DIC);
+        assert_eq!(checker.misspelled("the ctas and HTTP foo_bar src/cat.rs camelCase"), vec![(4, 8), (9, 12)]);
+
+        let mut fence = false;
+        let kind = ProseKind::Comments("//");
+        assert_eq!(prose_range(r#"let s = "// no"; // yes"#, kind, &mut fence), Some((19, 23)));
+        assert_eq!(prose_range("let x = 1;", kind, &mut fence), None);
+        assert_eq!(prose_range("```rust", ProseKind::Markdown, &mut fence), None);
+        assert!(fence);
+        assert_eq!(strip_inline_code("use `foo` here"), "use       here");
+    }
+}
@@ -0,0 +1,164 @@
+// Workspace statistics: files and lines per language, largest files, recent git activity
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::syntax;
+use super::workspace;
+
+/// Files larger than this are counted but their lines are not
+const MAX_FILE_SIZE: u64 = 4 * 1024 * 1024;
+
+/// How many of the largest files and latest commits to keep
+const TOP_N: usize = 10;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LanguageStats {
+    pub language: String,
+    pub files: usize,
+    pub lines: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WorkspaceStats {
+    /// Sorted by line count, largest first
+    pub languages: Vec<LanguageStats>,
+    pub total_files: usize,
+    pub total_lines: usize,
+    /// Paths relative to the workspace root with their size in bytes
+    pub largest_files: Vec<(PathBuf, u64)>,
+    /// One-line summaries of the latest commits; empty outside a git repository
+    pub recent_commits: Vec<String>,
+}
+
+impl WorkspaceStats {
+    /// Compact Markdown summary to give an AI model an overview of the project
+    pub fn to_context(&self) -> String {
+        let mut out = format!("Workspace: {} files, {} lines\n\nLanguages:\n", self.total_files, self.total_lines);
+        for lang in &self.languages {
+            out.push_str(&format!("- {}: {} files, {} lines\n", lang.language, lang.files, lang.lines));
+        }
+        if !self.largest_files.is_empty() {
+            out.push_str("\nLargest files:\n");
+            for (path, size) in &self.largest_files {
+                out.push_str(&format!("- {} ({})\n", path.display(), format_size(*size)));
+            }
+        }
+        if !self.recent_commits.is_empty() {
+            out.push_str("\nRecent commits:\n");
+            for commit in &self.recent_commits {
+                out.push_str(&format!("- {}\n", commit));
+            }
+        }
+        out
+    }
+}
+
+/// Walk the workspace and run `git log`; slow on big trees, so call it off the UI thread
+pub fn collect(root: &Path) -> WorkspaceStats {
+    let mut stats = WorkspaceStats::default();
+    let mut languages: HashMap<String, LanguageStats> = HashMap::new();
+    let mut sizes = Vec::new();
+
+    for entry in workspace::walk_files(root) {
+        let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+        let path = entry.path().strip_prefix(root).unwrap_or(entry.path()).to_path_buf();
+        let lines = if size <= MAX_FILE_SIZE { count_lines(entry.path()) } else { None };
+        // Binary files still count towards the total and the largest files
+        let language = match lines {
+            Some(_) => language_name(&path),
+            None => "Binary".to_string(),
+        };
+        let lang = languages.entry(language.clone()).or_insert_with(|| LanguageStats { language, ..Default::default() });
+        lang.files += 1;
+        lang.lines += lines.unwrap_or(0);
+        stats.total_files += 1;
+        stats.total_lines += lines.unwrap_or(0);
+        sizes.push((path, size));
+    }
+
+    stats.languages = languages.into_values().collect();
+    stats.languages.sort_by(|a, b| b.lines.cmp(&a.lines).then(b.files.cmp(&a.files)).then(a.language.cmp(&b.language)));
+    sizes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    sizes.truncate(TOP_N);
+    stats.largest_files = sizes;
+    stats.recent_commits = recent_commits(root);
+    stats
+}
+
+/// Line count of a text file; `None` for binary files (a NUL byte)
+fn count_lines(path: &Path) -> Option<usize> {
+    let bytes = fs::read(path).ok()?;
+    if bytes.contains(&0) {
+        return None;
+    }
+    let newlines = bytes.iter().filter(|&&b| b == b'\n').count();
+    Some(newlines + usize::from(!bytes.is_empty() && !bytes.ends_with(b"\n")))
+}
+
+/// Syntax name for a file, or its extension when syntect doesn't know it
+fn language_name(path: &Path) -> String {
+    let key = syntax::language_key(path);
+    let name = &syntax::assets_blocking().find_syntax(key.as_deref()).name;
+    match (name.as_str(), key) {
+        ("Plain Text", Some(key)) if path.extension().is_some() => format!(".{}", key),
+        _ => name.clone(),
+    }
+}
+
+fn recent_commits(root: &Path) -> Vec<String> {
+    let output = Command::new("git")
+        .args(["log", &format!("-{}", TOP_N), "--pretty=format:%h %ar %an: %s"])
+        .current_dir(root)
+        .output();
+    match output {
+        Ok(output) if output.status.success() => {
+            String::from_utf8_lossy(&output.stdout).lines().map(str::to_string).collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Human readable size, e.g. `12.3 KB`
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 { format!("{} B", bytes) } else { format!("{:.1} {}", size, UNITS[unit]) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collect() {
+        let root = std::env::temp_dir().join(format!("nterm-stats-{}", std::process::id()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {\n    println!(\"hi\");\n}\n").unwrap();
+        fs::write(root.join("src/lib.rs"), "pub mod a;").unwrap();
+        fs::write(root.join("notes.md"), "# Notes\n").unwrap();
+        fs::write(root.join("logo.png"), [0x89, b'P', b'N', b'G', 0, 1, 2]).unwrap();
+
+        let stats = collect(&root);
+        assert_eq!((stats.total_files, stats.total_lines), (4, 5));
+        assert_eq!(stats.languages[0], LanguageStats { language: "Rust".to_string(), files: 2, lines: 4 });
+        assert!(stats.languages.iter().any(|l| l.language == "Binary" && l.files == 1));
+        assert_eq!(stats.largest_files[0].0, PathBuf::from("src/main.rs"));
+        assert!(stats.to_context().contains("- Rust: 2 files, 4 lines"));
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(2048), "2.0 KB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0 MB");
+    }
+}
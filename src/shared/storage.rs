@@ -0,0 +1,340 @@
+// Persistence for chat transcripts, per-workspace sessions (scratch buffers)
+// and token usage. Backends implement `Storage`; the default is a SQLite
+// database in the data dir, which keeps long histories out of the config file
+// and can be searched.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rusqlite::{params, Connection};
+
+use super::ai::TokenUsage;
+use super::chat::{ChatMessage, Role};
+use super::config::Config;
+use super::scratch::ScratchBuffer;
+
+/// A chat message as stored, with the workspace it was sent from
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredMessage {
+    pub workspace: PathBuf,
+    pub message: ChatMessage,
+}
+
+/// Tokens used with one model across all stored replies
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelUsage {
+    pub model: String,
+    pub replies: u64,
+    pub input: u64,
+    pub output: u64,
+}
+
+pub trait Storage: Send {
+    /// Record a chat message sent or received in `workspace`
+    fn append_message(&mut self, workspace: &Path, message: &ChatMessage) -> Result<(), String>;
+
+    /// The last `limit` messages of `workspace`, oldest first
+    fn recent_messages(&self, workspace: &Path, limit: usize) -> Result<Vec<ChatMessage>, String>;
+
+    /// The last `limit` distinct prompts sent in `workspace`, oldest first
+    fn recent_prompts(&self, workspace: &Path, limit: usize) -> Result<Vec<String>, String>;
+
+    /// Messages from any workspace containing `query` (case-insensitive), newest first
+    fn search_messages(&self, query: &str, limit: usize) -> Result<Vec<StoredMessage>, String>;
+
+    /// Replace the scratch buffers kept for `workspace`
+    fn save_scratch(&mut self, workspace: &Path, buffers: &[ScratchBuffer]) -> Result<(), String>;
+
+    fn load_scratch(&self, workspace: &Path) -> Result<Vec<ScratchBuffer>, String>;
+
+    /// Token totals per model, most used first
+    fn usage_by_model(&self) -> Result<Vec<ModelUsage>, String>;
+
+    /// Write a copy of everything stored to a new database file at `path`
+    fn backup_to(&self, path: &Path) -> Result<(), String>;
+
+    /// Replace everything stored with the contents of a backup made by `backup_to`
+    fn restore_from(&mut self, path: &Path) -> Result<(), String>;
+}
+
+/// The storage backend used by the app: `nterm.db` in the data dir
+pub fn open_default() -> Result<Box<dyn Storage>, String> {
+    let dir = Config::data_dir();
+    fs::create_dir_all(&dir).map_err(|e| format!("could not create {}: {}", dir.display(), e))?;
+    Ok(Box::new(SqliteStorage::open(&dir.join("nterm.db"))?))
+}
+
+pub struct SqliteStorage {
+    connection: Connection,
+}
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS messages (
+        id INTEGER PRIMARY KEY,
+        workspace TEXT NOT NULL,
+        role TEXT NOT NULL,
+        content TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        model TEXT,
+        input_tokens INTEGER,
+        output_tokens INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS messages_by_workspace ON messages (workspace, id);
+    CREATE TABLE IF NOT EXISTS scratch_buffers (
+        workspace TEXT NOT NULL,
+        position INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        content TEXT NOT NULL,
+        PRIMARY KEY (workspace, position)
+    );
+";
+
+impl SqliteStorage {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let connection = Connection::open(path).map_err(|e| format!("could not open {}: {}", path.display(), e))?;
+        // Appends happen on the UI thread; WAL keeps each commit cheap
+        connection.pragma_update(None, "journal_mode", "WAL").map_err(db_error)?;
+        connection.pragma_update(None, "synchronous", "NORMAL").map_err(db_error)?;
+        Self::with_connection(connection)
+    }
+
+    /// A database that lives only as long as the value, for tests
+    pub fn in_memory() -> Result<Self, String> {
+        Self::with_connection(Connection::open_in_memory().map_err(db_error)?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Self, String> {
+        connection.execute_batch(SCHEMA).map_err(db_error)?;
+        Ok(Self { connection })
+    }
+}
+
+fn db_error(e: rusqlite::Error) -> String {
+    format!("storage error: {}", e)
+}
+
+fn workspace_key(workspace: &Path) -> String {
+    workspace.to_string_lossy().into_owned()
+}
+
+fn role_name(role: Role) -> &'static str {
+    match role {
+        Role::User => "user",
+        Role::Assistant => "assistant",
+        Role::System => "system",
+    }
+}
+
+fn role_from_name(name: &str) -> Role {
+    match name {
+        "user" => Role::User,
+        "assistant" => Role::Assistant,
+        _ => Role::System,
+    }
+}
+
+/// Columns after `workspace` in a messages query, in this order
+const MESSAGE_COLUMNS: &str = "role, content, timestamp, model, input_tokens, output_tokens";
+
+fn message_from_row(row: &rusqlite::Row, first: usize) -> rusqlite::Result<ChatMessage> {
+    let tokens: (Option<u32>, Option<u32>) = (row.get(first + 4)?, row.get(first + 5)?);
+    Ok(ChatMessage {
+        role: role_from_name(&row.get::<_, String>(first)?),
+        content: row.get(first + 1)?,
+        timestamp: row.get::<_, i64>(first + 2)? as u64,
+        model: row.get(first + 3)?,
+        usage: match tokens {
+            (Some(input), Some(output)) => Some(TokenUsage { input, output }),
+            _ => None,
+        },
+    })
+}
+
+impl Storage for SqliteStorage {
+    fn append_message(&mut self, workspace: &Path, message: &ChatMessage) -> Result<(), String> {
+        self.connection.execute(
+            &format!("INSERT INTO messages (workspace, {}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)", MESSAGE_COLUMNS),
+            params![
+                workspace_key(workspace),
+                role_name(message.role),
+                message.content,
+                message.timestamp as i64,
+                message.model,
+                message.usage.map(|u| u.input),
+                message.usage.map(|u| u.output),
+            ],
+        ).map_err(db_error)?;
+        Ok(())
+    }
+
+    fn recent_messages(&self, workspace: &Path, limit: usize) -> Result<Vec<ChatMessage>, String> {
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT {} FROM messages WHERE workspace = ?1 ORDER BY id DESC LIMIT ?2",
+            MESSAGE_COLUMNS
+        )).map_err(db_error)?;
+        let mut messages = statement
+            .query_map(params![workspace_key(workspace), limit as i64], |row| message_from_row(row, 0))
+            .and_then(Iterator::collect::<rusqlite::Result<Vec<_>>>)
+            .map_err(db_error)?;
+        messages.reverse();
+        Ok(messages)
+    }
+
+    fn recent_prompts(&self, workspace: &Path, limit: usize) -> Result<Vec<String>, String> {
+        let mut statement = self.connection.prepare(
+            "SELECT content FROM messages WHERE workspace = ?1 AND role = 'user'
+             GROUP BY content ORDER BY MAX(id) DESC LIMIT ?2",
+        ).map_err(db_error)?;
+        let mut prompts = statement
+            .query_map(params![workspace_key(workspace), limit as i64], |row| row.get(0))
+            .and_then(Iterator::collect::<rusqlite::Result<Vec<String>>>)
+            .map_err(db_error)?;
+        prompts.reverse();
+        Ok(prompts)
+    }
+
+    fn search_messages(&self, query: &str, limit: usize) -> Result<Vec<StoredMessage>, String> {
+        // instr() rather than LIKE, so `%` and `_` in the query match themselves
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT workspace, {} FROM messages WHERE instr(lower(content), lower(?1)) > 0 ORDER BY id DESC LIMIT ?2",
+            MESSAGE_COLUMNS
+        )).map_err(db_error)?;
+        statement
+            .query_map(params![query, limit as i64], |row| {
+                Ok(StoredMessage {
+                    workspace: PathBuf::from(row.get::<_, String>(0)?),
+                    message: message_from_row(row, 1)?,
+                })
+            })
+            .and_then(Iterator::collect)
+            .map_err(db_error)
+    }
+
+    fn save_scratch(&mut self, workspace: &Path, buffers: &[ScratchBuffer]) -> Result<(), String> {
+        let key = workspace_key(workspace);
+        let transaction = self.connection.transaction().map_err(db_error)?;
+        transaction.execute("DELETE FROM scratch_buffers WHERE workspace = ?1", params![key]).map_err(db_error)?;
+        for (position, buffer) in buffers.iter().enumerate() {
+            transaction.execute(
+                "INSERT INTO scratch_buffers (workspace, position, name, content) VALUES (?1, ?2, ?3, ?4)",
+                params![key, position as i64, buffer.name, buffer.lines.join("\n")],
+            ).map_err(db_error)?;
+        }
+        transaction.commit().map_err(db_error)
+    }
+
+    fn load_scratch(&self, workspace: &Path) -> Result<Vec<ScratchBuffer>, String> {
+        let mut statement = self.connection
+            .prepare("SELECT name, content FROM scratch_buffers WHERE workspace = ?1 ORDER BY position")
+            .map_err(db_error)?;
+        statement
+            .query_map(params![workspace_key(workspace)], |row| {
+                let content: String = row.get(1)?;
+                Ok(ScratchBuffer { name: row.get(0)?, lines: content.split('\n').map(str::to_string).collect() })
+            })
+            .and_then(Iterator::collect)
+            .map_err(db_error)
+    }
+
+    fn usage_by_model(&self) -> Result<Vec<ModelUsage>, String> {
+        let mut statement = self.connection.prepare(
+            "SELECT model, COUNT(*), SUM(input_tokens), SUM(output_tokens) FROM messages
+             WHERE model IS NOT NULL AND input_tokens IS NOT NULL
+             GROUP BY model ORDER BY SUM(input_tokens) + SUM(output_tokens) DESC",
+        ).map_err(db_error)?;
+        statement
+            .query_map([], |row| {
+                Ok(ModelUsage {
+                    model: row.get(0)?,
+                    replies: row.get::<_, i64>(1)? as u64,
+                    input: row.get::<_, i64>(2)? as u64,
+                    output: row.get::<_, i64>(3)? as u64,
+                })
+            })
+            .and_then(Iterator::collect)
+            .map_err(db_error)
+    }
+
+    fn backup_to(&self, path: &Path) -> Result<(), String> {
+        self.connection.execute("VACUUM INTO ?1", params![path.to_string_lossy()]).map_err(db_error)?;
+        Ok(())
+    }
+
+    fn restore_from(&mut self, path: &Path) -> Result<(), String> {
+        self.connection.execute("ATTACH DATABASE ?1 AS backup", params![path.to_string_lossy()]).map_err(db_error)?;
+        let restored = self.connection.execute_batch(
+            "BEGIN;
+             DELETE FROM messages;
+             INSERT INTO messages SELECT * FROM backup.messages;
+             DELETE FROM scratch_buffers;
+             INSERT INTO scratch_buffers SELECT * FROM backup.scratch_buffers;
+             COMMIT;",
+        );
+        if restored.is_err() {
+            let _ = self.connection.execute_batch("ROLLBACK");
+        }
+        let detached = self.connection.execute_batch("DETACH DATABASE backup");
+        restored.and(detached).map_err(db_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages() {
+        let mut storage = SqliteStorage::in_memory().unwrap();
+        let (here, there) = (Path::new("/work/a"), Path::new("/work/b"));
+        storage.append_message(here, &ChatMessage::user("How do I parse 100% of it?")).unwrap();
+        let reply = ChatMessage::assistant("Use a parser", "Gemini", Some(TokenUsage { input: 12, output: 40 }));
+        storage.append_message(here, &reply).unwrap();
+        storage.append_message(there, &ChatMessage::user("unrelated")).unwrap();
+
+        let recent = storage.recent_messages(here, 10).unwrap();
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[1], reply);
+        assert_eq!(storage.recent_messages(here, 1).unwrap(), vec![reply]);
+
+        let found = storage.search_messages("100%", 10).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].workspace, here);
+        assert!(storage.search_messages("PARSE", 10).unwrap().len() == 2);
+        storage.append_message(here, &ChatMessage::user("How do I parse 100% of it?")).unwrap();
+        assert_eq!(storage.recent_prompts(here, 10).unwrap(), vec!["How do I parse 100% of it?"]);
+
+        assert_eq!(storage.usage_by_model().unwrap(), vec![ModelUsage { model: "Gemini".to_string(), replies: 1, input: 12, output: 40 }]);
+    }
+
+    #[test]
+    fn test_scratch() {
+        let mut storage = SqliteStorage::in_memory().unwrap();
+        let workspace = Path::new("/work");
+        let buffers = vec![
+            ScratchBuffer { name: "scratch-1".to_string(), lines: vec!["a".to_string(), "".to_string()] },
+            ScratchBuffer { name: "scratch-2".to_string(), lines: vec!["b".to_string()] },
+        ];
+        storage.save_scratch(workspace, &buffers).unwrap();
+        assert_eq!(storage.load_scratch(workspace).unwrap(), buffers);
+        storage.save_scratch(workspace, &buffers[1..]).unwrap();
+        assert_eq!(storage.load_scratch(workspace).unwrap(), &buffers[1..]);
+        assert!(storage.load_scratch(Path::new("/elsewhere")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_backup_and_restore() {
+        let file = std::env::temp_dir().join(format!("nterm-storage-backup-{}.db", std::process::id()));
+        let _ = fs::remove_file(&file);
+        let mut storage = SqliteStorage::in_memory().unwrap();
+        let workspace = Path::new("/work");
+        storage.append_message(workspace, &ChatMessage::user("kept")).unwrap();
+        storage.backup_to(&file).unwrap();
+
+        storage.append_message(workspace, &ChatMessage::user("lost")).unwrap();
+        storage.restore_from(&file).unwrap();
+        assert_eq!(storage.recent_prompts(workspace, 10).unwrap(), vec!["kept"]);
+        assert!(storage.restore_from(Path::new("/not/a/backup.db")).is_err());
+        storage.append_message(workspace, &ChatMessage::user("after")).unwrap();
+        let _ = fs::remove_file(&file);
+    }
+}
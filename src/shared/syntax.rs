@@ -0,0 +1,447 @@
+// Shared syntax highlighting assets
+//
+// Loading the default SyntaxSet/ThemeSet takes a noticeable amount of time, so it
+// happens once, on a background thread. Until the assets are ready callers fall
+// back to plain text. Themes and syntaxes dropped into the user directory
+// (see `Config::user_dir`) are loaded on top of the bundled ones.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Once, OnceLock, RwLock};
+use std::thread;
+
+use regex::Regex;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::{ParseState, Scope, ScopeStack, SyntaxDefinition, SyntaxReference, SyntaxSet};
+
+use super::config::Config;
+use super::theme::ThemeMode;
+
+/// Theme used when nothing else is configured
+pub const DEFAULT_THEME: &str = "base16-ocean.dark";
+
+/// Theme used with the light app theme when nothing else is configured
+pub const DEFAULT_LIGHT_THEME: &str = "base16-ocean.light";
+
+/// Syntax definitions and themes shared by every editor
+pub struct SyntaxAssets {
+    pub syntax_set: SyntaxSet,
+    pub theme_set: ThemeSet,
+    /// User theme and syntax files that failed to load, with the reason
+    pub user_errors: Vec<(PathBuf, String)>,
+}
+
+impl SyntaxAssets {
+    fn load() -> Self {
+        Self::load_with_user_dir(&Config::user_dir())
+    }
+
+    /// Bundled assets plus `dir/themes/*.tmTheme` (named after the file) and
+    /// `dir/syntaxes/*.sublime-syntax`, which win over bundled syntaxes for the
+    /// same extension
+    fn load_with_user_dir(dir: &Path) -> Self {
+        let mut user_errors = Vec::new();
+
+        let mut theme_set = ThemeSet::load_defaults();
+        for path in files_with_extension(&dir.join("themes"), "tmTheme") {
+            let name = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+            match ThemeSet::get_theme(&path) {
+                Ok(theme) => {
+                    theme_set.themes.insert(name, theme);
+                }
+                Err(e) => user_errors.push((path, e.to_string())),
+            }
+        }
+
+        let mut syntax_set = SyntaxSet::load_defaults_newlines();
+        let syntax_files = files_with_extension(&dir.join("syntaxes"), "sublime-syntax");
+        if !syntax_files.is_empty() {
+            let mut builder = syntax_set.into_builder();
+            for path in syntax_files {
+                let name = path.file_stem().and_then(|s| s.to_str());
+                let loaded = fs::read_to_string(&path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|text| SyntaxDefinition::load_from_str(&text, true, name).map_err(|e| e.to_string()));
+                match loaded {
+                    Ok(syntax) => builder.add(syntax),
+                    Err(e) => user_errors.push((path, e)),
+                }
+            }
+            syntax_set = builder.build();
+        }
+
+        Self { syntax_set, theme_set, user_errors }
+    }
+
+    /// Look up a theme by name, falling back to the default theme
+    pub fn theme(&self, name: &str) -> &Theme {
+        self.theme_set
+            .themes
+            .get(name)
+            .or_else(|| self.theme_set.themes.get(DEFAULT_THEME))
+            .or_else(|| self.theme_set.themes.values().next())
+            .expect("syntect ships with default themes")
+    }
+
+    /// Syntax for a language key (see `language_key`), honouring the configured
+    /// overrides and falling back to plain text
+    pub fn find_syntax(&self, key: Option<&str>) -> &SyntaxReference {
+        let Some(key) = key else {
+            return self.syntax_set.find_syntax_plain_text();
+        };
+        let target = OVERRIDES.read().ok().and_then(|o| o.get(key).cloned());
+        target
+            .and_then(|t| self.syntax_set.find_syntax_by_token(&t))
+            .or_else(|| self.syntax_set.find_syntax_by_extension(key))
+            .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text())
+    }
+
+    /// Line comment token for a language key, e.g. `//` for `rs`
+    pub fn line_comment(&self, key: Option<&str>) -> Option<&'static str> {
+        let token = match self.find_syntax(key).name.as_str() {
+            "Rust" | "C" | "C++" | "C#" | "Java" | "JavaScript" | "TypeScript" | "Go" | "Scala"
+            | "Objective-C" | "Objective-C++" | "D" | "Groovy" | "PHP" | "JSON" | "Swift"
+            | "Kotlin" | "Dart" | "Graphviz (DOT)" | "ActionScript" => "//",
+            "Python" | "Ruby" | "Perl" | "Bourne Again Shell (bash)" | "Shell-Unix-Generic"
+            | "Makefile" | "R" | "YAML" | "TOML" | "Tcl" | "CMake" | "Dockerfile" | "Nix" => "#",
+            "SQL" | "Lua" | "Haskell" | "Literate Haskell" | "Ada" => "--",
+            "Erlang" | "TeX" | "LaTeX" | "MATLAB" | "Prolog" => "%",
+            "Lisp" | "Clojure" | "Scheme" | "INI" => ";",
+            "Batch File" => "REM",
+            "VimL" => "\"",
+            // Files without a bundled syntax that still have well-known comments
+            _ => match key? {
+                "toml" | "dockerfile" | "conf" | "env" | "gitignore" | "envrc" => "#",
+                "ts" | "tsx" | "swift" | "kt" | "kts" | "zig" => "//",
+                _ => return None,
+            },
+        };
+        Some(token)
+    }
+
+    /// Char ranges of each line that lie inside a string or comment, parsing
+    /// from the first line so multi-line strings and block comments count
+    pub fn non_code_ranges(&self, key: Option<&str>, lines: &[String]) -> Vec<Vec<Range<usize>>> {
+        let string = Scope::new("string").expect("valid scope");
+        let comment = Scope::new("comment").expect("valid scope");
+        let mut state = ParseState::new(self.find_syntax(key));
+        let mut stack = ScopeStack::new();
+        let mut ranges = Vec::with_capacity(lines.len());
+        for line in lines {
+            let mut line_ranges: Vec<Range<usize>> = Vec::new();
+            // The bundled syntaxes expect each line to end in a newline
+            let text = format!("{}\n", line);
+            let ops = state.parse_line(&text, &self.syntax_set).unwrap_or_default();
+            let mut start = 0;
+            let mut push = |stack: &ScopeStack, start: usize, end: usize| {
+                let inside = stack.as_slice().iter().any(|s| string.is_prefix_of(*s) || comment.is_prefix_of(*s));
+                let end = end.min(line.len());
+                if inside && start < end {
+                    let (from, to) = (line[..start].chars().count(), line[..end].chars().count());
+                    match line_ranges.last_mut() {
+                        Some(last) if last.end == from => last.end = to,
+                        _ => line_ranges.push(from..to),
+                    }
+                }
+            };
+            for (offset, op) in ops {
+                push(&stack, start, offset);
+                start = offset.min(line.len());
+                let _ = stack.apply(&op);
+            }
+            push(&stack, start, line.len());
+            ranges.push(line_ranges);
+        }
+        ranges
+    }
+}
+
+/// Files in `dir` ending in `.ext`, sorted; empty if the directory doesn't exist
+fn files_with_extension(dir: &Path, ext: &str) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else { return Vec::new() };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|e| e == ext))
+        .collect();
+    files.sort();
+    files
+}
+
+/// Extension or dotfile name -> syntax name or extension, from the config
+static OVERRIDES: RwLock<BTreeMap<String, String>> = RwLock::new(BTreeMap::new());
+
+/// Key used for per-language settings: the lowercased extension, or the
+/// file name without its leading dot for files like `.envrc`
+pub fn language_key(path: &Path) -> Option<String> {
+    path.extension()
+        .or_else(|| path.file_name())
+        .and_then(|e| e.to_str())
+        .map(normalize_key)
+}
+
+/// Keys may be written as `vue` or `.vue`
+pub fn normalize_key(key: &str) -> String {
+    key.trim_start_matches('.').to_lowercase()
+}
+
+/// Lines at the top and bottom of a file searched for a modeline
+const MODELINE_LINES: usize = 5;
+
+static VIM_MODELINE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:^|\s)(?:vi|vim|ex):.*?\b(?:ft|filetype|syn|syntax)=([\w+.-]+)").unwrap()
+});
+static EMACS_MODELINE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"-\*-\s*(.*?)\s*-\*-").unwrap());
+
+/// Language key for a file's contents as well as its name, for files the
+/// extension says nothing about: a Vim or Emacs modeline wins, then well-known
+/// file names (`Makefile`, `Dockerfile`), the extension, and for files without
+/// one the `#!` line
+pub fn detect_language<S: AsRef<str>>(path: &Path, lines: &[S]) -> Option<String> {
+    if let Some(key) = modeline_language(lines) {
+        return Some(key);
+    }
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    if let Some(key) = file_name_language(name) {
+        return Some(key.to_string());
+    }
+    if path.extension().is_none() {
+        if let Some(key) = lines.first().and_then(|line| shebang_language(line.as_ref())) {
+            return Some(key);
+        }
+    }
+    language_key(path)
+}
+
+/// `ft=` of a Vim modeline or `mode:` of an Emacs one, near the top or bottom
+fn modeline_language<S: AsRef<str>>(lines: &[S]) -> Option<String> {
+    let tail = lines.len().saturating_sub(MODELINE_LINES).max(MODELINE_LINES.min(lines.len()));
+    lines[..MODELINE_LINES.min(lines.len())].iter().chain(&lines[tail..])
+        .map(|line| line.as_ref())
+        .find_map(|line| {
+            if let Some(captures) = VIM_MODELINE.captures(line) {
+                return Some(language_alias(&captures[1]));
+            }
+            let variables = EMACS_MODELINE.captures(line)?.get(1)?.as_str();
+            let mode = match variables.split(';').find_map(|v| v.trim().strip_prefix("mode:")) {
+                Some(mode) => mode.trim(),
+                // `-*- python -*-` names just the mode
+                None if !variables.contains(':') => variables.trim(),
+                None => return None,
+            };
+            (!mode.is_empty()).then(|| language_alias(mode.trim_end_matches("-mode")))
+        })
+}
+
+/// Language of files recognized by their whole name
+fn file_name_language(name: &str) -> Option<&'static str> {
+    let key = match name.to_lowercase().as_str() {
+        "makefile" | "gnumakefile" => "mk",
+        "dockerfile" | "containerfile" => "dockerfile",
+        "cmakelists.txt" => "cmake",
+        "rakefile" | "gemfile" | "vagrantfile" | "podfile" | "brewfile" => "rb",
+        "jenkinsfile" => "groovy",
+        ".bashrc" | ".bash_profile" | ".bash_aliases" | ".zshrc" | ".zprofile" | ".profile" | "pkgbuild" => "sh",
+        ".vimrc" => "vim",
+        _ => return None,
+    };
+    Some(key)
+}
+
+/// Language of a script from its `#!` line, e.g. `#!/usr/bin/env python3`
+fn shebang_language(line: &str) -> Option<String> {
+    let mut words = line.strip_prefix("#!")?.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        program = words.find(|w| !w.starts_with('-') && !w.contains('='))?;
+    }
+    // `python3.12` and `python3` are python
+    let program = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    (!program.is_empty()).then(|| language_alias(program))
+}
+
+/// Language key for a Vim filetype, Emacs mode or interpreter name
+fn language_alias(name: &str) -> String {
+    let key = match name.to_lowercase().as_str() {
+        "python" | "pypy" => "py",
+        "sh" | "bash" | "zsh" | "dash" | "ksh" | "ash" | "shell-script" => "sh",
+        "javascript" | "node" | "nodejs" | "bun" | "js2" => "js",
+        "typescript" | "deno" | "ts-node" => "ts",
+        "ruby" => "rb",
+        "perl" | "cperl" => "pl",
+        "lua" | "luajit" => "lua",
+        "make" | "makefile" => "mk",
+        "markdown" => "md",
+        "rust" => "rs",
+        "c++" => "cpp",
+        "haskell" | "runghc" | "runhaskell" => "hs",
+        "rscript" => "r",
+        "tclsh" | "wish" => "tcl",
+        "emacs-lisp" => "el",
+        other => return normalize_key(other),
+    };
+    key.to_string()
+}
+
+/// Replace the syntax overrides, e.g. `vue` -> `html`, `envrc` -> `bash`
+pub fn set_overrides(overrides: &BTreeMap<String, String>) {
+    if let Ok(mut current) = OVERRIDES.write() {
+        *current = overrides.iter().map(|(k, v)| (normalize_key(k), v.clone())).collect();
+    }
+}
+
+/// Name of the theme editors highlight with; empty until `set_theme` is called
+static THEME: RwLock<String> = RwLock::new(String::new());
+
+/// The configured syntax theme, or the default one for the app theme
+pub fn theme_name(mode: ThemeMode, configured: Option<&str>) -> &str {
+    match (configured.map(str::trim).filter(|name| !name.is_empty()), mode) {
+        (Some(name), _) => name,
+        (None, ThemeMode::Dark) => DEFAULT_THEME,
+        (None, ThemeMode::Light) => DEFAULT_LIGHT_THEME,
+    }
+}
+
+/// Switch the theme editors highlight with; unknown names fall back to the default
+pub fn set_theme(name: &str) {
+    if let Ok(mut current) = THEME.write() {
+        *current = name.to_string();
+    }
+}
+
+/// Name of the theme set with `set_theme`
+pub fn current_theme() -> String {
+    THEME.read().ok()
+        .map(|name| name.clone())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| DEFAULT_THEME.to_string())
+}
+
+static ASSETS: OnceLock<SyntaxAssets> = OnceLock::new();
+static PRELOAD: Once = Once::new();
+
+/// Start loading the assets in the background (no-op after the first call)
+pub fn preload() {
+    PRELOAD.call_once(|| {
+        thread::spawn(|| {
+            ASSETS.get_or_init(SyntaxAssets::load);
+        });
+    });
+}
+
+/// The shared assets, or `None` while they are still loading
+pub fn assets() -> Option<&'static SyntaxAssets> {
+    preload();
+    ASSETS.get()
+}
+
+/// The shared assets, loading them on the current thread if necessary
+pub fn assets_blocking() -> &'static SyntaxAssets {
+    ASSETS.get_or_init(SyntaxAssets::load)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme_fallback() {
+        let assets = assets_blocking();
+        assert!(assets.theme_set.themes.contains_key(DEFAULT_THEME));
+        // Unknown names resolve to the default theme
+        let unknown = assets.theme("does-not-exist") as *const Theme;
+        let default = assets.theme(DEFAULT_THEME) as *const Theme;
+        assert_eq!(unknown, default);
+    }
+
+    #[test]
+    fn test_user_dir() {
+        let dir = std::env::temp_dir().join(format!("nterm-user-assets-{}", std::process::id()));
+        fs::create_dir_all(dir.join("themes")).unwrap();
+        fs::create_dir_all(dir.join("syntaxes")).unwrap();
+        fs::write(
+            dir.join("themes/Tiny.tmTheme"),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<plist version="1.0"><dict><key>name</key><string>Tiny</string><key>settings</key><array>
+<dict><key>settings</key><dict><key>foreground</key><string>#112233</string></dict></dict>
+</array></dict></plist>"#,
+        ).unwrap();
+        fs::write(
+            dir.join("syntaxes/Frob.sublime-syntax"),
+            "%YAML 1.2\n---\nname: Frob\nfile_extensions: [frob]\nscope: source.frob\ncontexts:\n  main:\n    - match: '\\bfrob\\b'\n      scope: keyword.frob\n",
+        ).unwrap();
+        fs::write(dir.join("syntaxes/Broken.sublime-syntax"), "name: [").unwrap();
+
+        let assets = SyntaxAssets::load_with_user_dir(&dir);
+        assert!(assets.theme_set.themes.contains_key("Tiny"));
+        assert!(assets.theme_set.themes.contains_key(DEFAULT_THEME));
+        assert_eq!(assets.find_syntax(Some("frob")).name, "Frob");
+        assert_eq!(assets.find_syntax(Some("rs")).name, "Rust");
+        assert_eq!(assets.user_errors.len(), 1);
+        assert!(assets.user_errors[0].0.ends_with("Broken.sublime-syntax"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_theme_name() {
+        assert!(assets_blocking().theme_set.themes.contains_key(DEFAULT_LIGHT_THEME));
+        assert_eq!(theme_name(ThemeMode::Dark, None), DEFAULT_THEME);
+        assert_eq!(theme_name(ThemeMode::Light, None), DEFAULT_LIGHT_THEME);
+        assert_eq!(theme_name(ThemeMode::Light, Some(" ")), DEFAULT_LIGHT_THEME);
+        assert_eq!(theme_name(ThemeMode::Light, Some("Solarized (dark)")), "Solarized (dark)");
+    }
+
+    #[test]
+    fn test_overrides() {
+        assert_eq!(language_key(Path::new("src/App.VUE")).as_deref(), Some("vue"));
+        assert_eq!(language_key(Path::new("/repo/.envrc")).as_deref(), Some("envrc"));
+
+        let assets = assets_blocking();
+        assert_eq!(assets.find_syntax(Some("vue")).name, "Plain Text");
+        let mut overrides = BTreeMap::new();
+        overrides.insert(".vue".to_string(), "html".to_string());
+        overrides.insert("envrc".to_string(), "bash".to_string());
+        set_overrides(&overrides);
+        assert_eq!(assets.find_syntax(Some("vue")).name, "HTML");
+        assert_eq!(assets.find_syntax(Some("envrc")).name, "Bourne Again Shell (bash)");
+        assert_eq!(assets.find_syntax(Some("rs")).name, "Rust");
+        set_overrides(&BTreeMap::new());
+    }
+
+    #[test]
+    fn test_detect_language() {
+        let detect = |path: &str, lines: &[&str]| detect_language(Path::new(path), lines);
+        assert_eq!(detect("bin/deploy", &["#!/usr/bin/env -S python3.12 -u", "print()"]).as_deref(), Some("py"));
+        assert_eq!(detect("run", &["#!/bin/bash"]).as_deref(), Some("sh"));
+        assert_eq!(detect("src/GNUmakefile", &["all:"]).as_deref(), Some("mk"));
+        assert_eq!(detect("notes.txt", &["x", "# vim: set ts=4 ft=ruby :"]).as_deref(), Some("rb"));
+        assert_eq!(detect("conf", &["# -*- mode: emacs-lisp; coding: utf-8 -*-"]).as_deref(), Some("el"));
+        assert_eq!(detect("a.js", &["#!/usr/bin/env python"]).as_deref(), Some("js"));
+        assert_eq!(detect(".envrc", &["export A=1"]).as_deref(), Some("envrc"));
+
+        let assets = assets_blocking();
+        assert_eq!(assets.find_syntax(Some("mk")).name, "Makefile");
+        assert_eq!(assets.find_syntax(Some("sh")).name, "Bourne Again Shell (bash)");
+    }
+
+    #[test]
+    fn test_line_comment() {
+        let assets = assets_blocking();
+        assert_eq!(assets.line_comment(Some("rs")), Some("//"));
+        assert_eq!(assets.line_comment(Some("py")), Some("#"));
+        assert_eq!(assets.line_comment(Some("lua")), Some("--"));
+        assert_eq!(assets.line_comment(Some("toml")), Some("#"));
+        assert_eq!(assets.line_comment(Some("txt")), None);
+        assert_eq!(assets.line_comment(None), None);
+    }
+
+    #[test]
+    fn test_non_code_ranges() {
+        let lines: Vec<String> = ["let s = \"(\"; // )", "/* {", "} */ f(x)"].iter().map(|l| l.to_string()).collect();
+        let ranges = assets_blocking().non_code_ranges(Some("rs"), &lines);
+        assert_eq!(ranges[0], vec![8..11, 13..17]);
+        assert_eq!(ranges[1], vec![0..4]);
+        assert_eq!(ranges[2], vec![0..4]);
+    }
+}
@@ -0,0 +1,254 @@
+// Tree-sitter highlighting: a parse tree of the whole buffer, edited and
+// re-parsed incrementally as the text changes, with the grammar's highlight
+// query mapped onto the syntect theme's colors. Built with the `tree-sitter`
+// feature; languages without a grammar here stay on syntect.
+
+use std::ops::Range;
+
+use syntect::highlighting::{Highlighter, Theme};
+use syntect::parsing::Scope;
+use tree_sitter::{InputEdit, Language, Parser, Point, Query, QueryCursor, StreamingIterator, Tree};
+
+use super::highlight::{self, HighlightSpan};
+
+/// Grammar and highlight query for a language key (see `syntax::language_key`)
+fn grammar(language: &str) -> Option<(Language, String)> {
+    Some(match language {
+        "rs" => (tree_sitter_rust::LANGUAGE.into(), tree_sitter_rust::HIGHLIGHTS_QUERY.to_string()),
+        "py" | "pyi" => (tree_sitter_python::LANGUAGE.into(), tree_sitter_python::HIGHLIGHTS_QUERY.to_string()),
+        "js" | "jsx" | "mjs" | "cjs" => (
+            tree_sitter_javascript::LANGUAGE.into(),
+            format!("{}\n{}", tree_sitter_javascript::HIGHLIGHT_QUERY, tree_sitter_javascript::JSX_HIGHLIGHT_QUERY),
+        ),
+        "go" => (tree_sitter_go::LANGUAGE.into(), tree_sitter_go::HIGHLIGHTS_QUERY.to_string()),
+        "json" => (tree_sitter_json::LANGUAGE.into(), tree_sitter_json::HIGHLIGHTS_QUERY.to_string()),
+        _ => return None,
+    })
+}
+
+/// The TextMate scope a capture name is colored as, e.g. `function.method`
+/// falls back to `function`, which is `entity.name.function`
+fn scope_for(capture: &str) -> Option<&'static str> {
+    let mut name = capture;
+    loop {
+        let scope = match name {
+            "keyword" => Some("keyword"),
+            "function" | "function.method" | "function.macro" => Some("entity.name.function"),
+            "function.builtin" => Some("support.function"),
+            "type" | "constructor" => Some("entity.name.type"),
+            "type.builtin" => Some("storage.type"),
+            "string" => Some("string"),
+            "string.special" | "escape" => Some("constant.character.escape"),
+            "string.special.key" => Some("string"),
+            "comment" => Some("comment"),
+            "number" => Some("constant.numeric"),
+            "constant" => Some("constant.other"),
+            "constant.builtin" | "boolean" => Some("constant.language"),
+            "variable.builtin" => Some("variable.language"),
+            "variable.parameter" => Some("variable.parameter"),
+            "property" => Some("variable.other.member"),
+            "attribute" => Some("entity.other.attribute-name"),
+            "operator" => Some("keyword.operator"),
+            "punctuation" => Some("punctuation"),
+            "label" => Some("entity.name.label"),
+            "module" => Some("entity.name.namespace"),
+            "tag" => Some("entity.name.tag"),
+            _ => None,
+        };
+        match (scope, name.rsplit_once('.')) {
+            (Some(scope), _) => return Some(scope),
+            (None, Some((parent, _))) => name = parent,
+            (None, None) => return None,
+        }
+    }
+}
+
+/// A buffer's parse tree and the text it was parsed from
+pub struct Document {
+    language: String,
+    parser: Parser,
+    query: Query,
+    tree: Option<Tree>,
+    lines: Vec<String>,
+    /// Byte offset of each line in `text`
+    starts: Vec<usize>,
+    text: String,
+}
+
+impl Document {
+    /// A document for `language`, parsed on the first `update`; `None`
+    /// without a grammar for it
+    pub fn new(language: &str) -> Option<Self> {
+        let (grammar, source) = grammar(language)?;
+        let mut parser = Parser::new();
+        parser.set_language(&grammar).ok()?;
+        let query = Query::new(&grammar, &source).ok()?;
+        Some(Self {
+            language: language.to_string(),
+            parser,
+            query,
+            tree: None,
+            lines: Vec::new(),
+            starts: Vec::new(),
+            text: String::new(),
+        })
+    }
+
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    /// Byte offset and position of the start of `row`, or of the end of the
+    /// text for rows past the last one
+    fn position(lines: &[String], starts: &[usize], text_len: usize, row: usize) -> (usize, Point) {
+        match starts.get(row) {
+            Some(&start) if row < lines.len() => (start, Point { row, column: 0 }),
+            _ => (text_len, Point { row: lines.len().saturating_sub(1), column: lines.last().map_or(0, String::len) }),
+        }
+    }
+
+    /// Bring the tree up to date with `lines`, re-parsing only around what
+    /// changed since the last call. Returns the rows whose highlighting may
+    /// have changed: the edited ones and any whose syntax the edit affected,
+    /// like the rest of a file after an opened block comment.
+    pub fn update<S: AsRef<str>>(&mut self, lines: &[S]) -> Range<usize> {
+        let old_count = self.lines.len();
+        let prefix = self.lines.iter().zip(lines).take_while(|(a, b)| a.as_str() == b.as_ref()).count();
+        if self.tree.is_some() && prefix == old_count && prefix == lines.len() {
+            return 0..0;
+        }
+        let suffix = self.lines[prefix..].iter().rev()
+            .zip(lines[prefix..].iter().rev())
+            .take_while(|(a, b)| a.as_str() == b.as_ref())
+            .count();
+
+        let old_start = Self::position(&self.lines, &self.starts, self.text.len(), prefix);
+        let old_end = Self::position(&self.lines, &self.starts, self.text.len(), old_count - suffix);
+
+        self.lines = lines.iter().map(|l| l.as_ref().to_string()).collect();
+        self.text = self.lines.join("\n");
+        self.starts = self.lines.iter()
+            .scan(0, |offset, line| {
+                let start = *offset;
+                *offset += line.len() + 1;
+                Some(start)
+            })
+            .collect();
+        let new_start = Self::position(&self.lines, &self.starts, self.text.len(), prefix);
+        let new_end = Self::position(&self.lines, &self.starts, self.text.len(), self.lines.len() - suffix);
+        // A line appended or removed at the end starts at the end of the shorter text
+        let start = if old_start.0 <= new_start.0 { old_start } else { new_start };
+
+        let Some(mut tree) = self.tree.take() else {
+            self.tree = self.parser.parse(&self.text, None);
+            return 0..self.lines.len();
+        };
+        tree.edit(&InputEdit {
+            start_byte: start.0,
+            old_end_byte: old_end.0,
+            new_end_byte: new_end.0,
+            start_position: start.1,
+            old_end_position: old_end.1,
+            new_end_position: new_end.1,
+        });
+        self.tree = self.parser.parse(&self.text, Some(&tree));
+        let mut rows = prefix..(self.lines.len() - suffix).max(prefix + 1);
+        if let Some(new_tree) = &self.tree {
+            for range in tree.changed_ranges(new_tree) {
+                rows.start = rows.start.min(range.start_point.row);
+                rows.end = rows.end.max(range.end_point.row + 1);
+            }
+        } else {
+            rows = 0..self.lines.len();
+        }
+        rows.end = rows.end.min(self.lines.len());
+        rows
+    }
+
+    /// Spans of each of `rows`, colored with `theme`; rows past the end are
+    /// left out
+    pub fn highlight(&self, rows: Range<usize>, theme: &Theme) -> Vec<Vec<HighlightSpan>> {
+        let rows = rows.start.min(self.lines.len())..rows.end.min(self.lines.len());
+        let Some(tree) = &self.tree else { return Vec::new() };
+        if rows.is_empty() {
+            return Vec::new();
+        }
+
+        let highlighter = Highlighter::new(theme);
+        let default = highlight::rgb(highlighter.get_default().foreground);
+        let colors: Vec<Option<[u8; 3]>> = self.query.capture_names().iter()
+            .map(|name| match scope_for(name).and_then(|s| Scope::new(s).ok()) {
+                Some(scope) => highlight::rgb(highlighter.style_for_stack(&[scope]).foreground),
+                None => default,
+            })
+            .collect();
+
+        let bytes = self.starts[rows.start]..self.starts[rows.end - 1] + self.lines[rows.end - 1].len();
+        let mut captured: Vec<(Range<usize>, usize, usize)> = Vec::new();
+        let mut cursor = QueryCursor::new();
+        cursor.set_byte_range(bytes.clone());
+        let mut captures = cursor.captures(&self.query, tree.root_node(), self.text.as_bytes());
+        while let Some((found, index)) = captures.next() {
+            let capture = found.captures()[*index];
+            captured.push((capture.node.byte_range(), found.pattern_index, capture.index as usize));
+        }
+        // Outer nodes first so nested ones paint over them; for the same node
+        // the earliest pattern in the query wins, as in tree-sitter's highlighter
+        captured.sort_by_key(|(range, pattern, _)| (std::cmp::Reverse(range.len()), std::cmp::Reverse(*pattern)));
+
+        let mut painted: Vec<Vec<Option<[u8; 3]>>> = rows.clone()
+            .map(|row| vec![default; self.lines[row].chars().count()])
+            .collect();
+        for (range, _, capture) in captured {
+            let range = range.start.max(bytes.start)..range.end.min(bytes.end);
+            for row in rows.clone() {
+                let (start, line) = (self.starts[row], &self.lines[row]);
+                if range.end <= start || range.start > start + line.len() {
+                    continue;
+                }
+                let col = |byte: usize| line.get(..byte.clamp(start, start + line.len()) - start).map_or(0, |s| s.chars().count());
+                let cols = &mut painted[row - rows.start];
+                for fg in &mut cols[col(range.start)..col(range.end)] {
+                    *fg = colors[capture];
+                }
+            }
+        }
+        painted.iter().map(|colors| highlight::spans_from_colors(colors)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syntect::highlighting::ThemeSet;
+
+    #[test]
+    fn test_incremental_highlight() {
+        let theme = &ThemeSet::load_defaults().themes["base16-ocean.dark"];
+        let mut doc = Document::new("rs").unwrap();
+        assert!(Document::new("txt").is_none());
+
+        let mut lines = vec!["fn main() {".to_string(), "    let x = 1;".to_string(), "}".to_string()];
+        assert_eq!(doc.update(&lines), 0..3);
+        assert_eq!(doc.update(&lines), 0..0);
+        let spans = doc.highlight(0..3, theme);
+        assert_eq!(spans.len(), 3);
+        // `fn` and `main` differ in color, and the spans cover the line
+        assert!(spans[0][0].start == 0 && spans[0][0].end == 2);
+        assert_ne!(spans[0][0].fg, spans[0][2].fg);
+        assert_eq!(spans[1].last().map(|s| s.end), Some(14));
+
+        // Opening a block comment re-highlights everything after it
+        lines.insert(1, "/*".to_string());
+        let rows = doc.update(&lines);
+        assert_eq!(rows, 1..4);
+        let comment = doc.highlight(1..4, theme);
+        assert_eq!(comment[1].len(), 1);
+        assert_eq!(comment[1][0].fg, comment[0][0].fg);
+
+        lines.remove(1);
+        lines.pop();
+        assert_eq!(doc.update(&lines).end, 2);
+        assert_eq!(doc.highlight(0..5, theme).len(), 2);
+    }
+}
@@ -3,4 +3,107 @@
 
 mod term;
 
+use std::collections::hash_map::RandomState;
+use std::fs;
+use std::hash::BuildHasher;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
 pub use term::{Terminal, TerminalCell, TerminalColor, TerminalEvent, TerminalSize};
+
+/// Current directory of a running process, e.g. the shell behind a PTY
+pub fn process_cwd(pid: u32) -> Option<PathBuf> {
+    #[cfg(target_os = "linux")]
+    {
+        std::fs::read_link(format!("/proc/{}/cwd", pid)).ok()
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        // lsof prints the cwd as a line starting with `n`
+        let output = std::process::Command::new("lsof")
+            .args(["-a", "-p", &pid.to_string(), "-d", "cwd", "-Fn"])
+            .output()
+            .ok()?;
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .find_map(|line| line.strip_prefix('n'))
+            .map(PathBuf::from)
+    }
+}
+
+/// Shell input that changes to `dir`: clears the current line first and is
+/// prefixed with a space to stay out of the shell history
+pub fn cd_command(dir: &Path) -> String {
+    format!("\x15 cd -- {}\r", quote(dir))
+}
+
+/// Write `bytes` to a file named `name` in a new directory under the temp dir
+/// that only the user can read, for `sudo_copy_command`. The directory has a
+/// random name and both it and the file are created exclusively, so another
+/// user can neither read the contents nor plant a file or symlink in their place.
+pub fn stage_private(name: &str, bytes: &[u8]) -> io::Result<PathBuf> {
+    let mut attempt = 0u32;
+    let dir = loop {
+        let random = RandomState::new().hash_one((std::process::id(), attempt));
+        let dir = std::env::temp_dir().join(format!("nterm-save-{:016x}", random));
+        let mut builder = fs::DirBuilder::new();
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        match builder.create(&dir) {
+            Ok(()) => break dir,
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempt < 8 => attempt += 1,
+            Err(e) => return Err(e),
+        }
+    };
+    let path = dir.join(if name.is_empty() { "buffer" } else { name });
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(&path)?.write_all(bytes)?;
+    Ok(path)
+}
+
+/// Shell input that copies `from` (staged by `stage_private`) over `to` with
+/// sudo, then removes the staging directory; left unsubmitted so the user
+/// confirms it with Enter
+pub fn sudo_copy_command(from: &Path, to: &Path) -> String {
+    let dir = from.parent().unwrap_or(from);
+    format!("\x15 sudo cp -- {} {} && rm -r -- {}", quote(from), quote(to), quote(dir))
+}
+
+/// `path` single-quoted for the shell
+pub fn quote(path: &Path) -> String {
+    format!("'{}'", path.to_string_lossy().replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cd_command() {
+        assert_eq!(cd_command(Path::new("/tmp/it's here")), "\x15 cd -- '/tmp/it'\\''s here'\r");
+        assert_eq!(sudo_copy_command(Path::new("/tmp/s/a"), Path::new("/etc/hosts")), "\x15 sudo cp -- '/tmp/s/a' '/etc/hosts' && rm -r -- '/tmp/s'");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stage_private() {
+        use std::os::unix::fs::PermissionsExt;
+        let (a, b) = (stage_private("hosts", b"127.0.0.1").unwrap(), stage_private("hosts", b"").unwrap());
+        assert_ne!(a.parent(), b.parent());
+        assert_eq!(fs::read(&a).unwrap(), b"127.0.0.1");
+        assert_eq!(fs::metadata(&a).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(fs::metadata(a.parent().unwrap()).unwrap().permissions().mode() & 0o777, 0o700);
+        for path in [a, b] {
+            fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_process_cwd() {
+        assert_eq!(process_cwd(std::process::id()), std::env::current_dir().ok());
+    }
+}
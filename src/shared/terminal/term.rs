@@ -7,10 +7,12 @@ use std::sync::Arc;
 use std::thread;
 
 use parking_lot::RwLock;
-use portable_pty::{CommandBuilder, NativePtySystem, PtySize, PtySystem};
+use portable_pty::{CommandBuilder, MasterPty, NativePtySystem, PtySize, PtySystem};
+
+use crate::shared::toolchain::Toolchain;
 
 /// Terminal size in cells
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct TerminalSize {
     pub rows: u16,
     pub cols: u16,
@@ -152,6 +154,7 @@ pub enum TerminalEvent {
 pub struct Terminal {
     parser: Arc<RwLock<vt100::Parser>>,
     writer: Arc<parking_lot::Mutex<Box<dyn Write + Send>>>,
+    master: Box<dyn MasterPty + Send>,
     event_rx: Receiver<TerminalEvent>,
     size: TerminalSize,
     _reader_thread: thread::JoinHandle<()>,
@@ -197,6 +200,9 @@ impl Terminal {
 
         // Set working directory
         if let Ok(cwd) = std::env::current_dir() {
+            for (key, value) in Toolchain::detect(&cwd).env() {
+                cmd.env(key, value);
+            }
             cmd.cwd(cwd);
         }
 
@@ -256,6 +262,7 @@ impl Terminal {
         Ok(Self {
             parser,
             writer,
+            master: pair.master,
             event_rx,
             size,
             _reader_thread: reader_thread,
@@ -293,9 +300,13 @@ impl Terminal {
         self.input(&[0x1A])
     }
 
-    /// Resize terminal
+    /// Resize terminal, the PTY included so the child gets SIGWINCH
     pub fn resize(&mut self, size: TerminalSize) {
+        if size == self.size {
+            return;
+        }
         self.size = size;
+        let _ = self.master.resize(PtySize { rows: size.rows, cols: size.cols, pixel_width: 0, pixel_height: 0 });
         self.parser.write().set_size(size.rows, size.cols);
     }
 
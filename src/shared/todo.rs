@@ -0,0 +1,82 @@
+// Project-wide TODO/FIXME/HACK comment scanner
+
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use super::workspace;
+
+/// Files larger than this are skipped (generated or minified code)
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+static TODO_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b(TODO|FIXME|HACK)\b(?:\([^)]*\))?:?\s*(.*)").unwrap()
+});
+
+/// A single to-do comment (line is 1-based)
+#[derive(Debug, Clone, PartialEq)]
+pub struct TodoItem {
+    pub path: PathBuf,
+    pub line: usize,
+    pub kind: String,
+    pub text: String,
+}
+
+/// Find to-do markers in the given file content
+pub fn scan_text(path: &Path, content: &str) -> Vec<TodoItem> {
+    content
+        .lines()
+        .enumerate()
+        .filter_map(|(idx, line)| {
+            TODO_PATTERN.captures(line).map(|caps| TodoItem {
+                path: path.to_path_buf(),
+                line: idx + 1,
+                kind: caps[1].to_string(),
+                text: caps[2].trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Scan every text file in the workspace; paths are relative to `root`
+pub fn scan_workspace(root: &Path) -> Vec<TodoItem> {
+    let mut items = Vec::new();
+    for entry in workspace::walk_files(root) {
+        if entry.metadata().map(|m| m.len() > MAX_FILE_SIZE).unwrap_or(true) {
+            continue;
+        }
+        // Non UTF-8 files are skipped
+        let Ok(content) = fs::read_to_string(entry.path()) else {
+            continue;
+        };
+        let path = entry.path().strip_prefix(root).unwrap_or(entry.path());
+        items.extend(scan_text(path, &content));
+    }
+    items.sort_by(|a, b| a.path.cmp(&b.path).then(a.line.cmp(&b.line)));
+    items
+}
+
+/// Group items (sorted by path) into per-file runs
+pub fn group_by_file(items: &[TodoItem]) -> Vec<(&Path, &[TodoItem])> {
+    items
+        .chunk_by(|a, b| a.path == b.path)
+        .map(|chunk| (chunk[0].path.as_path(), chunk))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_text() {
+        let content = "fn main() {\n    // TODO: handle errors\n    // FIXME(ash) broken on windows\n    let todos = 1; // not a marker\n}\n";
+        let items = scan_text(Path::new("src/main.rs"), content);
+        assert_eq!(items.len(), 2);
+        assert_eq!((items[0].line, items[0].kind.as_str()), (2, "TODO"));
+        assert_eq!(items[0].text, "handle errors");
+        assert_eq!(items[1].kind, "FIXME");
+        assert_eq!(items[1].text, "broken on windows");
+    }
+}
@@ -0,0 +1,126 @@
+// Per-workspace toolchains: a Python virtualenv, the Node version pinned by
+// `.nvmrc` and the Rust toolchain pinned by `rust-toolchain`. Shells, watch runs
+// and linters started by nterm get the environment that activates them.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory names checked for a virtualenv, in order
+const VENV_DIRS: &[&str] = &[".venv", "venv", "env"];
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Toolchain {
+    /// Virtualenv directory (the one holding `pyvenv.cfg`)
+    pub venv: Option<PathBuf>,
+    /// Node version from `.nvmrc` or `.node-version`, e.g. `18` or `v20.11.0`
+    pub node: Option<String>,
+    /// Rust toolchain from `rust-toolchain(.toml)`, e.g. `1.75.0` or `nightly`
+    pub rust: Option<String>,
+}
+
+impl Toolchain {
+    /// Look for toolchain files at the top of `root`
+    pub fn detect(root: &Path) -> Self {
+        let venv = VENV_DIRS.iter().map(|dir| root.join(dir)).find(|dir| dir.join("pyvenv.cfg").is_file());
+        let node = [".nvmrc", ".node-version"].iter()
+            .find_map(|name| fs::read_to_string(root.join(name)).ok())
+            .and_then(|content| content.lines().next().map(|line| line.trim().to_string()))
+            .filter(|version| !version.is_empty());
+        let rust = ["rust-toolchain.toml", "rust-toolchain"].iter()
+            .find_map(|name| fs::read_to_string(root.join(name)).ok())
+            .and_then(|content| parse_rust_toolchain(&content));
+        Self { venv, node, rust }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.venv.is_none() && self.node.is_none() && self.rust.is_none()
+    }
+
+    /// Short summary for the status bar, e.g. `py .venv · node 18 · rust nightly`
+    pub fn label(&self) -> Option<String> {
+        let mut parts = Vec::new();
+        if let Some(venv) = &self.venv {
+            parts.push(format!("py {}", venv.file_name().map(|n| n.to_string_lossy()).unwrap_or_default()));
+        }
+        if let Some(node) = &self.node {
+            parts.push(format!("node {}", node.trim_start_matches('v')));
+        }
+        if let Some(rust) = &self.rust {
+            parts.push(format!("rust {}", rust));
+        }
+        (!parts.is_empty()).then(|| parts.join(" · "))
+    }
+
+    /// Environment variables that activate the toolchains: the virtualenv's and
+    /// the installed Node's `bin` ahead on `PATH`, `VIRTUAL_ENV` and `RUSTUP_TOOLCHAIN`
+    pub fn env(&self) -> Vec<(String, String)> {
+        let mut vars = Vec::new();
+        let mut path = Vec::new();
+        if let Some(venv) = &self.venv {
+            path.push(venv.join(if cfg!(windows) { "Scripts" } else { "bin" }));
+            vars.push(("VIRTUAL_ENV".to_string(), venv.display().to_string()));
+        }
+        if let Some(bin) = self.node.as_deref().and_then(nvm_node_bin) {
+            path.push(bin);
+        }
+        if let Some(rust) = &self.rust {
+            vars.push(("RUSTUP_TOOLCHAIN".to_string(), rust.clone()));
+        }
+        if !path.is_empty() {
+            path.extend(env::var_os("PATH").map(|p| env::split_paths(&p).collect::<Vec<_>>()).unwrap_or_default());
+            if let Ok(joined) = env::join_paths(path) {
+                vars.push(("PATH".to_string(), joined.to_string_lossy().into_owned()));
+            }
+        }
+        vars
+    }
+}
+
+/// The channel of a `rust-toolchain` file: the TOML form's `channel = "..."`,
+/// or the legacy form's single line
+fn parse_rust_toolchain(content: &str) -> Option<String> {
+    let channel = content.lines()
+        .map(str::trim)
+        .find_map(|line| line.strip_prefix("channel").and_then(|rest| rest.trim_start().strip_prefix('=')))
+        .map(|value| value.trim().trim_matches('"').to_string());
+    let legacy = || content.lines().map(str::trim).find(|line| !line.is_empty() && !line.starts_with('[') && !line.contains('='));
+    channel.or_else(|| legacy().map(str::to_string)).filter(|channel| !channel.is_empty())
+}
+
+/// `bin` of the newest installed nvm Node matching `version` (`18` matches `v18.19.0`)
+fn nvm_node_bin(version: &str) -> Option<PathBuf> {
+    let nvm = env::var_os("NVM_DIR").map(PathBuf::from).or_else(|| dirs::home_dir().map(|home| home.join(".nvm")))?;
+    let wanted = format!("v{}", version.trim_start_matches('v'));
+    let mut installed: Vec<String> = fs::read_dir(nvm.join("versions/node")).ok()?
+        .filter_map(|entry| entry.ok().map(|e| e.file_name().to_string_lossy().into_owned()))
+        .filter(|name| *name == wanted || name.starts_with(&format!("{}.", wanted)))
+        .collect();
+    installed.sort_by_key(|name| name.trim_start_matches('v').split('.').map(|n| n.parse::<u32>().unwrap_or(0)).collect::<Vec<_>>());
+    installed.pop().map(|name| nvm.join("versions/node").join(name).join("bin"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        let root = env::temp_dir().join(format!("nterm-toolchain-{}", std::process::id()));
+        fs::create_dir_all(root.join(".venv/bin")).unwrap();
+        fs::write(root.join(".venv/pyvenv.cfg"), "home = /usr/bin\n").unwrap();
+        fs::write(root.join(".nvmrc"), "v18\n").unwrap();
+        fs::write(root.join("rust-toolchain.toml"), "[toolchain]\nchannel = \"1.75.0\"\n").unwrap();
+
+        let toolchain = Toolchain::detect(&root);
+        assert_eq!(toolchain.label().as_deref(), Some("py .venv · node 18 · rust 1.75.0"));
+        let env = toolchain.env();
+        assert!(env.contains(&("RUSTUP_TOOLCHAIN".to_string(), "1.75.0".to_string())));
+        let path = env.iter().find(|(k, _)| k == "PATH").map(|(_, v)| v.clone()).unwrap();
+        assert!(path.starts_with(&root.join(".venv/bin").display().to_string()));
+
+        assert_eq!(parse_rust_toolchain("nightly-2024-01-01\n").as_deref(), Some("nightly-2024-01-01"));
+        assert!(Toolchain::detect(&root.join(".venv")).is_empty());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,106 @@
+// Watch mode: re-run a configured command (e.g. `cargo check`) after saves.
+// Each run gets its own PTY so the command sees a terminal and keeps its colors.
+
+use std::io::Read;
+use std::path::Path;
+use std::thread;
+
+use portable_pty::{ChildKiller, CommandBuilder, NativePtySystem, PtySize, PtySystem};
+
+use super::toolchain::Toolchain;
+
+/// Outcome of the latest watch run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchStatus {
+    Idle,
+    Running,
+    Passed,
+    Failed,
+}
+
+impl WatchStatus {
+    /// Short marker for the status bar
+    pub fn label(&self) -> &'static str {
+        match self {
+            WatchStatus::Idle => "idle",
+            WatchStatus::Running => "running...",
+            WatchStatus::Passed => "✓ passed",
+            WatchStatus::Failed => "✗ failed",
+        }
+    }
+}
+
+/// Run `command` through the shell in `cwd` on a background thread.
+/// Output is passed to `on_output` as it arrives and `on_exit` gets whether the
+/// command succeeded. The returned killer stops a run that is still going; the
+/// process id is the shell running the command.
+pub fn spawn<O, E>(
+    command: &str,
+    cwd: &Path,
+    size: (u16, u16),
+    mut on_output: O,
+    on_exit: E,
+) -> Result<(Box<dyn ChildKiller + Send + Sync>, Option<u32>), String>
+where
+    O: FnMut(&[u8]) + Send + 'static,
+    E: FnOnce(bool) + Send + 'static,
+{
+    let pair = NativePtySystem::default()
+        .openpty(PtySize { rows: size.0, cols: size.1, pixel_width: 0, pixel_height: 0 })
+        .map_err(|e| e.to_string())?;
+    let mut cmd = CommandBuilder::new("sh");
+    cmd.args(["-c", command]);
+    cmd.cwd(cwd);
+    for (key, value) in Toolchain::detect(cwd).env() {
+        cmd.env(key, value);
+    }
+    let mut child = pair.slave.spawn_command(cmd).map_err(|e| e.to_string())?;
+    // Without our copy of the slave the reader sees EOF once the command exits
+    drop(pair.slave);
+    let mut reader = pair.master.try_clone_reader().map_err(|e| e.to_string())?;
+    let killer = child.clone_killer();
+    let pid = child.process_id();
+
+    thread::spawn(move || {
+        let mut buffer = [0u8; 1024];
+        while let Ok(n) = reader.read(&mut buffer) {
+            if n == 0 {
+                break;
+            }
+            on_output(&buffer[..n]);
+        }
+        let success = child.wait().map(|status| status.success()).unwrap_or(false);
+        drop(pair.master);
+        on_exit(success);
+    });
+    Ok((killer, pid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::time::Duration;
+
+    #[test]
+    fn test_spawn_reports_status() {
+        for (command, expected) in [("echo watched", true), ("exit 3", false)] {
+            let (tx, rx) = mpsc::channel();
+            let output = Arc::new(Mutex::new(Vec::new()));
+            let sink = output.clone();
+            spawn(
+                command,
+                &std::env::temp_dir(),
+                (24, 80),
+                move |data| sink.lock().unwrap().extend_from_slice(data),
+                move |success| {
+                    let _ = tx.send(success);
+                },
+            ).unwrap();
+            assert_eq!(rx.recv_timeout(Duration::from_secs(10)), Ok(expected));
+            if expected {
+                assert!(String::from_utf8_lossy(&output.lock().unwrap()).contains("watched"));
+            }
+        }
+    }
+}
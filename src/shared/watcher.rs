@@ -0,0 +1,52 @@
+// Polling filesystem watcher
+//
+// Periodically fingerprints the workspace (paths, sizes and modification times)
+// and invokes a callback when anything changed. Polling keeps this portable and
+// dependency free; the interval is coarse enough to be cheap on large trees.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use super::workspace;
+
+/// How often the workspace is re-scanned
+pub const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Fingerprint of every file's path, size and mtime under `root`
+pub fn fingerprint(root: &Path) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for entry in workspace::walk_files(root) {
+        entry.path().hash(&mut hasher);
+        if let Ok(meta) = entry.metadata() {
+            meta.len().hash(&mut hasher);
+            if let Ok(modified) = meta.modified() {
+                modified.hash(&mut hasher);
+            }
+        }
+    }
+    hasher.finish()
+}
+
+/// Watch `root` on a background thread, calling `on_change` after each detected change.
+/// The thread stops once `on_change` returns false.
+pub fn watch<F>(root: PathBuf, mut on_change: F)
+where
+    F: FnMut() -> bool + Send + 'static,
+{
+    thread::spawn(move || {
+        let mut last = fingerprint(&root);
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let current = fingerprint(&root);
+            if current != last {
+                last = current;
+                if !on_change() {
+                    break;
+                }
+            }
+        }
+    });
+}
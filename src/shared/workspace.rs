@@ -0,0 +1,90 @@
+// Workspace file enumeration shared by scanners, search and the watcher
+
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use walkdir::{DirEntry, WalkDir};
+
+/// Directories never worth descending into
+const SKIPPED_DIRS: &[&str] = &["target", "node_modules"];
+
+/// Directories the user excluded from the workspace, as canonical paths
+static EXCLUDED: RwLock<Vec<PathBuf>> = RwLock::new(Vec::new());
+
+/// Replace the excluded directories with `dirs`, relative to `workspace`
+pub fn set_excluded(workspace: &Path, dirs: &[PathBuf]) {
+    let root = workspace.canonicalize().unwrap_or_else(|_| workspace.to_path_buf());
+    if let Ok(mut excluded) = EXCLUDED.write() {
+        *excluded = dirs.iter().map(|dir| root.join(dir)).collect();
+    }
+}
+
+/// Whether `path` is an excluded directory
+pub fn is_excluded(path: &Path) -> bool {
+    let Ok(excluded) = EXCLUDED.read() else { return false };
+    !excluded.is_empty() && path.canonicalize().is_ok_and(|path| excluded.contains(&path))
+}
+
+/// The excluded directories under `root`, spelled as a walk from `root` reaches them
+pub fn excluded_under(root: &Path) -> Vec<PathBuf> {
+    let excluded = EXCLUDED.read().map(|e| e.clone()).unwrap_or_default();
+    if excluded.is_empty() {
+        return excluded;
+    }
+    let Ok(canonical) = root.canonicalize() else { return Vec::new() };
+    excluded.iter()
+        .filter_map(|dir| dir.strip_prefix(&canonical).ok())
+        .filter(|relative| !relative.as_os_str().is_empty())
+        .map(|relative| root.join(relative))
+        .collect()
+}
+
+fn is_skipped(entry: &DirEntry) -> bool {
+    // Never skip the root itself, even if it is e.g. "."
+    if entry.depth() == 0 {
+        return false;
+    }
+    let name = entry.file_name().to_string_lossy();
+    name.starts_with('.') || (entry.file_type().is_dir() && SKIPPED_DIRS.contains(&name.as_ref()))
+}
+
+/// All regular files in the workspace, skipping hidden entries, build output
+/// and excluded directories
+pub fn walk_files(root: &Path) -> impl Iterator<Item = DirEntry> {
+    let excluded = excluded_under(root);
+    WalkDir::new(root)
+        .into_iter()
+        .filter_entry(move |e| !is_skipped(e) && !excluded.iter().any(|dir| e.path() == dir))
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+}
+
+/// Paths of all workspace files
+pub fn file_paths(root: &Path) -> Vec<PathBuf> {
+    walk_files(root).map(|e| e.into_path()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_excluded_dirs() {
+        let root = std::env::temp_dir().join(format!("nterm-workspace-{}", std::process::id()));
+        for dir in ["src", "build/out", "target"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in ["src/main.rs", "build/out/gen.rs", "target/x.rs"] {
+            fs::write(root.join(file), "").unwrap();
+        }
+        set_excluded(&root, &[PathBuf::from("build")]);
+        assert_eq!(file_paths(&root), vec![root.join("src/main.rs")]);
+        assert!(is_excluded(&root.join("build")));
+        assert!(!is_excluded(&root.join("src")));
+        assert!(excluded_under(&root.join("src")).is_empty());
+
+        set_excluded(&root, &[]);
+        assert_eq!(file_paths(&root).len(), 2);
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -4,6 +4,7 @@ pub enum Action {
     SwitchFocus,
     ToggleMenu,
     ResetLayout,
+    ToggleZen,
     DumpHistory,
     ScrollUp,
     ScrollDown,
@@ -11,10 +12,161 @@ pub enum Action {
     CollapseDir,
     Open,
     FileSearch,
+    RecentFiles,
+    RestoreSession,
+    SearchEverything,
+    SearchBuffers,
     CycleModel,
+    SwitchPreset,
     OpenSettings,
     Copy,
+    Cut,
     Paste,
+    CopyPermalink,
     About,
+    ToggleProblems,
+    ToggleTodos,
+    WorkspaceStats,
+    ProjectSetup,
+    Save,
+    SaveAs,
+    SaveAll,
+    ReloadFile,
+    Find,
+    Replace,
+    ExportHtml,
+    ExportAnsi,
+    NewScratch,
+    NextBuffer,
+    PrevBuffer,
+    CloseBuffer,
+    CloseAll,
+    ToggleReadOnly,
+    ToggleLineEnding,
+    ShowDiff,
+    Compare,
+    Refactor,
+    RenameSymbol,
+    Outline,
+    FormatDocument,
+    ToggleInlayHints,
+    AiReview,
+    ToggleReviewComment,
+    ClearReview,
+    SpellSuggest,
+    Complete,
+    GotoDefinition,
+    FindReferences,
+    ToggleComment,
+    ToggleFold,
+    PickColor,
+    UnfoldAll,
+    NavigateBack,
+    NavigateForward,
+    NextChange,
+    PrevChange,
+    NextDiagnostic,
+    PrevDiagnostic,
+    ShowDiagnostic,
+    TerminalCdToFile,
+    FileTreeFromTerminal,
+    ToggleExcluded,
+    ShowExcluded,
+    ToggleWatch,
+    SwitchTerminalTab,
+    ProcessTree,
+    ToggleMirror,
+    Snapshot,
+    SnapshotRedacted,
     None,
 }
+
+/// Names used to refer to actions from outside the key map (remote control, config)
+const ACTION_NAMES: &[(&str, Action)] = &[
+    ("quit", Action::Quit),
+    ("switch_focus", Action::SwitchFocus),
+    ("toggle_menu", Action::ToggleMenu),
+    ("reset_layout", Action::ResetLayout),
+    ("toggle_zen", Action::ToggleZen),
+    ("dump_history", Action::DumpHistory),
+    ("file_search", Action::FileSearch),
+    ("recent_files", Action::RecentFiles),
+    ("restore_session", Action::RestoreSession),
+    ("search_everything", Action::SearchEverything),
+    ("search_buffers", Action::SearchBuffers),
+    ("cycle_model", Action::CycleModel),
+    ("switch_preset", Action::SwitchPreset),
+    ("open_settings", Action::OpenSettings),
+    ("copy", Action::Copy),
+    ("cut", Action::Cut),
+    ("paste", Action::Paste),
+    ("copy_permalink", Action::CopyPermalink),
+    ("about", Action::About),
+    ("toggle_problems", Action::ToggleProblems),
+    ("toggle_todos", Action::ToggleTodos),
+    ("workspace_stats", Action::WorkspaceStats),
+    ("project_setup", Action::ProjectSetup),
+    ("save", Action::Save),
+    ("save_as", Action::SaveAs),
+    ("save_all", Action::SaveAll),
+    ("reload_file", Action::ReloadFile),
+    ("find", Action::Find),
+    ("replace", Action::Replace),
+    ("export_html", Action::ExportHtml),
+    ("export_ansi", Action::ExportAnsi),
+    ("new_scratch", Action::NewScratch),
+    ("next_buffer", Action::NextBuffer),
+    ("prev_buffer", Action::PrevBuffer),
+    ("close_buffer", Action::CloseBuffer),
+    ("close_all", Action::CloseAll),
+    ("toggle_read_only", Action::ToggleReadOnly),
+    ("toggle_line_ending", Action::ToggleLineEnding),
+    ("show_diff", Action::ShowDiff),
+    ("compare", Action::Compare),
+    ("refactor", Action::Refactor),
+    ("rename_symbol", Action::RenameSymbol),
+    ("outline", Action::Outline),
+    ("format_document", Action::FormatDocument),
+    ("toggle_inlay_hints", Action::ToggleInlayHints),
+    ("ai_review", Action::AiReview),
+    ("toggle_review_comment", Action::ToggleReviewComment),
+    ("clear_review", Action::ClearReview),
+    ("spell_suggest", Action::SpellSuggest),
+    ("complete", Action::Complete),
+    ("goto_definition", Action::GotoDefinition),
+    ("find_references", Action::FindReferences),
+    ("toggle_comment", Action::ToggleComment),
+    ("toggle_fold", Action::ToggleFold),
+    ("pick_color", Action::PickColor),
+    ("unfold_all", Action::UnfoldAll),
+    ("navigate_back", Action::NavigateBack),
+    ("navigate_forward", Action::NavigateForward),
+    ("next_change", Action::NextChange),
+    ("prev_change", Action::PrevChange),
+    ("next_diagnostic", Action::NextDiagnostic),
+    ("prev_diagnostic", Action::PrevDiagnostic),
+    ("show_diagnostic", Action::ShowDiagnostic),
+    ("terminal_cd_to_file", Action::TerminalCdToFile),
+    ("file_tree_from_terminal", Action::FileTreeFromTerminal),
+    ("toggle_excluded", Action::ToggleExcluded),
+    ("show_excluded", Action::ShowExcluded),
+    ("toggle_watch", Action::ToggleWatch),
+    ("switch_terminal_tab", Action::SwitchTerminalTab),
+    ("process_tree", Action::ProcessTree),
+    ("toggle_mirror", Action::ToggleMirror),
+    ("snapshot", Action::Snapshot),
+    ("snapshot_redacted", Action::SnapshotRedacted),
+];
+
+impl Action {
+    /// Look up an action by name; `-` and `_` are interchangeable
+    pub fn from_name(name: &str) -> Option<Action> {
+        let name = name.trim().to_lowercase().replace('-', "_");
+        ACTION_NAMES.iter().find(|(n, _)| *n == name).map(|(_, a)| *a)
+    }
+
+    /// The name of this action, if it can be referred to by name
+    pub fn name(&self) -> Option<&'static str> {
+        ACTION_NAMES.iter().find(|(_, a)| a == self).map(|(n, _)| *n)
+    }
+}
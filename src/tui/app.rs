@@ -1,29 +1,123 @@
 use ratatui::{
-    crossterm::event::{Event, KeyCode, KeyModifiers},
+    buffer::Buffer,
+    crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind},
+    layout::{Position, Rect},
+    style::{Color, Modifier, Style},
     widgets::{Block, Borders, ListState, ScrollbarState},
 };
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet},
     fs,
     io::{Read, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::{Arc, RwLock, mpsc, Mutex},
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 use tui_textarea::TextArea;
-use portable_pty::{CommandBuilder, NativePtySystem, PtySize, PtySystem};
-use walkdir::WalkDir;
+use portable_pty::{ChildKiller, CommandBuilder, MasterPty, NativePtySystem, PtySize, PtySystem};
 use arboard::Clipboard;
 
 use super::action::Action;
-use super::editor::EditorState;
+use super::completion::Completion;
+use super::keymap::{self, ChordMatch, EditorKeyMap, Key, KeyMap};
+use super::editor::{self, AnnotationAction, EditorState, GutterMarker, HighlightRequest, LineAnnotation, RangeHighlight};
+use super::diff_view::DiffView;
+use super::hex_view::HexView;
+use super::navigation::{Location, NavHistory};
+use super::process_view::{self, ProcessView};
+use super::recent_files::RecentFiles;
+use super::outline::{self, Outline};
+use super::project_setup::{ProjectSetup, SetupItem};
+use super::references::{self, ReferenceList};
+use super::tree_menu::TreeMenu;
+use super::search_palette::{self, SearchPalette};
+use super::snapshot;
+use super::ui;
+use super::find::{FindBar, FindField};
 use super::theme::Theme;
-use crate::shared::{FileNode, VisibleItem, flatten_node, toggle_node_recursive};
+use super::vim::{Vim, VimMode};
+use crate::shared::{FileNode, VisibleItem, flatten_node, mark_excluded, toggle_node_recursive};
 use crate::shared::send_message;
-use crate::shared::Config;
-
-#[derive(PartialEq)]
+use crate::shared::ai::{self, presets, send_message_with_tools, tools, ContextRule, TokenUsage};
+use crate::shared::file_proposal::{self, FileProposal};
+use crate::shared::{AutoScroll, Config, ConfigError, KeybindingMode};
+use crate::shared::config_writer::ConfigWriter;
+use crate::shared::global_search::{self, SearchHit, SearchTarget};
+use crate::shared::highlight::{self, HighlightSpan};
+use crate::shared::project;
+use crate::shared::recovery::{self, BufferBackup};
+use crate::shared::chat::{self, ChatMessage, Role};
+use crate::shared::code_lens::{self, LensAction};
+use crate::shared::color::{self, ColorPicker};
+use crate::shared::ansi::{strip_ansi, AnsiColor, StyledLine};
+use crate::shared::stats::{self, WorkspaceStats};
+use crate::shared::toolchain::Toolchain;
+use crate::shared::format;
+use crate::shared::lint;
+use crate::shared::lsp::{self, LspClient, LspMessage, TextEdit};
+use super::welcome::{Welcome, WelcomeItem};
+use crate::shared::diff::Diff;
+use crate::shared::git_gutter::{self, LineChange};
+use crate::shared::mirror::{self, MirrorServer};
+use crate::shared::permalink;
+use crate::shared::encoding::{self, TextEncoding};
+use crate::shared::events::{EventBus, EventKind, WorkspaceEvent};
+use crate::shared::preview::{self, Preview};
+use crate::shared::process_tree::{self, Signal};
+use crate::shared::prompt_history::{self, PromptHistory};
+use crate::shared::capabilities::Capabilities;
+use crate::shared::todo::{self, TodoItem};
+use crate::shared::terminal;
+use crate::shared::watch::{self, WatchStatus};
+use crate::shared::watcher;
+use crate::shared::workspace;
+use crate::shared::syntax;
+use crate::shared::export::{self, ExportFormat};
+use crate::shared::remote::{self, RemoteCommand};
+use crate::shared::scratch::{self, ScratchBuffer};
+use crate::shared::sandbox;
+use crate::shared::scrollback;
+use crate::shared::search;
+use crate::shared::secrets::{self, SecretMatch};
+use crate::shared::session_backup::{self, RestorePoint};
+use crate::shared::spell::{self, ProseKind, SpellChecker};
+use crate::shared::storage::{self, Storage};
+use crate::shared::refactor::{self, LineEdit, Occurrence};
+use crate::shared::review::{self, ReviewComment};
+use crate::shared::diagnostics::{parse_build_output, Diagnostic, DiagnosticSource, DiagnosticStore, Severity};
+
+/// Rows between the view and the end of a preview's loaded text before the next chunk is read
+const PREVIEW_LOOKAHEAD: usize = 500;
+
+/// How often modified buffers are written to the backup directory
+const BACKUP_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long a status bar notification stays visible
+pub const STATUS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Amount of recent terminal output scanned for build errors
+const BUILD_OUTPUT_SCAN_BYTES: usize = 64 * 1024;
+
+/// Lines below the top of the editor checked for color literals each frame
+const SWATCH_SCAN_LINES: usize = 200;
+
+/// Characters of a collapsed review comment shown after its line
+const REVIEW_PREVIEW_CHARS: usize = 60;
+
+/// Typing pause before inlay hints are asked for again
+const INLAY_HINT_DELAY: Duration = Duration::from_millis(500);
+
+/// How many times one chat message may go back to the model with command output
+const MAX_COMMAND_ROUNDS: usize = 3;
+
+/// Editor lines around the cursor shown to session mirror viewers
+const MIRROR_EDITOR_ROWS: usize = 20;
+
+/// Rows of output kept above the shell and watch screens
+const TERMINAL_SCROLLBACK: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 
 pub enum ActivePanel {
 
@@ -37,6 +131,143 @@ pub enum ActivePanel {
 
 }
 
+/// What the terminal panel shows: the interactive shell or the watch command's last run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TerminalTab {
+    Shell,
+    Watch,
+}
+
+
+
+/// Result of a settings panel "Test connection" for one model
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectionTest {
+    Testing,
+    Passed(Duration),
+    Failed(String),
+}
+
+/// What the unsaved changes prompt is holding back
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnsavedPrompt {
+    Quit,
+    CloseBuffer,
+    CloseAll,
+    /// Reloading the current file from disk, which only offers to discard
+    Revert,
+}
+
+/// Ctrl+R search through the prompt history of the chat input
+#[derive(Debug, Clone, Default)]
+struct PromptSearch {
+    query: String,
+    /// History index of the prompt shown
+    found: Option<usize>,
+    /// Input before the search, restored when it is cancelled
+    draft: String,
+}
+
+/// A completion request sent to a language server
+struct CompletionRequest {
+    server: String,
+    id: u64,
+    /// The buffer and cursor row it was asked at
+    path: PathBuf,
+    row: usize,
+    /// Asked for with Ctrl+Space rather than by typing
+    explicit: bool,
+}
+
+/// A rename request sent to a language server
+struct RenameRequest {
+    server: String,
+    id: u64,
+    symbol: String,
+    new_name: String,
+}
+
+/// A formatting request sent to a language server
+struct FormatRequest {
+    server: String,
+    id: u64,
+    path: PathBuf,
+    /// The buffer text it was asked about; edits are dropped if it changed since
+    text: String,
+}
+
+/// An inlay hints request for the open file
+struct InlayHintRequest {
+    server: String,
+    id: u64,
+    path: PathBuf,
+    /// Content hash of the buffer it was asked about; hints for other text are dropped
+    hash: u64,
+}
+
+/// A document symbols request for the outline
+struct OutlineRequest {
+    server: String,
+    id: u64,
+    /// The file asked about, which may no longer be open when the reply comes
+    path: PathBuf,
+}
+
+/// Parts of the app that react to workspace events
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Subscriber {
+    Chat,
+    Recovery,
+    Config,
+    Watch,
+    Linter,
+    GitGutter,
+    Toolchain,
+    Todos,
+}
+
+/// The bus with everyone subscribed, in the order they hear about an event
+fn workspace_events() -> EventBus<Subscriber> {
+    let mut bus = EventBus::new();
+    bus.subscribe(Subscriber::Chat, &[EventKind::AiResponseCompleted]);
+    bus.subscribe(Subscriber::Recovery, &[EventKind::FileSaved]);
+    bus.subscribe(Subscriber::Config, &[EventKind::FileSaved]);
+    bus.subscribe(Subscriber::Watch, &[EventKind::FileSaved, EventKind::CommandFinished]);
+    bus.subscribe(Subscriber::Linter, &[EventKind::FileSaved]);
+    bus.subscribe(Subscriber::GitGutter, &[EventKind::FilesChanged]);
+    bus.subscribe(Subscriber::Toolchain, &[EventKind::FilesChanged]);
+    bus.subscribe(Subscriber::Todos, &[EventKind::FilesChanged]);
+    bus
+}
+
+/// An AI request with its prompt fully built, context included. It waits
+/// for confirmation when the prompt looks like it contains secrets.
+enum AiRequest {
+    /// A chat message as typed, and the prompt with the active preset's context
+    Chat { message: String, prompt: String },
+    /// A review of the file at `path`, whose `lines` the comments refer to
+    Review { path: PathBuf, lines: Vec<String>, prompt: String },
+    /// A refactor of the symbol's `occurrences`, which the edits are matched against
+    Refactor { occurrences: Vec<Occurrence>, prompt: String },
+}
+
+impl AiRequest {
+    fn prompt(&self) -> &str {
+        match self {
+            AiRequest::Chat { prompt, .. } | AiRequest::Review { prompt, .. } | AiRequest::Refactor { prompt, .. } => prompt,
+        }
+    }
+}
+
+/// A definition or references request sent to a language server
+struct LocationRequest {
+    server: String,
+    id: u64,
+    references: bool,
+    /// The word under the cursor when it was asked
+    symbol: String,
+}
+
 
 
 pub enum AppEvent {
@@ -47,7 +278,75 @@ pub enum AppEvent {
 
     Tick,
 
-    AiResponse(String),
+    AiResponse(ChatMessage),
+
+    FsChanged,
+
+    TodosScanned(Vec<TodoItem>),
+
+    StatsCollected(WorkspaceStats),
+
+    /// A chat message's prompt is built, with the workspace summary collected in the background
+    ChatPrepared { message: String, prompt: String },
+
+    /// The refactor prompt is built from the symbol's occurrences across the workspace
+    RefactorPrepared { occurrences: Vec<Occurrence>, prompt: String },
+
+    RefactorProposed(Result<Vec<LineEdit>, String>),
+
+    /// The review prompt for the file at this path is built, with its changes since HEAD
+    ReviewPrepared { path: PathBuf, lines: Vec<String>, prompt: String },
+
+    /// AI review of the file at this path finished
+    ReviewReceived { path: PathBuf, result: Result<Vec<ReviewComment>, String> },
+
+    /// `/summarize` finished; the summary replaces the first `count` messages
+    ChatSummarized { count: usize, result: Result<String, String> },
+
+    /// Connection test finished for the model at this index
+    ConnectionTested(usize, Result<Duration, String>),
+
+    FilesProposed(Vec<FileProposal>),
+
+    /// Contents of an opened file, with how much of it was read when it is previewed
+    FileLoaded(PathBuf, Result<(String, TextEncoding, Option<Preview>), String>),
+
+    SpellCheckerLoaded(Result<SpellChecker, String>),
+
+    WatchOutput,
+
+    /// A watch run exited; `run` identifies it so superseded runs are ignored
+    WatchFinished { run: u64, success: bool },
+
+    /// Changed lines of `path` against git HEAD were computed
+    GitChanges { path: PathBuf, changes: Vec<(usize, LineChange)> },
+
+    /// A linter run on `path` finished; `run` identifies it so superseded runs are ignored
+    LintFinished { path: PathBuf, run: u64, result: Result<Vec<Diagnostic>, String> },
+
+    /// Writing the config file failed on the background writer
+    ConfigSaveFailed(String),
+
+    /// File name and content hits of the search palette's query number `generation`
+    WorkspaceSearched { generation: u64, hits: Vec<SearchHit> },
+
+    /// Lines of the active buffer highlighted in the background
+    LinesHighlighted { request: HighlightRequest, lines: Vec<Vec<HighlightSpan>> },
+
+    /// Workspace references to the functions defined in `path`
+    ReferencesCounted { path: PathBuf, counts: HashMap<String, usize> },
+
+    /// A message from the language server started with this command
+    Lsp { server: String, message: LspMessage },
+
+    /// Permalink to the cursor line or selection, and whether the file has
+    /// uncommitted changes
+    PermalinkBuilt(Result<(String, bool), String>),
+
+    /// An external formatter finished with `text`, the buffer of `path` it was given
+    Formatted { path: PathBuf, text: String, result: Result<String, String> },
+
+    Remote(RemoteCommand),
 
 }
 
@@ -71,13 +370,48 @@ pub struct App<'a> {
 
     pub editor_state: EditorState,
 
+    /// All open buffers in tab order; the slot at `active_buffer` is a placeholder
+    /// while that buffer is swapped out into `editor_state`
+    buffers: Vec<EditorState>,
+
+    pub active_buffer: usize,
+
     pub editor_scroll_state: ScrollbarState,
 
+    pub find_bar: FindBar<'a>,
+
+    /// Modal editing state, used when `keybinding_mode` is vim
+    pub vim: Vim,
+
     
 
     pub chat_input: TextArea<'a>,
 
-    pub chat_history: Vec<String>,
+    pub chat_history: Vec<ChatMessage>,
+
+    /// Long chat messages shown in full (indices into `chat_history`)
+    pub chat_expanded: HashSet<usize>,
+
+    /// Chat message to scroll to on the next draw, once the wrapped height is known
+    pub chat_scroll_to: Option<usize>,
+
+    /// Largest `chat_scroll` at the last draw; the view is at the bottom when it reaches it
+    pub chat_max_scroll: u16,
+
+    /// A `/summarize` request is in flight
+    chat_summarizing: bool,
+
+    /// Likely secrets found in `pending_ai`; the request waits for confirmation
+    pub show_secret_warning: bool,
+
+    pub secret_findings: Vec<SecretMatch>,
+
+    pending_ai: Option<AiRequest>,
+
+    /// Prompts sent from the chat input, for Up and Ctrl+R
+    prompt_history: PromptHistory,
+
+    prompt_search: Option<PromptSearch>,
 
     pub chat_scroll: u16,
 
@@ -105,380 +439,1133 @@ pub struct App<'a> {
 
     pub settings_scroll_offset: usize,  // Scroll offset for settings list
 
+    /// Latest connection test per model index
+    pub settings_health: HashMap<usize, ConnectionTest>,
+
     pub config: Config,
 
+    pub workspace: PathBuf,
 
+    /// Virtualenv and pinned Node/Rust versions found in the workspace
+    pub toolchain: Toolchain,
 
-    pub active_panel: ActivePanel,
+    /// Directory shown in the file tree; "." is the workspace
+    pub file_tree_root: PathBuf,
 
-    pub should_quit: bool,
 
-    
 
-    // Terminal State
+    // Remote Control (None if the socket could not be bound)
 
-    pub pty_writer: Box<dyn Write + Send>,
+    pub remote_socket: Option<PathBuf>,
 
-    pub terminal_screen: Arc<RwLock<tui_term::vt100::Parser>>,
 
-    pub terminal_scroll_state: ScrollbarState,
 
-    pub history_buffer: Arc<RwLock<Vec<u8>>>,
+    // Status Bar Notification
 
-    pub event_rx: mpsc::Receiver<AppEvent>,
+    pub status_message: Option<(String, Instant)>,
 
-    pub event_tx: mpsc::Sender<AppEvent>,
 
-    
 
-    // Clipboard
+    // Save As Prompt
 
-    pub clipboard: Option<Arc<Mutex<Clipboard>>>,
+    pub show_save_as: bool,
 
-    
+    pub save_as_input: TextArea<'a>,
 
-    // Menus & Keys
 
-    pub menu_titles: Vec<String>,
 
-    pub menu_open_idx: Option<usize>,
+    // Compare Tool
 
-    pub menu_hover_idx: Option<usize>,
+    pub show_compare: bool,
 
-    pub key_map: HashMap<(KeyCode, KeyModifiers), Action>,
+    pub compare_input: TextArea<'a>,
 
-    pub current_theme: Theme,
-}
+    /// Side-by-side diff shown in place of the editor
+    pub diff_view: Option<DiffView>,
+    /// Hex dump of a binary file, shown in place of the editor
+    pub hex_view: Option<HexView>,
+    /// Earlier focus and cursor locations for Alt+Left/Right
+    nav_history: NavHistory,
+    /// What the host terminal can display; set at startup
+    pub capabilities: Capabilities,
+    /// Last copied or cut text, for pasting when there is no system clipboard
+    register: Option<String>,
+    /// Where chat messages and scratch buffers are kept; `None` when it couldn't be opened
+    storage: Option<Box<dyn Storage>>,
+    /// Writes config changes in the background, batched
+    config_writer: ConfigWriter,
+    /// The "search everything" palette, while open
+    pub search_palette: Option<SearchPalette<'a>>,
 
+    /// The Ctrl+E recent files switcher, while open
+    pub recent_files: Option<RecentFiles>,
 
+    /// Context menu of the selected file tree entry, while open
+    pub tree_menu: Option<TreeMenu>,
+    /// Whether directories excluded from the workspace are listed in the tree
+    pub show_excluded: bool,
 
-impl<'a> App<'a> {
 
-    pub fn new() -> Self {
 
-        let editor_state = EditorState::new();
+    // Terminal Output Capture
 
+    pub show_capture: bool,
 
+    pub capture_input: TextArea<'a>,
 
-        let mut chat_input = TextArea::default();
 
-        chat_input.set_block(Block::default().borders(Borders::ALL).title(" Chat Input "));
 
-        
+    // Crash Recovery
 
-        let mut search_input = TextArea::default();
+    pub show_recovery: bool,
 
-        search_input.set_block(Block::default().borders(Borders::ALL).title(" Search Files "));
+    pub recovery_backups: Vec<BufferBackup>,
 
-        
+    pub recovery_state: ListState,
 
-        let config = Config::load();
+    last_backup_at: Instant,
 
-        let mut settings_input = TextArea::default();
-        settings_input.set_block(Block::default().borders(Borders::ALL).title(" API Key "));
-        // Load API key from first model if available
-        if let Some(model) = config.models.first() {
-            if let Some(key) = &model.api_key {
-                settings_input.insert_str(key);
-            }
-        }
+    /// When the last session restore point was made
+    last_session_backup_at: Instant,
 
+    /// Restore points listed by File > Restore Session From..., while open
+    pub restore_points: Option<Vec<RestorePoint>>,
+    pub restore_selected: usize,
 
+    /// Row selected in the context preset picker while it is open; row 0 is "no preset"
+    pub preset_picker: Option<usize>,
 
-        // Initialize PTY
+    /// Processes of a terminal tab, while shown
+    pub process_view: Option<ProcessView>,
 
-        let pty_system = NativePtySystem::default();
 
-        let pair = pty_system.openpty(PtySize {
 
-            rows: 24,
+    // External Changes
 
-            cols: 80,
+    /// The active buffer changed on disk while it has unsaved edits
+    pub show_conflict: bool,
 
-            pixel_width: 0,
+    /// Quitting or closing a buffer waits for Save/Discard/Cancel
+    pub unsaved_prompt: Option<UnsavedPrompt>,
 
-            pixel_height: 0,
+    last_disk_check: Instant,
 
-        }).expect("Failed to create PTY");
+    /// User theme/syntax load errors have been moved to the Problems panel
+    user_assets_checked: bool,
 
 
 
-        let cmd = CommandBuilder::new("bash");
+    // Auto-save
 
-        let _child = pair.slave.spawn_command(cmd).expect("Failed to spawn shell");
+    last_edit_at: Instant,
 
+    /// Whether the editor had focus at the last tick
+    autosave_in_editor: bool,
 
+    pub last_autosave: Option<Instant>,
 
-        let mut reader = pair.master.try_clone_reader().expect("Failed to clone reader");
 
-        let writer = pair.master.take_writer().expect("Failed to take writer");
 
+    // Spell Checking
 
+    pub spell_checker: Option<SpellChecker>,
 
-        let parser = Arc::new(RwLock::new(tui_term::vt100::Parser::new(24, 80, 0)));
+    pub show_spell: bool,
 
-        let parser_clone = parser.clone();
+    /// Whether the problems on the cursor line are shown in a popup
+    pub show_diagnostic_popup: bool,
 
-        
+    pub spell_suggestions: Vec<String>,
 
-        let history = Arc::new(RwLock::new(Vec::new()));
+    pub spell_selected: usize,
 
-        let history_clone = history.clone();
+    /// Misspelled word the suggestions are for, as (row, start, end)
+    spell_target: (usize, usize, usize),
 
-        
+    // Language Servers
 
-        // Event Channel
+    /// Running language servers by command
+    lsp_servers: HashMap<String, LspClient>,
 
-        let (tx, rx) = mpsc::channel();
+    /// Server commands that failed to start or exited; not retried until the config changes
+    lsp_failed: HashSet<String>,
 
-        let pty_tx = tx.clone();
+    pub completion: Option<Completion>,
 
-        let tick_tx = tx.clone();
+    /// Completion request waiting for its reply
+    completion_request: Option<CompletionRequest>,
 
+    /// Definition or references request waiting for its reply
+    location_request: Option<LocationRequest>,
 
+    /// Rename request waiting for its reply
+    rename_request: Option<RenameRequest>,
 
-        // Spawn thread to read from PTY
+    /// Find All References results, while shown
+    pub reference_list: Option<ReferenceList>,
 
-        thread::spawn(move || {
+    /// Symbols of the open file, while the outline is shown
+    pub outline: Option<Outline>,
 
-            let mut buffer = [0u8; 1024];
+    /// Document symbols request waiting for its reply
+    outline_request: Option<OutlineRequest>,
 
-            loop {
+    /// The detected project's commands, while the setup panel is shown
+    pub project_setup: Option<ProjectSetup>,
 
-                match reader.read(&mut buffer) {
+    /// Formatting request waiting for its reply
+    format_request: Option<FormatRequest>,
 
-                    Ok(n) if n > 0 => {
 
-                        let data = &buffer[..n];
 
-                        if let Ok(mut p) = parser_clone.write() {
+    // Problems Panel
 
-                            p.process(data);
+    pub diagnostics: DiagnosticStore,
 
-                        }
+    pub show_problems: bool,
 
-                        if let Ok(mut h) = history_clone.write() {
+    pub problems_state: ListState,
 
-                            h.extend_from_slice(data);
+    pub problems_min_severity: Severity,
 
-                        }
 
-                        let _ = pty_tx.send(AppEvent::PtyData);
 
-                    }
+    // TODO Panel
 
-                    Ok(_) => break, 
+    pub todos: Vec<TodoItem>,
 
-                    Err(_) => break,
+    pub show_todos: bool,
 
-                }
+    pub todos_selected: usize,
 
-            }
+    pub todos_list_state: ListState,
 
-        });
+    pub todos_scanning: bool,
 
-        
+    todos_loaded: bool,
 
-        // Input Thread
 
-        let input_tx = tx.clone();
 
-        thread::spawn(move || {
+    // Color Picker
 
-            loop {
+    pub color_picker: Option<ColorPicker>,
 
-                if let Ok(event) = ratatui::crossterm::event::read() {
 
-                    let _ = input_tx.send(AppEvent::Input(event));
 
-                }
+    // Welcome View
 
-            }
+    /// Shown in the editor area until the first file is opened
+    pub welcome: Option<Welcome>,
 
-        });
 
-        
 
-        // Tick Thread
+    // Workspace Stats
 
-        thread::spawn(move || {
+    pub show_stats: bool,
 
-            loop {
+    pub stats: Option<WorkspaceStats>,
 
-                thread::sleep(Duration::from_millis(250));
+    pub stats_loading: bool,
 
-                let _ = tick_tx.send(AppEvent::Tick);
 
-            }
 
-        });
+    // Refactor Assistant
 
-        
+    pub show_refactor_prompt: bool,
 
-        // Clipboard
+    /// The prompt (`refactor_input`) asks for a new name for the language server to rename to
+    pub show_rename_prompt: bool,
 
-        let clipboard = Clipboard::new().ok().map(|c| Arc::new(Mutex::new(c)));
+    pub refactor_input: TextArea<'a>,
 
-        
+    refactor_symbol: String,
 
-        // Key Binding Init
+    /// Waiting for the model to propose edits
+    pub refactor_pending: bool,
 
-        let mut key_map = HashMap::new();
+    pub show_refactor: bool,
 
-        key_map.insert((KeyCode::Char('q'), KeyModifiers::CONTROL), Action::Quit);
+    pub refactor_edits: Vec<LineEdit>,
 
-        key_map.insert((KeyCode::Tab, KeyModifiers::NONE), Action::SwitchFocus);
+    pub refactor_selected: usize,
 
-        key_map.insert((KeyCode::Esc, KeyModifiers::NONE), Action::ToggleMenu); 
+    pub refactor_list_state: ListState,
 
-        key_map.insert((KeyCode::F(1), KeyModifiers::NONE), Action::ToggleMenu);
 
-        key_map.insert((KeyCode::Char('r'), KeyModifiers::CONTROL), Action::ResetLayout);
 
-        key_map.insert((KeyCode::Char('h'), KeyModifiers::CONTROL), Action::DumpHistory);
+    // AI File Proposals (nothing is written until approved)
 
-        key_map.insert((KeyCode::Char('p'), KeyModifiers::CONTROL), Action::FileSearch);
+    pub show_proposals: bool,
 
-        key_map.insert((KeyCode::Char('m'), KeyModifiers::CONTROL), Action::CycleModel);
+    pub file_proposals: Vec<FileProposal>,
 
-        key_map.insert((KeyCode::Char('s'), KeyModifiers::CONTROL), Action::OpenSettings);
-        key_map.insert((KeyCode::Char('c'), KeyModifiers::CONTROL), Action::Copy);
-        key_map.insert((KeyCode::Char('v'), KeyModifiers::CONTROL), Action::Paste);
+    pub proposal_selected: usize,
 
+    pub proposal_scroll: u16,
 
 
-        let theme_mode = config.theme;
 
-        let mut app = Self {
+    pub active_panel: ActivePanel,
 
-            file_tree: Vec::new(),
+    pub should_quit: bool,
 
-            visible_items: Vec::new(),
+    
 
-            selected_file_idx: 0,
-            
-            file_tree_state: ListState::default(),
+    // Terminal State
 
-            file_tree_scroll_offset: 0,
+    pub pty_writer: Box<dyn Write + Send>,
 
-            file_tree_scroll_state: ScrollbarState::default(),
+    /// The shell's PTY, resized with the terminal panel
+    pty_master: Box<dyn MasterPty + Send>,
 
-            
+    pub terminal_screen: Arc<RwLock<tui_term::vt100::Parser>>,
 
-            editor_state,
+    pub terminal_scroll_state: ScrollbarState,
 
-            editor_scroll_state: ScrollbarState::default(),
+    pub history_buffer: Arc<RwLock<Vec<u8>>>,
 
-            
+    pub terminal_tab: TerminalTab,
 
-            chat_input,
+    /// Scrollback rows of the shell and watch screens after the last output,
+    /// to keep the view in place when auto-scroll is off
+    scrollback_rows: (usize, usize),
 
-            chat_history: vec!["Hello! I'm your AI assistant. Press Tab to switch panels.".to_string()],
+    /// The shell running in the PTY, used to find its current directory
+    shell_pid: Option<u32>,
 
-            chat_scroll: 0,
 
-            chat_scroll_state: ScrollbarState::default(),
 
+    // Workspace Events
 
+    events: EventBus<Subscriber>,
 
-            is_searching: false,
 
-            search_input,
 
-            search_results: Vec::new(),
+    // Watch Mode
 
-            search_state: ListState::default(),
+    pub watch_enabled: bool,
 
-            
+    pub watch_status: WatchStatus,
 
-            show_settings: false,
+    /// Screen of the latest watch run
+    pub watch_screen: Arc<RwLock<tui_term::vt100::Parser>>,
 
-            settings_input,
+    watch_killer: Option<Box<dyn ChildKiller + Send + Sync>>,
 
-            settings_model_idx: 0,
+    /// Process of the running watch command
+    watch_pid: Option<u32>,
 
-            settings_editing: false,
+    watch_run: u64,
 
-            settings_scroll_offset: 0,
+    /// Latest linter run per workspace-relative file
+    lint_runs: HashMap<PathBuf, u64>,
 
-            config,
+    lint_run: u64,
 
-            
 
-            active_panel: ActivePanel::FileTree,
 
-            should_quit: false,
+    // Git Gutter
 
-            pty_writer: writer,
+    /// Lines of the open file changed since HEAD, with the file they belong to
+    pub git_changes: Option<(PathBuf, Vec<(usize, LineChange)>)>,
 
-            terminal_screen: parser,
+    /// File and content hash the latest computation was started for
+    git_changes_requested: Option<(PathBuf, u64)>,
 
-            terminal_scroll_state: ScrollbarState::default(),
+    git_changes_pending: bool,
 
-            history_buffer: history,
 
-            event_rx: rx,
 
-            event_tx: tx,
+    // Syntax Highlighting
 
-            
+    /// A batch of lines is being highlighted in the background
+    highlight_pending: bool,
 
-            clipboard,
 
-            
 
-            menu_titles: vec![" File ".to_string(), " Edit ".to_string(), " View ".to_string(), " Help ".to_string()],
+    // Code Lens
 
-            menu_open_idx: None,
+    /// Workspace references to each function of the open file, as last counted
+    lens_references: Option<(PathBuf, HashMap<String, usize>)>,
 
-            menu_hover_idx: None,
+    /// File and content hash the latest count was started for
+    lens_references_requested: Option<(PathBuf, u64)>,
 
-            key_map,
+    lens_references_pending: bool,
 
-            current_theme: Theme::new(theme_mode),
+    // Inlay Hints
 
-        };
+    /// File and content hash inlay hints were last asked for
+    inlay_hints_requested: Option<(PathBuf, u64)>,
 
-        
+    /// Inlay hints request waiting for its reply
+    inlay_hint_request: Option<InlayHintRequest>,
 
-        app.file_tree_state.select(Some(0));
+    // AI Review
 
-        app.refresh_file_tree();
+    /// Comments of the latest review and the file they are about
+    review: Option<(PathBuf, Vec<ReviewComment>)>,
 
-        app
+    review_pending: bool,
 
-    }
 
-    pub fn refresh_file_tree(&mut self) {
-        let root_path = PathBuf::from(".");
-        if let Ok(entries) = fs::read_dir(&root_path) {
-            let mut roots: Vec<FileNode> = entries
-                .filter_map(|res| res.ok())
-                .map(|e| FileNode::from_path(e.path(), 0))
-                .filter(|node| !node.name.starts_with('.'))
-                .collect();
-                
-             roots.sort_by(|a, b| {
-                match (a.is_dir, b.is_dir) {
-                    (true, false) => std::cmp::Ordering::Less,
-                    (false, true) => std::cmp::Ordering::Greater,
-                    _ => a.name.cmp(&b.name),
-                }
-            });
-            self.file_tree = roots;
-            self.update_visible_items();
-        }
-    }
 
-    pub fn update_visible_items(&mut self) {
-        let mut new_items = Vec::new();
-        for node in &self.file_tree {
-            flatten_node(node, &mut new_items);
-        }
-        self.visible_items = new_items;
-    }
+    // Session Mirror
 
-    pub fn toggle_selected_dir(&mut self) {
+    /// Serves a read-only view of the editor and terminal while sharing
+    pub mirror: Option<MirrorServer>,
+
+
+
+    // Screen Snapshot
+
+    /// Capture the screen after the next redraw; `true` blanks out the chat panel
+    pub pending_snapshot: Option<bool>,
+
+
+
+    // Zen Mode
+
+    /// Only the editor is shown, centered and without line numbers
+    pub zen_mode: bool,
+
+    pub event_rx: mpsc::Receiver<AppEvent>,
+
+    pub event_tx: mpsc::Sender<AppEvent>,
+
+    
+
+    // Clipboard
+
+    pub clipboard: Option<Arc<Mutex<Clipboard>>>,
+
+    
+
+    // Menus & Keys
+
+    pub menu_titles: Vec<String>,
+
+    pub menu_open_idx: Option<usize>,
+
+    pub menu_hover_idx: Option<usize>,
+
+    pub key_map: KeyMap,
+
+    /// Keys of a chord pressed so far, and when the last one was
+    pub pending_chord: Vec<Key>,
+    pending_chord_at: Instant,
+
+    /// Editor keys for the configured `keybinding_mode`
+    pub editor_key_map: EditorKeyMap,
+
+    pub current_theme: Theme,
+}
+
+
+
+impl Default for App<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> App<'a> {
+
+    pub fn new() -> Self {
+
+        let editor_state = EditorState::new();
+
+
+
+        let mut chat_input = TextArea::default();
+
+        chat_input.set_block(Block::default().borders(Borders::ALL).title(" Chat Input "));
+
+        
+
+        let mut search_input = TextArea::default();
+
+        search_input.set_block(Block::default().borders(Borders::ALL).title(" Search Files "));
+
+        
+
+        let config = Config::load();
+        let welcome = Welcome::new(&config, &std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")));
+
+        let mut settings_input = TextArea::default();
+        settings_input.set_block(Block::default().borders(Borders::ALL).title(" API Key "));
+        // Load API key from first model if available
+        if let Some(model) = config.models.first() {
+            if let Some(key) = &model.api_key {
+                settings_input.insert_str(key);
+            }
+        }
+
+
+
+        // Initialize PTY
+
+        let pty_system = NativePtySystem::default();
+
+        let pair = pty_system.openpty(PtySize {
+
+            rows: 24,
+
+            cols: 80,
+
+            pixel_width: 0,
+
+            pixel_height: 0,
+
+        }).expect("Failed to create PTY");
+
+
+
+        let remote_socket = remote::socket_path();
+
+        let mut cmd = CommandBuilder::new("bash");
+
+        cmd.env(remote::SOCKET_ENV, &remote_socket);
+
+        let toolchain = std::env::current_dir().map(|cwd| Toolchain::detect(&cwd)).unwrap_or_default();
+        for (key, value) in toolchain.env() {
+            cmd.env(key, value);
+        }
+
+        let child = pair.slave.spawn_command(cmd).expect("Failed to spawn shell");
+
+        let shell_pid = child.process_id();
+
+
+
+        let mut reader = pair.master.try_clone_reader().expect("Failed to clone reader");
+
+        let writer = pair.master.take_writer().expect("Failed to take writer");
+
+        let pty_master = pair.master;
+
+
+
+        let parser = Arc::new(RwLock::new(tui_term::vt100::Parser::new(24, 80, TERMINAL_SCROLLBACK)));
+
+        let parser_clone = parser.clone();
+
+        
+
+        let history = Arc::new(RwLock::new(Vec::new()));
+
+        let history_clone = history.clone();
+
+        
+
+        // Event Channel
+
+        let (tx, rx) = mpsc::channel();
+        let config_tx = tx.clone();
+        let config_writer = ConfigWriter::start(Config::get_config_path(), move |e| {
+            let _ = config_tx.send(AppEvent::ConfigSaveFailed(e));
+        });
+
+        let pty_tx = tx.clone();
+
+        let tick_tx = tx.clone();
+
+
+
+        // Spawn thread to read from PTY
+
+        thread::spawn(move || {
+
+            let mut buffer = [0u8; 1024];
+
+            loop {
+
+                match reader.read(&mut buffer) {
+
+                    Ok(n) if n > 0 => {
+
+                        let data = &buffer[..n];
+
+                        if let Ok(mut p) = parser_clone.write() {
+
+                            p.process(data);
+
+                        }
+
+                        if let Ok(mut h) = history_clone.write() {
+
+                            h.extend_from_slice(data);
+
+                        }
+
+                        let _ = pty_tx.send(AppEvent::PtyData);
+
+                    }
+
+                    Ok(_) => break, 
+
+                    Err(_) => break,
+
+                }
+
+            }
+
+        });
+
+        
+
+        // Input Thread
+
+        let input_tx = tx.clone();
+
+        thread::spawn(move || {
+
+            loop {
+
+                if let Ok(event) = ratatui::crossterm::event::read() {
+
+                    let _ = input_tx.send(AppEvent::Input(event));
+
+                }
+
+            }
+
+        });
+
+        
+
+        // Tick Thread
+
+        thread::spawn(move || {
+
+            loop {
+
+                thread::sleep(Duration::from_millis(250));
+
+                let _ = tick_tx.send(AppEvent::Tick);
+
+            }
+
+        });
+
+        
+
+        // Filesystem Watcher
+        let fs_tx = tx.clone();
+        let watch_root = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        watcher::watch(watch_root, move || fs_tx.send(AppEvent::FsChanged).is_ok());
+
+        // Remote Control Socket
+        let remote_tx = tx.clone();
+        let remote_socket = remote::serve(&remote_socket, move |cmd| remote_tx.send(AppEvent::Remote(cmd)).is_ok())
+            .ok()
+            .map(|_| remote_socket);
+
+        // Clipboard
+
+        let clipboard = Clipboard::new().ok().map(|c| Arc::new(Mutex::new(c)));
+
+        
+
+        // Key Binding Init
+
+        let (key_map, _) = keymap::build_key_map(&config.keybindings);
+
+        let editor_key_map = keymap::editor_key_map(config.keybinding_mode);
+
+
+
+        let theme_mode = config.theme;
+
+        let mut app = Self {
+
+            file_tree: Vec::new(),
+
+            visible_items: Vec::new(),
+
+            selected_file_idx: 0,
+            
+            file_tree_state: ListState::default(),
+
+            file_tree_scroll_offset: 0,
+
+            file_tree_scroll_state: ScrollbarState::default(),
+
+            
+
+            editor_state,
+
+            buffers: vec![EditorState::new()],
+
+            active_buffer: 0,
+
+            editor_scroll_state: ScrollbarState::default(),
+
+            find_bar: FindBar::new(),
+
+            vim: Vim::new(),
+
+            
+
+            chat_input,
+
+            chat_history: vec![ChatMessage::system("Hello! I'm your AI assistant. Press Tab to switch panels.")],
+            chat_expanded: HashSet::new(),
+            chat_scroll_to: None,
+            chat_max_scroll: 0,
+
+            chat_summarizing: false,
+
+            show_secret_warning: false,
+
+            secret_findings: Vec::new(),
+
+            pending_ai: None,
+            prompt_history: PromptHistory::default(),
+            prompt_search: None,
+
+            chat_scroll: 0,
+
+            chat_scroll_state: ScrollbarState::default(),
+
+
+
+            is_searching: false,
+
+            search_input,
+
+            search_results: Vec::new(),
+
+            search_state: ListState::default(),
+
+            
+
+            show_settings: false,
+
+            settings_input,
+
+            settings_model_idx: 0,
+
+            settings_editing: false,
+
+            settings_scroll_offset: 0,
+
+            settings_health: HashMap::new(),
+
+            config,
+
+            workspace: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+            toolchain,
+
+            file_tree_root: PathBuf::from("."),
+
+
+
+            remote_socket,
+
+
+
+            status_message: None,
+
+
+
+            show_save_as: false,
+
+            save_as_input: TextArea::default(),
+
+
+
+            show_compare: false,
+
+            compare_input: TextArea::default(),
+
+            diff_view: None,
+            hex_view: None,
+            nav_history: NavHistory::default(),
+            capabilities: Capabilities::default(),
+            register: None,
+            storage: None,
+            config_writer,
+            search_palette: None,
+            recent_files: None,
+            tree_menu: None,
+            show_excluded: false,
+
+
+
+            show_capture: false,
+
+            capture_input: TextArea::default(),
+
+
+
+            show_recovery: false,
+
+            recovery_backups: Vec::new(),
+
+            recovery_state: ListState::default(),
+
+
+            last_backup_at: Instant::now(),
+            last_session_backup_at: Instant::now(),
+            restore_points: None,
+            restore_selected: 0,
+            preset_picker: None,
+            process_view: None,
+
+
+
+            show_conflict: false,
+            unsaved_prompt: None,
+
+            last_disk_check: Instant::now(),
+
+            user_assets_checked: false,
+
+
+
+            last_edit_at: Instant::now(),
+
+            autosave_in_editor: true,
+
+            last_autosave: None,
+
+
+
+            spell_checker: None,
+
+            show_spell: false,
+            show_diagnostic_popup: false,
+
+            spell_suggestions: Vec::new(),
+
+            spell_selected: 0,
+
+            spell_target: (0, 0, 0),
+
+            lsp_servers: HashMap::new(),
+            lsp_failed: HashSet::new(),
+            completion: None,
+            completion_request: None,
+            location_request: None,
+            rename_request: None,
+            reference_list: None,
+            outline: None,
+            project_setup: None,
+            outline_request: None,
+            format_request: None,
+
+
+
+            diagnostics: DiagnosticStore::new(),
+
+            show_problems: false,
+
+            problems_state: ListState::default(),
+
+            problems_min_severity: Severity::Hint,
+
+
+
+            todos: Vec::new(),
+
+            show_todos: false,
+
+            todos_selected: 0,
+
+            todos_list_state: ListState::default(),
+
+            todos_scanning: false,
+
+            todos_loaded: false,
+
+
+
+            color_picker: None,
+
+
+
+            welcome: Some(welcome),
+
+            show_stats: false,
+
+            stats: None,
+
+            stats_loading: false,
+
+
+
+            show_refactor_prompt: false,
+            show_rename_prompt: false,
+
+            refactor_input: TextArea::default(),
+
+            refactor_symbol: String::new(),
+
+            refactor_pending: false,
+
+            show_refactor: false,
+
+            refactor_edits: Vec::new(),
+
+            refactor_selected: 0,
+
+            refactor_list_state: ListState::default(),
+
+
+
+            show_proposals: false,
+
+            file_proposals: Vec::new(),
+
+            proposal_selected: 0,
+
+            proposal_scroll: 0,
+
+            
+
+            active_panel: ActivePanel::FileTree,
+
+            should_quit: false,
+
+            pty_writer: writer,
+            pty_master,
+
+            terminal_screen: parser,
+
+            terminal_scroll_state: ScrollbarState::default(),
+
+            history_buffer: history,
+
+            terminal_tab: TerminalTab::Shell,
+
+            shell_pid,
+
+
+
+            watch_enabled: false,
+
+            watch_status: WatchStatus::Idle,
+
+            watch_screen: Arc::new(RwLock::new(tui_term::vt100::Parser::new(24, 80, TERMINAL_SCROLLBACK))),
+            scrollback_rows: (0, 0),
+
+            watch_killer: None,
+            watch_pid: None,
+
+            events: workspace_events(),
+
+            watch_run: 0,
+
+            lint_runs: HashMap::new(),
+
+            lint_run: 0,
+
+            git_changes: None,
+
+            git_changes_requested: None,
+
+            git_changes_pending: false,
+            highlight_pending: false,
+            lens_references: None,
+            lens_references_requested: None,
+            lens_references_pending: false,
+            inlay_hints_requested: None,
+            inlay_hint_request: None,
+            review: None,
+            review_pending: false,
+
+            mirror: None,
+            pending_snapshot: None,
+
+            zen_mode: false,
+
+            event_rx: rx,
+
+            event_tx: tx,
+
+            
+
+            clipboard,
+
+            
+
+            menu_titles: vec![" File ".to_string(), " Edit ".to_string(), " View ".to_string(), " Help ".to_string()],
+
+            menu_open_idx: None,
+
+            menu_hover_idx: None,
+
+            key_map,
+
+            pending_chord: Vec::new(),
+
+            pending_chord_at: Instant::now(),
+
+            editor_key_map,
+
+            current_theme: Theme::new(theme_mode),
+
+        };
+
+        
+
+        syntax::set_overrides(&app.config.syntax_overrides);
+        syntax::set_theme(syntax::theme_name(app.config.theme, app.config.syntax_theme.as_deref()));
+        app.apply_highlighter();
+
+        workspace::set_excluded(&app.workspace, &app.config.excluded_dirs(&app.workspace));
+
+        app.file_tree_state.select(Some(0));
+
+        app.refresh_file_tree();
+
+        app.check_recovery();
+
+        if !app.show_recovery {
+            app.suggest_project_setup();
+        }
+
+        app.load_spell_checker();
+
+        match storage::open_default() {
+            Ok(storage) => {
+                let prompts = storage.recent_prompts(&app.workspace, prompt_history::MAX_PROMPTS).unwrap_or_default();
+                app.prompt_history = PromptHistory::new(prompts);
+                app.storage = Some(storage);
+            }
+            Err(e) => app.notify(format!("Chat history and scratch buffers won't be kept: {}", e)),
+        }
+
+        app.backup_session();
+
+        if app.config.persist_scratch {
+            for buffer in app.stored_scratch_buffers() {
+                app.buffers.push(EditorState::scratch(buffer.name, buffer.lines));
+            }
+        }
+
+        app
+
+    }
+
+    pub fn refresh_file_tree(&mut self) {
+        if let Ok(entries) = fs::read_dir(&self.file_tree_root) {
+            let mut roots: Vec<FileNode> = entries
+                .filter_map(|res| res.ok())
+                .map(|e| FileNode::from_path(e.path(), 0))
+                .filter(|node| !node.name.starts_with('.'))
+                .collect();
+                
+             roots.sort_by(|a, b| {
+                match (a.is_dir, b.is_dir) {
+                    (true, false) => std::cmp::Ordering::Less,
+                    (false, true) => std::cmp::Ordering::Greater,
+                    _ => a.name.cmp(&b.name),
+                }
+            });
+            self.file_tree = roots;
+            self.update_visible_items();
+        }
+    }
+
+    pub fn update_visible_items(&mut self) {
+        let mut new_items = Vec::new();
+        for node in &self.file_tree {
+            flatten_node(node, &mut new_items, self.show_excluded);
+        }
+        self.visible_items = new_items;
+        self.selected_file_idx = self.selected_file_idx.min(self.visible_items.len().saturating_sub(1));
+    }
+
+    /// Open the context menu of the tree entry at `idx`, at a screen position
+    pub fn open_tree_menu(&mut self, idx: usize, column: u16, row: u16) {
+        let Some(item) = self.visible_items.get(idx) else { return };
+        self.tree_menu = Some(TreeMenu::new(item, self.show_excluded, column, row));
+        self.selected_file_idx = idx;
+        self.active_panel = ActivePanel::FileTree;
+    }
+
+    /// Keys of the tree context menu: arrows move, Enter runs the item, Esc closes
+    pub fn handle_tree_menu_key(&mut self, key: KeyEvent) {
+        let Some(menu) = self.tree_menu.as_mut() else { return };
+        match key.code {
+            KeyCode::Esc => self.tree_menu = None,
+            KeyCode::Up => menu.select(-1),
+            KeyCode::Down | KeyCode::Tab => menu.select(1),
+            KeyCode::Enter => {
+                if let Some(action) = self.tree_menu.take().and_then(|menu| menu.selected_action()) {
+                    self.handle_action(action);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Mouse over the tree context menu: hovering selects, a click runs the
+    /// item and a click elsewhere closes the menu
+    pub fn handle_tree_menu_mouse(&mut self, mouse: MouseEvent, screen: Rect) {
+        let Some(menu) = self.tree_menu.as_mut() else { return };
+        let item = menu.item_at(screen, mouse.column, mouse.row);
+        match mouse.kind {
+            MouseEventKind::Moved => {
+                if let Some(idx) = item {
+                    menu.selected = idx;
+                }
+            }
+            MouseEventKind::Down(_) => {
+                let action = item.and_then(|idx| menu.items.get(idx)).map(|&(_, action)| action);
+                self.tree_menu = None;
+                if let Some(action) = action {
+                    self.handle_action(action);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Exclude the directory selected in the tree from the workspace, or include it again
+    pub fn toggle_excluded_dir(&mut self) -> Result<(), String> {
+        let item = self.visible_items.get(self.selected_file_idx).filter(|item| item.is_dir)
+            .ok_or("Select a directory in the file tree to exclude it")?;
+        let root = self.workspace.canonicalize().unwrap_or_else(|_| self.workspace.clone());
+        let relative = item.path.canonicalize().ok()
+            .and_then(|path| path.strip_prefix(&root).ok().map(Path::to_path_buf))
+            .filter(|relative| !relative.as_os_str().is_empty())
+            .ok_or("Only directories inside the workspace can be excluded")?;
+        let excluded = self.config.toggle_excluded_dir(&self.workspace, relative.clone())
+            .ok_or("This workspace is not in the recent workspaces")?;
+        self.save_config();
+        self.apply_exclusions();
+        self.notify(if excluded {
+            format!("Excluded {}/ from the workspace", relative.display())
+        } else {
+            format!("{}/ is part of the workspace again", relative.display())
+        });
+        Ok(())
+    }
+
+    /// List the excluded directories in the tree (dimmed), or hide them again
+    pub fn toggle_show_excluded(&mut self) {
+        self.show_excluded = !self.show_excluded;
+        self.update_visible_items();
+        let count = self.config.excluded_dirs(&self.workspace).len();
+        self.notify(match (self.show_excluded, count) {
+            (_, 0) => "No directories are excluded from this workspace".to_string(),
+            (true, count) => format!("Showing {} excluded director{}", count, if count == 1 { "y" } else { "ies" }),
+            (false, _) => "Excluded directories hidden".to_string(),
+        });
+    }
+
+    /// Hand the workspace's exclusions to the tree, search and scanners
+    fn apply_exclusions(&mut self) {
+        workspace::set_excluded(&self.workspace, &self.config.excluded_dirs(&self.workspace));
+        mark_excluded(&mut self.file_tree);
+        self.update_visible_items();
+        self.publish(WorkspaceEvent::FilesChanged);
+    }
+
+    pub fn toggle_selected_dir(&mut self) {
         if let Some(item) = self.visible_items.get(self.selected_file_idx) {
             if item.is_dir {
                 let path_to_toggle = item.path.clone();
@@ -488,114 +1575,4304 @@ impl<'a> App<'a> {
         }
     }
 
-    pub fn load_selected_file(&mut self) {
-        if let Some(item) = self.visible_items.get(self.selected_file_idx) {
-            if !item.is_dir {
-                let _ = self.editor_state.load_file(item.path.clone());
+    pub fn load_selected_file(&mut self) {
+        if let Some(item) = self.visible_items.get(self.selected_file_idx) {
+            if !item.is_dir {
+                self.load_file_path(item.path.clone());
+            }
+        }
+    }
+
+    /// Open a file, switching to its tab if it is already open. The current buffer is
+    /// reused unless it holds unsaved changes or is a scratch buffer. The contents are
+    /// read on a background thread and arrive as `AppEvent::FileLoaded`.
+    pub fn load_file_path(&mut self, path: PathBuf) {
+        self.hex_view = None;
+        if let Some(idx) = self.find_buffer(&path) {
+            self.switch_buffer(idx);
+            self.add_recent_file(&path);
+            return;
+        }
+        let mut state = EditorState::loading(path.clone());
+        state.indent = self.config.indent_for(Some(&path));
+        if self.editor_state.modified || self.editor_state.is_scratch() {
+            self.buffers.push(state);
+            self.switch_buffer(self.buffers.len() - 1);
+        } else {
+            self.editor_state = state;
+        }
+
+        self.spawn_file_load(path, self.preview_limit());
+    }
+
+    /// Read `path` on a background thread, only its start if it is over `limit` bytes
+    fn spawn_file_load(&self, path: PathBuf, limit: u64) {
+        let tx = self.event_tx.clone();
+        thread::spawn(move || {
+            let result = preview::load(&path, limit).map_err(|e| e.to_string());
+            let _ = tx.send(AppEvent::FileLoaded(path, result));
+        });
+    }
+
+    /// Files over this many bytes open as previews
+    fn preview_limit(&self) -> u64 {
+        self.config.preview_threshold_kb.saturating_mul(1024)
+    }
+
+    /// Load the rest of the current file in full, leaving preview mode
+    fn open_in_full(&mut self) {
+        let Some(path) = self.editor_state.file_path.clone() else { return };
+        let indent = self.editor_state.indent;
+        let (row, col) = (self.editor_state.cursor_row, self.editor_state.cursor_col);
+        self.editor_state = EditorState::loading(path.clone());
+        self.editor_state.indent = indent;
+        self.editor_state.goto(row, col);
+        self.notify(format!("Loading all of {}", self.editor_state.display_name()));
+        self.spawn_file_load(path, u64::MAX);
+    }
+
+    /// Read more of a previewed file once the view gets near the end of what is loaded
+    fn load_preview_ahead(&mut self) {
+        let state = &self.editor_state;
+        if state.preview.is_none() || state.scroll_offset.max(state.cursor_row) + PREVIEW_LOOKAHEAD < state.lines.len() {
+            return;
+        }
+        if let Err(e) = self.editor_state.load_more() {
+            self.notify(format!("Could not read more of {}: {}", self.editor_state.display_name(), e));
+            self.editor_state.preview = None;
+        }
+    }
+
+    /// Fill the placeholder buffer for `path`; buffers closed or reused meanwhile are skipped
+    pub fn on_file_loaded(&mut self, path: PathBuf, result: Result<(String, TextEncoding, Option<Preview>), String>) {
+        let Some(idx) = (0..self.buffers.len())
+            .find(|&i| self.buffer(i).loading && self.buffer(i).file_path.as_ref() == Some(&path))
+        else {
+            return;
+        };
+        match result {
+            Ok((content, encoding, preview)) => {
+                self.welcome = None;
+                self.add_recent_file(&path);
+                let indent = self.config.indent_for_contents(Some(&path), &content.lines().collect::<Vec<_>>());
+                let buffer = self.buffer_mut(idx);
+                buffer.set_contents(path, &content);
+                buffer.indent = indent;
+                buffer.encoding = encoding;
+                buffer.set_preview(preview);
+                if preview.is_some() {
+                    let name = buffer.display_name();
+                    let action = self.shortcut_label(Action::ToggleReadOnly)
+                        .unwrap_or_else(|| "File > Toggle Read-Only".to_string());
+                    self.notify(format!("{} is large: read-only preview, loaded as you scroll ({} to open in full)", name, action));
+                }
+                if idx == self.active_buffer && self.find_bar.active {
+                    self.refresh_find();
+                }
+                if idx == self.active_buffer {
+                    self.sync_language_server();
+                }
+            }
+            Err(e) if e == encoding::BINARY_FILE => {
+                self.remove_buffer(idx);
+                self.open_hex_view(path);
+            }
+            Err(e) => {
+                self.notify(format!("Could not open {}: {}", path.display(), e));
+                self.remove_buffer(idx);
+            }
+        }
+    }
+
+    /// Show a file that isn't text as a hex dump in the editor area
+    fn open_hex_view(&mut self, path: PathBuf) {
+        match HexView::open(path.clone()) {
+            Ok(view) => {
+                self.welcome = None;
+                self.diff_view = None;
+                self.hex_view = Some(view);
+                self.active_panel = ActivePanel::Editor;
+                self.notify(format!("{} is a binary file: showing a hex dump", path.display()));
+            }
+            Err(e) => self.notify(format!("Could not open {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Open the recent files switcher; again while it is open moves down the list
+    pub fn open_recent_files(&mut self) {
+        if let Some(recent) = self.recent_files.as_mut() {
+            return recent.select(1);
+        }
+        let current = self.editor_state.file_path.as_deref()
+            .and_then(|p| self.normalize_path(p).strip_prefix(&self.workspace).ok().map(Path::to_path_buf));
+        let recent = RecentFiles::new(&self.config, &self.workspace, current.as_deref());
+        if recent.files.is_empty() {
+            self.notify("No recently opened files in this workspace");
+            return;
+        }
+        self.recent_files = Some(recent);
+    }
+
+    /// Keys of the recent files switcher: typing filters, arrows move, Enter opens
+    pub fn handle_recent_files_key(&mut self, key: KeyEvent) {
+        let Some(recent) = self.recent_files.as_mut() else { return };
+        match key.code {
+            KeyCode::Esc => self.recent_files = None,
+            KeyCode::Enter => self.open_selected_recent_file(),
+            KeyCode::Up => recent.select(-1),
+            KeyCode::Down | KeyCode::Tab => recent.select(1),
+            KeyCode::Char('e') if key.modifiers == KeyModifiers::CONTROL => recent.select(1),
+            KeyCode::Backspace => {
+                let mut query = recent.query.clone();
+                query.pop();
+                recent.set_query(query);
+            }
+            KeyCode::Char(c) if !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+                let query = format!("{}{}", recent.query, c);
+                recent.set_query(query);
+            }
+            _ => {}
+        }
+    }
+
+    /// Mouse over the recent files switcher: a click opens a file, the wheel
+    /// moves the selection and a click outside closes it
+    pub fn handle_recent_files_mouse(&mut self, mouse: MouseEvent, screen: Rect) {
+        let Some(recent) = self.recent_files.as_mut() else { return };
+        let at = Position::new(mouse.column, mouse.row);
+        match mouse.kind {
+            MouseEventKind::ScrollUp => recent.select(-1),
+            MouseEventKind::ScrollDown => recent.select(1),
+            MouseEventKind::Down(MouseButton::Left) if !ui::recent_files_area(screen).contains(at) => {
+                self.recent_files = None;
+            }
+            MouseEventKind::Down(MouseButton::Left) => {
+                let list = ui::recent_files_list_area(screen);
+                let idx = (mouse.row.saturating_sub(list.y)) as usize;
+                if list.contains(at) && idx < recent.matches().len() {
+                    recent.selected = idx;
+                    self.open_selected_recent_file();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn open_selected_recent_file(&mut self) {
+        let Some(file) = self.recent_files.take().and_then(|recent| recent.selected_file()) else { return };
+        self.load_file_path(self.workspace.join(file));
+        self.active_panel = ActivePanel::Editor;
+    }
+
+    /// Remember an opened workspace file for the welcome view and the recent files switcher
+    fn add_recent_file(&mut self, path: &Path) {
+        let Ok(relative) = self.normalize_path(path).strip_prefix(&self.workspace).map(Path::to_path_buf) else { return };
+        self.config.add_recent_file(&self.workspace, relative);
+        self.save_config();
+    }
+
+    /// Whether the editor area shows the welcome view: nothing was opened or typed yet
+    pub fn showing_welcome(&self) -> bool {
+        let state = &self.editor_state;
+        self.welcome.is_some() && self.buffers.len() == 1 && state.file_path.is_none()
+            && !state.is_scratch() && !state.modified
+    }
+
+    /// Arrows move through the welcome items and Enter picks one; returns false for other keys
+    pub fn handle_welcome_key(&mut self, key: KeyEvent) -> bool {
+        let Some(welcome) = self.welcome.as_mut() else { return false };
+        match key.code {
+            KeyCode::Up => welcome.select(-1),
+            KeyCode::Down => welcome.select(1),
+            KeyCode::Enter => {
+                if let Some(item) = welcome.selected_item().cloned() {
+                    self.open_welcome_item(item);
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn open_welcome_item(&mut self, item: WelcomeItem) {
+        match item {
+            WelcomeItem::Terminal => {
+                self.terminal_tab = TerminalTab::Shell;
+                self.active_panel = ActivePanel::Terminal;
+            }
+            WelcomeItem::File(path) => self.load_file_path(self.workspace.join(path)),
+            WelcomeItem::Workspace(path) => {
+                self.notify(format!("Browsing {}", path.display()));
+                self.file_tree_root = path;
+                self.selected_file_idx = 0;
+                self.file_tree_scroll_offset = 0;
+                self.refresh_file_tree();
+                self.active_panel = ActivePanel::FileTree;
+            }
+        }
+    }
+
+    /// Change the terminal's directory to the one holding the current file
+    pub fn terminal_cd_to_file(&mut self) -> Result<(), String> {
+        let dir = self.editor_state.file_path.as_deref().and_then(Path::parent)
+            .ok_or("The current buffer has no file")?;
+        let dir = if dir.as_os_str().is_empty() { self.workspace.clone() } else { self.workspace.join(dir) };
+        self.write_to_pty(terminal::cd_command(&dir).as_bytes())?;
+        self.terminal_tab = TerminalTab::Shell;
+        Ok(())
+    }
+
+    /// Root the file tree at the terminal's current directory; the workspace root shows as before
+    pub fn file_tree_from_terminal(&mut self) {
+        let Some(cwd) = self.shell_pid.and_then(terminal::process_cwd) else {
+            self.notify("Could not determine the terminal's directory");
+            return;
+        };
+        self.file_tree_root = if cwd == self.workspace { PathBuf::from(".") } else { cwd };
+        self.selected_file_idx = 0;
+        self.file_tree_scroll_offset = 0;
+        self.refresh_file_tree();
+        self.active_panel = ActivePanel::FileTree;
+    }
+
+    /// Apply the config's syntax overrides, syntax theme, highlighter and indentation to every open buffer
+    fn apply_language_settings(&mut self) {
+        syntax::set_overrides(&self.config.syntax_overrides);
+        syntax::set_theme(syntax::theme_name(self.config.theme, self.config.syntax_theme.as_deref()));
+        self.apply_highlighter();
+        let active = self.active_buffer;
+        let buffers = self.buffers.iter_mut().enumerate()
+            .filter(|(i, _)| *i != active)
+            .map(|(_, b)| b)
+            .chain(std::iter::once(&mut self.editor_state));
+        for buffer in buffers {
+            buffer.indent = self.config.indent_for_contents(buffer.file_path.as_deref(), &buffer.lines);
+            buffer.refresh_highlighting();
+        }
+    }
+
+    /// Switch to the configured highlighting backend, saying so when this
+    /// build doesn't have it
+    fn apply_highlighter(&mut self) {
+        if highlight::set_backend(self.config.highlighter) != self.config.highlighter {
+            self.notify("tree-sitter highlighting needs a build with --features tree-sitter; using syntect");
+        }
+    }
+
+    /// Index of the open buffer showing `path`
+    fn find_buffer(&self, path: &Path) -> Option<usize> {
+        let target = fs::canonicalize(path).ok()?;
+        (0..self.buffers.len()).find(|&i| {
+            self.buffer(i).file_path.as_ref()
+                .is_some_and(|p| fs::canonicalize(p).ok().as_ref() == Some(&target))
+        })
+    }
+
+    fn buffer(&self, idx: usize) -> &EditorState {
+        if idx == self.active_buffer { &self.editor_state } else { &self.buffers[idx] }
+    }
+
+    fn buffer_mut(&mut self, idx: usize) -> &mut EditorState {
+        if idx == self.active_buffer { &mut self.editor_state } else { &mut self.buffers[idx] }
+    }
+
+    /// Tab labels in order, with a `[+]` marker for unsaved buffers
+    pub fn buffer_labels(&self) -> Vec<String> {
+        (0..self.buffers.len())
+            .map(|i| {
+                let buffer = self.buffer(i);
+                let name = buffer.display_name();
+                let name = if buffer.read_only { format!("{} [RO]", name) } else { name };
+                if buffer.modified && !buffer.is_scratch() { format!("{} [+]", name) } else { name }
+            })
+            .collect()
+    }
+
+    /// Make the buffer at `idx` the one shown in the editor
+    pub fn switch_buffer(&mut self, idx: usize) {
+        if idx >= self.buffers.len() || idx == self.active_buffer {
+            return;
+        }
+        std::mem::swap(&mut self.editor_state, &mut self.buffers[self.active_buffer]);
+        std::mem::swap(&mut self.editor_state, &mut self.buffers[idx]);
+        self.active_buffer = idx;
+        self.active_panel = ActivePanel::Editor;
+        self.hex_view = None;
+        if self.find_bar.active {
+            self.refresh_find();
+        }
+    }
+
+    pub fn cycle_buffer(&mut self, forward: bool) {
+        let len = self.buffers.len();
+        let idx = if forward { (self.active_buffer + 1) % len } else { (self.active_buffer + len - 1) % len };
+        self.switch_buffer(idx);
+    }
+
+    /// Open a new empty scratch buffer in its own tab
+    pub fn new_scratch_buffer(&mut self) {
+        self.open_scratch_with(Vec::new());
+    }
+
+    /// Open a new scratch buffer holding `lines`
+    fn open_scratch_with(&mut self, lines: Vec<String>) {
+        let names: Vec<String> = (0..self.buffers.len())
+            .filter_map(|i| self.buffer(i).scratch_name.clone())
+            .collect();
+        let name = scratch::next_name(names.iter().map(String::as_str));
+        self.buffers.push(EditorState::scratch(name, lines));
+        self.switch_buffer(self.buffers.len() - 1);
+    }
+
+    /// Close the current buffer, asking first if it has unsaved changes
+    pub fn close_buffer(&mut self) {
+        if self.editor_state.modified && !self.editor_state.is_scratch() {
+            self.unsaved_prompt = Some(UnsavedPrompt::CloseBuffer);
+            return;
+        }
+        self.remove_buffer(self.active_buffer);
+    }
+
+    /// Close every file buffer (Close All), asking first if any has unsaved
+    /// changes; scratch buffers stay open
+    pub fn close_all_buffers(&mut self) {
+        if !self.unsaved_buffers().is_empty() {
+            self.unsaved_prompt = Some(UnsavedPrompt::CloseAll);
+            return;
+        }
+        self.remove_file_buffers();
+    }
+
+    fn remove_file_buffers(&mut self) {
+        let files: Vec<usize> = (0..self.buffers.len()).filter(|&i| !self.buffer(i).is_scratch()).collect();
+        for &idx in files.iter().rev() {
+            self.remove_buffer(idx);
+        }
+        self.notify(format!("Closed {} buffer{}", files.len(), if files.len() == 1 { "" } else { "s" }));
+    }
+
+    /// Reload the current file from disk (Revert File), asking first if that
+    /// would drop unsaved changes
+    pub fn revert_buffer(&mut self) {
+        let Some(path) = self.editor_state.file_path.as_deref() else {
+            self.notify("The current buffer has no file to revert to");
+            return;
+        };
+        if !path.exists() {
+            self.notify(format!("{} is not on disk", path.display()));
+            return;
+        }
+        if self.editor_state.modified {
+            self.unsaved_prompt = Some(UnsavedPrompt::Revert);
+            return;
+        }
+        self.reload_buffer(self.active_buffer);
+    }
+
+    /// Quit, asking first if any buffer has unsaved changes
+    pub fn request_quit(&mut self) {
+        if self.unsaved_buffers().is_empty() {
+            self.should_quit = true;
+        } else {
+            self.unsaved_prompt = Some(UnsavedPrompt::Quit);
+        }
+    }
+
+    /// Buffers whose edits would be lost; scratch buffers are kept across sessions
+    pub fn unsaved_buffers(&self) -> Vec<usize> {
+        (0..self.buffers.len())
+            .filter(|&i| self.buffer(i).modified && !self.buffer(i).is_scratch())
+            .collect()
+    }
+
+    /// Names of the buffers the unsaved changes prompt is about
+    pub fn unsaved_prompt_names(&self) -> Vec<String> {
+        match self.unsaved_prompt {
+            Some(UnsavedPrompt::Quit | UnsavedPrompt::CloseAll) => self.unsaved_buffers().into_iter().map(|i| self.buffer(i).display_name()).collect(),
+            Some(UnsavedPrompt::CloseBuffer | UnsavedPrompt::Revert) => vec![self.editor_state.display_name()],
+            None => Vec::new(),
+        }
+    }
+
+    /// Unsaved changes prompt: save the buffers, then quit or close. Untitled
+    /// buffers get the Save As prompt instead, which cancels quitting.
+    pub fn unsaved_save(&mut self) {
+        let Some(prompt) = self.unsaved_prompt.take() else { return };
+        let targets = match prompt {
+            UnsavedPrompt::Quit | UnsavedPrompt::CloseAll => self.unsaved_buffers(),
+            UnsavedPrompt::CloseBuffer => vec![self.active_buffer],
+            // Saving first would leave nothing to revert
+            UnsavedPrompt::Revert => {
+                self.unsaved_prompt = Some(prompt);
+                return;
+            }
+        };
+        for idx in targets {
+            if self.buffer(idx).file_path.is_some() {
+                self.switch_buffer(idx);
+                self.save_current_buffer();
+            }
+        }
+        let left = match prompt {
+            UnsavedPrompt::CloseBuffer => Some(self.active_buffer).filter(|_| self.editor_state.modified).into_iter().collect(),
+            _ => self.unsaved_buffers(),
+        };
+        let Some(&first) = left.first() else {
+            match prompt {
+                UnsavedPrompt::Quit => self.should_quit = true,
+                UnsavedPrompt::CloseBuffer => self.remove_buffer(self.active_buffer),
+                UnsavedPrompt::CloseAll => self.remove_file_buffers(),
+                UnsavedPrompt::Revert => {}
+            }
+            return;
+        };
+        self.switch_buffer(first);
+        if self.editor_state.file_path.is_none() {
+            self.open_save_as();
+        }
+    }
+
+    /// Unsaved changes prompt: drop the edits and quit or close anyway
+    pub fn unsaved_discard(&mut self) {
+        match self.unsaved_prompt.take() {
+            Some(UnsavedPrompt::Quit) => self.should_quit = true,
+            Some(UnsavedPrompt::CloseBuffer) => {
+                recovery::remove_backup(self.editor_state.file_path.as_deref(), self.editor_state.backup_id, &self.workspace);
+                self.remove_buffer(self.active_buffer);
+            }
+            Some(UnsavedPrompt::CloseAll) => {
+                for idx in self.unsaved_buffers() {
+                    let buffer = self.buffer(idx);
+                    recovery::remove_backup(buffer.file_path.as_deref(), buffer.backup_id, &self.workspace);
+                }
+                self.remove_file_buffers();
+            }
+            Some(UnsavedPrompt::Revert) => {
+                recovery::remove_backup(self.editor_state.file_path.as_deref(), self.editor_state.backup_id, &self.workspace);
+                self.reload_buffer(self.active_buffer);
+            }
+            None => {}
+        }
+    }
+
+    /// Drop the buffer at `idx`, switching away first if it is the active one
+    fn remove_buffer(&mut self, idx: usize) {
+        if self.buffers.len() == 1 {
+            self.editor_state = EditorState::new();
+            return;
+        }
+        if idx == self.active_buffer {
+            let next = if idx + 1 < self.buffers.len() { idx + 1 } else { idx - 1 };
+            self.switch_buffer(next);
+        }
+        self.buffers.remove(idx);
+        if self.active_buffer > idx {
+            self.active_buffer -= 1;
+        }
+    }
+
+    /// Scratch buffers kept by the last session, including ones an older version saved
+    fn stored_scratch_buffers(&self) -> Vec<ScratchBuffer> {
+        let stored = self.storage.as_ref()
+            .and_then(|storage| storage.load_scratch(&self.workspace).ok())
+            .unwrap_or_default();
+        if stored.is_empty() { scratch::take_legacy(&self.workspace) } else { stored }
+    }
+
+    /// Scratch buffers in tab order, as they are persisted
+    fn scratch_buffers(&self) -> Vec<ScratchBuffer> {
+        (0..self.buffers.len())
+            .map(|i| self.buffer(i))
+            .filter_map(|b| b.scratch_name.clone().map(|name| ScratchBuffer { name, lines: b.lines.clone() }))
+            .collect()
+    }
+
+    pub fn on_search_input(&mut self) {
+        let query = self.search_input.lines().join(" ");
+        if query.trim().is_empty() {
+            self.search_results.clear();
+            return;
+        }
+        
+        self.search_results = search::find_files(Path::new("."), &query, 20);
+        self.search_state.select(Some(0));
+    }
+
+    /// Open the selected file search result and close the modal
+    pub fn open_search_result(&mut self) {
+        let Some(path) = self.search_state.selected().and_then(|idx| self.search_results.get(idx)).cloned() else { return };
+        self.load_file_path(path);
+        self.active_panel = ActivePanel::Editor;
+        self.is_searching = false;
+    }
+
+    /// Mouse in the file search modal: a click opens a result, the wheel moves
+    /// the selection and a click outside closes the modal
+    pub fn handle_file_search_mouse(&mut self, mouse: MouseEvent, screen: Rect) {
+        let at = Position::new(mouse.column, mouse.row);
+        let last = self.search_results.len().saturating_sub(1);
+        let selected = self.search_state.selected().unwrap_or(0);
+        match mouse.kind {
+            MouseEventKind::ScrollUp => self.search_state.select(Some(selected.saturating_sub(1))),
+            MouseEventKind::ScrollDown => self.search_state.select(Some((selected + 1).min(last))),
+            MouseEventKind::Down(MouseButton::Left) if !ui::file_search_area(screen).contains(at) => {
+                self.is_searching = false;
+            }
+            MouseEventKind::Down(MouseButton::Left) => {
+                let results = ui::file_search_results_area(screen);
+                if results.contains(at) {
+                    let idx = self.search_state.offset() + (mouse.row - results.y) as usize;
+                    if idx < self.search_results.len() {
+                        self.search_state.select(Some(idx));
+                        self.open_search_result();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    
+    pub fn open_search_palette(&mut self) {
+        self.search_palette = Some(SearchPalette::new());
+    }
+
+    /// Search the lines of the open buffers as they are in the editor (Alt+G)
+    pub fn open_buffer_search(&mut self) {
+        self.search_palette = Some(SearchPalette::buffers());
+    }
+
+    /// Search chat and terminal history for the palette's query right away,
+    /// and the workspace in the background; or only the open buffers
+    pub fn on_search_palette_input(&mut self) {
+        let Some(palette) = self.search_palette.as_ref() else { return };
+        let query = palette.query();
+        if palette.buffers_only {
+            let buffers = (0..self.buffers.len()).map(|i| (self.buffer(i).display_name(), self.buffer(i).lines.as_slice()));
+            let hits = global_search::search_buffers(buffers, &query, search_palette::MAX_RESULTS);
+            if let Some(palette) = self.search_palette.as_mut() {
+                palette.set_query_hits(hits);
+            }
+            return;
+        }
+        let terminal: Vec<String> = self.history_buffer.read()
+            .map(|buffer| scrollback::history_lines(&buffer).into_iter().map(|line| line.text).collect())
+            .unwrap_or_default();
+        let local = [global_search::search_chat(&self.chat_history, &query), global_search::search_terminal(&terminal, &query)].concat();
+        let Some(palette) = self.search_palette.as_mut() else { return };
+        let generation = palette.set_query_hits(local);
+        // Single characters match nearly every line of every file
+        if query.trim().chars().count() < 2 {
+            return;
+        }
+        let (tx, root) = (self.event_tx.clone(), self.workspace.clone());
+        thread::spawn(move || {
+            let hits = global_search::search_workspace(&root, &query, search_palette::MAX_RESULTS);
+            let _ = tx.send(AppEvent::WorkspaceSearched { generation, hits });
+        });
+    }
+
+    pub fn on_workspace_searched(&mut self, generation: u64, hits: Vec<SearchHit>) {
+        if let Some(palette) = self.search_palette.as_mut() {
+            palette.set_workspace_hits(generation, hits);
+        }
+    }
+
+    /// Keys for the search palette
+    pub fn handle_search_palette_key(&mut self, key: KeyEvent) {
+        let Some(palette) = self.search_palette.as_mut() else { return };
+        match key.code {
+            KeyCode::Esc => self.search_palette = None,
+            KeyCode::Up => palette.select(-1),
+            KeyCode::Down => palette.select(1),
+            KeyCode::PageUp => palette.select(-10),
+            KeyCode::PageDown => palette.select(10),
+            KeyCode::Enter => {
+                if let Some(target) = palette.selected().map(|hit| hit.target.clone()) {
+                    self.search_palette = None;
+                    self.open_search_target(target);
+                }
+            }
+            _ => {
+                if palette.input.input(key) {
+                    self.on_search_palette_input();
+                }
+            }
+        }
+    }
+
+    /// Show a search result in its panel: files in the editor, chat messages in
+    /// the chat, and terminal lines in a scratch buffer of the terminal history
+    fn open_search_target(&mut self, target: SearchTarget) {
+        self.zen_mode = false;
+        match target {
+            SearchTarget::File(path) => self.open_location(&path, 1, 1),
+            SearchTarget::Line { path, line } => self.open_location(&path, line + 1, 1),
+            SearchTarget::Buffer { index, line, col } => {
+                self.switch_buffer(index);
+                self.editor_state.goto(line, col);
+                self.active_panel = ActivePanel::Editor;
+            }
+            SearchTarget::Chat(index) => {
+                self.chat_scroll_to = Some(index);
+                self.active_panel = ActivePanel::Chat;
+            }
+            SearchTarget::Terminal(line) => {
+                let lines: Vec<String> = match self.history_buffer.read() {
+                    Ok(buffer) => scrollback::history_lines(&buffer).into_iter().map(|line| line.text).collect(),
+                    Err(_) => return,
+                };
+                self.open_scratch_with(lines);
+                self.editor_state.goto(line, 0);
+                self.active_panel = ActivePanel::Editor;
+            }
+        }
+    }
+
+    pub fn cycle_model(&mut self) {
+        self.config.cycle_model();
+        self.save_config();
+    }
+
+    pub fn get_selected_model_name(&self) -> String {
+        self.config.get_selected_model().display_name()
+    }
+
+    /// List the context presets to switch to, the active one selected
+    pub fn open_preset_picker(&mut self) {
+        let active = self.config.active_preset.as_deref();
+        let row = self.config.context_presets.iter().position(|p| Some(p.name.as_str()) == active).map_or(0, |i| i + 1);
+        self.preset_picker = Some(row);
+    }
+
+    /// Keys of the preset picker: arrows move, Enter switches, Esc closes
+    pub fn handle_preset_picker_key(&mut self, key: KeyEvent) {
+        let Some(row) = self.preset_picker else { return };
+        let last = self.config.context_presets.len();
+        match key.code {
+            KeyCode::Esc => self.preset_picker = None,
+            KeyCode::Up => self.preset_picker = Some(row.saturating_sub(1)),
+            KeyCode::Down => self.preset_picker = Some((row + 1).min(last)),
+            KeyCode::Enter => {
+                self.preset_picker = None;
+                self.switch_preset(row.checked_sub(1));
+            }
+            _ => {}
+        }
+    }
+
+    /// Use the preset at `idx` (none for `None`) for the next chat messages,
+    /// selecting its model if it names one
+    fn switch_preset(&mut self, idx: Option<usize>) {
+        let Some(preset) = idx.and_then(|i| self.config.context_presets.get(i)).cloned() else {
+            self.config.active_preset = None;
+            self.save_config();
+            self.notify("Chat preset off");
+            return;
+        };
+        self.config.active_preset = Some(preset.name.clone());
+        match (preset.model_index(&self.config.models), &preset.model) {
+            (Some(model), _) => {
+                self.config.selected_model_idx = model;
+                self.notify(format!("Chat preset: {}", preset.summary()));
+            }
+            (None, Some(name)) => self.notify(format!("Chat preset: {} (model `{}` is not configured; keeping the current one)", preset.name, name)),
+            (None, None) => self.notify(format!("Chat preset: {}", preset.summary())),
+        }
+        self.save_config();
+    }
+
+    /// Context sections the active preset attaches to a chat message, apart
+    /// from the workspace summary, which is collected off the UI thread
+    fn preset_context(&self) -> Vec<(String, String)> {
+        let Some(preset) = self.config.active_preset() else { return Vec::new() };
+        let name = self.editor_state.display_name();
+        let mut sections = Vec::new();
+        for rule in &preset.context {
+            match rule {
+                ContextRule::WorkspaceSummary => {}
+                ContextRule::OpenFile if self.editor_state.file_path.is_some() => {
+                    sections.push((format!("Open file {}", name), self.editor_state.lines.join("\n")));
+                }
+                ContextRule::Selection => {
+                    if let Some((start, end)) = self.vim_selection().or_else(|| self.editor_state.selection()) {
+                        sections.push((format!("Selected in {}", name), self.editor_state.text_range(start, end)));
+                    }
+                }
+                ContextRule::Problems => {
+                    let problems: Vec<String> = self.current_diagnostics().iter()
+                        .map(|d| format!("{}:{}: {}: {}", d.line, d.column, d.severity.label(), d.message))
+                        .collect();
+                    sections.push((format!("Problems in {}", name), problems.join("\n")));
+                }
+                _ => {}
+            }
+        }
+        sections
+    }
+    
+    pub fn toggle_theme(&mut self) {
+        use crate::shared::ThemeMode;
+        self.config.theme = match self.config.theme {
+            ThemeMode::Light => ThemeMode::Dark,
+            ThemeMode::Dark => ThemeMode::Light,
+        };
+        self.save_config();
+        self.apply_theme();
+        // The editor's syntax theme follows unless one is configured
+        self.apply_language_settings();
+    }
+
+    /// Re-create the theme from the config and reset the terminal colors
+    fn apply_theme(&mut self) {
+        self.current_theme = Theme::new(self.config.theme);
+
+        // Reset the vt100 parser to apply new default colors
+        if let Ok(mut parser) = self.terminal_screen.write() {
+            let (rows, cols) = {
+                let size = parser.screen().size();
+                (size.0, size.1)
+            };
+            // Create new parser with same dimensions - this resets colors
+            *parser = tui_term::vt100::Parser::new(rows, cols, TERMINAL_SCROLLBACK);
+        }
+
+        // Send escape codes to reset terminal and re-run prompt
+        // \x1b[0m - Reset all attributes
+        // \x1b[2J - Clear screen
+        // \x1b[H - Move cursor to home
+        let reset_seq = "\x1b[0m\x1b[2J\x1b[H";
+        if let Err(e) = self.write_to_pty(reset_seq.as_bytes()) {
+            self.notify(e);
+        }
+    }
+
+    pub fn send_chat_message(&mut self, content: String) {
+        if content.trim() == "/summarize" {
+            return self.summarize_chat();
+        }
+        let mut sections = self.preset_context();
+        let summary = self.config.active_preset().is_some_and(|p| p.context.contains(&ContextRule::WorkspaceSummary));
+        if !summary {
+            let prompt = presets::with_context(&content, &sections);
+            return self.send_ai_request(AiRequest::Chat { message: content, prompt });
+        }
+        // The summary walks the workspace; the prompt is checked once it is ready
+        let tx = self.event_tx.clone();
+        let root = self.workspace.clone();
+        tokio::task::spawn_blocking(move || {
+            sections.insert(0, ("Workspace summary".to_string(), stats::collect(&root).to_context()));
+            let prompt = presets::with_context(&content, &sections);
+            let _ = tx.send(AppEvent::ChatPrepared { message: content, prompt });
+        });
+    }
+
+    pub fn on_chat_prepared(&mut self, message: String, prompt: String) {
+        self.send_ai_request(AiRequest::Chat { message, prompt });
+    }
+
+    /// Send `request`, unless its prompt looks like it contains secrets; then
+    /// it waits for the secret warning to be answered
+    fn send_ai_request(&mut self, request: AiRequest) {
+        let findings = secrets::scan(request.prompt(), &self.config.secret_allowlist);
+        if !findings.is_empty() {
+            self.secret_findings = findings;
+            self.pending_ai = Some(request);
+            self.show_secret_warning = true;
+            return;
+        }
+        self.dispatch_ai_request(request);
+    }
+
+    fn dispatch_ai_request(&mut self, request: AiRequest) {
+        match request {
+            AiRequest::Chat { message, prompt } => self.dispatch_chat_message(message, prompt),
+            AiRequest::Review { path, lines, prompt } => self.dispatch_review(path, lines, prompt),
+            AiRequest::Refactor { occurrences, prompt } => self.dispatch_refactor(occurrences, prompt),
+        }
+    }
+
+    /// Send (`send`) the request held back by the secret warning, or drop it;
+    /// a dropped chat message goes back into the chat input for editing
+    pub fn resolve_secret_warning(&mut self, send: bool) {
+        self.show_secret_warning = false;
+        self.secret_findings.clear();
+        let Some(request) = self.pending_ai.take() else { return };
+        if send {
+            self.dispatch_ai_request(request);
+            return;
+        }
+        match request {
+            AiRequest::Chat { message, .. } => {
+                self.set_chat_input(&message);
+                self.active_panel = ActivePanel::Chat;
+            }
+            AiRequest::Review { .. } => {
+                self.review_pending = false;
+                self.notify("Review cancelled");
+            }
+            AiRequest::Refactor { .. } => {
+                self.refactor_pending = false;
+                self.notify("Refactor cancelled");
+            }
+        }
+    }
+
+    /// Show `message` in the chat and send `prompt`, the message with its context
+    fn dispatch_chat_message(&mut self, message: String, content: String) {
+        self.push_chat(ChatMessage::user(message));
+
+        let tx = self.event_tx.clone();
+        let model_config = match self.config.active_preset() {
+            Some(preset) => preset.apply(self.config.get_selected_model()),
+            None => self.config.get_selected_model().clone(),
+        };
+        let history: Vec<String> = self.chat_history.iter().map(ChatMessage::transcript_line).collect();
+        let root = self.workspace.clone();
+        let sandbox = self.config.ai_commands.clone();
+
+        tokio::spawn(async move {
+            let mut tools = vec![tools::create_file_tool()];
+            if sandbox.enabled {
+                tools.push(tools::run_command_tool());
+            }
+            let mut input = content.clone();
+            let mut response = String::new();
+            let mut proposals = Vec::new();
+            let mut usage: Option<TokenUsage> = None;
+            for round in 0..=MAX_COMMAND_ROUNDS {
+                let reply = match send_message_with_tools(&model_config, &history, &input, &tools).await {
+                    Ok(reply) => reply,
+                    Err(e) => {
+                        response.push_str(&format!("\nError: {}", e));
+                        break;
+                    }
+                };
+                usage = match (usage, reply.usage) {
+                    (Some(a), Some(b)) => Some(TokenUsage { input: a.input + b.input, output: a.output + b.output }),
+                    (a, b) => a.or(b),
+                };
+                response.push_str(&reply.text);
+
+                // Unsafe proposals and commands are reported in the chat instead of being staged or run
+                let mut results = Vec::new();
+                for call in reply.tool_calls {
+                    if let Some(proposal) = file_proposal::from_tool_call(&root, &call) {
+                        match proposal {
+                            Ok(proposal) => proposals.push(proposal),
+                            Err(e) => response.push_str(&format!("\n[blocked create_file: {}]", e)),
+                        }
+                        continue;
+                    }
+                    let (root, sandbox) = (root.clone(), sandbox.clone());
+                    let name = call.name.clone();
+                    let ran = tokio::task::spawn_blocking(move || sandbox::from_tool_call(&root, &call, &sandbox)).await;
+                    match ran.ok().flatten() {
+                        Some(Ok(output)) => {
+                            response.push_str(&format!("\n{}", output.summary()));
+                            results.push(output.for_model());
+                        }
+                        Some(Err(e)) => {
+                            response.push_str(&format!("\n[blocked run_command: {}]", e));
+                            results.push(format!("run_command failed: {}", e));
+                        }
+                        None => response.push_str(&format!("\n[ignored unknown tool call: {}]", name)),
+                    }
+                }
+                if results.is_empty() {
+                    break;
+                }
+                if round == MAX_COMMAND_ROUNDS {
+                    response.push_str("\n[stopped: too many command rounds]");
+                    break;
+                }
+                // The model sees the output along with the original request
+                input = format!("{}\n\nOutput of the commands you ran:\n\n{}", content, results.join("\n\n"));
+                response.push('\n');
+            }
+            if !proposals.is_empty() {
+                response.push_str(&format!("\n[{} new file(s) waiting for approval]", proposals.len()));
+            }
+
+            let _ = tx.send(AppEvent::AiResponse(ChatMessage::assistant(response.trim(), model_config.name, usage)));
+            if !proposals.is_empty() {
+                let _ = tx.send(AppEvent::FilesProposed(proposals));
+            }
+        });
+    }
+
+    /// Ask the model to compress all but the last few messages into a summary
+    pub fn summarize_chat(&mut self) {
+        if self.chat_summarizing {
+            return;
+        }
+        let count = self.chat_history.len().saturating_sub(chat::KEEP_RECENT);
+        if count < 2 {
+            self.notify("Nothing to summarize yet");
+            return;
+        }
+        self.chat_summarizing = true;
+        self.notify(format!("Summarizing {} messages...", count));
+
+        let tx = self.event_tx.clone();
+        let model_config = self.config.get_selected_model().clone();
+        let prompt = chat::summary_prompt(&self.chat_history[..count]);
+        tokio::spawn(async move {
+            let result = send_message(&model_config, &[], &prompt).await;
+            let _ = tx.send(AppEvent::ChatSummarized { count, result });
+        });
+    }
+
+    /// Send what is in the chat input and remember it in the prompt history
+    pub fn submit_chat_input(&mut self) {
+        let content = self.chat_input.lines().join("\n");
+        if content.trim().is_empty() {
+            return;
+        }
+        self.prompt_history.push(&content);
+        self.send_chat_message(content);
+        self.set_chat_input("");
+        // Auto-scroll to bottom on new message
+        self.chat_scroll = u16::MAX;
+    }
+
+    /// Replace the chat input, with the cursor at the end
+    fn set_chat_input(&mut self, text: &str) {
+        self.chat_input = TextArea::new(text.split('\n').map(str::to_string).collect());
+        self.chat_input.move_cursor(tui_textarea::CursorMove::Bottom);
+        self.chat_input.move_cursor(tui_textarea::CursorMove::End);
+    }
+
+    /// Prompt history keys of the chat input: Up in an empty input recalls the
+    /// last prompt and Up/Down then step through older and newer ones; Ctrl+R
+    /// starts a reverse search. Returns whether the key was used.
+    pub fn handle_prompt_history_key(&mut self, key: KeyEvent) -> bool {
+        if self.prompt_search.is_some() {
+            self.handle_prompt_search_key(key);
+            return true;
+        }
+        let empty = self.chat_input.lines().iter().all(String::is_empty);
+        match key.code {
+            KeyCode::Char('r') if key.modifiers == KeyModifiers::CONTROL => {
+                let draft = self.chat_input.lines().join("\n");
+                self.prompt_search = Some(PromptSearch { draft, ..Default::default() });
+                true
+            }
+            KeyCode::Up if key.modifiers.is_empty() && (empty || self.prompt_history.is_browsing()) => {
+                let Some(prompt) = self.prompt_history.older().map(str::to_string) else { return false };
+                self.set_chat_input(&prompt);
+                true
+            }
+            KeyCode::Down if key.modifiers.is_empty() && self.prompt_history.is_browsing() => {
+                let prompt = self.prompt_history.newer().unwrap_or_default().to_string();
+                self.set_chat_input(&prompt);
+                true
+            }
+            _ => {
+                // Editing a recalled prompt makes it a draft of its own
+                self.prompt_history.reset();
+                false
+            }
+        }
+    }
+
+    /// Keys while reverse searching: typing narrows the search, Ctrl+R finds an
+    /// older match, Enter keeps the match for editing and Esc restores the input
+    fn handle_prompt_search_key(&mut self, key: KeyEvent) {
+        let Some(search) = self.prompt_search.as_mut() else { return };
+        let newest = self.prompt_history.len();
+        match key.code {
+            KeyCode::Char('r') if key.modifiers == KeyModifiers::CONTROL => {
+                let before = search.found.unwrap_or(newest);
+                search.found = self.prompt_history.search(&search.query, before).or(search.found);
+            }
+            KeyCode::Char(c) if !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+                search.query.push(c);
+                search.found = self.prompt_history.search(&search.query, newest);
+            }
+            KeyCode::Backspace => {
+                search.query.pop();
+                search.found = (!search.query.is_empty()).then(|| self.prompt_history.search(&search.query, newest)).flatten();
+            }
+            KeyCode::Enter | KeyCode::Right | KeyCode::End => {
+                if let Some(idx) = search.found {
+                    self.prompt_history.select(idx);
+                }
+                self.prompt_search = None;
+                return;
+            }
+            // Plain letters were taken above, so this is Ctrl+G
+            KeyCode::Esc | KeyCode::Char('g') => {
+                let draft = std::mem::take(&mut search.draft);
+                self.prompt_search = None;
+                self.set_chat_input(&draft);
+                return;
+            }
+            _ => return,
+        }
+        let shown = match search.found {
+            Some(idx) => self.prompt_history.get(idx).unwrap_or_default().to_string(),
+            None => search.draft.clone(),
+        };
+        self.set_chat_input(&shown);
+    }
+
+    /// Title of the chat input during a reverse search, e.g. ``reverse-i-search `parse': ``
+    pub fn prompt_search_title(&self) -> Option<String> {
+        let search = self.prompt_search.as_ref()?;
+        let failed = if search.found.is_none() && !search.query.is_empty() { "failed " } else { "" };
+        Some(format!(" {}reverse-i-search `{}' (Enter: Keep, Esc: Cancel) ", failed, search.query))
+    }
+
+    /// Show the newest folded message in full; once none are left, fold them all again
+    pub fn toggle_chat_fold(&mut self) {
+        let folded = (0..self.chat_history.len()).rev()
+            .find(|i| self.chat_history[*i].is_long() && !self.chat_expanded.contains(i));
+        match folded {
+            Some(i) => {
+                self.chat_expanded.insert(i);
+            }
+            None => self.chat_expanded.clear(),
+        }
+    }
+
+    pub fn on_chat_summarized(&mut self, count: usize, result: Result<String, String>) {
+        self.chat_summarizing = false;
+        match result {
+            Ok(summary) if !summary.trim().is_empty() => {
+                chat::replace_with_summary(&mut self.chat_history, count, &summary);
+                self.chat_expanded.clear();
+                self.notify(format!("Replaced {} messages with a summary", count));
+            }
+            Ok(_) => self.notify("Summarize failed: the model returned nothing"),
+            Err(e) => self.notify(format!("Summarize failed: {}", e)),
+        }
+    }
+
+    /// Stage files proposed by the AI for review
+    pub fn on_files_proposed(&mut self, proposals: Vec<FileProposal>) {
+        self.file_proposals.extend(proposals);
+        self.show_proposals = true;
+        self.proposal_scroll = 0;
+    }
+
+    /// Create (`approve`) or drop the selected proposal and move on to the next one
+    pub fn resolve_proposal(&mut self, approve: bool) {
+        if self.proposal_selected >= self.file_proposals.len() {
+            return;
+        }
+        let proposal = self.file_proposals.remove(self.proposal_selected);
+        if approve {
+            match file_proposal::write(&self.workspace, &proposal) {
+                Ok(_) => {
+                    self.notify(format!("Created {}", proposal.path.display()));
+                    self.refresh_file_tree();
+                }
+                Err(e) => self.notify(format!("Not created: {}", e)),
+            }
+        }
+        self.proposal_selected = self.proposal_selected.min(self.file_proposals.len().saturating_sub(1));
+        self.proposal_scroll = 0;
+        if self.file_proposals.is_empty() {
+            self.show_proposals = false;
+        }
+    }
+
+    pub fn cycle_proposal(&mut self, forward: bool) {
+        let len = self.file_proposals.len();
+        if len == 0 {
+            return;
+        }
+        self.proposal_selected = if forward { (self.proposal_selected + 1) % len } else { (self.proposal_selected + len - 1) % len };
+        self.proposal_scroll = 0;
+    }
+
+    pub fn discard_proposals(&mut self) {
+        self.file_proposals.clear();
+        self.proposal_selected = 0;
+        self.show_proposals = false;
+    }
+
+    /// Look for backups left by a session that did not exit cleanly
+    fn check_recovery(&mut self) {
+        self.recovery_backups = recovery::load_backups(&self.workspace);
+        if !self.recovery_backups.is_empty() {
+            self.show_recovery = true;
+            self.recovery_state.select(Some(0));
+        }
+    }
+
+    /// Show the setup panel the first time a workspace with a project is opened
+    fn suggest_project_setup(&mut self) {
+        let Some(project) = project::detect(&self.workspace) else { return };
+        if self.config.take_project_setup(&self.workspace) {
+            self.save_config();
+            self.project_setup = Some(ProjectSetup::new(project));
+        }
+    }
+
+    /// Show the setup panel for the workspace's project again
+    pub fn open_project_setup(&mut self) {
+        match project::detect(&self.workspace) {
+            Some(project) => self.project_setup = Some(ProjectSetup::new(project)),
+            None => self.notify("No Cargo, npm or Python project found in the workspace"),
+        }
+    }
+
+    pub fn handle_project_setup_key(&mut self, key: KeyEvent) {
+        let Some(setup) = self.project_setup.as_mut() else { return };
+        match key.code {
+            KeyCode::Esc => self.project_setup = None,
+            KeyCode::Up => setup.select(-1),
+            KeyCode::Down => setup.select(1),
+            KeyCode::Enter => {
+                let Some(item) = setup.selected_item().cloned() else { return };
+                match item {
+                    SetupItem::Command { label, command } => {
+                        if let Err(e) = self.write_to_pty(format!("{}\r", command).as_bytes()) {
+                            self.notify(format!("Could not run {}: {}", label, e));
+                            return;
+                        }
+                        self.project_setup = None;
+                        self.terminal_tab = TerminalTab::Shell;
+                        self.active_panel = ActivePanel::Terminal;
+                    }
+                    SetupItem::Summarize => {
+                        let prompt = setup.project.summary_prompt(&self.workspace);
+                        self.project_setup = None;
+                        self.send_chat_message(prompt);
+                        self.active_panel = ActivePanel::Chat;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Periodic work driven by the tick thread
+    pub fn on_tick(&mut self) {
+        if self.status_message.as_ref().is_some_and(|(_, at)| at.elapsed() >= STATUS_TIMEOUT) {
+            self.status_message = None;
+        }
+
+        if self.last_backup_at.elapsed() >= BACKUP_INTERVAL {
+            self.last_backup_at = Instant::now();
+            self.backup_buffers();
+        }
+
+        self.expire_chord();
+
+        if self.process_view.as_ref().is_some_and(|view| view.refreshed_at.elapsed() >= process_view::REFRESH_INTERVAL) {
+            if let Some(view) = self.process_view.as_mut() {
+                view.refresh();
+            }
+        }
+
+        if self.last_session_backup_at.elapsed() >= session_backup::SNAPSHOT_INTERVAL {
+            self.backup_session();
+        }
+
+        if self.last_disk_check.elapsed() >= watcher::POLL_INTERVAL {
+            self.last_disk_check = Instant::now();
+            self.check_external_changes();
+        }
+
+        if !self.user_assets_checked {
+            self.report_user_asset_errors();
+        }
+
+        self.autosave_tick();
+        self.refresh_git_changes();
+        self.refresh_lens_references();
+        self.refresh_inlay_hints();
+        self.load_preview_ahead();
+    }
+
+    /// Recompute the git gutter in the background once the buffer changed; at
+    /// most one computation runs at a time so typing stays responsive
+    fn refresh_git_changes(&mut self) {
+        // A preview would show everything past the loaded part as deleted
+        if self.git_changes_pending || self.editor_state.loading || self.editor_state.preview.is_some() {
+            return;
+        }
+        let Some(path) = self.editor_state.file_path.as_deref().map(|p| self.normalize_path(p)) else { return };
+        let key = (path.clone(), self.editor_state.content_hash());
+        if self.git_changes_requested.as_ref() == Some(&key) {
+            return;
+        }
+        self.git_changes_requested = Some(key);
+        self.git_changes_pending = true;
+
+        let tx = self.event_tx.clone();
+        let root = self.workspace.clone();
+        let lines = self.editor_state.lines.clone();
+        let algorithm = self.config.diff_algorithm;
+        thread::spawn(move || {
+            let changes = git_gutter::head_lines(&root, &path)
+                .map(|head| git_gutter::changes(algorithm, &head, &lines))
+                .unwrap_or_default();
+            let _ = tx.send(AppEvent::GitChanges { path, changes });
+        });
+    }
+
+    pub fn on_git_changes(&mut self, path: PathBuf, changes: Vec<(usize, LineChange)>) {
+        self.git_changes_pending = false;
+        self.git_changes = Some((path, changes));
+    }
+
+    /// Highlight the lines in and below the editor's view that aren't yet, on a
+    /// background thread; one batch at a time, the next one goes out once it is back
+    pub fn request_highlights(&mut self) {
+        if self.highlight_pending {
+            return;
+        }
+        let Some(request) = self.editor_state.highlight_requests() else { return };
+        self.highlight_pending = true;
+        let tx = self.event_tx.clone();
+        thread::spawn(move || {
+            let lines = request.run();
+            let _ = tx.send(AppEvent::LinesHighlighted { request, lines });
+        });
+    }
+
+    pub fn on_lines_highlighted(&mut self, request: HighlightRequest, lines: Vec<Vec<HighlightSpan>>) {
+        self.highlight_pending = false;
+        self.editor_state.apply_highlights(request, lines);
+    }
+
+    /// Count references to the open file's functions in the background once it
+    /// is opened or saved; unsaved edits aren't on disk to be counted
+    fn refresh_lens_references(&mut self) {
+        let state = &self.editor_state;
+        if !self.config.code_lens || self.lens_references_pending || state.loading || state.modified || state.preview.is_some() {
+            return;
+        }
+        let Some(path) = state.file_path.as_deref().map(|p| self.normalize_path(p)) else { return };
+        let Some(language) = syntax::language_key(&path).filter(|l| code_lens::supports(Some(l))) else { return };
+        let key = (path.clone(), state.content_hash());
+        if self.lens_references_requested.as_ref() == Some(&key) {
+            return;
+        }
+        self.lens_references_requested = Some(key);
+        self.lens_references_pending = true;
+
+        let names: HashSet<String> = code_lens::find_symbols(&language, &state.lines, 0..state.lines.len())
+            .into_iter().map(|symbol| symbol.name).collect();
+        let tx = self.event_tx.clone();
+        let root = self.workspace.clone();
+        thread::spawn(move || {
+            let counts = code_lens::count_references(&root, &names);
+            let _ = tx.send(AppEvent::ReferencesCounted { path, counts });
+        });
+    }
+
+    /// Ask the open file's language server for inlay hints once typing paused;
+    /// a server is never started just for them
+    fn refresh_inlay_hints(&mut self) {
+        // A request to a server that exited meanwhile is never answered
+        if self.inlay_hint_request.as_ref().is_some_and(|r| self.lsp_servers.contains_key(&r.server)) {
+            return;
+        }
+        let state = &self.editor_state;
+        if state.loading || state.preview.is_some() || self.last_edit_at.elapsed() < INLAY_HINT_DELAY {
+            return;
+        }
+        let Some(path) = state.file_path.as_deref().map(|p| self.normalize_path(p)) else { return };
+        if !self.config.inlay_hints_for(&path) {
+            if state.has_inlay_hints() {
+                self.editor_state.set_inlay_hints(Vec::new());
+            }
+            return;
+        }
+        let key = (path.clone(), state.content_hash());
+        if self.inlay_hints_requested.as_ref() == Some(&key) {
+            return;
+        }
+        let Some(server) = self.config.language_server_for(&path).map(str::to_string) else { return };
+        let Some(client) = self.lsp_servers.get_mut(&server).filter(|c| c.provides("inlayHintProvider")) else { return };
+        let id = client.inlay_hints(&path, &self.editor_state.lines);
+        self.inlay_hint_request = Some(InlayHintRequest { server, id, path, hash: key.1 });
+        self.inlay_hints_requested = Some(key);
+    }
+
+    fn on_inlay_hints(&mut self, request: InlayHintRequest, result: Result<serde_json::Value, String>) {
+        let current = self.editor_state.file_path.as_deref().map(|p| self.normalize_path(p));
+        if current.as_ref() != Some(&request.path) || self.editor_state.content_hash() != request.hash {
+            // Edited meanwhile: ask again for the new text
+            self.inlay_hints_requested = None;
+            return;
+        }
+        // Servers still indexing may fail; the next edit asks again
+        if let Ok(result) = result {
+            let hints = lsp::parse_inlay_hints(&result, &self.editor_state.lines);
+            self.editor_state.set_inlay_hints(hints);
+        }
+    }
+
+    /// Show or hide inlay hints in files of the open file's type (Alt+I)
+    pub fn toggle_inlay_hints(&mut self) {
+        let Some(path) = self.editor_state.file_path.as_deref().map(|p| self.normalize_path(p)) else {
+            self.notify("Open a file to toggle its inlay hints");
+            return;
+        };
+        let Some(shown) = self.config.toggle_inlay_hints(&path) else {
+            self.notify("Inlay hints are set per file type; this file has none");
+            return;
+        };
+        self.save_config();
+        self.inlay_hints_requested = None;
+        if shown {
+            // Start the server if it isn't yet, so the hints come without an edit
+            self.sync_language_server();
+        } else {
+            self.editor_state.set_inlay_hints(Vec::new());
+        }
+        let kind = syntax::language_key(&path).unwrap_or_default();
+        self.notify(format!("Inlay hints {} in .{} files", if shown { "shown" } else { "hidden" }, kind));
+    }
+
+    pub fn on_references_counted(&mut self, path: PathBuf, counts: HashMap<String, usize>) {
+        self.lens_references_pending = false;
+        self.lens_references = Some((path, counts));
+    }
+
+    /// Gutter markers of the open file: git changes, then the ranges of expanded review comments
+    pub fn gutter_markers(&self) -> Vec<GutterMarker> {
+        // Diagnostics come first so they win over git changes on the same line
+        let mut markers = self.diagnostic_gutter_markers();
+        markers.extend(self.git_gutter_markers());
+        let lines = &self.editor_state.lines;
+        let style = Style::default().fg(Color::Cyan);
+        for comment in self.current_review().unwrap_or_default().iter().filter(|c| c.expanded) {
+            let Some(start) = comment.locate(lines) else { continue };
+            let end = start + comment.end - comment.start;
+            markers.extend((start..=end).map(|line| GutterMarker { line, symbol: "▍", style }));
+        }
+        markers
+    }
+
+    /// Git change markers for the gutter of the open file
+    fn git_gutter_markers(&self) -> Vec<GutterMarker> {
+        let Some(changes) = self.current_git_changes() else { return Vec::new() };
+        changes.iter()
+            .map(|&(line, change)| GutterMarker {
+                line,
+                symbol: change.symbol(),
+                style: Style::default().fg(match change {
+                    LineChange::Added => Color::Green,
+                    LineChange::Modified => Color::Yellow,
+                    LineChange::Deleted => Color::Red,
+                }),
+            })
+            .collect()
+    }
+
+    fn current_git_changes(&self) -> Option<&[(usize, LineChange)]> {
+        let (path, changes) = self.git_changes.as_ref()?;
+        let current = self.editor_state.file_path.as_deref().map(|p| self.normalize_path(p))?;
+        (*path == current).then_some(changes.as_slice())
+    }
+
+    /// Move the cursor to the next (or previous) block of lines changed since HEAD, wrapping around
+    pub fn jump_to_change(&mut self, forward: bool) {
+        let starts = self.current_git_changes().map(git_gutter::hunk_starts).unwrap_or_default();
+        let row = self.editor_state.cursor_row;
+        let target = if forward {
+            starts.iter().find(|&&s| s > row).or(starts.first())
+        } else {
+            starts.iter().rev().find(|&&s| s < row).or(starts.last())
+        };
+        match target {
+            Some(&line) => self.editor_state.goto(line, 0),
+            None => self.notify("No changes since the last commit"),
+        }
+    }
+
+    /// Once the syntax assets have loaded, list user theme and syntax files that failed to load
+    fn report_user_asset_errors(&mut self) {
+        let Some(assets) = syntax::assets() else { return };
+        self.user_assets_checked = true;
+        for (path, message) in &assets.user_errors {
+            let error = ConfigError { line: 1, column: 1, message: message.clone() };
+            self.diagnostics.set_file(DiagnosticSource::Config, path, vec![config_diagnostic(path, &error)]);
+        }
+        if !assets.user_errors.is_empty() {
+            self.notify(format!("{} custom theme/syntax file(s) failed to load, see Problems", assets.user_errors.len()));
+        }
+    }
+
+    /// Save once editing has paused for `autosave_delay_ms`, or right away when focus leaves the editor
+    fn autosave_tick(&mut self) {
+        let Some(delay) = self.config.autosave_delay_ms else { return };
+        let in_editor = self.active_panel == ActivePanel::Editor;
+        let left_editor = self.autosave_in_editor && !in_editor;
+        self.autosave_in_editor = in_editor;
+        if left_editor || self.last_edit_at.elapsed() >= Duration::from_millis(delay) {
+            self.autosave();
+        }
+    }
+
+    /// Write every modified file buffer. Buffers changed on disk are left to the conflict prompt.
+    fn autosave(&mut self) {
+        let mut saved = false;
+        for idx in 0..self.buffers.len() {
+            let buffer = self.buffer(idx);
+            if !buffer.modified || buffer.loading || buffer.file_path.is_none() || buffer.changed_on_disk() {
+                continue;
+            }
+            if let Err(e) = self.buffer_mut(idx).save() {
+                self.notify(format!("Auto-save failed: {}", e));
+                // Try again after another idle period rather than on every tick
+                self.last_edit_at = Instant::now();
+                continue;
+            }
+            saved = true;
+            if let Some(path) = self.buffer(idx).file_path.clone() {
+                self.publish(WorkspaceEvent::FileSaved(path));
+            }
+        }
+        if saved {
+            self.last_autosave = Some(Instant::now());
+        }
+    }
+
+    /// Status bar marker while auto-save is on, e.g. "auto-saved 12s ago"
+    pub fn autosave_label(&self) -> Option<String> {
+        self.config.autosave_delay_ms?;
+        Some(match self.last_autosave {
+            None => "auto-save on".to_string(),
+            Some(at) => match at.elapsed().as_secs() {
+                secs @ 0..=59 => format!("auto-saved {}s ago", secs),
+                secs => format!("auto-saved {}m ago", secs / 60),
+            },
+        })
+    }
+
+    /// Reload buffers whose file was changed by another program. Buffers with unsaved
+    /// edits are brought to the front with the conflict prompt instead.
+    fn check_external_changes(&mut self) {
+        if self.show_conflict {
+            return;
+        }
+        for idx in 0..self.buffers.len() {
+            if !self.buffer(idx).changed_on_disk() {
+                continue;
+            }
+            if self.buffer(idx).modified {
+                self.switch_buffer(idx);
+                self.show_conflict = true;
+                return;
+            }
+            self.reload_buffer(idx);
+        }
+    }
+
+    /// Re-read a buffer's file, keeping the cursor where it was
+    fn reload_buffer(&mut self, idx: usize) {
+        let limit = self.preview_limit();
+        let buffer = self.buffer_mut(idx);
+        let Some(path) = buffer.file_path.clone() else { return };
+        let (row, col) = (buffer.cursor_row, buffer.cursor_col);
+        match preview::load(&path, limit) {
+            Ok((content, encoding, preview)) => {
+                buffer.set_contents(path, &content);
+                buffer.encoding = encoding;
+                buffer.set_preview(preview);
+                buffer.goto(row, col);
+                let name = buffer.display_name();
+                self.notify(format!("Reloaded {} from disk", name));
+            }
+            Err(e) => self.notify(format!("Reload failed: {}", e)),
+        }
+    }
+
+    /// Conflict prompt: take the version on disk, dropping the unsaved edits
+    pub fn conflict_reload(&mut self) {
+        self.show_conflict = false;
+        self.reload_buffer(self.active_buffer);
+    }
+
+    /// Conflict prompt: keep the edits; saving will overwrite the file on disk
+    pub fn conflict_keep_mine(&mut self) {
+        self.show_conflict = false;
+        self.editor_state.mark_synced();
+    }
+
+    /// Conflict prompt: compare the edits with the file on disk
+    pub fn conflict_diff(&mut self) {
+        self.show_conflict = false;
+        let Some(path) = self.editor_state.file_path.clone() else { return };
+        match encoding::read_file(&path) {
+            Ok((content, _)) => {
+                let name = self.editor_state.display_name();
+                let disk = content.lines().map(str::to_string).collect();
+                self.diff_view = Some(DiffView::new(self.config.diff_algorithm, name.clone(), self.editor_state.lines.clone(), format!("{} (on disk)", name), disk));
+                self.editor_state.mark_synced();
+                self.notify("Save to keep your version, or use File > Revert File");
+            }
+            Err(e) => self.notify(format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    /// Compare the buffer with its file as last saved, to review edits before saving
+    pub fn show_saved_diff(&mut self) {
+        let Some(path) = self.editor_state.file_path.clone() else {
+            self.notify("The current buffer has no file");
+            return;
+        };
+        match encoding::read_file(&path) {
+            Ok((content, _)) => {
+                let saved: Vec<String> = content.lines().map(str::to_string).collect();
+                let name = self.editor_state.display_name();
+                if saved == self.editor_state.lines {
+                    self.notify(format!("{} has no unsaved changes", name));
+                    return;
+                }
+                self.diff_view = Some(DiffView::new(self.config.diff_algorithm, format!("{} (saved)", name), saved, format!("{} (buffer)", name), self.editor_state.lines.clone()));
+                self.active_panel = ActivePanel::Editor;
+            }
+            Err(e) => self.notify(format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    /// Write a swap copy of each unsaved buffer that changed since its last backup
+    fn backup_buffers(&mut self) {
+        for idx in self.unsaved_buffers() {
+            let buffer = self.buffer(idx);
+            let hash = buffer.content_hash();
+            if hash == buffer.backup_hash {
+                continue;
+            }
+            let backup = BufferBackup::new(buffer.file_path.clone(), buffer.backup_id, self.workspace.clone(), buffer.lines.clone());
+            if recovery::write_backup(&backup).is_ok() {
+                self.buffer_mut(idx).backup_hash = hash;
+            }
+        }
+    }
+
+    /// Open the backup at `idx` in its file's buffer (or a new one) and take it
+    /// off the prompt, which stays up while backups are left
+    pub fn restore_backup(&mut self, idx: usize) {
+        if idx >= self.recovery_backups.len() {
+            return;
+        }
+        let backup = self.recovery_backups.remove(idx);
+        match backup.path.as_deref().and_then(|path| self.find_buffer(path)) {
+            Some(open) => self.switch_buffer(open),
+            None if self.editor_state.file_path.is_none() && !self.editor_state.modified && !self.editor_state.is_scratch() => {}
+            None => {
+                self.buffers.push(EditorState::new());
+                self.switch_buffer(self.buffers.len() - 1);
+            }
+        }
+        self.editor_state.restore(backup.path, backup.lines);
+        // Later backups of an untitled buffer go on in the file restored from
+        self.editor_state.backup_id = backup.buffer_id;
+        self.editor_state.indent = self.config.indent_for_contents(self.editor_state.file_path.as_deref(), &self.editor_state.lines);
+        // The backup on disk now protects this buffer until it is saved
+        self.editor_state.backup_hash = self.editor_state.content_hash();
+        self.active_panel = ActivePanel::Editor;
+        self.after_recovery_choice(idx);
+    }
+
+    /// Delete the backup at `idx` without restoring it
+    pub fn discard_backup(&mut self, idx: usize) {
+        if idx >= self.recovery_backups.len() {
+            return;
+        }
+        let backup = self.recovery_backups.remove(idx);
+        recovery::remove_backup(backup.path.as_deref(), backup.buffer_id, &self.workspace);
+        self.after_recovery_choice(idx);
+    }
+
+    /// Delete every backup still offered
+    pub fn discard_backups(&mut self) {
+        for backup in std::mem::take(&mut self.recovery_backups) {
+            recovery::remove_backup(backup.path.as_deref(), backup.buffer_id, &self.workspace);
+        }
+        self.show_recovery = false;
+    }
+
+    /// Keep the prompt on the next backup, or close it when none are left
+    fn after_recovery_choice(&mut self, idx: usize) {
+        self.show_recovery = !self.recovery_backups.is_empty();
+        self.recovery_state.select(self.show_recovery.then(|| idx.min(self.recovery_backups.len() - 1)));
+    }
+
+    /// Called when the app quits normally; backups are only kept after a crash.
+    /// Backups still waiting in the prompt are left for the next start.
+    pub fn on_clean_exit(&mut self) {
+        self.stop_watch();
+        self.config_writer.flush();
+        for idx in 0..self.buffers.len() {
+            let buffer = self.buffer(idx);
+            let (path, id) = (buffer.file_path.as_deref(), buffer.backup_id);
+            if !self.recovery_backups.iter().any(|b| b.belongs_to(path, id)) {
+                recovery::remove_backup(path, id, &self.workspace);
+            }
+        }
+        let buffers = if self.config.persist_scratch { self.scratch_buffers() } else { Vec::new() };
+        if let Some(storage) = self.storage.as_mut() {
+            if let Err(e) = storage.save_scratch(&self.workspace, &buffers) {
+                eprintln!("Could not keep scratch buffers: {}", e);
+            }
+        }
+        if let Some(socket) = &self.remote_socket {
+            remote::cleanup(socket);
+        }
+    }
+
+    /// Execute a command received over the remote control socket
+    pub fn handle_remote(&mut self, command: RemoteCommand) {
+        match command {
+            RemoteCommand::Open { path, line, column } => {
+                self.open_location(&path, line.unwrap_or(1), column.unwrap_or(1));
+            }
+            RemoteCommand::Action(name) => match Action::from_name(&name) {
+                Some(action) => self.handle_action(action),
+                None => self.notify(format!("Remote: unknown action '{}'", name)),
+            },
+            RemoteCommand::Chat(prompt) => {
+                self.send_chat_message(prompt);
+                self.chat_scroll = u16::MAX;
+            }
+        }
+    }
+
+    /// Execute an action triggered by a keybinding or a menu item. Failures
+    /// are shown in the status area.
+    pub fn handle_action(&mut self, action: Action) {
+        if let Err(e) = self.run_action(action) {
+            self.notify(e);
+        }
+    }
+
+    /// Execute a menu bar item. File Search opens the finder even if it is
+    /// already open, where its key closes it again.
+    pub fn handle_menu_action(&mut self, action: Action) {
+        match action {
+            Action::FileSearch => self.open_file_search(),
+            _ => self.handle_action(action),
+        }
+    }
+
+    /// Show the file finder with results for the current query
+    fn open_file_search(&mut self) {
+        self.is_searching = true;
+        self.on_search_input();
+    }
+
+    fn run_action(&mut self, action: Action) -> Result<(), String> {
+        match action {
+            Action::Quit => self.request_quit(),
+            // The other panels are hidden in zen mode
+            Action::SwitchFocus if self.zen_mode => {}
+            Action::SwitchFocus => {
+                self.active_panel = match self.active_panel {
+                    ActivePanel::FileTree => ActivePanel::Editor,
+                    ActivePanel::Editor => ActivePanel::Chat,
+                    ActivePanel::Chat => ActivePanel::Terminal,
+                    ActivePanel::Terminal => ActivePanel::FileTree,
+                };
+            }
+            Action::ToggleMenu => {
+                // Only closes an open menu; menus are opened with the mouse
+                self.menu_open_idx = None;
+            }
+            Action::ResetLayout => {
+                self.zen_mode = false;
+                self.active_panel = ActivePanel::Editor;
+            }
+            Action::ToggleZen => {
+                self.zen_mode = !self.zen_mode;
+                self.active_panel = ActivePanel::Editor;
+            }
+            Action::DumpHistory => self.open_capture(),
+            Action::FileSearch if self.is_searching => self.is_searching = false,
+            Action::FileSearch => self.open_file_search(),
+            Action::SearchEverything => self.open_search_palette(),
+            Action::SearchBuffers => self.open_buffer_search(),
+            Action::RecentFiles => self.open_recent_files(),
+            Action::RestoreSession => self.open_restore_picker(),
+            Action::CycleModel => self.cycle_model(),
+            Action::SwitchPreset => self.open_preset_picker(),
+            Action::OpenSettings => self.open_settings(),
+            Action::Copy if self.active_panel == ActivePanel::Editor => {
+                let text = match self.vim_selection() {
+                    Some((start, end)) => Some(self.editor_state.text_range(start, end)),
+                    None => self.editor_state.copy(),
+                };
+                if let Some(text) = text {
+                    self.copy_text(text)?;
+                }
+            }
+            Action::Cut if self.active_panel == ActivePanel::Editor => {
+                if self.editor_state.loading || self.refuse_read_only() {
+                    return Ok(());
+                }
+                let text = match self.vim_selection() {
+                    Some((start, end)) => {
+                        let text = self.editor_state.text_range(start, end);
+                        self.editor_state.delete_range(start, end);
+                        self.vim.mode = VimMode::Normal;
+                        Some(text)
+                    }
+                    None => self.editor_state.cut(),
+                };
+                self.last_edit_at = Instant::now();
+                if let Some(text) = text {
+                    self.copy_text(text)?;
+                }
+            }
+            Action::Paste => {
+                let text = self.paste_text()?;
+                if self.active_panel == ActivePanel::Editor {
+                    if self.refuse_read_only() {
+                        return Ok(());
+                    }
+                    if let Some((start, end)) = self.vim_selection() {
+                        self.editor_state.delete_range(start, end);
+                        self.vim.mode = VimMode::Normal;
+                    }
+                    self.editor_state.paste(&text);
+                    self.last_edit_at = Instant::now();
+                } else if self.active_panel == ActivePanel::Chat {
+                    // Pasted newlines stay part of the prompt instead of sending it
+                    self.chat_input.insert_str(&text);
+                } else if self.active_panel == ActivePanel::Terminal {
+                    self.write_to_pty(text.as_bytes())?;
+                }
+            }
+            Action::About => {
+                self.chat_history.push(ChatMessage::new(Role::Assistant, "nterm v0.1.0 - A terminal IDE built in Rust."));
+                // Make sure chat is visible
+                self.active_panel = ActivePanel::Chat;
+            }
+            Action::ToggleProblems => {
+                self.show_problems = !self.show_problems;
+                if self.show_problems {
+                    self.refresh_build_diagnostics();
+                }
+            }
+            Action::Find => self.open_find(false),
+            Action::Replace => self.open_find(true),
+            Action::ExportHtml => self.export_buffer(ExportFormat::Html),
+            Action::ExportAnsi => self.export_buffer(ExportFormat::Ansi),
+            Action::Save => self.save_current_buffer(),
+            Action::SaveAs => self.open_save_as(),
+            Action::SaveAll => self.save_all(),
+            Action::ReloadFile => self.revert_buffer(),
+            Action::Compare => self.open_compare(),
+            Action::Refactor => self.open_refactor(),
+            Action::RenameSymbol => self.open_rename(),
+            Action::Outline => self.open_outline(),
+            Action::ProjectSetup => self.open_project_setup(),
+            Action::FormatDocument => self.format_document(),
+            Action::ToggleInlayHints => self.toggle_inlay_hints(),
+            Action::AiReview => self.start_review(),
+            Action::ToggleReviewComment => self.toggle_review_comment_at_cursor(),
+            Action::ClearReview => self.clear_review(),
+            Action::SpellSuggest => self.open_spell_suggestions(),
+            Action::Complete => self.request_completion(),
+            Action::GotoDefinition => self.request_locations(false),
+            Action::FindReferences => self.request_locations(true),
+            Action::ToggleComment => self.toggle_comment(),
+            Action::ToggleFold => self.toggle_fold(),
+            Action::PickColor => self.open_color_picker(),
+            Action::CopyPermalink => self.copy_permalink(),
+            Action::UnfoldAll => self.editor_state.unfold_all(),
+            Action::NewScratch => self.new_scratch_buffer(),
+            Action::TerminalCdToFile => self.terminal_cd_to_file()?,
+            Action::FileTreeFromTerminal => self.file_tree_from_terminal(),
+            Action::ToggleExcluded => self.toggle_excluded_dir()?,
+            Action::ShowExcluded => self.toggle_show_excluded(),
+            Action::ToggleWatch => self.toggle_watch(),
+            Action::SwitchTerminalTab => self.switch_terminal_tab(),
+            Action::ProcessTree => self.open_process_view(),
+            Action::NextBuffer => self.cycle_buffer(true),
+            Action::PrevBuffer => self.cycle_buffer(false),
+            Action::CloseBuffer => self.close_buffer(),
+            Action::CloseAll => self.close_all_buffers(),
+            Action::ToggleReadOnly => self.toggle_read_only(),
+            Action::ToggleLineEnding => self.toggle_line_ending(),
+            Action::ShowDiff => self.show_saved_diff(),
+            Action::NavigateBack => self.navigate(false),
+            Action::NavigateForward => self.navigate(true),
+            Action::NextChange => self.jump_to_change(true),
+            Action::PrevChange => self.jump_to_change(false),
+            Action::NextDiagnostic => self.jump_to_diagnostic(true),
+            Action::PrevDiagnostic => self.jump_to_diagnostic(false),
+            Action::ShowDiagnostic => self.toggle_diagnostic_popup(),
+            Action::ToggleMirror => self.toggle_mirror(),
+            Action::Snapshot => self.pending_snapshot = Some(false),
+            Action::SnapshotRedacted => self.pending_snapshot = Some(true),
+            Action::ToggleTodos => {
+                self.show_todos = !self.show_todos;
+                if self.show_todos && !self.todos_loaded {
+                    self.scan_todos();
+                }
+            }
+            Action::WorkspaceStats => {
+                self.show_stats = !self.show_stats;
+                if self.show_stats && self.stats.is_none() {
+                    self.collect_stats();
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Add a message to the chat and keep it in storage
+    fn push_chat(&mut self, message: ChatMessage) {
+        let stored = self.storage.as_mut().map(|storage| storage.append_message(&self.workspace, &message));
+        if let Some(Err(e)) = stored {
+            self.notify(format!("Could not keep chat history: {}", e));
+        }
+        self.chat_history.push(message);
+        let follow = match self.config.auto_scroll.chat {
+            AutoScroll::Always => true,
+            AutoScroll::AtBottom => self.chat_scroll >= self.chat_max_scroll,
+            AutoScroll::Never => false,
+        };
+        if follow {
+            self.chat_scroll = u16::MAX;
+        }
+    }
+
+    fn terminal_parser(&self, tab: TerminalTab) -> &Arc<RwLock<tui_term::vt100::Parser>> {
+        match tab {
+            TerminalTab::Shell => &self.terminal_screen,
+            TerminalTab::Watch => &self.watch_screen,
+        }
+    }
+
+    /// Give the shell's PTY and screen the size of the terminal panel's inside
+    /// on a `screen` of this size, when it changed. The kernel sends the shell
+    /// SIGWINCH, so full-screen programs redraw and lines wrap at the border.
+    pub fn fit_terminal(&mut self, screen: Rect) {
+        if ui::too_small(screen) {
+            return;
+        }
+        let panel = ui::app_layout(screen, self).terminal;
+        let size = (panel.height.saturating_sub(2), panel.width.saturating_sub(2));
+        // A hidden panel keeps its last size
+        if size.0 == 0 || size.1 == 0 || self.terminal_screen.read().is_ok_and(|p| p.screen().size() == size) {
+            return;
+        }
+        let resized = self.pty_master.resize(PtySize { rows: size.0, cols: size.1, pixel_width: 0, pixel_height: 0 });
+        if let Err(e) = resized {
+            self.notify(format!("Could not resize the terminal: {}", e));
+        }
+        if let Ok(mut parser) = self.terminal_screen.write() {
+            parser.set_size(size.0, size.1);
+        }
+    }
+
+    /// New output reached the shell or watch screen: scroll it as configured
+    /// and update the scrollbar
+    pub fn on_terminal_output(&mut self, tab: TerminalTab) {
+        let mode = self.config.auto_scroll.terminal;
+        let screen = self.terminal_parser(tab).clone();
+        let Ok(mut parser) = screen.write() else { return };
+        let rows = scrollback_rows(&mut parser);
+        let previous = match tab {
+            TerminalTab::Shell => std::mem::replace(&mut self.scrollback_rows.0, rows),
+            TerminalTab::Watch => std::mem::replace(&mut self.scrollback_rows.1, rows),
+        };
+        // vt100 already keeps a scrolled-back view on the same lines and follows
+        // new output at the bottom, which is `AtBottom`
+        match mode {
+            AutoScroll::Always => parser.set_scrollback(0),
+            AutoScroll::Never if parser.screen().scrollback() == 0 => parser.set_scrollback(rows.saturating_sub(previous)),
+            _ => {}
+        }
+        drop(parser);
+        if tab == self.terminal_tab {
+            self.update_terminal_scrollbar();
+        }
+    }
+
+    /// Scroll the terminal tab on screen back (positive `rows`) or forward through its scrollback
+    pub fn scroll_terminal(&mut self, rows: isize) {
+        if let Ok(mut parser) = self.terminal_parser(self.terminal_tab).write() {
+            let offset = parser.screen().scrollback().saturating_add_signed(rows);
+            parser.set_scrollback(offset);
+        }
+        self.update_terminal_scrollbar();
+    }
+
+    /// Whether the terminal tab on screen runs a full-screen program, which gets the mouse wheel
+    pub fn terminal_in_alternate_screen(&self) -> bool {
+        self.terminal_parser(self.terminal_tab).read().is_ok_and(|p| p.screen().alternate_screen())
+    }
+
+    /// Rows the terminal tab on screen moves for Shift+PageUp/PageDown
+    pub fn terminal_page(&self) -> isize {
+        self.terminal_parser(self.terminal_tab).read().map_or(24, |p| p.screen().size().0 as isize)
+    }
+
+    fn update_terminal_scrollbar(&mut self) {
+        let Ok(mut parser) = self.terminal_parser(self.terminal_tab).write() else { return };
+        let rows = scrollback_rows(&mut parser);
+        let (offset, height) = (parser.screen().scrollback(), parser.screen().size().0 as usize);
+        drop(parser);
+        self.terminal_scroll_state = self.terminal_scroll_state
+            .content_length(rows + height)
+            .position(rows - offset.min(rows));
+    }
+
+    /// Write input to the shell
+    pub fn write_to_pty(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.pty_writer.write_all(bytes)
+            .and_then(|_| self.pty_writer.flush())
+            .map_err(|e| format!("Could not write to the terminal: {}", e))
+    }
+
+    /// Text on the system clipboard
+    fn clipboard_text(&self) -> Result<String, String> {
+        let clipboard = self.clipboard.as_ref().ok_or("No system clipboard is available")?;
+        let mut clipboard = clipboard.lock().map_err(|_| "The clipboard is in use")?;
+        clipboard.get_text().map_err(|e| format!("Could not read the clipboard: {}", e))
+    }
+
+    fn set_clipboard_text(&self, text: String) -> Result<(), String> {
+        let clipboard = self.clipboard.as_ref().ok_or("No system clipboard is available")?;
+        let mut clipboard = clipboard.lock().map_err(|_| "The clipboard is in use")?;
+        clipboard.set_text(text).map_err(|e| format!("Could not copy to the clipboard: {}", e))
+    }
+
+    /// Keep copied or cut text in the internal register and on the system clipboard.
+    /// The register still has it when the clipboard isn't available (e.g. over SSH).
+    fn copy_text(&mut self, text: String) -> Result<(), String> {
+        self.register = Some(text.clone());
+        self.set_clipboard_text(text)
+    }
+
+    /// Text to paste: the system clipboard, or the internal register without one
+    fn paste_text(&self) -> Result<String, String> {
+        self.clipboard_text().or_else(|e| self.register.clone().ok_or(e))
+    }
+
+    /// Vim's visual selection, when modal editing is on
+    fn vim_selection(&self) -> Option<((usize, usize), (usize, usize))> {
+        self.vim.selection(&self.editor_state).filter(|_| self.vim_enabled())
+    }
+
+    /// Queue the config to be written; failures come back as `AppEvent::ConfigSaveFailed`
+    fn save_config(&mut self) {
+        self.config_writer.save(&self.config);
+    }
+
+    /// Fold the block around the cursor, or open the fold on the cursor line
+    pub fn toggle_fold(&mut self) {
+        if !self.editor_state.toggle_fold() {
+            self.notify("Nothing to fold here");
+        }
+    }
+
+    /// Open the color picker for the color literal under the cursor
+    pub fn open_color_picker(&mut self) {
+        let (row, col) = (self.editor_state.cursor_row, self.editor_state.cursor_col);
+        let literal = color::find_colors(self.editor_state.current_line()).into_iter()
+            .find(|c| c.start <= col && col <= c.end);
+        match literal {
+            Some(literal) => {
+                if !self.refuse_read_only() {
+                    self.color_picker = Some(ColorPicker::new(row, literal));
+                }
+            }
+            None => self.notify("No color literal under the cursor"),
+        }
+    }
+
+    /// Write the picked color back over the literal
+    pub fn apply_color_picker(&mut self) {
+        let Some(picker) = self.color_picker.take() else { return };
+        let text = picker.literal_text();
+        self.editor_state.replace_range(picker.row, picker.literal.start, picker.literal.end, &text);
+        self.editor_state.cursor_row = picker.row;
+        self.editor_state.cursor_col = picker.literal.start;
+        self.last_edit_at = Instant::now();
+    }
+
+    /// Open the find bar, seeded with the word under the cursor
+    pub fn open_find(&mut self, replace_mode: bool) {
+        let word = self.editor_state.word_at_cursor();
+        let seed = if self.find_bar.active { None } else { word.as_deref() };
+        self.find_bar.open(replace_mode, seed);
+        self.active_panel = ActivePanel::Editor;
+        self.refresh_find();
+    }
+
+    /// Recompute matches after the query or buffer changed and move to the nearest one
+    pub fn refresh_find(&mut self) {
+        let cursor = (self.editor_state.cursor_row, self.editor_state.cursor_col);
+        self.find_bar.update(&self.editor_state.lines, cursor);
+        self.goto_current_match();
+    }
+
+    fn goto_current_match(&mut self) {
+        if let Some(m) = self.find_bar.current_match() {
+            self.editor_state.cursor_row = m.row;
+            self.editor_state.cursor_col = m.start;
+        }
+    }
+
+    /// Replace the current match and advance to the next one
+    pub fn replace_current(&mut self) {
+        if self.refuse_read_only() {
+            return;
+        }
+        let Some(m) = self.find_bar.current_match() else { return };
+        let replacement = self.find_bar.expand_replacement(&self.editor_state.lines, m);
+        self.editor_state.replace_range(m.row, m.start, m.end, &replacement);
+        self.editor_state.cursor_row = m.row;
+        self.editor_state.cursor_col = m.start + replacement.chars().count();
+        self.refresh_find();
+    }
+
+    /// Replace every match in the buffer
+    pub fn replace_all(&mut self) {
+        if self.refuse_read_only() {
+            return;
+        }
+        let matches = self.find_bar.matches.clone();
+        // Expand every replacement against the original text before editing
+        let replacements: Vec<String> = matches.iter()
+            .map(|m| self.find_bar.expand_replacement(&self.editor_state.lines, *m))
+            .collect();
+        // Back to front so earlier ranges stay valid
+        for (m, replacement) in matches.iter().zip(&replacements).rev() {
+            self.editor_state.replace_range(m.row, m.start, m.end, replacement);
+        }
+        self.refresh_find();
+        self.notify(format!("Replaced {} occurrence(s)", matches.len()));
+    }
+
+    /// Keys for the find bar; returns false if the key should fall through to global handling
+    pub fn handle_find_key(&mut self, key: KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => self.find_bar.close(),
+            KeyCode::Tab | KeyCode::BackTab => self.find_bar.toggle_focus(),
+            KeyCode::Up => {
+                self.find_bar.prev_match();
+                self.goto_current_match();
+            }
+            KeyCode::Down => {
+                self.find_bar.next_match();
+                self.goto_current_match();
+            }
+            KeyCode::Enter if self.find_bar.focus == FindField::Replace => self.replace_current(),
+            KeyCode::Enter if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                self.find_bar.prev_match();
+                self.goto_current_match();
+            }
+            KeyCode::Enter => {
+                self.find_bar.next_match();
+                self.goto_current_match();
+            }
+            KeyCode::Char('a') if ctrl && self.find_bar.focus == FindField::Replace => self.replace_all(),
+            KeyCode::Char('f') if ctrl => self.find_bar.focus = FindField::Query,
+            KeyCode::Char('r') if ctrl => {
+                self.find_bar.toggle_regex();
+                self.refresh_find();
+            }
+            _ if ctrl || key.modifiers.contains(KeyModifiers::ALT) => return false,
+            _ => {
+                match self.find_bar.focus {
+                    FindField::Query => {
+                        self.find_bar.query.input(key);
+                        self.refresh_find();
+                    }
+                    FindField::Replace => {
+                        self.find_bar.replacement.input(key);
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    /// Whether editor keys go through vim's modal handling
+    pub fn vim_enabled(&self) -> bool {
+        self.config.keybinding_mode == KeybindingMode::Vim
+    }
+
+    /// Mode label for the editor border, when modal editing is on
+    pub fn editor_mode_label(&self) -> Option<&'static str> {
+        self.vim_enabled().then(|| self.vim.mode.label())
+    }
+
+    /// Whether the editor wants Esc for itself rather than the menu
+    pub fn editor_captures_esc(&self) -> bool {
+        !self.editor_state.extra_cursors.is_empty()
+            || self.editor_state.selection().is_some()
+            || (self.vim_enabled() && self.vim.mode != VimMode::Normal)
+    }
+
+    /// Route a key to the editor buffer using the configured keybinding mode
+    pub fn handle_editor_key(&mut self, key: KeyEvent) {
+        if self.editor_state.loading {
+            return;
+        }
+        // The problems popup is for the line it was opened on
+        self.show_diagnostic_popup = false;
+        self.last_edit_at = Instant::now();
+        // Esc first drops extra carets and the selection
+        if key.code == KeyCode::Esc && (!self.editor_state.extra_cursors.is_empty() || self.editor_state.selection().is_some()) {
+            self.editor_state.extra_cursors.clear();
+            self.editor_state.clear_selection();
+            return;
+        }
+        let edits = match self.vim_enabled() {
+            true => self.vim.is_edit_key(key, &self.editor_key_map),
+            false => EditorState::is_edit_key(key, &self.editor_key_map),
+        };
+        if edits && self.refuse_read_only() {
+            return;
+        }
+        if self.vim_enabled() {
+            self.vim.handle_key(&mut self.editor_state, key, &self.editor_key_map);
+        } else {
+            self.editor_state.input(key, &self.editor_key_map);
+        }
+        self.load_preview_ahead();
+        // Keep the highlighted matches in step with the text, without jumping
+        if edits && self.find_bar.active {
+            let cursor = (self.editor_state.cursor_row, self.editor_state.cursor_col);
+            self.find_bar.update(&self.editor_state.lines, cursor);
+        }
+        self.update_completion(key);
+    }
+
+    /// Comment or uncomment the visual selection, or the lines with a caret
+    pub fn toggle_comment(&mut self) {
+        if self.editor_state.loading || self.refuse_read_only() {
+            return;
+        }
+        let editor = &self.editor_state;
+        let rows: Vec<usize> = match self.vim.selection(editor).filter(|_| self.vim_enabled()) {
+            Some((start, end)) => (start.0..=end.0).collect(),
+            None => std::iter::once(editor.cursor_row).chain(editor.extra_cursors.iter().map(|c| c.0)).collect(),
+        };
+        if self.editor_state.toggle_comment(&rows) {
+            self.last_edit_at = Instant::now();
+            if self.vim.mode == VimMode::Visual {
+                self.vim.mode = VimMode::Normal;
+            }
+        } else {
+            self.notify("No line comment syntax for this file type");
+        }
+    }
+
+    /// Tell the user why an edit was ignored; returns true if the buffer is read-only
+    fn refuse_read_only(&mut self) -> bool {
+        if !self.editor_state.read_only {
+            return false;
+        }
+        let action = self.shortcut_label(Action::ToggleReadOnly)
+            .unwrap_or_else(|| "File > Toggle Read-Only".to_string());
+        self.notify(format!("{} is read-only ({} to edit anyway)", self.editor_state.display_name(), action));
+        true
+    }
+
+    /// Lock or unlock editing of the current buffer
+    pub fn toggle_read_only(&mut self) {
+        if self.editor_state.preview.is_some() {
+            return self.open_in_full();
+        }
+        let state = &mut self.editor_state;
+        state.read_only = !state.read_only;
+        let message = match (state.read_only, state.file_path.as_deref().is_some_and(editor::is_read_only)) {
+            (true, _) => format!("{} locked", state.display_name()),
+            (false, true) => format!("Editing {}; you lack write permission, saving will offer sudo", state.display_name()),
+            (false, false) => format!("{} unlocked", state.display_name()),
+        };
+        self.notify(message);
+    }
+
+    /// Convert the buffer between LF and CRLF line endings
+    pub fn toggle_line_ending(&mut self) {
+        if self.refuse_read_only() {
+            return;
+        }
+        self.editor_state.toggle_line_ending();
+        let ending = self.editor_state.line_ending.label();
+        self.notify(format!("{} will be saved with {} line endings", self.editor_state.display_name(), ending));
+    }
+
+    /// The save failed for lack of permission: stage the contents in a temp file
+    /// and type a `sudo cp` into the terminal for the user to confirm with Enter
+    fn offer_sudo_save(&mut self) {
+        let Some(path) = self.editor_state.file_path.as_deref().map(|p| self.workspace.join(p)) else { return };
+        let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let staged = match self.editor_state.encoded_contents().and_then(|bytes| terminal::stage_private(&name, &bytes)) {
+            Ok(staged) => staged,
+            Err(e) => {
+                self.notify(format!("Save failed: permission denied, and staging a copy failed: {}", e));
+                return;
+            }
+        };
+        if let Err(e) = self.write_to_pty(terminal::sudo_copy_command(&staged, &path).as_bytes()) {
+            self.notify(format!("Save failed: {}", e));
+            return;
+        }
+        self.terminal_tab = TerminalTab::Shell;
+        self.active_panel = ActivePanel::Terminal;
+        self.notify("Permission denied: press Enter in the terminal to save with sudo");
+    }
+
+    /// Add or remove an extra caret at a screen position inside the editor panel
+    pub fn toggle_editor_cursor_at(&mut self, editor_area: Rect, x: u16, y: u16) {
+        if let Some((row, col)) = self.editor_state.position_at(self.editor_text_area(editor_area), x, y) {
+            self.editor_state.toggle_cursor(row, col);
+        }
+    }
+
+    /// Put the cursor at a screen position inside the editor panel; `extend`
+    /// selects from where it was
+    pub fn click_editor_at(&mut self, editor_area: Rect, x: u16, y: u16, extend: bool) {
+        if self.diff_view.is_some() || self.hex_view.is_some() || self.show_settings || self.showing_welcome()
+            || self.editor_state.loading
+        {
+            return;
+        }
+        if !extend {
+            let hit = self.editor_state.annotation_hits.iter().find(|hit| hit.y == y && hit.x.contains(&x));
+            match hit.map(|hit| (hit.line, hit.action)) {
+                Some((line, AnnotationAction::Lens(action))) => return self.run_code_lens(line, action),
+                Some((_, AnnotationAction::ToggleReview(idx))) => return self.toggle_review_comment(idx),
+                None => {}
+            }
+        }
+        if let Some((row, col)) = self.editor_state.position_at(self.editor_text_area(editor_area), x, y) {
+            self.editor_state.place_cursor(row, col, extend);
+        }
+    }
+
+    /// Where the editor draws text in its panel: inside the border (none in zen
+    /// mode) and above the find bar
+    pub fn editor_text_area(&self, editor_area: Rect) -> Rect {
+        let borders = if self.zen_mode { Borders::NONE } else { Borders::ALL };
+        let mut inner = Block::default().borders(borders).inner(editor_area);
+        if self.find_bar.active && editor_area.height > 6 {
+            inner.height = inner.height.saturating_sub(3);
+        }
+        inner
+    }
+
+    /// Range highlights for the editor (the active search match and the visual selection)
+    pub fn editor_highlights(&self) -> Vec<RangeHighlight> {
+        let mut highlights = self.spelling_highlights();
+        highlights.extend(self.diagnostic_highlights());
+        if !self.editor_state.modified {
+            highlights.extend(self.editor_state.output_colors.iter().cloned());
+        }
+        if let Some((start, end)) = self.vim_selection().or_else(|| self.editor_state.selection()) {
+            let style = Style::default().bg(self.current_theme.selection_bg).fg(self.current_theme.selection_fg);
+            for line in start.0..=end.0 {
+                highlights.push(RangeHighlight {
+                    line,
+                    start: if line == start.0 { start.1 } else { 0 },
+                    end: if line == end.0 { end.1 } else { self.editor_state.lines[line].chars().count() },
+                    style,
+                });
+            }
+        }
+        if self.find_bar.active {
+            // Every match in view, with the current one standing out
+            let current = self.find_bar.current_match();
+            let other = Style::default().bg(self.current_theme.selection_bg);
+            let selected = Style::default().bg(self.current_theme.border_active).fg(self.current_theme.selection_fg);
+            for &m in self.find_bar.matches_in(self.editor_state.scroll_offset, 200) {
+                highlights.push(RangeHighlight {
+                    line: m.row,
+                    start: m.start,
+                    end: m.end,
+                    style: if Some(m) == current { selected } else { other },
+                });
+            }
+        }
+        highlights
+    }
+
+    /// Write the buffer with its syntax highlighting next to the file (or `untitled.<fmt>`)
+    pub fn export_buffer(&mut self, format: ExportFormat) {
+        let source = self.editor_state.file_path.clone()
+            .unwrap_or_else(|| self.workspace.join("untitled"));
+        match export::export_to_file(&self.editor_state.contents(), &source, format) {
+            Ok(target) => self.notify(format!("Exported to {}", target.display())),
+            Err(e) => self.notify(format!("Export failed: {}", e)),
+        }
+    }
+
+    /// Show a message in the status area of the menu bar
+    pub fn notify(&mut self, message: impl Into<String>) {
+        self.status_message = Some((message.into(), Instant::now()));
+    }
+
+    /// Save the editor buffer, prompting for a path if it is untitled
+    pub fn save_current_buffer(&mut self) {
+        if self.editor_state.loading {
+            self.notify(format!("{} is still loading", self.editor_state.display_name()));
+            return;
+        }
+        if self.editor_state.file_path.is_none() {
+            self.open_save_as();
+            return;
+        }
+        match self.editor_state.save() {
+            Ok(()) => self.on_buffer_saved(),
+            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => self.offer_sudo_save(),
+            Err(e) => self.notify(format!("Save failed: {}", e)),
+        }
+    }
+
+    /// Write every modified file buffer (Save All). Untitled buffers need Save
+    /// As and ones changed on disk are left to the conflict prompt, so both
+    /// are only listed.
+    pub fn save_all(&mut self) {
+        let (mut saved, mut skipped, mut failed) = (0, Vec::new(), Vec::new());
+        for idx in 0..self.buffers.len() {
+            let buffer = self.buffer(idx);
+            if !buffer.modified || buffer.loading || buffer.is_scratch() {
+                continue;
+            }
+            let name = buffer.display_name();
+            if buffer.file_path.is_none() || buffer.changed_on_disk() {
+                skipped.push(name);
+                continue;
+            }
+            match self.buffer_mut(idx).save() {
+                Ok(()) => {
+                    saved += 1;
+                    if let Some(path) = self.buffer(idx).file_path.clone() {
+                        self.publish(WorkspaceEvent::FileSaved(path));
+                    }
+                }
+                Err(e) => failed.push(format!("{} ({})", name, e)),
+            }
+        }
+        if saved == 0 && skipped.is_empty() && failed.is_empty() {
+            self.notify("No unsaved changes");
+            return;
+        }
+        let mut message = format!("Saved {} file{}", saved, if saved == 1 { "" } else { "s" });
+        if !skipped.is_empty() {
+            message.push_str(&format!("; untitled or changed on disk: {}", skipped.join(", ")));
+        }
+        if !failed.is_empty() {
+            message.push_str(&format!("; failed: {}", failed.join(", ")));
+        }
+        self.notify(message);
+    }
+
+    /// Open the Save As prompt, pre-filled with the current path
+    pub fn open_save_as(&mut self) {
+        let mut input = TextArea::default();
+        input.set_block(Block::default().borders(Borders::ALL).title(" Save As (Enter: Save, Esc: Cancel) "));
+        if let Some(path) = &self.editor_state.file_path {
+            input.insert_str(path.to_string_lossy());
+        }
+        self.save_as_input = input;
+        self.show_save_as = true;
+    }
+
+    /// Save the buffer to the path typed into the Save As prompt
+    pub fn confirm_save_as(&mut self) {
+        let typed = self.save_as_input.lines().join("");
+        let typed = typed.trim();
+        if typed.is_empty() {
+            return;
+        }
+        let path = PathBuf::from(typed);
+        let old_path = self.editor_state.file_path.clone();
+        match self.editor_state.save_as(path) {
+            Ok(()) => {
+                self.show_save_as = false;
+                self.editor_state.indent = self.config.indent_for_contents(self.editor_state.file_path.as_deref(), &self.editor_state.lines);
+                // The backup was keyed by the old path
+                recovery::remove_backup(old_path.as_deref(), self.editor_state.backup_id, &self.workspace);
+                self.on_buffer_saved();
+                self.refresh_file_tree();
+            }
+            Err(e) => self.notify(format!("Save failed: {}", e)),
+        }
+    }
+
+    /// Open the "Compare with..." prompt
+    pub fn open_compare(&mut self) {
+        let mut input = TextArea::default();
+        input.set_block(Block::default().borders(Borders::ALL)
+            .title(" Compare buffer with (path, two paths, or empty for clipboard) "));
+        self.compare_input = input;
+        self.show_compare = true;
+    }
+
+    /// Diff the current buffer against a file or the clipboard, or two files against each other
+    pub fn confirm_compare(&mut self) {
+        let typed = self.compare_input.lines().join("");
+        let typed = typed.trim();
+        let read = |path: &Path| -> Result<Vec<String>, String> {
+            encoding::read_file(path)
+                .map(|(content, _)| content.lines().map(str::to_string).collect())
+                .map_err(|e| format!("{}: {}", path.display(), e))
+        };
+        let buffer_side = || (self.editor_state.display_name(), self.editor_state.lines.clone());
+
+        let sides = if typed.is_empty() || typed == "clipboard" {
+            self.clipboard_text()
+                .map(|text| (buffer_side(), ("[clipboard]".to_string(), text.lines().map(str::to_string).collect())))
+        } else {
+            let parts: Vec<&str> = typed.split_whitespace().collect();
+            match parts.as_slice() {
+                [a, b] if Path::new(a).is_file() && Path::new(b).is_file() => read(Path::new(a))
+                    .and_then(|left| read(Path::new(b)).map(|right| ((a.to_string(), left), (b.to_string(), right)))),
+                _ => read(Path::new(typed)).map(|right| (buffer_side(), (typed.to_string(), right))),
+            }
+        };
+
+        match sides {
+            Ok(((left_title, left), (right_title, right))) => {
+                let view = DiffView::new(self.config.diff_algorithm, left_title, left, right_title, right);
+                if view.hunk_count() == 0 {
+                    self.notify("No differences");
+                }
+                self.diff_view = Some(view);
+                self.show_compare = false;
+                self.active_panel = ActivePanel::Editor;
+            }
+            Err(e) => self.notify(format!("Compare failed: {}", e)),
+        }
+    }
+
+    /// Open the "Capture terminal output" prompt
+    pub fn open_capture(&mut self) {
+        let mut input = TextArea::default();
+        input.set_block(Block::default().borders(Borders::ALL)
+            .title(" Capture terminal output (empty = last command, N = last N lines, A-B, all; add 'color' to keep colors) "));
+        self.capture_input = input;
+        self.show_capture = true;
+    }
+
+    /// Copy the chosen scrollback range, without escape sequences, into a new scratch buffer
+    pub fn confirm_capture(&mut self) {
+        let (range, keep_colors) = match scrollback::parse_request(&self.capture_input.lines().join("")) {
+            Ok(request) => request,
+            Err(e) => {
+                self.notify(format!("Capture failed: {}", e));
+                return;
+            }
+        };
+        let captured = match self.history_buffer.read() {
+            Ok(buffer) => scrollback::capture(&scrollback::history_lines(&buffer), range),
+            Err(_) => return,
+        };
+        if captured.is_empty() {
+            self.notify("Nothing to capture");
+            return;
+        }
+        self.show_capture = false;
+        self.notify(format!("Captured {} lines", captured.len()));
+        let colors = if keep_colors { output_highlights(&captured) } else { Vec::new() };
+        self.open_scratch_with(captured.into_iter().map(|line| line.text).collect());
+        self.editor_state.output_colors = colors;
+        self.active_panel = ActivePanel::Editor;
+    }
+
+    /// Keys for the diff view; returns false for keys that should reach the global key map
+    pub fn handle_diff_key(&mut self, key: KeyEvent) -> bool {
+        let Some(view) = self.diff_view.as_mut() else { return false };
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => self.diff_view = None,
+            KeyCode::Up | KeyCode::Char('k') => view.scroll_by(-1),
+            KeyCode::Down | KeyCode::Char('j') => view.scroll_by(1),
+            KeyCode::PageUp => view.scroll_by(-20),
+            KeyCode::PageDown => view.scroll_by(20),
+            KeyCode::Char('n') | KeyCode::Char(']') => view.next_hunk(),
+            KeyCode::Char('p') | KeyCode::Char('[') => view.prev_hunk(),
+            KeyCode::Char('u') => view.unified = !view.unified,
+            _ => return !self.key_map.contains_key(&(key.code, key.modifiers)),
+        }
+        true
+    }
+
+    /// Keys for the hex view; returns false for keys that should reach the global key map
+    pub fn handle_hex_key(&mut self, key: KeyEvent) -> bool {
+        let Some(view) = self.hex_view.as_mut() else { return false };
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => self.hex_view = None,
+            KeyCode::Up | KeyCode::Char('k') => view.scroll_by(-1),
+            KeyCode::Down | KeyCode::Char('j') => view.scroll_by(1),
+            KeyCode::PageUp => view.scroll_by(-20),
+            KeyCode::PageDown => view.scroll_by(20),
+            KeyCode::Home | KeyCode::Char('g') => view.scroll_to(0),
+            KeyCode::End | KeyCode::Char('G') => view.scroll_to(u64::MAX),
+            _ => return !self.key_map.contains_key(&(key.code, key.modifiers)),
+        }
+        true
+    }
+
+    fn on_buffer_saved(&mut self) {
+        let name = self.editor_state.file_path.as_ref()
+            .map(|p| p.display().to_string())
+            .unwrap_or_default();
+        self.notify(format!("Saved {}", name));
+        self.after_save();
+    }
+
+    /// Follow-up work once the active buffer was written
+    fn after_save(&mut self) {
+        if let Some(path) = self.editor_state.file_path.clone() {
+            self.publish(WorkspaceEvent::FileSaved(path));
+        }
+    }
+
+    /// Hand `event` to everyone subscribed to it, and whatever they publish in turn
+    pub fn publish(&mut self, event: WorkspaceEvent) {
+        self.events.publish(event);
+        while let Some((subscriber, event)) = self.events.pop() {
+            self.deliver(subscriber, event);
+        }
+    }
+
+    fn deliver(&mut self, subscriber: Subscriber, event: WorkspaceEvent) {
+        let active = |app: &Self, path: &Path| app.editor_state.file_path.as_deref() == Some(path);
+        match (subscriber, event) {
+            (Subscriber::Chat, WorkspaceEvent::AiResponseCompleted(message)) => self.push_chat(message),
+            (Subscriber::Recovery, WorkspaceEvent::FileSaved(path)) => {
+                // The buffer id only keys untitled buffers' backups
+                recovery::remove_backup(Some(&path), 0, &self.workspace);
+                if let Some(idx) = self.find_buffer(&path) {
+                    self.buffer_mut(idx).backup_hash = 0;
+                }
+            }
+            (Subscriber::Config, WorkspaceEvent::FileSaved(path)) if Config::is_config_file(&path) => {
+                // A settings change still waiting to be written would overwrite what was just saved
+                self.config_writer.cancel();
+                if active(self, &path) {
+                    self.reload_config_from_editor();
+                }
+            }
+            (Subscriber::Watch, WorkspaceEvent::FileSaved(path)) if self.watch_enabled && path.starts_with(&self.workspace) => {
+                self.run_watch();
+            }
+            (Subscriber::Watch, WorkspaceEvent::CommandFinished { success, .. }) => {
+                self.watch_status = if success { WatchStatus::Passed } else { WatchStatus::Failed };
+            }
+            (Subscriber::Linter, WorkspaceEvent::FileSaved(path)) => self.run_linter(&path),
+            // A commit or checkout changes what the gutter compares against
+            (Subscriber::GitGutter, WorkspaceEvent::FilesChanged) => {
+                self.git_changes_requested = None;
+                self.lens_references_requested = None;
+            }
+            // Picks up a virtualenv or version pin created while running (new shells get it)
+            (Subscriber::Toolchain, WorkspaceEvent::FilesChanged) => self.toolchain = Toolchain::detect(&self.workspace),
+            (Subscriber::Todos, WorkspaceEvent::FilesChanged) if self.todos_loaded => self.scan_todos(),
+            _ => {}
+        }
+    }
+
+    /// Run the linter configured for `path`'s file type in the background
+    fn run_linter(&mut self, path: &Path) {
+        let Some(template) = self.config.linter_for(path) else { return };
+        let Ok(relative) = self.normalize_path(path).strip_prefix(&self.workspace).map(Path::to_path_buf) else { return };
+        let command = lint::command_for(template, &relative);
+        self.lint_run += 1;
+        let run = self.lint_run;
+        self.lint_runs.insert(relative.clone(), run);
+
+        let tx = self.event_tx.clone();
+        let root = self.workspace.clone();
+        thread::spawn(move || {
+            let result = lint::run(&command, &root);
+            let _ = tx.send(AppEvent::LintFinished { path: relative, run, result });
+        });
+    }
+
+    pub fn on_lint_finished(&mut self, path: PathBuf, run: u64, result: Result<Vec<Diagnostic>, String>) {
+        if self.lint_runs.get(&path) != Some(&run) {
+            return;
+        }
+        self.lint_runs.remove(&path);
+        match result {
+            Ok(found) => {
+                self.diagnostics.set_files(DiagnosticSource::Lint, &[path], found);
+                self.clamp_problems_selection();
+            }
+            Err(e) => self.notify(format!("Lint failed: {}", e)),
+        }
+    }
+
+    /// Start or stop serving a read-only view of the session
+    pub fn toggle_mirror(&mut self) {
+        if self.mirror.take().is_some() {
+            self.notify("Stopped sharing the session");
+            return;
+        }
+        match MirrorServer::start(&self.config.mirror_address) {
+            Ok(server) => {
+                let address = server.address();
+                let token = server.token().to_string();
+                self.mirror = Some(server);
+                self.update_mirror();
+                self.notify(format!("Sharing a read-only view: nc {} {}, token {}", address.ip(), address.port(), token));
+            }
+            Err(e) => self.notify(format!("Could not share on {}: {}", self.config.mirror_address, e)),
+        }
+    }
+
+    /// Copy a GitHub/GitLab link to the cursor line, or the selected lines, at the current commit
+    pub fn copy_permalink(&mut self) {
+        let Some(path) = self.editor_state.file_path.as_deref().filter(|_| !self.editor_state.is_scratch()) else {
+            self.notify("Open a file to link to");
+            return;
+        };
+        let path = self.normalize_path(path);
+        let cursor = self.editor_state.cursor_row;
+        let lines = match self.vim_selection().or_else(|| self.editor_state.selection()) {
+            // A selection ending at the start of a line doesn't take that line
+            Some((start, end)) if end.1 == 0 && end.0 > start.0 => (start.0 + 1, end.0),
+            Some((start, end)) => (start.0 + 1, end.0 + 1),
+            None => (cursor + 1, cursor + 1),
+        };
+        let tx = self.event_tx.clone();
+        thread::spawn(move || {
+            let _ = tx.send(AppEvent::PermalinkBuilt(permalink::permalink(&path, lines)));
+        });
+    }
+
+    pub fn on_permalink_built(&mut self, result: Result<(String, bool), String>) {
+        let (url, changed) = match result {
+            Ok(built) => built,
+            Err(e) => return self.notify(format!("No permalink: {}", e)),
+        };
+        let note = if changed || self.editor_state.modified { " (the file has uncommitted changes, so lines may differ)" } else { "" };
+        match self.copy_text(url.clone()) {
+            Ok(()) => self.notify(format!("Copied {}{}", url, note)),
+            Err(_) => self.notify(format!("Permalink (copied to the register): {}{}", url, note)),
+        }
+    }
+
+    /// Write the drawn screen to plain-text and ANSI files in the data dir and
+    /// copy the text to the clipboard
+    pub fn save_snapshot(&mut self, mut buffer: Buffer, redact_chat: bool) {
+        if redact_chat {
+            let layout = ui::app_layout(buffer.area, self);
+            snapshot::redact(&mut buffer, layout.chat_history);
+            snapshot::redact(&mut buffer, layout.chat_input);
+        }
+        let dir = Config::data_dir().join("snapshots");
+        let stamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let base = dir.join(format!("nterm-screen-{}", stamp));
+        let text = snapshot::to_plain(&buffer);
+        let written = std::fs::create_dir_all(&dir)
+            .and_then(|_| std::fs::write(base.with_extension("txt"), &text))
+            .and_then(|_| std::fs::write(base.with_extension("ansi"), snapshot::to_ansi(&buffer)));
+        if let Err(e) = written {
+            self.notify(format!("Could not save the snapshot: {}", e));
+            return;
+        }
+        let copied = match self.set_clipboard_text(text) {
+            Ok(()) => " (text copied to the clipboard)",
+            Err(_) => "",
+        };
+        self.notify(format!("Screen saved to {}.txt and .ansi{}", base.display(), copied));
+    }
+
+    /// Send the current editor and terminal to mirror viewers; called after each redraw
+    pub fn update_mirror(&mut self) {
+        let Some(server) = &self.mirror else { return };
+        let editor = &self.editor_state;
+        let first = editor.cursor_row.saturating_sub(MIRROR_EDITOR_ROWS / 2);
+        let last = (first + MIRROR_EDITOR_ROWS).min(editor.lines.len());
+        let terminal_rows: Vec<Vec<u8>> = match self.terminal_screen.read() {
+            Ok(parser) => {
+                let screen = parser.screen();
+                screen.rows_formatted(0, screen.size().1).collect()
+            }
+            Err(_) => Vec::new(),
+        };
+        let title = format!("{}  Ln {}, Col {}", editor.display_name(), editor.cursor_row + 1, editor.cursor_col + 1);
+        server.publish(mirror::compose(&title, &editor.lines[first..last], first, editor.cursor_row, &terminal_rows));
+    }
+
+    /// Turn watch mode on (running the command right away) or off
+    pub fn toggle_watch(&mut self) {
+        if self.watch_enabled {
+            self.watch_enabled = false;
+            self.stop_watch();
+            self.watch_status = WatchStatus::Idle;
+            self.terminal_tab = TerminalTab::Shell;
+            self.notify("Watch mode off");
+            return;
+        }
+        let Some(command) = self.config.watch_command.clone() else {
+            self.notify("Set \"watch_command\" in the config to use watch mode");
+            return;
+        };
+        self.watch_enabled = true;
+        self.terminal_tab = TerminalTab::Watch;
+        self.notify(format!("Watching: {}", command));
+        self.run_watch();
+    }
+
+    /// Show the processes running in the terminal tab in view
+    pub fn open_process_view(&mut self) {
+        let (name, root) = match self.terminal_tab {
+            TerminalTab::Shell => ("Terminal", self.shell_pid),
+            TerminalTab::Watch => ("Watch", self.watch_pid),
+        };
+        match root {
+            Some(root) => self.process_view = Some(ProcessView::open(name, root)),
+            None if self.terminal_tab == TerminalTab::Watch => self.notify("The watch command isn't running"),
+            None => self.notify("The shell's process id is unknown"),
+        }
+    }
+
+    pub fn handle_process_view_key(&mut self, key: KeyEvent) {
+        let Some(view) = self.process_view.as_mut() else { return };
+        let signal = match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.process_view = None;
+                return;
+            }
+            KeyCode::Up => return view.select(-1),
+            KeyCode::Down => return view.select(1),
+            KeyCode::PageUp => return view.select(-10),
+            KeyCode::PageDown => return view.select(10),
+            KeyCode::Char('r') => return view.refresh(),
+            KeyCode::Char('i') => Signal::Interrupt,
+            KeyCode::Char('t') => Signal::Terminate,
+            KeyCode::Char('k') => Signal::Kill,
+            KeyCode::Char('s') => Signal::Stop,
+            KeyCode::Char('c') => Signal::Continue,
+            _ => return,
+        };
+        let Some(row) = view.rows.get(view.selected) else { return };
+        let (pid, command) = (row.info.pid, row.info.command.clone());
+        let result = process_tree::send_signal(pid, signal);
+        view.refresh();
+        match result {
+            Ok(()) => self.notify(format!("Sent {} to {} ({})", signal.label(), pid, command)),
+            Err(e) => self.notify(format!("Could not send {} to {}: {}", signal.label(), pid, e)),
+        }
+    }
+
+    pub fn switch_terminal_tab(&mut self) {
+        self.terminal_tab = match self.terminal_tab {
+            TerminalTab::Shell if self.watch_enabled => TerminalTab::Watch,
+            _ => TerminalTab::Shell,
+        };
+        self.update_terminal_scrollbar();
+    }
+
+    fn stop_watch(&mut self) {
+        if let Some(mut killer) = self.watch_killer.take() {
+            let _ = killer.kill();
+        }
+    }
+
+    /// Start the watch command in a fresh screen, cancelling a run that is still going
+    fn run_watch(&mut self) {
+        let Some(command) = self.config.watch_command.clone() else { return };
+        self.stop_watch();
+        self.watch_run += 1;
+        let run = self.watch_run;
+
+        let size = self.terminal_screen.read().map(|p| p.screen().size()).unwrap_or((24, 80));
+        if let Ok(mut parser) = self.watch_screen.write() {
+            *parser = tui_term::vt100::Parser::new(size.0, size.1, TERMINAL_SCROLLBACK);
+            parser.process(format!("$ {}\r\n", command).as_bytes());
+        }
+        self.scrollback_rows.1 = 0;
+        let screen = self.watch_screen.clone();
+        let output_tx = self.event_tx.clone();
+        let exit_tx = self.event_tx.clone();
+        let spawned = watch::spawn(
+            &command,
+            &self.workspace,
+            size,
+            move |data| {
+                if let Ok(mut parser) = screen.write() {
+                    parser.process(data);
+                }
+                let _ = output_tx.send(AppEvent::WatchOutput);
+            },
+            move |success| {
+                let _ = exit_tx.send(AppEvent::WatchFinished { run, success });
+            },
+        );
+        match spawned {
+            Ok((killer, pid)) => {
+                self.watch_killer = Some(killer);
+                self.watch_pid = pid;
+                self.watch_status = WatchStatus::Running;
+            }
+            Err(e) => {
+                self.watch_status = WatchStatus::Failed;
+                self.notify(format!("Watch command failed to start: {}", e));
+            }
+        }
+    }
+
+    pub fn on_watch_finished(&mut self, run: u64, success: bool) {
+        if run != self.watch_run || !self.watch_enabled {
+            return;
+        }
+        self.watch_killer = None;
+        self.watch_pid = None;
+        let command = self.config.watch_command.clone().unwrap_or_default();
+        self.publish(WorkspaceEvent::CommandFinished { command, success });
+    }
+
+    /// Switch to `config`, with `key_map` built from its bindings
+    fn apply_config(&mut self, config: Config, key_map: KeyMap) {
+        let theme_changed = config.theme != self.config.theme;
+        let spelling_changed = (config.spellcheck, &config.spell_language) != (self.config.spellcheck, &self.config.spell_language);
+        self.config = config;
+        self.key_map = key_map;
+        self.editor_key_map = keymap::editor_key_map(self.config.keybinding_mode);
+        // Language servers no longer configured stop; failed ones get another try
+        let servers: HashSet<&String> = self.config.language_servers.values().collect();
+        self.lsp_servers.retain(|command, _| servers.contains(command));
+        self.lsp_failed.clear();
+        self.apply_language_settings();
+        if theme_changed {
+            self.apply_theme();
+        }
+        if spelling_changed {
+            self.load_spell_checker();
+        }
+        // Keep the settings panel in sync with the reloaded models
+        let idx = self.settings_model_idx.min(self.config.models.len().saturating_sub(1));
+        self.load_settings_for_model(idx);
+    }
+
+    /// Save a restore point of the config and chat database, unless `session_backups` is 0
+    fn backup_session(&mut self) {
+        self.backup_session_sparing(None);
+    }
+
+    /// `backup_session`, keeping `spare` even if it is past the limit
+    fn backup_session_sparing(&mut self, spare: Option<&RestorePoint>) {
+        self.last_session_backup_at = Instant::now();
+        if self.config.session_backups == 0 {
+            return;
+        }
+        let storage = self.storage.as_deref();
+        if let Err(e) = session_backup::create(&session_backup::backups_dir(), &Config::get_config_path(), storage, self.config.session_backups, spare) {
+            self.notify(format!("Session backup failed: {}", e));
+        }
+    }
+
+    /// List the restore points to pick one to go back to
+    pub fn open_restore_picker(&mut self) {
+        let points = session_backup::list(&session_backup::backups_dir());
+        if points.is_empty() {
+            self.notify("No session restore points yet");
+            return;
+        }
+        self.restore_points = Some(points);
+        self.restore_selected = 0;
+    }
+
+    /// Keys of the restore point picker: arrows move, Enter restores, Esc closes
+    pub fn handle_restore_picker_key(&mut self, key: KeyEvent) {
+        let Some(points) = self.restore_points.as_ref() else { return };
+        match key.code {
+            KeyCode::Esc => self.restore_points = None,
+            KeyCode::Up => self.restore_selected = self.restore_selected.saturating_sub(1),
+            KeyCode::Down => self.restore_selected = (self.restore_selected + 1).min(points.len() - 1),
+            KeyCode::Enter => {
+                let point = points[self.restore_selected].clone();
+                self.restore_points = None;
+                match self.restore_session(&point) {
+                    Ok(()) => self.notify(format!("Session restored from {}", point.label())),
+                    Err(e) => self.notify(format!("Could not restore the session: {}", e)),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Go back to the config and chat database of `point`, after saving the
+    /// current state as a restore point of its own. Restored scratch buffers
+    /// that aren't open are reopened.
+    fn restore_session(&mut self, point: &RestorePoint) -> Result<(), String> {
+        let config = point.config()?;
+        self.backup_session_sparing(Some(point));
+        if let (Some(database), Some(storage)) = (point.database(), self.storage.as_mut()) {
+            storage.restore_from(&database)?;
+            let prompts = storage.recent_prompts(&self.workspace, prompt_history::MAX_PROMPTS).unwrap_or_default();
+            let scratch = storage.load_scratch(&self.workspace).unwrap_or_default();
+            self.prompt_history = PromptHistory::new(prompts);
+            let open: Vec<String> = self.scratch_buffers().into_iter().map(|b| b.name).collect();
+            for buffer in scratch.into_iter().filter(|b| !open.contains(&b.name)) {
+                self.buffers.push(EditorState::scratch(buffer.name, buffer.lines));
+            }
+        }
+        if let Some(config) = config {
+            let (key_map, _) = keymap::build_key_map(&config.keybindings);
+            self.apply_config(config, key_map);
+            workspace::set_excluded(&self.workspace, &self.config.excluded_dirs(&self.workspace));
+            self.save_config();
+        }
+        Ok(())
+    }
+
+    /// Apply the config file open in the editor; problems are shown inline on the offending lines
+    pub fn reload_config_from_editor(&mut self) {
+        let Some(path) = self.editor_state.file_path.clone() else { return };
+        let content = self.editor_state.contents();
+
+        let config = match Config::from_json(&content) {
+            Ok(config) => config,
+            Err(e) => {
+                self.diagnostics.set_file(DiagnosticSource::Config, &path, vec![config_diagnostic(&path, &e)]);
+                self.notify(format!("Config not applied: {}", e));
+                return;
+            }
+        };
+
+        // Invalid key bindings are reported but don't block the rest of the config
+        let (key_map, errors) = keymap::build_key_map(&config.keybindings);
+        let problems: Vec<Diagnostic> = errors.iter()
+            .map(|(spec, message)| {
+                let needle = format!("\"{}\"", spec);
+                let line = content.lines().position(|l| l.contains(&needle)).map(|i| i + 1).unwrap_or(1);
+                config_diagnostic(&path, &ConfigError { line, column: 1, message: format!("{}: {}", spec, message) })
+            })
+            .collect();
+        let problem_count = problems.len();
+        self.diagnostics.set_file(DiagnosticSource::Config, &path, problems);
+
+        self.apply_config(config, key_map);
+
+        if problem_count == 0 {
+            self.notify("Config reloaded");
+        } else {
+            self.notify(format!("Config reloaded with {} invalid key binding(s)", problem_count));
+        }
+    }
+
+    /// Load the configured hunspell dictionary in the background, or drop it when spell checking is off
+    pub fn load_spell_checker(&mut self) {
+        self.spell_checker = None;
+        if !self.config.spellcheck {
+            return;
+        }
+        let language = self.config.spell_language.clone();
+        let root = self.workspace.clone();
+        let tx = self.event_tx.clone();
+        thread::spawn(move || {
+            let checker = SpellChecker::load(&language).map(|mut checker| {
+                checker.load_custom(&root);
+                checker
+            });
+            let _ = tx.send(AppEvent::SpellCheckerLoaded(checker));
+        });
+    }
+
+    pub fn on_spell_checker_loaded(&mut self, result: Result<SpellChecker, String>) {
+        // Spell checking may have been turned off while the dictionary loaded
+        if !self.config.spellcheck {
+            return;
+        }
+        match result {
+            Ok(checker) => self.spell_checker = Some(checker),
+            Err(e) => self.notify(format!("Spell checking unavailable: {}", e)),
+        }
+    }
+
+    /// Misspelled words in the prose parts of the editor lines from `first` on, as (row, start, end)
+    fn editor_misspellings(&self, first: usize, count: usize) -> Vec<(usize, usize, usize)> {
+        let Some(checker) = &self.spell_checker else { return Vec::new() };
+        let Some(kind) = self.editor_state.file_path.as_deref().and_then(ProseKind::for_path) else {
+            return Vec::new();
+        };
+        // Code fences above the window decide whether it starts inside one
+        let mut in_fence = false;
+        if kind == ProseKind::Markdown {
+            for line in self.editor_state.lines.iter().take(first) {
+                spell::prose_range(line, kind, &mut in_fence);
+            }
+        }
+        let mut found = Vec::new();
+        for (row, line) in self.editor_state.lines.iter().enumerate().skip(first).take(count) {
+            let Some((start, end)) = spell::prose_range(line, kind, &mut in_fence) else { continue };
+            let prose: String = line.chars().take(end).skip(start).collect();
+            let prose = if kind == ProseKind::Markdown { spell::strip_inline_code(&prose) } else { prose };
+            found.extend(checker.misspelled(&prose).into_iter().map(|(s, e)| (row, start + s, start + e)));
+        }
+        found
+    }
+
+    /// Underlines for misspellings around the visible part of the editor
+    fn spelling_highlights(&self) -> Vec<RangeHighlight> {
+        let style = Style::default().fg(Color::Red).add_modifier(Modifier::UNDERLINED);
+        self.editor_misspellings(self.editor_state.scroll_offset, 200).into_iter()
+            .map(|(line, start, end)| RangeHighlight { line, start, end, style })
+            .collect()
+    }
+
+    /// Search pattern matching the misspelled words typed into the chat input
+    pub fn chat_misspelling_pattern(&self) -> Option<String> {
+        let checker = self.spell_checker.as_ref()?;
+        let mut words: Vec<String> = Vec::new();
+        for line in self.chat_input.lines() {
+            let line = spell::strip_inline_code(line);
+            for (start, end) in checker.misspelled(&line) {
+                let word: String = line.chars().skip(start).take(end - start).collect();
+                if !words.contains(&word) {
+                    words.push(word);
+                }
+            }
+        }
+        if words.is_empty() {
+            return None;
+        }
+        let alternatives: Vec<String> = words.iter().map(|w| regex::escape(w)).collect();
+        Some(format!(r"\b(?:{})\b", alternatives.join("|")))
+    }
+
+    /// The language server for the current file's type, started on first use,
+    /// with the file's absolute path
+    fn language_server(&mut self) -> Option<(String, PathBuf)> {
+        if self.editor_state.loading || self.editor_state.preview.is_some() {
+            return None;
+        }
+        let path = self.editor_state.file_path.as_deref().map(|p| self.normalize_path(p))?;
+        let command = self.config.language_server_for(&path)?.to_string();
+        if self.lsp_failed.contains(&command) {
+            return None;
+        }
+        if !self.lsp_servers.contains_key(&command) {
+            let tx = self.event_tx.clone();
+            let server = command.clone();
+            let started = LspClient::start(&command, &self.workspace, move |message| {
+                let _ = tx.send(AppEvent::Lsp { server: server.clone(), message });
+            });
+            match started {
+                Ok(client) => {
+                    self.lsp_servers.insert(command.clone(), client);
+                }
+                Err(e) => {
+                    self.lsp_failed.insert(command);
+                    self.notify(format!("Language server failed: {}", e));
+                    return None;
+                }
+            }
+        }
+        Some((command, path))
+    }
+
+    /// Open the current file on its language server, so answers are ready sooner
+    fn sync_language_server(&mut self) {
+        let Some((command, path)) = self.language_server() else { return };
+        if let Some(client) = self.lsp_servers.get_mut(&command) {
+            client.sync_document(&path, &self.editor_state.lines);
+        }
+    }
+
+    pub fn on_lsp_message(&mut self, server: String, message: LspMessage) {
+        match message {
+            LspMessage::Response { id, result } => {
+                if let Some(request) = self.location_request.take_if(|r| r.server == server && r.id == id) {
+                    return self.show_locations(request, result);
+                }
+                if let Some(request) = self.rename_request.take_if(|r| r.server == server && r.id == id) {
+                    return self.show_rename(request, result);
+                }
+                if let Some(request) = self.outline_request.take_if(|r| r.server == server && r.id == id) {
+                    return self.show_outline_symbols(request, result);
+                }
+                if let Some(request) = self.format_request.take_if(|r| r.server == server && r.id == id) {
+                    return self.on_lsp_formatted(request, result);
+                }
+                if let Some(request) = self.inlay_hint_request.take_if(|r| r.server == server && r.id == id) {
+                    return self.on_inlay_hints(request, result);
+                }
+                let Some(request) = self.completion_request.take_if(|r| r.server == server && r.id == id) else { return };
+                match result {
+                    Ok(reply) => self.show_completions(request, &reply),
+                    Err(e) if request.explicit => self.notify(format!("Completion failed: {}", e)),
+                    Err(_) => {}
+                }
+            }
+            LspMessage::Exited => {
+                if self.lsp_servers.remove(&server).is_some() {
+                    self.lsp_failed.insert(server.clone());
+                    self.notify(format!("Language server `{}` exited", server));
+                }
+            }
+            LspMessage::Notification { .. } => {}
+        }
+    }
+
+    /// Ask for completions at the cursor (Ctrl+Space)
+    pub fn request_completion(&mut self) {
+        if self.language_server().is_none() {
+            self.notify("No language server for this file type (set \"language_servers\" in the config)");
+            return;
+        }
+        self.ask_for_completion(None, true);
+    }
+
+    /// Ask where the symbol at the cursor is defined (Alt+D, Ctrl+Click), or
+    /// for all its references (Alt+R)
+    pub fn request_locations(&mut self, references: bool) {
+        let Some((server, path)) = self.language_server() else {
+            self.notify("No language server for this file type (set \"language_servers\" in the config)");
+            return;
+        };
+        let Some(symbol) = self.editor_state.word_at_cursor() else {
+            self.notify("No symbol under the cursor");
+            return;
+        };
+        let Some(client) = self.lsp_servers.get_mut(&server) else { return };
+        let (row, col) = (self.editor_state.cursor_row, self.editor_state.cursor_col);
+        let id = if references {
+            client.references(&path, &self.editor_state.lines, row, col)
+        } else {
+            client.definition(&path, &self.editor_state.lines, row, col)
+        };
+        self.location_request = Some(LocationRequest { server, id, references, symbol });
+    }
+
+    /// Jump to the one definition found, or list the references (or the
+    /// several definitions) to pick from
+    fn show_locations(&mut self, request: LocationRequest, result: Result<serde_json::Value, String>) {
+        let what = if request.references { "References" } else { "Definition" };
+        let locations = match result {
+            Ok(reply) => lsp::parse_locations(&reply),
+            Err(e) => return self.notify(format!("{} failed: {}", what, e)),
+        };
+        let path = self.editor_state.file_path.as_deref().map(|p| self.normalize_path(p));
+        let open = path.as_deref().map(|p| (p, self.editor_state.lines.as_slice()));
+        let found = references::resolve(&locations, open);
+        match found.as_slice() {
+            [] if request.references => self.notify(format!("No references to `{}` found", request.symbol)),
+            [] => self.notify(format!("No definition of `{}` found", request.symbol)),
+            [only] if !request.references => {
+                let (path, row, col) = (only.path.clone(), only.row, only.col);
+                self.open_location(&path, row + 1, col + 1);
+            }
+            _ if request.references => {
+                self.reference_list = Some(ReferenceList::new(format!("References to `{}`", request.symbol), found));
+            }
+            _ => self.reference_list = Some(ReferenceList::new(format!("Definitions of `{}`", request.symbol), found)),
+        }
+    }
+
+    pub fn handle_reference_list_key(&mut self, key: KeyEvent) {
+        let Some(list) = self.reference_list.as_mut() else { return };
+        match key.code {
+            KeyCode::Esc => self.reference_list = None,
+            KeyCode::Up => list.select(-1),
+            KeyCode::Down => list.select(1),
+            KeyCode::PageUp => list.select(-10),
+            KeyCode::PageDown => list.select(10),
+            KeyCode::Enter => {
+                let Some(reference) = list.selected_reference() else { return };
+                let (path, row, col) = (reference.path.clone(), reference.row, reference.col);
+                self.reference_list = None;
+                self.open_location(&path, row + 1, col + 1);
+            }
+            _ => {}
+        }
+    }
+
+    /// Format the open file (Alt+L) with its language server, or else the
+    /// formatter configured for its type
+    pub fn format_document(&mut self) {
+        if self.refuse_read_only() {
+            return;
+        }
+        let Some(path) = self.editor_state.file_path.clone().filter(|_| !self.editor_state.is_scratch()) else {
+            self.notify("Open a file to format");
+            return;
+        };
+        if let Some((server, absolute)) = self.language_server() {
+            let indent = self.editor_state.indent;
+            if let Some(client) = self.lsp_servers.get_mut(&server).filter(|c| c.provides("documentFormattingProvider")) {
+                let id = client.formatting(&absolute, &self.editor_state.lines, indent.width, !indent.tabs);
+                self.format_request = Some(FormatRequest { server, id, path, text: self.editor_state.lines.join("\n") });
+                return;
+            }
+        }
+        if !self.run_formatter(&path) {
+            self.notify("No formatter for this file type (set \"formatters\" or \"language_servers\" in the config)");
+        }
+    }
+
+    /// Run the formatter configured for `path`'s type on the buffer in the
+    /// background; false if there is none
+    fn run_formatter(&mut self, path: &Path) -> bool {
+        let Some(template) = self.config.formatter_for(path) else { return false };
+        let command = lint::command_for(template, &self.normalize_path(path));
+        let text = self.editor_state.lines.join("\n");
+        let tx = self.event_tx.clone();
+        let root = self.workspace.clone();
+        let path = path.to_path_buf();
+        thread::spawn(move || {
+            let result = format::run(&command, &root, &format!("{}\n", text));
+            let _ = tx.send(AppEvent::Formatted { path, text, result });
+        });
+        true
+    }
+
+    fn on_lsp_formatted(&mut self, request: FormatRequest, result: Result<serde_json::Value, String>) {
+        match result {
+            Ok(reply) => {
+                let Some(lines) = self.unchanged_since_format(&request.path, &request.text) else { return };
+                let edits: Vec<TextEdit> = reply.as_array().into_iter().flatten()
+                    .filter_map(|edit| TextEdit::from_json(edit, &lines))
+                    .collect();
+                self.apply_formatting(&lines, &format::apply_edits(&lines, &edits));
+            }
+            // e.g. a syntax error; the configured formatter may still manage
+            Err(e) => {
+                if !self.run_formatter(&request.path) {
+                    self.notify(format!("Format failed: {}", e));
+                }
+            }
+        }
+    }
+
+    pub fn on_formatted(&mut self, path: PathBuf, text: String, result: Result<String, String>) {
+        match result {
+            Ok(formatted) => {
+                let Some(lines) = self.unchanged_since_format(&path, &text) else { return };
+                self.apply_formatting(&lines, &formatted);
+            }
+            Err(e) => self.notify(format!("Format failed: {}", e)),
+        }
+    }
+
+    /// The buffer's lines if it still shows `path` with `text`, as when
+    /// formatting was asked for
+    fn unchanged_since_format(&mut self, path: &Path, text: &str) -> Option<Vec<String>> {
+        if self.editor_state.file_path.as_deref() != Some(path) {
+            return None;
+        }
+        if self.editor_state.lines.join("\n") != text {
+            self.notify("The file changed while it was being formatted; format it again");
+            return None;
+        }
+        Some(self.editor_state.lines.clone())
+    }
+
+    /// Replace the lines that formatting changed, keeping the cursor where it was
+    fn apply_formatting(&mut self, lines: &[String], formatted: &str) {
+        let name = self.editor_state.display_name();
+        let Some(edit) = format::edit_for(lines, formatted) else {
+            self.notify(format!("{} is already formatted", name));
+            return;
+        };
+        let (row, col) = (self.editor_state.cursor_row, self.editor_state.cursor_col);
+        let scroll = self.editor_state.scroll_offset;
+        self.editor_state.replace_text(edit.start, edit.end, &edit.new_text);
+        self.editor_state.place_cursor(row, col, false);
+        self.editor_state.scroll_offset = scroll.min(self.editor_state.lines.len().saturating_sub(1));
+        self.last_edit_at = Instant::now();
+        self.notify(format!("Formatted {}", name));
+    }
+
+    /// Show the symbols of the open file (Alt+O): found by patterns at once,
+    /// then replaced by the language server's when they arrive
+    pub fn open_outline(&mut self) {
+        let language = self.editor_state.file_path.as_deref()
+            .and_then(|p| p.extension())
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let symbols = outline::scan(&language, &self.editor_state.lines);
+        let server = self.language_server();
+        if symbols.is_empty() && server.is_none() {
+            self.notify("No symbols found in this file");
+            return;
+        }
+        let row = self.editor_state.cursor_row;
+        self.outline = Some(Outline::new(self.editor_state.display_name(), symbols, false, row));
+        let Some((server, path)) = server else { return };
+        let Some(client) = self.lsp_servers.get_mut(&server) else { return };
+        let id = client.document_symbols(&path, &self.editor_state.lines);
+        self.outline_request = Some(OutlineRequest { server, id, path });
+    }
+
+    /// Put the language server's symbols in the outline, keeping the ones
+    /// found by patterns when it has none
+    fn show_outline_symbols(&mut self, request: OutlineRequest, result: Result<serde_json::Value, String>) {
+        let current = self.editor_state.file_path.as_deref().map(|p| self.normalize_path(p));
+        if current.as_deref() != Some(request.path.as_path()) {
+            return;
+        }
+        let symbols = result.map(|reply| outline::from_lsp(&reply, &self.editor_state.lines)).unwrap_or_default();
+        let row = self.editor_state.cursor_row;
+        let Some(outline) = self.outline.as_mut() else { return };
+        if !symbols.is_empty() {
+            outline.set_symbols(symbols, true, row);
+        } else if outline.symbols.is_empty() {
+            self.outline = None;
+            self.notify("No symbols found in this file");
+        }
+    }
+
+    pub fn handle_outline_key(&mut self, key: KeyEvent) {
+        let Some(outline) = self.outline.as_mut() else { return };
+        match key.code {
+            KeyCode::Esc => self.outline = None,
+            KeyCode::Up => outline.select(-1),
+            KeyCode::Down => outline.select(1),
+            KeyCode::PageUp => outline.select(-10),
+            KeyCode::PageDown => outline.select(10),
+            KeyCode::Backspace => {
+                let mut query = outline.query.clone();
+                query.pop();
+                outline.set_query(query);
+            }
+            KeyCode::Char(c) if !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+                let query = format!("{}{}", outline.query, c);
+                outline.set_query(query);
+            }
+            KeyCode::Enter => {
+                let Some(symbol) = outline.selected_symbol() else { return };
+                let (row, col) = (symbol.row, symbol.col);
+                self.outline = None;
+                self.outline_request = None;
+                self.editor_state.goto(row, col);
+                self.active_panel = ActivePanel::Editor;
+            }
+            _ => {}
+        }
+    }
+
+    /// Send a completion request for the cursor position; `trigger` is the
+    /// trigger character just typed, `explicit` whether the user asked
+    fn ask_for_completion(&mut self, trigger: Option<char>, explicit: bool) {
+        let Some((server, path)) = self.language_server() else { return };
+        let Some(client) = self.lsp_servers.get_mut(&server) else { return };
+        let row = self.editor_state.cursor_row;
+        let id = client.completion(&path, &self.editor_state.lines, row, self.editor_state.cursor_col, trigger);
+        self.completion_request = Some(CompletionRequest { server, id, path, row, explicit });
+    }
+
+    /// Open the popup with the items of a completion reply, unless the user
+    /// moved on to another line or buffer while waiting
+    fn show_completions(&mut self, request: CompletionRequest, reply: &serde_json::Value) {
+        let path = self.editor_state.file_path.as_deref().map(|p| self.normalize_path(p));
+        if path.as_ref() != Some(&request.path) || self.editor_state.cursor_row != request.row || self.active_panel != ActivePanel::Editor {
+            return;
+        }
+        let items = lsp::parse_completions(reply, &self.editor_state.lines);
+        let start = self.completion_word_start();
+        let completion = Completion::new(items, request.path, request.row, start, &self.completion_query(start));
+        if completion.matches.is_empty() {
+            if request.explicit {
+                self.notify("No completions");
+            }
+            self.completion = None;
+        } else {
+            self.completion = Some(completion);
+        }
+    }
+
+    /// Column where the identifier before the cursor starts
+    fn completion_word_start(&self) -> usize {
+        let before: Vec<char> = self.editor_state.current_line().chars().take(self.editor_state.cursor_col).collect();
+        before.iter().rposition(|&c| !is_identifier_char(c)).map_or(0, |i| i + 1)
+    }
+
+    /// Text typed from `start` to the cursor
+    fn completion_query(&self, start: usize) -> String {
+        let col = self.editor_state.cursor_col;
+        self.editor_state.current_line().chars().skip(start).take(col.saturating_sub(start)).collect()
+    }
+
+    /// Keep the completion popup in step with typing, or ask for completions:
+    /// after a trigger character such as `.`, or on the first letter of a word
+    fn update_completion(&mut self, key: KeyEvent) {
+        let (row, col) = (self.editor_state.cursor_row, self.editor_state.cursor_col);
+        if let Some(mut completion) = self.completion.take() {
+            let query = self.completion_query(completion.start);
+            if row == completion.row && col >= completion.start && query.chars().all(is_identifier_char) {
+                completion.filter(&query);
+                if !completion.matches.is_empty() {
+                    self.completion = Some(completion);
+                    return;
+                }
+            }
+        }
+
+        let KeyCode::Char(c) = key.code else { return };
+        let typing = !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+            && (!self.vim_enabled() || self.vim.mode == VimMode::Insert)
+            && col > 0 && self.editor_state.current_line().chars().nth(col - 1) == Some(c);
+        if !typing {
+            return;
+        }
+        let Some((server, _)) = self.language_server() else { return };
+        let triggers = self.lsp_servers.get(&server).map(LspClient::trigger_characters).unwrap_or_default();
+        if triggers.iter().any(|t| *t == c.to_string()) {
+            self.ask_for_completion(Some(c), false);
+        } else if is_identifier_char(c) && col - self.completion_word_start() == 1 {
+            self.ask_for_completion(None, false);
+        }
+    }
+
+    /// Whether the completion popup is showing: it belongs to the current
+    /// buffer and the cursor is still in the word it completes
+    pub fn completion_visible(&self) -> bool {
+        let Some(completion) = &self.completion else { return false };
+        self.active_panel == ActivePanel::Editor && !self.show_settings && self.diff_view.is_none() && self.hex_view.is_none()
+            && self.editor_state.cursor_row == completion.row && self.editor_state.cursor_col >= completion.start
+            && self.editor_state.file_path.as_deref().map(|p| self.normalize_path(p)).as_ref() == Some(&completion.path)
+    }
+
+    /// Keys for the completion popup; false for keys it doesn't use, which go on
+    /// to the editor (typing keeps filtering it)
+    pub fn handle_completion_key(&mut self, key: KeyEvent) -> bool {
+        if !self.completion_visible() || key.modifiers != KeyModifiers::NONE {
+            return false;
+        }
+        let Some(completion) = self.completion.as_mut() else { return false };
+        match key.code {
+            KeyCode::Up => completion.select(-1),
+            KeyCode::Down => completion.select(1),
+            KeyCode::Enter | KeyCode::Tab => self.accept_completion(),
+            KeyCode::Esc => self.completion = None,
+            _ => return false,
+        }
+        true
+    }
+
+    /// Insert the selected completion: its edit, stretched over anything typed
+    /// since it was asked for, and the edits it needs elsewhere such as an import
+    fn accept_completion(&mut self) {
+        let Some(completion) = self.completion.take() else { return };
+        let Some(item) = completion.selected_item() else { return };
+        if self.refuse_read_only() {
+            return;
+        }
+        let cursor = (self.editor_state.cursor_row, self.editor_state.cursor_col);
+        let main = match &item.edit {
+            Some(edit) => TextEdit { start: edit.start, end: edit.end.max(cursor), new_text: edit.new_text.clone() },
+            None => TextEdit { start: (completion.row, completion.start), end: cursor, new_text: item.insert_text.clone() },
+        };
+        let mut edits: Vec<(&TextEdit, bool)> = item.additional_edits.iter().map(|e| (e, false)).chain([(&main, true)]).collect();
+        edits.sort_by_key(|(edit, _)| std::cmp::Reverse(edit.start));
+        let mut cursor = main.start;
+        for (edit, is_main) in edits {
+            let end = self.editor_state.replace_text(edit.start, edit.end, &edit.new_text);
+            if is_main {
+                cursor = end;
+            } else if edit.end.0 < main.start.0 {
+                // Lines added or removed above move the inserted text
+                cursor.0 = (cursor.0 + end.0).saturating_sub(edit.end.0);
+            }
+        }
+        self.editor_state.extra_cursors.clear();
+        self.editor_state.place_cursor(cursor.0, cursor.1, false);
+        self.last_edit_at = Instant::now();
+    }
+
+    /// Show suggestions for the misspelled word under the editor cursor
+    pub fn open_spell_suggestions(&mut self) {
+        let Some(checker) = &self.spell_checker else {
+            self.notify("Spell checking is off (set \"spellcheck\": true in the config)");
+            return;
+        };
+        let (row, col) = (self.editor_state.cursor_row, self.editor_state.cursor_col);
+        let target = self.editor_misspellings(row, 1).into_iter()
+            .find(|&(_, start, end)| (start..=end).contains(&col));
+        let Some((row, start, end)) = target else {
+            self.notify("No misspelling at the cursor");
+            return;
+        };
+        let word: String = self.editor_state.current_line().chars().skip(start).take(end - start).collect();
+        self.spell_suggestions = checker.suggest(&word, 8);
+        self.spell_target = (row, start, end);
+        self.spell_selected = 0;
+        self.show_spell = true;
+    }
+
+    /// The misspelled word the suggestion popup is for
+    pub fn spell_word(&self) -> String {
+        let (row, start, end) = self.spell_target;
+        self.editor_state.lines.get(row)
+            .map(|line| line.chars().skip(start).take(end - start).collect())
+            .unwrap_or_default()
+    }
+
+    /// Replace the misspelled word with the selected suggestion
+    pub fn apply_spell_suggestion(&mut self) {
+        self.show_spell = false;
+        let Some(replacement) = self.spell_suggestions.get(self.spell_selected).cloned() else { return };
+        let (row, start, end) = self.spell_target;
+        self.editor_state.extra_cursors.clear();
+        self.editor_state.delete_range((row, start), (row, end));
+        self.editor_state.goto(row, start);
+        self.editor_state.paste(&replacement);
+    }
+
+    /// Accept the misspelled word from now on in this workspace
+    pub fn add_spell_word(&mut self) {
+        self.show_spell = false;
+        let word = self.spell_word();
+        let Some(checker) = self.spell_checker.as_mut() else { return };
+        match checker.add_word(&self.workspace, &word) {
+            Ok(()) => self.notify(format!("Added \"{}\" to the workspace dictionary", word)),
+            Err(e) => self.notify(format!("Could not add word: {}", e)),
+        }
+    }
+
+    /// Diagnostics for the open file, as inline editor annotations
+    pub fn editor_annotations(&self) -> Vec<LineAnnotation> {
+        let Some(path) = self.editor_state.file_path.as_deref() else { return Vec::new() };
+        let mut annotations = self.color_swatches(path);
+        annotations.extend(self.code_lenses(path));
+        annotations.extend(self.review_annotations());
+        annotations.extend(self.current_diagnostics().into_iter()
+            .filter(|d| d.severity >= Severity::Warning)
+            .map(|d| LineAnnotation {
+                line: d.line.saturating_sub(1),
+                text: format!("{} {}", d.severity.marker(), d.message),
+                style: Style::default().fg(severity_color(d.severity)).add_modifier(Modifier::ITALIC),
+                action: None,
+            }));
+        annotations
+    }
+
+    /// Diagnostics from every source (language servers, builds, linters) for the open file, by line
+    pub fn current_diagnostics(&self) -> Vec<&Diagnostic> {
+        let Some(current) = self.editor_state.file_path.as_deref().map(|p| self.normalize_path(p)) else { return Vec::new() };
+        self.diagnostics.filtered(Severity::Hint).into_iter()
+            .filter(|d| self.normalize_path(&d.path) == current)
+            .collect()
+    }
+
+    /// Underlines under the words diagnostics point at
+    fn diagnostic_highlights(&self) -> Vec<RangeHighlight> {
+        self.current_diagnostics().into_iter()
+            .filter_map(|d| {
+                let line = d.line.checked_sub(1)?;
+                let (start, end) = d.span_in(self.editor_state.lines.get(line)?);
+                let style = Style::default().add_modifier(Modifier::UNDERLINED).underline_color(severity_color(d.severity));
+                Some(RangeHighlight { line, start, end, style })
+            })
+            .collect()
+    }
+
+    /// Gutter icons on lines with diagnostics, the most severe one's color
+    fn diagnostic_gutter_markers(&self) -> Vec<GutterMarker> {
+        let mut worst: BTreeMap<usize, Severity> = BTreeMap::new();
+        for d in self.current_diagnostics() {
+            let entry = worst.entry(d.line.saturating_sub(1)).or_insert(d.severity);
+            *entry = (*entry).max(d.severity);
+        }
+        worst.into_iter()
+            .map(|(line, severity)| GutterMarker { line, symbol: "●", style: Style::default().fg(severity_color(severity)) })
+            .collect()
+    }
+
+    /// Move the cursor to the next (or previous) diagnostic in the open file,
+    /// wrapping around, and show its message
+    pub fn jump_to_diagnostic(&mut self, forward: bool) {
+        let positions: Vec<(usize, usize)> = self.current_diagnostics().iter()
+            .map(|d| (d.line.saturating_sub(1), d.column.saturating_sub(1)))
+            .collect();
+        let cursor = (self.editor_state.cursor_row, self.editor_state.cursor_col);
+        let target = if forward {
+            positions.iter().find(|&&p| p > cursor).or(positions.first())
+        } else {
+            positions.iter().rev().find(|&&p| p < cursor).or(positions.last())
+        };
+        match target {
+            Some(&(line, column)) => {
+                self.editor_state.goto(line, column);
+                self.active_panel = ActivePanel::Editor;
+                self.show_diagnostic_popup = true;
             }
+            None => self.notify("No problems in this file"),
         }
     }
 
-    pub fn load_file_path(&mut self, path: PathBuf) {
-        let _ = self.editor_state.load_file(path);
+    /// Show the problems on the cursor line in a popup, or hide it
+    pub fn toggle_diagnostic_popup(&mut self) {
+        if !self.show_diagnostic_popup && self.cursor_line_diagnostics().is_empty() {
+            self.notify("No problems on this line");
+            return;
+        }
+        self.show_diagnostic_popup = !self.show_diagnostic_popup;
     }
 
-    pub fn on_search_input(&mut self) {
-        let query = self.search_input.lines().join(" ");
-        if query.trim().is_empty() {
-            self.search_results.clear();
+    /// Diagnostics on the cursor line, most severe first
+    pub fn cursor_line_diagnostics(&self) -> Vec<&Diagnostic> {
+        let mut found: Vec<&Diagnostic> = self.current_diagnostics().into_iter()
+            .filter(|d| d.line.saturating_sub(1) == self.editor_state.cursor_row)
+            .collect();
+        found.sort_by_key(|d| std::cmp::Reverse(d.severity));
+        found
+    }
+
+    /// A swatch after each line with color literals, for the lines around the viewport
+    fn color_swatches(&self, path: &Path) -> Vec<LineAnnotation> {
+        if !color::supports(syntax::language_key(path).as_deref()) {
+            return Vec::new();
+        }
+        let editor = &self.editor_state;
+        let start = editor.scroll_offset;
+        editor.lines.iter().enumerate().skip(start).take(SWATCH_SCAN_LINES)
+            .flat_map(|(line, text)| color::find_colors(text).into_iter().map(move |c| LineAnnotation {
+                line,
+                text: "■".to_string(),
+                style: Style::default().fg(Color::Rgb(c.rgb[0], c.rgb[1], c.rgb[2])),
+                action: None,
+            }))
+            .collect()
+    }
+
+    /// Clickable lenses after each function definition around the viewport
+    fn code_lenses(&self, path: &Path) -> Vec<LineAnnotation> {
+        let Some(language) = syntax::language_key(path) else { return Vec::new() };
+        if !self.config.code_lens || !code_lens::supports(Some(&language)) {
+            return Vec::new();
+        }
+        let editor = &self.editor_state;
+        let current = self.normalize_path(path);
+        let references = self.lens_references.as_ref().filter(|(p, _)| *p == current).map(|(_, counts)| counts);
+        let style = Style::default().fg(self.current_theme.line_number).add_modifier(Modifier::UNDERLINED);
+        let start = editor.scroll_offset;
+        code_lens::find_symbols(&language, &editor.lines, start..start + SWATCH_SCAN_LINES).into_iter()
+            .flat_map(|symbol| {
+                let count = references.map(|counts| counts.get(&symbol.name).copied().unwrap_or(0));
+                code_lens::lenses(&symbol, count).into_iter().map(move |(action, text)| LineAnnotation {
+                    line: symbol.line,
+                    text,
+                    style,
+                    action: Some(AnnotationAction::Lens(action)),
+                })
+            })
+            .collect()
+    }
+
+    /// Run the code lens `action` on the function defined at `line` of the open file
+    pub fn run_code_lens(&mut self, line: usize, action: LensAction) {
+        let Some(path) = self.editor_state.file_path.clone() else { return };
+        let Some(language) = syntax::language_key(&path) else { return };
+        let Some(symbol) = code_lens::find_symbols(&language, &self.editor_state.lines, line..line + 1).pop() else { return };
+        match action {
+            LensAction::RunTest => {
+                let relative = self.normalize_path(&path).strip_prefix(&self.workspace).map(Path::to_path_buf).unwrap_or(path);
+                let Some(command) = code_lens::test_command(&language, &relative, &symbol.name) else { return };
+                if let Err(e) = self.write_to_pty(format!("{}\r", command).as_bytes()) {
+                    self.notify(format!("Could not run {}: {}", symbol.name, e));
+                    return;
+                }
+                self.terminal_tab = TerminalTab::Shell;
+                self.active_panel = ActivePanel::Terminal;
+            }
+            LensAction::Explain => {
+                let body = code_lens::symbol_body(&language, &self.editor_state.lines, line);
+                self.send_chat_message(code_lens::explain_prompt(&path, &language, &symbol, &body));
+                self.active_panel = ActivePanel::Chat;
+            }
+            LensAction::References => {
+                let mut palette = SearchPalette::new();
+                palette.input.insert_str(&symbol.name);
+                self.search_palette = Some(palette);
+                self.on_search_palette_input();
+            }
+        }
+    }
+
+    /// Ask the AI to review the open file, focusing on the lines changed since HEAD
+    pub fn start_review(&mut self) {
+        let Some(path) = self.editor_state.file_path.clone() else {
+            self.notify("Save the file before reviewing it");
+            return;
+        };
+        if self.review_pending {
+            self.notify("A review is already running");
             return;
         }
-        
-        let query_lower = query.to_lowercase();
-        self.search_results = WalkDir::new(".")
-            .into_iter()
-            .filter_map(|e| e.ok())
-            .filter(|e| e.file_type().is_file())
-            .filter(|e| !e.path().to_string_lossy().starts_with("./.git"))
-            .filter(|e| !e.path().to_string_lossy().contains("/target/"))
-            .filter(|e| {
-                e.file_name()
-                    .to_str()
-                    .map(|s| s.to_lowercase().contains(&query_lower))
-                    .unwrap_or(false)
+        let lines = self.editor_state.lines.clone();
+        self.review_pending = true;
+        self.notify(format!("Asking the AI to review {}...", path.display()));
+
+        let tx = self.event_tx.clone();
+        let (root, algorithm) = (self.workspace.clone(), self.config.diff_algorithm);
+        let path = self.normalize_path(&path);
+        tokio::task::spawn_blocking(move || {
+            // What changed since HEAD, for the model to focus on
+            let changes = git_gutter::head_lines(&root, &path).map(|head| Diff::new(algorithm, head, lines.clone()));
+            let prompt = review::build_prompt(&path, &lines, changes.as_ref());
+            let _ = tx.send(AppEvent::ReviewPrepared { path, lines, prompt });
+        });
+    }
+
+    pub fn on_review_prepared(&mut self, path: PathBuf, lines: Vec<String>, prompt: String) {
+        self.send_ai_request(AiRequest::Review { path, lines, prompt });
+    }
+
+    fn dispatch_review(&mut self, path: PathBuf, lines: Vec<String>, prompt: String) {
+        let tx = self.event_tx.clone();
+        let model_config = self.config.get_selected_model().clone();
+        tokio::spawn(async move {
+            let result = send_message(&model_config, &[], &prompt).await
+                .map(|reply| review::parse_comments(&reply, &lines));
+            let _ = tx.send(AppEvent::ReviewReceived { path, result });
+        });
+    }
+
+    pub fn on_review_received(&mut self, path: PathBuf, result: Result<Vec<ReviewComment>, String>) {
+        self.review_pending = false;
+        match result {
+            Ok(comments) if comments.is_empty() => {
+                self.review = None;
+                self.notify("Review found nothing to flag");
+            }
+            Ok(comments) => {
+                self.notify(format!("Review: {} comment{}", comments.len(), if comments.len() == 1 { "" } else { "s" }));
+                self.review = Some((path, comments));
+            }
+            Err(e) => self.notify(format!("Review failed: {}", e)),
+        }
+    }
+
+    /// Review comments of the open file, if the latest review was of it
+    fn current_review(&self) -> Option<&[ReviewComment]> {
+        let (path, comments) = self.review.as_ref()?;
+        let current = self.editor_state.file_path.as_deref().map(|p| self.normalize_path(p))?;
+        (*path == current).then_some(comments.as_slice())
+    }
+
+    /// Each review comment after the line it starts at: its first words while
+    /// collapsed, all of it when expanded
+    fn review_annotations(&self) -> Vec<LineAnnotation> {
+        let Some(comments) = self.current_review() else { return Vec::new() };
+        let style = Style::default().fg(Color::Cyan).add_modifier(Modifier::ITALIC);
+        comments.iter().enumerate()
+            .filter_map(|(i, comment)| {
+                let line = comment.locate(&self.editor_state.lines)?;
+                let text = if comment.expanded || comment.text.chars().count() <= REVIEW_PREVIEW_CHARS {
+                    format!("{} {}", if comment.expanded { "▾" } else { "▸" }, comment.text)
+                } else {
+                    format!("▸ {}…", comment.text.chars().take(REVIEW_PREVIEW_CHARS).collect::<String>())
+                };
+                Some(LineAnnotation { line, text, style, action: Some(AnnotationAction::ToggleReview(i)) })
             })
-            .take(20)
-            .map(|e| e.path().to_path_buf())
-            .collect();
-            
-        self.search_state.select(Some(0));
+            .collect()
     }
-    
-    pub fn cycle_model(&mut self) {
-        self.config.cycle_model();
-        let _ = self.config.save();
+
+    /// Expand or collapse the review comment at `idx`
+    pub fn toggle_review_comment(&mut self, idx: usize) {
+        if let Some(comment) = self.review.as_mut().and_then(|(_, comments)| comments.get_mut(idx)) {
+            comment.expanded = !comment.expanded;
+        }
     }
 
-    pub fn get_selected_model_name(&self) -> String {
-        self.config.get_selected_model().display_name()
+    /// Expand or collapse the review comment covering the cursor line
+    pub fn toggle_review_comment_at_cursor(&mut self) {
+        let row = self.editor_state.cursor_row;
+        let found = self.current_review().and_then(|comments| {
+            comments.iter().position(|c| c.locate(&self.editor_state.lines)
+                .is_some_and(|start| (start..=start + c.end - c.start).contains(&row)))
+        });
+        match found {
+            Some(idx) => self.toggle_review_comment(idx),
+            None => self.notify("No review comment on this line"),
+        }
     }
-    
-    pub fn toggle_theme(&mut self) {
-        use crate::shared::ThemeMode;
-        self.config.theme = match self.config.theme {
-            ThemeMode::Light => ThemeMode::Dark,
-            ThemeMode::Dark => ThemeMode::Light,
+
+    pub fn clear_review(&mut self) {
+        if self.review.take().is_some() {
+            self.notify("Review comments cleared");
+        }
+    }
+
+    /// Absolute path without `.` components, for comparing paths from different sources
+    fn normalize_path(&self, path: &Path) -> PathBuf {
+        let joined = if path.is_relative() { self.workspace.join(path) } else { path.to_path_buf() };
+        joined.components().filter(|c| !matches!(c, std::path::Component::CurDir)).collect()
+    }
+
+    /// Scan the workspace for TODO/FIXME/HACK comments on a background thread
+    pub fn scan_todos(&mut self) {
+        if self.todos_scanning {
+            return;
+        }
+        self.todos_scanning = true;
+        self.todos_loaded = true;
+        let tx = self.event_tx.clone();
+        let root = self.workspace.clone();
+        thread::spawn(move || {
+            let _ = tx.send(AppEvent::TodosScanned(todo::scan_workspace(&root)));
+        });
+    }
+
+    pub fn on_todos_scanned(&mut self, items: Vec<TodoItem>) {
+        self.todos = items;
+        self.todos_scanning = false;
+        self.todos_selected = self.todos_selected.min(self.todos.len().saturating_sub(1));
+    }
+
+    /// Recompute the workspace statistics in the background
+    pub fn collect_stats(&mut self) {
+        if self.stats_loading {
+            return;
+        }
+        self.stats_loading = true;
+        let tx = self.event_tx.clone();
+        let root = self.workspace.clone();
+        thread::spawn(move || {
+            let _ = tx.send(AppEvent::StatsCollected(stats::collect(&root)));
+        });
+    }
+
+    pub fn on_stats_collected(&mut self, stats: WorkspaceStats) {
+        self.stats = Some(stats);
+        self.stats_loading = false;
+    }
+
+    /// Put the stats summary into the chat input as context for a prompt
+    pub fn stats_to_chat(&mut self) {
+        let Some(stats) = &self.stats else { return };
+        let context = stats.to_context();
+        self.chat_input.insert_str(context);
+        self.show_stats = false;
+        self.active_panel = ActivePanel::Chat;
+    }
+
+    /// Ask what to do with the symbol under the cursor
+    pub fn open_refactor(&mut self) {
+        if self.refactor_pending {
+            self.notify("A refactor is already being prepared");
+            return;
+        }
+        let Some(symbol) = self.editor_state.word_at_cursor() else {
+            self.notify("Place the cursor on a symbol to refactor");
+            return;
         };
-        self.current_theme = Theme::new(self.config.theme);
-        let _ = self.config.save();
+        let mut input = TextArea::default();
+        input.set_block(Block::default().borders(Borders::ALL)
+            .title(format!(" Refactor `{}` (e.g. \"rename to new_name\"; Enter: Ask AI, Esc: Cancel) ", symbol)));
+        self.refactor_input = input;
+        self.refactor_symbol = symbol;
+        self.show_refactor_prompt = true;
+    }
 
-        // Reset the vt100 parser to apply new default colors
-        if let Ok(mut parser) = self.terminal_screen.write() {
-            let (rows, cols) = {
-                let size = parser.screen().size();
-                (size.0, size.1)
-            };
-            // Create new parser with same dimensions - this resets colors
-            *parser = tui_term::vt100::Parser::new(rows, cols, 0);
+    /// Ask for the new name of the symbol under the cursor, to rename it
+    /// through the language server
+    pub fn open_rename(&mut self) {
+        if self.language_server().is_none() {
+            self.notify("No language server for this file type (set \"language_servers\" in the config)");
+            return;
+        }
+        let Some(symbol) = self.editor_state.word_at_cursor() else {
+            self.notify("Place the cursor on a symbol to rename");
+            return;
+        };
+        let mut input = TextArea::new(vec![symbol.clone()]);
+        input.move_cursor(tui_textarea::CursorMove::End);
+        input.set_block(Block::default().borders(Borders::ALL)
+            .title(format!(" Rename `{}` to (Enter: Preview, Esc: Cancel) ", symbol)));
+        self.refactor_input = input;
+        self.refactor_symbol = symbol;
+        self.show_rename_prompt = true;
+    }
+
+    /// Send the rename to the language server; its edits come back for review
+    pub fn confirm_rename(&mut self) {
+        let new_name = self.refactor_input.lines().join("").trim().to_string();
+        if new_name.is_empty() || new_name == self.refactor_symbol {
+            return;
         }
+        self.show_rename_prompt = false;
+        let Some((server, path)) = self.language_server() else { return };
+        let Some(client) = self.lsp_servers.get_mut(&server) else { return };
+        let (row, col) = (self.editor_state.cursor_row, self.editor_state.cursor_col);
+        let id = client.rename(&path, &self.editor_state.lines, row, col, &new_name);
+        self.rename_request = Some(RenameRequest { server, id, symbol: self.refactor_symbol.clone(), new_name });
+    }
 
-        // Send escape codes to reset terminal and re-run prompt
-        // \x1b[0m - Reset all attributes
-        // \x1b[2J - Clear screen
-        // \x1b[H - Move cursor to home
-        let reset_seq = "\x1b[0m\x1b[2J\x1b[H";
-        let _ = self.pty_writer.write_all(reset_seq.as_bytes());
-        let _ = self.pty_writer.flush();
+    /// Turn the server's workspace edit into line edits, read against open
+    /// buffers or the files on disk, and show them in the refactor review
+    fn show_rename(&mut self, request: RenameRequest, result: Result<serde_json::Value, String>) {
+        let edits = result.and_then(|reply| {
+            let mut edits = Vec::new();
+            for (path, raw) in lsp::workspace_edit_files(&reply)? {
+                let lines = match self.find_buffer(&path) {
+                    Some(idx) => self.buffer(idx).lines.clone(),
+                    None => fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?
+                        .split('\n').map(|line| line.trim_end_matches('\r').to_string()).collect(),
+                };
+                let text_edits: Vec<TextEdit> = raw.iter().filter_map(|edit| TextEdit::from_json(edit, &lines)).collect();
+                let shown = path.strip_prefix(&self.workspace).unwrap_or(&path);
+                edits.extend(refactor::from_text_edits(shown, &lines, &text_edits)?);
+            }
+            Ok(edits)
+        });
+        match edits {
+            Ok(edits) if edits.is_empty() => self.notify(format!("Nothing to rename for `{}`", request.symbol)),
+            Ok(mut edits) => {
+                edits.sort_by(|a, b| a.path.cmp(&b.path).then(a.line.cmp(&b.line)));
+                self.notify(format!("Renaming `{}` to `{}`: review the edits", request.symbol, request.new_name));
+                self.on_refactor_proposed(Ok(edits));
+            }
+            Err(e) => self.notify(format!("Rename failed: {}", e)),
+        }
     }
 
-    pub fn send_chat_message(&mut self, content: String) {
-        self.chat_history.push(format!("You: {}", content));
+    /// Collect the symbol's occurrences and ask the model for edits in the background
+    pub fn confirm_refactor(&mut self) {
+        let instruction = self.refactor_input.lines().join(" ");
+        let instruction = instruction.trim().to_string();
+        if instruction.is_empty() {
+            return;
+        }
+        self.show_refactor_prompt = false;
+        self.refactor_pending = true;
+        self.notify(format!("Asking the AI to refactor `{}`...", self.refactor_symbol));
 
         let tx = self.event_tx.clone();
-        let model_config = self.config.get_selected_model().clone();
-        let history = self.chat_history.clone();
+        let root = self.workspace.clone();
+        let symbol = self.refactor_symbol.clone();
+        tokio::task::spawn_blocking(move || {
+            let occurrences = refactor::find_occurrences(&root, &symbol);
+            if occurrences.is_empty() {
+                let _ = tx.send(AppEvent::RefactorProposed(Err(format!("No occurrences of `{}` found", symbol))));
+                return;
+            }
+            let prompt = refactor::build_prompt(&symbol, &instruction, &occurrences);
+            let _ = tx.send(AppEvent::RefactorPrepared { occurrences, prompt });
+        });
+    }
 
+    pub fn on_refactor_prepared(&mut self, occurrences: Vec<Occurrence>, prompt: String) {
+        self.send_ai_request(AiRequest::Refactor { occurrences, prompt });
+    }
+
+    fn dispatch_refactor(&mut self, occurrences: Vec<Occurrence>, prompt: String) {
+        let tx = self.event_tx.clone();
+        let model_config = self.config.get_selected_model().clone();
         tokio::spawn(async move {
-            let response = match send_message(&model_config, &history, &content).await {
-                Ok(resp) => resp,
-                Err(e) => format!("Error: {}", e),
-            };
+            let result = async {
+                let response = send_message(&model_config, &[], &prompt).await?;
+                let edits = refactor::parse_edits(&response, &occurrences);
+                if edits.is_empty() {
+                    return Err("The AI proposed no changes".to_string());
+                }
+                Ok(edits)
+            }.await;
+            let _ = tx.send(AppEvent::RefactorProposed(result));
+        });
+    }
+
+    pub fn on_refactor_proposed(&mut self, result: Result<Vec<LineEdit>, String>) {
+        self.refactor_pending = false;
+        match result {
+            Ok(edits) => {
+                self.refactor_edits = edits;
+                self.refactor_selected = 0;
+                self.show_refactor = true;
+            }
+            Err(e) => self.notify(format!("Refactor failed: {}", e)),
+        }
+    }
 
-            let _ = tx.send(AppEvent::AiResponse(response));
+    /// Rows of the refactor review: `None` for a file header, `Some(idx)` for an edit
+    pub fn refactor_rows(&self) -> Vec<Option<usize>> {
+        let mut rows = Vec::new();
+        let mut idx = 0;
+        for (_, edits) in refactor::group_by_file(&self.refactor_edits) {
+            rows.push(None);
+            for _ in edits {
+                rows.push(Some(idx));
+                idx += 1;
+            }
+        }
+        rows
+    }
+
+    pub fn toggle_refactor_edit(&mut self, all: bool) {
+        if all {
+            let accept = !self.refactor_edits.iter().all(|e| e.accepted);
+            self.refactor_edits.iter_mut().for_each(|e| e.accepted = accept);
+        } else if let Some(edit) = self.refactor_edits.get_mut(self.refactor_selected) {
+            edit.accepted = !edit.accepted;
+        }
+    }
+
+    /// Apply the accepted edits: files open in a buffer are edited there (and
+    /// left to be saved), the rest are written. Nothing changes if any target
+    /// line no longer matches what was proposed.
+    pub fn apply_refactor(&mut self) {
+        let (in_buffers, on_disk): (Vec<LineEdit>, Vec<LineEdit>) = self.refactor_edits.iter()
+            .filter(|edit| edit.accepted)
+            .cloned()
+            .partition(|edit| self.find_buffer(&self.workspace.join(&edit.path)).is_some());
+        let mut buffer_edits = Vec::new();
+        for edit in in_buffers {
+            let Some(idx) = self.find_buffer(&self.workspace.join(&edit.path)) else { continue };
+            if self.buffer(idx).lines.get(edit.line - 1) != Some(&edit.old) {
+                self.notify(format!("Refactor not applied: {}:{} changed since it was proposed", edit.path.display(), edit.line));
+                return;
+            }
+            buffer_edits.push((idx, edit));
+        }
+        let written = match refactor::apply_edits(&self.workspace, &on_disk) {
+            Ok(written) => written,
+            Err(e) => return self.notify(format!("Refactor not applied: {}", e)),
+        };
+        let mut edited: Vec<usize> = Vec::new();
+        for (idx, edit) in buffer_edits {
+            let buffer = self.buffer_mut(idx);
+            let row = edit.line - 1;
+            buffer.replace_text((row, 0), (row, edit.old.chars().count()), &edit.new);
+            let line_len = buffer.lines.get(buffer.cursor_row).map_or(0, |line| line.chars().count());
+            buffer.cursor_col = buffer.cursor_col.min(line_len);
+            if !edited.contains(&idx) {
+                edited.push(idx);
+            }
+        }
+        self.last_edit_at = Instant::now();
+        self.show_refactor = false;
+        self.refactor_edits.clear();
+        self.notify(match edited.len() {
+            0 => format!("Refactor applied to {} file(s)", written.len()),
+            open => format!("Refactor applied to {} file(s); {} open buffer(s) changed, unsaved", written.len() + open, open),
         });
     }
 
+    /// Rows of the TODO panel: `None` for a file header, `Some(idx)` for an item
+    pub fn todo_rows(&self) -> Vec<Option<usize>> {
+        let mut rows = Vec::new();
+        let mut idx = 0;
+        for (_, items) in todo::group_by_file(&self.todos) {
+            rows.push(None);
+            for _ in items {
+                rows.push(Some(idx));
+                idx += 1;
+            }
+        }
+        rows
+    }
+
+    /// Jump to a TODO item and close the panel
+    pub fn open_todo(&mut self, idx: usize) {
+        if let Some(item) = self.todos.get(idx).cloned() {
+            self.open_location(&item.path, item.line, 1);
+            self.show_todos = false;
+        }
+    }
+
+    /// Re-parse recent terminal output for compiler errors (cargo, tsc, gcc-style)
+    pub fn refresh_build_diagnostics(&mut self) {
+        let output = match self.history_buffer.read() {
+            Ok(buffer) => {
+                let start = buffer.len().saturating_sub(BUILD_OUTPUT_SCAN_BYTES);
+                strip_ansi(&String::from_utf8_lossy(&buffer[start..]))
+            }
+            Err(_) => return,
+        };
+        self.diagnostics.set_source(DiagnosticSource::Build, parse_build_output(&output));
+        self.clamp_problems_selection();
+    }
+
+    /// Diagnostics shown in the problems panel with the current severity filter
+    pub fn visible_problems(&self) -> Vec<&Diagnostic> {
+        self.diagnostics.filtered(self.problems_min_severity)
+    }
+
+    /// Cycle the severity filter: all -> warnings and errors -> errors only
+    pub fn cycle_problems_filter(&mut self) {
+        self.problems_min_severity = match self.problems_min_severity {
+            Severity::Hint | Severity::Info => Severity::Warning,
+            Severity::Warning => Severity::Error,
+            Severity::Error => Severity::Hint,
+        };
+        self.clamp_problems_selection();
+    }
+
+    fn clamp_problems_selection(&mut self) {
+        let count = self.visible_problems().len();
+        let selected = self.problems_state.selected().unwrap_or(0);
+        self.problems_state.select(if count == 0 { None } else { Some(selected.min(count - 1)) });
+    }
+
+    /// Jump to the selected problem and close the panel
+    pub fn open_selected_problem(&mut self) {
+        let target = self.problems_state.selected()
+            .and_then(|i| self.visible_problems().get(i).map(|d| (d.path.clone(), d.line, d.column)));
+        if let Some((path, line, column)) = target {
+            self.open_location(&path, line, column);
+            self.show_problems = false;
+        }
+    }
+
+    /// Where focus and the editor cursor are now
+    fn location(&self) -> Location {
+        let editor = self.active_panel == ActivePanel::Editor;
+        Location {
+            panel: self.active_panel,
+            file: self.editor_state.file_path.clone().filter(|_| editor),
+            row: if editor { self.editor_state.cursor_row } else { 0 },
+            col: if editor { self.editor_state.cursor_col } else { 0 },
+        }
+    }
+
+    /// Record the current location in the navigation history; called after each batch of events
+    pub fn track_navigation(&mut self) {
+        let here = self.location();
+        self.nav_history.visit(here);
+    }
+
+    /// Go back (or forward) to the location before the last jump
+    fn navigate(&mut self, forward: bool) {
+        self.track_navigation();
+        let target = if forward { self.nav_history.forward() } else { self.nav_history.back() };
+        let Some(target) = target else {
+            self.notify(format!("No {} location", if forward { "next" } else { "previous" }));
+            return;
+        };
+        if let Some(path) = target.file {
+            if self.editor_state.file_path.as_ref() != Some(&path) {
+                self.load_file_path(path);
+            }
+            self.editor_state.goto(target.row, target.col);
+        }
+        self.active_panel = target.panel;
+    }
+
+    /// Open a file at a 1-based line/column, resolving relative paths against the workspace
+    pub fn open_location(&mut self, path: &Path, line: usize, column: usize) {
+        let path = if path.is_relative() { self.workspace.join(path) } else { path.to_path_buf() };
+        self.load_file_path(path);
+        self.editor_state.goto(line.saturating_sub(1), column.saturating_sub(1));
+        self.active_panel = ActivePanel::Editor;
+    }
+
+    /// Feed a key to the key map's chords: the start of a chord waits for the
+    /// next key, a finished one runs its action. False for keys that aren't
+    /// part of a chord, which go on to the single-key bindings.
+    pub fn handle_chord_key(&mut self, key: KeyEvent) -> bool {
+        self.expire_chord();
+        let mut keys = std::mem::take(&mut self.pending_chord);
+        let pending = !keys.is_empty();
+        keys.push((key.code, key.modifiers));
+        match self.key_map.lookup(&keys) {
+            ChordMatch::Prefix => {
+                self.pending_chord = keys;
+                self.pending_chord_at = Instant::now();
+            }
+            ChordMatch::Action(action) if pending => self.handle_action(action),
+            ChordMatch::Action(_) => return false,
+            ChordMatch::None if pending => self.notify(format!("{} is not bound", keymap::format_keys(&keys))),
+            ChordMatch::None => return false,
+        }
+        true
+    }
+
+    /// Drop a chord whose next key didn't come in time
+    fn expire_chord(&mut self) {
+        if !self.pending_chord.is_empty() && self.pending_chord_at.elapsed() >= keymap::CHORD_TIMEOUT {
+            self.pending_chord.clear();
+        }
+    }
+
+    /// Status bar label of a chord waiting for its next key, e.g. "Ctrl+K …"
+    pub fn pending_chord_label(&self) -> Option<String> {
+        (!self.pending_chord.is_empty()).then(|| format!("{} …", keymap::format_keys(&self.pending_chord)))
+    }
+
+    /// Human readable key binding for an action, e.g. "Ctrl+S"
+    pub fn shortcut_label(&self, action: Action) -> Option<String> {
+        let mut bindings: Vec<String> = self.key_map.iter()
+            .filter(|(_, a)| *a == action)
+            .map(|(keys, _)| keymap::format_keys(keys))
+            .collect();
+        // Prefer the shortest binding so the label is stable
+        bindings.sort_by_key(|b| (b.len(), b.clone()));
+        bindings.into_iter().next()
+    }
+
     /// Returns the menu items for a given menu index
     pub fn get_menu_items(idx: usize) -> Vec<(&'static str, Action)> {
         match idx {
             0 => vec![
+                ("New Scratch Buffer", Action::NewScratch),
+                ("Save", Action::Save),
+                ("Save As...", Action::SaveAs),
+                ("Save All", Action::SaveAll),
+                ("Close Buffer", Action::CloseBuffer),
+                ("Close All", Action::CloseAll),
+                ("Toggle Read-Only", Action::ToggleReadOnly),
+                ("Convert LF/CRLF", Action::ToggleLineEnding),
+                ("Revert File", Action::ReloadFile),
+                ("Diff with Saved", Action::ShowDiff),
+                ("Compare with...", Action::Compare),
+                ("Export as HTML", Action::ExportHtml),
+                ("Export as ANSI", Action::ExportAnsi),
                 ("Settings", Action::OpenSettings),
+                ("Recent Files", Action::RecentFiles),
+                ("Restore Session From...", Action::RestoreSession),
                 ("File Search", Action::FileSearch),
+                ("Search Everything", Action::SearchEverything),
+                ("Search Open Buffers", Action::SearchBuffers),
                 ("Exit", Action::Quit),
             ],
             1 => vec![
                 ("Copy", Action::Copy),
+                ("Cut", Action::Cut),
                 ("Paste", Action::Paste),
+                ("Copy Permalink", Action::CopyPermalink),
+                ("Find", Action::Find),
+                ("Replace", Action::Replace),
+                ("Refactor Symbol...", Action::Refactor),
+                ("Rename Symbol...", Action::RenameSymbol),
+                ("AI Review File", Action::AiReview),
+                ("Toggle Review Comment", Action::ToggleReviewComment),
+                ("Clear Review", Action::ClearReview),
+                ("Spelling Suggestions", Action::SpellSuggest),
+                ("Complete Code", Action::Complete),
+                ("Go to Definition", Action::GotoDefinition),
+                ("Find All References", Action::FindReferences),
+                ("Outline", Action::Outline),
+                ("Format Document", Action::FormatDocument),
+                ("Toggle Comment", Action::ToggleComment),
+                ("Toggle Fold", Action::ToggleFold),
+                ("Edit Color...", Action::PickColor),
+                ("Unfold All", Action::UnfoldAll),
+                ("Next Change", Action::NextChange),
+                ("Previous Change", Action::PrevChange),
+                ("Next Problem", Action::NextDiagnostic),
+                ("Previous Problem", Action::PrevDiagnostic),
+                ("Show Line Problems", Action::ShowDiagnostic),
+                ("Go Back", Action::NavigateBack),
+                ("Go Forward", Action::NavigateForward),
             ],
             2 => vec![
                 ("Reset Layout", Action::ResetLayout),
-                ("Dump History", Action::DumpHistory),
+                ("Zen Mode", Action::ToggleZen),
+                ("Capture Terminal Output...", Action::DumpHistory),
+                ("Terminal: cd to File", Action::TerminalCdToFile),
+                ("Tree: Terminal Dir", Action::FileTreeFromTerminal),
+                ("Tree: Exclude Directory", Action::ToggleExcluded),
+                ("Tree: Show Excluded", Action::ShowExcluded),
+                ("Toggle Watch Mode", Action::ToggleWatch),
+                ("Switch Terminal Tab", Action::SwitchTerminalTab),
+                ("Terminal Processes", Action::ProcessTree),
+                ("Share Session (Read-Only)", Action::ToggleMirror),
+                ("Screen Snapshot", Action::Snapshot),
+                ("Screen Snapshot (Hide Chat)", Action::SnapshotRedacted),
+                ("Toggle Inlay Hints", Action::ToggleInlayHints),
+                ("Problems", Action::ToggleProblems),
+                ("TODOs", Action::ToggleTodos),
+                ("Workspace Stats", Action::WorkspaceStats),
+                ("Project Setup", Action::ProjectSetup),
+                ("AI Context Preset...", Action::SwitchPreset),
             ],
             3 => vec![
                 ("About", Action::About),
@@ -643,6 +5920,56 @@ impl<'a> App<'a> {
         }
     }
 
+    /// Select the model card at `idx`, saving a key being edited
+    pub fn settings_select(&mut self, idx: usize) {
+        if idx >= self.config.models.len() || (idx == self.settings_model_idx && !self.settings_editing) {
+            return;
+        }
+        self.settings_stop_edit();
+        self.settings_model_idx = idx;
+        self.load_settings_for_model(idx);
+    }
+
+    /// Mouse in the settings panel shown in `area`: a click selects a model
+    /// card, starts editing its API key row or presses its test button, the
+    /// wheel moves between cards and a click outside closes the panel
+    pub fn handle_settings_mouse(&mut self, mouse: MouseEvent, area: Rect) {
+        let at = Position::new(mouse.column, mouse.row);
+        let list = ui::settings_list_area(area);
+        match mouse.kind {
+            MouseEventKind::ScrollUp => self.settings_select(self.settings_model_idx.saturating_sub(1)),
+            MouseEventKind::ScrollDown => self.settings_select(self.settings_model_idx + 1),
+            MouseEventKind::Down(MouseButton::Left) if !area.contains(at) => {
+                self.settings_stop_edit();
+                self.show_settings = false;
+            }
+            MouseEventKind::Down(MouseButton::Left) if list.contains(at) => {
+                let line = self.settings_scroll_offset + (mouse.row - list.y) as usize;
+                let idx = line / ui::SETTINGS_CARD_LINES;
+                if idx >= self.config.models.len() {
+                    return self.settings_stop_edit();
+                }
+                let editing_this = self.settings_editing && idx == self.settings_model_idx;
+                match line % ui::SETTINGS_CARD_LINES {
+                    2 if !editing_this => {
+                        self.settings_select(idx);
+                        self.settings_start_edit();
+                    }
+                    2 => {}
+                    3 => {
+                        self.settings_select(idx);
+                        let button = list.x + 3..list.x + 3 + ui::SETTINGS_TEST_BUTTON.chars().count() as u16;
+                        if button.contains(&mouse.column) {
+                            self.settings_test_connection();
+                        }
+                    }
+                    _ => self.settings_select(idx),
+                }
+            }
+            _ => {}
+        }
+    }
+
     /// Start editing the current model's API key
     pub fn settings_start_edit(&mut self) {
         self.settings_editing = true;
@@ -668,13 +5995,37 @@ impl<'a> App<'a> {
         if let Some(model) = self.config.models.get_mut(self.settings_model_idx) {
             model.api_key = api_key;
         }
-        let _ = self.config.save();
+        self.save_config();
+    }
+
+    /// Send a minimal request with the selected model's settings; the result
+    /// is shown on its card rather than in the chat
+    pub fn settings_test_connection(&mut self) {
+        let idx = self.settings_model_idx;
+        let Some(model_config) = self.config.models.get(idx).cloned() else { return };
+        if self.settings_health.get(&idx) == Some(&ConnectionTest::Testing) {
+            return;
+        }
+        self.settings_health.insert(idx, ConnectionTest::Testing);
+        let tx = self.event_tx.clone();
+        tokio::spawn(async move {
+            let result = ai::test_connection(&model_config).await;
+            let _ = tx.send(AppEvent::ConnectionTested(idx, result));
+        });
+    }
+
+    pub fn on_connection_tested(&mut self, idx: usize, result: Result<Duration, String>) {
+        let status = match result {
+            Ok(latency) => ConnectionTest::Passed(latency),
+            Err(e) => ConnectionTest::Failed(e),
+        };
+        self.settings_health.insert(idx, status);
     }
 
     /// Set the selected model as the active model for chat
     pub fn settings_set_active_model(&mut self) {
         self.config.selected_model_idx = self.settings_model_idx;
-        let _ = self.config.save();
+        self.save_config();
     }
 
     /// Load the API key for the specified model into the settings input
@@ -694,4 +6045,55 @@ impl<'a> App<'a> {
         }
     }
 
-}
\ No newline at end of file
+}
+
+fn config_diagnostic(path: &Path, error: &ConfigError) -> Diagnostic {
+    Diagnostic {
+        path: path.to_path_buf(),
+        line: error.line,
+        column: error.column,
+        severity: Severity::Error,
+        message: error.message.clone(),
+        code: None,
+        source: DiagnosticSource::Config,
+    }
+}
+
+/// Letters, digits and `_`: what a completed word is made of
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Color of a diagnostic's underline, gutter icon and message
+pub fn severity_color(severity: Severity) -> Color {
+    match severity {
+        Severity::Error => Color::Red,
+        Severity::Warning => Color::Yellow,
+        Severity::Info => Color::Blue,
+        Severity::Hint => Color::DarkGray,
+    }
+}
+
+/// Editor highlights reproducing the foreground colors of captured terminal lines
+fn output_highlights(lines: &[StyledLine]) -> Vec<RangeHighlight> {
+    lines.iter().enumerate()
+        .flat_map(|(line, styled)| styled.spans.iter().map(move |span| RangeHighlight {
+            line,
+            start: span.start,
+            end: span.end,
+            style: Style::default().fg(match span.fg {
+                AnsiColor::Indexed(n) => Color::Indexed(n),
+                AnsiColor::Rgb(r, g, b) => Color::Rgb(r, g, b),
+            }),
+        }))
+        .collect()
+}
+
+/// Rows in a screen's scrollback (not only the ones scrolled into view)
+fn scrollback_rows(parser: &mut tui_term::vt100::Parser) -> usize {
+    let offset = parser.screen().scrollback();
+    parser.set_scrollback(usize::MAX);
+    let rows = parser.screen().scrollback();
+    parser.set_scrollback(offset);
+    rows
+}
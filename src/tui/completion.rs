@@ -0,0 +1,121 @@
+// Completion popup in the editor: the language server's items for the word
+// being typed, fuzzy-filtered as typing continues
+
+use std::path::PathBuf;
+
+use crate::shared::lsp::CompletionItem;
+
+/// Items listed at once; the list scrolls past them
+pub const VISIBLE_ITEMS: usize = 10;
+
+pub struct Completion {
+    pub items: Vec<CompletionItem>,
+    /// The buffer and the (row, col) where the word being completed starts
+    pub path: PathBuf,
+    pub row: usize,
+    pub start: usize,
+    /// Indices into `items` that match the typed word, best first
+    pub matches: Vec<usize>,
+    /// Index into `matches`
+    pub selected: usize,
+}
+
+impl Completion {
+    pub fn new(items: Vec<CompletionItem>, path: PathBuf, row: usize, start: usize, query: &str) -> Self {
+        let mut completion = Self { items, path, row, start, matches: Vec::new(), selected: 0 };
+        completion.filter(query);
+        completion
+    }
+
+    /// Keep the items matching `query`, best match first (the server's order for ties)
+    pub fn filter(&mut self, query: &str) {
+        let mut scored: Vec<(i64, usize)> = self.items.iter().enumerate()
+            .filter_map(|(i, item)| fuzzy_score(&item.filter_text, query).map(|score| (score, i)))
+            .collect();
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| self.items[a.1].sort_text.cmp(&self.items[b.1].sort_text)));
+        self.matches = scored.into_iter().map(|(_, i)| i).collect();
+        self.selected = 0;
+    }
+
+    /// Move the selection, wrapping around the matches
+    pub fn select(&mut self, delta: isize) {
+        if !self.matches.is_empty() {
+            self.selected = (self.selected as isize + delta).rem_euclid(self.matches.len() as isize) as usize;
+        }
+    }
+
+    pub fn selected_item(&self) -> Option<&CompletionItem> {
+        self.matches.get(self.selected).map(|&i| &self.items[i])
+    }
+
+    /// First match shown, keeping the selection in view
+    pub fn scroll_offset(&self) -> usize {
+        (self.selected + 1).saturating_sub(VISIBLE_ITEMS)
+    }
+}
+
+/// How well `candidate` matches the typed `query`: every query char must appear
+/// in order, ignoring case; runs of consecutive chars, word starts and exact
+/// case score higher. `None` when it doesn't match.
+pub fn fuzzy_score(candidate: &str, query: &str) -> Option<i64> {
+    let chars: Vec<char> = candidate.chars().collect();
+    let mut score = 0;
+    let mut next = 0;
+    let mut previous: Option<usize> = None;
+    for q in query.chars() {
+        let offset = chars[next..].iter().position(|c| c.to_lowercase().eq(q.to_lowercase()))?;
+        let idx = next + offset;
+        let c = chars[idx];
+        let word_start = idx == 0
+            || !chars[idx - 1].is_alphanumeric()
+            || (chars[idx - 1].is_lowercase() && c.is_uppercase());
+        score += 1;
+        if previous.is_some_and(|p| p + 1 == idx) {
+            score += 5;
+        } else if word_start {
+            score += 8;
+        }
+        if c == q {
+            score += 1;
+        }
+        score -= offset.min(10) as i64;
+        previous = Some(idx);
+        next = idx + 1;
+    }
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(label: &str) -> CompletionItem {
+        CompletionItem {
+            label: label.to_string(),
+            kind: 3,
+            detail: None,
+            filter_text: label.to_string(),
+            sort_text: label.to_string(),
+            insert_text: label.to_string(),
+            edit: None,
+            additional_edits: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_filter_and_select() {
+        assert!(fuzzy_score("to_string", "tstr").is_some());
+        assert_eq!(fuzzy_score("len", "nl"), None);
+        assert!(fuzzy_score("HashMap", "hm") > fuzzy_score("hashmap", "hm"));
+
+        let items = vec![item("push_str"), item("as_ptr"), item("push"), item("len")];
+        let mut completion = Completion::new(items, PathBuf::from("a.rs"), 0, 4, "");
+        assert_eq!(completion.matches.len(), 4);
+        completion.filter("pu");
+        assert_eq!(completion.selected_item().map(|i| i.label.as_str()), Some("push"));
+        completion.select(-1);
+        assert_eq!(completion.selected_item().map(|i| i.label.as_str()), Some("push_str"));
+        completion.filter("pst");
+        assert_eq!(completion.matches, vec![0]);
+    }
+}
@@ -0,0 +1,74 @@
+// Side-by-side (or unified) comparison of two texts shown in the editor area
+
+use crate::shared::diff::{self, Algorithm, Diff};
+
+pub struct DiffView {
+    pub left_title: String,
+    pub right_title: String,
+    pub diff: Diff,
+    /// First visible row
+    pub scroll: usize,
+    /// One column with `-`/`+` lines instead of two sides
+    pub unified: bool,
+    hunks: Vec<usize>,
+}
+
+impl DiffView {
+    pub fn new(algorithm: Algorithm, left_title: String, left: Vec<String>, right_title: String, right: Vec<String>) -> Self {
+        let diff = Diff::new(algorithm, left, right);
+        let hunks = diff::hunk_starts(&diff.rows);
+        // Open at the first difference
+        let scroll = hunks.first().map(|h| h.saturating_sub(CONTEXT)).unwrap_or(0);
+        Self {
+            left_title,
+            right_title,
+            diff,
+            scroll,
+            unified: false,
+            hunks,
+        }
+    }
+
+    pub fn hunk_count(&self) -> usize {
+        self.hunks.len()
+    }
+
+    /// 1-based index of the last hunk starting at or above the top of the view (context included)
+    pub fn current_hunk(&self) -> Option<usize> {
+        self.hunks.iter().rposition(|&h| h.saturating_sub(CONTEXT) <= self.scroll).map(|i| i + 1)
+    }
+
+    pub fn scroll_by(&mut self, delta: isize) {
+        let max = self.diff.rows.len().saturating_sub(1);
+        self.scroll = self.scroll.saturating_add_signed(delta).min(max);
+    }
+
+    /// Scroll to the next block of changes (with a little context above it)
+    pub fn next_hunk(&mut self) {
+        if let Some(&h) = self.hunks.iter().find(|&&h| h.saturating_sub(CONTEXT) > self.scroll) {
+            self.scroll = h.saturating_sub(CONTEXT);
+        }
+    }
+
+    pub fn prev_hunk(&mut self) {
+        if let Some(&h) = self.hunks.iter().rev().find(|&&h| h.saturating_sub(CONTEXT) < self.scroll) {
+            self.scroll = h.saturating_sub(CONTEXT);
+        }
+    }
+}
+
+/// Rows of unchanged lines kept above a hunk when jumping to it
+const CONTEXT: usize = 3;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_lines() {
+        let lines = |s: &[&str]| s.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+        let view = DiffView::new(Algorithm::Myers, "saved".into(), lines(&["a", "b", "c"]), "buffer".into(), lines(&["a", "B", "d"]));
+        let unified: Vec<(char, &str)> = view.diff.unified(&view.diff.rows).map(|l| (l.marker, l.text)).collect();
+        assert_eq!(unified, vec![(' ', "a"), ('-', "b"), ('+', "B"), ('-', "c"), ('+', "d")]);
+    }
+}
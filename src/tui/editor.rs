@@ -2,23 +2,33 @@ use std::collections::hash_map::DefaultHasher;
 use std::fs;
 use std::hash::{Hash, Hasher};
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 use ratatui::{
     buffer::Buffer,
+    crossterm::event::{KeyCode, KeyEvent, KeyModifiers},
     layout::Rect,
-    style::{Color, Style},
-    text::{Line, Span},
+    style::{Color, Modifier, Style},
     widgets::{Block, StatefulWidget, Widget},
 };
-use syntect::easy::HighlightLines;
-use syntect::highlighting::ThemeSet;
-use syntect::parsing::SyntaxSet;
-use syntect_tui::into_span;
+
+use super::keymap::EditorKeyMap;
+use crate::shared::code_lens::LensAction;
+use crate::shared::encoding::{self, LineEnding, TextEncoding};
+use crate::shared::highlight::{self, HighlightSpan};
+use crate::shared::indent_guides;
+use crate::shared::lsp::InlayHint;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+use crate::shared::preview::{self, Preview};
+use crate::shared::recovery;
+use crate::shared::syntax;
+use crate::shared::IndentConfig;
 
 /// Cache for syntax-highlighted lines to avoid re-processing unchanged content
 struct HighlightCache {
-    lines: Vec<Option<Line<'static>>>,
+    lines: Vec<Option<Vec<HighlightSpan>>>,
     line_hashes: Vec<u64>,
     extension: Option<String>,
 }
@@ -43,6 +53,31 @@ impl HighlightCache {
         }
     }
 
+    /// Rows `start..=end` moved one line down (or up); cached lines move with them
+    fn move_lines(&mut self, start: usize, end: usize, down: bool) {
+        if end + usize::from(down) >= self.lines.len() || (!down && start == 0) {
+            return self.invalidate_all();
+        }
+        if down {
+            self.lines[start..=end + 1].rotate_right(1);
+            self.line_hashes[start..=end + 1].rotate_right(1);
+        } else {
+            self.lines[start - 1..=end].rotate_left(1);
+            self.line_hashes[start - 1..=end].rotate_left(1);
+        }
+    }
+
+    /// Rows `start..=end` were copied to just below themselves
+    fn duplicate_lines(&mut self, start: usize, end: usize) {
+        if end >= self.lines.len() {
+            return self.invalidate_all();
+        }
+        let lines: Vec<_> = self.lines[start..=end].to_vec();
+        let hashes: Vec<_> = self.line_hashes[start..=end].to_vec();
+        self.lines.splice(end + 1..end + 1, lines);
+        self.line_hashes.splice(end + 1..end + 1, hashes);
+    }
+
     fn invalidate_all(&mut self) {
         for line in &mut self.lines {
             *line = None;
@@ -63,7 +98,134 @@ impl HighlightCache {
     }
 }
 
-/// Editor state holding content, cursor position, and syntax highlighting resources
+/// Virtual text shown after the end of a line (diagnostics, hints)
+#[derive(Debug, Clone)]
+pub struct LineAnnotation {
+    /// 0-based line index
+    pub line: usize,
+    pub text: String,
+    pub style: Style,
+    /// Run when the annotation is clicked
+    pub action: Option<AnnotationAction>,
+}
+
+/// What clicking an annotation does
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnnotationAction {
+    Lens(LensAction),
+    /// Expand or collapse the AI review comment at this index
+    ToggleReview(usize),
+}
+
+/// Where the last render drew a clickable annotation
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnnotationHit {
+    pub y: u16,
+    pub x: std::ops::Range<u16>,
+    /// 0-based line the annotation belongs to
+    pub line: usize,
+    pub action: AnnotationAction,
+}
+
+/// Marker drawn in the gutter between the line number and the text (git changes)
+#[derive(Debug, Clone)]
+pub struct GutterMarker {
+    /// 0-based line index
+    pub line: usize,
+    pub symbol: &'static str,
+    pub style: Style,
+}
+
+/// Background highlight of a char range on one line (search matches, selection)
+#[derive(Debug, Clone)]
+pub struct RangeHighlight {
+    /// 0-based line index
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+    pub style: Style,
+}
+
+/// Bracket pairs that are auto-closed and matched
+const BRACKETS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
+/// How far (in lines) to look for a matching bracket before giving up
+const BRACKET_SCAN_LINES: usize = 5_000;
+
+/// Lines below the viewport highlighted ahead of scrolling
+const HIGHLIGHT_LOOKAHEAD: usize = 100;
+
+/// The cursor line is highlighted while rendering when it is at most this long
+const SYNC_HIGHLIGHT_CHARS: usize = 500;
+
+/// Editing operations that editor key presets bind keys to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditorCommand {
+    Backspace,
+    Delete,
+    Newline,
+    Up,
+    Down,
+    Left,
+    Right,
+    LineStart,
+    LineEnd,
+    WordForward,
+    WordBackward,
+    PageUp,
+    PageDown,
+    KillLine,
+    Indent,
+    Dedent,
+    AddCursorAtNextMatch,
+    MoveLineUp,
+    MoveLineDown,
+    DuplicateLine,
+    /// Jump to the bracket matching the one at the cursor
+    MatchBracket,
+    /// Extend the selection with a cursor motion
+    SelectLeft,
+    SelectRight,
+    SelectUp,
+    SelectDown,
+    SelectLineStart,
+    SelectLineEnd,
+    SelectAll,
+}
+
+impl EditorCommand {
+    /// Whether the command changes the text (as opposed to moving the cursor)
+    pub fn is_edit(&self) -> bool {
+        matches!(
+            self,
+            EditorCommand::Backspace
+                | EditorCommand::Delete
+                | EditorCommand::Newline
+                | EditorCommand::KillLine
+                | EditorCommand::Indent
+                | EditorCommand::Dedent
+                | EditorCommand::MoveLineUp
+                | EditorCommand::MoveLineDown
+                | EditorCommand::DuplicateLine
+        )
+    }
+
+    /// Whether the command extends the selection instead of dropping it
+    pub fn selects(&self) -> bool {
+        matches!(
+            self,
+            EditorCommand::SelectLeft
+                | EditorCommand::SelectRight
+                | EditorCommand::SelectUp
+                | EditorCommand::SelectDown
+                | EditorCommand::SelectLineStart
+                | EditorCommand::SelectLineEnd
+                | EditorCommand::SelectAll
+        )
+    }
+}
+
+/// Editor state holding content, cursor position, and highlight cache
 pub struct EditorState {
     pub lines: Vec<String>,
     pub cursor_row: usize,
@@ -71,9 +233,52 @@ pub struct EditorState {
     pub scroll_offset: usize,
     pub file_path: Option<PathBuf>,
     pub modified: bool,
-    syntax_set: SyntaxSet,
-    theme_set: ThemeSet,
+    /// Name of a scratch buffer (never written unless saved under a path)
+    pub scratch_name: Option<String>,
+    /// Additional carets as (row, col); typing, backspace and paste apply at all of them
+    pub extra_cursors: Vec<(usize, usize)>,
+    /// Indentation for this buffer's language
+    pub indent: IndentConfig,
+    /// Colors kept from captured terminal output; only shown until the buffer is edited
+    pub output_colors: Vec<RangeHighlight>,
+    /// Set while the file's contents are read in the background
+    pub loading: bool,
+    /// Edits are refused: the file isn't writable, or the buffer was locked by hand
+    pub read_only: bool,
+    /// Cursor target requested while loading, applied once the contents arrive
+    pending_goto: Option<(usize, usize)>,
+    /// Other end of the selection; the cursor is the end that moves
+    selection_anchor: Option<(usize, usize)>,
+    /// Size and mtime of the file when it was last loaded or saved
+    disk_stamp: Option<(u64, SystemTime)>,
+    /// Whether the last render drew line numbers (zen mode hides them)
+    show_gutter: bool,
+    /// Clickable annotations drawn by the last render
+    pub annotation_hits: Vec<AnnotationHit>,
+    /// Text rows the last render had room for
+    viewport_height: usize,
+    /// Whether the file ended with a newline when loaded (preserved on save)
+    trailing_newline: bool,
+    /// Encoding the file was read in and is written back in
+    pub encoding: TextEncoding,
+    /// Line ending most lines used when loaded; every line is written with it
+    pub line_ending: LineEnding,
+    /// Set while only the start of a large file is loaded
+    pub preview: Option<Preview>,
+    /// Content hash of the last crash recovery backup written for this buffer, 0 for none
+    pub backup_hash: u64,
+    /// Tells this buffer's crash recovery backup apart from other untitled ones
+    pub backup_id: u64,
+    /// First rows of folded regions, sorted; the region's extent is recomputed
+    /// from the text so edits inside a fold keep it intact
+    folds: Vec<usize>,
+    /// Inlay hints in order, each with the hash of the line it came for; a
+    /// line's hints are only drawn while it is unchanged
+    inlay_hints: Vec<(InlayHint, u64)>,
     highlight_cache: HighlightCache,
+    /// Parse tree of the buffer while tree-sitter highlights it
+    #[cfg(feature = "tree-sitter")]
+    syntax_tree: Option<crate::shared::syntax_tree::Document>,
 }
 
 impl EditorState {
@@ -85,42 +290,743 @@ impl EditorState {
             scroll_offset: 0,
             file_path: None,
             modified: false,
-            syntax_set: SyntaxSet::load_defaults_newlines(),
-            theme_set: ThemeSet::load_defaults(),
+            scratch_name: None,
+            extra_cursors: Vec::new(),
+            indent: IndentConfig::default(),
+            output_colors: Vec::new(),
+            loading: false,
+            read_only: false,
+            show_gutter: true,
+            annotation_hits: Vec::new(),
+            viewport_height: 0,
+            pending_goto: None,
+            selection_anchor: None,
+            disk_stamp: None,
+            trailing_newline: true,
+            encoding: TextEncoding::default(),
+            preview: None,
+            backup_hash: 0,
+            backup_id: recovery::new_buffer_id(),
+            line_ending: LineEnding::default(),
+            folds: Vec::new(),
+            inlay_hints: Vec::new(),
             highlight_cache: HighlightCache::new(),
+            #[cfg(feature = "tree-sitter")]
+            syntax_tree: None,
+        }
+    }
+
+    /// An unsaved scratch buffer
+    pub fn scratch(name: String, lines: Vec<String>) -> Self {
+        let mut state = Self::new();
+        if !lines.is_empty() {
+            state.lines = lines;
         }
+        state.highlight_cache.resize(state.lines.len());
+        state.scratch_name = Some(name);
+        state
+    }
+
+    pub fn is_scratch(&self) -> bool {
+        self.scratch_name.is_some()
+    }
+
+    /// Name shown in the tab list and editor title
+    pub fn display_name(&self) -> String {
+        if let Some(name) = &self.scratch_name {
+            return name.clone();
+        }
+        self.file_path.as_ref()
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "[untitled]".to_string())
+    }
+
+    /// Placeholder for `path` until its contents are loaded with `set_contents`
+    pub fn loading(path: PathBuf) -> Self {
+        let mut state = Self::new();
+        state.file_path = Some(path);
+        state.loading = true;
+        state
     }
 
     pub fn load_file(&mut self, path: PathBuf) -> io::Result<()> {
-        let content = fs::read_to_string(&path)?;
+        let (content, encoding) = encoding::read_file(&path)?;
+        self.set_contents(path, &content);
+        self.encoding = encoding;
+        Ok(())
+    }
+
+    /// Fill the buffer with `content` read from `path`. The encoding is reset to
+    /// UTF-8; set `encoding` afterwards for text that was decoded from another.
+    pub fn set_contents(&mut self, path: PathBuf, content: &str) {
+        self.trailing_newline = content.is_empty() || content.ends_with('\n');
+        self.line_ending = LineEnding::detect(content);
         self.lines = content.lines().map(|s| s.to_string()).collect();
         if self.lines.is_empty() {
             self.lines.push(String::new());
         }
 
-        let ext = path.extension()
-            .and_then(|e| e.to_str())
-            .map(|s| s.to_string());
-        self.highlight_cache.set_extension(ext);
+        self.highlight_cache.set_extension(syntax::detect_language(&path, &self.lines));
         self.highlight_cache.resize(self.lines.len());
 
+        self.read_only = is_read_only(&path);
+        self.encoding = TextEncoding::default();
+        self.preview = None;
         self.file_path = Some(path);
+        self.scratch_name = None;
         self.cursor_row = 0;
         self.cursor_col = 0;
+        self.extra_cursors.clear();
+        self.selection_anchor = None;
+        self.folds.clear();
         self.scroll_offset = 0;
         self.modified = false;
+        self.loading = false;
+        self.mark_synced();
+        if let Some((row, col)) = self.pending_goto.take() {
+            self.goto(row, col);
+        }
+    }
+
+    /// Buffer contents as they would be written to disk
+    pub fn contents(&self) -> String {
+        let ending = self.line_ending.as_str();
+        let mut content = self.lines.join(ending);
+        if self.trailing_newline {
+            content.push_str(ending);
+        }
+        content
+    }
+
+    /// Buffer contents encoded for writing to disk. Fails for a preview, which
+    /// would cut the file off where loading stopped.
+    pub fn encoded_contents(&self) -> io::Result<Vec<u8>> {
+        if let Some(preview) = self.preview {
+            return Err(io::Error::other(format!("only {}% of the file is loaded (read-only preview)", preview.percent())));
+        }
+        self.encoding.encode(&self.contents())
+    }
+
+    /// Mark the buffer as a read-only preview of the start of its file
+    pub fn set_preview(&mut self, preview: Option<Preview>) {
+        self.preview = preview;
+        self.read_only |= preview.is_some();
+    }
+
+    /// Read the next chunk of a previewed file onto the end of the buffer.
+    /// Returns false when everything is loaded already.
+    pub fn load_more(&mut self) -> io::Result<bool> {
+        let (Some(current), Some(path)) = (self.preview, self.file_path.clone()) else { return Ok(false) };
+        let (text, next) = preview::next_chunk(&path, current)?;
+        let mut lines = text.lines().map(str::to_string);
+        // The previous chunk may have stopped inside a very long line
+        if !self.trailing_newline {
+            if let (Some(last), Some(rest)) = (self.lines.last_mut(), lines.next()) {
+                last.push_str(&rest);
+            }
+        }
+        self.lines.extend(lines);
+        self.trailing_newline = text.ends_with('\n') || (text.is_empty() && self.trailing_newline);
+        self.highlight_cache.resize(self.lines.len());
+        self.preview = Some(next).filter(|p| !p.is_complete());
+        Ok(true)
+    }
+
+    /// Switch between LF and CRLF; the change is written on the next save
+    pub fn toggle_line_ending(&mut self) {
+        self.line_ending = self.line_ending.toggled();
+        self.modified = true;
+    }
+
+    /// Write the buffer back to its file
+    pub fn save(&mut self) -> io::Result<()> {
+        let path = self.file_path.clone()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Buffer has no file path"))?;
+        fs::write(&path, self.encoded_contents()?)?;
+        // A shebang or modeline typed since loading takes effect
+        self.highlight_cache.set_extension(syntax::detect_language(&path, &self.lines));
+        self.modified = false;
+        self.mark_synced();
         Ok(())
     }
 
+    /// Write the buffer to a new path, which becomes the buffer's file
+    pub fn save_as(&mut self, path: PathBuf) -> io::Result<()> {
+        fs::write(&path, self.encoded_contents()?)?;
+        self.highlight_cache.set_extension(syntax::detect_language(&path, &self.lines));
+        self.file_path = Some(path);
+        self.scratch_name = None;
+        self.modified = false;
+        self.read_only = false;
+        self.mark_synced();
+        Ok(())
+    }
+
+    /// Take the file as it is on disk now as the version this buffer is based on
+    pub fn mark_synced(&mut self) {
+        self.disk_stamp = self.file_path.as_deref().and_then(disk_stamp);
+    }
+
+    /// Whether the file was changed by something else since it was loaded or saved.
+    /// A file that disappeared doesn't count; saving recreates it.
+    pub fn changed_on_disk(&self) -> bool {
+        if self.loading {
+            return false;
+        }
+        match (self.disk_stamp, self.file_path.as_deref().and_then(disk_stamp)) {
+            (Some(known), Some(current)) => known != current,
+            _ => false,
+        }
+    }
+
+    /// Drop cached highlighting, e.g. after the syntax overrides changed
+    pub fn refresh_highlighting(&mut self) {
+        self.highlight_cache.invalidate_all();
+    }
+
+    /// Replace the buffer with recovered content, keeping it marked as modified
+    pub fn restore(&mut self, path: Option<PathBuf>, lines: Vec<String>) {
+        self.lines = if lines.is_empty() { vec![String::new()] } else { lines };
+
+        self.highlight_cache.set_extension(path.as_deref().and_then(|p| syntax::detect_language(p, &self.lines)));
+        self.highlight_cache.resize(self.lines.len());
+
+        self.read_only = path.as_deref().is_some_and(is_read_only);
+        // Recovered text is written back the way the file on disk is encoded
+        let disk = path.as_deref().and_then(|p| encoding::read_file(p).ok());
+        self.line_ending = disk.as_ref().map(|(text, _)| LineEnding::detect(text)).unwrap_or_default();
+        self.encoding = disk.map(|(_, e)| e).unwrap_or_default();
+        self.preview = None;
+        self.file_path = path;
+        self.cursor_row = 0;
+        self.cursor_col = 0;
+        self.extra_cursors.clear();
+        self.selection_anchor = None;
+        self.folds.clear();
+        self.scroll_offset = 0;
+        self.modified = true;
+        self.mark_synced();
+    }
+
+    /// Hash of the buffer contents, used to detect changes between backups
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.lines.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Replace a char range on one line with `text` (which must not contain newlines)
+    pub fn replace_range(&mut self, row: usize, start: usize, end: usize, text: &str) {
+        let Some(line) = self.lines.get_mut(row) else { return };
+        let start_byte = line.char_indices().nth(start).map(|(i, _)| i).unwrap_or(line.len());
+        let end_byte = line.char_indices().nth(end).map(|(i, _)| i).unwrap_or(line.len());
+        line.replace_range(start_byte..end_byte, text);
+        self.highlight_cache.invalidate(row);
+        self.modified = true;
+    }
+
+    /// Text between two (row, col) positions; `end` is exclusive
+    pub fn text_range(&self, start: (usize, usize), end: (usize, usize)) -> String {
+        let mut out = String::new();
+        for row in start.0..=end.0.min(self.lines.len().saturating_sub(1)) {
+            let line = &self.lines[row];
+            let from = if row == start.0 { start.1 } else { 0 };
+            let to = if row == end.0 { end.1 } else { usize::MAX };
+            out.extend(line.chars().skip(from).take(to.saturating_sub(from)));
+            if row != end.0 {
+                out.push('\n');
+            }
+        }
+        out
+    }
+
+    /// Remove the text between two (row, col) positions and put the cursor at `start`
+    pub fn delete_range(&mut self, start: (usize, usize), end: (usize, usize)) {
+        let last = self.lines.len().saturating_sub(1);
+        let (start_row, end_row) = (start.0.min(last), end.0.min(last));
+        let head: String = self.lines[start_row].chars().take(start.1).collect();
+        let tail: String = self.lines[end_row].chars().skip(end.1).collect();
+        self.lines.splice(start_row..=end_row, [head + &tail]);
+        self.shift_folds(start_row + 1, -((end_row - start_row) as isize));
+        self.highlight_cache.resize(self.lines.len());
+        self.cursor_row = start_row;
+        self.cursor_col = start.1.min(self.current_line_len());
+        self.modified = true;
+    }
+
+    /// Replace the text between two (row, col) positions with `text` as is
+    /// (no auto-indent or bracket pairing), returning where the new text ends
+    pub fn replace_text(&mut self, start: (usize, usize), end: (usize, usize), text: &str) -> (usize, usize) {
+        let last = self.lines.len().saturating_sub(1);
+        let start_row = start.0.min(last);
+        let end_row = end.0.clamp(start_row, last);
+        let head: String = self.lines[start_row].chars().take(start.1).collect();
+        let tail: String = self.lines[end_row].chars().skip(end.1).collect();
+        let mut lines: Vec<String> = text.split('\n').map(|line| line.strip_suffix('\r').unwrap_or(line).to_string()).collect();
+        let end_col = lines.last().map_or(0, |line| line.chars().count()) + if lines.len() == 1 { head.chars().count() } else { 0 };
+        let end_pos = (start_row + lines.len() - 1, end_col);
+        lines[0].insert_str(0, &head);
+        if let Some(line) = lines.last_mut() {
+            line.push_str(&tail);
+        }
+        self.shift_folds(start_row + 1, lines.len() as isize - (end_row - start_row + 1) as isize);
+        self.lines.splice(start_row..=end_row, lines);
+        self.highlight_cache.resize(self.lines.len());
+        self.modified = true;
+        end_pos
+    }
+
+    /// Remove `count` whole lines starting at `row`, returning them
+    pub fn delete_lines(&mut self, row: usize, count: usize) -> Vec<String> {
+        if row >= self.lines.len() {
+            return Vec::new();
+        }
+        let end = (row + count).min(self.lines.len());
+        let removed: Vec<String> = self.lines.drain(row..end).collect();
+        self.shift_folds(row, -((end - row) as isize));
+        if self.lines.is_empty() {
+            self.lines.push(String::new());
+        }
+        self.highlight_cache.resize(self.lines.len());
+        self.cursor_row = row.min(self.lines.len() - 1);
+        self.cursor_col = self.cursor_col.min(self.current_line_len());
+        self.modified = true;
+        removed
+    }
+
+    /// Move rows `start..=end` one line down (or up), keeping the cursor on the
+    /// moved text. Returns false when the rows are already at the edge.
+    pub fn move_lines(&mut self, start: usize, end: usize, down: bool) -> bool {
+        let end = end.min(self.lines.len().saturating_sub(1));
+        if start > end || (down && end + 1 >= self.lines.len()) || (!down && start == 0) {
+            return false;
+        }
+        // Folds next to the moved rows would end up covering different text
+        self.folds.retain(|&fold| fold + 1 < start || fold > end + 1);
+        if down {
+            self.lines[start..=end + 1].rotate_right(1);
+            self.cursor_row += 1;
+        } else {
+            self.lines[start - 1..=end].rotate_left(1);
+            self.cursor_row -= 1;
+        }
+        self.highlight_cache.move_lines(start, end, down);
+        self.modified = true;
+        true
+    }
+
+    /// Copy rows `start..=end` to just below themselves and move the cursor onto the copy
+    pub fn duplicate_lines(&mut self, start: usize, end: usize) {
+        let end = end.min(self.lines.len().saturating_sub(1));
+        if start > end {
+            return;
+        }
+        let copy = self.lines[start..=end].to_vec();
+        self.lines.splice(end + 1..end + 1, copy);
+        self.shift_folds(end + 1, (end - start + 1) as isize);
+        self.highlight_cache.duplicate_lines(start, end);
+        self.cursor_row += end - start + 1;
+        self.modified = true;
+    }
+
+    /// Insert whole lines before `row` (`row == line_count()` appends)
+    pub fn insert_lines(&mut self, row: usize, lines: Vec<String>) {
+        let row = row.min(self.lines.len());
+        self.shift_folds(row, lines.len() as isize);
+        self.lines.splice(row..row, lines);
+        self.highlight_cache.resize(self.lines.len());
+        self.modified = true;
+    }
+
+    /// Move to the start of the next word, continuing onto following lines
+    pub fn move_word_forward(&mut self) {
+        let class = |c: char| if c.is_whitespace() { 0 } else if c.is_alphanumeric() || c == '_' { 1 } else { 2 };
+        let chars: Vec<char> = self.current_line().chars().collect();
+        let mut col = self.cursor_col;
+        if col < chars.len() {
+            let start_class = class(chars[col]);
+            while col < chars.len() && class(chars[col]) == start_class {
+                col += 1;
+            }
+        }
+        while col < chars.len() && chars[col].is_whitespace() {
+            col += 1;
+        }
+        if col >= chars.len() && self.cursor_row + 1 < self.lines.len() {
+            self.cursor_row += 1;
+            let next: Vec<char> = self.current_line().chars().collect();
+            col = next.iter().position(|c| !c.is_whitespace()).unwrap_or(0);
+        }
+        self.cursor_col = col.min(self.current_line_len());
+    }
+
+    /// Move to the start of the current or previous word
+    pub fn move_word_backward(&mut self) {
+        let class = |c: char| if c.is_whitespace() { 0 } else if c.is_alphanumeric() || c == '_' { 1 } else { 2 };
+        if self.cursor_col == 0 {
+            if self.cursor_row == 0 {
+                return;
+            }
+            self.cursor_row -= 1;
+            self.cursor_col = self.current_line_len();
+        }
+        let chars: Vec<char> = self.current_line().chars().collect();
+        let mut col = self.cursor_col.min(chars.len());
+        while col > 0 && chars[col - 1].is_whitespace() {
+            col -= 1;
+        }
+        if col > 0 {
+            let word_class = class(chars[col - 1]);
+            while col > 0 && class(chars[col - 1]) == word_class {
+                col -= 1;
+            }
+        }
+        self.cursor_col = col;
+    }
+
+    /// Handle a key pressed in the editor: mapped keys run their command,
+    /// other printable keys are inserted
+    pub fn input(&mut self, key: KeyEvent, keys: &EditorKeyMap) {
+        if let Some(&command) = keys.get(&(key.code, key.modifiers)) {
+            self.apply(command);
+        } else if let KeyCode::Char(c) = key.code {
+            if !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
+                self.delete_selection();
+                self.at_each_cursor(|state| state.type_char(c));
+            }
+        }
+    }
+
+    /// Whether `input` would change the text for this key
+    pub fn is_edit_key(key: KeyEvent, keys: &EditorKeyMap) -> bool {
+        match keys.get(&(key.code, key.modifiers)) {
+            Some(command) => command.is_edit(),
+            None => matches!(key.code, KeyCode::Char(_))
+                && !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT),
+        }
+    }
+
+    /// Run an editing command. Typing and indent commands apply at every caret;
+    /// anything else drops the extra carets first.
+    pub fn apply(&mut self, command: EditorCommand) {
+        if command.selects() {
+            self.extend_selection(command);
+            return;
+        }
+        // Deleting or typing over a selection removes it first
+        match command {
+            EditorCommand::Backspace | EditorCommand::Delete if self.delete_selection() => return,
+            EditorCommand::Newline => {
+                self.delete_selection();
+            }
+            _ => self.selection_anchor = None,
+        }
+        if !matches!(
+            command,
+            EditorCommand::Backspace | EditorCommand::Newline | EditorCommand::AddCursorAtNextMatch | EditorCommand::Indent | EditorCommand::Dedent
+        ) {
+            self.extra_cursors.clear();
+        }
+        match command {
+            EditorCommand::Backspace => self.at_each_cursor(Self::backspace),
+            EditorCommand::Newline => self.at_each_cursor(Self::insert_newline),
+            EditorCommand::AddCursorAtNextMatch => {
+                self.add_cursor_at_next_match();
+            }
+            EditorCommand::Delete => self.delete(),
+            EditorCommand::Up => self.move_cursor_up(),
+            EditorCommand::Down => self.move_cursor_down(),
+            EditorCommand::Left => self.move_cursor_left(),
+            EditorCommand::Right => self.move_cursor_right(),
+            EditorCommand::LineStart => self.move_cursor_home(),
+            EditorCommand::LineEnd => self.move_cursor_end(),
+            EditorCommand::WordForward => self.move_word_forward(),
+            EditorCommand::WordBackward => self.move_word_backward(),
+            EditorCommand::PageUp => self.page_up(20),
+            EditorCommand::PageDown => self.page_down(20),
+            EditorCommand::KillLine => self.kill_line(),
+            EditorCommand::Indent | EditorCommand::Dedent => {
+                let rows: Vec<usize> = std::iter::once(self.cursor_row)
+                    .chain(self.extra_cursors.iter().map(|c| c.0))
+                    .collect();
+                self.shift_indent(&rows, command == EditorCommand::Indent);
+            }
+            EditorCommand::MoveLineUp => {
+                self.move_lines(self.cursor_row, self.cursor_row, false);
+            }
+            EditorCommand::MoveLineDown => {
+                self.move_lines(self.cursor_row, self.cursor_row, true);
+            }
+            EditorCommand::DuplicateLine => self.duplicate_lines(self.cursor_row, self.cursor_row),
+            EditorCommand::MatchBracket => {
+                self.jump_to_matching_bracket();
+            }
+            _ => {}
+        }
+    }
+
+    /// Move the cursor for a select command, anchoring the selection where it was
+    fn extend_selection(&mut self, command: EditorCommand) {
+        self.extra_cursors.clear();
+        if command == EditorCommand::SelectAll {
+            self.selection_anchor = Some((0, 0));
+            self.cursor_row = self.lines.len() - 1;
+            self.cursor_col = self.current_line_len();
+            return;
+        }
+        let anchor = self.selection_anchor.unwrap_or((self.cursor_row, self.cursor_col));
+        match command {
+            EditorCommand::SelectLeft => self.move_cursor_left(),
+            EditorCommand::SelectRight => self.move_cursor_right(),
+            EditorCommand::SelectUp => self.move_cursor_up(),
+            EditorCommand::SelectDown => self.move_cursor_down(),
+            EditorCommand::SelectLineStart => self.move_cursor_home(),
+            EditorCommand::SelectLineEnd => self.move_cursor_end(),
+            _ => {}
+        }
+        self.selection_anchor = Some(anchor);
+    }
+
+    /// The selected text's (start, end) positions, `end` exclusive; `None` when
+    /// nothing is selected
+    pub fn selection(&self) -> Option<((usize, usize), (usize, usize))> {
+        let anchor = self.selection_anchor?;
+        // Edits made elsewhere (replace, refactor) may have shortened the text
+        let clamp = |(row, col): (usize, usize)| {
+            let row = row.min(self.lines.len().saturating_sub(1));
+            (row, col.min(self.lines[row].chars().count()))
+        };
+        let (anchor, cursor) = (clamp(anchor), clamp((self.cursor_row, self.cursor_col)));
+        match anchor.cmp(&cursor) {
+            std::cmp::Ordering::Less => Some((anchor, cursor)),
+            std::cmp::Ordering::Greater => Some((cursor, anchor)),
+            std::cmp::Ordering::Equal => None,
+        }
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.selection_anchor = None;
+    }
+
+    /// Remove the selected text; returns false when nothing was selected
+    pub fn delete_selection(&mut self) -> bool {
+        let selection = self.selection();
+        self.selection_anchor = None;
+        let Some((start, end)) = selection else { return false };
+        self.delete_range(start, end);
+        true
+    }
+
+    /// Add (or remove) one indent level at the start of each of `rows`, moving
+    /// carets on them along. Blank lines are skipped when several rows are indented.
+    pub fn shift_indent(&mut self, rows: &[usize], indent: bool) {
+        let mut rows: Vec<usize> = rows.iter().copied().filter(|&r| r < self.lines.len()).collect();
+        rows.sort_unstable();
+        rows.dedup();
+        let unit = self.indent.unit();
+        let skip_blank = rows.len() > 1;
+        for row in rows {
+            let line = &self.lines[row];
+            let delta = if indent {
+                if skip_blank && line.trim().is_empty() {
+                    continue;
+                }
+                self.replace_range(row, 0, 0, &unit);
+                unit.chars().count() as isize
+            } else {
+                let width = if line.starts_with('\t') {
+                    1
+                } else {
+                    line.chars().take(self.indent.width.max(1)).take_while(|&c| c == ' ').count()
+                };
+                if width == 0 {
+                    continue;
+                }
+                self.replace_range(row, 0, width, "");
+                -(width as isize)
+            };
+            if self.cursor_row == row {
+                self.cursor_col = self.cursor_col.saturating_add_signed(delta);
+            }
+            for caret in self.extra_cursors.iter_mut().filter(|c| c.0 == row) {
+                caret.1 = caret.1.saturating_add_signed(delta);
+            }
+        }
+    }
+
+    /// Comment out the given rows with the language's line comment token, or
+    /// uncomment them if every non-blank one is already commented.
+    /// Returns false when the language has no known line comment.
+    pub fn toggle_comment(&mut self, rows: &[usize]) -> bool {
+        let ext = self.highlight_cache.extension.as_deref();
+        let Some(token) = syntax::assets_blocking().line_comment(ext) else { return false };
+        let mut rows: Vec<usize> = rows.iter().copied().filter(|&r| r < self.lines.len()).collect();
+        rows.sort_unstable();
+        rows.dedup();
+        let indent_of = |line: &str| line.chars().take_while(|c| c.is_whitespace()).count();
+        let content: Vec<usize> = rows.iter().copied().filter(|&r| !self.lines[r].trim().is_empty()).collect();
+        // A lone blank line still gets a comment to type after
+        let targets = if content.is_empty() { rows } else { content };
+        let commented = |line: &str| line.trim_start().starts_with(token);
+        let uncomment = targets.iter().all(|&r| commented(&self.lines[r]));
+        let min_indent = targets.iter().map(|&r| indent_of(&self.lines[r])).min().unwrap_or(0);
+
+        for &row in &targets {
+            let (col, removed, inserted) = if uncomment {
+                let indent = indent_of(&self.lines[row]);
+                let rest = &self.lines[row][self.lines[row].len() - self.lines[row].trim_start().len()..];
+                let width = token.chars().count() + usize::from(rest[token.len()..].starts_with(' '));
+                self.replace_range(row, indent, indent + width, "");
+                (indent, width, 0)
+            } else {
+                let text = format!("{} ", token);
+                self.replace_range(row, min_indent, min_indent, &text);
+                (min_indent, 0, text.chars().count())
+            };
+            if row == self.cursor_row && self.cursor_col >= col {
+                self.cursor_col = (self.cursor_col + inserted).saturating_sub(removed).max(col);
+            }
+        }
+        true
+    }
+
+    /// Run an edit at the main cursor and every extra caret. `edit` must only change
+    /// text before or at the cursor (insertions, backspace), so carets further down
+    /// the buffer can be shifted by the same amount the cursor moved.
+    fn at_each_cursor(&mut self, mut edit: impl FnMut(&mut Self)) {
+        if self.extra_cursors.is_empty() {
+            edit(self);
+            return;
+        }
+        let main = (self.cursor_row, self.cursor_col);
+        let mut carets = self.extra_cursors.clone();
+        carets.push(main);
+        carets.sort_unstable();
+        carets.dedup();
+
+        // Bottom-most first, so each edit only moves carets that were already handled
+        let mut done: Vec<((usize, usize), bool)> = Vec::new();
+        for &pos in carets.iter().rev() {
+            (self.cursor_row, self.cursor_col) = pos;
+            edit(self);
+            let after = (self.cursor_row, self.cursor_col);
+            for (caret, _) in done.iter_mut() {
+                if *caret >= pos {
+                    *caret = if caret.0 == pos.0 {
+                        (after.0, after.1 + caret.1 - pos.1)
+                    } else {
+                        ((caret.0 + after.0).saturating_sub(pos.0), caret.1)
+                    };
+                }
+            }
+            done.push((after, pos == main));
+        }
+
+        let main = done.iter().find(|(_, is_main)| *is_main).map(|(caret, _)| *caret).unwrap_or(main);
+        (self.cursor_row, self.cursor_col) = main;
+        self.extra_cursors = done.into_iter().map(|(caret, _)| caret).filter(|&c| c != main).collect();
+        self.extra_cursors.sort_unstable();
+        self.extra_cursors.dedup();
+    }
+
+    /// Add a caret at the next whole-word occurrence of the word under the main
+    /// cursor, after the last caret and wrapping around. Returns whether one was added.
+    pub fn add_cursor_at_next_match(&mut self) -> bool {
+        let Some((start, end)) = self.word_range_at_cursor() else { return false };
+        let word: Vec<char> = self.current_line().chars().skip(start).take(end - start).collect();
+        let offset = self.cursor_col - start;
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+
+        let mut candidates = Vec::new();
+        for (row, line) in self.lines.iter().enumerate() {
+            let chars: Vec<char> = line.chars().collect();
+            for col in 0..chars.len().saturating_sub(word.len() - 1) {
+                let before_ok = col == 0 || !is_word(chars[col - 1]);
+                let after_ok = chars.get(col + word.len()).is_none_or(|&c| !is_word(c));
+                if before_ok && after_ok && chars[col..col + word.len()] == word[..] {
+                    candidates.push((row, col + offset));
+                }
+            }
+        }
+
+        let main = (self.cursor_row, self.cursor_col);
+        let taken = |pos: &(usize, usize)| *pos == main || self.extra_cursors.contains(pos);
+        let last = self.extra_cursors.iter().copied().chain(std::iter::once(main)).max().unwrap_or(main);
+        let next = candidates.iter().find(|&&pos| pos > last && !taken(&pos))
+            .or_else(|| candidates.iter().find(|pos| !taken(pos)));
+        match next {
+            Some(&pos) => {
+                self.extra_cursors.push(pos);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Add (or remove, if one is already there) a caret at a position
+    pub fn toggle_cursor(&mut self, row: usize, col: usize) {
+        let row = row.min(self.lines.len().saturating_sub(1));
+        let col = col.min(self.lines[row].chars().count());
+        if (row, col) == (self.cursor_row, self.cursor_col) {
+            return;
+        }
+        match self.extra_cursors.iter().position(|&c| c == (row, col)) {
+            Some(idx) => {
+                self.extra_cursors.remove(idx);
+            }
+            None => self.extra_cursors.push((row, col)),
+        }
+    }
+
+    /// Delete to the end of the line, or join the next line when already there
+    pub fn kill_line(&mut self) {
+        let len = self.current_line_len();
+        if self.cursor_col >= len {
+            self.delete();
+        } else {
+            self.delete_range((self.cursor_row, self.cursor_col), (self.cursor_row, len));
+        }
+    }
+
+    /// The word (alphanumerics and `_`) under or just before the cursor
+    pub fn word_at_cursor(&self) -> Option<String> {
+        let (start, end) = self.word_range_at_cursor()?;
+        Some(self.current_line().chars().skip(start).take(end - start).collect())
+    }
+
+    /// Char range of the word under or just before the cursor
+    fn word_range_at_cursor(&self) -> Option<(usize, usize)> {
+        let chars: Vec<char> = self.current_line().chars().collect();
+        let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+        let mut start = self.cursor_col.min(chars.len());
+        if start == chars.len() || !is_word(&chars[start]) {
+            // Allow the cursor to sit just after the word
+            if start > 0 && is_word(&chars[start - 1]) {
+                start -= 1;
+            } else {
+                return None;
+            }
+        }
+        let mut end = start;
+        while start > 0 && is_word(&chars[start - 1]) {
+            start -= 1;
+        }
+        while end < chars.len() && is_word(&chars[end]) {
+            end += 1;
+        }
+        Some((start, end))
+    }
+
     pub fn line_count(&self) -> usize {
         self.lines.len()
     }
 
-    fn current_line(&self) -> &str {
+    pub fn current_line(&self) -> &str {
         self.lines.get(self.cursor_row).map(|s| s.as_str()).unwrap_or("")
     }
 
-    fn current_line_len(&self) -> usize {
+    pub fn current_line_len(&self) -> usize {
         self.current_line().chars().count()
     }
 
@@ -134,19 +1040,142 @@ impl EditorState {
         }
     }
 
+    /// Insert a typed character: opening brackets and quotes get their closing
+    /// character too, and typing a closing character that is already next steps over it
+    pub fn type_char(&mut self, c: char) {
+        let chars: Vec<char> = self.current_line().chars().collect();
+        let next = chars.get(self.cursor_col).copied();
+        let prev = self.cursor_col.checked_sub(1).and_then(|i| chars.get(i)).copied();
+        let is_quote = matches!(c, '"' | '\'' | '`');
+        if next == Some(c) && (is_quote || BRACKETS.iter().any(|&(_, close)| close == c)) {
+            self.cursor_col += 1;
+            return;
+        }
+
+        // Only pair before whitespace or a closer, and not for apostrophes inside words
+        let closer = BRACKETS.iter().find(|&&(open, _)| open == c).map(|&(_, close)| close)
+            .or_else(|| (is_quote && !prev.is_some_and(char::is_alphanumeric)).then_some(c));
+        let free = next.is_none_or(|n| n.is_whitespace() || BRACKETS.iter().any(|&(_, close)| close == n));
+        // Another caret later on this line would be shifted by one char only
+        let shared_row = self.extra_cursors.iter().any(|&(row, _)| row == self.cursor_row);
+        self.insert_char(c);
+        if let Some(closer) = closer.filter(|_| free && !shared_row) {
+            self.insert_char(closer);
+            self.cursor_col -= 1;
+        }
+    }
+
+    /// Positions of the bracket under (or else just before) the cursor and its match
+    pub fn matching_bracket(&self) -> Option<((usize, usize), (usize, usize))> {
+        let chars: Vec<char> = self.current_line().chars().collect();
+        let col = [Some(self.cursor_col), self.cursor_col.checked_sub(1)].into_iter().flatten()
+            .find(|&col| chars.get(col).is_some_and(|&c| BRACKETS.iter().any(|&(o, cl)| c == o || c == cl)))?;
+        let other = self.bracket_match((self.cursor_row, col))?;
+        Some(((self.cursor_row, col), other))
+    }
+
+    /// Move the cursor to the bracket matching the one under (or else just
+    /// before) it. Brackets inside strings and comments are ignored once the
+    /// syntax assets have loaded. Returns false when there is no match.
+    pub fn jump_to_matching_bracket(&mut self) -> bool {
+        let end = match syntax::assets() {
+            Some(_) => (self.cursor_row + BRACKET_SCAN_LINES + 1).min(self.lines.len()),
+            None => 0,
+        };
+        let non_code = syntax::assets()
+            .map(|assets| assets.non_code_ranges(self.highlight_cache.extension.as_deref(), &self.lines[..end]))
+            .unwrap_or_default();
+        let skip = |(row, col): (usize, usize)| non_code.get(row).is_some_and(|r| r.iter().any(|r| r.contains(&col)));
+
+        let chars: Vec<char> = self.current_line().chars().collect();
+        let Some(col) = [Some(self.cursor_col), self.cursor_col.checked_sub(1)].into_iter().flatten()
+            .find(|&col| !skip((self.cursor_row, col)) && chars.get(col).is_some_and(|&c| BRACKETS.iter().any(|&(o, cl)| c == o || c == cl)))
+        else {
+            return false;
+        };
+        match self.bracket_match_skipping((self.cursor_row, col), &skip) {
+            Some((row, col)) => {
+                self.cursor_row = row;
+                self.cursor_col = col;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Position of the bracket matching the one at `(row, col)`
+    fn bracket_match(&self, pos: (usize, usize)) -> Option<(usize, usize)> {
+        self.bracket_match_skipping(pos, &|_| false)
+    }
+
+    /// Like `bracket_match`, ignoring brackets at positions where `skip` is true
+    fn bracket_match_skipping(&self, (start_row, col): (usize, usize), skip: &dyn Fn((usize, usize)) -> bool) -> Option<(usize, usize)> {
+        let mut line: Vec<char> = self.lines.get(start_row)?.chars().collect();
+        let bracket = *line.get(col)?;
+        let (forward, (open, close)) = BRACKETS.iter()
+            .find_map(|&(o, cl)| if bracket == o { Some((true, (o, cl))) } else if bracket == cl { Some((false, (o, cl))) } else { None })?;
+        let (same, other) = if forward { (open, close) } else { (close, open) };
+
+        let mut depth = 0usize;
+        let mut row = start_row;
+        let mut pos = col;
+        loop {
+            let c = line[pos];
+            if skip((row, pos)) {
+                // In a string or comment
+            } else if c == same {
+                depth += 1;
+            } else if c == other {
+                depth -= 1;
+                if depth == 0 {
+                    return Some((row, pos));
+                }
+            }
+            // Step to the next char in the scan direction, crossing lines
+            loop {
+                if forward {
+                    if pos + 1 < line.len() {
+                        pos += 1;
+                        break;
+                    }
+                    row += 1;
+                    if row - start_row > BRACKET_SCAN_LINES {
+                        return None;
+                    }
+                    line = self.lines.get(row)?.chars().collect();
+                    if !line.is_empty() {
+                        pos = 0;
+                        break;
+                    }
+                } else {
+                    if pos > 0 {
+                        pos -= 1;
+                        break;
+                    }
+                    row = row.checked_sub(1).filter(|&r| start_row - r <= BRACKET_SCAN_LINES)?;
+                    line = self.lines[row].chars().collect();
+                    if !line.is_empty() {
+                        pos = line.len() - 1;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
     pub fn backspace(&mut self) {
         if self.cursor_col > 0 {
             if let Some(line) = self.lines.get_mut(self.cursor_row) {
-                let byte_idx: usize = line.chars().take(self.cursor_col).map(|c| c.len_utf8()).sum();
-                let char_to_remove = line.chars().nth(self.cursor_col - 1).unwrap();
-                let remove_start = byte_idx - char_to_remove.len_utf8();
-                line.remove(remove_start);
-                self.cursor_col -= 1;
+                // The whole character the user sees: an emoji sequence or a letter with its accents
+                let start = prev_grapheme(line, self.cursor_col);
+                line.replace_range(byte_index(line, start)..byte_index(line, self.cursor_col), "");
+                self.cursor_col = start;
                 self.highlight_cache.invalidate(self.cursor_row);
                 self.modified = true;
             }
         } else if self.cursor_row > 0 {
             let current_line = self.lines.remove(self.cursor_row);
+            self.shift_folds(self.cursor_row, -1);
             self.cursor_row -= 1;
             self.cursor_col = self.lines[self.cursor_row].chars().count();
             self.lines[self.cursor_row].push_str(&current_line);
@@ -160,14 +1189,14 @@ impl EditorState {
         let line_len = self.current_line_len();
         if self.cursor_col < line_len {
             if let Some(line) = self.lines.get_mut(self.cursor_row) {
-                let byte_idx: usize = line.chars().take(self.cursor_col).map(|c| c.len_utf8()).sum();
-                let char_to_remove = line.chars().nth(self.cursor_col).unwrap();
-                line.drain(byte_idx..byte_idx + char_to_remove.len_utf8());
+                let end = next_grapheme(line, self.cursor_col);
+                line.replace_range(byte_index(line, self.cursor_col)..byte_index(line, end), "");
                 self.highlight_cache.invalidate(self.cursor_row);
                 self.modified = true;
             }
         } else if self.cursor_row < self.lines.len() - 1 {
             let next_line = self.lines.remove(self.cursor_row + 1);
+            self.shift_folds(self.cursor_row + 1, -1);
             self.lines[self.cursor_row].push_str(&next_line);
             self.highlight_cache.invalidate(self.cursor_row);
             self.highlight_cache.resize(self.lines.len());
@@ -181,6 +1210,7 @@ impl EditorState {
             let remainder = line.split_off(byte_idx);
             self.highlight_cache.invalidate(self.cursor_row);
             self.lines.insert(self.cursor_row + 1, remainder);
+            self.shift_folds(self.cursor_row + 1, 1);
             self.cursor_row += 1;
             self.cursor_col = 0;
             self.highlight_cache.resize(self.lines.len());
@@ -190,23 +1220,28 @@ impl EditorState {
 
     pub fn move_cursor_up(&mut self) {
         if self.cursor_row > 0 {
-            self.cursor_row -= 1;
-            self.cursor_col = self.cursor_col.min(self.current_line_len());
+            self.move_to_row(self.visible_row(self.cursor_row - 1));
         }
     }
 
     pub fn move_cursor_down(&mut self) {
-        if self.cursor_row < self.lines.len() - 1 {
-            self.cursor_row += 1;
-            self.cursor_col = self.cursor_col.min(self.current_line_len());
+        if let Some(row) = self.visible_rows(self.cursor_row).nth(1) {
+            self.move_to_row(row);
         }
     }
 
+    /// Move to `row` keeping the cursor's screen column (CJK text is two cells wide)
+    fn move_to_row(&mut self, row: usize) {
+        let width = display_width(self.current_line(), self.cursor_col);
+        self.cursor_row = row;
+        self.cursor_col = col_at_width(self.current_line(), width);
+    }
+
     pub fn move_cursor_left(&mut self) {
         if self.cursor_col > 0 {
-            self.cursor_col -= 1;
+            self.cursor_col = prev_grapheme(self.current_line(), self.cursor_col);
         } else if self.cursor_row > 0 {
-            self.cursor_row -= 1;
+            self.cursor_row = self.visible_row(self.cursor_row - 1);
             self.cursor_col = self.current_line_len();
         }
     }
@@ -214,9 +1249,9 @@ impl EditorState {
     pub fn move_cursor_right(&mut self) {
         let line_len = self.current_line_len();
         if self.cursor_col < line_len {
-            self.cursor_col += 1;
-        } else if self.cursor_row < self.lines.len() - 1 {
-            self.cursor_row += 1;
+            self.cursor_col = next_grapheme(self.current_line(), self.cursor_col);
+        } else if let Some(row) = self.visible_rows(self.cursor_row).nth(1) {
+            self.cursor_row = row;
             self.cursor_col = 0;
         }
     }
@@ -229,14 +1264,36 @@ impl EditorState {
         self.cursor_col = self.current_line_len();
     }
 
+    /// Move the cursor to a (0-based) position, clamped to the buffer
+    pub fn goto(&mut self, row: usize, col: usize) {
+        if self.loading {
+            self.pending_goto = Some((row, col));
+            return;
+        }
+        self.cursor_row = row.min(self.lines.len().saturating_sub(1));
+        self.cursor_col = col.min(self.current_line_len());
+        self.selection_anchor = None;
+        // Keep a few lines of context above the target
+        self.scroll_offset = self.cursor_row.saturating_sub(5);
+    }
+
+    /// Scroll so the cursor is on screen, opening any fold it was moved into
+    /// (by a jump or a search match)
     pub fn ensure_cursor_visible(&mut self, viewport_height: usize) {
         if viewport_height == 0 {
             return;
         }
+        self.reveal(self.cursor_row);
+        self.scroll_offset = self.visible_row(self.scroll_offset);
         if self.cursor_row < self.scroll_offset {
             self.scroll_offset = self.cursor_row;
-        } else if self.cursor_row >= self.scroll_offset + viewport_height {
-            self.scroll_offset = self.cursor_row - viewport_height + 1;
+            return;
+        }
+        let shown: Vec<usize> = self.visible_rows(self.scroll_offset)
+            .take_while(|&row| row <= self.cursor_row)
+            .collect();
+        if shown.len() > viewport_height {
+            self.scroll_offset = shown[shown.len() - viewport_height];
         }
     }
 
@@ -253,7 +1310,7 @@ impl EditorState {
         self.scroll_offset = self.scroll_offset.saturating_sub(viewport_height);
         // Move cursor to stay in view
         if self.cursor_row >= self.scroll_offset + viewport_height {
-            self.cursor_row = self.scroll_offset + viewport_height.saturating_sub(1);
+            self.cursor_row = self.visible_row(self.scroll_offset + viewport_height.saturating_sub(1));
         }
     }
 
@@ -262,15 +1319,137 @@ impl EditorState {
         self.scroll_offset = (self.scroll_offset + viewport_height).min(max_scroll);
         // Move cursor to stay in view
         if self.cursor_row < self.scroll_offset {
-            self.cursor_row = self.scroll_offset;
+            self.cursor_row = self.visible_row(self.scroll_offset);
         }
         self.cursor_col = self.cursor_col.min(self.current_line_len());
     }
 
-    pub fn get_highlighted_line(&mut self, line_idx: usize) -> Line<'static> {
+    /// Last row of the foldable region starting at `row`: the block opened by a
+    /// bracket left unclosed on the line, or else the following lines indented
+    /// deeper than it
+    pub fn fold_end(&self, row: usize) -> Option<usize> {
+        let line = self.lines.get(row)?;
+        let mut open = Vec::new();
+        for (col, c) in line.chars().enumerate() {
+            if BRACKETS.iter().any(|&(o, _)| c == o) {
+                open.push(col);
+            } else if BRACKETS.iter().any(|&(_, cl)| c == cl) {
+                open.pop();
+            }
+        }
+        if let Some(&col) = open.last() {
+            return self.bracket_match((row, col)).map(|(end, _)| end).filter(|&end| end > row);
+        }
+
+        let indent = |line: &str| line.chars().take_while(|c| c.is_whitespace()).count();
+        if line.trim().is_empty() {
+            return None;
+        }
+        let base = indent(line);
+        let mut end = None;
+        for (offset, next) in self.lines[row + 1..].iter().enumerate() {
+            if next.trim().is_empty() {
+                continue;
+            }
+            if indent(next) <= base {
+                break;
+            }
+            end = Some(row + 1 + offset);
+        }
+        end
+    }
+
+    /// Folded regions as (first, last) rows, outer folds before the ones they contain
+    pub fn folded_ranges(&self) -> Vec<(usize, usize)> {
+        self.folds.iter().filter_map(|&start| Some((start, self.fold_end(start)?))).collect()
+    }
+
+    /// Innermost foldable region containing `row`
+    fn fold_region(&self, row: usize) -> Option<(usize, usize)> {
+        (row.saturating_sub(BRACKET_SCAN_LINES)..=row).rev()
+            .find_map(|start| self.fold_end(start).filter(|&end| end >= row).map(|end| (start, end)))
+    }
+
+    /// Fold the region around the cursor, leaving the cursor on its first line
+    pub fn fold(&mut self) -> bool {
+        // Folding again from a folded line closes the region around it
+        let row = self.cursor_row;
+        let region = match self.folds.contains(&row) {
+            true => row.checked_sub(1).and_then(|r| self.fold_region(r)).filter(|&(_, end)| end >= row),
+            false => self.fold_region(row),
+        };
+        let Some((start, _)) = region else { return false };
+        if let Err(pos) = self.folds.binary_search(&start) {
+            self.folds.insert(pos, start);
+        }
+        self.extra_cursors.clear();
+        self.cursor_row = start;
+        self.cursor_col = self.cursor_col.min(self.current_line_len());
+        true
+    }
+
+    /// Open the fold on the cursor line
+    pub fn unfold(&mut self) -> bool {
+        let before = self.folds.len();
+        let row = self.cursor_row;
+        self.folds.retain(|&start| start != row);
+        self.folds.len() != before
+    }
+
+    pub fn toggle_fold(&mut self) -> bool {
+        self.unfold() || self.fold()
+    }
+
+    pub fn unfold_all(&mut self) {
+        self.folds.clear();
+    }
+
+    /// Open every fold that hides `row`
+    pub fn reveal(&mut self, row: usize) {
+        let hiding: Vec<usize> = self.folded_ranges().into_iter()
+            .filter(|&(start, end)| start < row && row <= end)
+            .map(|(start, _)| start)
+            .collect();
+        self.folds.retain(|start| !hiding.contains(start));
+    }
+
+    /// `row`, or the first line of the outermost fold hiding it
+    fn visible_row(&self, row: usize) -> usize {
+        self.folded_ranges().into_iter()
+            .find(|&(start, end)| start < row && row <= end)
+            .map_or(row, |(start, _)| start)
+    }
+
+    /// Rows shown on screen from `from` on, skipping the insides of folds
+    pub fn visible_rows(&self, from: usize) -> impl Iterator<Item = usize> {
+        let folds = self.folded_ranges();
+        let len = self.lines.len();
+        let mut next = from;
+        std::iter::from_fn(move || {
+            let row = next;
+            if row >= len {
+                return None;
+            }
+            next = folds.iter().find(|&&(start, _)| start == row).map_or(row + 1, |&(_, end)| end + 1);
+            Some(row)
+        })
+    }
+
+    /// Keep folds on the same text after `delta` lines were inserted (positive)
+    /// or removed (negative) at `at`; folds starting on removed lines are dropped
+    fn shift_folds(&mut self, at: usize, delta: isize) {
+        let removed = at..at + delta.min(0).unsigned_abs();
+        self.folds.retain(|start| !removed.contains(start));
+        for start in self.folds.iter_mut().filter(|start| **start >= at) {
+            *start = start.saturating_add_signed(delta);
+        }
+    }
+
+    /// Colors of a line; empty (plain text) until it is highlighted
+    pub fn highlight_spans(&mut self, line_idx: usize) -> Vec<HighlightSpan> {
         let content = match self.lines.get(line_idx) {
             Some(line) => line.clone(),
-            None => return Line::default(),
+            None => return Vec::new(),
         };
 
         let content_hash = HighlightCache::hash_line(&content);
@@ -284,58 +1463,230 @@ impl EditorState {
             }
         }
 
-        // Highlight the line
-        let highlighted = self.highlight_line(&content);
+        // Other lines are highlighted in the background (see `highlight_requests`);
+        // only the line being typed on is done here so it doesn't flicker
+        if line_idx != self.cursor_row || content.chars().count() > SYNC_HIGHLIGHT_CHARS || self.uses_syntax_tree() {
+            return Vec::new();
+        }
+        // Syntax assets are still loading: show plain text and don't cache it
+        let Some(assets) = syntax::assets() else {
+            return Vec::new();
+        };
+        let spans = highlight::syntect_line(assets, self.highlight_cache.extension.as_deref(), &syntax::current_theme(), &content);
+        self.store_highlight(line_idx, content_hash, spans.clone());
+        spans
+    }
 
-        // Cache result
-        self.highlight_cache.resize(line_idx + 1);
-        self.highlight_cache.lines[line_idx] = Some(highlighted.clone());
+    fn store_highlight(&mut self, line_idx: usize, content_hash: u64, line: Vec<HighlightSpan>) {
+        self.highlight_cache.resize(self.lines.len().max(line_idx + 1));
+        self.highlight_cache.lines[line_idx] = Some(line);
         self.highlight_cache.line_hashes[line_idx] = content_hash;
+    }
 
-        highlighted
+    /// Whether tree-sitter highlights this buffer rather than syntect
+    #[cfg(feature = "tree-sitter")]
+    fn uses_syntax_tree(&self) -> bool {
+        self.syntax_tree.is_some()
     }
 
-    fn highlight_line(&self, content: &str) -> Line<'static> {
-        let ext = self.highlight_cache.extension.as_deref();
-        let syntax = ext
-            .and_then(|e| self.syntax_set.find_syntax_by_extension(e))
-            .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text());
-
-        let theme = &self.theme_set.themes["base16-ocean.dark"];
-        let mut highlighter = HighlightLines::new(syntax, theme);
-
-        match highlighter.highlight_line(content, &self.syntax_set) {
-            Ok(ranges) => {
-                let spans: Vec<Span<'static>> = ranges
-                    .into_iter()
-                    .filter_map(|segment| {
-                        into_span(segment).ok().map(|span| {
-                            // Convert borrowed span to owned for 'static lifetime
-                            // Only use foreground color, strip background to avoid visual artifacts
-                            let style = Style::default().fg(span.style.fg.unwrap_or(Color::Reset));
-                            Span::styled(span.content.to_string(), style)
-                        })
-                    })
-                    .collect();
-                Line::from(spans)
+    #[cfg(not(feature = "tree-sitter"))]
+    fn uses_syntax_tree(&self) -> bool {
+        false
+    }
+
+    /// With the tree-sitter backend, re-parse what changed since the last
+    /// render and highlight the `rows` that aren't yet; languages without a
+    /// grammar stay on syntect
+    #[cfg(feature = "tree-sitter")]
+    fn update_syntax_tree(&mut self, rows: &[usize]) {
+        use crate::shared::syntax_tree::Document;
+
+        let language = self.highlight_cache.extension.as_deref()
+            .filter(|_| highlight::backend() == highlight::HighlightBackend::TreeSitter);
+        if self.syntax_tree.as_ref().map(Document::language) != language {
+            self.syntax_tree = language.and_then(Document::new);
+            self.highlight_cache.invalidate_all();
+        }
+        let Some(tree) = &mut self.syntax_tree else { return };
+        for row in tree.update(&self.lines) {
+            self.highlight_cache.invalidate(row);
+        }
+        let Some(assets) = syntax::assets() else { return };
+        let cache = &mut self.highlight_cache;
+        cache.resize(self.lines.len());
+        let missing: Vec<usize> = rows.iter().copied()
+            .filter(|&row| cache.lines[row].is_none() || cache.line_hashes[row] != HighlightCache::hash_line(&self.lines[row]))
+            .collect();
+        let (Some(&first), Some(&last)) = (missing.iter().min(), missing.iter().max()) else { return };
+        let spans = tree.highlight(first..last + 1, assets.theme(&syntax::current_theme()));
+        for row in missing {
+            cache.lines[row] = spans.get(row - first).cloned();
+            cache.line_hashes[row] = HighlightCache::hash_line(&self.lines[row]);
+        }
+    }
+
+    /// Lines in and below the last rendered viewport that aren't highlighted
+    /// yet, for the background highlighter
+    pub fn highlight_requests(&self) -> Option<HighlightRequest> {
+        if self.loading || self.uses_syntax_tree() {
+            return None;
+        }
+        let cache = &self.highlight_cache;
+        let lines: Vec<(usize, String)> = self.visible_rows(self.scroll_offset)
+            .take(self.viewport_height + HIGHLIGHT_LOOKAHEAD)
+            .filter_map(|row| {
+                let content = self.lines.get(row)?;
+                let cached = cache.lines.get(row).is_some_and(Option::is_some)
+                    && cache.line_hashes.get(row) == Some(&HighlightCache::hash_line(content));
+                (!cached).then(|| (row, content.clone()))
+            })
+            .collect();
+        (!lines.is_empty()).then(|| HighlightRequest {
+            extension: cache.extension.clone(),
+            theme: syntax::current_theme(),
+            lines,
+        })
+    }
+
+    /// Take highlighted lines from the background highlighter; lines edited since,
+    /// or done for another language or theme, are dropped
+    pub fn apply_highlights(&mut self, request: HighlightRequest, highlighted: Vec<Vec<HighlightSpan>>) {
+        if self.uses_syntax_tree() || request.extension != self.highlight_cache.extension || request.theme != syntax::current_theme() {
+            return;
+        }
+        for ((row, content), line) in request.lines.into_iter().zip(highlighted) {
+            if self.lines.get(row) == Some(&content) {
+                self.store_highlight(row, HighlightCache::hash_line(&content), line);
             }
-            Err(_) => Line::from(content.to_string()),
         }
     }
+    /// The selected text, or the cursor line when nothing is selected
     pub fn copy(&self) -> Option<String> {
-        // TODO: Implement selection support. For now, copy current line.
-        Some(self.current_line().to_string())
+        match self.selection() {
+            Some((start, end)) => Some(self.text_range(start, end)),
+            None => Some(self.current_line().to_string()),
+        }
+    }
+
+    /// Remove and return the selected text, or the cursor line when nothing is selected
+    pub fn cut(&mut self) -> Option<String> {
+        let text = self.copy();
+        if !self.delete_selection() {
+            self.extra_cursors.clear();
+            self.delete_lines(self.cursor_row, 1);
+        }
+        text
     }
 
+    /// Insert `text` at every caret, replacing the selection
     pub fn paste(&mut self, text: &str) {
-        for c in text.chars() {
-            if c == '\n' {
-                self.insert_newline();
-            } else {
-                self.insert_char(c);
+        self.delete_selection();
+        self.at_each_cursor(|state| {
+            for c in text.chars() {
+                if c == '\n' {
+                    state.insert_newline();
+                } else {
+                    state.insert_char(c);
+                }
+            }
+        });
+    }
+
+    /// Width of the line number gutter
+    fn gutter_width(&self) -> u16 {
+        if !self.show_gutter {
+            return 0;
+        }
+        ((self.line_count().max(1) as f64).log10().floor() as u16) + 3
+    }
+
+    /// Buffer position under a screen cell, given the editor's inner area. The
+    /// gutter counts as the start of its line; the column may be past the line's end.
+    pub fn position_at(&self, inner: Rect, x: u16, y: u16) -> Option<(usize, usize)> {
+        let content_x = inner.x + self.gutter_width();
+        if x < inner.x || y < inner.y || x >= inner.x + inner.width || y >= inner.y + inner.height {
+            return None;
+        }
+        let row = self.visible_rows(self.scroll_offset).nth((y - inner.y) as usize)?;
+        let offset = x.saturating_sub(content_x) as usize;
+        let line = self.lines.get(row).map(String::as_str).unwrap_or("");
+        let hints = self.line_hints(row);
+        // Clicks past the end keep their distance from it
+        let end = self.display_x(row, usize::MAX);
+        if offset >= end {
+            return Some((row, line.chars().count() + offset - end));
+        }
+        // A click on a hint goes to the text after it
+        let (mut x, mut col) = (0, 0);
+        for grapheme in line.graphemes(true) {
+            x += hints.iter().filter(|h| h.col == col).map(|h| h.label.width()).sum::<usize>() + grapheme_width(grapheme);
+            if x > offset {
+                break;
+            }
+            col += grapheme.chars().count();
+        }
+        Some((row, col))
+    }
+
+    /// Show `hints` (in order) on their lines until those lines are edited
+    pub fn set_inlay_hints(&mut self, hints: Vec<InlayHint>) {
+        self.inlay_hints = hints.into_iter()
+            .filter_map(|hint| {
+                let hash = HighlightCache::hash_line(self.lines.get(hint.row)?);
+                Some((hint, hash))
+            })
+            .collect();
+    }
+
+    pub fn has_inlay_hints(&self) -> bool {
+        !self.inlay_hints.is_empty()
+    }
+
+    /// Inlay hints of `row` from left to right; none once the line was edited
+    /// since they came
+    fn line_hints(&self, row: usize) -> Vec<&InlayHint> {
+        let start = self.inlay_hints.partition_point(|(hint, _)| hint.row < row);
+        let hints = &self.inlay_hints[start..];
+        let current = |hash: u64| self.lines.get(row).is_some_and(|line| HighlightCache::hash_line(line) == hash);
+        match hints.first() {
+            Some((hint, hash)) if hint.row == row && current(*hash) => {
+                hints.iter().take_while(|(hint, _)| hint.row == row).map(|(hint, _)| hint).collect()
             }
+            _ => Vec::new(),
         }
     }
+
+    /// Screen cells before char column `col` of `row`: the text and the inlay
+    /// hints drawn before it
+    fn display_x(&self, row: usize, col: usize) -> usize {
+        let line = self.lines.get(row).map(String::as_str).unwrap_or("");
+        let hints: usize = self.line_hints(row).iter().filter(|h| h.col <= col).map(|h| h.label.width()).sum();
+        display_width(line, col) + hints
+    }
+
+    /// Screen cell of a buffer position, given the editor's inner area; `None`
+    /// when it is scrolled out of view
+    pub fn screen_position(&self, inner: Rect, row: usize, col: usize) -> Option<(u16, u16)> {
+        let y = self.visible_rows(self.scroll_offset).take(inner.height as usize).position(|r| r == row)?;
+        let x = inner.x as usize + self.gutter_width() as usize + self.display_x(row, col);
+        Some((x.min(inner.right().saturating_sub(1) as usize) as u16, inner.y + y as u16))
+    }
+
+    /// Move the cursor to a clicked position, clamped to the text. With `extend`
+    /// the selection grows from where the cursor was (Shift+click, dragging).
+    pub fn place_cursor(&mut self, row: usize, col: usize, extend: bool) {
+        let anchor = self.selection_anchor.unwrap_or((self.cursor_row, self.cursor_col));
+        self.extra_cursors.clear();
+        self.cursor_row = row.min(self.lines.len().saturating_sub(1));
+        self.cursor_col = col.min(self.current_line_len());
+        self.selection_anchor = extend.then_some(anchor);
+    }
+}
+
+impl Default for EditorState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 /// Widget for rendering the editor with syntax highlighting
@@ -344,6 +1695,14 @@ pub struct EditorWidget<'a> {
     line_number_style: Style,
     cursor_style: Style,
     focused: bool,
+    line_numbers: bool,
+    indent_guides: bool,
+    indent_guide_style: Style,
+    bracket_style: Style,
+    inlay_hint_style: Style,
+    annotations: Vec<LineAnnotation>,
+    gutter_markers: Vec<GutterMarker>,
+    highlights: Vec<RangeHighlight>,
 }
 
 impl<'a> EditorWidget<'a> {
@@ -353,6 +1712,14 @@ impl<'a> EditorWidget<'a> {
             line_number_style: Style::default().fg(Color::DarkGray),
             cursor_style: Style::default().bg(Color::White).fg(Color::Black),
             focused: false,
+            line_numbers: true,
+            indent_guides: false,
+            indent_guide_style: Style::default().fg(Color::DarkGray),
+            bracket_style: Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+            inlay_hint_style: Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC),
+            annotations: Vec::new(),
+            gutter_markers: Vec::new(),
+            highlights: Vec::new(),
         }
     }
 
@@ -375,6 +1742,163 @@ impl<'a> EditorWidget<'a> {
         self.focused = focused;
         self
     }
+
+    pub fn line_numbers(mut self, line_numbers: bool) -> Self {
+        self.line_numbers = line_numbers;
+        self
+    }
+
+    pub fn indent_guides(mut self, indent_guides: bool) -> Self {
+        self.indent_guides = indent_guides;
+        self
+    }
+
+    pub fn indent_guide_style(mut self, style: Style) -> Self {
+        self.indent_guide_style = style;
+        self
+    }
+
+    /// Style added to the bracket under the cursor and its match
+    pub fn bracket_style(mut self, style: Style) -> Self {
+        self.bracket_style = style;
+        self
+    }
+
+    /// Style of the inlay hints drawn inside lines
+    pub fn inlay_hint_style(mut self, style: Style) -> Self {
+        self.inlay_hint_style = style;
+        self
+    }
+
+    pub fn annotations(mut self, annotations: Vec<LineAnnotation>) -> Self {
+        self.annotations = annotations;
+        self
+    }
+
+    pub fn gutter_markers(mut self, markers: Vec<GutterMarker>) -> Self {
+        self.gutter_markers = markers;
+        self
+    }
+
+    pub fn highlights(mut self, highlights: Vec<RangeHighlight>) -> Self {
+        self.highlights = highlights;
+        self
+    }
+}
+
+/// Whether we lack permission to write `path` (read-only mode bits, or e.g. owned by root)
+/// Lines to highlight in the background, with what they're highlighted for
+#[derive(Debug, Clone)]
+pub struct HighlightRequest {
+    pub extension: Option<String>,
+    pub theme: String,
+    /// (row, content)
+    pub lines: Vec<(usize, String)>,
+}
+
+impl HighlightRequest {
+    /// Highlight the lines; slow on long lines, so call it off the UI thread
+    pub fn run(&self) -> Vec<Vec<HighlightSpan>> {
+        let assets = syntax::assets_blocking();
+        self.lines.iter()
+            .map(|(_, content)| highlight::syntect_line(assets, self.extension.as_deref(), &self.theme, content))
+            .collect()
+    }
+}
+
+/// Byte offset of char column `col` in `line` (its length past the end)
+fn byte_index(line: &str, col: usize) -> usize {
+    line.char_indices().nth(col).map_or(line.len(), |(i, _)| i)
+}
+
+/// Cells a grapheme takes on screen; zero-width ones still get a cell
+fn grapheme_width(grapheme: &str) -> usize {
+    grapheme.width().max(1)
+}
+
+/// Char column where the grapheme before `col` starts
+pub fn prev_grapheme(line: &str, col: usize) -> usize {
+    let (mut start, mut pos) = (0, 0);
+    for grapheme in line.graphemes(true) {
+        if pos >= col {
+            break;
+        }
+        start = pos;
+        pos += grapheme.chars().count();
+    }
+    start
+}
+
+/// Char column just past the grapheme at `col`
+pub fn next_grapheme(line: &str, col: usize) -> usize {
+    let mut pos = 0;
+    for grapheme in line.graphemes(true) {
+        pos += grapheme.chars().count();
+        if pos > col {
+            break;
+        }
+    }
+    pos
+}
+
+/// Screen cells taken by the first `col` chars of `line`
+pub fn display_width(line: &str, col: usize) -> usize {
+    let (mut width, mut pos) = (0, 0);
+    for grapheme in line.graphemes(true) {
+        if pos >= col {
+            break;
+        }
+        width += grapheme_width(grapheme);
+        pos += grapheme.chars().count();
+    }
+    width
+}
+
+/// Draw as much of `text` as fits left of `right`; returns the cells it took
+fn draw_clipped(buf: &mut Buffer, x: u16, y: u16, right: u16, text: &str, style: Style) -> u16 {
+    let mut width = 0;
+    for grapheme in text.graphemes(true) {
+        let cells = grapheme_width(grapheme) as u16;
+        if x + width + cells > right {
+            break;
+        }
+        buf.set_string(x + width, y, grapheme, style);
+        width += cells;
+    }
+    width
+}
+
+/// Char column of the grapheme drawn at screen cell `width` of `line` (its
+/// length past the end)
+pub fn col_at_width(line: &str, width: usize) -> usize {
+    let (mut x, mut pos) = (0, 0);
+    for grapheme in line.graphemes(true) {
+        x += grapheme_width(grapheme);
+        if x > width {
+            break;
+        }
+        pos += grapheme.chars().count();
+    }
+    pos
+}
+
+pub fn is_read_only(path: &Path) -> bool {
+    match fs::metadata(path) {
+        // Opening for append checks write access without touching the contents
+        Ok(meta) => meta.permissions().readonly() || fs::OpenOptions::new().append(true).open(path).is_err(),
+        Err(_) => false,
+    }
+}
+
+fn disk_stamp(path: &Path) -> Option<(u64, SystemTime)> {
+    let meta = fs::metadata(path).ok()?;
+    Some((meta.len(), meta.modified().ok()?))
+}
+
+impl Default for EditorWidget<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<'a> StatefulWidget for EditorWidget<'a> {
@@ -395,55 +1919,526 @@ impl<'a> StatefulWidget for EditorWidget<'a> {
             return;
         }
 
+        if state.loading {
+            buf.set_string(inner_area.x + 1, inner_area.y, "Loading…", self.line_number_style);
+            return;
+        }
+
         // Calculate gutter width
+        state.show_gutter = self.line_numbers;
+        state.annotation_hits.clear();
         let line_count = state.line_count();
-        let gutter_width = ((line_count.max(1) as f64).log10().floor() as u16) + 3;
+        let gutter_width = state.gutter_width();
         let _content_width = inner_area.width.saturating_sub(gutter_width);
         let viewport_height = inner_area.height as usize;
+        state.viewport_height = viewport_height;
 
         // Ensure cursor is visible
         state.ensure_cursor_visible(viewport_height);
+        let brackets = if self.focused { state.matching_bracket() } else { None };
+        let is_bracket = |pos: (usize, usize)| brackets.is_some_and(|(a, b)| pos == a || pos == b);
 
-        // Render visible lines
-        for (view_row, line_idx) in (state.scroll_offset..)
-            .take(viewport_height)
-            .enumerate()
-        {
+        // Render visible lines; a folded region shows only its first line
+        let folds = state.folded_ranges();
+        let rows: Vec<usize> = state.visible_rows(state.scroll_offset).take(viewport_height).collect();
+        #[cfg(feature = "tree-sitter")]
+        state.update_syntax_tree(&rows);
+        for (view_row, line_idx) in rows.into_iter().enumerate() {
             let y = inner_area.y + view_row as u16;
 
             if line_idx < line_count {
                 // Render line number
-                let line_num = format!("{:>width$} ", line_idx + 1, width = (gutter_width - 2) as usize);
-                buf.set_string(inner_area.x, y, &line_num, self.line_number_style);
+                if gutter_width > 0 {
+                    let line_num = format!("{:>width$} ", line_idx + 1, width = (gutter_width - 2) as usize);
+                    buf.set_string(inner_area.x, y, &line_num, self.line_number_style);
+                    if let Some(marker) = self.gutter_markers.iter().find(|m| m.line == line_idx) {
+                        buf.set_string(inner_area.x + gutter_width - 2, y, marker.symbol, marker.style);
+                    }
+                }
 
                 // Render highlighted content
                 let content_x = inner_area.x + gutter_width;
-                let highlighted_line = state.get_highlighted_line(line_idx);
+                let spans = state.highlight_spans(line_idx);
+                let content = state.lines.get(line_idx).cloned().unwrap_or_default();
+                let hints: Vec<InlayHint> = state.line_hints(line_idx).into_iter().cloned().collect();
+                let right = inner_area.x + inner_area.width;
+
+                let line_highlights: Vec<&RangeHighlight> = self.highlights.iter()
+                    .filter(|h| h.line == line_idx)
+                    .collect();
 
                 let mut x = content_x;
-                for span in highlighted_line.spans.iter() {
-                    let text = span.content.as_ref();
-                    for ch in text.chars() {
-                        if x >= inner_area.x + inner_area.width {
+                let mut col = 0;
+                let mut span = spans.iter().peekable();
+                // Whole graphemes, so accents stay on their letter and wide characters take two cells
+                for grapheme in content.graphemes(true) {
+                    for hint in hints.iter().filter(|h| h.col == col) {
+                        x += draw_clipped(buf, x, y, right, &hint.label, self.inlay_hint_style);
+                    }
+                    let width = grapheme_width(grapheme) as u16;
+                    if x + width > right {
+                        break;
+                    }
+                    while span.next_if(|s| s.end <= col).is_some() {}
+                    let syntax_style = match span.peek() {
+                        Some(s) if s.start <= col => Style::default().fg(s.fg.map_or(Color::Reset, |[r, g, b]| Color::Rgb(r, g, b))),
+                        _ => Style::default(),
+                    };
+                    let mut style = line_highlights.iter()
+                        .filter(|h| col >= h.start && col < h.end)
+                        .fold(syntax_style, |style, h| style.patch(h.style));
+                    if is_bracket((line_idx, col)) {
+                        style = style.patch(self.bracket_style);
+                    }
+                    buf.set_string(x, y, grapheme, style);
+                    x += width;
+                    col += grapheme.chars().count();
+                }
+                // Hints after the last character, e.g. chained method types
+                if col >= content.chars().count() {
+                    for hint in hints.iter().filter(|h| h.col >= col) {
+                        x += draw_clipped(buf, x, y, right, &hint.label, self.inlay_hint_style);
+                    }
+                }
+
+                // Indentation guides over the leading whitespace (and through blank lines)
+                if self.indent_guides {
+                    for col in indent_guides::guide_columns(&state.lines, line_idx, state.indent.width) {
+                        let guide_x = content_x + col as u16;
+                        if guide_x >= inner_area.x + inner_area.width {
                             break;
                         }
-                        let char_width = unicode_width::UnicodeWidthChar::width(ch).unwrap_or(1) as u16;
-                        buf.set_string(x, y, &ch.to_string(), span.style);
-                        x += char_width;
+                        if let Some(cell) = buf.cell_mut((guide_x, y)) {
+                            cell.set_symbol("│");
+                            if let Some(fg) = self.indent_guide_style.fg {
+                                cell.set_fg(fg);
+                            }
+                        }
                     }
                 }
 
-                // Render cursor
-                if self.focused && line_idx == state.cursor_row {
-                    let cursor_x = content_x + state.cursor_col as u16;
+                // Placeholder for the hidden lines of a fold
+                if let Some(&(start, end)) = folds.iter().find(|&&(start, _)| start == line_idx) {
+                    if x + 1 < right {
+                        let placeholder = format!(" ⋯ {} lines ", end - start);
+                        let text: String = placeholder.chars().take((right - x - 1) as usize).collect();
+                        buf.set_string(x + 1, y, &text, self.line_number_style.add_modifier(Modifier::REVERSED));
+                        x += 1 + unicode_width::UnicodeWidthStr::width(text.as_str()) as u16;
+                    }
+                }
+
+                // Render annotations after the line content
+                for annotation in self.annotations.iter().filter(|a| a.line == line_idx) {
+                    x += 2;
+                    let right = inner_area.x + inner_area.width;
+                    if x >= right {
+                        break;
+                    }
+                    let max_width = (right - x) as usize;
+                    let text: String = annotation.text.chars().take(max_width).collect();
+                    buf.set_string(x, y, &text, annotation.style);
+                    let width = unicode_width::UnicodeWidthStr::width(text.as_str()) as u16;
+                    if let Some(action) = annotation.action {
+                        state.annotation_hits.push(AnnotationHit { y, x: x..x + width, line: line_idx, action });
+                    }
+                    x += width;
+                }
+
+                // Render cursors
+                let carets = std::iter::once((state.cursor_row, state.cursor_col))
+                    .chain(state.extra_cursors.iter().copied())
+                    .filter(|&(row, _)| self.focused && row == line_idx);
+                for (_, caret_col) in carets {
+                    let line = state.lines.get(line_idx).map(String::as_str).unwrap_or("");
+                    let cursor_x = content_x + state.display_x(line_idx, caret_col) as u16;
                     if cursor_x < inner_area.x + inner_area.width {
-                        let cursor_char = state.lines.get(line_idx)
-                            .and_then(|l| l.chars().nth(state.cursor_col))
-                            .unwrap_or(' ');
-                        buf.set_string(cursor_x, y, &cursor_char.to_string(), self.cursor_style);
+                        let start = byte_index(line, caret_col);
+                        let cursor_text = line[start..].graphemes(true).next().unwrap_or(" ");
+                        buf.set_string(cursor_x, y, cursor_text, self.cursor_style);
                     }
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multi_cursor_edits() {
+        let mut editor = EditorState::new();
+        editor.lines = vec!["let foo = foo + 1;".to_string(), "foo()".to_string()];
+        editor.goto(0, 4);
+        assert!(editor.add_cursor_at_next_match());
+        assert!(editor.add_cursor_at_next_match());
+        assert_eq!(editor.extra_cursors, vec![(0, 10), (1, 0)]);
+
+        // Backspace at the start of the second line joins it onto the first
+        editor.apply(EditorCommand::Backspace);
+        assert_eq!(editor.lines, vec!["letfoo =foo + 1;foo()"]);
+        assert_eq!(editor.extra_cursors, vec![(0, 8), (0, 16)]);
+
+        editor.paste("x\n");
+        assert_eq!(editor.lines, vec!["letx", "foo =x", "foo + 1;x", "foo()"]);
+        assert_eq!((editor.cursor_row, editor.cursor_col), (1, 0));
+        assert_eq!(editor.extra_cursors, vec![(2, 0), (3, 0)]);
+
+        editor.apply(EditorCommand::Down);
+        assert!(editor.extra_cursors.is_empty());
+    }
+
+    #[test]
+    fn test_selection() {
+        let mut editor = EditorState::new();
+        editor.lines = vec!["hello world".to_string(), "second".to_string()];
+        editor.goto(0, 6);
+        assert_eq!(editor.copy().as_deref(), Some("hello world"));
+
+        editor.apply(EditorCommand::SelectLineEnd);
+        editor.apply(EditorCommand::SelectDown);
+        assert_eq!(editor.selection(), Some(((0, 6), (1, 6))));
+        assert_eq!(editor.copy().as_deref(), Some("world\nsecond"));
+
+        // Typing replaces the selection, plain motions drop it
+        editor.input(KeyEvent::new(KeyCode::Char('!'), KeyModifiers::NONE), &EditorKeyMap::new());
+        assert_eq!(editor.lines, vec!["hello !"]);
+        editor.apply(EditorCommand::SelectLeft);
+        editor.apply(EditorCommand::Right);
+        assert_eq!(editor.selection(), None);
+
+        editor.apply(EditorCommand::SelectAll);
+        editor.paste("a\nb");
+        assert_eq!(editor.lines, vec!["a", "b"]);
+        assert_eq!(editor.cut().as_deref(), Some("b"));
+        assert_eq!(editor.lines, vec!["a"]);
+    }
+
+    #[test]
+    fn test_click_to_place_cursor() {
+        let mut editor = EditorState::new();
+        editor.lines = (0..30).map(|i| format!("line {}", i)).collect();
+        editor.scroll_offset = 10;
+        // Two digits plus padding in the gutter
+        let inner = Rect::new(1, 1, 40, 10);
+        assert_eq!(editor.position_at(inner, 6, 3), Some((12, 1)));
+        assert_eq!(editor.position_at(inner, 2, 3), Some((12, 0)));
+        assert_eq!(editor.position_at(inner, 6, 11), None);
+
+        editor.place_cursor(12, 99, false);
+        assert_eq!((editor.cursor_row, editor.cursor_col), (12, 7));
+        editor.place_cursor(13, 2, true);
+        assert_eq!(editor.selection(), Some(((12, 7), (13, 2))));
+        editor.place_cursor(13, 0, false);
+        assert_eq!(editor.selection(), None);
+    }
+
+    #[test]
+    fn test_grapheme_cursor() {
+        let mut editor = EditorState::new();
+        // "e" + combining acute, a family emoji (ZWJ sequence) and two CJK characters
+        editor.lines = vec!["e\u{301}👨\u{200d}👩\u{200d}👧中文!".to_string(), "abcdefghij".to_string()];
+        editor.move_cursor_right();
+        assert_eq!(editor.cursor_col, 2);
+        editor.move_cursor_right();
+        assert_eq!(editor.cursor_col, 7);
+        assert_eq!(display_width(&editor.lines[0], 7), 3);
+        editor.move_cursor_right();
+        editor.move_cursor_down();
+        assert_eq!(editor.cursor_col, 5);
+        editor.move_cursor_up();
+        assert_eq!(editor.cursor_col, 8);
+
+        editor.backspace();
+        editor.backspace();
+        assert_eq!(editor.cursor_col, 2);
+        editor.move_cursor_left();
+        editor.delete();
+        assert_eq!(editor.lines[0], "文!");
+        assert_eq!(editor.cursor_col, 0);
+
+        let inner = Rect::new(0, 0, 40, 10);
+        // Gutter of 3 cells; the right half of 文 is still 文
+        assert_eq!(editor.position_at(inner, 4, 0), Some((0, 0)));
+        assert_eq!(editor.position_at(inner, 5, 0), Some((0, 1)));
+        assert_eq!(editor.position_at(inner, 8, 0), Some((0, 4)));
+    }
+
+    #[test]
+    fn test_inlay_hint_columns() {
+        let mut editor = EditorState::new();
+        editor.lines = vec!["let x = f(1, 2);".to_string()];
+        let hint = |col: usize, label: &str| InlayHint { row: 0, col, label: label.to_string() };
+        editor.set_inlay_hints(vec![hint(5, ": i32"), hint(10, "a: "), hint(13, "b: ")]);
+        let inner = Rect::new(0, 0, 40, 10);
+        let gutter = editor.gutter_width();
+
+        assert_eq!(editor.screen_position(inner, 0, 4), Some((gutter + 4, 0)));
+        assert_eq!(editor.screen_position(inner, 0, 10), Some((gutter + 18, 0)));
+        // Clicks on a hint land before the text it precedes
+        assert_eq!(editor.position_at(inner, gutter + 4, 0), Some((0, 4)));
+        assert_eq!(editor.position_at(inner, gutter + 7, 0), Some((0, 5)));
+        assert_eq!(editor.position_at(inner, gutter + 18, 0), Some((0, 10)));
+        assert_eq!(editor.position_at(inner, gutter + 30, 0), Some((0, 19)));
+
+        // Editing the line drops its hints until new ones come
+        editor.lines[0].push(' ');
+        assert_eq!(editor.screen_position(inner, 0, 10), Some((gutter + 10, 0)));
+    }
+
+    #[test]
+    fn test_background_highlighting() {
+        let mut editor = EditorState::new();
+        editor.set_contents(PathBuf::from("main.rs"), "fn main() {}\nlet x = 1;\n");
+        editor.viewport_height = 10;
+        let request = editor.highlight_requests().unwrap();
+        assert_eq!(request.lines.iter().map(|(row, _)| *row).collect::<Vec<_>>(), vec![0, 1]);
+
+        let highlighted = request.run();
+        assert!(highlighted[0].len() > 1);
+        // A line edited while the batch was out keeps waiting for the next one
+        editor.lines[1].push_str(" // changed");
+        editor.apply_highlights(request, highlighted);
+        let pending = editor.highlight_requests().unwrap();
+        assert_eq!(pending.lines, vec![(1, "let x = 1; // changed".to_string())]);
+        // Until then it is drawn as plain text
+        assert!(editor.highlight_spans(1).is_empty());
+        assert!(editor.highlight_spans(0).len() > 1);
+    }
+
+    #[test]
+    fn test_toggle_comment() {
+        let mut editor = EditorState::new();
+        editor.set_contents(PathBuf::from("main.rs"), "fn main() {\n    let x = 1;\n\n        x\n}\n");
+        editor.goto(1, 8);
+        assert!(editor.toggle_comment(&[1, 2, 3]));
+        assert_eq!(editor.lines[1..4], ["    // let x = 1;", "", "    //     x"]);
+        assert_eq!(editor.cursor_col, 11);
+        assert!(editor.toggle_comment(&[1, 2, 3]));
+        assert_eq!(editor.lines[1..4], ["    let x = 1;", "", "        x"]);
+        assert_eq!(editor.cursor_col, 8);
+
+        editor.set_contents(PathBuf::from("notes.txt"), "plain");
+        assert!(!editor.toggle_comment(&[0]));
+    }
+
+    #[test]
+    fn test_auto_close_brackets() {
+        let keys = EditorKeyMap::new();
+        let mut editor = EditorState::new();
+        for c in "f(a[0], \"x".chars() {
+            editor.input(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE), &keys);
+        }
+        assert_eq!(editor.lines, vec!["f(a[0], \"x\")"]);
+        // Closing characters that are already there are stepped over
+        for c in "\")".chars() {
+            editor.input(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE), &keys);
+        }
+        assert_eq!(editor.lines, vec!["f(a[0], \"x\")"]);
+        assert_eq!(editor.cursor_col, 12);
+        // No pairing in front of a word or for apostrophes
+        editor.lines = vec!["word".to_string(), "don".to_string()];
+        editor.goto(0, 0);
+        editor.type_char('(');
+        editor.goto(1, 3);
+        editor.type_char('\'');
+        assert_eq!(editor.lines, vec!["(word", "don'"]);
+    }
+
+    #[test]
+    fn test_matching_bracket() {
+        let mut editor = EditorState::new();
+        editor.lines = vec!["fn main() {".to_string(), "    let v = [(1), 2];".to_string(), "}".to_string()];
+        editor.goto(0, 10);
+        assert_eq!(editor.matching_bracket(), Some(((0, 10), (2, 0))));
+        editor.goto(1, 20);
+        assert_eq!(editor.matching_bracket(), Some(((1, 19), (1, 12))));
+        editor.goto(1, 5);
+        assert_eq!(editor.matching_bracket(), None);
+        editor.lines[2].clear();
+        editor.goto(0, 10);
+        assert_eq!(editor.matching_bracket(), None);
+    }
+
+    #[test]
+    fn test_jump_to_matching_bracket() {
+        syntax::assets_blocking();
+        let mut editor = EditorState::new();
+        editor.set_contents(PathBuf::from("main.rs"), "fn f() {\n    g(\")\"); // }\n}\n");
+        editor.goto(0, 7);
+        assert!(editor.jump_to_matching_bracket());
+        assert_eq!((editor.cursor_row, editor.cursor_col), (2, 0));
+        assert!(editor.jump_to_matching_bracket());
+        assert_eq!((editor.cursor_row, editor.cursor_col), (0, 7));
+        editor.goto(1, 7);
+        assert!(!editor.jump_to_matching_bracket());
+        editor.goto(1, 5);
+        assert!(editor.jump_to_matching_bracket());
+        assert_eq!(editor.cursor_col, 9);
+    }
+
+    #[test]
+    fn test_preview_buffer() {
+        let path = std::env::temp_dir().join(format!("nterm-editor-preview-{}.log", std::process::id()));
+        let count = (preview::CHUNK_BYTES as usize / 10) * 2;
+        let text: String = (0..count).map(|i| format!("{:>9}\n", i)).collect();
+        fs::write(&path, &text).unwrap();
+
+        let (content, _, first) = preview::load(&path, 1024).unwrap();
+        let mut editor = EditorState::new();
+        editor.set_contents(path.clone(), &content);
+        editor.set_preview(first);
+        assert!(editor.read_only && editor.lines.len() < count);
+        assert!(editor.encoded_contents().is_err());
+        while editor.load_more().unwrap() {}
+        assert_eq!((editor.lines.len(), editor.preview), (count, None));
+        assert_eq!(editor.lines[count - 1].trim(), (count - 1).to_string());
+        assert_eq!(editor.encoded_contents().unwrap(), text.into_bytes());
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_move_and_duplicate_lines() {
+        let mut editor = EditorState::new();
+        editor.lines = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        editor.goto(0, 1);
+        editor.apply(EditorCommand::MoveLineDown);
+        assert_eq!(editor.lines, vec!["b", "a", "c"]);
+        assert_eq!((editor.cursor_row, editor.cursor_col), (1, 1));
+        editor.apply(EditorCommand::DuplicateLine);
+        assert_eq!(editor.lines, vec!["b", "a", "a", "c"]);
+        assert_eq!(editor.cursor_row, 2);
+        assert!(editor.move_lines(2, 3, false));
+        assert_eq!(editor.lines, vec!["b", "a", "c", "a"]);
+        assert!(!editor.move_lines(0, 1, false));
+        assert!(!editor.move_lines(2, 3, true));
+    }
+
+    #[test]
+    fn test_replace_text() {
+        let mut editor = EditorState::new();
+        editor.lines = vec!["fn main() {".to_string(), "    s.le".to_string(), "}".to_string()];
+        assert_eq!(editor.replace_text((1, 6), (1, 8), "len()"), (1, 11));
+        assert_eq!(editor.lines[1], "    s.len()");
+        assert_eq!(editor.replace_text((0, 0), (0, 0), "use a;\r\nuse b;\n"), (2, 0));
+        assert_eq!(editor.lines, vec!["use a;", "use b;", "fn main() {", "    s.len()", "}"]);
+        assert_eq!(editor.replace_text((2, 9), (4, 0), ""), (2, 9));
+        assert_eq!(editor.lines, vec!["use a;", "use b;", "fn main()}"]);
+    }
+
+    #[test]
+    fn test_shift_indent() {
+        let mut editor = EditorState::new();
+        editor.lines = vec!["a".to_string(), "".to_string(), "  b".to_string()];
+        editor.goto(2, 3);
+        editor.shift_indent(&[0, 1, 2], true);
+        assert_eq!(editor.lines, vec!["    a", "", "      b"]);
+        assert_eq!(editor.cursor_col, 7);
+        editor.shift_indent(&[0, 2], false);
+        editor.shift_indent(&[0, 2], false);
+        assert_eq!(editor.lines, vec!["a", "", "b"]);
+        assert_eq!(editor.cursor_col, 1);
+
+        // Tab at every caret
+        editor.indent = IndentConfig { width: 4, tabs: true };
+        editor.goto(0, 1);
+        editor.extra_cursors = vec![(2, 0)];
+        editor.apply(EditorCommand::Indent);
+        assert_eq!(editor.lines, vec!["\ta", "", "\tb"]);
+        assert_eq!(editor.extra_cursors, vec![(2, 1)]);
+        editor.apply(EditorCommand::Dedent);
+        assert_eq!(editor.lines, vec!["a", "", "b"]);
+    }
+
+    #[test]
+    fn test_folding() {
+        let mut editor = EditorState::new();
+        editor.lines = ["fn main() {", "    let a = 1;", "    if a {", "        go();", "    }", "}", "def f():", "    pass", "", "x = 1"]
+            .iter().map(|s| s.to_string()).collect();
+        assert_eq!(editor.fold_end(0), Some(5));
+        assert_eq!(editor.fold_end(6), Some(7));
+        assert_eq!(editor.fold_end(1), None);
+
+        // Folding from inside a block folds the innermost region around the cursor
+        editor.goto(3, 4);
+        assert!(editor.fold());
+        assert_eq!(editor.cursor_row, 2);
+        editor.move_cursor_down();
+        assert_eq!(editor.cursor_row, 5);
+        editor.move_cursor_up();
+        assert_eq!(editor.cursor_row, 2);
+        assert_eq!(editor.visible_rows(0).collect::<Vec<_>>(), vec![0, 1, 2, 5, 6, 7, 8, 9]);
+
+        // Folds follow their text when lines are inserted above
+        editor.insert_lines(0, vec!["// header".to_string()]);
+        assert_eq!(editor.folded_ranges(), vec![(3, 5)]);
+
+        // A search match inside the fold opens it
+        editor.cursor_row = 4;
+        editor.ensure_cursor_visible(20);
+        assert!(editor.folded_ranges().is_empty());
+    }
+
+    #[test]
+    fn test_line_endings_preserved() {
+        let mut editor = EditorState::new();
+        editor.set_contents(PathBuf::from("win.txt"), "one\r\ntwo\r\n");
+        assert_eq!(editor.lines, vec!["one", "two"]);
+        assert_eq!(editor.contents(), "one\r\ntwo\r\n");
+        editor.toggle_line_ending();
+        assert!(editor.modified);
+        assert_eq!(editor.contents(), "one\ntwo\n");
+    }
+
+    #[test]
+    fn test_read_only() {
+        let path = std::env::temp_dir().join(format!("nterm-readonly-{}.txt", std::process::id()));
+        fs::write(&path, "locked\n").unwrap();
+        let mut perms = fs::metadata(&path).unwrap().permissions();
+        perms.set_readonly(true);
+        fs::set_permissions(&path, perms.clone()).unwrap();
+
+        let mut editor = EditorState::new();
+        editor.load_file(path.clone()).unwrap();
+        assert!(editor.read_only);
+        let keys = crate::tui::keymap::editor_key_map(crate::shared::KeybindingMode::Default);
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        assert!(EditorState::is_edit_key(key(KeyCode::Char('x')), &keys));
+        assert!(EditorState::is_edit_key(key(KeyCode::Backspace), &keys));
+        assert!(!EditorState::is_edit_key(key(KeyCode::Down), &keys));
+
+        #[allow(clippy::permissions_set_readonly_false)]
+        perms.set_readonly(false);
+        fs::set_permissions(&path, perms).unwrap();
+        editor.load_file(path.clone()).unwrap();
+        assert!(!editor.read_only);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_changed_on_disk() {
+        let path = std::env::temp_dir().join(format!("nterm-disk-{}.txt", std::process::id()));
+        fs::write(&path, "one\n").unwrap();
+        let mut editor = EditorState::new();
+        editor.load_file(path.clone()).unwrap();
+        assert!(!editor.changed_on_disk());
+
+        fs::write(&path, "one\ntwo\n").unwrap();
+        assert!(editor.changed_on_disk());
+        editor.save().unwrap();
+        assert!(!editor.changed_on_disk());
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_goto_while_loading() {
+        let mut editor = EditorState::loading(PathBuf::from("big.txt"));
+        editor.goto(2, 3);
+        assert_eq!((editor.cursor_row, editor.cursor_col), (0, 0));
+        editor.set_contents(PathBuf::from("big.txt"), "a\nb\nline three\n");
+        assert!(!editor.loading);
+        assert_eq!(editor.lines.len(), 3);
+        assert_eq!((editor.cursor_row, editor.cursor_col), (2, 3));
+    }
+}
@@ -0,0 +1,277 @@
+// In-editor find/replace bar
+
+use ratatui::widgets::{Block, Borders};
+use regex::{Regex, RegexBuilder};
+use tui_textarea::TextArea;
+
+/// A match inside the buffer: row plus a char range within that line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Match {
+    pub row: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FindField {
+    Query,
+    Replace,
+}
+
+/// State of the find bar shown at the bottom of the editor
+pub struct FindBar<'a> {
+    pub active: bool,
+    pub replace_mode: bool,
+    pub focus: FindField,
+    pub query: TextArea<'a>,
+    pub replacement: TextArea<'a>,
+    pub matches: Vec<Match>,
+    pub current: Option<usize>,
+    /// Treat the query as a regular expression
+    pub regex_mode: bool,
+    /// Compiled query in regex mode
+    regex: Option<Regex>,
+    /// Why the regex query is invalid, shown in the bar
+    pub error: Option<String>,
+}
+
+impl Default for FindBar<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> FindBar<'a> {
+    pub fn new() -> Self {
+        Self {
+            active: false,
+            replace_mode: false,
+            focus: FindField::Query,
+            query: Self::input(" Find "),
+            replacement: Self::input(" Replace "),
+            matches: Vec::new(),
+            current: None,
+            regex_mode: false,
+            regex: None,
+            error: None,
+        }
+    }
+
+    fn input(title: &'static str) -> TextArea<'a> {
+        let mut input = TextArea::default();
+        input.set_block(Block::default().borders(Borders::ALL).title(title));
+        input
+    }
+
+    /// Show the bar, optionally pre-filling the query (e.g. with the word under the cursor)
+    pub fn open(&mut self, replace_mode: bool, initial: Option<&str>) {
+        self.active = true;
+        self.replace_mode = replace_mode;
+        self.focus = FindField::Query;
+        if let Some(text) = initial.filter(|t| !t.is_empty()) {
+            self.query = Self::input(" Find ");
+            self.query.insert_str(text);
+        }
+    }
+
+    pub fn close(&mut self) {
+        self.active = false;
+        self.matches.clear();
+        self.current = None;
+    }
+
+    pub fn query_text(&self) -> String {
+        self.query.lines().join("")
+    }
+
+    pub fn replacement_text(&self) -> String {
+        self.replacement.lines().join("")
+    }
+
+    /// Switch between the query and replacement fields (enables replace mode)
+    pub fn toggle_focus(&mut self) {
+        self.replace_mode = true;
+        self.focus = match self.focus {
+            FindField::Query => FindField::Replace,
+            FindField::Replace => FindField::Query,
+        };
+    }
+
+    pub fn toggle_regex(&mut self) {
+        self.regex_mode = !self.regex_mode;
+    }
+
+    /// Recompute matches and select the first one at or after the cursor
+    pub fn update(&mut self, lines: &[String], cursor: (usize, usize)) {
+        let query = self.query_text();
+        self.error = None;
+        self.regex = None;
+        self.matches = if self.regex_mode && !query.is_empty() {
+            match compile(&query) {
+                Ok(re) => {
+                    let matches = find_all_regex(lines, &re);
+                    self.regex = Some(re);
+                    matches
+                }
+                Err(e) => {
+                    self.error = Some(e);
+                    Vec::new()
+                }
+            }
+        } else {
+            find_all(lines, &query)
+        };
+        self.current = self.matches.iter()
+            .position(|m| (m.row, m.start) >= cursor)
+            .or(if self.matches.is_empty() { None } else { Some(0) });
+    }
+
+    /// Text that replaces `m`: the literal replacement, or with `$1`/`${name}` expanded in regex mode
+    pub fn expand_replacement(&self, lines: &[String], m: Match) -> String {
+        let replacement = self.replacement_text();
+        let (Some(re), Some(line)) = (&self.regex, lines.get(m.row)) else {
+            return replacement;
+        };
+        let start_byte = char_to_byte(line, m.start);
+        match re.captures_at(line, start_byte) {
+            Some(caps) => {
+                let mut out = String::new();
+                caps.expand(&replacement, &mut out);
+                out
+            }
+            None => replacement,
+        }
+    }
+
+    pub fn current_match(&self) -> Option<Match> {
+        self.current.and_then(|i| self.matches.get(i).copied())
+    }
+
+    /// Matches on rows `first..first + count`, e.g. the ones in the viewport
+    pub fn matches_in(&self, first: usize, count: usize) -> &[Match] {
+        let start = self.matches.partition_point(|m| m.row < first);
+        let end = self.matches.partition_point(|m| m.row < first.saturating_add(count));
+        &self.matches[start..end]
+    }
+
+    pub fn next_match(&mut self) -> Option<Match> {
+        if self.matches.is_empty() {
+            return None;
+        }
+        self.current = Some(self.current.map(|i| (i + 1) % self.matches.len()).unwrap_or(0));
+        self.current_match()
+    }
+
+    pub fn prev_match(&mut self) -> Option<Match> {
+        if self.matches.is_empty() {
+            return None;
+        }
+        let len = self.matches.len();
+        self.current = Some(self.current.map(|i| (i + len - 1) % len).unwrap_or(0));
+        self.current_match()
+    }
+}
+
+/// All non-overlapping occurrences of `query`, case-insensitive unless the query has uppercase
+pub fn find_all(lines: &[String], query: &str) -> Vec<Match> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let case_sensitive = query.chars().any(|c| c.is_uppercase());
+    let fold = |c: char| if case_sensitive { c } else { c.to_lowercase().next().unwrap_or(c) };
+    let needle: Vec<char> = query.chars().map(fold).collect();
+
+    let mut matches = Vec::new();
+    for (row, line) in lines.iter().enumerate() {
+        // Folding per char keeps indices aligned with the original line
+        let hay: Vec<char> = line.chars().map(fold).collect();
+        let mut col = 0;
+        while col + needle.len() <= hay.len() {
+            if hay[col..col + needle.len()] == needle[..] {
+                matches.push(Match { row, start: col, end: col + needle.len() });
+                col += needle.len();
+            } else {
+                col += 1;
+            }
+        }
+    }
+    matches
+}
+
+/// Compile a regex query with the same smart-case rule as plain search
+fn compile(pattern: &str) -> Result<Regex, String> {
+    let case_insensitive = !pattern.chars().any(|c| c.is_uppercase());
+    RegexBuilder::new(pattern)
+        .case_insensitive(case_insensitive)
+        .build()
+        .map_err(|e| {
+            // The regex crate reports a multi-line message; the last line is the reason
+            let msg = e.to_string();
+            msg.lines().last().unwrap_or("invalid pattern").trim().trim_start_matches("error: ").to_string()
+        })
+}
+
+/// All non-empty matches of `re`, converted to char ranges
+pub fn find_all_regex(lines: &[String], re: &Regex) -> Vec<Match> {
+    let mut matches = Vec::new();
+    for (row, line) in lines.iter().enumerate() {
+        for m in re.find_iter(line).filter(|m| !m.is_empty()) {
+            matches.push(Match {
+                row,
+                start: line[..m.start()].chars().count(),
+                end: line[..m.end()].chars().count(),
+            });
+        }
+    }
+    matches
+}
+
+fn char_to_byte(line: &str, col: usize) -> usize {
+    line.char_indices().nth(col).map(|(i, _)| i).unwrap_or(line.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_regex_replacement() {
+        let lines = vec!["let x = foo(1);".to_string(), "bar(22)".to_string()];
+        let mut bar = FindBar::new();
+        bar.regex_mode = true;
+        bar.query.insert_str(r"(\w+)\((\d+)\)");
+        bar.replacement.insert_str("$2.$1()");
+        bar.update(&lines, (0, 0));
+        assert_eq!(bar.matches.len(), 2);
+        assert_eq!(bar.expand_replacement(&lines, bar.matches[0]), "1.foo()");
+        assert_eq!(bar.expand_replacement(&lines, bar.matches[1]), "22.bar()");
+
+        bar.query = FindBar::input(" Find ");
+        bar.query.insert_str("(unclosed");
+        bar.update(&lines, (0, 0));
+        assert!(bar.matches.is_empty());
+        assert!(bar.error.is_some());
+    }
+
+    #[test]
+    fn test_find_all() {
+        let lines = vec!["Foo foo".to_string(), "barfoo".to_string()];
+        assert_eq!(find_all(&lines, "foo").len(), 3);
+        assert_eq!(find_all(&lines, "Foo"), vec![Match { row: 0, start: 0, end: 3 }]);
+        assert_eq!(find_all(&lines, "foo")[2], Match { row: 1, start: 3, end: 6 });
+        assert!(find_all(&lines, "").is_empty());
+    }
+
+    #[test]
+    fn test_matches_in() {
+        let lines: Vec<String> = (0..10).map(|i| format!("x{} x", i)).collect();
+        let mut bar = FindBar::new();
+        bar.query.insert_str("x");
+        bar.update(&lines, (4, 1));
+        assert_eq!((bar.matches.len(), bar.current), (20, Some(9)));
+        let visible = bar.matches_in(3, 2);
+        assert_eq!(visible.len(), 4);
+        assert_eq!((visible[0].row, visible[3].row), (3, 4));
+        assert!(bar.matches_in(10, 5).is_empty());
+    }
+}
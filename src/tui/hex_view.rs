@@ -0,0 +1,121 @@
+// Hex dump of a binary file shown in the editor area instead of its text:
+// offset, sixteen bytes and their printable characters per row
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+
+/// Bytes shown per row
+pub const BYTES_PER_ROW: u64 = 16;
+
+/// Bytes read from the file at a time; only the part being looked at is kept
+const WINDOW_BYTES: u64 = 64 * 1024;
+
+pub struct HexView {
+    pub path: PathBuf,
+    pub size: u64,
+    /// First visible row
+    pub scroll: u64,
+    window_start: u64,
+    window: Vec<u8>,
+}
+
+impl HexView {
+    pub fn open(path: PathBuf) -> io::Result<Self> {
+        let size = path.metadata()?.len();
+        let mut view = Self { path, size, scroll: 0, window_start: 0, window: Vec::new() };
+        view.read_window(0)?;
+        Ok(view)
+    }
+
+    pub fn row_count(&self) -> u64 {
+        self.size.div_ceil(BYTES_PER_ROW).max(1)
+    }
+
+    pub fn scroll_by(&mut self, delta: i64) {
+        self.scroll_to(self.scroll.saturating_add_signed(delta));
+    }
+
+    pub fn scroll_to(&mut self, row: u64) {
+        self.scroll = row.min(self.row_count() - 1);
+    }
+
+    /// Up to `count` formatted rows from the top of the view, reading the file
+    /// when they aren't loaded yet
+    pub fn visible_rows(&mut self, count: usize) -> io::Result<Vec<String>> {
+        let start = self.scroll * BYTES_PER_ROW;
+        let end = (start + count as u64 * BYTES_PER_ROW).min(self.size);
+        if start < self.window_start || end > self.window_start + self.window.len() as u64 {
+            self.read_window(start)?;
+        }
+        let mut rows = Vec::with_capacity(count);
+        let mut offset = start;
+        while offset < end {
+            let from = (offset - self.window_start) as usize;
+            let to = (from + BYTES_PER_ROW as usize).min(self.window.len());
+            rows.push(format_row(offset, &self.window[from..to]));
+            offset += BYTES_PER_ROW;
+        }
+        Ok(rows)
+    }
+
+    fn read_window(&mut self, start: u64) -> io::Result<()> {
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(start))?;
+        self.window.clear();
+        file.take(WINDOW_BYTES).read_to_end(&mut self.window)?;
+        self.window_start = start;
+        Ok(())
+    }
+}
+
+/// One row in the style of `hexdump -C`, padded when `bytes` is a short last row
+pub fn format_row(offset: u64, bytes: &[u8]) -> String {
+    let mut row = format!("{:08x}  ", offset);
+    for i in 0..BYTES_PER_ROW as usize {
+        match bytes.get(i) {
+            Some(b) => row.push_str(&format!("{:02x} ", b)),
+            None => row.push_str("   "),
+        }
+        if i == 7 {
+            row.push(' ');
+        }
+    }
+    let text: String = bytes.iter()
+        .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+        .collect();
+    row.push_str(&format!(" |{}|", text));
+    row
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_row() {
+        assert_eq!(
+            format_row(16, b"Hello, world!\n\x00\xff"),
+            "00000010  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0a 00 ff  |Hello, world!...|"
+        );
+        assert_eq!(format_row(0, b"ab"), format!("00000000  61 62 {} |ab|", " ".repeat(43)));
+    }
+
+    #[test]
+    fn test_scrolling() {
+        let path = std::env::temp_dir().join(format!("nterm-hex-{}.bin", std::process::id()));
+        let bytes: Vec<u8> = (0..WINDOW_BYTES * 2 + 5).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &bytes).unwrap();
+        let mut view = HexView::open(path.clone()).unwrap();
+        assert_eq!(view.row_count(), (WINDOW_BYTES * 2) / BYTES_PER_ROW + 1);
+
+        view.scroll_to(u64::MAX);
+        let rows = view.visible_rows(10).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert!(rows[0].starts_with(&format!("{:08x}", WINDOW_BYTES * 2)));
+        view.scroll_by(-(WINDOW_BYTES as i64 / BYTES_PER_ROW as i64));
+        let expected = &bytes[WINDOW_BYTES as usize..WINDOW_BYTES as usize + 16];
+        assert_eq!(view.visible_rows(3).unwrap()[0], format_row(WINDOW_BYTES, expected));
+        let _ = std::fs::remove_file(&path);
+    }
+}
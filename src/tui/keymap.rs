@@ -0,0 +1,347 @@
+// Key bindings: the default key map and user overrides from Config. A binding
+// is one key or a chord of keys pressed in turn, e.g. `ctrl+k ctrl+s`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use ratatui::crossterm::event::{KeyCode, KeyModifiers};
+
+use super::action::Action;
+use super::editor::EditorCommand;
+use crate::shared::KeybindingMode;
+
+/// A key with the modifiers held
+pub type Key = (KeyCode, KeyModifiers);
+
+/// How long the next key of a chord is waited for
+pub const CHORD_TIMEOUT: Duration = Duration::from_millis(1500);
+
+/// What the keys pressed so far amount to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChordMatch {
+    Action(Action),
+    /// The start of a longer chord
+    Prefix,
+    None,
+}
+
+/// Global bindings from single keys and chords to actions
+#[derive(Debug, Clone, Default)]
+pub struct KeyMap {
+    bindings: HashMap<Vec<Key>, Action>,
+}
+
+impl KeyMap {
+    pub fn insert(&mut self, key: Key, action: Action) {
+        self.insert_chord(vec![key], action);
+    }
+
+    /// Bind keys pressed in turn; a chord replaces bindings it conflicts with,
+    /// as a key can't both run an action and start a chord
+    pub fn insert_chord(&mut self, keys: Vec<Key>, action: Action) {
+        self.bindings.retain(|bound, _| !(bound.starts_with(&keys) || keys.starts_with(bound)));
+        self.bindings.insert(keys, action);
+    }
+
+    pub fn remove(&mut self, keys: &[Key]) {
+        self.bindings.remove(keys);
+    }
+
+    /// The action bound to a single key
+    pub fn get(&self, key: &Key) -> Option<&Action> {
+        self.bindings.get(std::slice::from_ref(key))
+    }
+
+    /// Whether a key is bound on its own or starts a chord
+    pub fn contains_key(&self, key: &Key) -> bool {
+        self.lookup(std::slice::from_ref(key)) != ChordMatch::None
+    }
+
+    pub fn lookup(&self, keys: &[Key]) -> ChordMatch {
+        if let Some(&action) = self.bindings.get(keys) {
+            ChordMatch::Action(action)
+        } else if self.bindings.keys().any(|bound| bound.len() > keys.len() && bound.starts_with(keys)) {
+            ChordMatch::Prefix
+        } else {
+            ChordMatch::None
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&[Key], Action)> {
+        self.bindings.iter().map(|(keys, &action)| (keys.as_slice(), action))
+    }
+}
+
+/// Keys handled by the editor buffer itself; unmapped printable keys are inserted
+pub type EditorKeyMap = HashMap<(KeyCode, KeyModifiers), EditorCommand>;
+
+/// Built-in global key bindings
+pub fn default_key_map() -> KeyMap {
+    let mut key_map = KeyMap::default();
+    key_map.insert((KeyCode::Char('q'), KeyModifiers::CONTROL), Action::Quit);
+    key_map.insert((KeyCode::Tab, KeyModifiers::NONE), Action::SwitchFocus);
+    // Tab indents while the editor has focus; F12 switches focus from anywhere
+    key_map.insert((KeyCode::F(12), KeyModifiers::NONE), Action::SwitchFocus);
+    key_map.insert((KeyCode::Esc, KeyModifiers::NONE), Action::ToggleMenu);
+    key_map.insert((KeyCode::F(1), KeyModifiers::NONE), Action::ToggleMenu);
+    key_map.insert((KeyCode::Char('r'), KeyModifiers::CONTROL), Action::ResetLayout);
+    key_map.insert((KeyCode::Char('h'), KeyModifiers::CONTROL), Action::DumpHistory);
+    key_map.insert((KeyCode::Char('p'), KeyModifiers::CONTROL), Action::FileSearch);
+    key_map.insert((KeyCode::Char('p'), KeyModifiers::ALT), Action::SearchEverything);
+    key_map.insert((KeyCode::Char('g'), KeyModifiers::ALT), Action::SearchBuffers);
+    // The Emacs preset keeps Ctrl+E (end of line) while editing
+    key_map.insert((KeyCode::Char('e'), KeyModifiers::CONTROL), Action::RecentFiles);
+    key_map.insert((KeyCode::Char('m'), KeyModifiers::CONTROL), Action::CycleModel);
+    key_map.insert((KeyCode::Char('m'), KeyModifiers::ALT), Action::SwitchPreset);
+    key_map.insert((KeyCode::Char('s'), KeyModifiers::CONTROL), Action::Save);
+    key_map.insert((KeyCode::Char('s'), KeyModifiers::CONTROL | KeyModifiers::ALT), Action::SaveAll);
+    key_map.insert((KeyCode::F(2), KeyModifiers::NONE), Action::OpenSettings);
+    key_map.insert((KeyCode::Char('c'), KeyModifiers::CONTROL), Action::Copy);
+    key_map.insert((KeyCode::Char('x'), KeyModifiers::CONTROL), Action::Cut);
+    key_map.insert((KeyCode::Char('v'), KeyModifiers::CONTROL), Action::Paste);
+    key_map.insert((KeyCode::F(8), KeyModifiers::NONE), Action::ToggleProblems);
+    key_map.insert((KeyCode::F(7), KeyModifiers::NONE), Action::ToggleTodos);
+    key_map.insert((KeyCode::F(4), KeyModifiers::NONE), Action::SpellSuggest);
+    key_map.insert((KeyCode::Char(' '), KeyModifiers::CONTROL), Action::Complete);
+    key_map.insert((KeyCode::Char('d'), KeyModifiers::ALT), Action::GotoDefinition);
+    key_map.insert((KeyCode::Char('r'), KeyModifiers::ALT), Action::FindReferences);
+    key_map.insert((KeyCode::F(6), KeyModifiers::NONE), Action::Refactor);
+    key_map.insert((KeyCode::F(6), KeyModifiers::SHIFT), Action::RenameSymbol);
+    key_map.insert((KeyCode::Char('o'), KeyModifiers::ALT), Action::Outline);
+    key_map.insert((KeyCode::Char('l'), KeyModifiers::ALT), Action::FormatDocument);
+    key_map.insert((KeyCode::Char('i'), KeyModifiers::ALT), Action::ToggleInlayHints);
+    key_map.insert((KeyCode::F(9), KeyModifiers::NONE), Action::ToggleWatch);
+    key_map.insert((KeyCode::F(10), KeyModifiers::NONE), Action::SwitchTerminalTab);
+    key_map.insert((KeyCode::F(5), KeyModifiers::NONE), Action::ProcessTree);
+    key_map.insert((KeyCode::F(11), KeyModifiers::NONE), Action::ToggleZen);
+    key_map.insert((KeyCode::Char('f'), KeyModifiers::CONTROL), Action::Find);
+    // Most terminals send Ctrl+/ as Ctrl+7 (0x1f)
+    key_map.insert((KeyCode::Char('/'), KeyModifiers::CONTROL), Action::ToggleComment);
+    key_map.insert((KeyCode::Char('7'), KeyModifiers::CONTROL), Action::ToggleComment);
+    key_map.insert((KeyCode::F(3), KeyModifiers::NONE), Action::ToggleFold);
+    key_map.insert((KeyCode::Char('n'), KeyModifiers::CONTROL), Action::NewScratch);
+    key_map.insert((KeyCode::Char('w'), KeyModifiers::CONTROL), Action::CloseBuffer);
+    key_map.insert((KeyCode::PageDown, KeyModifiers::CONTROL), Action::NextBuffer);
+    key_map.insert((KeyCode::PageUp, KeyModifiers::CONTROL), Action::PrevBuffer);
+    key_map.insert((KeyCode::Char(']'), KeyModifiers::ALT), Action::NextChange);
+    key_map.insert((KeyCode::Char('['), KeyModifiers::ALT), Action::PrevChange);
+    key_map.insert((KeyCode::Char('.'), KeyModifiers::ALT), Action::NextDiagnostic);
+    key_map.insert((KeyCode::Char(','), KeyModifiers::ALT), Action::PrevDiagnostic);
+    key_map.insert((KeyCode::Char('k'), KeyModifiers::ALT), Action::ShowDiagnostic);
+    key_map.insert((KeyCode::Left, KeyModifiers::ALT), Action::NavigateBack);
+    key_map.insert((KeyCode::Right, KeyModifiers::ALT), Action::NavigateForward);
+    key_map
+}
+
+/// Editor keys for a keybinding preset (vim's insert mode uses the default preset)
+pub fn editor_key_map(mode: KeybindingMode) -> EditorKeyMap {
+    let mut keys = HashMap::new();
+    keys.insert((KeyCode::Backspace, KeyModifiers::NONE), EditorCommand::Backspace);
+    keys.insert((KeyCode::Delete, KeyModifiers::NONE), EditorCommand::Delete);
+    keys.insert((KeyCode::Enter, KeyModifiers::NONE), EditorCommand::Newline);
+    keys.insert((KeyCode::Up, KeyModifiers::NONE), EditorCommand::Up);
+    keys.insert((KeyCode::Down, KeyModifiers::NONE), EditorCommand::Down);
+    keys.insert((KeyCode::Left, KeyModifiers::NONE), EditorCommand::Left);
+    keys.insert((KeyCode::Right, KeyModifiers::NONE), EditorCommand::Right);
+    keys.insert((KeyCode::Home, KeyModifiers::NONE), EditorCommand::LineStart);
+    keys.insert((KeyCode::End, KeyModifiers::NONE), EditorCommand::LineEnd);
+    keys.insert((KeyCode::PageUp, KeyModifiers::NONE), EditorCommand::PageUp);
+    keys.insert((KeyCode::PageDown, KeyModifiers::NONE), EditorCommand::PageDown);
+    keys.insert((KeyCode::Tab, KeyModifiers::NONE), EditorCommand::Indent);
+    keys.insert((KeyCode::BackTab, KeyModifiers::SHIFT), EditorCommand::Dedent);
+    keys.insert((KeyCode::BackTab, KeyModifiers::NONE), EditorCommand::Dedent);
+    keys.insert((KeyCode::Char('d'), KeyModifiers::CONTROL), EditorCommand::AddCursorAtNextMatch);
+    keys.insert((KeyCode::Up, KeyModifiers::ALT), EditorCommand::MoveLineUp);
+    keys.insert((KeyCode::Down, KeyModifiers::ALT), EditorCommand::MoveLineDown);
+    keys.insert((KeyCode::Down, KeyModifiers::SHIFT | KeyModifiers::ALT), EditorCommand::DuplicateLine);
+    // Terminals send Ctrl+] as the same byte as Ctrl+5
+    keys.insert((KeyCode::Char(']'), KeyModifiers::CONTROL), EditorCommand::MatchBracket);
+    keys.insert((KeyCode::Char('5'), KeyModifiers::CONTROL), EditorCommand::MatchBracket);
+    keys.insert((KeyCode::Left, KeyModifiers::SHIFT), EditorCommand::SelectLeft);
+    keys.insert((KeyCode::Right, KeyModifiers::SHIFT), EditorCommand::SelectRight);
+    keys.insert((KeyCode::Up, KeyModifiers::SHIFT), EditorCommand::SelectUp);
+    keys.insert((KeyCode::Down, KeyModifiers::SHIFT), EditorCommand::SelectDown);
+    keys.insert((KeyCode::Home, KeyModifiers::SHIFT), EditorCommand::SelectLineStart);
+    keys.insert((KeyCode::End, KeyModifiers::SHIFT), EditorCommand::SelectLineEnd);
+    keys.insert((KeyCode::Char('a'), KeyModifiers::CONTROL), EditorCommand::SelectAll);
+
+    if mode == KeybindingMode::Emacs {
+        // Ctrl+F/B/N/P stay with their global actions; Ctrl+D deletes instead of adding a caret
+        keys.insert((KeyCode::Char('a'), KeyModifiers::CONTROL), EditorCommand::LineStart);
+        keys.insert((KeyCode::Char('e'), KeyModifiers::CONTROL), EditorCommand::LineEnd);
+        keys.insert((KeyCode::Char('k'), KeyModifiers::CONTROL), EditorCommand::KillLine);
+        keys.insert((KeyCode::Char('d'), KeyModifiers::CONTROL), EditorCommand::Delete);
+        keys.insert((KeyCode::Char('f'), KeyModifiers::ALT), EditorCommand::WordForward);
+        keys.insert((KeyCode::Char('b'), KeyModifiers::ALT), EditorCommand::WordBackward);
+    }
+    keys
+}
+
+/// The default key map with the config's overrides applied.
+/// Returns the offending config keys alongside an error message for invalid entries.
+pub fn build_key_map(overrides: &BTreeMap<String, String>) -> (KeyMap, Vec<(String, String)>) {
+    let mut key_map = default_key_map();
+    let mut errors = Vec::new();
+
+    for (spec, action_name) in overrides {
+        let keys = match parse_keys(spec) {
+            Ok(keys) => keys,
+            Err(e) => {
+                errors.push((spec.clone(), e));
+                continue;
+            }
+        };
+        // "none" unbinds a default
+        if action_name.eq_ignore_ascii_case("none") {
+            key_map.remove(&keys);
+            continue;
+        }
+        match Action::from_name(action_name) {
+            Some(action) => {
+                key_map.insert_chord(keys, action);
+            }
+            None => errors.push((spec.clone(), format!("unknown action '{}'", action_name))),
+        }
+    }
+
+    (key_map, errors)
+}
+
+/// Parse a binding of one or more space-separated keys, e.g. `ctrl+k ctrl+s`
+pub fn parse_keys(spec: &str) -> Result<Vec<Key>, String> {
+    let keys = spec.split_whitespace().map(parse_key).collect::<Result<Vec<_>, _>>()?;
+    if keys.is_empty() {
+        return Err("empty key".to_string());
+    }
+    Ok(keys)
+}
+
+/// Parse a key spec such as `ctrl+s`, `alt+shift+up` or `f5`
+pub fn parse_key(spec: &str) -> Result<Key, String> {
+    let mut modifiers = KeyModifiers::NONE;
+    let parts: Vec<&str> = spec.split('+').map(str::trim).collect();
+    let (key, mods) = parts.split_last().ok_or_else(|| "empty key".to_string())?;
+
+    for m in mods {
+        match m.to_lowercase().as_str() {
+            "ctrl" | "control" => modifiers |= KeyModifiers::CONTROL,
+            "alt" | "meta" => modifiers |= KeyModifiers::ALT,
+            "shift" => modifiers |= KeyModifiers::SHIFT,
+            other => return Err(format!("unknown modifier '{}'", other)),
+        }
+    }
+
+    let lower = key.to_lowercase();
+    let code = match lower.as_str() {
+        "tab" => KeyCode::Tab,
+        "esc" | "escape" => KeyCode::Esc,
+        "enter" | "return" => KeyCode::Enter,
+        "space" => KeyCode::Char(' '),
+        "backspace" => KeyCode::Backspace,
+        "delete" | "del" => KeyCode::Delete,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        f if f.len() > 1 && f.starts_with('f') && f[1..].parse::<u8>().is_ok() => {
+            KeyCode::F(f[1..].parse().unwrap())
+        }
+        _ if key.chars().count() == 1 => KeyCode::Char(lower.chars().next().unwrap()),
+        _ => return Err(format!("unknown key '{}'", key)),
+    };
+
+    Ok((code, modifiers))
+}
+
+/// Human readable form of a key, e.g. "Ctrl+S"
+pub fn format_key(code: &KeyCode, modifiers: &KeyModifiers) -> String {
+    let mut label = String::new();
+    if modifiers.contains(KeyModifiers::CONTROL) {
+        label.push_str("Ctrl+");
+    }
+    if modifiers.contains(KeyModifiers::ALT) {
+        label.push_str("Alt+");
+    }
+    if modifiers.contains(KeyModifiers::SHIFT) {
+        label.push_str("Shift+");
+    }
+    match code {
+        KeyCode::Char(' ') => label.push_str("Space"),
+        KeyCode::Char(c) => label.push(c.to_ascii_uppercase()),
+        other => label.push_str(&other.to_string()),
+    }
+    label
+}
+
+/// Human readable form of a chord, e.g. "Ctrl+K Ctrl+S"
+pub fn format_keys(keys: &[Key]) -> String {
+    keys.iter().map(|(code, modifiers)| format_key(code, modifiers)).collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(parse_key("ctrl+s"), Ok((KeyCode::Char('s'), KeyModifiers::CONTROL)));
+        assert_eq!(parse_key("F5"), Ok((KeyCode::F(5), KeyModifiers::NONE)));
+        assert_eq!(
+            parse_key("alt+shift+up"),
+            Ok((KeyCode::Up, KeyModifiers::ALT | KeyModifiers::SHIFT))
+        );
+        assert!(parse_key("hyper+x").is_err());
+    }
+
+    #[test]
+    fn test_overrides() {
+        let mut overrides = BTreeMap::new();
+        overrides.insert("ctrl+s".to_string(), "open_settings".to_string());
+        overrides.insert("ctrl+q".to_string(), "none".to_string());
+        overrides.insert("ctrl+x".to_string(), "bogus".to_string());
+        let (key_map, errors) = build_key_map(&overrides);
+        assert_eq!(key_map.get(&(KeyCode::Char('s'), KeyModifiers::CONTROL)), Some(&Action::OpenSettings));
+        assert!(!key_map.contains_key(&(KeyCode::Char('q'), KeyModifiers::CONTROL)));
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn test_chords() {
+        let mut overrides = BTreeMap::new();
+        overrides.insert("ctrl+k ctrl+s".to_string(), "open_settings".to_string());
+        overrides.insert("alt+space f  f".to_string(), "file_search".to_string());
+        let (key_map, errors) = build_key_map(&overrides);
+        assert!(errors.is_empty());
+
+        let ctrl_k = (KeyCode::Char('k'), KeyModifiers::CONTROL);
+        let ctrl_s = (KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(key_map.lookup(&[ctrl_k]), ChordMatch::Prefix);
+        assert_eq!(key_map.lookup(&[ctrl_k, ctrl_s]), ChordMatch::Action(Action::OpenSettings));
+        assert_eq!(key_map.lookup(&[ctrl_k, ctrl_k]), ChordMatch::None);
+        assert_eq!(key_map.get(&ctrl_s), Some(&Action::Save));
+        assert!(key_map.contains_key(&ctrl_k));
+
+        let leader = parse_keys("alt+space f f").unwrap();
+        assert_eq!(key_map.lookup(&leader), ChordMatch::Action(Action::FileSearch));
+        assert_eq!(format_keys(&leader), "Alt+Space F F");
+
+        // A chord starting with a bound key takes its place
+        let (key_map, _) = build_key_map(&BTreeMap::from([("ctrl+s ctrl+a".to_string(), "save_as".to_string())]));
+        assert_eq!(key_map.lookup(&[ctrl_s]), ChordMatch::Prefix);
+    }
+
+    #[test]
+    fn test_editor_presets() {
+        let kill = (KeyCode::Char('k'), KeyModifiers::CONTROL);
+        assert_eq!(editor_key_map(KeybindingMode::Emacs).get(&kill), Some(&EditorCommand::KillLine));
+        assert!(!editor_key_map(KeybindingMode::Default).contains_key(&kill));
+        assert_eq!(
+            editor_key_map(KeybindingMode::Default).get(&(KeyCode::Home, KeyModifiers::NONE)),
+            Some(&EditorCommand::LineStart)
+        );
+    }
+}
@@ -3,13 +3,31 @@
 
 pub mod action;
 pub mod app;
+pub mod completion;
+pub mod diff_view;
 pub mod editor;
+pub mod find;
+pub mod hex_view;
+pub mod keymap;
+pub mod navigation;
+pub mod outline;
+pub mod process_view;
+pub mod project_setup;
+pub mod recent_files;
+pub mod references;
+mod run;
+pub mod search_palette;
+pub mod snapshot;
 pub mod theme;
+pub mod tree_menu;
 pub mod ui;
+pub mod vim;
+pub mod welcome;
 pub mod workspace_selector;
 
 // Re-export commonly used types
 pub use action::Action;
-pub use app::{App, AppEvent, ActivePanel};
-pub use ui::{ui, get_layout_chunks};
+pub use run::run;
+pub use app::{App, AppEvent, ActivePanel, ConnectionTest, TerminalTab};
+pub use ui::{ui, app_layout, get_layout_chunks, todo_panel_area};
 pub use workspace_selector::WorkspaceSelector;
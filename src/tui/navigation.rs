@@ -0,0 +1,94 @@
+// Navigation history: where focus and the cursor were before each jump, so
+// back/forward can return across files and panels like an IDE's navigation stack
+
+use std::path::PathBuf;
+
+use super::app::ActivePanel;
+
+/// Locations kept to go back to
+const MAX_HISTORY: usize = 100;
+
+/// Cursor moves within a file over more lines than this count as a jump
+const JUMP_LINES: usize = 10;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub panel: ActivePanel,
+    /// The editor's file and cursor; `None` for untitled buffers and other panels
+    pub file: Option<PathBuf>,
+    pub row: usize,
+    pub col: usize,
+}
+
+impl Location {
+    /// Whether going from `self` to `other` is a jump rather than ordinary cursor movement
+    fn is_jump_to(&self, other: &Location) -> bool {
+        self.panel != other.panel || self.file != other.file || self.row.abs_diff(other.row) > JUMP_LINES
+    }
+}
+
+#[derive(Default)]
+pub struct NavHistory {
+    back: Vec<Location>,
+    forward: Vec<Location>,
+    current: Option<Location>,
+}
+
+impl NavHistory {
+    /// Note where focus is now. After a jump the previous location can be gone back to.
+    pub fn visit(&mut self, here: Location) {
+        if let Some(previous) = self.current.take().filter(|p| p.is_jump_to(&here)) {
+            self.back.push(previous);
+            if self.back.len() > MAX_HISTORY {
+                self.back.remove(0);
+            }
+            self.forward.clear();
+        }
+        self.current = Some(here);
+    }
+
+    /// The location before the last jump
+    pub fn back(&mut self) -> Option<Location> {
+        let target = self.back.pop()?;
+        self.forward.extend(self.current.replace(target.clone()));
+        Some(target)
+    }
+
+    /// The location left by going back
+    pub fn forward(&mut self) -> Option<Location> {
+        let target = self.forward.pop()?;
+        self.back.extend(self.current.replace(target.clone()));
+        Some(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(panel: ActivePanel, file: &str, row: usize) -> Location {
+        Location { panel, file: Some(PathBuf::from(file)), row, col: 0 }
+    }
+
+    #[test]
+    fn test_back_and_forward() {
+        let mut history = NavHistory::default();
+        history.visit(at(ActivePanel::Editor, "a.rs", 0));
+        history.visit(at(ActivePanel::Editor, "a.rs", 5));
+        history.visit(at(ActivePanel::Editor, "a.rs", 80));
+        history.visit(at(ActivePanel::Editor, "b.rs", 3));
+        history.visit(Location { panel: ActivePanel::Terminal, file: None, row: 0, col: 0 });
+
+        assert_eq!(history.back(), Some(at(ActivePanel::Editor, "b.rs", 3)));
+        assert_eq!(history.back(), Some(at(ActivePanel::Editor, "a.rs", 80)));
+        // Small moves update the location instead of adding one
+        assert_eq!(history.back(), Some(at(ActivePanel::Editor, "a.rs", 5)));
+        assert_eq!(history.back(), None);
+        assert_eq!(history.forward(), Some(at(ActivePanel::Editor, "a.rs", 80)));
+
+        // A new jump drops the forward history
+        history.visit(at(ActivePanel::Editor, "c.rs", 0));
+        assert_eq!(history.forward(), None);
+        assert_eq!(history.back(), Some(at(ActivePanel::Editor, "a.rs", 80)));
+    }
+}
@@ -0,0 +1,241 @@
+// Outline popup: the functions, types and modules of the open file, from the
+// language server's document symbols or, without one, per-language patterns,
+// narrowed down by typing and jumped to with Enter
+
+use regex::Regex;
+use serde_json::Value;
+use std::sync::LazyLock;
+
+use crate::shared::lsp;
+
+static RUST_ITEM: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^(\s*)(?:pub(?:\([^)]*\))?\s+)?(?:(?:const|async|unsafe|default|extern\s+"[^"]*")\s+)*(fn|struct|enum|union|trait|mod|type|const|static)\s+([A-Za-z_][A-Za-z0-9_]*)"#).unwrap()
+});
+static RUST_IMPL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(\s*)(?:unsafe\s+)?impl(?:<[^{]*?>)?\s+([^{]+?)\s*(?:where\b.*)?\{?\s*$").unwrap()
+});
+static PYTHON_ITEM: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(\s*)(?:async\s+)?(def|class)\s+([A-Za-z_][A-Za-z0-9_]*)").unwrap());
+static GO_ITEM: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^()(func|type)\s+(?:\([^)]*\)\s*)?([A-Za-z_][A-Za-z0-9_]*)").unwrap());
+static JS_ITEM: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(\s*)(?:export\s+)?(?:default\s+)?(?:declare\s+)?(?:abstract\s+)?(?:async\s+)?(function\s*\*?|class|interface|enum|type)\s+([A-Za-z_$][A-Za-z0-9_$]*)").unwrap()
+});
+
+/// A named place in the file
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutlineSymbol {
+    pub name: String,
+    /// LSP `SymbolKind`, e.g. 12 for a function
+    pub kind: u64,
+    /// 0-based row and char column of the name
+    pub row: usize,
+    pub col: usize,
+    /// Nesting, 0 at the top level
+    pub depth: usize,
+}
+
+impl OutlineSymbol {
+    /// One-letter kind, like the completion popup's
+    pub fn icon(&self) -> &'static str {
+        match self.kind {
+            6 | 9 | 12 => "f",
+            7 | 8 => "p",
+            13 | 14 => "v",
+            5 | 10 | 11 | 19 | 23 | 26 => "t",
+            2..=4 => "m",
+            22 => "e",
+            _ => "·",
+        }
+    }
+}
+
+/// Symbols of a `textDocument/documentSymbol` reply, either nested
+/// `DocumentSymbol`s or flat `SymbolInformation`s, for the document `lines`
+pub fn from_lsp<S: AsRef<str>>(result: &Value, lines: &[S]) -> Vec<OutlineSymbol> {
+    let mut symbols = Vec::new();
+    for value in result.as_array().into_iter().flatten() {
+        add_lsp_symbol(value, 0, lines, &mut symbols);
+    }
+    // Flat lists come in no particular order
+    symbols.sort_by_key(|s| (s.row, s.col));
+    symbols
+}
+
+fn add_lsp_symbol<S: AsRef<str>>(value: &Value, depth: usize, lines: &[S], out: &mut Vec<OutlineSymbol>) {
+    let Some(name) = value.get("name").and_then(Value::as_str) else { return };
+    let range = value.get("selectionRange")
+        .or_else(|| value.pointer("/location/range"))
+        .or_else(|| value.get("range"));
+    let Some(start) = range.and_then(|r| r.get("start")) else { return };
+    let Some((row, col)) = lsp::position_from_json(start, lines) else { return };
+    let container = value.get("containerName").and_then(Value::as_str).is_some_and(|c| !c.is_empty());
+    out.push(OutlineSymbol {
+        name: name.to_string(),
+        kind: value.get("kind").and_then(Value::as_u64).unwrap_or(0),
+        row,
+        col,
+        depth: depth + usize::from(container),
+    });
+    for child in value.get("children").and_then(Value::as_array).into_iter().flatten() {
+        add_lsp_symbol(child, depth + 1, lines, out);
+    }
+}
+
+/// Symbols found by per-language patterns, for files without a language
+/// server; nesting follows the indentation
+pub fn scan<S: AsRef<str>>(language: &str, lines: &[S]) -> Vec<OutlineSymbol> {
+    let pattern: &Regex = match language {
+        "rs" => &RUST_ITEM,
+        "py" | "pyi" => &PYTHON_ITEM,
+        "go" => &GO_ITEM,
+        "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" => &JS_ITEM,
+        _ => return Vec::new(),
+    };
+    let mut symbols = Vec::new();
+    // Indentation and kind of the symbols enclosing the current line
+    let mut open: Vec<(usize, u64)> = Vec::new();
+    for (row, line) in lines.iter().enumerate() {
+        let line = line.as_ref();
+        let found = pattern.captures(line)
+            .map(|c| (c.get(1).map_or(0, |m| m.len()), c.get(2).map_or("", |m| m.as_str()), c.get(3).map(|m| (m.as_str(), m.start()))))
+            .or_else(|| {
+                let c = RUST_IMPL.captures(line).filter(|_| language == "rs")?;
+                Some((c[1].len(), "impl", c.get(2).map(|m| (m.as_str(), m.start()))))
+            });
+        let Some((indent, keyword, Some((name, start)))) = found else { continue };
+        while open.last().is_some_and(|&(i, _)| i >= indent) {
+            open.pop();
+        }
+        let (kind, enclosed) = match keyword.trim_end_matches(|c: char| c == '*' || c.is_whitespace()) {
+            "fn" | "def" | "function" if open.last().is_some_and(|&(_, k)| matches!(k, 5 | 11 | 19)) => (6, true),
+            "fn" | "def" | "function" => (12, true),
+            "func" => (if line.starts_with("func (") { 6 } else { 12 }, true),
+            "struct" => (23, true),
+            "enum" => (10, true),
+            "trait" | "interface" => (11, true),
+            "class" | "union" => (5, true),
+            "impl" => (19, true),
+            "mod" => (2, true),
+            "const" | "static" => (14, false),
+            _ => (26, false),
+        };
+        symbols.push(OutlineSymbol {
+            name: name.trim().to_string(),
+            kind,
+            row,
+            col: line[..start].chars().count(),
+            depth: open.len(),
+        });
+        if enclosed {
+            open.push((indent, kind));
+        }
+    }
+    symbols
+}
+
+pub struct Outline {
+    /// The file's name, for the title
+    pub title: String,
+    pub symbols: Vec<OutlineSymbol>,
+    /// Whether the symbols came from the language server rather than patterns
+    pub from_server: bool,
+    pub query: String,
+    /// Index into `matches()`
+    pub selected: usize,
+}
+
+impl Outline {
+    /// The outline with the symbol the cursor (`row`) is in selected
+    pub fn new(title: String, symbols: Vec<OutlineSymbol>, from_server: bool, row: usize) -> Self {
+        let mut outline = Self { title, symbols: Vec::new(), from_server, query: String::new(), selected: 0 };
+        outline.set_symbols(symbols, from_server, row);
+        outline
+    }
+
+    /// Replace the symbols, e.g. when the language server's arrive
+    pub fn set_symbols(&mut self, symbols: Vec<OutlineSymbol>, from_server: bool, row: usize) {
+        self.symbols = symbols;
+        self.from_server = from_server;
+        self.selected = if self.query.is_empty() {
+            self.symbols.iter().rposition(|s| s.row <= row).unwrap_or(0)
+        } else {
+            0
+        };
+    }
+
+    /// Symbols whose name contains the query, ignoring case
+    pub fn matches(&self) -> Vec<&OutlineSymbol> {
+        let query = self.query.to_lowercase();
+        self.symbols.iter().filter(|s| s.name.to_lowercase().contains(&query)).collect()
+    }
+
+    pub fn select(&mut self, delta: isize) {
+        let last = self.matches().len().saturating_sub(1) as isize;
+        self.selected = (self.selected as isize + delta).clamp(0, last) as usize;
+    }
+
+    pub fn set_query(&mut self, query: String) {
+        self.query = query;
+        self.selected = 0;
+    }
+
+    pub fn selected_symbol(&self) -> Option<&OutlineSymbol> {
+        self.matches().get(self.selected).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_scan_and_lsp_symbols() {
+        let lines = [
+            "pub struct Parser {",
+            "}",
+            "impl<'a> Display for Parser {",
+            "    pub(crate) fn parse(&self) {",
+            "        const LIMIT: usize = 3;",
+            "    }",
+            "}",
+            "async fn main() {}",
+        ];
+        let rust = scan("rs", &lines);
+        let names: Vec<(&str, &str, usize, usize)> = rust.iter().map(|s| (s.name.as_str(), s.icon(), s.col, s.depth)).collect();
+        assert_eq!(names, vec![
+            ("Parser", "t", 11, 0),
+            ("Display for Parser", "t", 9, 0),
+            ("parse", "f", 18, 1),
+            ("LIMIT", "v", 14, 2),
+            ("main", "f", 9, 0),
+        ]);
+        let python = scan("py", &["class A:", "    def run(self):", "        pass", "def helper():"]);
+        assert_eq!(python.iter().map(|s| (s.kind, s.depth)).collect::<Vec<_>>(), vec![(5, 0), (6, 1), (12, 0)]);
+
+        let nested = json!([{
+            "name": "Parser", "kind": 23,
+            "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 6, "character": 1 } },
+            "selectionRange": { "start": { "line": 0, "character": 11 }, "end": { "line": 0, "character": 17 } },
+            "children": [{
+                "name": "parse", "kind": 6,
+                "range": { "start": { "line": 3, "character": 4 }, "end": { "line": 5, "character": 5 } },
+                "selectionRange": { "start": { "line": 3, "character": 18 }, "end": { "line": 3, "character": 23 } }
+            }]
+        }]);
+        let symbols = from_lsp(&nested, &lines);
+        assert_eq!(symbols.iter().map(|s| (s.name.as_str(), s.row, s.col, s.depth)).collect::<Vec<_>>(), vec![("Parser", 0, 11, 0), ("parse", 3, 18, 1)]);
+        let flat = json!([
+            { "name": "main", "kind": 12, "location": { "uri": "file:///a.rs", "range": { "start": { "line": 7, "character": 0 }, "end": { "line": 7, "character": 18 } } } },
+            { "name": "parse", "kind": 6, "containerName": "Parser", "location": { "uri": "file:///a.rs", "range": { "start": { "line": 3, "character": 4 }, "end": { "line": 5, "character": 5 } } } }
+        ]);
+        assert_eq!(from_lsp(&flat, &lines).iter().map(|s| (s.name.as_str(), s.depth)).collect::<Vec<_>>(), vec![("parse", 1), ("main", 0)]);
+
+        let mut outline = Outline::new("a.rs".to_string(), symbols, true, 4);
+        assert_eq!(outline.selected_symbol().map(|s| s.name.as_str()), Some("parse"));
+        outline.set_query("PAR".to_string());
+        outline.select(5);
+        assert_eq!(outline.selected_symbol().map(|s| s.name.as_str()), Some("parse"));
+        outline.set_query("zzz".to_string());
+        assert!(outline.selected_symbol().is_none());
+    }
+}
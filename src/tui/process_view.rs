@@ -0,0 +1,83 @@
+// Processes running in a terminal tab, refreshed while shown, with signals to
+// interrupt, stop or kill one when the terminal stops responding
+
+use std::time::{Duration, Instant};
+
+use crate::shared::process_tree::{self, ProcessRow};
+
+/// How often the list is read again while it is open
+pub const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct ProcessView {
+    /// Terminal tab the processes run in, e.g. "Terminal" or "Watch"
+    pub name: String,
+    /// The tab's shell or command
+    pub root: u32,
+    pub rows: Vec<ProcessRow>,
+    pub selected: usize,
+    /// Why the list couldn't be read, e.g. the root exited
+    pub error: Option<String>,
+    pub refreshed_at: Instant,
+}
+
+impl ProcessView {
+    pub fn open(name: &str, root: u32) -> Self {
+        let mut view = Self {
+            name: name.to_string(),
+            root,
+            rows: Vec::new(),
+            selected: 0,
+            error: None,
+            refreshed_at: Instant::now(),
+        };
+        view.refresh();
+        view
+    }
+
+    /// Read the processes again, keeping the selected one selected
+    pub fn refresh(&mut self) {
+        let selected = self.selected_pid();
+        self.refreshed_at = Instant::now();
+        match process_tree::list(self.root) {
+            Ok(rows) => {
+                self.rows = rows;
+                self.error = None;
+            }
+            Err(e) => {
+                self.rows.clear();
+                self.error = Some(e);
+            }
+        }
+        self.selected = selected
+            .and_then(|pid| self.rows.iter().position(|row| row.info.pid == pid))
+            .unwrap_or(self.selected.min(self.rows.len().saturating_sub(1)));
+    }
+
+    pub fn select(&mut self, delta: isize) {
+        let last = self.rows.len().saturating_sub(1) as isize;
+        self.selected = (self.selected as isize + delta).clamp(0, last) as usize;
+    }
+
+    pub fn selected_pid(&self) -> Option<u32> {
+        self.rows.get(self.selected).map(|row| row.info.pid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_open_and_select() {
+        let mut view = ProcessView::open("Terminal", std::process::id());
+        assert_eq!(view.selected_pid(), Some(std::process::id()));
+        view.select(-3);
+        assert_eq!(view.selected, 0);
+
+        let mut gone = ProcessView::open("Watch", u32::MAX);
+        assert!(gone.error.is_some() && gone.selected_pid().is_none());
+        gone.select(1);
+        assert_eq!(gone.selected, 0);
+    }
+}
@@ -0,0 +1,64 @@
+// Project setup panel: shown once when a workspace with a detected project is
+// first opened, listing its build, test and run commands to send to the shell
+// and offering a model-written summary of the project for the chat
+
+use crate::shared::project::Project;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SetupItem {
+    /// A command typed into the shell on Enter
+    Command { label: String, command: String },
+    /// Ask the selected model for a summary of the project in the chat
+    Summarize,
+}
+
+pub struct ProjectSetup {
+    pub project: Project,
+    pub items: Vec<SetupItem>,
+    pub selected: usize,
+}
+
+impl ProjectSetup {
+    /// The project's tasks, then its run command, then the summary
+    pub fn new(project: Project) -> Self {
+        let tasks = project.tasks.iter().map(|task| (task.label.to_string(), task.command.clone()));
+        let run = project.run.iter().map(|command| ("Run".to_string(), command.clone()));
+        let items = tasks.chain(run)
+            .map(|(label, command)| SetupItem::Command { label, command })
+            .chain([SetupItem::Summarize])
+            .collect();
+        Self { project, items, selected: 0 }
+    }
+
+    pub fn select(&mut self, delta: isize) {
+        let last = self.items.len().saturating_sub(1) as isize;
+        self.selected = (self.selected as isize + delta).clamp(0, last) as usize;
+    }
+
+    pub fn selected_item(&self) -> Option<&SetupItem> {
+        self.items.get(self.selected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::project::{ProjectKind, Task};
+
+    #[test]
+    fn test_setup_items() {
+        let project = Project {
+            kind: ProjectKind::Npm,
+            name: "web".to_string(),
+            manifest: "package.json",
+            tasks: vec![Task { label: "Install", command: "npm install".to_string() }],
+            run: Some("npm run start".to_string()),
+        };
+        let mut setup = ProjectSetup::new(project);
+        assert_eq!(setup.items.len(), 3);
+        setup.select(1);
+        assert_eq!(setup.selected_item(), Some(&SetupItem::Command { label: "Run".to_string(), command: "npm run start".to_string() }));
+        setup.select(5);
+        assert_eq!(setup.selected_item(), Some(&SetupItem::Summarize));
+    }
+}
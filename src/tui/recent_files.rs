@@ -0,0 +1,82 @@
+// Ctrl+E quick switcher: the files opened in this workspace, most recent
+// first, narrowed down by typing part of the path
+
+use std::path::{Path, PathBuf};
+
+use crate::shared::Config;
+
+pub struct RecentFiles {
+    /// Paths relative to the workspace, most recent first
+    pub files: Vec<PathBuf>,
+    pub query: String,
+    /// Index into `matches()`
+    pub selected: usize,
+}
+
+impl RecentFiles {
+    /// Recent files of `workspace` that still exist; when the first is the
+    /// open file (`current`, relative), the one before it is selected
+    pub fn new(config: &Config, workspace: &Path, current: Option<&Path>) -> Self {
+        let files: Vec<PathBuf> = config.recent_workspace(workspace).iter()
+            .flat_map(|w| &w.recent_files)
+            .filter(|f| workspace.join(f).is_file())
+            .cloned()
+            .collect();
+        let selected = usize::from(current.is_some() && files.first().map(PathBuf::as_path) == current && files.len() > 1);
+        Self { files, query: String::new(), selected }
+    }
+
+    /// Files whose path contains the query, ignoring case
+    pub fn matches(&self) -> Vec<&PathBuf> {
+        let query = self.query.to_lowercase();
+        self.files.iter().filter(|f| f.to_string_lossy().to_lowercase().contains(&query)).collect()
+    }
+
+    /// Move the selection, wrapping around the list
+    pub fn select(&mut self, delta: isize) {
+        let count = self.matches().len();
+        if count > 0 {
+            self.selected = (self.selected as isize + delta).rem_euclid(count as isize) as usize;
+        }
+    }
+
+    pub fn set_query(&mut self, query: String) {
+        self.query = query;
+        self.selected = 0;
+    }
+
+    pub fn selected_file(&self) -> Option<PathBuf> {
+        self.matches().get(self.selected).map(|f| f.to_path_buf())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recent_files() {
+        let root = std::env::temp_dir().join(format!("nterm-recent-files-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        for name in ["src/main.rs", "src/app.rs", "README.md"] {
+            std::fs::write(root.join(name), "").unwrap();
+        }
+        let mut config = Config::from_json("{}").unwrap();
+        config.add_recent_workspace(root.clone());
+        for name in ["README.md", "gone.rs", "src/app.rs", "src/main.rs"] {
+            config.add_recent_file(&root, PathBuf::from(name));
+        }
+
+        let mut recent = RecentFiles::new(&config, &root, Some(Path::new("src/main.rs")));
+        assert_eq!(recent.files, vec![PathBuf::from("src/main.rs"), PathBuf::from("src/app.rs"), PathBuf::from("README.md")]);
+        assert_eq!(recent.selected_file(), Some(PathBuf::from("src/app.rs")));
+        recent.select(2);
+        assert_eq!(recent.selected, 0);
+
+        recent.set_query("SRC/".to_string());
+        assert_eq!(recent.matches().len(), 2);
+        recent.select(-1);
+        assert_eq!(recent.selected_file(), Some(PathBuf::from("src/app.rs")));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
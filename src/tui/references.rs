@@ -0,0 +1,95 @@
+// References popup: every use of a symbol the language server found, each
+// with the line it is on, to jump to one with Enter
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::shared::lsp::{self, Location};
+
+pub struct Reference {
+    pub path: PathBuf,
+    /// 0-based row and char column
+    pub row: usize,
+    pub col: usize,
+    /// The line, trimmed
+    pub text: String,
+}
+
+pub struct ReferenceList {
+    /// e.g. "References to `parse`"
+    pub title: String,
+    pub references: Vec<Reference>,
+    pub selected: usize,
+}
+
+impl ReferenceList {
+    pub fn new(title: String, references: Vec<Reference>) -> Self {
+        Self { title, references, selected: 0 }
+    }
+
+    pub fn select(&mut self, delta: isize) {
+        let last = self.references.len().saturating_sub(1) as isize;
+        self.selected = (self.selected as isize + delta).clamp(0, last) as usize;
+    }
+
+    pub fn selected_reference(&self) -> Option<&Reference> {
+        self.references.get(self.selected)
+    }
+}
+
+/// Turn a server's locations into char positions with their lines, reading
+/// each file once; `open` is the path and text of the buffer being edited,
+/// which may differ from what is on disk
+pub fn resolve(locations: &[Location], open: Option<(&Path, &[String])>) -> Vec<Reference> {
+    let mut files: HashMap<&Path, Vec<String>> = HashMap::new();
+    locations.iter()
+        .map(|location| {
+            let line = match open {
+                Some((path, lines)) if path == location.path => lines.get(location.line).cloned(),
+                _ => files.entry(&location.path)
+                    .or_insert_with(|| fs::read_to_string(&location.path).map(|s| s.lines().map(str::to_string).collect()).unwrap_or_default())
+                    .get(location.line)
+                    .cloned(),
+            }.unwrap_or_default();
+            Reference {
+                path: location.path.clone(),
+                row: location.line,
+                col: lsp::char_col(&line, location.character),
+                text: line.trim().to_string(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_and_select() {
+        let dir = std::env::temp_dir().join(format!("nterm-references-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let on_disk = dir.join("lib.rs");
+        fs::write(&on_disk, "fn main() {\n    let é = parse();\n}\n").unwrap();
+        let open = dir.join("open.rs");
+        let open_lines = vec!["use crate::parse;".to_string()];
+
+        let locations = vec![
+            Location { path: on_disk.clone(), line: 1, character: 12 },
+            Location { path: open.clone(), line: 0, character: 11 },
+            Location { path: dir.join("missing.rs"), line: 3, character: 2 },
+        ];
+        let references = resolve(&locations, Some((&open, &open_lines)));
+        assert_eq!((references[0].row, references[0].col, references[0].text.as_str()), (1, 12, "let é = parse();"));
+        assert_eq!((references[1].col, references[1].text.as_str()), (11, "use crate::parse;"));
+        assert_eq!(references[2].text, "");
+
+        let mut list = ReferenceList::new("References to `parse`".to_string(), references);
+        list.select(5);
+        assert_eq!(list.selected_reference().map(|r| r.path.clone()), Some(dir.join("missing.rs")));
+        list.select(-5);
+        assert_eq!(list.selected, 0);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
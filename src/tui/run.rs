@@ -0,0 +1,1029 @@
+// Entry point of the terminal interface: terminal setup, the workspace
+// selector and the event loop
+
+use ratatui::{
+    backend::{Backend, CrosstermBackend},
+    crossterm::{
+        event::{
+            DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers, KeyboardEnhancementFlags,
+            MouseEventKind, MouseButton, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+        },
+        execute,
+        terminal::{disable_raw_mode, enable_raw_mode, supports_keyboard_enhancement, EnterAlternateScreen, LeaveAlternateScreen},
+    },
+    layout::{Position, Rect},
+    Terminal,
+};
+use std::error::Error;
+use std::io;
+
+use super::{Action, App, AppEvent, ActivePanel, TerminalTab, ui, app_layout, todo_panel_area, WorkspaceSelector};
+use crate::shared::events::WorkspaceEvent;
+use crate::shared::capabilities::{Capabilities, TerminalOverrides};
+use crate::shared::{syntax, Config, KeybindingMode};
+
+/// Run nterm in the current terminal: pick a workspace, then edit until the
+/// user quits. Starts a tokio runtime for the AI requests unless called from
+/// inside one.
+pub fn run() -> Result<(), Box<dyn Error>> {
+    let runtime = match tokio::runtime::Handle::try_current() {
+        Ok(_) => None,
+        Err(_) => Some(tokio::runtime::Runtime::new()?),
+    };
+    let _guard = runtime.as_ref().map(|r| r.enter());
+
+    // Start loading syntax definitions while the workspace selector is shown
+    syntax::preload();
+
+    // Setup terminal
+    let mut config = Config::load();
+    enable_raw_mode()?;
+    let capabilities = detect_capabilities(&config.terminal);
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    if capabilities.mouse {
+        execute!(stdout, EnableMouseCapture)?;
+    }
+    if capabilities.kitty_keyboard {
+        execute!(stdout, PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES))?;
+    }
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+
+    // Run workspace selector
+    let mut selector = WorkspaceSelector::new(&config);
+    let workspace_path = match selector.run(&mut terminal)? {
+        Some(path) => path,
+        None => {
+            // User pressed Esc - exit gracefully
+            restore_terminal(&mut terminal, &capabilities)?;
+            return Ok(());
+        }
+    };
+
+    // Change to selected workspace BEFORE App::new()
+    std::env::set_current_dir(&workspace_path)?;
+
+    // Update config with recent workspace and save
+    config.add_recent_workspace(workspace_path);
+    let _ = config.save();
+
+    // Run app (PTY and file tree will use selected workspace)
+    let mut app = App::new();
+    app.capabilities = capabilities;
+    let res = run_app(&mut terminal, &mut app);
+
+    restore_terminal(&mut terminal, &capabilities)?;
+
+    match res {
+        // Backups only need to survive a crash
+        Ok(()) => app.on_clean_exit(),
+        Err(err) => eprintln!("{:?}", err),
+    }
+
+    Ok(())
+}
+
+/// What the terminal supports: guessed from the environment, then the kitty
+/// keyboard protocol is queried (needs raw mode), then config overrides apply
+fn detect_capabilities(overrides: &TerminalOverrides) -> Capabilities {
+    let mut capabilities = Capabilities::from_env();
+    // Minimal terminals (the ones without mouse reporting) would only make the query time out
+    if !capabilities.kitty_keyboard && overrides.kitty_keyboard.is_none() && capabilities.mouse {
+        capabilities.kitty_keyboard = supports_keyboard_enhancement().unwrap_or(false);
+    }
+    capabilities.with_overrides(overrides)
+}
+
+/// Undo the setup in `run`
+fn restore_terminal<B: Backend + io::Write>(terminal: &mut Terminal<B>, capabilities: &Capabilities) -> io::Result<()> {
+    disable_raw_mode()?;
+    if capabilities.kitty_keyboard {
+        execute!(terminal.backend_mut(), PopKeyboardEnhancementFlags)?;
+    }
+    if capabilities.mouse {
+        execute!(terminal.backend_mut(), DisableMouseCapture)?;
+    }
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()
+}
+
+fn run_app<B: Backend + std::io::Write>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
+    loop {
+        if app.should_quit {
+            return Ok(());
+        }
+        
+        // Update Scrollbar States
+        app.file_tree_scroll_state = app.file_tree_scroll_state.content_length(app.visible_items.len()).position(app.file_tree_scroll_offset);
+        app.editor_scroll_state = app.editor_scroll_state.content_length(app.editor_state.line_count()).position(app.editor_state.scroll_offset);
+        let chat_lines: usize = app.chat_history.iter().map(|m| m.content.lines().count()).sum();
+        app.chat_scroll_state = app.chat_scroll_state.content_length(chat_lines).position(app.chat_scroll as usize);
+        
+        app.track_navigation();
+        if let Ok(size) = terminal.size() {
+            app.fit_terminal(Rect::new(0, 0, size.width, size.height));
+        }
+        let frame = terminal.draw(|f| ui(f, app))?;
+        if let Some(redact_chat) = app.pending_snapshot.take() {
+            let buffer = frame.buffer.clone();
+            app.save_snapshot(buffer, redact_chat);
+        }
+        // After drawing, so the request covers the viewport just rendered
+        app.request_highlights();
+        app.update_mirror();
+
+        // Wait for at least one event
+        let first_event = match app.event_rx.recv() {
+            Ok(e) => e,
+            Err(_) => return Ok(()),
+        };
+        
+        let mut events = vec![first_event];
+        // Drain pending events (limit to 50)
+        while let Ok(e) = app.event_rx.try_recv() {
+            events.push(e);
+            if events.len() > 50 { break; }
+        }
+
+        for event in events {
+            match event {
+                AppEvent::PtyData => app.on_terminal_output(TerminalTab::Shell),
+                AppEvent::AiResponse(message) => app.publish(WorkspaceEvent::AiResponseCompleted(message)),
+                AppEvent::Tick => app.on_tick(),
+                AppEvent::FsChanged => app.publish(WorkspaceEvent::FilesChanged),
+                AppEvent::Remote(command) => app.handle_remote(command),
+                AppEvent::TodosScanned(items) => app.on_todos_scanned(items),
+                AppEvent::StatsCollected(stats) => app.on_stats_collected(stats),
+                AppEvent::ChatPrepared { message, prompt } => app.on_chat_prepared(message, prompt),
+                AppEvent::RefactorPrepared { occurrences, prompt } => app.on_refactor_prepared(occurrences, prompt),
+                AppEvent::RefactorProposed(result) => app.on_refactor_proposed(result),
+                AppEvent::ReviewPrepared { path, lines, prompt } => app.on_review_prepared(path, lines, prompt),
+                AppEvent::ReviewReceived { path, result } => app.on_review_received(path, result),
+                AppEvent::ChatSummarized { count, result } => app.on_chat_summarized(count, result),
+                AppEvent::ConnectionTested(idx, result) => app.on_connection_tested(idx, result),
+                AppEvent::FilesProposed(proposals) => app.on_files_proposed(proposals),
+                AppEvent::FileLoaded(path, result) => app.on_file_loaded(path, result),
+                AppEvent::SpellCheckerLoaded(result) => app.on_spell_checker_loaded(result),
+                AppEvent::WatchOutput => app.on_terminal_output(TerminalTab::Watch),
+                AppEvent::WatchFinished { run, success } => app.on_watch_finished(run, success),
+                AppEvent::GitChanges { path, changes } => app.on_git_changes(path, changes),
+                AppEvent::ReferencesCounted { path, counts } => app.on_references_counted(path, counts),
+                AppEvent::LinesHighlighted { request, lines } => app.on_lines_highlighted(request, lines),
+                AppEvent::LintFinished { path, run, result } => app.on_lint_finished(path, run, result),
+                AppEvent::ConfigSaveFailed(message) => app.notify(message),
+                AppEvent::WorkspaceSearched { generation, hits } => app.on_workspace_searched(generation, hits),
+                AppEvent::Lsp { server, message } => app.on_lsp_message(server, message),
+                AppEvent::PermalinkBuilt(result) => app.on_permalink_built(result),
+                AppEvent::Formatted { path, text, result } => app.on_formatted(path, text, result),
+                AppEvent::Input(input) => {
+                    if let Event::Key(key) = input {
+                        // Recovery prompt takes precedence over everything else
+                        if app.show_recovery {
+                            let count = app.recovery_backups.len();
+                            let selected = app.recovery_state.selected().unwrap_or(0);
+                            match key.code {
+                                KeyCode::Up if selected > 0 => {
+                                    app.recovery_state.select(Some(selected - 1));
+                                }
+                                KeyCode::Down if selected + 1 < count => {
+                                    app.recovery_state.select(Some(selected + 1));
+                                }
+                                KeyCode::Enter => app.restore_backup(selected),
+                                KeyCode::Char('d') | KeyCode::Delete => app.discard_backup(selected),
+                                KeyCode::Esc => app.discard_backups(),
+                                _ => {}
+                            }
+                            continue;
+                        }
+
+                        // Likely secrets in an outgoing chat message
+                        if app.show_secret_warning {
+                            match key.code {
+                                KeyCode::Char('y') => app.resolve_secret_warning(true),
+                                KeyCode::Char('n') | KeyCode::Esc => app.resolve_secret_warning(false),
+                                _ => {}
+                            }
+                            continue;
+                        }
+
+                        if app.unsaved_prompt.is_some() {
+                            match key.code {
+                                KeyCode::Char('s') | KeyCode::Enter => app.unsaved_save(),
+                                KeyCode::Char('d') => app.unsaved_discard(),
+                                KeyCode::Char('c') | KeyCode::Esc => app.unsaved_prompt = None,
+                                _ => {}
+                            }
+                            continue;
+                        }
+
+                        // File changed on disk while it has unsaved edits
+                        if app.show_conflict {
+                            match key.code {
+                                KeyCode::Char('r') => app.conflict_reload(),
+                                KeyCode::Char('k') | KeyCode::Esc => app.conflict_keep_mine(),
+                                KeyCode::Char('d') => app.conflict_diff(),
+                                _ => {}
+                            }
+                            continue;
+                        }
+
+                        // AI File Proposals: nothing is written without an explicit approval
+                        if app.show_proposals {
+                            match key.code {
+                                KeyCode::Esc => app.discard_proposals(),
+                                KeyCode::Char('y') => app.resolve_proposal(true),
+                                KeyCode::Char('n') => app.resolve_proposal(false),
+                                KeyCode::Left => app.cycle_proposal(false),
+                                KeyCode::Right => app.cycle_proposal(true),
+                                KeyCode::Up => app.proposal_scroll = app.proposal_scroll.saturating_sub(1),
+                                KeyCode::Down => app.proposal_scroll = app.proposal_scroll.saturating_add(1),
+                                KeyCode::PageUp => app.proposal_scroll = app.proposal_scroll.saturating_sub(20),
+                                KeyCode::PageDown => app.proposal_scroll = app.proposal_scroll.saturating_add(20),
+                                _ => {}
+                            }
+                            continue;
+                        }
+
+                        // Problems Panel Handling
+                        if app.show_problems {
+                            let count = app.visible_problems().len();
+                            let selected = app.problems_state.selected().unwrap_or(0);
+                            match key.code {
+                                KeyCode::Esc | KeyCode::F(8) => app.show_problems = false,
+                                KeyCode::Up if count > 0 => {
+                                    app.problems_state.select(Some(if selected == 0 { count - 1 } else { selected - 1 }));
+                                }
+                                KeyCode::Down if count > 0 => {
+                                    app.problems_state.select(Some((selected + 1) % count));
+                                }
+                                KeyCode::Tab => app.cycle_problems_filter(),
+                                KeyCode::Char('r') => app.refresh_build_diagnostics(),
+                                KeyCode::Enter => app.open_selected_problem(),
+                                _ => {}
+                            }
+                            continue;
+                        }
+
+                        // Save As Prompt Handling
+                        if app.show_save_as {
+                            match key.code {
+                                KeyCode::Esc => app.show_save_as = false,
+                                KeyCode::Enter => app.confirm_save_as(),
+                                _ => {
+                                    app.save_as_input.input(key);
+                                }
+                            }
+                            continue;
+                        }
+
+                        if app.show_compare {
+                            match key.code {
+                                KeyCode::Esc => app.show_compare = false,
+                                KeyCode::Enter => app.confirm_compare(),
+                                _ => {
+                                    app.compare_input.input(key);
+                                }
+                            }
+                            continue;
+                        }
+
+                        if app.show_capture {
+                            match key.code {
+                                KeyCode::Esc => app.show_capture = false,
+                                KeyCode::Enter => app.confirm_capture(),
+                                _ => {
+                                    app.capture_input.input(key);
+                                }
+                            }
+                            continue;
+                        }
+
+                        if app.show_spell {
+                            match key.code {
+                                KeyCode::Esc => app.show_spell = false,
+                                KeyCode::Up => app.spell_selected = app.spell_selected.saturating_sub(1),
+                                KeyCode::Down if app.spell_selected + 1 < app.spell_suggestions.len() => app.spell_selected += 1,
+                                KeyCode::Enter => app.apply_spell_suggestion(),
+                                KeyCode::Char('a') => app.add_spell_word(),
+                                _ => {}
+                            }
+                            continue;
+                        }
+
+                        if app.show_refactor_prompt {
+                            match key.code {
+                                KeyCode::Esc => app.show_refactor_prompt = false,
+                                KeyCode::Enter => app.confirm_refactor(),
+                                _ => {
+                                    app.refactor_input.input(key);
+                                }
+                            }
+                            continue;
+                        }
+
+                        if app.show_rename_prompt {
+                            match key.code {
+                                KeyCode::Esc => app.show_rename_prompt = false,
+                                KeyCode::Enter => app.confirm_rename(),
+                                _ => {
+                                    app.refactor_input.input(key);
+                                }
+                            }
+                            continue;
+                        }
+
+                        // Refactor Review Handling
+                        if app.show_refactor {
+                            let count = app.refactor_edits.len();
+                            match key.code {
+                                KeyCode::Esc => app.show_refactor = false,
+                                KeyCode::Up if app.refactor_selected > 0 => app.refactor_selected -= 1,
+                                KeyCode::Down if app.refactor_selected + 1 < count => app.refactor_selected += 1,
+                                KeyCode::Char(' ') => app.toggle_refactor_edit(false),
+                                KeyCode::Char('a') => app.toggle_refactor_edit(true),
+                                KeyCode::Enter => app.apply_refactor(),
+                                _ => {}
+                            }
+                            continue;
+                        }
+
+                        // Color Picker Handling
+                        if let Some(picker) = app.color_picker.as_mut() {
+                            let step = if key.modifiers.contains(KeyModifiers::SHIFT) { 16 } else { 1 };
+                            match key.code {
+                                KeyCode::Esc => app.color_picker = None,
+                                KeyCode::Enter => app.apply_color_picker(),
+                                KeyCode::Up | KeyCode::Char('k') => picker.select(-1),
+                                KeyCode::Down | KeyCode::Char('j') => picker.select(1),
+                                KeyCode::Left | KeyCode::Char('h') => picker.adjust(-step),
+                                KeyCode::Right | KeyCode::Char('l') => picker.adjust(step),
+                                _ => {}
+                            }
+                            continue;
+                        }
+
+                        // Workspace Stats Handling
+                        if app.show_stats {
+                            match key.code {
+                                KeyCode::Esc => app.show_stats = false,
+                                KeyCode::Char('r') => app.collect_stats(),
+                                KeyCode::Char('c') => app.stats_to_chat(),
+                                _ => {}
+                            }
+                            continue;
+                        }
+
+                        // TODO Panel Handling
+                        if app.show_todos {
+                            let count = app.todos.len();
+                            match key.code {
+                                KeyCode::Esc | KeyCode::F(7) => app.show_todos = false,
+                                KeyCode::Up if app.todos_selected > 0 => app.todos_selected -= 1,
+                                KeyCode::Down if app.todos_selected + 1 < count => app.todos_selected += 1,
+                                KeyCode::Char('r') => app.scan_todos(),
+                                KeyCode::Enter => app.open_todo(app.todos_selected),
+                                _ => {}
+                            }
+                            continue;
+                        }
+
+                        // Settings Mode Handling
+                        if app.show_settings {
+                            if app.settings_editing {
+                                // Editing mode: handle text input
+                                match key.code {
+                                    KeyCode::Esc => {
+                                        // Cancel editing, reload original value
+                                        app.load_settings_for_model(app.settings_model_idx);
+                                        app.settings_editing = false;
+                                    },
+                                    KeyCode::Enter => {
+                                        // Save and stop editing
+                                        app.settings_stop_edit();
+                                    },
+                                    _ => {
+                                        app.settings_input.input(key);
+                                    }
+                                }
+                            } else {
+                                // Navigation mode
+                                match key.code {
+                                    KeyCode::Esc => {
+                                        app.show_settings = false;
+                                    },
+                                    KeyCode::Tab => {
+                                        app.toggle_theme();
+                                    },
+                                    KeyCode::Up => {
+                                        app.settings_select_prev();
+                                    },
+                                    KeyCode::Down => {
+                                        app.settings_select_next();
+                                    },
+                                    KeyCode::Enter => {
+                                        // Start editing the API key
+                                        app.settings_start_edit();
+                                    },
+                                    KeyCode::Char(' ') => {
+                                        // Set as active model
+                                        app.settings_set_active_model();
+                                    },
+                                    KeyCode::Char('t') => {
+                                        app.settings_test_connection();
+                                    },
+                                    _ => {}
+                                }
+                            }
+                            continue;
+                        }
+
+                        if app.search_palette.is_some() {
+                            app.handle_search_palette_key(key);
+                            continue;
+                        }
+
+                        if app.recent_files.is_some() {
+                            app.handle_recent_files_key(key);
+                            continue;
+                        }
+
+                        if app.tree_menu.is_some() {
+                            app.handle_tree_menu_key(key);
+                            continue;
+                        }
+
+                        if app.restore_points.is_some() {
+                            app.handle_restore_picker_key(key);
+                            continue;
+                        }
+
+                        if app.process_view.is_some() {
+                            app.handle_process_view_key(key);
+                            continue;
+                        }
+
+                        if app.reference_list.is_some() {
+                            app.handle_reference_list_key(key);
+                            continue;
+                        }
+
+                        if app.preset_picker.is_some() {
+                            app.handle_preset_picker_key(key);
+                            continue;
+                        }
+
+                        if app.outline.is_some() {
+                            app.handle_outline_key(key);
+                            continue;
+                        }
+
+                        if app.project_setup.is_some() {
+                            app.handle_project_setup_key(key);
+                            continue;
+                        }
+
+                        // Search Mode Handling
+                        if app.is_searching {
+                            match key.code {
+                                KeyCode::Esc => app.is_searching = false,
+                                KeyCode::Enter => app.open_search_result(),
+                                KeyCode::Up => {
+                                    let i = match app.search_state.selected() {
+                                        Some(i) => if i == 0 { app.search_results.len().saturating_sub(1) } else { i - 1 },
+                                        None => 0,
+                                    };
+                                    app.search_state.select(Some(i));
+                                },
+                                KeyCode::Down => {
+                                    let i = match app.search_state.selected() {
+                                        Some(i) => if i >= app.search_results.len().saturating_sub(1) { 0 } else { i + 1 },
+                                        None => 0,
+                                    };
+                                    app.search_state.select(Some(i));
+                                },
+                                _ => {
+                                    app.search_input.input(key);
+                                    app.on_search_input();
+                                }
+                            }
+                            continue;
+                        }
+
+                        // The rest of a key chord (e.g. Ctrl+K Ctrl+S) goes to the key map
+                        if !app.pending_chord.is_empty() && app.handle_chord_key(key) {
+                            continue;
+                        }
+
+                        // The diff view replaces the editor while it is open
+                        if app.diff_view.is_some() && app.active_panel == ActivePanel::Editor && app.handle_diff_key(key) {
+                            continue;
+                        }
+                        if app.hex_view.is_some() && app.active_panel == ActivePanel::Editor && app.handle_hex_key(key) {
+                            continue;
+                        }
+
+                        // The welcome view takes arrows and Enter; typing starts an untitled buffer
+                        if app.active_panel == ActivePanel::Editor && app.showing_welcome() && app.handle_welcome_key(key) {
+                            continue;
+                        }
+
+                        // Find bar gets first pick of keys while the editor is focused
+                        if app.find_bar.active && app.active_panel == ActivePanel::Editor && app.handle_find_key(key) {
+                            continue;
+                        }
+
+                        // The completion popup takes Up/Down, Enter/Tab and Esc; typing goes on to filter it
+                        if app.active_panel == ActivePanel::Editor && app.handle_completion_key(key) {
+                            continue;
+                        }
+
+                        // Esc clears extra carets, and takes vim insert/visual mode back to normal mode
+                        if key.code == KeyCode::Esc && app.active_panel == ActivePanel::Editor && app.editor_captures_esc() {
+                            app.handle_editor_key(key);
+                            continue;
+                        }
+
+                        // Tab and Shift+Tab indent in the editor instead of switching focus, and the Emacs
+                        // preset's keys (Ctrl+E) win over global ones
+                        let editor_first = matches!(key.code, KeyCode::Tab | KeyCode::BackTab) || app.config.keybinding_mode == KeybindingMode::Emacs;
+                        if editor_first && app.active_panel == ActivePanel::Editor
+                            && app.diff_view.is_none() && app.hex_view.is_none() && app.editor_key_map.contains_key(&(key.code, key.modifiers))
+                        {
+                            app.handle_editor_key(key);
+                            continue;
+                        }
+
+                        // Sent prompts are recalled in the chat input; Ctrl+R searches them there rather than resetting the layout
+                        if app.active_panel == ActivePanel::Chat && app.handle_prompt_history_key(key) {
+                            continue;
+                        }
+
+                        // Check Global Actions
+                        if app.handle_chord_key(key) {
+                            continue;
+                        }
+                        if let Some(&action) = app.key_map.get(&(key.code, key.modifiers)) {
+                            // Copy, cut, completion and problem navigation are for the editor; the shell keeps
+                            // Ctrl+C (SIGINT), Ctrl+X, Ctrl+Space (set mark) and Alt+. (last argument)
+                            let for_shell = app.active_panel == ActivePanel::Terminal
+                                && matches!(action, Action::Copy | Action::Cut | Action::Complete | Action::GotoDefinition | Action::FindReferences | Action::Outline | Action::FormatDocument
+                                    | Action::ToggleInlayHints | Action::NextDiagnostic | Action::PrevDiagnostic);
+                            if !for_shell {
+                                app.handle_action(action);
+                                continue;
+                            }
+                        }
+                        
+                        // Close menu on Esc if not handled by action
+                        if key.code == KeyCode::Esc && app.menu_open_idx.is_some() {
+                            app.menu_open_idx = None;
+                            continue;
+                        }
+                    }
+                    
+                    // File search and settings take the mouse while open
+                    if let Event::Mouse(mouse) = input {
+                        if let Some(view) = app.process_view.as_mut() {
+                            match mouse.kind {
+                                MouseEventKind::ScrollUp => view.select(-3),
+                                MouseEventKind::ScrollDown => view.select(3),
+                                _ => {}
+                            }
+                            continue;
+                        }
+                        if let Some(list) = app.reference_list.as_mut() {
+                            match mouse.kind {
+                                MouseEventKind::ScrollUp => list.select(-3),
+                                MouseEventKind::ScrollDown => list.select(3),
+                                _ => {}
+                            }
+                            continue;
+                        }
+                        if app.preset_picker.is_some() {
+                            continue;
+                        }
+                        if let Some(outline) = app.outline.as_mut() {
+                            match mouse.kind {
+                                MouseEventKind::ScrollUp => outline.select(-3),
+                                MouseEventKind::ScrollDown => outline.select(3),
+                                _ => {}
+                            }
+                            continue;
+                        }
+                        if app.tree_menu.is_some() || app.recent_files.is_some() || app.is_searching || app.show_settings {
+                            if let Ok(size) = terminal.size() {
+                                let screen = Rect::new(0, 0, size.width, size.height);
+                                if app.tree_menu.is_some() {
+                                    app.handle_tree_menu_mouse(mouse, screen);
+                                } else if app.recent_files.is_some() {
+                                    app.handle_recent_files_mouse(mouse, screen);
+                                } else if app.is_searching {
+                                    app.handle_file_search_mouse(mouse, screen);
+                                } else {
+                                    let editor = app_layout(screen, app).editor;
+                                    app.handle_settings_mouse(mouse, editor);
+                                }
+                            }
+                            continue;
+                        }
+                    }
+
+                    // TODO Panel Mouse Handling
+                    if let Event::Mouse(mouse) = input {
+                        if app.show_todos {
+                            match mouse.kind {
+                                MouseEventKind::ScrollUp => app.todos_selected = app.todos_selected.saturating_sub(1),
+                                MouseEventKind::ScrollDown => {
+                                    app.todos_selected = (app.todos_selected + 1).min(app.todos.len().saturating_sub(1));
+                                }
+                                MouseEventKind::Down(MouseButton::Left) => {
+                                    if let Ok(size) = terminal.size() {
+                                        let area = todo_panel_area(Rect::new(0, 0, size.width, size.height));
+                                        let inner_top = area.y + 1;
+                                        if mouse.row >= inner_top && mouse.row < area.y + area.height.saturating_sub(1) {
+                                            let row = app.todos_list_state.offset() + (mouse.row - inner_top) as usize;
+                                            if let Some(Some(idx)) = app.todo_rows().get(row) {
+                                                app.open_todo(*idx);
+                                            }
+                                        } else if !(mouse.column >= area.x && mouse.column < area.x + area.width
+                                            && mouse.row >= area.y && mouse.row < area.y + area.height) {
+                                            app.show_todos = false;
+                                        }
+                                    }
+                                }
+                                _ => {}
+                            }
+                            continue;
+                        }
+                    }
+
+                    // Menu Mouse Handling
+                    if let Event::Mouse(mouse) = input {
+                        // Handle hover when menu is open
+                        if let Some(idx) = app.menu_open_idx {
+                            let menu_x = (idx * 10) as u16;
+                            let menu_items = App::get_menu_items(idx);
+                            let menu_width = 24u16;
+                            let menu_height = menu_items.len() as u16 + 2; // +2 for borders
+
+                            // Check if mouse is within menu dropdown area
+                            if mouse.column >= menu_x
+                                && mouse.column < menu_x + menu_width
+                                && mouse.row >= 1
+                                && mouse.row < 1 + menu_height
+                            {
+                                // Calculate which item is hovered (row 1 is border, items start at row 2)
+                                let item_row = mouse.row.saturating_sub(2);
+                                if (item_row as usize) < menu_items.len() {
+                                    app.menu_hover_idx = Some(item_row as usize);
+                                } else {
+                                    app.menu_hover_idx = None;
+                                }
+                            } else if mouse.row != 0 {
+                                // Mouse outside menu and not on menu bar - could close on move
+                                app.menu_hover_idx = None;
+                            }
+                        }
+
+                        if mouse.kind == MouseEventKind::Down(MouseButton::Left) {
+                            if mouse.row == 0 {
+                                // Click on menu bar
+                                let idx = (mouse.column / 10) as usize;
+                                if idx < app.menu_titles.len() {
+                                    if app.menu_open_idx == Some(idx) {
+                                        // Toggle off if clicking same menu
+                                        app.menu_open_idx = None;
+                                    } else {
+                                        app.menu_open_idx = Some(idx);
+                                    }
+                                    app.menu_hover_idx = None;
+                                } else {
+                                    app.menu_open_idx = None;
+                                    app.menu_hover_idx = None;
+                                }
+                                continue;
+                            } else if let Some(idx) = app.menu_open_idx {
+                                let menu_x = (idx * 10) as u16;
+                                let menu_items = App::get_menu_items(idx);
+                                let menu_width = 24u16;
+
+                                if mouse.column >= menu_x
+                                    && mouse.column < menu_x + menu_width
+                                    && mouse.row >= 2
+                                    && mouse.row < 2 + menu_items.len() as u16
+                                {
+                                    // Click on a menu item
+                                    let item_idx = (mouse.row - 2) as usize;
+                                    if let Some(&(_, action)) = menu_items.get(item_idx) {
+                                        app.handle_menu_action(action);
+                                    }
+                                }
+                                app.menu_open_idx = None;
+                                app.menu_hover_idx = None;
+                                continue;
+                            } else {
+                                // Click outside menu closes it
+                                app.menu_open_idx = None;
+                                app.menu_hover_idx = None;
+                            }
+                        }
+                    }
+
+                    // Global Focus Switching via Mouse
+                    if let Event::Mouse(mouse) = input {
+                        if mouse.kind == MouseEventKind::Down(MouseButton::Left) {
+                             if let Ok(size) = terminal.size() {
+                                 let rect = Rect { x: 0, y: 0, width: size.width, height: size.height };
+                                 let layout = app_layout(rect, app);
+                                 let col = mouse.column;
+                                 let row = mouse.row;
+                                 
+                                 if col >= layout.file_tree.x && col < layout.file_tree.x + layout.file_tree.width &&
+                                    row >= layout.file_tree.y && row < layout.file_tree.y + layout.file_tree.height {
+                                     app.active_panel = ActivePanel::FileTree;
+                                 } else if col >= layout.editor.x && col < layout.editor.x + layout.editor.width &&
+                                    row >= layout.editor.y && row < layout.editor.y + layout.editor.height {
+                                     app.active_panel = ActivePanel::Editor;
+                                     // Alt+Click adds (or removes) a caret, Ctrl+Click goes to the
+                                     // definition, Shift+Click selects
+                                     if mouse.modifiers.contains(KeyModifiers::ALT) && app.diff_view.is_none() && app.hex_view.is_none() {
+                                         app.toggle_editor_cursor_at(layout.editor, col, row);
+                                     } else if mouse.modifiers.contains(KeyModifiers::CONTROL) && app.diff_view.is_none() && app.hex_view.is_none() {
+                                         app.click_editor_at(layout.editor, col, row, false);
+                                         app.request_locations(false);
+                                     } else {
+                                         app.click_editor_at(layout.editor, col, row, mouse.modifiers.contains(KeyModifiers::SHIFT));
+                                     }
+                                 } else if col >= layout.terminal.x && col < layout.terminal.x + layout.terminal.width &&
+                                    row >= layout.terminal.y && row < layout.terminal.y + layout.terminal.height {
+                                     app.active_panel = ActivePanel::Terminal;
+                                 } else if (col >= layout.chat_history.x && col < layout.chat_history.x + layout.chat_history.width &&
+                                            row >= layout.chat_history.y && row < layout.chat_history.y + layout.chat_history.height) ||
+                                           (col >= layout.chat_input.x && col < layout.chat_input.x + layout.chat_input.width &&
+                                            row >= layout.chat_input.y && row < layout.chat_input.y + layout.chat_input.height) {
+                                     app.active_panel = ActivePanel::Chat;
+                                     // The chat header switches the context preset
+                                     if row == layout.chat_history.y {
+                                         app.open_preset_picker();
+                                     }
+                                 }
+                             }
+                        }
+                    }
+
+                    // Right click on a file tree entry opens its context menu
+                    if let Event::Mouse(mouse) = input {
+                        if mouse.kind == MouseEventKind::Down(MouseButton::Right) {
+                            if let Ok(size) = terminal.size() {
+                                let tree = app_layout(Rect::new(0, 0, size.width, size.height), app).file_tree;
+                                if tree.contains(Position::new(mouse.column, mouse.row)) && mouse.row > tree.y && mouse.row + 1 < tree.bottom() {
+                                    let idx = app.file_tree_scroll_offset + (mouse.row - tree.y - 1) as usize;
+                                    app.open_tree_menu(idx, mouse.column, mouse.row + 1);
+                                    continue;
+                                }
+                            }
+                        }
+                    }
+
+                    match input {
+                        Event::Mouse(mouse) => {
+                            match app.active_panel {
+                                // The wheel scrolls back through output; full-screen programs get arrow keys
+                                ActivePanel::Terminal if !app.terminal_in_alternate_screen() => match mouse.kind {
+                                    MouseEventKind::ScrollDown => app.scroll_terminal(-3),
+                                    MouseEventKind::ScrollUp => app.scroll_terminal(3),
+                                    _ => {}
+                                },
+                                ActivePanel::Terminal => {
+                                     let input_bytes = match mouse.kind {
+                                        MouseEventKind::ScrollDown => vec![27, 91, 66], 
+                                        MouseEventKind::ScrollUp => vec![27, 91, 65],   
+                                        _ => vec![],
+                                    };
+                                    if !input_bytes.is_empty() {
+                                        if let Err(e) = app.write_to_pty(&input_bytes) {
+                                            app.notify(e);
+                                        }
+                                    }
+                                },
+                                ActivePanel::FileTree => {
+                                    match mouse.kind {
+                                        MouseEventKind::ScrollDown => {
+                                            let max_scroll = app.visible_items.len().saturating_sub(1);
+                                            app.file_tree_scroll_offset = (app.file_tree_scroll_offset + 3).min(max_scroll);
+                                        },
+                                        MouseEventKind::ScrollUp => {
+                                            app.file_tree_scroll_offset = app.file_tree_scroll_offset.saturating_sub(3);
+                                        },
+                                        _ => {} // Other mouse events
+                                    }
+                                },
+                                ActivePanel::Editor => {
+                                     match mouse.kind {
+                                        MouseEventKind::ScrollDown => match app.hex_view.as_mut() {
+                                            Some(view) => view.scroll_by(3),
+                                            None => app.editor_state.scroll_down(3),
+                                        },
+                                        MouseEventKind::ScrollUp => match app.hex_view.as_mut() {
+                                            Some(view) => view.scroll_by(-3),
+                                            None => app.editor_state.scroll_up(3),
+                                        },
+                                        // Dragging selects
+                                        MouseEventKind::Drag(MouseButton::Left) => {
+                                            if let Ok(size) = terminal.size() {
+                                                let layout = app_layout(Rect::new(0, 0, size.width, size.height), app);
+                                                app.click_editor_at(layout.editor, mouse.column, mouse.row, true);
+                                            }
+                                        }
+                                        _ => {} // Other mouse events
+                                    }
+                                },
+                                ActivePanel::Chat => {
+                                    match mouse.kind {
+                                        MouseEventKind::ScrollDown => {
+                                            app.chat_scroll = app.chat_scroll.saturating_add(3);
+                                        },
+                                        MouseEventKind::ScrollUp => {
+                                            app.chat_scroll = app.chat_scroll.saturating_sub(3);
+                                        },
+                                        _ => {} // Other mouse events
+                                    }
+                                }
+                            }
+                        },
+                        Event::Key(key) => {
+                            // Only process panel specific keys if NOT a global action (handled above)
+                            // But wait, we need to pass input to terminal for Ctrl+C etc if it was NOT a global action map.
+                            // Currently key_map has Ctrl+Q. Ctrl+C is NOT in map, so it falls through here.
+                            // This is correct.
+                            
+                            // Check if menu is open, Esc handled in global key map (ToggleMenu)?
+                            // We added Esc -> ToggleMenu.
+                            // If menu is open, any key might close it?
+                            if app.menu_open_idx.is_some() {
+                                app.menu_open_idx = None;
+                                // Don't consume key? Or consume? Usually consume.
+                                // But if it was "Esc", action handled it.
+                                // If it was "Enter", we might want to select menu item?
+                                // Simplified: clicking only for now.
+                                continue; // Close menu and don't process key further
+                            }
+
+                            match app.active_panel {
+                                    ActivePanel::Editor => {
+                                        app.handle_editor_key(key);
+                                    }
+                                    ActivePanel::Chat => {
+                                        match key.code {
+                                            // Not every terminal reports Shift+Enter, so Alt+Enter works too
+                                            KeyCode::Enter if key.modifiers.intersects(KeyModifiers::SHIFT | KeyModifiers::ALT) => {
+                                                app.chat_input.insert_newline();
+                                            }
+                                            KeyCode::Enter => app.submit_chat_input(),
+                                            KeyCode::Char('o') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                                app.toggle_chat_fold();
+                                            }
+                                            // Up/Down move between input lines before scrolling the history
+                                            KeyCode::Up if app.chat_input.cursor().0 > 0 => {
+                                                app.chat_input.input(key);
+                                            }
+                                            KeyCode::Down if app.chat_input.cursor().0 + 1 < app.chat_input.lines().len() => {
+                                                app.chat_input.input(key);
+                                            }
+                                            KeyCode::Up => {
+                                                app.chat_scroll = app.chat_scroll.saturating_sub(1);
+                                            }
+                                            KeyCode::Down => {
+                                                app.chat_scroll = app.chat_scroll.saturating_add(1);
+                                            }
+                                            KeyCode::PageUp => {
+                                                app.chat_scroll = app.chat_scroll.saturating_sub(10);
+                                            }
+                                            KeyCode::PageDown => {
+                                                app.chat_scroll = app.chat_scroll.saturating_add(10);
+                                            }
+                                            KeyCode::Home => {
+                                                app.chat_scroll = 0;
+                                            }
+                                            KeyCode::End => {
+                                                app.chat_scroll = u16::MAX; // Will be clamped in render
+                                            }
+                                            _ => {
+                                                app.chat_input.input(key);
+                                            }
+                                        }
+                                    }
+                                    ActivePanel::FileTree => {
+                                        match key.code {
+                                            KeyCode::Up if app.selected_file_idx > 0 => {
+                                                app.selected_file_idx -= 1;
+                                                app.file_tree_state.select(Some(app.selected_file_idx));
+                                                app.load_selected_file();
+                                            }
+                                            KeyCode::Down if app.selected_file_idx < app.visible_items.len().saturating_sub(1) => {
+                                                app.selected_file_idx += 1;
+                                                app.file_tree_state.select(Some(app.selected_file_idx));
+                                                app.load_selected_file();
+                                            }
+                                            KeyCode::PageUp => {
+                                                let jump = 10;
+                                                app.selected_file_idx = app.selected_file_idx.saturating_sub(jump);
+                                                app.file_tree_state.select(Some(app.selected_file_idx));
+                                            }
+                                            KeyCode::PageDown => {
+                                                let jump = 10;
+                                                let max_idx = app.visible_items.len().saturating_sub(1);
+                                                app.selected_file_idx = (app.selected_file_idx + jump).min(max_idx);
+                                                app.file_tree_state.select(Some(app.selected_file_idx));
+                                            }
+                                            KeyCode::Right => {
+                                                if let Some(item) = app.visible_items.get(app.selected_file_idx) {
+                                                    if item.is_dir && !item.expanded {
+                                                        app.toggle_selected_dir();
+                                                    }
+                                                }
+                                            }
+                                            KeyCode::Left => {
+                                                if let Some(item) = app.visible_items.get(app.selected_file_idx) {
+                                                    if item.is_dir && item.expanded {
+                                                        app.toggle_selected_dir();
+                                                    }
+                                                }
+                                            }
+                                            KeyCode::F(10) if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                                                if let Ok(size) = terminal.size() {
+                                                    let tree = app_layout(Rect::new(0, 0, size.width, size.height), app).file_tree;
+                                                    let row = tree.y + 1 + app.selected_file_idx.saturating_sub(app.file_tree_scroll_offset) as u16;
+                                                    app.open_tree_menu(app.selected_file_idx, tree.x + 2, row + 1);
+                                                }
+                                            }
+                                            KeyCode::Enter => {
+                                                if let Some(item) = app.visible_items.get(app.selected_file_idx) {
+                                                    if item.is_dir {
+                                                        app.toggle_selected_dir();
+                                                    } else {
+                                                        app.load_selected_file();
+                                                        app.active_panel = ActivePanel::Editor;
+                                                    }
+                                                }
+                                            }
+                                            _ => {} // Ignore other key presses for now
+                                        }
+                                    }
+                                    // The watch tab only shows output
+                                    ActivePanel::Terminal if app.terminal_tab == TerminalTab::Watch => {}
+                                    ActivePanel::Terminal if key.modifiers == KeyModifiers::SHIFT && matches!(key.code, KeyCode::PageUp | KeyCode::PageDown) => {
+                                        let page = app.terminal_page();
+                                        app.scroll_terminal(if key.code == KeyCode::PageUp { page } else { -page });
+                                    }
+                                    ActivePanel::Terminal => {
+                                        let input_bytes = match key.code {
+                                            KeyCode::Char('v') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                                app.handle_action(Action::Paste);
+                                                vec![] // Don't send ^V to PTY
+                                            },
+                                            KeyCode::Char(c) => {
+                                                if key.modifiers.contains(KeyModifiers::CONTROL) {
+                                                    match c {
+                                                        'c' => vec![3],
+                                                        'd' => vec![4],
+                                                        'z' => vec![26],
+                                                        c => vec![(c as u8) & 0x1f],
+                                                    }
+                                                } else {
+                                                     let mut b = [0; 4];
+                                                     c.encode_utf8(&mut b).as_bytes().to_vec()
+                                                }
+                                            },
+                                            KeyCode::Enter => vec![13],
+                                            KeyCode::Backspace => vec![8],
+                                            KeyCode::Left => vec![27, 91, 68],
+                                            KeyCode::Right => vec![27, 91, 67],
+                                            KeyCode::Up => vec![27, 91, 65],
+                                            KeyCode::Down => vec![27, 91, 66],
+                                            KeyCode::PageUp => vec![27, 91, 53, 126], // ESC [5~
+                                            KeyCode::PageDown => vec![27, 91, 54, 126], // ESC [6~
+                                            KeyCode::Home => vec![27, 91, 72], // ESC [H
+                                            KeyCode::End => vec![27, 91, 70], // ESC [F
+                                            KeyCode::Esc => vec![27],
+                                            _ => vec![],
+                                        };
+
+                                        if !input_bytes.is_empty() {
+                                            // Typing returns to the live screen
+                                            app.scroll_terminal(isize::MIN);
+                                            if let Err(e) = app.write_to_pty(&input_bytes) {
+                                                app.notify(e);
+                                            }
+                                        }
+                                    }
+                                }
+                        }
+                        _ => {} // Ignore other event types for now
+                    }
+                }
+            }
+        }
+    }
+}
\ No newline at end of file
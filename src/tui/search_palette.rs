@@ -0,0 +1,112 @@
+// "Search everything" palette: one query over file names, file contents, chat
+// and terminal history. Chat and terminal hits are found as the query is typed;
+// workspace hits arrive from a background search and are merged in. The same
+// palette searches only the open buffers, unsaved edits included.
+
+use ratatui::widgets::{Block, Borders, ListState};
+use tui_textarea::TextArea;
+
+use crate::shared::global_search::{self, SearchHit};
+
+/// Results listed at once
+pub const MAX_RESULTS: usize = 50;
+
+pub struct SearchPalette<'a> {
+    pub input: TextArea<'a>,
+    pub results: Vec<SearchHit>,
+    pub state: ListState,
+    /// Chat and terminal hits for the current query
+    local: Vec<SearchHit>,
+    /// File name and content hits for the current query
+    workspace: Vec<SearchHit>,
+    /// Identifies the latest workspace search so superseded ones are ignored
+    pub generation: u64,
+    /// Searching the open buffers rather than everything
+    pub buffers_only: bool,
+}
+
+impl Default for SearchPalette<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SearchPalette<'_> {
+    pub fn new() -> Self {
+        let mut input = TextArea::default();
+        input.set_block(Block::default().borders(Borders::ALL).title(" Search Everything "));
+        Self {
+            input,
+            results: Vec::new(),
+            state: ListState::default(),
+            local: Vec::new(),
+            workspace: Vec::new(),
+            generation: 0,
+            buffers_only: false,
+        }
+    }
+
+    /// A palette over the lines of the open buffers only
+    pub fn buffers() -> Self {
+        let mut palette = Self::new();
+        palette.input.set_block(Block::default().borders(Borders::ALL).title(" Search Open Buffers "));
+        palette.buffers_only = true;
+        palette
+    }
+
+    pub fn query(&self) -> String {
+        self.input.lines().join(" ")
+    }
+
+    /// Start over for a new query with its chat and terminal hits; returns the
+    /// generation the workspace results should come back with
+    pub fn set_query_hits(&mut self, local: Vec<SearchHit>) -> u64 {
+        self.generation += 1;
+        self.local = local;
+        self.workspace.clear();
+        self.merge();
+        self.generation
+    }
+
+    /// Workspace results of search `generation`; dropped when the query changed since
+    pub fn set_workspace_hits(&mut self, generation: u64, hits: Vec<SearchHit>) {
+        if generation == self.generation {
+            self.workspace = hits;
+            self.merge();
+        }
+    }
+
+    fn merge(&mut self) {
+        self.results = global_search::rank([self.workspace.clone(), self.local.clone()].concat(), MAX_RESULTS);
+        self.state.select(if self.results.is_empty() { None } else { Some(0) });
+    }
+
+    pub fn select(&mut self, delta: isize) {
+        let last = self.results.len().saturating_sub(1);
+        let current = self.state.selected().unwrap_or(0);
+        self.state.select(Some(current.saturating_add_signed(delta).min(last)));
+    }
+
+    pub fn selected(&self) -> Option<&SearchHit> {
+        self.state.selected().and_then(|i| self.results.get(i))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::global_search::search_terminal;
+
+    #[test]
+    fn test_stale_workspace_hits_are_dropped() {
+        let mut palette = SearchPalette::new();
+        let first = palette.set_query_hits(Vec::new());
+        let second = palette.set_query_hits(search_terminal(&["cargo test".to_string()], "test"));
+        palette.set_workspace_hits(first, search_terminal(&["old test".to_string()], "test"));
+        assert_eq!(palette.results.len(), 1);
+        palette.set_workspace_hits(second, search_terminal(&["a test".to_string(), "b test".to_string()], "test"));
+        assert_eq!(palette.results.len(), 3);
+        palette.select(5);
+        assert_eq!(palette.selected().map(|h| h.text.as_str()), Some("cargo test"));
+    }
+}
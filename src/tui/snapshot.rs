@@ -0,0 +1,140 @@
+// Snapshot of the whole TUI screen as plain text or ANSI-colored text, for bug
+// reports and sharing layouts. The chat panel can be blanked out first.
+
+use ratatui::buffer::Buffer;
+use ratatui::layout::{Margin, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use unicode_width::UnicodeWidthStr;
+
+/// Shown in place of redacted content
+const REDACTED: &str = "[redacted]";
+
+/// Blank out everything inside the border of `area`
+pub fn redact(buffer: &mut Buffer, area: Rect) {
+    let inner = area.inner(Margin::new(1, 1)).intersection(buffer.area);
+    if inner.is_empty() {
+        return;
+    }
+    let style = buffer.cell((inner.x, inner.y)).map(|cell| Style::default().bg(cell.bg)).unwrap_or_default();
+    for y in inner.top()..inner.bottom() {
+        buffer.set_string(inner.x, y, " ".repeat(inner.width as usize), style);
+    }
+    buffer.set_stringn(inner.x, inner.y, REDACTED, inner.width as usize, style.add_modifier(Modifier::DIM));
+}
+
+/// Symbols of each row, skipping the cells covered by wide characters
+fn rows(buffer: &Buffer) -> impl Iterator<Item = Vec<&ratatui::buffer::Cell>> {
+    let area = buffer.area;
+    (area.top()..area.bottom()).map(move |y| {
+        let mut cells = Vec::new();
+        let mut covered = 0;
+        for x in area.left()..area.right() {
+            let Some(cell) = buffer.cell((x, y)) else { continue };
+            if covered > 0 {
+                covered -= 1;
+                continue;
+            }
+            if cell.skip {
+                continue;
+            }
+            covered = cell.symbol().width().saturating_sub(1);
+            cells.push(cell);
+        }
+        cells
+    })
+}
+
+/// The screen as plain text, without trailing spaces
+pub fn to_plain(buffer: &Buffer) -> String {
+    rows(buffer)
+        .map(|cells| cells.iter().map(|cell| cell.symbol()).collect::<String>().trim_end().to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The screen with its colors and text attributes as ANSI escape sequences
+pub fn to_ansi(buffer: &Buffer) -> String {
+    let mut out = String::new();
+    for cells in rows(buffer) {
+        let mut current = None;
+        for cell in cells {
+            let style = (cell.fg, cell.bg, cell.modifier);
+            if current != Some(style) {
+                out.push_str(&sgr(cell.fg, cell.bg, cell.modifier));
+                current = Some(style);
+            }
+            out.push_str(cell.symbol());
+        }
+        out.push_str("\x1b[0m\n");
+    }
+    out
+}
+
+/// Escape sequence that resets the attributes and sets these
+fn sgr(fg: Color, bg: Color, modifier: Modifier) -> String {
+    let mut codes = vec!["0".to_string()];
+    const MODIFIERS: [(Modifier, &str); 9] = [
+        (Modifier::BOLD, "1"),
+        (Modifier::DIM, "2"),
+        (Modifier::ITALIC, "3"),
+        (Modifier::UNDERLINED, "4"),
+        (Modifier::SLOW_BLINK, "5"),
+        (Modifier::RAPID_BLINK, "6"),
+        (Modifier::REVERSED, "7"),
+        (Modifier::HIDDEN, "8"),
+        (Modifier::CROSSED_OUT, "9"),
+    ];
+    codes.extend(MODIFIERS.iter().filter(|(m, _)| modifier.contains(*m)).map(|(_, code)| code.to_string()));
+    codes.extend(color_code(fg, false));
+    codes.extend(color_code(bg, true));
+    format!("\x1b[{}m", codes.join(";"))
+}
+
+/// SGR parameter for a foreground or background color; none for the default
+fn color_code(color: Color, background: bool) -> Option<String> {
+    let base = if background { 40 } else { 30 };
+    let extended = if background { 48 } else { 38 };
+    let named = |offset: u8| Some((base + offset).to_string());
+    let bright = |offset: u8| Some((base + 60 + offset).to_string());
+    match color {
+        Color::Reset => None,
+        Color::Black => named(0),
+        Color::Red => named(1),
+        Color::Green => named(2),
+        Color::Yellow => named(3),
+        Color::Blue => named(4),
+        Color::Magenta => named(5),
+        Color::Cyan => named(6),
+        Color::Gray => named(7),
+        Color::DarkGray => bright(0),
+        Color::LightRed => bright(1),
+        Color::LightGreen => bright(2),
+        Color::LightYellow => bright(3),
+        Color::LightBlue => bright(4),
+        Color::LightMagenta => bright(5),
+        Color::LightCyan => bright(6),
+        Color::White => bright(7),
+        Color::Indexed(n) => Some(format!("{};5;{}", extended, n)),
+        Color::Rgb(r, g, b) => Some(format!("{};2;{};{};{}", extended, r, g, b)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_and_redact() {
+        let mut buffer = Buffer::empty(Rect::new(0, 0, 16, 4));
+        buffer.set_string(0, 0, "ed 中文", Style::default().fg(Color::Indexed(39)));
+        buffer.set_string(4, 2, "secret chat", Style::default().add_modifier(Modifier::BOLD));
+        assert_eq!(to_plain(&buffer), "ed 中文\n\n    secret chat\n");
+
+        let ansi = to_ansi(&buffer);
+        assert!(ansi.starts_with("\x1b[0;38;5;39med 中文\x1b[0m"));
+        assert!(ansi.contains("\x1b[0;1msecret chat"));
+
+        redact(&mut buffer, Rect::new(2, 1, 14, 3));
+        assert_eq!(to_plain(&buffer), "ed 中文\n\n   [redacted]\n");
+    }
+}
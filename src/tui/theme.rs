@@ -14,6 +14,7 @@ pub struct Theme {
     pub status_bar_bg: Color,
     pub status_bar_fg: Color,
     pub line_number: Color,
+    pub indent_guide: Color,
     pub cursor_bg: Color,
     pub cursor_fg: Color,
     pub directory: Color,
@@ -40,6 +41,7 @@ impl Theme {
             status_bar_bg: Color::Indexed(235),
             status_bar_fg: Color::Indexed(250),
             line_number: Color::Indexed(240),
+            indent_guide: Color::Indexed(237),
             cursor_bg: Color::Indexed(252),
             cursor_fg: Color::Indexed(235),
             directory: Color::Indexed(39),
@@ -59,6 +61,7 @@ impl Theme {
             status_bar_bg: Color::Indexed(253),
             status_bar_fg: Color::Indexed(233),
             line_number: Color::Indexed(244),
+            indent_guide: Color::Indexed(252),
             cursor_bg: Color::Indexed(233),
             cursor_fg: Color::Indexed(255),
             directory: Color::Indexed(33),
@@ -0,0 +1,79 @@
+// Context menu of a file tree entry, opened with a right click or Shift+F10:
+// actions on the entry such as excluding a directory from the workspace
+
+use ratatui::layout::{Position, Rect};
+
+use super::action::Action;
+use crate::shared::VisibleItem;
+
+pub struct TreeMenu {
+    /// Labels and the actions they run on the selected tree entry
+    pub items: Vec<(&'static str, Action)>,
+    pub selected: usize,
+    /// Where the menu was opened; it opens down and to the right of it
+    pub column: u16,
+    pub row: u16,
+}
+
+impl TreeMenu {
+    pub fn new(item: &VisibleItem, show_excluded: bool, column: u16, row: u16) -> Self {
+        let mut items = Vec::new();
+        if item.is_dir {
+            items.push((if item.excluded { "Include in Workspace" } else { "Exclude from Workspace" }, Action::ToggleExcluded));
+        }
+        items.push((if show_excluded { "Hide Excluded Dirs" } else { "Show Excluded Dirs" }, Action::ShowExcluded));
+        Self { items, selected: 0, column, row }
+    }
+
+    /// Move the selection, wrapping around the items
+    pub fn select(&mut self, delta: isize) {
+        self.selected = (self.selected as isize + delta).rem_euclid(self.items.len() as isize) as usize;
+    }
+
+    pub fn selected_action(&self) -> Option<Action> {
+        self.items.get(self.selected).map(|&(_, action)| action)
+    }
+
+    /// The menu's box, moved left or up where it would leave the screen
+    pub fn area(&self, screen: Rect) -> Rect {
+        let width = (self.items.iter().map(|(label, _)| label.len()).max().unwrap_or(0) as u16 + 4).min(screen.width);
+        let height = (self.items.len() as u16 + 2).min(screen.height);
+        let x = self.column.min(screen.right().saturating_sub(width));
+        let y = self.row.min(screen.bottom().saturating_sub(height));
+        Rect::new(x, y, width, height)
+    }
+
+    /// The item at a screen position
+    pub fn item_at(&self, screen: Rect, column: u16, row: u16) -> Option<usize> {
+        let area = self.area(screen);
+        let inner = Rect::new(area.x + 1, area.y + 1, area.width.saturating_sub(2), area.height.saturating_sub(2));
+        inner.contains(Position::new(column, row)).then(|| (row - inner.y) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_tree_menu() {
+        let item = VisibleItem {
+            name: "build".to_string(),
+            path: PathBuf::from("build"),
+            is_dir: true,
+            depth: 0,
+            expanded: false,
+            excluded: true,
+        };
+        let mut menu = TreeMenu::new(&item, true, 78, 3);
+        assert_eq!(menu.items[0], ("Include in Workspace", Action::ToggleExcluded));
+        menu.select(-1);
+        assert_eq!(menu.selected_action(), Some(Action::ShowExcluded));
+
+        let screen = Rect::new(0, 0, 80, 24);
+        assert_eq!(menu.area(screen), Rect::new(56, 3, 24, 4));
+        assert_eq!(menu.item_at(screen, 60, 5), Some(1));
+        assert_eq!(menu.item_at(screen, 60, 3), None);
+    }
+}
@@ -1,14 +1,27 @@
 use ratatui::{
+    buffer::Buffer,
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Modifier, Style},
+    style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Scrollbar, ScrollbarOrientation, Wrap},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Scrollbar, ScrollbarOrientation, Wrap},
     Frame,
 };
+use std::collections::HashSet;
+use std::path::Path;
 use tui_term::widget::PseudoTerminal;
 
-use super::app::{App, ActivePanel};
+use super::app::{severity_color, App, ActivePanel, ConnectionTest, TerminalTab, UnsavedPrompt};
+use super::completion::VISIBLE_ITEMS;
+use super::action::Action;
 use super::editor::EditorWidget;
+use super::project_setup::SetupItem;
+use super::welcome::WelcomeItem;
+use crate::shared::capabilities::{self, Capabilities, ColorLevel};
+use crate::shared::chat::{self, ChatMessage};
+use crate::shared::color::ColorPicker;
+use crate::shared::diff::DiffKind;
+use crate::shared::lsp::CompletionItem;
+use crate::shared::watch::WatchStatus;
 use super::theme::Theme;
 
 pub struct AppLayout {
@@ -20,7 +33,66 @@ pub struct AppLayout {
     pub chat_input: Rect,
 }
 
-pub fn get_layout_chunks(area: Rect, active_panel: &ActivePanel) -> AppLayout {
+/// Smallest window the panels are laid out in; below it only a message is shown
+pub const MIN_WIDTH: u16 = 40;
+pub const MIN_HEIGHT: u16 = 10;
+
+/// Narrowest the file tree and chat get before they are hidden
+const MIN_TREE_WIDTH: u16 = 12;
+const MIN_CHAT_WIDTH: u16 = 18;
+/// Room the editor and terminal column keeps, hiding the chat and then the tree
+const MIN_MIDDLE_WIDTH: u16 = 30;
+/// Lowest the editor and terminal get before the unfocused one is hidden
+const MIN_PANEL_HEIGHT: u16 = 5;
+
+/// Whether `area` is below `MIN_WIDTH` x `MIN_HEIGHT`
+pub fn too_small(area: Rect) -> bool {
+    area.width < MIN_WIDTH || area.height < MIN_HEIGHT
+}
+
+/// Widths of the file tree, middle and chat columns; 0 for a hidden one.
+/// The focused panel is never hidden.
+fn column_widths(width: u16, active_panel: &ActivePanel) -> (u16, u16, u16) {
+    // Chat panel expands to 35% when focused, otherwise 20%
+    let chat_percent = if *active_panel == ActivePanel::Chat { 35 } else { 20 };
+    let share = |percent: u32| (width as u32 * percent / 100) as u16;
+    let (keep_tree, keep_chat) = (*active_panel == ActivePanel::FileTree, *active_panel == ActivePanel::Chat);
+    let mut tree = share(20);
+    let mut chat = share(chat_percent);
+    if tree < MIN_TREE_WIDTH && !keep_tree {
+        tree = 0;
+    }
+    if chat < MIN_CHAT_WIDTH && !keep_chat {
+        chat = 0;
+    }
+    let middle_fits = |tree: u16, chat: u16| width.saturating_sub(tree + chat) >= MIN_MIDDLE_WIDTH;
+    if !middle_fits(tree, chat) && !keep_chat {
+        chat = 0;
+    }
+    if !middle_fits(tree, chat) && !keep_tree {
+        tree = 0;
+    }
+    let tree = if keep_tree { tree.max(MIN_TREE_WIDTH).min(width) } else { tree };
+    let chat = if keep_chat { chat.max(MIN_CHAT_WIDTH).min(width - tree) } else { chat };
+    (tree, width - tree - chat, chat)
+}
+
+/// `chat_input_lines` lets the chat input grow with a multi-line prompt.
+/// On small windows the chat and file tree are hidden (left empty) to keep
+/// the editor usable; below the minimum window size every panel is.
+pub fn get_layout_chunks(area: Rect, active_panel: &ActivePanel, chat_input_lines: usize) -> AppLayout {
+    let hidden = Rect::default();
+    if too_small(area) {
+        return AppLayout {
+            menu: hidden,
+            file_tree: hidden,
+            editor: hidden,
+            terminal: hidden,
+            chat_history: hidden,
+            chat_input: hidden,
+        };
+    }
+
     let main_chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
@@ -31,23 +103,17 @@ pub fn get_layout_chunks(area: Rect, active_panel: &ActivePanel) -> AppLayout {
 
     let menu = main_chunks[0];
 
-    // Chat panel expands to 35% when focused, otherwise 20%
-    let (file_tree_percent, middle_percent, chat_percent) = if *active_panel == ActivePanel::Chat {
-        (20, 45, 35)
-    } else {
-        (20, 60, 20)
-    };
-
+    let (tree_width, middle_width, chat_width) = column_widths(area.width, active_panel);
     let chunks = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([
-            Constraint::Percentage(file_tree_percent),
-            Constraint::Percentage(middle_percent),
-            Constraint::Percentage(chat_percent),
+            Constraint::Length(tree_width),
+            Constraint::Length(middle_width),
+            Constraint::Length(chat_width),
         ])
         .split(main_chunks[1]);
 
-    let file_tree = chunks[0];
+    let file_tree = if tree_width > 0 { chunks[0] } else { hidden };
 
     let (editor_percent, terminal_percent) = if *active_panel == ActivePanel::Terminal {
         (40, 60)
@@ -59,13 +125,27 @@ pub fn get_layout_chunks(area: Rect, active_panel: &ActivePanel) -> AppLayout {
         .direction(Direction::Vertical)
         .constraints([Constraint::Percentage(editor_percent), Constraint::Percentage(terminal_percent)])
         .split(chunks[1]);
-        
-    let editor = middle_chunks[0];
-    let terminal = middle_chunks[1];
 
+    let (mut editor, mut terminal) = (middle_chunks[0], middle_chunks[1]);
+    if editor.height < MIN_PANEL_HEIGHT || terminal.height < MIN_PANEL_HEIGHT {
+        if *active_panel == ActivePanel::Terminal {
+            (editor, terminal) = (hidden, chunks[1]);
+        } else {
+            (editor, terminal) = (chunks[1], hidden);
+        }
+    }
+
+    if chat_width == 0 {
+        return AppLayout { menu, file_tree, editor, terminal, chat_history: hidden, chat_input: hidden };
+    }
+
+    // At least a fifth of the column, growing up to half of it
+    let chat_column = chunks[2].height;
+    let chat_input_height = (chat_input_lines.min(u16::MAX as usize) as u16).saturating_add(2)
+        .clamp(chat_column / 5, chat_column / 2);
     let chat_chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Percentage(80), Constraint::Percentage(20)])
+        .constraints([Constraint::Min(0), Constraint::Length(chat_input_height)])
         .split(chunks[2]);
         
     let chat_history = chat_chunks[0];
@@ -81,8 +161,44 @@ pub fn get_layout_chunks(area: Rect, active_panel: &ActivePanel) -> AppLayout {
     }
 }
 
+/// Widest the editor gets in zen mode, borders included
+const ZEN_WIDTH: u16 = 90;
+
+/// Zen mode: only a centered, width-limited editor; every other panel is empty
+pub fn zen_layout(area: Rect) -> AppLayout {
+    let width = area.width.min(ZEN_WIDTH);
+    let editor = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + area.height.min(1),
+        width,
+        height: area.height.saturating_sub(2),
+    };
+    let hidden = Rect::default();
+    AppLayout {
+        menu: hidden,
+        file_tree: hidden,
+        editor,
+        terminal: hidden,
+        chat_history: hidden,
+        chat_input: hidden,
+    }
+}
+
+/// The panel layout for the app's current state
+pub fn app_layout(area: Rect, app: &App) -> AppLayout {
+    if app.zen_mode && !too_small(area) {
+        zen_layout(area)
+    } else {
+        get_layout_chunks(area, &app.active_panel, app.chat_input.lines().len())
+    }
+}
+
 pub fn ui(f: &mut Frame, app: &mut App) {
-    let layout = get_layout_chunks(f.area(), &app.active_panel);
+    if too_small(f.area()) {
+        render_too_small(f, app);
+        return;
+    }
+    let layout = app_layout(f.area(), app);
 
     // Apply main background color
     f.render_widget(Block::default().style(Style::default().bg(app.current_theme.background)), f.area());
@@ -91,8 +207,7 @@ pub fn ui(f: &mut Frame, app: &mut App) {
     // --- Menu Bar ---
     let menu_bar_area = layout.menu;
     let menu_titles_count = app.menu_titles.len();
-    let menu_constraints = std::iter::repeat(Constraint::Length(10))
-        .take(menu_titles_count)
+    let menu_constraints = std::iter::repeat_n(Constraint::Length(10), menu_titles_count)
         .chain(std::iter::once(Constraint::Min(0)))
         .collect::<Vec<_>>();
     
@@ -110,6 +225,48 @@ pub fn ui(f: &mut Frame, app: &mut App) {
         f.render_widget(Paragraph::new(title.as_str()).style(style), menu_chunks[i]);
     }
 
+    // Status area (right side of the menu bar)
+    let mut status_spans = Vec::new();
+    if let Some(label) = app.pending_chord_label() {
+        status_spans.push(Span::styled(format!("[{}] ", label), Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)));
+    }
+    if let Some((message, _)) = &app.status_message {
+        status_spans.push(Span::styled(format!("{} ", message), Style::default().fg(app.current_theme.foreground)));
+    }
+    if app.editor_state.file_path.is_some() && !app.editor_state.loading {
+        let state = &app.editor_state;
+        let mut file_format = format!("[{} {}] ", state.encoding.label(), state.line_ending.label());
+        if let Some(preview) = state.preview {
+            file_format = format!("[preview {}%] {}", preview.percent(), file_format);
+        }
+        status_spans.push(Span::styled(file_format, Style::default().fg(app.current_theme.border)));
+    }
+    if let Some(label) = app.toolchain.label() {
+        status_spans.push(Span::styled(format!("[{}] ", label), Style::default().fg(app.current_theme.border)));
+    }
+    if let Some(label) = app.autosave_label() {
+        status_spans.push(Span::styled(format!("[{}] ", label), Style::default().fg(app.current_theme.border)));
+    }
+    if let Some(server) = &app.mirror {
+        let viewers = server.viewer_count();
+        let label = format!("[sharing: {} viewer{}] ", viewers, if viewers == 1 { "" } else { "s" });
+        status_spans.push(Span::styled(label, Style::default().fg(Color::Magenta)));
+    }
+    if app.watch_enabled {
+        let color = match app.watch_status {
+            WatchStatus::Passed => Color::Green,
+            WatchStatus::Failed => Color::Red,
+            _ => Color::Yellow,
+        };
+        status_spans.push(Span::styled(format!("[watch {}] ", app.watch_status.label()), Style::default().fg(color)));
+    }
+    if !status_spans.is_empty() {
+        f.render_widget(
+            Paragraph::new(Line::from(status_spans)).alignment(ratatui::layout::Alignment::Right),
+            menu_chunks[menu_titles_count],
+        );
+    }
+
     // File Tree
     let height = layout.file_tree.height as usize;
     if app.selected_file_idx < app.file_tree_scroll_offset {
@@ -126,6 +283,8 @@ pub fn ui(f: &mut Frame, app: &mut App) {
             let actual_idx = app.file_tree_scroll_offset + i;
             let style = if actual_idx == app.selected_file_idx {
                 Style::default().bg(app.current_theme.selection_bg).fg(app.current_theme.selection_fg)
+            } else if item.excluded {
+                Style::default().fg(app.current_theme.line_number).add_modifier(Modifier::DIM)
             } else {
                 Style::default().fg(if item.is_dir { app.current_theme.directory } else { app.current_theme.file })
             };
@@ -137,13 +296,19 @@ pub fn ui(f: &mut Frame, app: &mut App) {
             };
             
             let indent = "  ".repeat(item.depth);
-            let content = format!("{}{}{}", indent, prefix, item.name);
+            let suffix = if item.excluded { " (excluded)" } else { "" };
+            let content = format!("{}{}{}{}", indent, prefix, item.name, suffix);
             
             ListItem::new(content).style(style)
         }).collect();
     
+    let file_tree_title = if app.file_tree_root == Path::new(".") {
+        " File Tree ".to_string()
+    } else {
+        format!(" File Tree - {} ", app.file_tree_root.display())
+    };
     let file_tree_block = Block::default()
-        .title(" File Tree ")
+        .title(file_tree_title)
         .borders(Borders::ALL)
         .border_style(if app.active_panel == ActivePanel::FileTree { Style::default().fg(app.current_theme.border_active) } else { Style::default().fg(app.current_theme.border) });
     
@@ -163,27 +328,60 @@ pub fn ui(f: &mut Frame, app: &mut App) {
     // Editor (or Settings when show_settings is true)
     if app.show_settings {
         render_settings_panel(f, app, layout.editor);
+    } else if app.diff_view.is_some() {
+        render_diff_view(f, app, layout.editor);
+    } else if app.hex_view.is_some() {
+        render_hex_view(f, app, layout.editor);
+    } else if app.showing_welcome() {
+        render_welcome(f, app, layout.editor);
     } else {
-        let editor_title = app.editor_state.file_path
-            .as_ref()
-            .and_then(|p| p.file_name())
-            .map(|n| format!(" Editor - {} ", n.to_string_lossy()))
-            .unwrap_or_else(|| " Editor ".to_string());
+        let editor_title = editor_title(app);
+
+        let mut editor_block = Block::default()
+            .borders(if app.zen_mode { Borders::NONE } else { Borders::ALL })
+            .title(editor_title)
+            .border_style(if app.active_panel == ActivePanel::Editor {
+                Style::default().fg(app.current_theme.border_active)
+            } else {
+                Style::default().fg(app.current_theme.border)
+            });
+        if let Some(mode) = app.editor_mode_label() {
+            editor_block = editor_block.title(Line::from(format!(" {} ", mode)).right_aligned());
+        }
+        // The menu bar (and its status area) is hidden in zen mode
+        if let Some((message, _)) = app.status_message.as_ref().filter(|_| app.zen_mode) {
+            editor_block = editor_block.title_bottom(Line::from(format!(" {} ", message)).right_aligned());
+        }
 
         let editor_widget = EditorWidget::new()
-            .block(Block::default()
-                .borders(Borders::ALL)
-                .title(editor_title)
-                .border_style(if app.active_panel == ActivePanel::Editor {
-                    Style::default().fg(app.current_theme.border_active)
-                } else {
-                    Style::default().fg(app.current_theme.border)
-                }))
+            .block(editor_block)
             .line_number_style(Style::default().fg(app.current_theme.line_number))
             .cursor_style(Style::default().bg(app.current_theme.cursor_bg).fg(app.current_theme.cursor_fg))
-            .focused(app.active_panel == ActivePanel::Editor);
+            .focused(app.active_panel == ActivePanel::Editor)
+            .line_numbers(!app.zen_mode)
+            .indent_guides(app.config.indent_guides)
+            .indent_guide_style(Style::default().fg(app.current_theme.indent_guide))
+            .inlay_hint_style(Style::default().fg(app.current_theme.line_number).add_modifier(Modifier::ITALIC))
+            .annotations(app.editor_annotations())
+            .gutter_markers(app.gutter_markers())
+            .highlights(app.editor_highlights());
+
+        // Leave room for the find bar at the bottom of the editor
+        let (editor_area, find_area) = if app.find_bar.active && layout.editor.height > 6 {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(3), Constraint::Length(3)])
+                .split(layout.editor);
+            (chunks[0], Some(chunks[1]))
+        } else {
+            (layout.editor, None)
+        };
+
+        f.render_stateful_widget(editor_widget, editor_area, &mut app.editor_state);
 
-        f.render_stateful_widget(editor_widget, layout.editor, &mut app.editor_state);
+        if let Some(area) = find_area {
+            render_find_bar(f, app, area);
+        }
 
         f.render_stateful_widget(
             Scrollbar::default()
@@ -201,22 +399,32 @@ pub fn ui(f: &mut Frame, app: &mut App) {
     } else {
         Style::default().fg(app.current_theme.border)
     };
+    let terminal_title = if app.watch_enabled {
+        let tab = |tab: TerminalTab, label: String| if app.terminal_tab == tab { format!("[{}]", label) } else { label };
+        format!(" {} {} (F10 to Switch) ", tab(TerminalTab::Shell, "Terminal".to_string()),
+            tab(TerminalTab::Watch, format!("Watch {}", app.watch_status.label())))
+    } else {
+        " Terminal ".to_string()
+    };
     let terminal_block = Block::default()
-        .title(" Terminal ")
+        .title(terminal_title)
         .borders(Borders::ALL)
         .border_style(terminal_border_style)
         .style(Style::default().bg(app.current_theme.background).fg(app.current_theme.foreground));
 
-    let screen = app.terminal_screen.read().unwrap();
+    let screen = match app.terminal_tab {
+        TerminalTab::Shell => app.terminal_screen.read().unwrap(),
+        TerminalTab::Watch => app.watch_screen.read().unwrap(),
+    };
     let pseudo_term = PseudoTerminal::new(screen.screen())
         .block(terminal_block.clone());
 
     f.render_widget(pseudo_term, layout.terminal);
+    drop(screen);
 
     // Post-process: Replace Color::Reset backgrounds with theme background
     // tui-term uses Color::Reset for "default" terminal colors, which renders as black
     // We override these to match our theme (process entire terminal area including borders)
-    use ratatui::style::Color;
     for y in layout.terminal.y..layout.terminal.y + layout.terminal.height {
         for x in layout.terminal.x..layout.terminal.x + layout.terminal.width {
             if let Some(cell) = f.buffer_mut().cell_mut((x, y)) {
@@ -245,34 +453,49 @@ pub fn ui(f: &mut Frame, app: &mut App) {
     );
 
     // Chat
-    let chat_text = app.chat_history.join("\n\n");
     let chat_history_block = Block::default()
-        .title(format!(" AI Chat ({}) (Ctrl+M to Switch) ", app.get_selected_model_name()))
+        .title(format!(
+            " AI Chat ({}){} (Ctrl+M: Model, Alt+M: Preset) ",
+            app.get_selected_model_name(),
+            app.config.active_preset.as_deref().map(|p| format!(" [{}]", p)).unwrap_or_default(),
+        ))
         .borders(Borders::ALL)
         .border_style(if app.active_panel == ActivePanel::Chat { Style::default().fg(app.current_theme.border_active) } else { Style::default().fg(app.current_theme.border) })
         .style(Style::default().bg(app.current_theme.background));
 
     // Parse markdown for styled rendering
-    let chat_lines = parse_markdown_to_lines(&chat_text, &app.current_theme);
+    let chat_lines = chat_message_lines(&app.chat_history, &app.current_theme, app.config.chat_timestamps, &app.chat_expanded);
 
     // Calculate wrapped line count for proper scroll limits
     let chat_inner_width = layout.chat_history.width.saturating_sub(2) as usize; // Subtract borders
     let chat_inner_height = layout.chat_history.height.saturating_sub(2) as usize;
 
     // Estimate wrapped lines (each line wraps based on width)
-    let wrapped_lines: usize = chat_lines.iter()
-        .map(|line| {
-            let line_width: usize = line.spans.iter().map(|s| s.content.len()).sum();
-            if line_width == 0 {
-                1
-            } else {
-                (line_width + chat_inner_width - 1) / chat_inner_width.max(1)
-            }
-        })
-        .sum();
+    let wrapped_height = |lines: &[Line]| -> usize {
+        lines.iter()
+            .map(|line| {
+                let line_width: usize = line.spans.iter().map(|s| s.content.len()).sum();
+                if line_width == 0 {
+                    1
+                } else {
+                    (line_width + chat_inner_width - 1) / chat_inner_width.max(1)
+                }
+            })
+            .sum()
+    };
+    let wrapped_lines = wrapped_height(&chat_lines);
+
+    // A search result asked for this message to be at the top
+    if let Some(index) = app.chat_scroll_to.take() {
+        let earlier = &app.chat_history[..index.min(app.chat_history.len())];
+        let above = chat_message_lines(earlier, &app.current_theme, app.config.chat_timestamps, &app.chat_expanded);
+        // Plus the blank line between messages
+        app.chat_scroll = (wrapped_height(&above) + usize::from(index > 0)) as u16;
+    }
 
     let max_scroll = wrapped_lines.saturating_sub(chat_inner_height) as u16;
     app.chat_scroll = app.chat_scroll.min(max_scroll);
+    app.chat_max_scroll = max_scroll;
 
     // Create paragraph with styled lines
     // Note: Don't set a default style here as it would override span styles
@@ -293,9 +516,16 @@ pub fn ui(f: &mut Frame, app: &mut App) {
     );
 
     let mut chat_input = app.chat_input.clone();
+    if let Some(pattern) = app.chat_misspelling_pattern() {
+        if chat_input.set_search_pattern(pattern).is_ok() {
+            chat_input.set_search_style(Style::default().fg(Color::Red).add_modifier(Modifier::UNDERLINED));
+        }
+    }
+    let title = app.prompt_search_title()
+        .unwrap_or_else(|| " Chat Input (Enter: Send, Shift/Alt+Enter: New Line, Up: History) ".to_string());
     chat_input.set_block(Block::default()
         .borders(Borders::ALL)
-        .title(" Chat Input ")
+        .title(title)
         .border_style(if app.active_panel == ActivePanel::Chat { Style::default().fg(app.current_theme.border_active) } else { Style::default().fg(app.current_theme.border) }));
     f.render_widget(&chat_input, layout.chat_input);
 
@@ -307,17 +537,10 @@ pub fn ui(f: &mut Frame, app: &mut App) {
         let menu_items: Vec<ListItem> = raw_items
             .iter()
             .enumerate()
-            .map(|(i, (label, _action))| {
-                let shortcut = match (idx, i) {
-                    (0, 0) => " (Ctrl+S)",
-                    (0, 1) => " (Ctrl+P)",
-                    (0, 2) => " (Ctrl+Q)",
-                    (1, 0) => " (Ctrl+C)",
-                    (1, 1) => " (Ctrl+V)",
-                    (2, 0) => " (Ctrl+R)",
-                    (2, 1) => " (Ctrl+H)",
-                    _ => "",
-                };
+            .map(|(i, (label, action))| {
+                let shortcut = app.shortcut_label(*action)
+                    .map(|s| format!(" ({})", s))
+                    .unwrap_or_default();
                 let text = format!(" {}{} ", label, shortcut);
                 let style = if app.menu_hover_idx == Some(i) {
                     Style::default()
@@ -344,9 +567,11 @@ pub fn ui(f: &mut Frame, app: &mut App) {
         );
     }
 
+    render_tree_menu(f, app);
+
     // --- Search Modal ---
     if app.is_searching {
-        let area = centered_rect(60, 50, f.area());
+        let area = file_search_area(f.area());
         f.render_widget(Clear, area);
         
         let block = Block::default()
@@ -356,11 +581,7 @@ pub fn ui(f: &mut Frame, app: &mut App) {
             .style(Style::default().bg(app.current_theme.background).fg(app.current_theme.foreground));
         f.render_widget(block.clone(), area);
         
-        let inner_area = block.inner(area);
-        let chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([Constraint::Length(3), Constraint::Min(0)])
-            .split(inner_area);
+        let chunks = file_search_chunks(area);
             
         f.render_widget(&app.search_input, chunks[0]);
         
@@ -375,28 +596,1261 @@ pub fn ui(f: &mut Frame, app: &mut App) {
         f.render_stateful_widget(list, chunks[1], &mut app.search_state);
     }
 
+    // --- Search Everything Palette ---
+    if let Some(palette) = app.search_palette.as_mut() {
+        let theme = &app.current_theme;
+        let area = centered_rect(70, 60, f.area());
+        f.render_widget(Clear, area);
+        let title = if palette.buffers_only {
+            " Search Open Buffers: unsaved edits included (Enter to Open, Esc to Close) "
+        } else {
+            " Search Everything: files, text, chat, terminal (Enter to Open, Esc to Close) "
+        };
+        let block = Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.border))
+            .style(Style::default().bg(theme.background).fg(theme.foreground));
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Min(0)])
+            .split(inner);
+        f.render_widget(&palette.input, chunks[0]);
+
+        let items: Vec<ListItem> = palette.results.iter()
+            .map(|hit| ListItem::new(Line::from(vec![
+                Span::styled(format!("{:<5}", hit.target.tag()), Style::default().fg(theme.line_number)),
+                Span::raw(hit.text.clone()),
+            ])))
+            .collect();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::TOP))
+            .highlight_style(Style::default().bg(theme.selection_bg).fg(theme.selection_fg));
+        f.render_stateful_widget(list, chunks[1], &mut palette.state);
+    }
+
+    // --- Recent Files Switcher ---
+    if app.recent_files.is_some() {
+        render_recent_files(f, app);
+    }
+
+    // --- Restore Session Picker ---
+    if app.restore_points.is_some() {
+        render_restore_picker(f, app);
+    }
+
+    // --- Problems Panel ---
+    if app.show_problems {
+        render_problems_panel(f, app);
+    }
+
+    // --- Terminal Processes ---
+    if app.process_view.is_some() {
+        render_process_view(f, app);
+    }
+
+    // --- References ---
+    if app.preset_picker.is_some() {
+        render_preset_picker(f, app);
+    }
+
+    if app.outline.is_some() {
+        render_outline(f, app);
+    }
+
+    if app.project_setup.is_some() {
+        render_project_setup(f, app);
+    }
+
+    if app.reference_list.is_some() {
+        render_reference_list(f, app);
+    }
+
+    // --- Save As Prompt ---
+    if app.show_save_as {
+        let outer = centered_rect(60, 20, f.area());
+        let area = Rect::new(outer.x, outer.y + outer.height.saturating_sub(3) / 2, outer.width, 3.min(outer.height));
+        f.render_widget(Clear, area);
+        f.render_widget(&app.save_as_input, area);
+    }
+
+    // --- Compare Prompt ---
+    if app.show_compare {
+        let outer = centered_rect(60, 20, f.area());
+        let area = Rect::new(outer.x, outer.y + outer.height.saturating_sub(3) / 2, outer.width, 3.min(outer.height));
+        f.render_widget(Clear, area);
+        f.render_widget(&app.compare_input, area);
+    }
+
+    // --- Capture Prompt ---
+    if app.show_capture {
+        let outer = centered_rect(60, 20, f.area());
+        let area = Rect::new(outer.x, outer.y + outer.height.saturating_sub(3) / 2, outer.width, 3.min(outer.height));
+        f.render_widget(Clear, area);
+        f.render_widget(&app.capture_input, area);
+    }
+
+    // --- TODO Panel ---
+    if app.show_todos {
+        render_todo_panel(f, app);
+    }
+
+    // --- Color Picker ---
+    if let Some(picker) = &app.color_picker {
+        render_color_picker(f, app, picker);
+    }
+
+    // --- Workspace Stats ---
+    if app.show_stats {
+        render_stats_panel(f, app);
+    }
+
+    // --- Refactor and Rename Prompt ---
+    if app.show_refactor_prompt || app.show_rename_prompt {
+        let outer = centered_rect(60, 20, f.area());
+        let area = Rect::new(outer.x, outer.y + outer.height.saturating_sub(3) / 2, outer.width, 3.min(outer.height));
+        f.render_widget(Clear, area);
+        f.render_widget(&app.refactor_input, area);
+    }
+
+    // --- Refactor Review ---
+    if app.show_refactor {
+        render_refactor_panel(f, app);
+    }
+
+    // --- AI File Proposals ---
+    if app.show_proposals {
+        render_proposals_panel(f, app);
+    }
+
+    // --- Problems on the Cursor Line ---
+    if app.show_diagnostic_popup && app.active_panel == ActivePanel::Editor && !app.show_settings
+        && app.diff_view.is_none() && app.hex_view.is_none() && !app.showing_welcome()
+    {
+        render_diagnostic_popup(f, app, layout.editor);
+    }
+
+    // --- Code Completion ---
+    if app.completion_visible() {
+        render_completion(f, app, layout.editor);
+    }
+
+    // --- Spelling Suggestions ---
+    if app.show_spell {
+        let area = centered_rect(30, 40, f.area());
+        f.render_widget(Clear, area);
+        let items: Vec<ListItem> = if app.spell_suggestions.is_empty() {
+            vec![ListItem::new("(no suggestions)").style(Style::default().fg(app.current_theme.border))]
+        } else {
+            app.spell_suggestions.iter().enumerate()
+                .map(|(i, s)| {
+                    let style = if i == app.spell_selected {
+                        Style::default().bg(app.current_theme.selection_bg).fg(app.current_theme.selection_fg)
+                    } else {
+                        Style::default()
+                    };
+                    ListItem::new(s.as_str()).style(style)
+                })
+                .collect()
+        };
+        f.render_widget(
+            List::new(items)
+                .block(Block::default()
+                    .title(format!(" \"{}\" (Enter: Replace, a: Add to Dictionary) ", app.spell_word()))
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(app.current_theme.border_active)))
+                .style(Style::default().bg(app.current_theme.background).fg(app.current_theme.foreground)),
+            area,
+        );
+    }
+
+    // --- Secret Warning ---
+    if app.show_secret_warning {
+        let area = centered_rect(60, 40, f.area());
+        f.render_widget(Clear, area);
+        let mut text = vec![
+            Line::from("What is about to be sent to the AI looks like it contains secrets:"),
+            Line::from(""),
+        ];
+        for finding in &app.secret_findings {
+            text.push(Line::from(vec![
+                Span::styled(format!("  line {}: {} ", finding.line, finding.kind), Style::default().fg(Color::Red)),
+                Span::raw(finding.preview.clone()),
+            ]));
+        }
+        text.push(Line::from(""));
+        text.push(Line::from("Known false positives can be added to secret_allowlist in the config."));
+        f.render_widget(
+            Paragraph::new(text)
+                .wrap(Wrap { trim: false })
+                .block(Block::default()
+                    .title(" Send to AI? (y: Send Anyway, n/Esc: Edit) ")
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(Color::Red)))
+                .style(Style::default().bg(app.current_theme.background).fg(app.current_theme.foreground)),
+            area,
+        );
+    }
+
+    // --- External Change Conflict ---
+    if app.show_conflict {
+        let area = centered_rect(50, 20, f.area());
+        f.render_widget(Clear, area);
+        let text = vec![
+            Line::from(format!("{} was changed on disk, but has unsaved edits here.", app.editor_state.display_name())),
+            Line::from(""),
+            Line::from("r: Reload from disk   k: Keep mine   d: Show diff"),
+        ];
+        f.render_widget(
+            Paragraph::new(text)
+                .wrap(Wrap { trim: false })
+                .block(Block::default()
+                    .title(" File Changed on Disk ")
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(app.current_theme.border_active)))
+                .style(Style::default().bg(app.current_theme.background).fg(app.current_theme.foreground)),
+            area,
+        );
+    }
+
+    // --- Unsaved Changes Prompt ---
+    if app.unsaved_prompt.is_some() {
+        let names = app.unsaved_prompt_names();
+        let height = (names.len() as u16 + 6).min(f.area().height);
+        let outer = centered_rect(50, 100, f.area());
+        let area = Rect::new(outer.x, outer.y + outer.height.saturating_sub(height) / 2, outer.width, height);
+        f.render_widget(Clear, area);
+        let (question, keys) = match app.unsaved_prompt {
+            Some(UnsavedPrompt::Quit) => ("Save changes before quitting?", "s: Save   d: Discard   Esc: Cancel"),
+            Some(UnsavedPrompt::Revert) => ("Discard changes and reload from disk?", "d: Discard   Esc: Cancel"),
+            _ => ("Save changes before closing?", "s: Save   d: Discard   Esc: Cancel"),
+        };
+        let mut text = vec![Line::from(question), Line::from("")];
+        text.extend(names.into_iter().map(|name| Line::from(Span::styled(format!("  {}", name), Style::default().fg(app.current_theme.file)))));
+        text.push(Line::from(""));
+        text.push(Line::from(keys));
+        f.render_widget(
+            Paragraph::new(text)
+                .block(Block::default()
+                    .title(" Unsaved Changes ")
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(app.current_theme.border_active)))
+                .style(Style::default().bg(app.current_theme.background).fg(app.current_theme.foreground)),
+            area,
+        );
+    }
+
+    // --- Recovery Prompt ---
+    if app.show_recovery {
+        let area = centered_rect(60, 40, f.area());
+        f.render_widget(Clear, area);
+
+        let block = Block::default()
+            .title(" Recover Unsaved Changes (Enter: Restore, d: Discard, Esc: Discard All) ")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(app.current_theme.border_active))
+            .style(Style::default().bg(app.current_theme.background).fg(app.current_theme.foreground));
+        f.render_widget(block.clone(), area);
+
+        let items: Vec<ListItem> = app.recovery_backups.iter()
+            .map(|b| ListItem::new(Line::from(vec![
+                Span::raw(b.display_name()),
+                Span::styled(format!("  ({})", b.age_label()), Style::default().fg(app.current_theme.border)),
+            ])))
+            .collect();
+
+        let list = List::new(items)
+            .highlight_style(Style::default().bg(app.current_theme.selection_bg).fg(app.current_theme.selection_fg));
+
+        f.render_stateful_widget(list, block.inner(area), &mut app.recovery_state);
+    }
+
+    // Last, so every panel (and syntax highlighting) falls back the same way
+    adapt_to_terminal(f.buffer_mut(), &app.capabilities);
 }
 
-/// Render the settings panel in the editor area with two-column form layout
-fn render_settings_panel(f: &mut Frame, app: &mut App, area: Rect) {
+/// Replace colors and characters the terminal can't show with the closest ones it can
+fn adapt_to_terminal(buffer: &mut Buffer, caps: &Capabilities) {
+    if caps.color == ColorLevel::TrueColor && caps.unicode {
+        return;
+    }
+    for cell in buffer.content.iter_mut() {
+        if caps.color != ColorLevel::TrueColor {
+            cell.fg = limit_color(cell.fg, caps.color);
+            cell.bg = limit_color(cell.bg, caps.color);
+            cell.underline_color = limit_color(cell.underline_color, caps.color);
+        }
+        if !caps.unicode && !cell.symbol().is_ascii() {
+            let c = cell.symbol().chars().next().map_or(' ', capabilities::ascii_fallback);
+            cell.set_char(c);
+        }
+    }
+}
+
+fn limit_color(color: Color, level: ColorLevel) -> Color {
+    let rgb = match color {
+        Color::Rgb(r, g, b) => [r, g, b],
+        Color::Indexed(i) if level == ColorLevel::Basic => capabilities::indexed_rgb(i),
+        _ => return color,
+    };
+    if level == ColorLevel::Indexed {
+        return Color::Indexed(capabilities::nearest_indexed(rgb));
+    }
+    const ANSI: [Color; 16] = [
+        Color::Black, Color::Red, Color::Green, Color::Yellow, Color::Blue, Color::Magenta, Color::Cyan, Color::Gray,
+        Color::DarkGray, Color::LightRed, Color::LightGreen, Color::LightYellow, Color::LightBlue, Color::LightMagenta,
+        Color::LightCyan, Color::White,
+    ];
+    ANSI[capabilities::nearest_ansi(rgb) as usize]
+}
+
+/// Render the find (and optional replace) inputs with a match counter
+/// Editor border title: the buffer name, or the tab list once several buffers are open
+fn editor_title(app: &App) -> Line<'static> {
+    let labels = app.buffer_labels();
+    if labels.len() <= 1 {
+        let mut title = format!(" Editor - {} ", app.editor_state.display_name());
+        if app.editor_state.read_only {
+            title.push_str("[RO] ");
+        }
+        if app.editor_state.modified && !app.editor_state.is_scratch() {
+            title.push_str("[+] ");
+        }
+        return Line::from(title);
+    }
+    let mut spans = vec![Span::raw(" Editor -")];
+    for (i, label) in labels.into_iter().enumerate() {
+        let style = if i == app.active_buffer {
+            Style::default().bg(app.current_theme.selection_bg).fg(app.current_theme.selection_fg)
+        } else {
+            Style::default()
+        };
+        spans.push(Span::raw(" "));
+        spans.push(Span::styled(format!(" {} ", label), style));
+    }
+    spans.push(Span::raw(" "));
+    Line::from(spans)
+}
+
+/// Welcome view in the editor area: a terminal shortcut, recent files and workspaces, key hints
+fn render_welcome(f: &mut Frame, app: &App, area: Rect) {
+    let Some(welcome) = &app.welcome else { return };
+    let theme = &app.current_theme;
+    let focused = app.active_panel == ActivePanel::Editor;
+    let block = Block::default()
+        .borders(if app.zen_mode { Borders::NONE } else { Borders::ALL })
+        .title(" Welcome ")
+        .title_bottom(" ↑/↓: Select, Enter: Open, or start typing ")
+        .border_style(Style::default().fg(if focused { theme.border_active } else { theme.border }));
+    let heading = |text: &str| Line::from(Span::styled(text.to_string(), Style::default().fg(theme.directory).add_modifier(Modifier::BOLD)));
+    let dim = Style::default().fg(theme.line_number);
+
+    let mut lines = vec![
+        Line::from(Span::styled("nterm", Style::default().fg(theme.foreground).add_modifier(Modifier::BOLD))),
+        Line::from(Span::styled(app.workspace.display().to_string(), dim)),
+    ];
+    let mut section = "";
+    for (i, item) in welcome.items.iter().enumerate() {
+        let (title, label) = match item {
+            WelcomeItem::Terminal => ("Start", ">_ Open Terminal".to_string()),
+            WelcomeItem::File(path) => ("Recent Files", path.display().to_string()),
+            WelcomeItem::Workspace(path) => ("Recent Workspaces", path.display().to_string()),
+        };
+        if title != section {
+            section = title;
+            lines.push(Line::from(""));
+            lines.push(heading(title));
+        }
+        let style = if i == welcome.selected && focused {
+            Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+        } else {
+            Style::default().fg(theme.foreground)
+        };
+        lines.push(Line::from(Span::styled(format!("  {}", label), style)));
+    }
+
+    lines.push(Line::from(""));
+    lines.push(heading("Keys"));
+    let hints = [
+        ("Find file", Action::FileSearch),
+        ("Switch panel", Action::SwitchFocus),
+        ("Menu", Action::ToggleMenu),
+        ("Save", Action::Save),
+        ("Find in file", Action::Find),
+        ("Settings", Action::OpenSettings),
+        ("Quit", Action::Quit),
+    ];
+    for (label, action) in hints {
+        let Some(key) = app.shortcut_label(action) else { continue };
+        lines.push(Line::from(vec![Span::styled(format!("  {:<12}", key), dim), Span::raw(label)]));
+    }
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+fn render_hex_view(f: &mut Frame, app: &mut App, area: Rect) {
+    let border = if app.active_panel == ActivePanel::Editor {
+        app.current_theme.border_active
+    } else {
+        app.current_theme.border
+    };
+    let theme = &app.current_theme;
+    let Some(view) = app.hex_view.as_mut() else { return };
+    let name = view.path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
     let block = Block::default()
-        .title(" Settings ")
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(app.current_theme.border_active))
-        .style(Style::default().bg(app.current_theme.background).fg(app.current_theme.foreground));
+        .title(format!(" Hex: {} ({} bytes, binary) ", name, view.size))
+        .title_bottom(" Up/Down/PgUp/PgDn: Scroll, Home/End: Start/End, Esc: Close ")
+        .border_style(Style::default().fg(border));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
 
-    let inner_area = block.inner(area);
+    let lines: Vec<Line> = match view.visible_rows(inner.height as usize) {
+        // Offset, hex bytes and the printable column (after the last two spaces)
+        Ok(rows) => rows.into_iter().map(|row| {
+            let (offset, rest) = row.split_at(8);
+            let (bytes, text) = rest.split_at(rest.rfind(" |").unwrap_or(rest.len()));
+            Line::from(vec![
+                Span::styled(offset.to_string(), Style::default().fg(theme.line_number)),
+                Span::styled(bytes.to_string(), Style::default().fg(theme.foreground)),
+                Span::styled(text.to_string(), Style::default().fg(theme.directory)),
+            ])
+        }).collect(),
+        Err(e) => vec![Line::from(Span::styled(format!("Could not read file: {}", e), Style::default().fg(Color::Red)))],
+    };
+    f.render_widget(Paragraph::new(lines), inner);
+}
+
+fn render_diff_view(f: &mut Frame, app: &App, area: Rect) {
+    let Some(view) = &app.diff_view else { return };
+    let hunk = match (view.current_hunk(), view.hunk_count()) {
+        (_, 0) => "no differences".to_string(),
+        (Some(i), n) => format!("change {}/{}", i, n),
+        (None, n) => format!("{} changes", n),
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(" Compare: {} | {} ({}) ", view.left_title, view.right_title, hunk))
+        .title_bottom(" n/p: Next/Prev Change, u: Unified/Split, Up/Down/PgUp/PgDn: Scroll, Esc: Close ")
+        .border_style(if app.active_panel == ActivePanel::Editor {
+            Style::default().fg(app.current_theme.border_active)
+        } else {
+            Style::default().fg(app.current_theme.border)
+        });
+    let inner = block.inner(area);
     f.render_widget(block, area);
 
-    // Calculate layout: header, model list, footer
+    let width = view.diff.left.len().max(view.diff.right.len()).max(1).to_string().len();
+    let number_style = Style::default().fg(app.current_theme.line_number);
+    let kind_style = |kind: DiffKind| match kind {
+        DiffKind::Same => Style::default().fg(app.current_theme.foreground),
+        DiffKind::Changed => Style::default().fg(Color::Yellow),
+        DiffKind::Removed => Style::default().fg(Color::Red),
+        DiffKind::Added => Style::default().fg(Color::Green),
+    };
+
+    if view.unified {
+        let rows = &view.diff.rows[view.scroll.min(view.diff.rows.len())..];
+        let lines: Vec<Line> = view.diff.unified(rows)
+            .take(inner.height as usize)
+            .map(|line| Line::from(Span::styled(format!("{} {}", line.marker, line.text), kind_style(line.kind))))
+            .collect();
+        f.render_widget(Paragraph::new(lines), inner);
+        return;
+    }
+
+    let halves = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(inner);
+
+    let side = |line: Option<(usize, &str)>, kind: DiffKind| -> Line<'static> {
+        match line {
+            Some((i, text)) => Line::from(vec![
+                Span::styled(format!("{:>width$} ", i + 1, width = width), number_style),
+                Span::styled(text.to_string(), kind_style(kind)),
+            ]),
+            None => Line::from(Span::styled(" ".repeat(width + 1), number_style)),
+        }
+    };
+
+    let rows = &view.diff.rows[view.scroll.min(view.diff.rows.len())..];
+    let (left, right): (Vec<Line>, Vec<Line>) = view.diff.side_by_side(rows)
+        .take(inner.height as usize)
+        // A removed line shows as removed on the left and a gap on the right, and vice versa
+        .map(|row| (side(row.left, row.kind), side(row.right, row.kind)))
+        .unzip();
+    f.render_widget(Paragraph::new(left), halves[0]);
+    f.render_widget(Paragraph::new(right).block(Block::default().borders(Borders::LEFT)
+        .border_style(Style::default().fg(app.current_theme.border))), halves[1]);
+}
+
+fn render_find_bar(f: &mut Frame, app: &mut App, area: Rect) {
+    use super::find::FindField;
+
+    let counter = match (app.find_bar.current, app.find_bar.matches.len()) {
+        _ if app.find_bar.error.is_some() => String::new(),
+        (_, 0) if app.find_bar.query_text().is_empty() => String::new(),
+        (_, 0) => " No matches ".to_string(),
+        (Some(i), n) => format!(" {}/{} ", i + 1, n),
+        (None, n) => format!(" {} matches ", n),
+    };
+    let focused = Style::default().fg(app.current_theme.border_active);
+    let unfocused = Style::default().fg(app.current_theme.border);
+
+    let hint = if app.find_bar.replace_mode {
+        " Enter: Next/Replace, Ctrl+A: All, Ctrl+R: Regex, Tab: Switch, Esc: Close "
+    } else {
+        " Enter: Next, Shift+Enter: Prev, Ctrl+R: Regex, Tab: Replace, Esc: Close "
+    };
+    let mut title = vec![Span::raw(if app.find_bar.regex_mode { " Find [.*]" } else { " Find" }), Span::raw(counter)];
+    if let Some(error) = &app.find_bar.error {
+        title.push(Span::styled(format!(" {} ", error), Style::default().fg(Color::Red)));
+    }
+    let find_block = Block::default()
+        .borders(Borders::ALL)
+        .title(Line::from(title))
+        .title_bottom(hint)
+        .border_style(if app.find_bar.focus == FindField::Query { focused } else { unfocused });
+    app.find_bar.query.set_block(find_block);
+
+    if app.find_bar.replace_mode {
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(area);
+        let replace_block = Block::default()
+            .borders(Borders::ALL)
+            .title(" Replace ")
+            .border_style(if app.find_bar.focus == FindField::Replace { focused } else { unfocused });
+        app.find_bar.replacement.set_block(replace_block);
+        f.render_widget(Clear, area);
+        f.render_widget(&app.find_bar.query, chunks[0]);
+        f.render_widget(&app.find_bar.replacement, chunks[1]);
+    } else {
+        f.render_widget(Clear, area);
+        f.render_widget(&app.find_bar.query, area);
+    }
+}
+
+/// Screen area of the TODO panel (shared with mouse hit-testing)
+pub fn todo_panel_area(area: Rect) -> Rect {
+    centered_rect(70, 60, area)
+}
+
+/// Screen area of the file search modal (shared with mouse hit-testing)
+pub fn file_search_area(area: Rect) -> Rect {
+    centered_rect(60, 50, area)
+}
+
+/// Query input and result list inside the file search modal at `area`
+fn file_search_chunks(area: Rect) -> std::rc::Rc<[Rect]> {
+    Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(0)])
+        .split(Block::default().borders(Borders::ALL).inner(area))
+}
+
+/// Rows of file search results, below the list's top border
+pub fn file_search_results_area(area: Rect) -> Rect {
+    let list = file_search_chunks(file_search_area(area))[1];
+    Rect { y: list.y + 1, height: list.height.saturating_sub(1), ..list }
+}
+
+/// Screen area of the recent files switcher (shared with mouse hit-testing)
+pub fn recent_files_area(area: Rect) -> Rect {
+    centered_rect(50, 40, area)
+}
+
+/// Rows of the recent files list, below the query line
+pub fn recent_files_list_area(area: Rect) -> Rect {
+    let inner = Block::default().borders(Borders::ALL).inner(recent_files_area(area));
+    Rect { y: inner.y + 1, height: inner.height.saturating_sub(1), ..inner }
+}
+
+/// The process tree of a terminal tab: PID, CPU, memory and the command line,
+/// indented under its parent
+fn render_process_view(f: &mut Frame, app: &App) {
+    let Some(view) = app.process_view.as_ref() else { return };
+    let theme = &app.current_theme;
+    let area = centered_rect(70, 60, f.area());
+    let block = Block::default()
+        .title(format!(" Processes in {} (i: Interrupt, t: Terminate, k: Kill, s: Stop, c: Continue, Esc: Close) ", view.name))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.border_active))
+        .style(Style::default().bg(theme.background).fg(theme.foreground));
+    f.render_widget(Clear, area);
+    if let Some(error) = &view.error {
+        f.render_widget(Paragraph::new(format!("Could not list the processes: {}", error)).block(block), area);
+        return;
+    }
+    let inner = block.inner(area);
+    f.render_widget(block, area);
     let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)])
+        .split(inner);
+    f.render_widget(
+        Paragraph::new(format!("{:>7} {:>6} {:>7}  COMMAND", "PID", "CPU%", "MEM"))
+            .style(Style::default().fg(theme.line_number).add_modifier(Modifier::BOLD)),
+        chunks[0],
+    );
+    let items: Vec<ListItem> = view.rows.iter()
+        .map(|row| {
+            let branch = if row.depth == 0 { String::new() } else { format!("{}└ ", "  ".repeat(row.depth - 1)) };
+            let busy = if row.info.cpu >= 50.0 { Style::default().fg(Color::Red) } else { Style::default() };
+            ListItem::new(Line::from(vec![
+                Span::raw(format!("{:>7} ", row.info.pid)),
+                Span::styled(format!("{:>6.1}", row.info.cpu), busy),
+                Span::raw(format!(" {:>7}  ", row.info.memory_label())),
+                Span::styled(branch, Style::default().fg(theme.line_number)),
+                Span::raw(row.info.command.clone()),
+            ]))
+        })
+        .collect();
+    f.render_stateful_widget(
+        List::new(items).highlight_style(Style::default().bg(theme.selection_bg).fg(theme.selection_fg)),
+        chunks[1],
+        &mut ListState::default().with_selected(Some(view.selected)),
+    );
+}
+
+fn render_reference_list(f: &mut Frame, app: &App) {
+    let Some(list) = app.reference_list.as_ref() else { return };
+    let theme = &app.current_theme;
+    let area = centered_rect(70, 50, f.area());
+    let items: Vec<ListItem> = list.references.iter()
+        .map(|reference| {
+            let path = reference.path.strip_prefix(&app.workspace).unwrap_or(&reference.path);
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{}:{}", path.display(), reference.row + 1), Style::default().fg(theme.line_number)),
+                Span::raw(format!("  {}", reference.text)),
+            ]))
+        })
+        .collect();
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(
+        List::new(items)
+            .block(Block::default()
+                .title(format!(" {} ({}) (Enter: Open, Esc: Close) ", list.title, list.references.len()))
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(theme.border_active)))
+            .style(Style::default().bg(theme.background).fg(theme.foreground))
+            .highlight_style(Style::default().bg(theme.selection_bg).fg(theme.selection_fg)),
+        area,
+        &mut ListState::default().with_selected(Some(list.selected)),
+    );
+}
+
+fn render_outline(f: &mut Frame, app: &App) {
+    let Some(outline) = app.outline.as_ref() else { return };
+    let theme = &app.current_theme;
+    let area = centered_rect(50, 60, f.area());
+    f.render_widget(Clear, area);
+    let source = if outline.from_server { "language server" } else { "patterns" };
+    let block = Block::default()
+        .title(format!(" Outline of {} ({}) (Enter: Go, Esc: Close) ", outline.title, source))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.border_active))
+        .style(Style::default().bg(theme.background).fg(theme.foreground));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let query = Line::from(vec![
+        Span::styled("> ", Style::default().fg(theme.line_number)),
+        Span::raw(outline.query.clone()),
+        Span::styled("█", Style::default().fg(theme.cursor_bg)),
+    ]);
+    f.render_widget(Paragraph::new(query), Rect { height: 1.min(inner.height), ..inner });
+
+    let matches = outline.matches();
+    let items: Vec<ListItem> = matches.iter()
+        .map(|symbol| {
+            ListItem::new(Line::from(vec![
+                Span::raw("  ".repeat(symbol.depth)),
+                Span::styled(format!("{} ", symbol.icon()), Style::default().fg(theme.line_number)),
+                Span::raw(symbol.name.clone()),
+                Span::styled(format!("  :{}", symbol.row + 1), Style::default().fg(theme.line_number)),
+            ]))
+        })
+        .collect();
+    let list_area = Rect { y: inner.y + 1.min(inner.height), height: inner.height.saturating_sub(1), ..inner };
+    let list = List::new(items)
+        .highlight_style(Style::default().bg(theme.selection_bg).fg(theme.selection_fg));
+    let mut state = ListState::default().with_selected((!matches.is_empty()).then_some(outline.selected));
+    f.render_stateful_widget(list, list_area, &mut state);
+}
+
+fn render_project_setup(f: &mut Frame, app: &App) {
+    let Some(setup) = app.project_setup.as_ref() else { return };
+    let theme = &app.current_theme;
+    let area = centered_rect(50, 40, f.area());
+    f.render_widget(Clear, area);
+    let block = Block::default()
+        .title(format!(" {} project {} (Enter: Run, Esc: Close) ", setup.project.kind.label(), setup.project.name))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.border_active))
+        .style(Style::default().bg(theme.background).fg(theme.foreground));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let width = setup.items.iter()
+        .map(|item| match item {
+            SetupItem::Command { label, .. } => label.chars().count(),
+            SetupItem::Summarize => 0,
+        })
+        .max()
+        .unwrap_or(0);
+    let items: Vec<ListItem> = setup.items.iter()
+        .map(|item| match item {
+            SetupItem::Command { label, command } => ListItem::new(Line::from(vec![
+                Span::styled(format!("{:<width$}  ", label, width = width), Style::default().fg(theme.line_number)),
+                Span::raw(command.clone()),
+            ])),
+            SetupItem::Summarize => ListItem::new(Line::from(vec![
+                Span::raw("Summarize the project in the chat"),
+                Span::styled(format!("  (sends {} and README to {})", setup.project.manifest, app.get_selected_model_name()), Style::default().fg(theme.line_number)),
+            ])),
+        })
+        .collect();
+    let list = List::new(items)
+        .highlight_style(Style::default().bg(theme.selection_bg).fg(theme.selection_fg));
+    let mut state = ListState::default().with_selected(Some(setup.selected));
+    f.render_stateful_widget(list, inner, &mut state);
+}
+
+/// In place of the panels when the window is below the minimum size
+fn render_too_small(f: &mut Frame, app: &App) {
+    let area = f.area();
+    let theme = &app.current_theme;
+    let lines = vec![
+        Line::from(Span::styled("Window too small", Style::default().fg(theme.foreground).add_modifier(Modifier::BOLD))),
+        Line::from(Span::styled(
+            format!("{}x{}, needs {}x{}", area.width, area.height, MIN_WIDTH, MIN_HEIGHT),
+            Style::default().fg(theme.line_number),
+        )),
+    ];
+    let top = area.height.saturating_sub(2) / 2;
+    f.render_widget(Block::default().style(Style::default().bg(theme.background)), area);
+    f.render_widget(
+        Paragraph::new(lines).alignment(ratatui::layout::Alignment::Center).wrap(Wrap { trim: true }),
+        Rect { y: area.y + top, height: area.height - top, ..area },
+    );
+}
+
+fn render_restore_picker(f: &mut Frame, app: &App) {
+    let Some(points) = app.restore_points.as_ref() else { return };
+    let theme = &app.current_theme;
+    let area = centered_rect(50, 40, f.area());
+    let items: Vec<ListItem> = points.iter()
+        .map(|point| {
+            let contents = if point.database().is_some() { "config, chat history, scratch buffers" } else { "config" };
+            ListItem::new(Line::from(vec![
+                Span::raw(point.label()),
+                Span::styled(format!("  {}", contents), Style::default().fg(theme.line_number)),
+            ]))
+        })
+        .collect();
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(
+        List::new(items)
+            .block(Block::default()
+                .title(" Restore Session From (Enter: Restore, Esc: Close) ")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(theme.border_active)))
+            .style(Style::default().bg(theme.background).fg(theme.foreground))
+            .highlight_style(Style::default().bg(theme.selection_bg).fg(theme.selection_fg)),
+        area,
+        &mut ListState::default().with_selected(Some(app.restore_selected)),
+    );
+}
+
+fn render_preset_picker(f: &mut Frame, app: &App) {
+    let Some(selected) = app.preset_picker else { return };
+    let theme = &app.current_theme;
+    let area = centered_rect(50, 40, f.area());
+    let active = app.config.active_preset.as_deref();
+    let items: Vec<ListItem> = std::iter::once(ListItem::new(Span::styled("No preset", Style::default().fg(theme.line_number))))
+        .chain(app.config.context_presets.iter().map(|preset| {
+            let marker = if Some(preset.name.as_str()) == active { "* " } else { "  " };
+            ListItem::new(format!("{}{}", marker, preset.summary()))
+        }))
+        .collect();
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(
+        List::new(items)
+            .block(Block::default()
+                .title(" AI Context Preset (Enter: Use, Esc: Close) ")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(theme.border_active)))
+            .style(Style::default().bg(theme.background).fg(theme.foreground))
+            .highlight_style(Style::default().bg(theme.selection_bg).fg(theme.selection_fg)),
+        area,
+        &mut ListState::default().with_selected(Some(selected)),
+    );
+}
+
+/// The messages of the diagnostics on the cursor line, just below it (above
+/// it near the bottom of the editor)
+fn render_diagnostic_popup(f: &mut Frame, app: &App, editor: Rect) {
+    let diagnostics = app.cursor_line_diagnostics();
+    if diagnostics.is_empty() {
+        return;
+    }
+    let width = editor.width.saturating_sub(8).clamp(20, 80);
+    let text_width = width.saturating_sub(2).max(1) as usize;
+    let lines: Vec<Line> = diagnostics.iter()
+        .map(|d| {
+            let code = d.code.as_deref().map(|c| format!(" [{}]", c)).unwrap_or_default();
+            Line::from(vec![
+                Span::styled(format!("{} ", d.severity.marker()), Style::default().fg(severity_color(d.severity)).add_modifier(Modifier::BOLD)),
+                Span::raw(format!("{}{}", d.message, code)),
+                Span::styled(format!("  {}", d.source.label()), Style::default().fg(app.current_theme.line_number)),
+            ])
+        })
+        .collect();
+    let rows: usize = lines.iter().map(|line| line.width().div_ceil(text_width).max(1)).sum();
+    let height = (rows as u16 + 2).min(editor.height.saturating_sub(2).max(3));
+
+    let cursor_y = editor.y + 1 + app.editor_state.cursor_row.saturating_sub(app.editor_state.scroll_offset) as u16;
+    let y = if cursor_y + 1 + height <= editor.bottom() { cursor_y + 1 } else { cursor_y.saturating_sub(height).max(editor.y) };
+    let area = Rect::new(editor.x + 6, y, width, height).intersection(f.area());
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .block(Block::default()
+                .title(" Problems (Alt+. Next, Alt+, Previous) ")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(app.current_theme.border_active)))
+            .style(Style::default().bg(app.current_theme.background).fg(app.current_theme.foreground)),
+        area,
+    );
+}
+
+/// The completion items under the word being completed (above it near the
+/// bottom of the screen), each with its kind's icon and detail
+fn render_completion(f: &mut Frame, app: &App, editor: Rect) {
+    let Some(completion) = &app.completion else { return };
+    let inner = app.editor_text_area(editor);
+    let Some((x, y)) = app.editor_state.screen_position(inner, completion.row, completion.start) else { return };
+    let screen = f.area();
+
+    let shown: Vec<&CompletionItem> = completion.matches.iter()
+        .skip(completion.scroll_offset())
+        .take(VISIBLE_ITEMS)
+        .map(|&i| &completion.items[i])
+        .collect();
+    let label_width = shown.iter().map(|item| item.label.chars().count()).max().unwrap_or(0);
+    let detail_width = shown.iter().filter_map(|item| item.detail.as_ref()).map(|d| d.chars().count()).max().unwrap_or(0);
+    let width = ((label_width + detail_width.min(30) + 6) as u16).clamp(20, 70).min(screen.width);
+    let height = shown.len() as u16 + 2;
+    // Icon and label line up with the typed word
+    let x = x.saturating_sub(3).min(screen.right().saturating_sub(width));
+    let y = if y + 1 + height <= screen.bottom() { y + 1 } else { y.saturating_sub(height) };
+    let area = Rect::new(x, y, width, height).intersection(screen);
+
+    let selected = completion.selected - completion.scroll_offset();
+    let items: Vec<ListItem> = shown.iter().enumerate()
+        .map(|(i, item)| {
+            let icon_color = match item.icon() {
+                "f" => Color::Magenta,
+                "p" => Color::Cyan,
+                "v" => Color::Blue,
+                "t" => Color::Yellow,
+                "m" => Color::Green,
+                "k" => Color::Red,
+                _ => app.current_theme.line_number,
+            };
+            let mut spans = vec![
+                Span::styled(format!("{} ", item.icon()), Style::default().fg(icon_color).add_modifier(Modifier::BOLD)),
+                Span::raw(format!("{:<1$}", item.label, label_width)),
+            ];
+            if let Some(detail) = &item.detail {
+                spans.push(Span::styled(format!(" {}", detail), Style::default().fg(app.current_theme.line_number)));
+            }
+            let style = if i == selected {
+                Style::default().bg(app.current_theme.selection_bg).fg(app.current_theme.selection_fg)
+            } else {
+                Style::default()
+            };
+            ListItem::new(Line::from(spans)).style(style)
+        })
+        .collect();
+    let title = if completion.matches.len() > VISIBLE_ITEMS {
+        format!(" {}/{} ", completion.selected + 1, completion.matches.len())
+    } else {
+        String::new()
+    };
+    f.render_widget(Clear, area);
+    f.render_widget(
+        List::new(items)
+            .block(Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(app.current_theme.border_active)))
+            .style(Style::default().bg(app.current_theme.background).fg(app.current_theme.foreground)),
+        area,
+    );
+}
+
+fn render_tree_menu(f: &mut Frame, app: &App) {
+    let Some(menu) = app.tree_menu.as_ref() else { return };
+    let theme = &app.current_theme;
+    let area = menu.area(f.area());
+    let items: Vec<ListItem> = menu.items.iter().map(|(label, _)| ListItem::new(format!(" {} ", label))).collect();
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(
+        List::new(items)
+            .block(Block::default().borders(Borders::ALL).border_style(Style::default().fg(theme.border)))
+            .style(Style::default().bg(theme.background).fg(theme.foreground))
+            .highlight_style(Style::default().bg(theme.selection_bg).fg(theme.selection_fg)),
+        area,
+        &mut ListState::default().with_selected(Some(menu.selected)),
+    );
+}
+
+fn render_recent_files(f: &mut Frame, app: &App) {
+    let Some(recent) = app.recent_files.as_ref() else { return };
+    let theme = &app.current_theme;
+    let area = recent_files_area(f.area());
+    f.render_widget(Clear, area);
+    let block = Block::default()
+        .title(" Recent Files (Enter: Open, Esc: Close) ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.border_active))
+        .style(Style::default().bg(theme.background).fg(theme.foreground));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let query = Line::from(vec![
+        Span::styled("> ", Style::default().fg(theme.line_number)),
+        Span::raw(recent.query.clone()),
+        Span::styled("█", Style::default().fg(theme.cursor_bg)),
+    ]);
+    f.render_widget(Paragraph::new(query), Rect { height: 1.min(inner.height), ..inner });
+
+    let matches = recent.matches();
+    let items: Vec<ListItem> = matches.iter()
+        .map(|file| {
+            let name = file.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            let dir = file.parent().map(|d| d.display().to_string()).filter(|d| !d.is_empty());
+            let mut spans = vec![Span::raw(name)];
+            if let Some(dir) = dir {
+                spans.push(Span::styled(format!("  {}", dir), Style::default().fg(theme.line_number)));
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    let list = List::new(items)
+        .highlight_style(Style::default().bg(theme.selection_bg).fg(theme.selection_fg));
+    let mut state = ListState::default().with_selected((!matches.is_empty()).then_some(recent.selected));
+    f.render_stateful_widget(list, recent_files_list_area(f.area()), &mut state);
+}
+
+/// Lines of each model card in the settings panel: top border, two rows, bottom border
+pub const SETTINGS_CARD_LINES: usize = 4;
+
+/// Button on the bottom border of each model card, after `└─ `
+pub const SETTINGS_TEST_BUTTON: &str = "[ Test connection (t) ]";
+
+/// Header, model list and footer of the settings panel shown in `area`
+fn settings_chunks(area: Rect) -> std::rc::Rc<[Rect]> {
+    Layout::default()
         .direction(Direction::Vertical)
         .constraints([
             Constraint::Length(1),  // Header/instructions
             Constraint::Min(0),     // Model list
             Constraint::Length(1),  // Footer
         ])
-        .split(inner_area);
+        .split(Block::default().borders(Borders::ALL).inner(area))
+}
+
+/// Where the model cards of the settings panel shown in `area` are drawn
+pub fn settings_list_area(area: Rect) -> Rect {
+    settings_chunks(area)[1]
+}
+
+/// Render the TODO panel: items grouped under a header per file
+fn render_todo_panel(f: &mut Frame, app: &mut App) {
+    let area = todo_panel_area(f.area());
+    f.render_widget(Clear, area);
+
+    let status = if app.todos_scanning { " (scanning...)" } else { "" };
+    let block = Block::default()
+        .title(format!(" TODOs: {}{} (Enter: Jump, r: Rescan, Esc: Close) ", app.todos.len(), status))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(app.current_theme.border_active))
+        .style(Style::default().bg(app.current_theme.background).fg(app.current_theme.foreground));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let theme = &app.current_theme;
+    let mut items = Vec::new();
+    for (path, todos) in crate::shared::todo::group_by_file(&app.todos) {
+        items.push(ListItem::new(Line::from(Span::styled(
+            format!("{} ({})", path.display(), todos.len()),
+            Style::default().fg(theme.directory).add_modifier(Modifier::BOLD),
+        ))));
+        for item in todos {
+            let kind_color = match item.kind.as_str() {
+                "FIXME" => Color::Red,
+                "HACK" => Color::Yellow,
+                _ => Color::Cyan,
+            };
+            items.push(ListItem::new(Line::from(vec![
+                Span::styled(format!("  {:>5}  ", item.line), Style::default().fg(theme.line_number)),
+                Span::styled(format!("{} ", item.kind), Style::default().fg(kind_color).add_modifier(Modifier::BOLD)),
+                Span::raw(item.text.clone()),
+            ])));
+        }
+    }
+
+    if items.is_empty() {
+        let message = if app.todos_scanning { "Scanning workspace..." } else { "No TODO, FIXME or HACK comments found." };
+        f.render_widget(Paragraph::new(message).style(Style::default().fg(theme.line_number)), inner);
+        return;
+    }
+
+    let selected_row = app.todo_rows().iter().position(|r| *r == Some(app.todos_selected));
+    app.todos_list_state.select(selected_row);
+    let list = List::new(items)
+        .highlight_style(Style::default().bg(app.current_theme.selection_bg).fg(app.current_theme.selection_fg));
+    f.render_stateful_widget(list, inner, &mut app.todos_list_state);
+}
+
+/// Render the color picker: a preview swatch and one bar per channel
+fn render_color_picker(f: &mut Frame, app: &App, picker: &ColorPicker) {
+    let screen = f.area();
+    let area = Rect::new(
+        screen.x + screen.width.saturating_sub(62) / 2,
+        screen.y + screen.height.saturating_sub(9) / 2,
+        62.min(screen.width),
+        9.min(screen.height),
+    );
+    f.render_widget(Clear, area);
+    let theme = &app.current_theme;
+    let block = Block::default()
+        .title(" Edit Color ")
+        .title_bottom(" ←/→: Adjust (Shift: ×16), ↑/↓: Channel, Enter: Apply, Esc: Cancel ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.border_active))
+        .style(Style::default().bg(theme.background).fg(theme.foreground));
+    let [r, g, b] = picker.rgb;
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled("        ", Style::default().bg(Color::Rgb(r, g, b))),
+            Span::raw(format!("  {}", picker.literal_text())),
+        ]),
+        Line::from(""),
+    ];
+    const BAR_WIDTH: usize = 24;
+    for (i, (name, color)) in [("R", Color::Red), ("G", Color::Green), ("B", Color::Blue)].into_iter().enumerate() {
+        let value = picker.rgb[i];
+        let filled = value as usize * BAR_WIDTH / 255;
+        let marker = if i == picker.channel { "▶ " } else { "  " };
+        lines.push(Line::from(vec![
+            Span::styled(format!("{}{} ", marker, name), Style::default().add_modifier(if i == picker.channel { Modifier::BOLD } else { Modifier::empty() })),
+            Span::styled("█".repeat(filled), Style::default().fg(color)),
+            Span::styled("░".repeat(BAR_WIDTH - filled), Style::default().fg(theme.line_number)),
+            Span::raw(format!(" {:>3}", value)),
+        ]));
+    }
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// Render the workspace statistics: languages, largest files and recent commits
+fn render_stats_panel(f: &mut Frame, app: &App) {
+    let area = centered_rect(70, 70, f.area());
+    f.render_widget(Clear, area);
+    let theme = &app.current_theme;
+    let status = if app.stats_loading { " (collecting...)" } else { "" };
+    let block = Block::default()
+        .title(format!(" Workspace Stats{} (c: Add to Chat, r: Refresh, Esc: Close) ", status))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.border_active))
+        .style(Style::default().bg(theme.background).fg(theme.foreground));
+
+    let Some(stats) = &app.stats else {
+        f.render_widget(Paragraph::new("Collecting workspace statistics...").style(Style::default().fg(theme.line_number)).block(block), area);
+        return;
+    };
+    let heading = |text: &str| Line::from(Span::styled(text.to_string(), Style::default().fg(theme.directory).add_modifier(Modifier::BOLD)));
+    let dim = Style::default().fg(theme.line_number);
+
+    let mut lines = vec![
+        Line::from(format!("{} files, {} lines", stats.total_files, stats.total_lines)),
+        Line::from(""),
+        heading("Languages"),
+    ];
+    for lang in &stats.languages {
+        lines.push(Line::from(vec![
+            Span::raw(format!("  {:<24}", lang.language)),
+            Span::styled(format!("{:>6} files {:>9} lines", lang.files, lang.lines), dim),
+        ]));
+    }
+    lines.push(Line::from(""));
+    lines.push(heading("Largest Files"));
+    for (path, size) in &stats.largest_files {
+        lines.push(Line::from(vec![
+            Span::styled(format!("  {:>10}  ", crate::shared::stats::format_size(*size)), dim),
+            Span::raw(path.display().to_string()),
+        ]));
+    }
+    lines.push(Line::from(""));
+    lines.push(heading("Recent Commits"));
+    if stats.recent_commits.is_empty() {
+        lines.push(Line::from(Span::styled("  (not a git repository)", dim)));
+    }
+    for commit in &stats.recent_commits {
+        lines.push(Line::from(format!("  {}", commit)));
+    }
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// Render the proposed refactor: old and new line for each edit, grouped by file
+fn render_refactor_panel(f: &mut Frame, app: &mut App) {
+    let area = centered_rect(80, 70, f.area());
+    f.render_widget(Clear, area);
+
+    let accepted = app.refactor_edits.iter().filter(|e| e.accepted).count();
+    let block = Block::default()
+        .title(format!(
+            " Refactor: {}/{} edits accepted (Space: Toggle, a: Toggle All, Enter: Apply, Esc: Discard) ",
+            accepted,
+            app.refactor_edits.len(),
+        ))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(app.current_theme.border_active))
+        .style(Style::default().bg(app.current_theme.background).fg(app.current_theme.foreground));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let theme = &app.current_theme;
+    let mut items = Vec::new();
+    for (path, edits) in crate::shared::refactor::group_by_file(&app.refactor_edits) {
+        items.push(ListItem::new(Line::from(Span::styled(
+            format!("{} ({})", path.display(), edits.len()),
+            Style::default().fg(theme.directory).add_modifier(Modifier::BOLD),
+        ))));
+        for edit in edits {
+            let (mark, dim) = if edit.accepted { ("[x]", Modifier::empty()) } else { ("[ ]", Modifier::DIM) };
+            let number = Span::styled(format!("  {} {:>5}  ", mark, edit.line), Style::default().fg(theme.line_number));
+            let pad = Span::raw(" ".repeat(number.width()));
+            items.push(ListItem::new(vec![
+                Line::from(vec![number, Span::styled(format!("- {}", edit.old), Style::default().fg(Color::Red).add_modifier(dim))]),
+                Line::from(vec![pad, Span::styled(format!("+ {}", edit.new), Style::default().fg(Color::Green).add_modifier(dim))]),
+            ]));
+        }
+    }
+
+    let selected_row = app.refactor_rows().iter().position(|r| *r == Some(app.refactor_selected));
+    app.refactor_list_state.select(selected_row);
+    let list = List::new(items)
+        .highlight_style(Style::default().bg(app.current_theme.selection_bg));
+    f.render_stateful_widget(list, inner, &mut app.refactor_list_state);
+}
+
+/// Render the full content of the selected AI file proposal for approval
+fn render_proposals_panel(f: &mut Frame, app: &App) {
+    let Some(proposal) = app.file_proposals.get(app.proposal_selected) else { return };
+    let area = centered_rect(80, 80, f.area());
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(format!(
+            " New file {}/{}: {} ({} lines) ",
+            app.proposal_selected + 1,
+            app.file_proposals.len(),
+            proposal.path.display(),
+            proposal.content.lines().count(),
+        ))
+        .title_bottom(" y: Create, n: Skip, Left/Right: Switch, Up/Down/PgUp/PgDn: Scroll, Esc: Discard All ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(app.current_theme.border_active))
+        .style(Style::default().bg(app.current_theme.background).fg(app.current_theme.foreground));
+
+    let width = proposal.content.lines().count().max(1).to_string().len();
+    let lines: Vec<Line> = proposal.content.lines().enumerate()
+        .map(|(i, line)| Line::from(vec![
+            Span::styled(format!("{:>width$} ", i + 1, width = width), Style::default().fg(app.current_theme.line_number)),
+            Span::raw(line.to_string()),
+        ]))
+        .collect();
+    f.render_widget(Paragraph::new(lines).block(block).scroll((app.proposal_scroll, 0)), area);
+}
+
+/// Render the problems panel as a modal list of diagnostics
+fn render_problems_panel(f: &mut Frame, app: &mut App) {
+    use crate::shared::diagnostics::Severity;
+
+    let area = centered_rect(80, 60, f.area());
+    f.render_widget(Clear, area);
+
+    let filter = match app.problems_min_severity {
+        Severity::Error => "Errors",
+        Severity::Warning => "Errors + Warnings",
+        _ => "All",
+    };
+    let title = format!(
+        " Problems: {} errors, {} warnings [{}] (Tab: Filter, r: Refresh, Enter: Jump, Esc: Close) ",
+        app.diagnostics.count(Severity::Error),
+        app.diagnostics.count(Severity::Warning),
+        filter,
+    );
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(app.current_theme.border_active))
+        .style(Style::default().bg(app.current_theme.background).fg(app.current_theme.foreground));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let theme = &app.current_theme;
+    let items: Vec<ListItem> = app.visible_problems().iter()
+        .map(|d| {
+            let color = match d.severity {
+                Severity::Error => Color::Red,
+                Severity::Warning => Color::Yellow,
+                _ => theme.line_number,
+            };
+            let code = d.code.as_ref().map(|c| format!("[{}] ", c)).unwrap_or_default();
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{} ", d.severity.marker()), Style::default().fg(color).add_modifier(Modifier::BOLD)),
+                Span::styled(format!("{} ", d.location()), Style::default().fg(theme.directory)),
+                Span::raw(format!("{}{}", code, d.message)),
+                Span::styled(format!("  ({})", d.source.label()), Style::default().fg(theme.line_number)),
+            ]))
+        })
+        .collect();
+
+    if items.is_empty() {
+        f.render_widget(
+            Paragraph::new("No problems found. Run a build in the terminal and press r to refresh.")
+                .style(Style::default().fg(app.current_theme.line_number)),
+            inner,
+        );
+        return;
+    }
+
+    let list = List::new(items)
+        .highlight_style(Style::default().bg(app.current_theme.selection_bg).fg(app.current_theme.selection_fg));
+    f.render_stateful_widget(list, inner, &mut app.problems_state);
+}
+
+/// Render the settings panel in the editor area with two-column form layout
+fn render_settings_panel(f: &mut Frame, app: &mut App, area: Rect) {
+    let block = Block::default()
+        .title(" Settings ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(app.current_theme.border_active))
+        .style(Style::default().bg(app.current_theme.background).fg(app.current_theme.foreground));
+
+    f.render_widget(block, area);
+
+    let chunks = settings_chunks(area);
+    let list_area = chunks[1];
 
     // Header with keyboard shortcuts
     let header = Line::from(vec![
@@ -406,6 +1860,8 @@ fn render_settings_panel(f: &mut Frame, app: &mut App, area: Rect) {
         Span::styled(" Edit  ", Style::default().fg(app.current_theme.line_number)),
         Span::styled("Space", Style::default().fg(app.current_theme.directory).add_modifier(Modifier::BOLD)),
         Span::styled(" Set Active  ", Style::default().fg(app.current_theme.line_number)),
+        Span::styled("t", Style::default().fg(app.current_theme.directory).add_modifier(Modifier::BOLD)),
+        Span::styled(" Test  ", Style::default().fg(app.current_theme.line_number)),
         Span::styled("Tab", Style::default().fg(app.current_theme.directory).add_modifier(Modifier::BOLD)),
         Span::styled(" Theme  ", Style::default().fg(app.current_theme.line_number)),
         Span::styled("Esc", Style::default().fg(app.current_theme.directory).add_modifier(Modifier::BOLD)),
@@ -414,13 +1870,9 @@ fn render_settings_panel(f: &mut Frame, app: &mut App, area: Rect) {
     f.render_widget(Paragraph::new(header), chunks[0]);
 
     // Model list area
-    let list_area = chunks[1];
     let visible_height = list_area.height as usize;
     let total_models = app.config.models.len();
-
-    // Each model card takes 4 lines (top border, content row 1, content row 2, bottom border)
-    // But we can share borders between adjacent cards
-    let lines_per_model = 3usize; // top border shared, 2 content lines, bottom becomes next top
+    let lines_per_model = SETTINGS_CARD_LINES;
 
     // Update scroll to keep selected model visible
     let selected_start_line = app.settings_model_idx * lines_per_model;
@@ -534,9 +1986,26 @@ fn render_settings_panel(f: &mut Frame, app: &mut App, area: Rect) {
         ]);
         all_lines.push(row2);
 
-        // Bottom border
-        let bottom_border = format!("└{}┘", "─".repeat(total_width.saturating_sub(2) as usize));
-        all_lines.push(Line::from(Span::styled(bottom_border, border_style)));
+        // Bottom border carries the "Test connection" button and its last result
+        let button_style = if is_selected {
+            Style::default().fg(app.current_theme.selection_fg).bg(app.current_theme.selection_bg)
+        } else {
+            Style::default().fg(app.current_theme.line_number)
+        };
+        let (result, result_style) = match app.settings_health.get(&i) {
+            Some(ConnectionTest::Testing) => (" testing... ".to_string(), Style::default().fg(app.current_theme.line_number)),
+            Some(ConnectionTest::Passed(latency)) => (format!(" ✓ {} ms ", latency.as_millis()), Style::default().fg(Color::Green)),
+            Some(ConnectionTest::Failed(e)) => (format!(" ✗ {} ", e), Style::default().fg(Color::Red)),
+            None => (String::new(), Style::default()),
+        };
+        let button = SETTINGS_TEST_BUTTON;
+        let used = 3 + button.chars().count() + result.chars().count() + 1;
+        all_lines.push(Line::from(vec![
+            Span::styled("└─ ", border_style),
+            Span::styled(button, button_style),
+            Span::styled(result, result_style),
+            Span::styled(format!("{}┘", "─".repeat((total_width as usize).saturating_sub(used))), border_style),
+        ]));
     }
 
     // Apply scroll offset and render visible lines
@@ -550,10 +2019,8 @@ fn render_settings_panel(f: &mut Frame, app: &mut App, area: Rect) {
 
     // If editing, render the TextArea over the API key value
     if app.settings_editing {
-        // Find the API key line position (each model has 4 lines: top, row1, row2, bottom)
-        // API key is in row2, which is index 2 within each model's lines
-        let lines_per_model_with_border = 4usize;
-        let selected_api_line = app.settings_model_idx * lines_per_model_with_border + 2;
+        // The API key is the second row of the card, after its top border
+        let selected_api_line = app.settings_model_idx * SETTINGS_CARD_LINES + 2;
         let line_in_view = selected_api_line.saturating_sub(app.settings_scroll_offset);
 
         if line_in_view < visible_height {
@@ -586,7 +2053,7 @@ fn render_settings_panel(f: &mut Frame, app: &mut App, area: Rect) {
     f.render_widget(Paragraph::new(footer), chunks[2]);
 
     // Scrollbar
-    let total_lines = total_models * 4; // 4 lines per model with borders
+    let total_lines = total_models * SETTINGS_CARD_LINES;
     let mut scroll_state = ratatui::widgets::ScrollbarState::default()
         .content_length(total_lines)
         .position(app.settings_scroll_offset);
@@ -602,6 +2069,37 @@ fn render_settings_panel(f: &mut Frame, app: &mut App, area: Rect) {
 }
 
 /// Parse markdown text and return styled Lines for rendering
+/// Chat messages as styled lines: role badge, metadata (time, model, tokens), then the markdown body
+/// Long messages are folded to their first lines unless their index is in `expanded`
+fn chat_message_lines(messages: &[ChatMessage], theme: &Theme, show_timestamps: bool, expanded: &HashSet<usize>) -> Vec<Line<'static>> {
+    let mut lines = Vec::new();
+    for (i, message) in messages.iter().enumerate() {
+        if i > 0 {
+            lines.push(Line::default());
+        }
+        let transcript = message.transcript_line();
+        let (text, hidden) = chat::render_text(&transcript, expanded.contains(&i));
+        let mut message_lines = parse_markdown_to_lines(&text, theme);
+        if hidden > 0 {
+            message_lines.push(Line::from(Span::styled(
+                format!("▸ {} more lines (Ctrl+O: show more)", hidden),
+                Style::default().fg(theme.line_number).add_modifier(Modifier::ITALIC),
+            )));
+        }
+        let meta = message.meta(show_timestamps);
+        if let Some(first) = message_lines.first_mut().filter(|_| !meta.is_empty()) {
+            // The body after a badge already starts with a space
+            let (at, text) = match message.role.label() {
+                Some(_) => (1.min(first.spans.len()), format!("[{}]", meta)),
+                None => (0, format!("[{}] ", meta)),
+            };
+            first.spans.insert(at, Span::styled(text, Style::default().fg(theme.line_number)));
+        }
+        lines.extend(message_lines);
+    }
+    lines
+}
+
 fn parse_markdown_to_lines(text: &str, theme: &Theme) -> Vec<Line<'static>> {
     let mut lines: Vec<Line<'static>> = Vec::new();
     let mut in_code_block = false;
@@ -661,28 +2159,28 @@ fn parse_markdown_to_lines(text: &str, theme: &Theme) -> Vec<Line<'static>> {
         }
 
         // Handle headers
-        if line.starts_with("### ") {
+        if let Some(header) = line.strip_prefix("### ") {
             lines.push(Line::from(vec![
                 Span::styled(
-                    line[4..].to_string(),
+                    header.to_string(),
                     Style::default()
                         .fg(theme.border_active)
                         .add_modifier(Modifier::BOLD),
                 ),
             ]));
-        } else if line.starts_with("## ") {
+        } else if let Some(header) = line.strip_prefix("## ") {
             lines.push(Line::from(vec![
                 Span::styled(
-                    line[3..].to_string(),
+                    header.to_string(),
                     Style::default()
                         .fg(theme.border_active)
                         .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
                 ),
             ]));
-        } else if line.starts_with("# ") {
+        } else if let Some(header) = line.strip_prefix("# ") {
             lines.push(Line::from(vec![
                 Span::styled(
-                    line[2..].to_string(),
+                    header.to_string(),
                     Style::default()
                         .fg(theme.selection_fg)
                         .bg(theme.selection_bg)
@@ -714,8 +2212,7 @@ fn parse_markdown_to_lines(text: &str, theme: &Theme) -> Vec<Line<'static>> {
             }
         }
         // Handle "You:" prefix (user messages)
-        else if line.starts_with("You:") {
-            let rest = if line.len() > 4 { &line[4..] } else { "" };
+        else if let Some(rest) = line.strip_prefix("You:") {
             // Check if the rest of the message contains inline markdown
             let rest_spans = if rest.contains('`') || rest.contains("**") {
                 parse_inline_markdown(rest, theme)
@@ -735,8 +2232,7 @@ fn parse_markdown_to_lines(text: &str, theme: &Theme) -> Vec<Line<'static>> {
             lines.push(Line::from(spans));
         }
         // Handle "AI:" prefix (AI messages)
-        else if line.starts_with("AI:") {
-            let rest = if line.len() > 3 { &line[3..] } else { "" };
+        else if let Some(rest) = line.strip_prefix("AI:") {
             // Check if the rest of the message contains inline markdown
             let rest_spans = if rest.contains('`') || rest.contains("**") {
                 parse_inline_markdown(rest, theme)
@@ -881,7 +2377,7 @@ mod tests {
     #[test]
     fn test_get_layout_chunks() {
         let area = Rect::new(0, 0, 100, 100);
-        let layout = get_layout_chunks(area, &ActivePanel::Editor);
+        let layout = get_layout_chunks(area, &ActivePanel::Editor, 1);
 
         // Check if areas are contained within main area
         assert!(layout.menu.area() > 0);
@@ -894,6 +2390,43 @@ mod tests {
         // Basic split checks
         assert_eq!(layout.menu.y, 0);
         assert_eq!(layout.menu.height, 1);
+
+        // The chat input grows with its content, up to half the column
+        let grown = get_layout_chunks(area, &ActivePanel::Chat, 30);
+        assert!(grown.chat_input.height > layout.chat_input.height);
+        assert!(grown.chat_input.height <= 50);
+    }
+
+    #[test]
+    fn test_layout_minimums() {
+        // Narrow windows hide the chat, then the file tree
+        let narrow = get_layout_chunks(Rect::new(0, 0, 70, 30), &ActivePanel::Editor, 1);
+        assert_eq!((narrow.chat_history.area(), narrow.chat_input.area()), (0, 0));
+        assert_eq!((narrow.file_tree.width, narrow.editor.width), (14, 56));
+        let narrower = get_layout_chunks(Rect::new(0, 0, 50, 30), &ActivePanel::Editor, 1);
+        assert_eq!((narrower.file_tree.area(), narrower.editor.width), (0, 50));
+        // ...unless focused, when the other one goes instead
+        let chat = get_layout_chunks(Rect::new(0, 0, 50, 30), &ActivePanel::Chat, 1);
+        assert_eq!((chat.file_tree.area(), chat.editor.width, chat.chat_input.width), (0, 32, 18));
+
+        // Short windows hide the terminal, or the editor while the terminal is focused
+        let short = get_layout_chunks(Rect::new(0, 0, 100, 12), &ActivePanel::Editor, 1);
+        assert_eq!((short.terminal.area(), short.editor.height), (0, 11));
+        let terminal = get_layout_chunks(Rect::new(0, 0, 100, 12), &ActivePanel::Terminal, 1);
+        assert_eq!((terminal.editor.area(), terminal.terminal.height), (0, 11));
+
+        assert!(too_small(Rect::new(0, 0, 39, 40)) && too_small(Rect::new(0, 0, 120, 9)));
+        assert_eq!(get_layout_chunks(Rect::new(0, 0, 30, 8), &ActivePanel::Editor, 1).editor.area(), 0);
+    }
+
+    #[test]
+    fn test_zen_layout() {
+        let layout = zen_layout(Rect::new(0, 0, 200, 50));
+        assert_eq!(layout.editor, Rect::new(55, 1, ZEN_WIDTH, 48));
+        assert_eq!(layout.terminal.area(), 0);
+        assert_eq!(layout.file_tree.area(), 0);
+        // Narrow screens get the full width
+        assert_eq!(zen_layout(Rect::new(0, 0, 60, 20)).editor.width, 60);
     }
 
     #[test]
@@ -907,9 +2440,18 @@ mod tests {
         assert_eq!(center.y, 25);
     }
 
+    #[test]
+    fn test_modal_hit_areas() {
+        let screen = Rect::new(0, 0, 100, 40);
+        assert_eq!(file_search_area(screen), Rect::new(20, 10, 60, 20));
+        // Below the border, the 3-row query input and the list's top border
+        assert_eq!(file_search_results_area(screen), Rect::new(21, 15, 58, 14));
+        assert_eq!(settings_list_area(Rect::new(0, 1, 80, 30)), Rect::new(1, 3, 78, 26));
+    }
+
     #[test]
     fn test_parse_markdown_to_lines() {
-        let theme = Theme::new(crate::theme::ThemeMode::Dark);
+        let theme = Theme::new(crate::shared::ThemeMode::Dark);
 
         // Test "You:" prefix
         let lines = parse_markdown_to_lines("You: Hello world", &theme);
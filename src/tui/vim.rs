@@ -0,0 +1,357 @@
+// Vim-style modal editing on top of EditorState
+
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use super::editor::{next_grapheme, prev_grapheme, EditorCommand, EditorState};
+use super::keymap::EditorKeyMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VimMode {
+    Normal,
+    Insert,
+    Visual,
+}
+
+impl VimMode {
+    /// Label shown in the editor border
+    pub fn label(&self) -> &'static str {
+        match self {
+            VimMode::Normal => "NORMAL",
+            VimMode::Insert => "INSERT",
+            VimMode::Visual => "VISUAL",
+        }
+    }
+}
+
+/// Text yanked or deleted by the last `y`/`d`/`x`
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Register {
+    text: String,
+    /// Whole lines (`dd`/`yy`), pasted above/below the cursor line
+    linewise: bool,
+}
+
+/// Modal state; the buffer itself stays in EditorState
+pub struct Vim {
+    pub mode: VimMode,
+    /// First key of a two-key command (`dd`, `yy`, `gg`)
+    pending: Option<char>,
+    /// Where visual mode started, as (row, col)
+    anchor: (usize, usize),
+    register: Register,
+}
+
+impl Default for Vim {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Vim {
+    pub fn new() -> Self {
+        Self {
+            mode: VimMode::Normal,
+            pending: None,
+            anchor: (0, 0),
+            register: Register::default(),
+        }
+    }
+
+    /// Handle a key pressed in the editor; `keys` applies in insert mode, and
+    /// its line move/duplicate bindings in every mode
+    pub fn handle_key(&mut self, editor: &mut EditorState, key: KeyEvent, keys: &EditorKeyMap) {
+        if self.mode != VimMode::Insert {
+            if let Some(&command) = keys.get(&(key.code, key.modifiers)) {
+                if self.line_command(editor, command) {
+                    return;
+                }
+            }
+        }
+        match self.mode {
+            VimMode::Insert => self.insert_key(editor, key, keys),
+            VimMode::Normal => self.normal_key(editor, key),
+            VimMode::Visual => self.visual_key(editor, key),
+        }
+    }
+
+    /// Whether `handle_key` would change the text for this key
+    pub fn is_edit_key(&self, key: KeyEvent, keys: &EditorKeyMap) -> bool {
+        if self.mode == VimMode::Insert {
+            return key.code != KeyCode::Esc && EditorState::is_edit_key(key, keys);
+        }
+        let line_command = keys.get(&(key.code, key.modifiers)).is_some_and(|command| {
+            matches!(
+                command,
+                EditorCommand::MoveLineUp | EditorCommand::MoveLineDown | EditorCommand::DuplicateLine
+                    | EditorCommand::Indent | EditorCommand::Dedent
+            )
+        });
+        if line_command {
+            return true;
+        }
+        if key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
+            return false;
+        }
+        match self.mode {
+            VimMode::Visual => matches!(key.code, KeyCode::Char('d' | 'x')),
+            // `zo` opens a fold, it doesn't insert a line
+            _ => match (self.pending, key.code) {
+                (Some('d'), KeyCode::Char('d')) => true,
+                (Some('z'), _) => false,
+                (_, code) => matches!(code, KeyCode::Char('x' | 'o' | 'O' | 'p' | 'P') | KeyCode::Delete),
+            },
+        }
+    }
+
+    /// The visual selection as (start, end) positions, `end` exclusive
+    pub fn selection(&self, editor: &EditorState) -> Option<((usize, usize), (usize, usize))> {
+        if self.mode != VimMode::Visual {
+            return None;
+        }
+        let cursor = (editor.cursor_row, editor.cursor_col);
+        let (start, end) = if self.anchor <= cursor { (self.anchor, cursor) } else { (cursor, self.anchor) };
+        let end_len = editor.lines.get(end.0).map(|l| l.chars().count()).unwrap_or(0);
+        Some((start, (end.0, (end.1 + 1).min(end_len))))
+    }
+
+    fn insert_key(&mut self, editor: &mut EditorState, key: KeyEvent, keys: &EditorKeyMap) {
+        if key.code == KeyCode::Esc {
+            self.mode = VimMode::Normal;
+            // Like vim, leaving insert mode steps back onto the last inserted char
+            editor.cursor_col = editor.cursor_col.saturating_sub(1);
+            return;
+        }
+        editor.input(key, keys);
+    }
+
+    /// Cursor motions shared by normal and visual mode; returns whether the key was one
+    fn motion(editor: &mut EditorState, key: KeyEvent, pending: Option<char>) -> bool {
+        match key.code {
+            // h/l stay on the current line, unlike the arrow keys
+            KeyCode::Char('h') => editor.cursor_col = prev_grapheme(editor.current_line(), editor.cursor_col),
+            KeyCode::Char('l') => editor.cursor_col = next_grapheme(editor.current_line(), editor.cursor_col).min(editor.current_line_len()),
+            KeyCode::Left => editor.move_cursor_left(),
+            KeyCode::Char('j') | KeyCode::Down => editor.move_cursor_down(),
+            KeyCode::Char('k') | KeyCode::Up => editor.move_cursor_up(),
+            KeyCode::Right => editor.move_cursor_right(),
+            KeyCode::Char('w') => editor.move_word_forward(),
+            KeyCode::Char('b') => editor.move_word_backward(),
+            KeyCode::Char('0') | KeyCode::Home => editor.move_cursor_home(),
+            KeyCode::Char('$') | KeyCode::End => editor.move_cursor_end(),
+            KeyCode::Char('G') => editor.goto(editor.line_count().saturating_sub(1), 0),
+            KeyCode::Char('%') => {
+                editor.jump_to_matching_bracket();
+            }
+            KeyCode::Char('g') if pending == Some('g') => editor.goto(0, 0),
+            KeyCode::PageUp => editor.page_up(20),
+            KeyCode::PageDown => editor.page_down(20),
+            _ => return false,
+        }
+        true
+    }
+
+    fn normal_key(&mut self, editor: &mut EditorState, key: KeyEvent) {
+        if key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
+            return;
+        }
+        let pending = self.pending.take();
+        if Self::motion(editor, key, pending) {
+            return;
+        }
+
+        match (pending, key.code) {
+            (Some('d'), KeyCode::Char('d')) => {
+                let removed = editor.delete_lines(editor.cursor_row, 1);
+                self.register = Register { text: removed.join("\n"), linewise: true };
+            }
+            (Some('y'), KeyCode::Char('y')) => {
+                self.register = Register { text: editor.current_line().to_string(), linewise: true };
+            }
+            (Some('z'), KeyCode::Char('a')) => {
+                editor.toggle_fold();
+            }
+            (Some('z'), KeyCode::Char('o')) => {
+                editor.unfold();
+            }
+            (Some('z'), KeyCode::Char('c')) => {
+                editor.fold();
+            }
+            (Some('z'), KeyCode::Char('R')) => editor.unfold_all(),
+            (None, KeyCode::Char(c @ ('d' | 'y' | 'g' | 'z'))) => self.pending = Some(c),
+            (_, KeyCode::Char('i')) => self.mode = VimMode::Insert,
+            (_, KeyCode::Char('a')) => {
+                editor.cursor_col = (editor.cursor_col + 1).min(editor.current_line_len());
+                self.mode = VimMode::Insert;
+            }
+            (_, KeyCode::Char('A')) => {
+                editor.move_cursor_end();
+                self.mode = VimMode::Insert;
+            }
+            (_, KeyCode::Char('I')) => {
+                editor.move_cursor_home();
+                self.mode = VimMode::Insert;
+            }
+            (_, KeyCode::Char('o')) => {
+                editor.move_cursor_end();
+                editor.insert_newline();
+                self.mode = VimMode::Insert;
+            }
+            (_, KeyCode::Char('O')) => {
+                editor.insert_lines(editor.cursor_row, vec![String::new()]);
+                editor.cursor_col = 0;
+                self.mode = VimMode::Insert;
+            }
+            (_, KeyCode::Char('v')) => {
+                self.anchor = (editor.cursor_row, editor.cursor_col);
+                self.mode = VimMode::Visual;
+            }
+            (_, KeyCode::Char('x') | KeyCode::Delete) if editor.cursor_col < editor.current_line_len() => {
+                let start = (editor.cursor_row, editor.cursor_col);
+                let end = (editor.cursor_row, editor.cursor_col + 1);
+                self.register = Register { text: editor.text_range(start, end), linewise: false };
+                editor.delete();
+            }
+            (_, KeyCode::Char('p')) => self.put(editor, true),
+            (_, KeyCode::Char('P')) => self.put(editor, false),
+            _ => {}
+        }
+    }
+
+    fn visual_key(&mut self, editor: &mut EditorState, key: KeyEvent) {
+        let pending = self.pending.take();
+        if Self::motion(editor, key, pending) {
+            return;
+        }
+
+        match key.code {
+            KeyCode::Char('g') => self.pending = Some('g'),
+            KeyCode::Esc | KeyCode::Char('v') => self.mode = VimMode::Normal,
+            KeyCode::Char('y') => {
+                if let Some((start, end)) = self.selection(editor) {
+                    self.register = Register { text: editor.text_range(start, end), linewise: false };
+                    editor.goto(start.0, start.1);
+                }
+                self.mode = VimMode::Normal;
+            }
+            KeyCode::Char('d') | KeyCode::Char('x') => {
+                if let Some((start, end)) = self.selection(editor) {
+                    self.register = Register { text: editor.text_range(start, end), linewise: false };
+                    editor.delete_range(start, end);
+                }
+                self.mode = VimMode::Normal;
+            }
+            _ => {}
+        }
+    }
+
+    /// Move, duplicate or indent the cursor line, or every line of the visual
+    /// selection. Returns false for other commands.
+    fn line_command(&mut self, editor: &mut EditorState, command: EditorCommand) -> bool {
+        let (start, end) = match self.selection(editor) {
+            Some((start, end)) => (start.0, end.0),
+            None => (editor.cursor_row, editor.cursor_row),
+        };
+        match command {
+            EditorCommand::MoveLineUp | EditorCommand::MoveLineDown => {
+                let down = command == EditorCommand::MoveLineDown;
+                // The selection's anchor moves with the text
+                if editor.move_lines(start, end, down) && self.mode == VimMode::Visual {
+                    self.anchor.0 = if down { self.anchor.0 + 1 } else { self.anchor.0 - 1 };
+                }
+            }
+            EditorCommand::DuplicateLine => {
+                editor.duplicate_lines(start, end);
+                if self.mode == VimMode::Visual {
+                    self.anchor.0 += end - start + 1;
+                }
+            }
+            EditorCommand::Indent | EditorCommand::Dedent => {
+                let rows: Vec<usize> = (start..=end).collect();
+                editor.shift_indent(&rows, command == EditorCommand::Indent);
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Paste the register after (`p`) or before (`P`) the cursor
+    fn put(&self, editor: &mut EditorState, after: bool) {
+        if self.register.text.is_empty() && !self.register.linewise {
+            return;
+        }
+        if self.register.linewise {
+            let row = if after { editor.cursor_row + 1 } else { editor.cursor_row };
+            let lines = self.register.text.split('\n').map(str::to_string).collect();
+            editor.insert_lines(row, lines);
+            editor.goto(row, 0);
+        } else {
+            if after {
+                editor.cursor_col = (editor.cursor_col + 1).min(editor.current_line_len());
+            }
+            editor.paste(&self.register.text);
+            editor.cursor_col = editor.cursor_col.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    use crate::shared::KeybindingMode;
+    use crate::tui::keymap::editor_key_map;
+
+    fn press(vim: &mut Vim, editor: &mut EditorState, keys: &str) {
+        let map = editor_key_map(KeybindingMode::Vim);
+        for c in keys.chars() {
+            vim.handle_key(editor, KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE), &map);
+        }
+    }
+
+    fn buffer(lines: &[&str]) -> EditorState {
+        let mut editor = EditorState::new();
+        editor.lines = lines.iter().map(|l| l.to_string()).collect();
+        editor
+    }
+
+    #[test]
+    fn test_dd_and_p() {
+        let mut editor = buffer(&["one", "two", "three"]);
+        let mut vim = Vim::new();
+        press(&mut vim, &mut editor, "dd");
+        assert_eq!(editor.lines, vec!["two", "three"]);
+        press(&mut vim, &mut editor, "p");
+        assert_eq!(editor.lines, vec!["two", "one", "three"]);
+        press(&mut vim, &mut editor, "ggyyGp");
+        assert_eq!(editor.lines, vec!["two", "one", "three", "two"]);
+    }
+
+    #[test]
+    fn test_insert_and_visual() {
+        let mut editor = buffer(&["hello world"]);
+        let mut vim = Vim::new();
+        press(&mut vim, &mut editor, "ix");
+        assert_eq!(vim.mode, VimMode::Insert);
+        vim.handle_key(&mut editor, KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE), &HashMap::new());
+        assert_eq!(vim.mode, VimMode::Normal);
+        assert_eq!(editor.lines, vec!["xhello world"]);
+
+        press(&mut vim, &mut editor, "wvlld");
+        assert_eq!(editor.lines, vec!["xhello ld"]);
+        assert_eq!(vim.mode, VimMode::Normal);
+        press(&mut vim, &mut editor, "0P");
+        assert_eq!(editor.lines, vec!["worxhello ld"]);
+    }
+
+    #[test]
+    fn test_move_visual_selection() {
+        let mut editor = buffer(&["one", "two", "three"]);
+        let mut vim = Vim::new();
+        let map = editor_key_map(KeybindingMode::Vim);
+        press(&mut vim, &mut editor, "vj");
+        vim.handle_key(&mut editor, KeyEvent::new(KeyCode::Down, KeyModifiers::ALT), &map);
+        assert_eq!(editor.lines, vec!["three", "one", "two"]);
+        assert_eq!(vim.selection(&editor).map(|(start, end)| (start.0, end.0)), Some((1, 2)));
+    }
+}
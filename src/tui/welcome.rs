@@ -0,0 +1,82 @@
+// Welcome view shown in the editor area until a file is opened: recent files,
+// recent workspaces and a shortcut to the terminal
+
+use std::path::{Path, PathBuf};
+
+use crate::shared::Config;
+
+/// Recent files listed for the current workspace
+const MAX_FILES: usize = 8;
+
+/// Other recent workspaces listed
+const MAX_WORKSPACES: usize = 5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WelcomeItem {
+    /// Focus the terminal panel
+    Terminal,
+    /// A recently opened file, relative to the workspace
+    File(PathBuf),
+    /// Another recent workspace, browsed in the file tree
+    Workspace(PathBuf),
+}
+
+pub struct Welcome {
+    pub items: Vec<WelcomeItem>,
+    pub selected: usize,
+}
+
+impl Welcome {
+    pub fn new(config: &Config, workspace: &Path) -> Self {
+        let current = config.recent_workspace(workspace);
+        let files = current.iter()
+            .flat_map(|w| &w.recent_files)
+            .filter(|f| workspace.join(f).is_file())
+            .take(MAX_FILES)
+            .map(|f| WelcomeItem::File(f.clone()));
+        let workspaces = config.get_recent_workspaces().iter()
+            .filter(|w| current.is_none_or(|c| c.path != w.path) && w.path.is_dir())
+            .take(MAX_WORKSPACES)
+            .map(|w| WelcomeItem::Workspace(w.path.clone()));
+        let items = std::iter::once(WelcomeItem::Terminal).chain(files).chain(workspaces).collect();
+        Self { items, selected: 0 }
+    }
+
+    pub fn select(&mut self, delta: isize) {
+        let last = self.items.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+    }
+
+    pub fn selected_item(&self) -> Option<&WelcomeItem> {
+        self.items.get(self.selected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_items() {
+        let root = std::env::temp_dir().join(format!("nterm-welcome-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("kept.rs"), "").unwrap();
+        let mut config = Config::from_json("{}").unwrap();
+        config.add_recent_workspace(std::env::temp_dir());
+        config.add_recent_workspace(root.clone());
+        config.add_recent_file(&root, PathBuf::from("deleted.rs"));
+        config.add_recent_file(&root, PathBuf::from("kept.rs"));
+
+        let mut welcome = Welcome::new(&config, &root);
+        assert_eq!(welcome.items, vec![
+            WelcomeItem::Terminal,
+            WelcomeItem::File(PathBuf::from("kept.rs")),
+            WelcomeItem::Workspace(std::env::temp_dir()),
+        ]);
+        welcome.select(5);
+        assert_eq!(welcome.selected, 2);
+        welcome.select(-1);
+        assert_eq!(welcome.selected_item(), Some(&WelcomeItem::File(PathBuf::from("kept.rs"))));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -1,6 +1,6 @@
 use std::fs;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use ratatui::{
     backend::Backend,
@@ -91,7 +91,7 @@ impl WorkspaceSelector {
                 .filter(|node| !node.name.starts_with('.'))
                 .collect();
 
-            dirs.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+            dirs.sort_by_key(|a| a.name.to_lowercase());
             self.browser_tree = dirs;
         }
 
@@ -101,7 +101,7 @@ impl WorkspaceSelector {
     fn update_visible_items(&mut self) {
         self.browser_visible_items.clear();
         for node in &self.browser_tree {
-            flatten_node(node, &mut self.browser_visible_items);
+            flatten_node(node, &mut self.browser_visible_items, false);
         }
         self.browser_scroll_state = self.browser_scroll_state.content_length(self.browser_visible_items.len());
     }
@@ -113,7 +113,7 @@ impl WorkspaceSelector {
 
         let target_path = self.browser_visible_items[idx].path.clone();
 
-        fn toggle_recursive(nodes: &mut Vec<FileNode>, target: &PathBuf) -> bool {
+        fn toggle_recursive(nodes: &mut [FileNode], target: &PathBuf) -> bool {
             for node in nodes.iter_mut() {
                 if &node.path == target {
                     node.toggle_expand();
@@ -130,19 +130,6 @@ impl WorkspaceSelector {
         self.update_visible_items();
     }
 
-    fn navigate_into(&mut self, idx: usize) {
-        if idx >= self.browser_visible_items.len() {
-            return;
-        }
-
-        let path = self.browser_visible_items[idx].path.clone();
-        if path.is_dir() {
-            self.current_path = path;
-            self.refresh_browser();
-            self.browser_state.select(Some(0));
-        }
-    }
-
     fn navigate_up(&mut self) {
         if let Some(parent) = self.current_path.parent() {
             self.current_path = parent.to_path_buf();
@@ -221,32 +208,28 @@ impl WorkspaceSelector {
                             }
                         }
                     },
-                    KeyCode::Right => {
-                        if self.active_section == SelectorSection::Browser {
-                            if let Some(i) = self.browser_state.selected() {
-                                if i < self.browser_visible_items.len() {
-                                    let item = &self.browser_visible_items[i];
-                                    if item.is_dir && !item.expanded {
-                                        self.toggle_expand(i);
-                                    }
+                    KeyCode::Right if self.active_section == SelectorSection::Browser => {
+                        if let Some(i) = self.browser_state.selected() {
+                            if i < self.browser_visible_items.len() {
+                                let item = &self.browser_visible_items[i];
+                                if item.is_dir && !item.expanded {
+                                    self.toggle_expand(i);
                                 }
                             }
                         }
                     }
-                    KeyCode::Left => {
-                        if self.active_section == SelectorSection::Browser {
-                            if let Some(i) = self.browser_state.selected() {
-                                if i < self.browser_visible_items.len() {
-                                    let item = &self.browser_visible_items[i];
-                                    if item.is_dir && item.expanded {
-                                        self.toggle_expand(i);
-                                    } else {
-                                        self.navigate_up();
-                                    }
+                    KeyCode::Left if self.active_section == SelectorSection::Browser => {
+                        if let Some(i) = self.browser_state.selected() {
+                            if i < self.browser_visible_items.len() {
+                                let item = &self.browser_visible_items[i];
+                                if item.is_dir && item.expanded {
+                                    self.toggle_expand(i);
+                                } else {
+                                    self.navigate_up();
                                 }
-                            } else {
-                                self.navigate_up();
                             }
+                        } else {
+                            self.navigate_up();
                         }
                     }
                     KeyCode::Enter => match self.active_section {
@@ -270,11 +253,9 @@ impl WorkspaceSelector {
                             }
                         }
                     },
-                    KeyCode::Char(' ') => {
+                    KeyCode::Char(' ') if self.active_section == SelectorSection::Browser => {
                         // Space to select current browsing directory
-                        if self.active_section == SelectorSection::Browser {
-                            self.selected_workspace = Some(self.current_path.clone());
-                        }
+                        self.selected_workspace = Some(self.current_path.clone());
                     }
                     _ => {}
                 }
@@ -486,7 +467,7 @@ impl WorkspaceSelector {
         f.render_widget(footer, area);
     }
 
-    fn format_path(&self, path: &PathBuf) -> String {
+    fn format_path(&self, path: &Path) -> String {
         if let Some(home) = dirs::home_dir() {
             if let Ok(stripped) = path.strip_prefix(&home) {
                 return format!("~/{}", stripped.display());